@@ -1,32 +1,123 @@
 // Copyright (c) The nextest Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-//! Weekly aggregation of download statistics.
+//! Weekly and monthly aggregation of download statistics.
 
-use crate::db;
+use crate::{
+    config::{AggregationConfig, Interpolation, ResetHandling, WeekStart},
+    db,
+    platform::{self, AssetFormat},
+};
 use anyhow::{Context, Result};
-use chrono::{Datelike, NaiveDate};
+use chrono::{Datelike, NaiveDate, Weekday};
 use rusqlite::Connection;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 
-/// Get the Monday of the week containing the given date.
-fn get_week_start(date: NaiveDate) -> NaiveDate {
-    let weekday = date.weekday();
-    let days_from_monday = weekday.num_days_from_monday();
-    date - chrono::Duration::days(days_from_monday as i64)
+/// The format label for excluded assets in `weekly_format_stats`, with
+/// [`AggregationConfig::report_excluded`].
+pub const EXCLUDED_FORMAT: &str = "excluded";
+
+/// The `aggregation_settings` key for the week convention.
+const WEEK_START_SETTING: &str = "week_start";
+
+/// Get the first day of the week containing the given date, for weeks
+/// starting on `first_day`.
+fn get_week_start(date: NaiveDate, first_day: Weekday) -> NaiveDate {
+    let days_into_week =
+        (date.weekday().num_days_from_monday() + 7 - first_day.num_days_from_monday()) % 7;
+    date - chrono::Duration::days(days_into_week as i64)
 }
 
-/// Compute weekly aggregates for crates.io downloads.
+/// Get the week convention the weekly aggregates were computed with.
 ///
-/// This sums up daily downloads into weekly buckets (Monday-Sunday).
-pub fn compute_crates_weekly(conn: &Connection) -> Result<()> {
-    // Query all crates.io downloads
-    let mut stmt = conn.prepare(
+/// Databases aggregated before the convention was recorded used Monday-start
+/// weeks.
+pub fn stored_week_start(conn: &Connection) -> Result<WeekStart> {
+    match db::get_aggregation_setting(conn, WEEK_START_SETTING)? {
+        Some(value) => WeekStart::parse(&value)
+            .with_context(|| format!("unknown week convention '{}' in database", value)),
+        None => Ok(WeekStart::Monday),
+    }
+}
+
+/// Format the start of a week for display: the date, or the ISO week number
+/// for ISO weeks.
+pub fn week_label(week_start: WeekStart, date: NaiveDate) -> String {
+    match week_start {
+        WeekStart::Iso => date.format("%G-W%V").to_string(),
+        _ => date.to_string(),
+    }
+}
+
+/// Get the first day of the month containing the given date.
+fn get_month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).expect("day 1 is valid for every month")
+}
+
+/// A period that downloads are aggregated over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    /// Weeks starting on the configured day, stored in `weekly_stats`.
+    Week(WeekStart),
+    /// Calendar months, stored in `monthly_stats`.
+    Month,
+}
+
+impl Period {
+    /// Get the start of the period containing the given date.
+    pub fn start_of(self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Period::Week(week_start) => get_week_start(date, week_start.weekday()),
+            Period::Month => get_month_start(date),
+        }
+    }
+
+    fn clear_stats(self, conn: &Connection, source: &str) -> Result<()> {
+        match self {
+            Period::Week(_) => db::clear_weekly_stats(conn, source),
+            Period::Month => db::clear_monthly_stats(conn, source),
+        }
+    }
+
+    fn insert_stat(
+        self,
+        conn: &Connection,
+        start: NaiveDate,
+        source: &str,
+        identifier: &str,
+        downloads: u64,
+    ) -> Result<()> {
+        match self {
+            Period::Week(_) => db::insert_weekly_stat(conn, start, source, identifier, downloads),
+            Period::Month => db::insert_monthly_stat(conn, start, source, identifier, downloads),
+        }
+    }
+}
+
+/// Get daily crates.io downloads per crate, summed across versions.
+fn crates_daily_totals(conn: &Connection) -> Result<Vec<(NaiveDate, String, u64)>> {
+    daily_totals(
+        conn,
         "SELECT date, crate_name, SUM(downloads) as total
          FROM crates_downloads
          GROUP BY date, crate_name
          ORDER BY date",
-    )?;
+    )
+}
+
+/// Get daily install endpoint downloads per source.
+fn installer_daily_totals(conn: &Connection) -> Result<Vec<(NaiveDate, String, u64)>> {
+    daily_totals(
+        conn,
+        "SELECT date, name, downloads
+         FROM installer_downloads
+         ORDER BY date",
+    )
+}
+
+/// Run a query returning (date, identifier, downloads) rows.
+fn daily_totals(conn: &Connection, query: &str) -> Result<Vec<(NaiveDate, String, u64)>> {
+    let mut stmt = conn.prepare(query)?;
 
     let rows = stmt.query_map([], |row| {
         Ok((
@@ -36,75 +127,719 @@ pub fn compute_crates_weekly(conn: &Connection) -> Result<()> {
         ))
     })?;
 
-    let mut weekly_data: HashMap<(NaiveDate, String), u64> = HashMap::new();
-
+    let mut totals = Vec::new();
     for row in rows {
         let (date_str, crate_name, downloads) = row?;
         let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
             .with_context(|| format!("failed to parse date '{}'", date_str))?;
-        let week_start = get_week_start(date);
+        totals.push((date, crate_name, downloads as u64));
+    }
+
+    Ok(totals)
+}
+
+/// A download delta for one asset between two consecutive snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetDelta {
+    /// Date of the later snapshot, which the delta is attributed to.
+    pub date: NaiveDate,
+    /// Date of the earlier snapshot.
+    pub prev_date: NaiveDate,
+    /// `owner/repo`, empty if unknown.
+    pub repo: String,
+    pub release_tag: String,
+    pub asset_name: String,
+    /// Cumulative count in the earlier snapshot.
+    pub prev_count: i64,
+    /// Cumulative count in the later snapshot.
+    pub count: i64,
+    /// Downloads attributed to the interval, according to how resets are
+    /// handled.
+    pub downloads: u64,
+}
 
-        *weekly_data.entry((week_start, crate_name)).or_insert(0) += downloads as u64;
+impl AssetDelta {
+    /// Change in the cumulative count.
+    pub fn delta(&self) -> i64 {
+        self.count - self.prev_count
     }
 
-    for ((week_start, crate_name), downloads) in weekly_data {
-        db::insert_weekly_stat(conn, week_start, "crates", &crate_name, downloads)?;
+    /// Whether the cumulative count went backwards, e.g. because the asset
+    /// was deleted and re-uploaded.
+    pub fn is_reset(&self) -> bool {
+        self.count < self.prev_count
     }
 
-    Ok(())
+    /// Attribute the downloads to dates according to `interpolation`.
+    ///
+    /// With [`Interpolation::Linear`], the downloads are split as evenly as
+    /// possible across the days after the previous snapshot, up to and
+    /// including the later one. Any remainder goes to the latest days, so the
+    /// parts always sum to `downloads`.
+    pub fn attribute(&self, interpolation: Interpolation) -> Vec<(NaiveDate, u64)> {
+        let days = (self.date - self.prev_date).num_days();
+        if interpolation == Interpolation::None || days <= 1 {
+            return vec![(self.date, self.downloads)];
+        }
+
+        let per_day = self.downloads / days as u64;
+        let remainder = self.downloads % days as u64;
+        (1..=days)
+            .map(|offset| {
+                let extra = u64::from(offset as u64 > days as u64 - remainder);
+                (
+                    self.prev_date + chrono::Duration::days(offset),
+                    per_day + extra,
+                )
+            })
+            .collect()
+    }
 }
 
-/// Compute weekly aggregates for GitHub release downloads.
+/// Downloads in an interval given the cumulative counts at either end.
+fn interval_downloads(prev_count: i64, count: i64, resets: ResetHandling) -> u64 {
+    if count >= prev_count {
+        (count - prev_count) as u64
+    } else {
+        match resets {
+            ResetHandling::Discard => 0,
+            ResetHandling::Baseline => count.max(0) as u64,
+        }
+    }
+}
+
+/// Get per-asset GitHub download deltas between consecutive snapshots,
+/// leaving out excluded assets.
 ///
-/// Since GitHub only provides cumulative counts, we compute deltas between snapshots
-/// and attribute them to the week of the later snapshot.
-pub fn compute_github_weekly(conn: &Connection) -> Result<()> {
-    let mut stmt = conn.prepare(
-        "SELECT date, release_tag, asset_name, download_count
-         FROM github_snapshots
-         ORDER BY release_tag, asset_name, date",
-    )?;
+/// Since GitHub only provides cumulative counts, each delta is attributed to
+/// the date of the later snapshot.
+fn github_deltas(conn: &Connection, resets: ResetHandling) -> Result<Vec<AssetDelta>> {
+    snapshot_deltas(conn, "github_included", resets)
+}
+
+/// Get per-asset deltas for the assets excluded from download totals.
+fn excluded_github_deltas(conn: &Connection, resets: ResetHandling) -> Result<Vec<AssetDelta>> {
+    snapshot_deltas(conn, "github_excluded", resets)
+}
+
+/// Compute per-asset deltas from a view over `github_snapshots`.
+fn snapshot_deltas(
+    conn: &Connection,
+    view: &str,
+    resets: ResetHandling,
+) -> Result<Vec<AssetDelta>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT date, repo, release_tag, asset_name, download_count
+         FROM {}
+         ORDER BY repo, release_tag, asset_name, date",
+        view
+    ))?;
 
     let rows = stmt.query_map([], |row| {
         Ok((
             row.get::<_, String>(0)?,
             row.get::<_, String>(1)?,
             row.get::<_, String>(2)?,
-            row.get::<_, i64>(3)?,
+            row.get::<_, String>(3)?,
+            row.get::<_, i64>(4)?,
         ))
     })?;
 
-    let mut prev_snapshots: HashMap<(String, String), (NaiveDate, i64)> = HashMap::new();
-    let mut weekly_data: HashMap<NaiveDate, u64> = HashMap::new();
+    let mut prev_snapshots: HashMap<(String, String, String), (NaiveDate, i64)> = HashMap::new();
+    let mut deltas = Vec::new();
 
     for row in rows {
-        let (date_str, release_tag, asset_name, download_count) = row?;
+        let (date_str, repo, release_tag, asset_name, download_count) = row?;
         let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
             .with_context(|| format!("failed to parse date '{}'", date_str))?;
 
-        let key = (release_tag, asset_name);
+        let key = (repo, release_tag, asset_name);
 
-        if let Some((_prev_date, prev_count)) = prev_snapshots.get(&key) {
-            let delta = (download_count - prev_count).max(0) as u64;
-            let week_start = get_week_start(date);
-
-            *weekly_data.entry(week_start).or_insert(0) += delta;
+        if let Some((prev_date, prev_count)) = prev_snapshots.get(&key) {
+            deltas.push(AssetDelta {
+                date,
+                prev_date: *prev_date,
+                repo: key.0.clone(),
+                release_tag: key.1.clone(),
+                asset_name: key.2.clone(),
+                prev_count: *prev_count,
+                count: download_count,
+                downloads: interval_downloads(*prev_count, download_count, resets),
+            });
         }
 
         prev_snapshots.insert(key, (date, download_count));
     }
 
-    for (week_start, downloads) in weekly_data {
-        db::insert_weekly_stat(conn, week_start, "github", "releases", downloads)?;
+    Ok(deltas)
+}
+
+/// Compute crates.io aggregates for the given period.
+///
+/// This sums up daily downloads into period buckets.
+pub fn compute_crates(conn: &Connection, period: Period) -> Result<()> {
+    let mut bucketed: HashMap<(NaiveDate, String), u64> = HashMap::new();
+
+    for (date, crate_name, downloads) in crates_daily_totals(conn)? {
+        *bucketed
+            .entry((period.start_of(date), crate_name))
+            .or_insert(0) += downloads;
+    }
+
+    for ((start, crate_name), downloads) in bucketed {
+        period.insert_stat(conn, start, "crates", &crate_name, downloads)?;
     }
 
     Ok(())
 }
 
+/// Compute install endpoint aggregates for the given period.
+pub fn compute_installer(conn: &Connection, period: Period) -> Result<()> {
+    let mut bucketed: HashMap<(NaiveDate, String), u64> = HashMap::new();
+
+    for (date, name, downloads) in installer_daily_totals(conn)? {
+        *bucketed.entry((period.start_of(date), name)).or_insert(0) += downloads;
+    }
+
+    for ((start, name), downloads) in bucketed {
+        period.insert_stat(conn, start, "installer", &name, downloads)?;
+    }
+
+    Ok(())
+}
+
+/// The aggregate identifier of GitHub downloads whose repository isn't known,
+/// such as those of snapshots recorded before repositories were.
+pub const UNKNOWN_REPO: &str = "releases";
+
+/// Compute GitHub release aggregates for the given period, per repository
+/// (`owner/repo`) the snapshots were collected from.
+///
+/// Deltas between snapshots are attributed to periods according to
+/// the configured interpolation. Existing GitHub rows are replaced, since a different
+/// interpolation mode may attribute downloads to fewer periods.
+pub fn compute_github(
+    conn: &Connection,
+    period: Period,
+    aggregation: &AggregationConfig,
+) -> Result<()> {
+    let deltas = github_deltas(conn, aggregation.resets)?;
+    let mut bucketed: HashMap<(NaiveDate, &str), u64> = HashMap::new();
+
+    for delta in &deltas {
+        let repo = match delta.repo.as_str() {
+            "" => UNKNOWN_REPO,
+            repo => repo,
+        };
+        for (date, downloads) in delta.attribute(aggregation.interpolation) {
+            *bucketed.entry((period.start_of(date), repo)).or_insert(0) += downloads;
+        }
+    }
+
+    period.clear_stats(conn, "github")?;
+    for ((start, repo), downloads) in bucketed {
+        period.insert_stat(conn, start, "github", repo, downloads)?;
+    }
+
+    Ok(())
+}
+
+/// Compute weekly GitHub aggregates per platform.
+///
+/// Platforms are parsed from asset names; assets that can't be mapped are
+/// counted under [`platform::UNKNOWN_PLATFORM`].
+pub fn compute_github_platforms_weekly(
+    conn: &Connection,
+    aggregation: &AggregationConfig,
+) -> Result<()> {
+    let week = Period::Week(aggregation.week_start);
+    let mut bucketed: HashMap<(NaiveDate, String), u64> = HashMap::new();
+    let mut labels: HashMap<String, String> = HashMap::new();
+
+    for delta in github_deltas(conn, aggregation.resets)? {
+        let label = labels
+            .entry(delta.asset_name.clone())
+            .or_insert_with(|| platform::platform_label(&delta.asset_name));
+        for (date, downloads) in delta.attribute(aggregation.interpolation) {
+            *bucketed
+                .entry((week.start_of(date), label.clone()))
+                .or_insert(0) += downloads;
+        }
+    }
+
+    db::clear_weekly_platform_stats(conn)?;
+    for ((week_start, platform), downloads) in bucketed {
+        db::insert_weekly_platform_stat(conn, week_start, &platform, downloads)?;
+    }
+
+    Ok(())
+}
+
+/// Compute weekly GitHub aggregates per platform and asset format.
+pub fn compute_github_formats_weekly(
+    conn: &Connection,
+    aggregation: &AggregationConfig,
+) -> Result<()> {
+    let week = Period::Week(aggregation.week_start);
+    let mut bucketed: HashMap<(NaiveDate, String, &'static str), u64> = HashMap::new();
+    let mut labels: HashMap<String, (String, &'static str)> = HashMap::new();
+
+    let mut deltas: Vec<(AssetDelta, bool)> = github_deltas(conn, aggregation.resets)?
+        .into_iter()
+        .map(|delta| (delta, false))
+        .collect();
+    if aggregation.report_excluded {
+        deltas.extend(
+            excluded_github_deltas(conn, aggregation.resets)?
+                .into_iter()
+                .map(|delta| (delta, true)),
+        );
+    }
+
+    for (delta, excluded) in deltas {
+        let (platform, format) = labels
+            .entry(delta.asset_name.clone())
+            .or_insert_with(|| {
+                let format = if excluded {
+                    EXCLUDED_FORMAT
+                } else {
+                    AssetFormat::from_asset_name(&delta.asset_name).label()
+                };
+                (platform::platform_label(&delta.asset_name), format)
+            })
+            .clone();
+        for (date, downloads) in delta.attribute(aggregation.interpolation) {
+            *bucketed
+                .entry((week.start_of(date), platform.clone(), format))
+                .or_insert(0) += downloads;
+        }
+    }
+
+    db::clear_weekly_format_stats(conn)?;
+    for ((week_start, platform, format), downloads) in bucketed {
+        db::insert_weekly_format_stat(conn, week_start, &platform, format, downloads)?;
+    }
+
+    Ok(())
+}
+
+/// Compute the share of each source's weekly downloads per major.minor
+/// version.
+///
+/// crates.io downloads are attributed by version and GitHub downloads by
+/// release tag. Downloads that can't be mapped to a version (such as
+/// crates.io version IDs without a known mapping) are left out, so shares
+/// are relative to the downloads with a known version.
+pub fn compute_version_share_weekly(
+    conn: &Connection,
+    aggregation: &AggregationConfig,
+) -> Result<()> {
+    let mut bucketed: BTreeMap<(NaiveDate, &str), BTreeMap<String, u64>> = BTreeMap::new();
+    let mut lines: HashMap<String, Option<String>> = HashMap::new();
+    for ((week_start, source, version), downloads) in weekly_version_downloads(conn, aggregation)? {
+        let line = lines
+            .entry(version.clone())
+            .or_insert_with(|| version_line(&version));
+        let Some(line) = line else {
+            continue;
+        };
+        *bucketed
+            .entry((week_start, source))
+            .or_default()
+            .entry(line.clone())
+            .or_default() += downloads;
+    }
+
+    db::clear_weekly_version_share(conn)?;
+    for ((week_start, source), versions) in bucketed {
+        let total: u64 = versions.values().sum();
+        if total == 0 {
+            continue;
+        }
+        for (version, downloads) in versions {
+            let share = downloads as f64 / total as f64;
+            db::insert_weekly_version_share(conn, week_start, source, &version, downloads, share)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Compute weekly downloads of stable and pre-release versions.
+///
+/// Versions are classified by whether they have a semver pre-release
+/// component, such as `0.9.100-rc.1`. As with version shares, downloads
+/// without a known version are left out.
+pub fn compute_channel_weekly(conn: &Connection, aggregation: &AggregationConfig) -> Result<()> {
+    let mut bucketed: BTreeMap<(NaiveDate, &str, &str), u64> = BTreeMap::new();
+    for ((week_start, source, version), downloads) in weekly_version_downloads(conn, aggregation)? {
+        let Some(channel) = release_channel(&version) else {
+            continue;
+        };
+        *bucketed.entry((week_start, source, channel)).or_default() += downloads;
+    }
+
+    db::clear_weekly_channel_stats(conn)?;
+    for ((week_start, source, channel), downloads) in bucketed {
+        db::insert_weekly_channel_stat(conn, week_start, source, channel, downloads)?;
+    }
+
+    Ok(())
+}
+
+/// Compute the `release_targets` table of the target triples each GitHub
+/// release shipped artifacts for.
+///
+/// Every asset ever snapshotted is included, so targets whose artifacts were
+/// later deleted still count as shipped. Checksums, signatures, and assets without
+/// a recognizable target are left out.
+pub fn compute_release_targets(conn: &Connection) -> Result<()> {
+    let mut targets: BTreeMap<(String, String), db::ReleaseTarget> = BTreeMap::new();
+    for asset in db::get_github_assets(conn)? {
+        let format = AssetFormat::from_asset_name(&asset.asset_name);
+        if !format.is_artifact() || format == AssetFormat::Other {
+            continue;
+        }
+        let Some(platform) = platform::Platform::from_asset_name(&asset.asset_name) else {
+            continue;
+        };
+        let target = targets
+            .entry((asset.release_tag.clone(), platform.target.clone()))
+            .or_insert_with(|| db::ReleaseTarget {
+                release_tag: asset.release_tag.clone(),
+                target: platform.target.clone(),
+                platform: platform.label(),
+                artifacts: 0,
+                downloads: 0,
+                first_seen: asset.first_seen,
+            });
+        target.artifacts += 1;
+        target.downloads += asset.last_download_count;
+        target.first_seen = target.first_seen.min(asset.first_seen);
+    }
+
+    db::clear_release_targets(conn)?;
+    for target in targets.values() {
+        db::insert_release_target(conn, target)?;
+    }
+    Ok(())
+}
+
+/// Compute weekly GitHub downloads of release artifacts and of the signatures
+/// and attestations published alongside them.
+///
+/// Only releases with at least one signature asset are counted, so that
+/// releases from before signing started don't dilute the ratio. Signatures
+/// are counted even though they're excluded from download totals by default;
+/// excluded artifacts are not.
+pub fn compute_verification_weekly(
+    conn: &Connection,
+    aggregation: &AggregationConfig,
+) -> Result<()> {
+    let week = Period::Week(aggregation.week_start);
+    let included = github_deltas(conn, aggregation.resets)?;
+    let excluded = excluded_github_deltas(conn, aggregation.resets)?;
+    let is_signature = |delta: &&AssetDelta| {
+        AssetFormat::from_asset_name(&delta.asset_name) == AssetFormat::Signature
+    };
+    let signatures: Vec<&AssetDelta> = included
+        .iter()
+        .chain(&excluded)
+        .filter(is_signature)
+        .collect();
+    let signed: HashSet<&str> = signatures
+        .iter()
+        .map(|delta| delta.release_tag.as_str())
+        .collect();
+    let artifacts = included.iter().filter(|delta| {
+        let format = AssetFormat::from_asset_name(&delta.asset_name);
+        signed.contains(delta.release_tag.as_str())
+            && format.is_artifact()
+            && format != AssetFormat::Other
+    });
+
+    let mut bucketed: BTreeMap<NaiveDate, (u64, u64)> = BTreeMap::new();
+    for delta in artifacts {
+        for (date, downloads) in delta.attribute(aggregation.interpolation) {
+            bucketed.entry(week.start_of(date)).or_default().0 += downloads;
+        }
+    }
+    for delta in signatures {
+        for (date, downloads) in delta.attribute(aggregation.interpolation) {
+            bucketed.entry(week.start_of(date)).or_default().1 += downloads;
+        }
+    }
+
+    db::clear_weekly_verification_stats(conn)?;
+    for (week_start, (artifacts, signatures)) in bucketed {
+        db::insert_weekly_verification_stat(conn, week_start, artifacts, signatures)?;
+    }
+
+    Ok(())
+}
+
+/// Sum each week's downloads per source and version.
+///
+/// crates.io downloads are keyed by version and GitHub downloads by release
+/// tag, either of which may not parse as a version.
+fn weekly_version_downloads(
+    conn: &Connection,
+    aggregation: &AggregationConfig,
+) -> Result<BTreeMap<(NaiveDate, &'static str, String), u64>> {
+    let week = Period::Week(aggregation.week_start);
+    let mut bucketed = BTreeMap::new();
+
+    let mut stmt = conn.prepare(
+        "SELECT date, version, SUM(downloads)
+         FROM crates_downloads_named
+         GROUP BY date, version",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (date_str, version, downloads) = row?;
+        let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", date_str))?;
+        *bucketed
+            .entry((week.start_of(date), "crates", version))
+            .or_default() += downloads as u64;
+    }
+
+    for delta in github_deltas(conn, aggregation.resets)? {
+        for (date, downloads) in delta.attribute(aggregation.interpolation) {
+            *bucketed
+                .entry((week.start_of(date), "github", delta.release_tag.clone()))
+                .or_default() += downloads;
+        }
+    }
+
+    Ok(bucketed)
+}
+
+/// Parse the version in a version string or release tag.
+pub(crate) fn parse_version(version_or_tag: &str) -> Option<semver::Version> {
+    // Release tags are prefixed with the crate name or a `v`, such as
+    // `cargo-nextest-0.9.98`, `v0.9.98`, or `tool@0.9.98`, so try each
+    // position where a version could start: a digit at the start of a word,
+    // or right after a `v` that is.
+    let bytes = version_or_tag.as_bytes();
+    let starts_word = |i: usize| i == 0 || !bytes[i - 1].is_ascii_alphanumeric();
+    (0..bytes.len())
+        .filter(|&i| {
+            bytes[i].is_ascii_digit()
+                && (starts_word(i)
+                    || (bytes[i - 1].eq_ignore_ascii_case(&b'v') && starts_word(i - 1)))
+        })
+        .find_map(|i| semver::Version::parse(&version_or_tag[i..]).ok())
+}
+
+/// Get the major.minor release line of a version or release tag, such as
+/// `0.9` for `0.9.98` or `cargo-nextest-0.9.98-b.1`.
+pub fn version_line(version_or_tag: &str) -> Option<String> {
+    parse_version(version_or_tag).map(|version| format!("{}.{}", version.major, version.minor))
+}
+
+/// Release channel of versions without a pre-release component.
+pub const STABLE_CHANNEL: &str = "stable";
+
+/// Release channel of pre-release versions, such as `0.9.100-rc.1`.
+pub const PRERELEASE_CHANNEL: &str = "prerelease";
+
+/// Get the release channel of a version or release tag: [`STABLE_CHANNEL`]
+/// or [`PRERELEASE_CHANNEL`].
+pub fn release_channel(version_or_tag: &str) -> Option<&'static str> {
+    parse_version(version_or_tag).map(|version| {
+        if version.pre.is_empty() {
+            STABLE_CHANNEL
+        } else {
+            PRERELEASE_CHANNEL
+        }
+    })
+}
+
+/// Compute the `github_daily` table of per-asset deltas.
+///
+/// The table is rebuilt from scratch from the snapshots. Deltas where the
+/// cumulative count went backwards are stored but flagged, and recorded in
+/// `github_resets`. Returns the resets that weren't recorded before.
+pub fn compute_github_daily(
+    conn: &Connection,
+    aggregation: &AggregationConfig,
+) -> Result<Vec<db::CountReset>> {
+    let tx = conn.unchecked_transaction()?;
+    db::clear_github_daily(&tx)?;
+
+    let mut new_resets = Vec::new();
+    for delta in github_deltas(&tx, aggregation.resets)? {
+        db::insert_github_daily(
+            &tx,
+            &db::GithubDaily {
+                date: delta.date,
+                repo: delta.repo.clone(),
+                release_tag: delta.release_tag.clone(),
+                asset_name: delta.asset_name.clone(),
+                days: (delta.date - delta.prev_date).num_days(),
+                downloads: delta.downloads,
+                raw_delta: delta.delta(),
+            },
+        )?;
+        if delta.is_reset() {
+            let reset = db::CountReset {
+                date: delta.date,
+                repo: delta.repo,
+                release_tag: delta.release_tag,
+                asset_name: delta.asset_name,
+                previous_count: delta.prev_count,
+                count: delta.count,
+            };
+            if db::insert_github_reset(&tx, &reset)? {
+                new_resets.push(reset);
+            }
+        }
+    }
+
+    tx.commit()
+        .context("failed to commit GitHub daily deltas")?;
+    Ok(new_resets)
+}
+
+/// Compute the `rolling_stats` table of daily downloads with trailing 7-day
+/// and 30-day averages, per source.
+///
+/// GitHub deltas are spread evenly over the days since the previous snapshot
+/// so that the GitHub series has a value for every day.
+pub fn compute_rolling_averages(conn: &Connection, aggregation: &AggregationConfig) -> Result<()> {
+    let mut series: BTreeMap<&str, BTreeMap<NaiveDate, f64>> = BTreeMap::new();
+
+    for (date, _, downloads) in crates_daily_totals(conn)? {
+        *series.entry("crates").or_default().entry(date).or_default() += downloads as f64;
+    }
+    for (date, _, downloads) in installer_daily_totals(conn)? {
+        *series
+            .entry("installer")
+            .or_default()
+            .entry(date)
+            .or_default() += downloads as f64;
+    }
+
+    let github = series.entry("github").or_default();
+    for delta in github_deltas(conn, aggregation.resets)? {
+        let days = (delta.date - delta.prev_date).num_days().max(1);
+        let per_day = delta.downloads as f64 / days as f64;
+        for offset in 0..days {
+            let date = delta.date - chrono::Duration::days(offset);
+            *github.entry(date).or_default() += per_day;
+        }
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    db::clear_rolling_stats(&tx)?;
+    for (source, daily) in &series {
+        let avg_7d = rolling_average(daily, 7);
+        let avg_30d = rolling_average(daily, 30);
+        for (idx, (date, downloads)) in daily.iter().enumerate() {
+            db::insert_rolling_stat(&tx, *date, source, *downloads, avg_7d[idx], avg_30d[idx])?;
+        }
+    }
+    tx.commit().context("failed to commit rolling averages")?;
+    Ok(())
+}
+
+/// Compute the trailing mean over `days` calendar days for each point.
+///
+/// Days missing from the series are left out of the mean rather than counted
+/// as zero, so gaps in collection don't drag the average down.
+fn rolling_average(daily: &BTreeMap<NaiveDate, f64>, days: i64) -> Vec<f64> {
+    let points: Vec<(NaiveDate, f64)> = daily.iter().map(|(d, v)| (*d, *v)).collect();
+    let mut start = 0;
+    let mut sum = 0.0;
+
+    points
+        .iter()
+        .enumerate()
+        .map(|(idx, &(date, value))| {
+            sum += value;
+            while (date - points[start].0).num_days() >= days {
+                sum -= points[start].1;
+                start += 1;
+            }
+            sum / (idx + 1 - start) as f64
+        })
+        .collect()
+}
+
+/// Compute weekly aggregates for crates.io downloads.
+///
+/// This sums up daily downloads into weekly buckets.
+pub fn compute_crates_weekly(conn: &Connection, week_start: WeekStart) -> Result<()> {
+    compute_crates(conn, Period::Week(week_start))
+}
+
+/// Compute weekly aggregates for GitHub release downloads.
+///
+/// Since GitHub only provides cumulative counts, we compute deltas between snapshots
+/// and attribute them to the week of the later snapshot, or spread them across
+/// the intervening weeks with [`Interpolation::Linear`].
+pub fn compute_github_weekly(conn: &Connection, aggregation: &AggregationConfig) -> Result<()> {
+    compute_github(conn, Period::Week(aggregation.week_start), aggregation)
+}
+
 /// Compute all weekly aggregates.
-pub fn compute_all_weekly(conn: &Connection) -> Result<()> {
-    compute_crates_weekly(conn).context("failed to compute crates.io weekly aggregates")?;
-    compute_github_weekly(conn).context("failed to compute GitHub weekly aggregates")?;
+///
+/// If the weekly aggregates were computed with a different week convention,
+/// they are all rebuilt and the previous convention's name is returned.
+pub fn compute_all_weekly(
+    conn: &Connection,
+    aggregation: &AggregationConfig,
+) -> Result<Option<String>> {
+    let tx = conn.unchecked_transaction()?;
+    let current = aggregation.week_start.as_str();
+    let stored = db::get_aggregation_setting(&tx, WEEK_START_SETTING)?;
+    if stored.as_deref() != Some(current) {
+        // Weeks bucketed under another convention would otherwise survive
+        // next to the new ones.
+        db::clear_all_weekly_stats(&tx)?;
+        db::set_aggregation_setting(&tx, WEEK_START_SETTING, current)?;
+    }
+    let changed = stored.filter(|previous| previous != current);
+
+    compute_crates_weekly(&tx, aggregation.week_start)
+        .context("failed to compute crates.io weekly aggregates")?;
+    compute_github_weekly(&tx, aggregation)
+        .context("failed to compute GitHub weekly aggregates")?;
+    compute_installer(&tx, Period::Week(aggregation.week_start))
+        .context("failed to compute installer weekly aggregates")?;
+    compute_github_platforms_weekly(&tx, aggregation)
+        .context("failed to compute GitHub weekly platform aggregates")?;
+    compute_github_formats_weekly(&tx, aggregation)
+        .context("failed to compute GitHub weekly format aggregates")?;
+    compute_version_share_weekly(&tx, aggregation)
+        .context("failed to compute weekly version shares")?;
+    compute_channel_weekly(&tx, aggregation)
+        .context("failed to compute weekly release channel aggregates")?;
+    compute_verification_weekly(&tx, aggregation)
+        .context("failed to compute weekly verification aggregates")?;
+    compute_release_targets(&tx).context("failed to compute release targets")?;
+    tx.commit().context("failed to commit weekly aggregates")?;
+    Ok(changed)
+}
+
+/// Compute all monthly aggregates.
+///
+/// Quarterly figures are derived from these at query time.
+pub fn compute_all_monthly(conn: &Connection, aggregation: &AggregationConfig) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    compute_crates(&tx, Period::Month).context("failed to compute crates.io monthly aggregates")?;
+    compute_github(&tx, Period::Month, aggregation)
+        .context("failed to compute GitHub monthly aggregates")?;
+    compute_installer(&tx, Period::Month)
+        .context("failed to compute installer monthly aggregates")?;
+    tx.commit().context("failed to commit monthly aggregates")?;
     Ok(())
 }
 
@@ -113,24 +848,398 @@ mod tests {
     use super::*;
     use chrono::Weekday;
 
+    #[test]
+    fn test_rolling_average() {
+        let date = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        let daily: BTreeMap<NaiveDate, f64> = [
+            (date(1), 10.0),
+            (date(2), 20.0),
+            (date(3), 30.0),
+            // 4 and 5 are missing.
+            (date(6), 60.0),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(rolling_average(&daily, 2), [10.0, 15.0, 25.0, 60.0]);
+        assert_eq!(rolling_average(&daily, 7), [10.0, 15.0, 20.0, 30.0]);
+    }
+
+    #[test]
+    fn test_attribute_delta() {
+        let date = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        let delta = AssetDelta {
+            date: date(11),
+            prev_date: date(7),
+            repo: "nextest-rs/nextest".to_string(),
+            release_tag: "cargo-nextest-0.9.98".to_string(),
+            asset_name: "cargo-nextest-0.9.98-x86_64-unknown-linux-gnu.tar.gz".to_string(),
+            prev_count: 100,
+            count: 110,
+            downloads: 10,
+        };
+
+        assert_eq!(delta.attribute(Interpolation::None), [(date(11), 10)]);
+        // The remainder goes to the latest days.
+        assert_eq!(
+            delta.attribute(Interpolation::Linear),
+            [(date(8), 2), (date(9), 2), (date(10), 3), (date(11), 3)]
+        );
+
+        let negative = AssetDelta {
+            count: 95,
+            downloads: 0,
+            ..delta
+        };
+        assert!(negative.is_reset());
+        assert_eq!(
+            negative.attribute(Interpolation::Linear),
+            [(date(8), 0), (date(9), 0), (date(10), 0), (date(11), 0)]
+        );
+    }
+
+    #[test]
+    fn test_interval_downloads() {
+        assert_eq!(interval_downloads(100, 110, ResetHandling::Discard), 10);
+        assert_eq!(interval_downloads(100, 110, ResetHandling::Baseline), 10);
+        assert_eq!(interval_downloads(100, 7, ResetHandling::Discard), 0);
+        assert_eq!(interval_downloads(100, 7, ResetHandling::Baseline), 7);
+    }
+
+    #[test]
+    fn test_version_line() {
+        assert_eq!(version_line("0.9.98").as_deref(), Some("0.9"));
+        assert_eq!(version_line("1.2.3-rc.1").as_deref(), Some("1.2"));
+        assert_eq!(
+            version_line("cargo-nextest-0.9.98-b.1").as_deref(),
+            Some("0.9")
+        );
+        assert_eq!(version_line("tool-2-1.0.0").as_deref(), Some("1.0"));
+        assert_eq!(version_line("v0.9.98").as_deref(), Some("0.9"));
+        assert_eq!(version_line("V1.2.3").as_deref(), Some("1.2"));
+        assert_eq!(
+            version_line("cargo-nextest-v0.9.98").as_deref(),
+            Some("0.9")
+        );
+        assert_eq!(version_line("cargo-nextest@0.9.98").as_deref(), Some("0.9"));
+        assert_eq!(version_line("cargo_nextest_0.9.98").as_deref(), Some("0.9"));
+        assert_eq!(version_line("releases/v2.0.0").as_deref(), Some("2.0"));
+        // A version inside a word isn't one.
+        assert_eq!(version_line("abc1.2.3"), None);
+        assert_eq!(version_line("dev1.2.3"), None);
+        assert_eq!(version_line("1808836"), None);
+        assert_eq!(version_line(""), None);
+        assert_eq!(version_line("cargo-nextest"), None);
+    }
+
+    #[test]
+    fn test_release_channel() {
+        assert_eq!(release_channel("0.9.98"), Some(STABLE_CHANNEL));
+        assert_eq!(
+            release_channel("cargo-nextest-0.9.98"),
+            Some(STABLE_CHANNEL)
+        );
+        assert_eq!(
+            release_channel("cargo-nextest-0.9.100-rc.1"),
+            Some(PRERELEASE_CHANNEL)
+        );
+        assert_eq!(release_channel("0.9.41-a.4"), Some(PRERELEASE_CHANNEL));
+        assert_eq!(release_channel("v0.9.100-rc.1"), Some(PRERELEASE_CHANNEL));
+        // Build metadata doesn't make a version a pre-release.
+        assert_eq!(release_channel("1.0.0+build.5"), Some(STABLE_CHANNEL));
+        assert_eq!(release_channel("1808836"), None);
+    }
+
     #[test]
     fn test_get_week_start() {
         // 2025-11-19 is a Wednesday
         let date = NaiveDate::from_ymd_opt(2025, 11, 19).unwrap();
-        let week_start = get_week_start(date);
+        let week_start = get_week_start(date, Weekday::Mon);
 
         // Should return Monday of that week (2025-11-17)
         assert_eq!(week_start, NaiveDate::from_ymd_opt(2025, 11, 17).unwrap());
         assert_eq!(week_start.weekday(), Weekday::Mon);
+
+        // Sunday-start weeks begin on the previous Sunday (2025-11-16)
+        assert_eq!(
+            get_week_start(date, Weekday::Sun),
+            NaiveDate::from_ymd_opt(2025, 11, 16).unwrap()
+        );
+        // and Thursday-start weeks on the previous Thursday (2025-11-13)
+        assert_eq!(
+            get_week_start(date, Weekday::Thu),
+            NaiveDate::from_ymd_opt(2025, 11, 13).unwrap()
+        );
     }
 
     #[test]
     fn test_get_week_start_already_monday() {
         // 2025-11-17 is a Monday
         let date = NaiveDate::from_ymd_opt(2025, 11, 17).unwrap();
-        let week_start = get_week_start(date);
+        let week_start = get_week_start(date, Weekday::Mon);
 
         // Should return itself
         assert_eq!(week_start, date);
     }
+
+    #[test]
+    fn test_week_label() {
+        let date = NaiveDate::from_ymd_opt(2024, 12, 30).unwrap();
+        assert_eq!(week_label(WeekStart::Monday, date), "2024-12-30");
+        // ISO week years can differ from the calendar year.
+        assert_eq!(week_label(WeekStart::Iso, date), "2025-W01");
+    }
+
+    #[test]
+    fn test_compute_all_weekly_convention_change() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        // A Wednesday.
+        let date = NaiveDate::from_ymd_opt(2025, 11, 19).unwrap();
+        db::insert_crates_download(&conn, date, "foo", None, 5).unwrap();
+
+        let mut aggregation = AggregationConfig::default();
+        assert_eq!(compute_all_weekly(&conn, &aggregation).unwrap(), None);
+        assert_eq!(compute_all_weekly(&conn, &aggregation).unwrap(), None);
+
+        aggregation.week_start = WeekStart::Sunday;
+        assert_eq!(
+            compute_all_weekly(&conn, &aggregation).unwrap().as_deref(),
+            Some("monday")
+        );
+        assert_eq!(stored_week_start(&conn).unwrap(), WeekStart::Sunday);
+        let weeks: Vec<String> = conn
+            .prepare("SELECT week_start FROM weekly_stats")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(weeks, ["2025-11-16"]);
+    }
+
+    #[test]
+    fn test_compute_github_per_repo() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        // Both repositories publish the same tag and asset name, and rows
+        // recorded before repositories were have an empty repo.
+        let assets = [
+            ("o/a", "v1.0", "checksums.txt"),
+            ("o/b", "v1.0", "checksums.txt"),
+            ("", "old", "c.tar.gz"),
+        ];
+        for (d, count) in [(17, 100), (18, 150)] {
+            let at = day(d).and_hms_opt(12, 0, 0).unwrap().and_utc();
+            for (idx, (repo, tag, name)) in assets.iter().enumerate() {
+                let count = count * (idx as u64 + 1);
+                db::insert_github_snapshot(&conn, day(d), Some(at), repo, tag, name, count)
+                    .unwrap();
+            }
+        }
+        // Collecting o/b last doesn't move o/a's downloads to it.
+        db::update_github_assets(&conn, day(18), "o/a").unwrap();
+        db::update_github_assets(&conn, day(18), "o/b").unwrap();
+
+        compute_all_weekly(&conn, &AggregationConfig::default()).unwrap();
+        let rows: Vec<(String, i64)> = conn
+            .prepare(
+                "SELECT identifier, downloads FROM weekly_stats WHERE source = 'github'
+                 ORDER BY identifier",
+            )
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("o/a".to_string(), 50),
+                ("o/b".to_string(), 100),
+                (UNKNOWN_REPO.to_string(), 150)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_compute_channel_weekly() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        // Monday 2025-11-17 and the next day, then the Monday after.
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        for (d, version, downloads) in [
+            (17, "0.9.100", 10),
+            (17, "0.9.101-rc.1", 3),
+            (18, "0.9.100", 5),
+            (18, "0.9.101-rc.2", 4),
+            (24, "0.9.101", 20),
+            // An unresolved version ID has no known channel.
+            (24, "1808836", 7),
+        ] {
+            db::insert_crates_download(&conn, day(d), "cargo-nextest", Some(version), downloads)
+                .unwrap();
+        }
+        for (d, stable, prerelease) in [(17, 100, 10), (18, 150, 40)] {
+            let at = day(d).and_hms_opt(12, 0, 0).unwrap().and_utc();
+            for (tag, count) in [
+                ("cargo-nextest-0.9.100", stable),
+                ("cargo-nextest-0.9.101-rc.1", prerelease),
+            ] {
+                db::insert_github_snapshot(
+                    &conn,
+                    day(d),
+                    Some(at),
+                    "nextest-rs/nextest",
+                    tag,
+                    "a.tar.gz",
+                    count,
+                )
+                .unwrap();
+            }
+        }
+
+        compute_channel_weekly(&conn, &AggregationConfig::default()).unwrap();
+        let rows: Vec<(String, String, String, i64)> = conn
+            .prepare(
+                "SELECT week_start, source, channel, downloads FROM weekly_channel_stats
+                 ORDER BY week_start, source, channel",
+            )
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let row = |week: &str, source: &str, channel: &str, downloads| {
+            (
+                week.to_string(),
+                source.to_string(),
+                channel.to_string(),
+                downloads,
+            )
+        };
+        assert_eq!(
+            rows,
+            [
+                row("2025-11-17", "crates", PRERELEASE_CHANNEL, 7),
+                row("2025-11-17", "crates", STABLE_CHANNEL, 15),
+                row("2025-11-17", "github", PRERELEASE_CHANNEL, 30),
+                row("2025-11-17", "github", STABLE_CHANNEL, 50),
+                row("2025-11-24", "crates", STABLE_CHANNEL, 20),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_excluded_assets() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        for (d, archive, checksum) in [(17, 100, 50), (18, 110, 60)] {
+            let at = day(d).and_hms_opt(12, 0, 0).unwrap().and_utc();
+            db::insert_github_snapshot(
+                &conn,
+                day(d),
+                Some(at),
+                "nextest-rs/nextest",
+                "v1",
+                "foo-linux.tar.gz",
+                archive,
+            )
+            .unwrap();
+            db::insert_github_snapshot(
+                &conn,
+                day(d),
+                Some(at),
+                "nextest-rs/nextest",
+                "v1",
+                "foo-linux.tar.gz.sha256",
+                checksum,
+            )
+            .unwrap();
+        }
+
+        let aggregation = AggregationConfig {
+            report_excluded: true,
+            ..AggregationConfig::default()
+        };
+        db::replace_asset_exclusions(&conn, &aggregation.exclude_assets).unwrap();
+        compute_all_weekly(&conn, &aggregation).unwrap();
+
+        let github: i64 = conn
+            .query_row(
+                "SELECT downloads FROM weekly_stats WHERE source = 'github'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(github, 10);
+        let excluded: i64 = conn
+            .query_row(
+                "SELECT downloads FROM weekly_format_stats WHERE format = ?1",
+                [EXCLUDED_FORMAT],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(excluded, 10);
+    }
+
+    #[test]
+    fn test_compute_verification_weekly() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        let assets = [
+            ("v1", "foo-x86_64-unknown-linux-gnu.tar.gz", 100, 200),
+            ("v1", "foo-x86_64-unknown-linux-gnu.tar.gz.sha256", 10, 20),
+            (
+                "v1",
+                "foo-x86_64-unknown-linux-gnu.tar.gz.sigstore.json",
+                1,
+                6,
+            ),
+            // Unsigned releases are left out.
+            ("v0", "foo-x86_64-unknown-linux-gnu.tar.gz", 100, 1000),
+        ];
+        for (d, first) in [(17, true), (18, false)] {
+            let at = day(d).and_hms_opt(12, 0, 0).unwrap().and_utc();
+            for (tag, name, before, after) in assets {
+                let count = if first { before } else { after };
+                db::insert_github_snapshot(
+                    &conn,
+                    day(d),
+                    Some(at),
+                    "nextest-rs/nextest",
+                    tag,
+                    name,
+                    count,
+                )
+                .unwrap();
+            }
+        }
+
+        let aggregation = AggregationConfig::default();
+        db::replace_asset_exclusions(&conn, &aggregation.exclude_assets).unwrap();
+        compute_all_weekly(&conn, &aggregation).unwrap();
+
+        let row: (String, i64, i64) = conn
+            .query_row(
+                "SELECT week_start, artifacts, signatures FROM weekly_verification_stats",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap();
+        assert_eq!(row, ("2025-11-17".to_string(), 100, 5));
+    }
+
+    #[test]
+    fn test_period_start_of() {
+        let date = NaiveDate::from_ymd_opt(2025, 11, 19).unwrap();
+        assert_eq!(
+            Period::Week(WeekStart::Iso).start_of(date),
+            NaiveDate::from_ymd_opt(2025, 11, 17).unwrap()
+        );
+        assert_eq!(
+            Period::Month.start_of(date),
+            NaiveDate::from_ymd_opt(2025, 11, 1).unwrap()
+        );
+    }
 }
@@ -0,0 +1,196 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Alerts on download trends.
+//!
+//! Alert rules, declared as `[[alert]]` in `config.toml`, compare a source's
+//! downloads in its latest complete week against the average of the weeks
+//! before it, and fire when downloads have dropped or risen by at least a
+//! given percentage. `collect` checks the rules after aggregation: fired
+//! alerts are part of the collection summary, and so of notifications, and
+//! fail the run with `--fail-on-alert`. `analyze alerts` checks them against
+//! the database as it is.
+
+use crate::{
+    analysis,
+    config::{AlertConfig, AlertDirection},
+    format,
+};
+use anyhow::Result;
+use chrono::{Days, NaiveDate};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// An alert rule that fired.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Alert {
+    /// The name of the rule.
+    pub name: String,
+    pub source: String,
+    pub direction: AlertDirection,
+    /// The Monday starting the latest complete week.
+    pub week_start: NaiveDate,
+    /// Downloads in the latest complete week.
+    pub downloads: u64,
+    /// Average weekly downloads over the weeks before, rounded.
+    pub baseline: u64,
+    pub baseline_weeks: usize,
+    /// The change from the baseline, in percent, to one decimal place.
+    pub change_percent: f64,
+    /// What happened, as a sentence.
+    pub message: String,
+}
+
+/// Check `rules` against the weekly aggregates, returning those that fire.
+///
+/// Rules for sources without enough complete weeks of data are skipped.
+pub fn evaluate(conn: &Connection, rules: &[AlertConfig]) -> Result<Vec<Alert>> {
+    let mut trends = BTreeMap::new();
+    let mut alerts = Vec::new();
+    for rule in rules {
+        if !trends.contains_key(&rule.source) {
+            let trend = analysis::weekly_trend(conn, &rule.source)?;
+            trends.insert(rule.source.clone(), trend);
+        }
+        alerts.extend(check(rule, &trends[&rule.source]));
+    }
+    Ok(alerts)
+}
+
+/// Check a rule against weekly downloads, oldest first.
+fn check(rule: &AlertConfig, trend: &[(NaiveDate, u64)]) -> Option<Alert> {
+    let (&(week_start, downloads), before) = trend.split_last()?;
+    // A baseline reaching back past the earliest date can't be complete.
+    let days = (rule.baseline_weeks as u64).checked_mul(7)?;
+    let since = week_start.checked_sub_days(Days::new(days))?;
+    let baseline: Vec<u64> = before
+        .iter()
+        .filter(|(week, _)| *week >= since)
+        .map(|(_, downloads)| *downloads)
+        .collect();
+    // A week missing from the aggregates is a gap in the data rather than a
+    // week without downloads, so it can't be averaged.
+    if baseline.len() < rule.baseline_weeks {
+        return None;
+    }
+    let mean = baseline.iter().sum::<u64>() as f64 / baseline.len() as f64;
+    if mean <= 0.0 {
+        return None;
+    }
+
+    let change = (downloads as f64 - mean) / mean * 100.0;
+    let fired = match rule.direction {
+        AlertDirection::Drop => change <= -rule.percent,
+        AlertDirection::Rise => change >= rule.percent,
+    };
+    if !fired {
+        return None;
+    }
+    let baseline = mean.round() as u64;
+    let message = format!(
+        "{} downloads in the week of {} were {}, a {} of {:.1}% against the {}-week average of {}",
+        match rule.source.as_str() {
+            "all" => "total",
+            source => source,
+        },
+        week_start,
+        format::number(downloads),
+        rule.direction.as_str(),
+        change.abs(),
+        rule.baseline_weeks,
+        format::number(baseline)
+    );
+    Some(Alert {
+        name: rule.name.clone(),
+        source: rule.source.clone(),
+        direction: rule.direction,
+        week_start,
+        downloads,
+        baseline,
+        baseline_weeks: rule.baseline_weeks,
+        change_percent: (change * 10.0).round() / 10.0,
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use camino::Utf8Path;
+
+    fn rule(direction: AlertDirection, percent: f64) -> AlertConfig {
+        AlertConfig {
+            name: "test".to_string(),
+            source: "crates".to_string(),
+            direction,
+            percent,
+            baseline_weeks: 4,
+        }
+    }
+
+    #[test]
+    fn test_check() {
+        let week = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        let trend = [
+            (week(3), 2000),
+            (week(10), 1000),
+            (week(17), 900),
+            (week(24), 1100),
+            (NaiveDate::from_ymd_opt(2025, 12, 1).unwrap(), 1000),
+            (NaiveDate::from_ymd_opt(2025, 12, 8).unwrap(), 650),
+        ];
+
+        let alert = check(&rule(AlertDirection::Drop, 30.0), &trend).unwrap();
+        assert_eq!(
+            alert.week_start,
+            NaiveDate::from_ymd_opt(2025, 12, 8).unwrap()
+        );
+        assert_eq!(alert.baseline, 1000);
+        assert_eq!(alert.change_percent, -35.0);
+        assert_eq!(
+            alert.message,
+            "crates downloads in the week of 2025-12-08 were 650, a drop of 35.0% against the \
+             4-week average of 1,000"
+        );
+        assert_eq!(check(&rule(AlertDirection::Drop, 40.0), &trend), None);
+        assert_eq!(check(&rule(AlertDirection::Rise, 10.0), &trend), None);
+
+        // The first week counts once the baseline reaches back to it.
+        let mut longer = rule(AlertDirection::Drop, 30.0);
+        longer.baseline_weeks = 5;
+        assert_eq!(check(&longer, &trend).unwrap().baseline, 1200);
+
+        // Not enough history, or a gap in it.
+        longer.baseline_weeks = 6;
+        assert_eq!(check(&longer, &trend), None);
+        longer.baseline_weeks = usize::MAX;
+        assert_eq!(check(&longer, &trend), None);
+        let mut gap = trend.to_vec();
+        gap.remove(2);
+        assert_eq!(check(&rule(AlertDirection::Drop, 30.0), &gap), None);
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        // Data through Nov 23 completes the week of Nov 17.
+        db::insert_crates_download(&conn, date(23), "cargo-nextest", None, 10).unwrap();
+        for (day, downloads) in [(3, 100), (10, 100), (17, 300)] {
+            db::insert_weekly_stat(&conn, date(day), "crates", "cargo-nextest", downloads).unwrap();
+        }
+
+        let mut rise = rule(AlertDirection::Rise, 100.0);
+        rise.baseline_weeks = 2;
+        let mut drop = rule(AlertDirection::Drop, 10.0);
+        drop.name = "drop".to_string();
+        drop.baseline_weeks = 2;
+        let alerts = evaluate(&conn, &[rise, drop]).unwrap();
+        assert_eq!(alerts.len(), 1);
+        assert_eq!(alerts[0].name, "test");
+        assert_eq!(alerts[0].week_start, date(17));
+        assert_eq!(alerts[0].change_percent, 200.0);
+    }
+}
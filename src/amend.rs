@@ -0,0 +1,552 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Corrections to collected data.
+//!
+//! When a bad run pollutes the database, such as with a GitHub snapshot taken
+//! while the API was missing releases, `db amend` deletes or overwrites what
+//! was recorded for one source on one date. The weekly and monthly aggregates
+//! of that date are cleared along with it, since aggregation only adds and
+//! replaces rows, and would otherwise keep totals for data that's gone.
+//!
+//! Only rows in the main database can be amended, not archived ones.
+
+use crate::{aggregate::Period, config::WeekStart};
+use anyhow::{Context, Result, bail};
+use chrono::{Days, NaiveDate};
+use rusqlite::{Connection, OptionalExtension, params};
+use std::{fmt, str::FromStr};
+
+/// The source whose data is amended.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// GitHub snapshots of one repository (`owner/repo`), or of all of them.
+    Github(Option<String>),
+    Crates(String),
+    Installer(String),
+}
+
+impl FromStr for Source {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            None if s == "github" => Ok(Source::Github(None)),
+            Some(("github", repo)) if is_repo(repo) => Ok(Source::Github(Some(repo.to_string()))),
+            Some(("crates", name)) if !name.is_empty() => Ok(Source::Crates(name.to_string())),
+            Some(("installer", name)) if !name.is_empty() => {
+                Ok(Source::Installer(name.to_string()))
+            }
+            _ => bail!(
+                "expected 'github', 'github:OWNER/REPO', 'crates:NAME', or 'installer:NAME', \
+                 found '{}'",
+                s
+            ),
+        }
+    }
+}
+
+fn is_repo(s: &str) -> bool {
+    s.split_once('/')
+        .is_some_and(|(owner, repo)| !owner.is_empty() && !repo.is_empty() && !repo.contains('/'))
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Github(None) => f.write_str("github"),
+            Source::Github(Some(repo)) => write!(f, "github:{}", repo),
+            Source::Crates(name) => write!(f, "crates:{}", name),
+            Source::Installer(name) => write!(f, "installer:{}", name),
+        }
+    }
+}
+
+/// What happens to the matching rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    Delete,
+    /// Overwrite the count: a GitHub asset's cumulative downloads, or a day's
+    /// downloads of a crate version or install endpoint.
+    Set(u64),
+}
+
+/// An amendment to one source's data on one date.
+#[derive(Clone, Debug)]
+pub struct Amendment {
+    pub source: Source,
+    pub date: NaiveDate,
+    /// Only GitHub snapshots of this release.
+    pub release: Option<String>,
+    /// Only GitHub snapshots of this asset.
+    pub asset: Option<String>,
+    /// Only crates.io downloads of this version, as stored.
+    pub version: Option<String>,
+    pub change: Change,
+}
+
+/// Rows changed (or, for a dry run, due to change) by an amendment.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Amended {
+    /// Rows deleted or written per table.
+    pub rows: Vec<(&'static str, usize)>,
+    /// The count overwritten by [`Change::Set`], if there was one.
+    pub previous: Option<u64>,
+    /// The first days of the weeks whose aggregates the amendment affects.
+    pub weeks: Vec<NaiveDate>,
+}
+
+impl Amended {
+    pub fn total_rows(&self) -> usize {
+        self.rows.iter().map(|(_, count)| count).sum()
+    }
+}
+
+impl Amendment {
+    fn check(&self) -> Result<()> {
+        let github = matches!(self.source, Source::Github(_));
+        if !github && (self.release.is_some() || self.asset.is_some()) {
+            bail!("--release and --asset only apply to 'github'");
+        }
+        if self.version.is_some() && !matches!(self.source, Source::Crates(_)) {
+            bail!("--version only applies to 'crates:NAME'");
+        }
+        if let Change::Set(_) = self.change {
+            if github && (self.release.is_none() || self.asset.is_none()) {
+                bail!("--set for 'github' needs --release and --asset to name one snapshot");
+            }
+            if matches!(self.source, Source::Crates(_)) && self.version.is_none() {
+                bail!("--set for crates needs --version, since downloads are stored per version");
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Apply `amendment`, clearing the aggregates of the weeks and months it
+/// affects so they're rebuilt by the next aggregation.
+///
+/// With `dry_run`, nothing is changed and the rows that would change are
+/// returned.
+pub fn amend(
+    conn: &Connection,
+    amendment: &Amendment,
+    week_start: WeekStart,
+    dry_run: bool,
+) -> Result<Amended> {
+    amendment.check()?;
+    let date = amendment.date.to_string();
+    let year = amendment.date.format("%Y").to_string();
+    let archived: Option<String> = conn
+        .query_row(
+            "SELECT path FROM main.archives WHERE year = ?1",
+            [&year],
+            |row| row.get(0),
+        )
+        .optional()
+        .context("failed to read archives")?;
+    if let Some(path) = archived {
+        bail!(
+            "{} has been archived to {}, and archived data can't be amended",
+            year,
+            path
+        );
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let amended = match &amendment.source {
+        Source::Github(repo) => amend_github(&tx, amendment, repo.as_deref(), &date, week_start)?,
+        Source::Crates(name) => {
+            let version = amendment.version.as_deref();
+            let mut amended = match amendment.change {
+                Change::Delete => Amended {
+                    rows: vec![(
+                        "crates_downloads",
+                        tx.execute(
+                            "DELETE FROM main.crates_downloads
+                             WHERE date = ?1 AND crate_name = ?2
+                               AND (?3 IS NULL OR version = ?3)",
+                            params![date, name, version],
+                        )?,
+                    )],
+                    previous: None,
+                    weeks: Vec::new(),
+                },
+                Change::Set(count) => {
+                    let previous = tx
+                        .query_row(
+                            "SELECT downloads FROM main.crates_downloads
+                             WHERE date = ?1 AND crate_name = ?2 AND version = ?3",
+                            params![date, name, version],
+                            |row| row.get::<_, i64>(0),
+                        )
+                        .optional()?;
+                    tx.execute(
+                        "INSERT OR REPLACE INTO main.crates_downloads
+                         (date, crate_name, version, downloads) VALUES (?1, ?2, ?3, ?4)",
+                        params![date, name, version, count as i64],
+                    )?;
+                    Amended {
+                        rows: vec![("crates_downloads", 1)],
+                        previous: previous.map(|count| count as u64),
+                        weeks: Vec::new(),
+                    }
+                }
+            };
+            // The metadata snapshot covers the whole crate.
+            if amendment.change == Change::Delete && version.is_none() {
+                let deleted = tx.execute(
+                    "DELETE FROM main.crates_metadata WHERE date = ?1 AND crate_name = ?2",
+                    params![date, name],
+                )?;
+                amended.rows.push(("crates_metadata", deleted));
+            }
+            amended.weeks = clear_aggregates(&tx, amendment.date, "crates", name, week_start)?;
+            amended
+        }
+        Source::Installer(name) => {
+            let mut amended = match amendment.change {
+                Change::Delete => Amended {
+                    rows: vec![(
+                        "installer_downloads",
+                        tx.execute(
+                            "DELETE FROM main.installer_downloads WHERE date = ?1 AND name = ?2",
+                            params![date, name],
+                        )?,
+                    )],
+                    previous: None,
+                    weeks: Vec::new(),
+                },
+                Change::Set(count) => {
+                    let previous = tx
+                        .query_row(
+                            "SELECT downloads FROM main.installer_downloads
+                             WHERE date = ?1 AND name = ?2",
+                            params![date, name],
+                            |row| row.get::<_, i64>(0),
+                        )
+                        .optional()?;
+                    tx.execute(
+                        "INSERT OR REPLACE INTO main.installer_downloads (date, name, downloads)
+                         VALUES (?1, ?2, ?3)",
+                        params![date, name, count as i64],
+                    )?;
+                    Amended {
+                        rows: vec![("installer_downloads", 1)],
+                        previous: previous.map(|count| count as u64),
+                        weeks: Vec::new(),
+                    }
+                }
+            };
+            amended.weeks = clear_aggregates(&tx, amendment.date, "installer", name, week_start)?;
+            amended
+        }
+    };
+
+    if !dry_run {
+        tx.commit().context("failed to commit amendment")?;
+    }
+    Ok(amended)
+}
+
+fn amend_github(
+    conn: &Connection,
+    amendment: &Amendment,
+    repo: Option<&str>,
+    date: &str,
+    week_start: WeekStart,
+) -> Result<Amended> {
+    let release = amendment.release.as_deref();
+    let asset = amendment.asset.as_deref();
+    const FILTER: &str = "(?2 IS NULL OR release_tag = ?2) AND (?3 IS NULL OR asset_name = ?3) \
+                          AND (?4 IS NULL OR repo = ?4)";
+
+    // Snapshots are cumulative, so the deltas on either side of the date
+    // change: those from the snapshot before, and to the snapshot after.
+    let neighbor = |query: &str| -> Result<Option<NaiveDate>> {
+        let neighbor: Option<String> = conn
+            .query_row(
+                &format!("{} AND {}", query, FILTER),
+                params![date, release, asset, repo],
+                |row| row.get(0),
+            )
+            .context("failed to look up neighboring GitHub snapshots")?;
+        neighbor
+            .map(|date| NaiveDate::from_str(&date).context("invalid GitHub snapshot date"))
+            .transpose()
+    };
+    let previous_date = neighbor("SELECT MAX(date) FROM github_snapshots WHERE date < ?1")?;
+    let next_date = neighbor("SELECT MIN(date) FROM github_snapshots WHERE date > ?1")?;
+    let first = previous_date.map_or(amendment.date, |previous| previous + Days::new(1));
+    let weeks = weeks_between(first, next_date.unwrap_or(amendment.date), week_start);
+
+    let mut rows = Vec::new();
+    let mut previous = None;
+    match amendment.change {
+        Change::Delete => {
+            for table in ["github_snapshots", "github_superseded", "github_backfill"] {
+                let deleted = conn.execute(
+                    &format!("DELETE FROM main.{} WHERE date = ?1 AND {}", table, FILTER),
+                    params![date, release, asset, repo],
+                )?;
+                rows.push((table, deleted));
+            }
+        }
+        Change::Set(count) => {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT repo, download_count FROM main.github_snapshots
+                 WHERE date = ?1 AND {}",
+                FILTER
+            ))?;
+            let existing = stmt
+                .query_map(params![date, release, asset, repo], |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                })?
+                .collect::<Result<Vec<_>, _>>()
+                .context("failed to look up the GitHub snapshot to overwrite")?;
+            let repo = match (repo, existing.as_slice()) {
+                (Some(repo), _) => repo.to_string(),
+                (None, [(repo, _)]) => repo.clone(),
+                (None, []) => bail!(
+                    "there is no snapshot to overwrite; name its repository with \
+                     'github:OWNER/REPO'"
+                ),
+                (None, _) => bail!(
+                    "several repositories have a snapshot of the asset; name one with \
+                     'github:OWNER/REPO'"
+                ),
+            };
+            previous = existing.first().map(|(_, count)| *count as u64);
+            // The timestamp is kept, so a later collection the same day
+            // still supersedes the amended count.
+            conn.execute(
+                "INSERT INTO main.github_snapshots
+                 (date, repo, release_tag, asset_name, download_count, collected_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, NULL)
+                 ON CONFLICT (date, repo, release_tag, asset_name)
+                 DO UPDATE SET download_count = excluded.download_count",
+                params![date, repo, release, asset, count as i64],
+            )?;
+            rows.push(("github_snapshots", 1));
+            // The count no longer comes from a backfill.
+            conn.execute(
+                &format!(
+                    "DELETE FROM main.github_backfill WHERE date = ?1 AND {}",
+                    FILTER
+                ),
+                params![date, release, asset, repo],
+            )?;
+        }
+    }
+
+    // Resets found on the date, or at the next snapshot, may have been
+    // caused by the bad data; any that remain are found again.
+    for reset_date in [Some(amendment.date), next_date].into_iter().flatten() {
+        conn.execute(
+            &format!("DELETE FROM github_resets WHERE date = ?1 AND {}", FILTER),
+            params![reset_date.to_string(), release, asset, repo],
+        )?;
+    }
+
+    Ok(Amended {
+        rows,
+        previous,
+        weeks,
+    })
+}
+
+/// Clear the weekly and monthly aggregates of `identifier` for the week and
+/// month containing `date`, returning the week's first day.
+///
+/// GitHub aggregates are rebuilt from scratch, so only crates.io and install
+/// endpoint aggregates need this.
+fn clear_aggregates(
+    conn: &Connection,
+    date: NaiveDate,
+    source: &str,
+    identifier: &str,
+    week_start: WeekStart,
+) -> Result<Vec<NaiveDate>> {
+    let week = Period::Week(week_start).start_of(date);
+    conn.execute(
+        "DELETE FROM weekly_stats WHERE week_start = ?1 AND source = ?2 AND identifier = ?3",
+        params![week.to_string(), source, identifier],
+    )
+    .context("failed to clear weekly aggregates")?;
+    conn.execute(
+        "DELETE FROM monthly_stats WHERE month_start = ?1 AND source = ?2 AND identifier = ?3",
+        params![Period::Month.start_of(date).to_string(), source, identifier],
+    )
+    .context("failed to clear monthly aggregates")?;
+    Ok(vec![week])
+}
+
+/// The first days of the weeks from the one containing `first` to the one
+/// containing `last`.
+fn weeks_between(first: NaiveDate, last: NaiveDate, week_start: WeekStart) -> Vec<NaiveDate> {
+    let period = Period::Week(week_start);
+    let last = period.start_of(last);
+    let mut week = period.start_of(first);
+    let mut weeks = Vec::new();
+    while week <= last {
+        weeks.push(week);
+        week = week + Days::new(7);
+    }
+    weeks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use camino::Utf8Path;
+    use chrono::Utc;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 3, day).unwrap()
+    }
+
+    fn amendment(source: Source, change: Change) -> Amendment {
+        Amendment {
+            source,
+            date: date(5),
+            release: None,
+            asset: None,
+            version: None,
+            change,
+        }
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!("github".parse::<Source>().unwrap(), Source::Github(None));
+        assert_eq!(
+            "github:nextest-rs/nextest".parse::<Source>().unwrap(),
+            Source::Github(Some("nextest-rs/nextest".to_string()))
+        );
+        assert_eq!(
+            "crates:cargo-nextest".parse::<Source>().unwrap(),
+            Source::Crates("cargo-nextest".to_string())
+        );
+        for bad in [
+            "github:nextest",
+            "github:a/b/c",
+            "crates:",
+            "docs:x",
+            "cargo-nextest",
+        ] {
+            assert!(bad.parse::<Source>().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_amend_crates() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        for (day, version, downloads) in [(5, "0.9.1", 100), (5, "0.9.2", 50), (6, "0.9.2", 70)] {
+            db::insert_crates_download(&conn, date(day), "cargo-nextest", Some(version), downloads)
+                .unwrap();
+        }
+        // The week of March 3 and a stale week with nothing left under it.
+        db::insert_weekly_stat(&conn, date(3), "crates", "cargo-nextest", 220).unwrap();
+        db::insert_weekly_stat(&conn, date(10), "crates", "cargo-nextest", 5).unwrap();
+        let crates = Source::Crates("cargo-nextest".to_string());
+
+        let mut set = amendment(crates.clone(), Change::Set(10));
+        assert!(amend(&conn, &set, WeekStart::Monday, false).is_err());
+        set.version = Some("0.9.1".to_string());
+        let amended = amend(&conn, &set, WeekStart::Monday, false).unwrap();
+        assert_eq!(amended.previous, Some(100));
+        assert_eq!(amended.weeks, [date(3)]);
+
+        let delete = amendment(crates, Change::Delete);
+        let amended = amend(&conn, &delete, WeekStart::Monday, true).unwrap();
+        assert_eq!(
+            amended.rows,
+            [("crates_downloads", 2), ("crates_metadata", 0)]
+        );
+        amend(&conn, &delete, WeekStart::Monday, false).unwrap();
+        let remaining: Vec<(String, i64)> = conn
+            .prepare("SELECT date, downloads FROM crates_downloads")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(remaining, [("2025-03-06".to_string(), 70)]);
+        let weeks: Vec<String> = conn
+            .prepare("SELECT week_start FROM weekly_stats")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(weeks, ["2025-03-10"]);
+    }
+
+    #[test]
+    fn test_amend_github() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let at = Utc::now();
+        for (day, repo, tag, count) in [
+            (3, "o/a", "v1", 10),
+            (5, "o/a", "v1", 900),
+            (5, "o/a", "v2", 5),
+            (12, "o/a", "v1", 30),
+            (5, "o/b", "v1", 7),
+        ] {
+            db::insert_github_snapshot(
+                &conn,
+                date(day),
+                Some(at),
+                repo,
+                tag,
+                "linux.tar.gz",
+                count,
+            )
+            .unwrap();
+        }
+
+        let mut set = amendment(Source::Github(None), Change::Set(20));
+        set.release = Some("v1".to_string());
+        assert!(amend(&conn, &set, WeekStart::Monday, false).is_err());
+        set.asset = Some("linux.tar.gz".to_string());
+        // Both repositories have a v1 snapshot that day.
+        assert!(amend(&conn, &set, WeekStart::Monday, false).is_err());
+        set.source = Source::Github(Some("o/a".to_string()));
+        let amended = amend(&conn, &set, WeekStart::Monday, false).unwrap();
+        assert_eq!(amended.previous, Some(900));
+        // From the day after the previous snapshot to the next one.
+        assert_eq!(amended.weeks, [date(3), date(10)]);
+
+        let mut delete = amendment(Source::Github(None), Change::Delete);
+        delete.release = Some("v2".to_string());
+        let amended = amend(&conn, &delete, WeekStart::Monday, false).unwrap();
+        assert_eq!(amended.total_rows(), 1);
+        assert_eq!(amended.weeks, [date(3)]);
+        let delete = amendment(Source::Github(Some("o/b".to_string())), Change::Delete);
+        let amended = amend(&conn, &delete, WeekStart::Monday, false).unwrap();
+        assert_eq!(amended.total_rows(), 1);
+        let snapshots: Vec<(String, String, String, i64)> = conn
+            .prepare("SELECT date, repo, release_tag, download_count FROM github_snapshots")
+            .unwrap()
+            .query_map([], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        let row = |day: &str, count| (day.to_string(), "o/a".to_string(), "v1".to_string(), count);
+        assert_eq!(
+            snapshots,
+            [
+                row("2025-03-03", 10),
+                row("2025-03-05", 20),
+                row("2025-03-12", 30)
+            ]
+        );
+
+        let mut version = amendment(Source::Github(None), Change::Delete);
+        version.version = Some("0.9.1".to_string());
+        assert!(amend(&conn, &version, WeekStart::Monday, false).is_err());
+    }
+}
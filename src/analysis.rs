@@ -0,0 +1,2260 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Derived analyses over collected download statistics.
+
+use crate::{
+    aggregate::{self, Period},
+    db,
+    platform::{AssetFormat, Platform, PlatformGroup},
+    query::DateRange,
+};
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, Months, NaiveDate, Utc};
+use rusqlite::{Connection, OptionalExtension};
+use serde::Serialize;
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+};
+
+/// Downloads per day between two consecutive GitHub snapshots.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RatePoint {
+    /// The date of the later snapshot.
+    pub date: NaiveDate,
+    /// Days since the previous snapshot, fractional if both snapshots have
+    /// collection timestamps.
+    pub days: f64,
+    /// Downloads between the two snapshots.
+    pub downloads: i64,
+    /// Downloads per day over this interval.
+    pub per_day: f64,
+    /// Downloads per day over a trailing window of intervals.
+    pub smoothed_per_day: f64,
+}
+
+/// A cumulative download total as of a snapshot.
+#[derive(Debug, Clone, Copy)]
+struct SnapshotTotal {
+    date: NaiveDate,
+    /// When the snapshot was taken, if recorded.
+    collected_at: Option<DateTime<Utc>>,
+    total: i64,
+}
+
+impl SnapshotTotal {
+    /// Days elapsed since `prev`, measured between collection timestamps when
+    /// both are known, since snapshots are taken at varying times of day.
+    fn days_since(&self, prev: &SnapshotTotal) -> f64 {
+        match (prev.collected_at, self.collected_at) {
+            (Some(prev_at), Some(at)) if at > prev_at => {
+                (at - prev_at).num_seconds() as f64 / 86_400.0
+            }
+            _ => (self.date - prev.date).num_days().max(1) as f64,
+        }
+    }
+}
+
+/// Compute the rate of change of cumulative GitHub downloads.
+///
+/// The smoothed rate is the total delta over the last `window` intervals
+/// divided by the days they span, so irregularly spaced snapshots are
+/// weighted by their length.
+pub fn github_download_rate(conn: &Connection, window: usize) -> Result<Vec<RatePoint>> {
+    let mut stmt = conn.prepare(
+        "SELECT c.date, SUM(c.download_count) as total,
+                (SELECT MAX(s.collected_at) FROM github_snapshots s WHERE s.date = c.date)
+         FROM github_cumulative c
+         GROUP BY c.date
+         ORDER BY c.date ASC",
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, Option<String>>(2)?,
+        ))
+    })?;
+
+    let mut totals = Vec::new();
+    for row in rows {
+        let (date_str, total, collected_at) = row?;
+        let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", date_str))?;
+        let collected_at = collected_at
+            .map(|at| {
+                DateTime::parse_from_rfc3339(&at)
+                    .map(|at| at.with_timezone(&Utc))
+                    .with_context(|| format!("invalid snapshot timestamp '{}'", at))
+            })
+            .transpose()?;
+        totals.push(SnapshotTotal {
+            date,
+            collected_at,
+            total,
+        });
+    }
+
+    Ok(rate_of_change(&totals, window))
+}
+
+/// Compute per-day rates from a series of cumulative totals.
+fn rate_of_change(totals: &[SnapshotTotal], window: usize) -> Vec<RatePoint> {
+    let window = window.max(1);
+    let intervals: Vec<(NaiveDate, f64, i64)> = totals
+        .windows(2)
+        .map(|pair| {
+            let (prev, snapshot) = (&pair[0], &pair[1]);
+            let days = snapshot.days_since(prev);
+            (snapshot.date, days, (snapshot.total - prev.total).max(0))
+        })
+        .collect();
+
+    intervals
+        .iter()
+        .enumerate()
+        .map(|(idx, &(date, days, downloads))| {
+            let trailing = &intervals[(idx + 1).saturating_sub(window)..=idx];
+            let window_days: f64 = trailing.iter().map(|(_, d, _)| d).sum();
+            let window_downloads: i64 = trailing.iter().map(|(_, _, n)| n).sum();
+            RatePoint {
+                date,
+                days,
+                downloads,
+                per_day: downloads as f64 / days,
+                smoothed_per_day: window_downloads as f64 / window_days,
+            }
+        })
+        .collect()
+}
+
+/// How spread out a week's GitHub downloads are across release versions.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiversityPoint {
+    pub week_start: NaiveDate,
+    /// Number of versions with any downloads in the week.
+    pub versions: usize,
+    /// Number of versions with at least the minimum share of downloads.
+    pub active_versions: usize,
+    /// Shannon entropy (in bits) of the per-version download distribution.
+    pub entropy: f64,
+    /// The effective number of versions, `2^entropy`.
+    ///
+    /// This equals the version count when downloads are spread evenly and
+    /// approaches 1 when a single version dominates.
+    pub effective_versions: f64,
+}
+
+/// Compute weekly version diversity from GitHub release deltas.
+///
+/// `min_share` is the fraction of a week's downloads (0.0 to 1.0) a version
+/// needs to count as active.
+pub fn version_diversity(conn: &Connection, min_share: f64) -> Result<Vec<DiversityPoint>> {
+    let week = Period::Week(aggregate::stored_week_start(conn)?);
+    let mut stmt = conn.prepare(
+        "SELECT date, release_tag, SUM(downloads)
+         FROM github_daily
+         GROUP BY date, release_tag",
+    )?;
+
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+
+    let mut weeks: BTreeMap<NaiveDate, BTreeMap<String, u64>> = BTreeMap::new();
+    for row in rows {
+        let (date_str, tag, downloads) = row?;
+        let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", date_str))?;
+        *weeks
+            .entry(week.start_of(date))
+            .or_default()
+            .entry(tag)
+            .or_default() += downloads as u64;
+    }
+
+    Ok(weeks
+        .into_iter()
+        .filter_map(|(week_start, by_version)| {
+            let counts: Vec<u64> = by_version.into_values().collect();
+            diversity(&counts, min_share).map(|(versions, active_versions, entropy)| {
+                DiversityPoint {
+                    week_start,
+                    versions,
+                    active_versions,
+                    entropy,
+                    effective_versions: entropy.exp2(),
+                }
+            })
+        })
+        .collect())
+}
+
+/// Compute (versions, active versions, entropy) for a set of download counts.
+///
+/// Returns `None` if there were no downloads at all.
+fn diversity(counts: &[u64], min_share: f64) -> Option<(usize, usize, f64)> {
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return None;
+    }
+
+    let shares = counts
+        .iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| c as f64 / total as f64);
+    let versions = counts.iter().filter(|&&c| c > 0).count();
+    let active_versions = shares.clone().filter(|&p| p >= min_share).count();
+    let entropy = -shares.map(|p| p * p.log2()).sum::<f64>();
+
+    Some((versions, active_versions, entropy))
+}
+
+/// Label for versions grouped together by [`version_share`].
+pub const OLDER_VERSIONS: &str = "older";
+
+/// Weekly downloads per major.minor version, as fractions of the week.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VersionShare {
+    /// Version lines, newest first, ending with [`OLDER_VERSIONS`] if any
+    /// were grouped.
+    pub versions: Vec<String>,
+    /// For each week, the share of each entry in `versions` (summing to 1).
+    pub weeks: Vec<(NaiveDate, Vec<f64>)>,
+}
+
+/// Compute the share of weekly downloads per major.minor version.
+///
+/// `source` is `github`, `crates`, or `all`. The newest `max_versions`
+/// version lines are reported individually, and the rest are grouped under
+/// [`OLDER_VERSIONS`].
+pub fn version_share(conn: &Connection, source: &str, max_versions: usize) -> Result<VersionShare> {
+    let mut stmt = conn.prepare(
+        "SELECT week_start, version, SUM(downloads)
+         FROM weekly_version_share
+         WHERE ?1 = 'all' OR source = ?1
+         GROUP BY week_start, version",
+    )?;
+    let rows = stmt.query_map([source], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+
+    let mut weeks: BTreeMap<NaiveDate, BTreeMap<String, u64>> = BTreeMap::new();
+    for row in rows {
+        let (week_str, version, downloads) = row?;
+        let week_start = NaiveDate::parse_from_str(&week_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", week_str))?;
+        weeks
+            .entry(week_start)
+            .or_default()
+            .insert(version, downloads as u64);
+    }
+
+    Ok(group_version_shares(weeks, max_versions))
+}
+
+fn group_version_shares(
+    weeks: BTreeMap<NaiveDate, BTreeMap<String, u64>>,
+    max_versions: usize,
+) -> VersionShare {
+    let mut versions: Vec<String> = weeks
+        .values()
+        .flat_map(|by_version| by_version.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    // Sort numerically so that 0.10 is newer than 0.9.
+    versions.sort_by_cached_key(|version| {
+        let mut parts = version
+            .split('.')
+            .map(|part| part.parse::<u64>().unwrap_or(0));
+        Reverse((parts.next(), parts.next()))
+    });
+    if versions.len() > max_versions {
+        versions.truncate(max_versions);
+        versions.push(OLDER_VERSIONS.to_string());
+    }
+
+    let weeks = weeks
+        .into_iter()
+        .filter_map(|(week_start, by_version)| {
+            let total: u64 = by_version.values().sum();
+            if total == 0 {
+                return None;
+            }
+            let mut shares = vec![0.0; versions.len()];
+            for (version, downloads) in by_version {
+                // Versions not shown individually fall into the last entry.
+                let idx = versions
+                    .iter()
+                    .position(|v| *v == version)
+                    .unwrap_or(versions.len() - 1);
+                shares[idx] += downloads as f64 / total as f64;
+            }
+            Some((week_start, shares))
+        })
+        .collect();
+
+    VersionShare { versions, weeks }
+}
+
+/// Downloads per configured project, per week or month.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ProjectDownloads {
+    /// Project names, in alphabetical order.
+    pub projects: Vec<String>,
+    /// For each period (oldest first), the downloads of each entry in
+    /// `projects`.
+    pub periods: Vec<(NaiveDate, Vec<u64>)>,
+}
+
+/// Get downloads per project for each week, or each month if `monthly`.
+pub fn project_downloads(conn: &Connection, monthly: bool) -> Result<ProjectDownloads> {
+    let query = if monthly {
+        "SELECT month_start, project, downloads FROM monthly_project_stats"
+    } else {
+        "SELECT week_start, project, downloads FROM weekly_project_stats"
+    };
+    let mut stmt = conn.prepare(query)?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+
+    let mut by_period: BTreeMap<NaiveDate, BTreeMap<String, u64>> = BTreeMap::new();
+    for row in rows {
+        let (date_str, project, downloads) = row?;
+        let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", date_str))?;
+        by_period
+            .entry(date)
+            .or_default()
+            .insert(project, downloads as u64);
+    }
+
+    let projects: Vec<String> = by_period
+        .values()
+        .flat_map(|by_project| by_project.keys())
+        .cloned()
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    let periods = by_period
+        .into_iter()
+        .map(|(date, by_project)| {
+            let downloads = projects
+                .iter()
+                .map(|project| by_project.get(project).copied().unwrap_or(0))
+                .collect();
+            (date, downloads)
+        })
+        .collect();
+    Ok(ProjectDownloads { projects, periods })
+}
+
+/// Downloads of a single version over a window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionDownloads {
+    pub version: semver::Version,
+    pub crates: u64,
+    pub github: u64,
+}
+
+impl VersionDownloads {
+    pub fn total(&self) -> u64 {
+        self.crates + self.github
+    }
+}
+
+/// Get the downloads of each version over the last `days` days of each
+/// source's data, most downloaded first.
+///
+/// `source` is `github`, `crates`, or `all`. crates.io downloads are keyed by
+/// version, and GitHub downloads by the version in the release tag; downloads
+/// without a known version are left out.
+pub fn version_downloads(
+    conn: &Connection,
+    source: &str,
+    days: u32,
+) -> Result<Vec<VersionDownloads>> {
+    let window = format!("-{} days", days);
+    let mut by_version: BTreeMap<semver::Version, VersionDownloads> = BTreeMap::new();
+    let mut add = |version: &str, downloads: i64, github: bool| {
+        let Some(version) = aggregate::parse_version(version) else {
+            return;
+        };
+        let entry = by_version
+            .entry(version.clone())
+            .or_insert(VersionDownloads {
+                version,
+                crates: 0,
+                github: 0,
+            });
+        if github {
+            entry.github += downloads as u64;
+        } else {
+            entry.crates += downloads as u64;
+        }
+    };
+
+    if source != "github" {
+        let mut stmt = conn.prepare(
+            "SELECT version, SUM(downloads) FROM crates_downloads_named
+             WHERE version != ''
+               AND date > date((SELECT MAX(date) FROM crates_downloads), ?1)
+             GROUP BY version",
+        )?;
+        let rows = stmt.query_map([&window], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (version, downloads) = row?;
+            add(&version, downloads, false);
+        }
+    }
+    if source != "crates" {
+        let mut stmt = conn.prepare(
+            "SELECT release_tag, SUM(downloads) FROM github_daily
+             WHERE date > date((SELECT MAX(date) FROM github_daily), ?1)
+             GROUP BY release_tag",
+        )?;
+        let rows = stmt.query_map([&window], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        for row in rows {
+            let (tag, downloads) = row?;
+            add(&tag, downloads, true);
+        }
+    }
+
+    let mut versions: Vec<VersionDownloads> = by_version
+        .into_values()
+        .filter(|version| version.total() > 0)
+        .collect();
+    // Newer versions first among equal downloads.
+    versions.sort_by(|a, b| {
+        b.total()
+            .cmp(&a.total())
+            .then_with(|| b.version.cmp(&a.version))
+    });
+    Ok(versions)
+}
+
+/// Downloads of a version, or of a major.minor line, in two equal windows of
+/// weeks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionGrowth {
+    /// The version, such as `0.9.98`, or the line, such as `0.9`.
+    pub version: String,
+    /// Downloads in the recent window.
+    pub recent: u64,
+    /// Downloads in the window before it.
+    pub previous: u64,
+}
+
+impl VersionGrowth {
+    pub fn change(&self) -> i64 {
+        self.recent as i64 - self.previous as i64
+    }
+}
+
+/// The windows compared by [`version_growth`], with the downloads of each
+/// version in them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionGrowthWindows {
+    pub recent: DateRange,
+    pub previous: DateRange,
+    /// Largest increase from the previous window to the recent one first.
+    pub versions: Vec<VersionGrowth>,
+}
+
+/// The most weeks [`version_growth`] can compare in each window.
+pub const MAX_GROWTH_WEEKS: u32 = 52 * 50;
+
+/// Get the downloads of each version over the last `weeks` complete weeks
+/// and the `weeks` weeks before, or `None` if there's no complete week yet.
+///
+/// `source` is `github`, `crates`, or `all`. With `all`, the windows end at
+/// the latest week every source has complete. With `lines`, versions are
+/// grouped by major.minor line. Downloads without a known version are left
+/// out, and so are versions without downloads in either window.
+///
+/// Versions are ranked by how much their downloads grew, so a release that's
+/// picking up installs comes before one that's downloaded more but fading.
+pub fn version_growth(
+    conn: &Connection,
+    source: &str,
+    weeks: u32,
+    lines: bool,
+) -> Result<Option<VersionGrowthWindows>> {
+    let sources: &[&str] = match source {
+        "github" | "crates" => &[source],
+        _ => &["github", "crates"],
+    };
+    let mut last = None;
+    for source in sources {
+        if let Some(week) = latest_complete_week(conn, source)? {
+            last = Some(last.map_or(week, |last: NaiveDate| last.min(week)));
+        }
+    }
+    let Some(last) = last else {
+        return Ok(None);
+    };
+    if weeks > MAX_GROWTH_WEEKS {
+        bail!(
+            "{} weeks is too long a window: at most {} is allowed",
+            weeks,
+            MAX_GROWTH_WEEKS
+        );
+    }
+    let weeks = u64::from(weeks.max(1));
+    let out_of_range = || format!("{} weeks before {} is out of range", 2 * weeks, last);
+    let recent_start = last
+        .checked_sub_days(chrono::Days::new(7 * (weeks - 1)))
+        .with_context(out_of_range)?;
+    let previous_start = recent_start
+        .checked_sub_days(chrono::Days::new(7 * weeks))
+        .with_context(out_of_range)?;
+    let recent = DateRange {
+        since: Some(recent_start),
+        until: Some(last + chrono::Days::new(6)),
+    };
+    let previous = DateRange {
+        since: Some(previous_start),
+        until: Some(recent_start - chrono::Days::new(1)),
+    };
+
+    let mut by_version: BTreeMap<semver::Version, VersionGrowth> = BTreeMap::new();
+    for &source in sources {
+        let (table, version) = match source {
+            "github" => ("github_daily", "release_tag"),
+            _ => ("crates_downloads_named", "version"),
+        };
+        for (range, is_recent) in [(recent, true), (previous, false)] {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {version}, SUM(downloads) FROM {table}
+                 WHERE {range}
+                 GROUP BY {version}",
+                range = range.condition("date"),
+            ))?;
+            let rows = stmt.query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?;
+            for row in rows {
+                let (version, downloads) = row?;
+                let Some(mut parsed) = aggregate::parse_version(&version) else {
+                    continue;
+                };
+                if lines {
+                    parsed = semver::Version::new(parsed.major, parsed.minor, 0);
+                }
+                let entry = by_version.entry(parsed.clone()).or_insert(VersionGrowth {
+                    version: if lines {
+                        format!("{}.{}", parsed.major, parsed.minor)
+                    } else {
+                        parsed.to_string()
+                    },
+                    recent: 0,
+                    previous: 0,
+                });
+                if is_recent {
+                    entry.recent += downloads.max(0) as u64;
+                } else {
+                    entry.previous += downloads.max(0) as u64;
+                }
+            }
+        }
+    }
+
+    // More recent downloads first among equal growth, then newer versions.
+    let mut versions: Vec<_> = by_version.into_iter().rev().collect();
+    versions.retain(|(_, version)| version.recent > 0 || version.previous > 0);
+    versions.sort_by_key(|(_, version)| Reverse((version.change(), version.recent)));
+    Ok(Some(VersionGrowthWindows {
+        recent,
+        previous,
+        versions: versions.into_iter().map(|(_, version)| version).collect(),
+    }))
+}
+
+/// GitHub release downloads of a platform group over a range of dates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlatformDownloads {
+    /// The target triple, OS, or architecture, or
+    /// [`platform::UNKNOWN_PLATFORM`](crate::platform::UNKNOWN_PLATFORM).
+    pub group: String,
+    pub downloads: u64,
+}
+
+/// Get GitHub release downloads in `range` grouped by platform, most
+/// downloaded first.
+///
+/// Only artifacts are counted: checksums and signatures are downloaded
+/// alongside them and would count the same install more than once.
+pub fn platform_downloads(
+    conn: &Connection,
+    by: PlatformGroup,
+    range: DateRange,
+) -> Result<Vec<PlatformDownloads>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT asset_name, SUM(downloads) FROM github_daily
+         WHERE {}
+         GROUP BY asset_name",
+        range.condition("date")
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?;
+    let mut by_group: HashMap<String, u64> = HashMap::new();
+    for row in rows {
+        let (asset_name, downloads) = row?;
+        if !AssetFormat::from_asset_name(&asset_name).is_artifact() {
+            continue;
+        }
+        *by_group.entry(by.group_of(&asset_name)).or_insert(0) += downloads.max(0) as u64;
+    }
+
+    let mut groups: Vec<PlatformDownloads> = by_group
+        .into_iter()
+        .filter(|(_, downloads)| *downloads > 0)
+        .map(|(group, downloads)| PlatformDownloads { group, downloads })
+        .collect();
+    groups.sort_by(|a, b| {
+        b.downloads
+            .cmp(&a.downloads)
+            .then_with(|| a.group.cmp(&b.group))
+    });
+    Ok(groups)
+}
+
+/// A week's downloads of stable and pre-release versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelWeek {
+    pub week_start: NaiveDate,
+    pub stable: u64,
+    pub prerelease: u64,
+}
+
+impl ChannelWeek {
+    /// The fraction of the week's versioned downloads that were pre-releases.
+    pub fn prerelease_share(&self) -> Option<f64> {
+        let total = self.stable + self.prerelease;
+        (total > 0).then(|| self.prerelease as f64 / total as f64)
+    }
+}
+
+/// Get weekly stable and pre-release downloads, oldest first.
+///
+/// `source` is `github`, `crates`, or `all`.
+pub fn release_channels(conn: &Connection, source: &str) -> Result<Vec<ChannelWeek>> {
+    let mut stmt = conn.prepare(
+        "SELECT week_start,
+                SUM(CASE WHEN channel = ?2 THEN downloads ELSE 0 END),
+                SUM(CASE WHEN channel = ?3 THEN downloads ELSE 0 END)
+         FROM weekly_channel_stats
+         WHERE ?1 = 'all' OR source = ?1
+         GROUP BY week_start
+         ORDER BY week_start",
+    )?;
+    let rows = stmt.query_map(
+        [
+            source,
+            aggregate::STABLE_CHANNEL,
+            aggregate::PRERELEASE_CHANNEL,
+        ],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        },
+    )?;
+
+    let mut weeks = Vec::new();
+    for row in rows {
+        let (week_str, stable, prerelease) = row?;
+        let week_start = NaiveDate::parse_from_str(&week_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", week_str))?;
+        weeks.push(ChannelWeek {
+            week_start,
+            stable: stable as u64,
+            prerelease: prerelease as u64,
+        });
+    }
+    Ok(weeks)
+}
+
+/// A week's GitHub downloads of artifacts and of the signatures and
+/// attestations used to verify them, for releases that publish signatures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VerificationWeek {
+    pub week_start: NaiveDate,
+    pub artifacts: u64,
+    pub signatures: u64,
+}
+
+impl VerificationWeek {
+    /// Signature downloads per artifact download, a proxy for how many
+    /// downloads are verified.
+    pub fn signature_ratio(&self) -> Option<f64> {
+        (self.artifacts > 0).then(|| self.signatures as f64 / self.artifacts as f64)
+    }
+}
+
+/// Get weekly artifact and signature downloads, oldest first.
+pub fn verification(conn: &Connection) -> Result<Vec<VerificationWeek>> {
+    let mut stmt = conn.prepare(
+        "SELECT week_start, artifacts, signatures
+         FROM weekly_verification_stats
+         ORDER BY week_start",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, i64>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })?;
+
+    let mut weeks = Vec::new();
+    for row in rows {
+        let (week_str, artifacts, signatures) = row?;
+        let week_start = NaiveDate::parse_from_str(&week_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", week_str))?;
+        weeks.push(VerificationWeek {
+            week_start,
+            artifacts: artifacts as u64,
+            signatures: signatures as u64,
+        });
+    }
+    Ok(weeks)
+}
+
+/// Number of days of GitHub data that recent target downloads are counted
+/// over.
+pub const RECENT_TARGET_DAYS: i64 = 28;
+
+/// How a target triple was supported across stable releases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetSupport {
+    pub target: String,
+    pub platform: String,
+    /// The first stable release that shipped the target.
+    pub added_in: String,
+    /// The first stable release after the target was last shipped, or `None`
+    /// if the latest stable release ships it.
+    pub dropped_in: Option<String>,
+    /// Number of stable releases that shipped the target.
+    pub releases: usize,
+    /// Cumulative downloads of the target's artifacts across all releases.
+    pub downloads: u64,
+    /// Downloads of the target's artifacts over the last
+    /// [`RECENT_TARGET_DAYS`] days of GitHub data, across all releases.
+    pub recent_downloads: u64,
+}
+
+/// Targets added and dropped by a stable release, compared to the previous
+/// stable release.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetChange {
+    pub version: String,
+    /// The GitHub publish date, if it was recorded.
+    pub published_on: Option<NaiveDate>,
+    pub added: Vec<String>,
+    pub dropped: Vec<String>,
+}
+
+/// The target triples shipped across releases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TargetMatrix {
+    /// Targets shipped by at least one stable release, most recently
+    /// downloaded first.
+    pub targets: Vec<TargetSupport>,
+    /// Stable releases that changed the set of targets, oldest first.
+    pub changes: Vec<TargetChange>,
+}
+
+/// Compute when each target was added and dropped across stable releases,
+/// from `release_targets`, along with its downloads.
+///
+/// Pre-releases often ship a partial set of targets, so they're left out of
+/// the comparison, though their downloads are counted.
+pub fn target_matrix(conn: &Connection) -> Result<TargetMatrix> {
+    let releases = db::get_github_releases(conn)?;
+    let published: HashMap<&str, NaiveDate> = releases
+        .iter()
+        .map(|release| (release.release_tag.as_str(), release.published_on))
+        .collect();
+    let prereleases: HashSet<&str> = releases
+        .iter()
+        .filter(|release| release.prerelease)
+        .map(|release| release.release_tag.as_str())
+        .collect();
+
+    let mut totals: BTreeMap<String, (String, u64)> = BTreeMap::new();
+    let mut stable: BTreeMap<semver::Version, (String, BTreeSet<String>)> = BTreeMap::new();
+    for target in db::get_release_targets(conn)? {
+        totals
+            .entry(target.target.clone())
+            .or_insert_with(|| (target.platform.clone(), 0))
+            .1 += target.downloads;
+        let Some(version) = aggregate::parse_version(&target.release_tag) else {
+            continue;
+        };
+        if !version.pre.is_empty() || prereleases.contains(target.release_tag.as_str()) {
+            continue;
+        }
+        stable
+            .entry(version)
+            .or_insert_with(|| (target.release_tag.clone(), BTreeSet::new()))
+            .1
+            .insert(target.target);
+    }
+
+    let versions: Vec<(&semver::Version, &(String, BTreeSet<String>))> = stable.iter().collect();
+    let mut added_in: BTreeMap<&str, &semver::Version> = BTreeMap::new();
+    let mut last_in: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    let mut changes = Vec::new();
+    for (idx, (version, (tag, targets))) in versions.iter().enumerate() {
+        for target in targets {
+            added_in.entry(target).or_insert(version);
+            last_in.insert(target, idx);
+            *counts.entry(target).or_default() += 1;
+        }
+        // The first release is the baseline.
+        if let Some((_, (_, previous))) = idx.checked_sub(1).map(|prev| versions[prev]) {
+            let added: Vec<String> = targets.difference(previous).cloned().collect();
+            let dropped: Vec<String> = previous.difference(targets).cloned().collect();
+            if !added.is_empty() || !dropped.is_empty() {
+                changes.push(TargetChange {
+                    version: version.to_string(),
+                    published_on: published.get(tag.as_str()).copied(),
+                    added,
+                    dropped,
+                });
+            }
+        }
+    }
+
+    let recent = recent_target_downloads(conn)?;
+    let mut targets: Vec<TargetSupport> = added_in
+        .into_iter()
+        .map(|(target, version)| {
+            let (platform, downloads) = totals[target].clone();
+            TargetSupport {
+                target: target.to_string(),
+                platform,
+                added_in: version.to_string(),
+                dropped_in: versions
+                    .get(last_in[target] + 1)
+                    .map(|(version, _)| version.to_string()),
+                releases: counts[target],
+                downloads,
+                recent_downloads: recent.get(target).copied().unwrap_or(0),
+            }
+        })
+        .collect();
+    targets.sort_by(|a, b| {
+        b.recent_downloads
+            .cmp(&a.recent_downloads)
+            .then_with(|| a.target.cmp(&b.target))
+    });
+
+    Ok(TargetMatrix { targets, changes })
+}
+
+/// Get artifact downloads per target over the last [`RECENT_TARGET_DAYS`]
+/// days of GitHub data.
+fn recent_target_downloads(conn: &Connection) -> Result<HashMap<String, u64>> {
+    let mut stmt = conn.prepare(
+        "SELECT asset_name, SUM(downloads) FROM github_daily
+         WHERE date > date((SELECT MAX(date) FROM github_daily), ?1)
+         GROUP BY asset_name",
+    )?;
+    let rows = stmt.query_map([format!("-{} days", RECENT_TARGET_DAYS)], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut downloads = HashMap::new();
+    for row in rows {
+        let (asset_name, count) = row?;
+        let format = AssetFormat::from_asset_name(&asset_name);
+        if !format.is_artifact() || format == AssetFormat::Other {
+            continue;
+        }
+        if let Some(platform) = Platform::from_asset_name(&asset_name) {
+            *downloads.entry(platform.target).or_default() += count.max(0) as u64;
+        }
+    }
+    Ok(downloads)
+}
+
+/// A period-over-period comparison of downloads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrowthMetric {
+    /// Week over week.
+    Wow,
+    /// Month over month.
+    Mom,
+    /// Year over year, comparing a month with the same month a year earlier.
+    Yoy,
+}
+
+impl GrowthMetric {
+    pub fn label(self) -> &'static str {
+        match self {
+            GrowthMetric::Wow => "WoW",
+            GrowthMetric::Mom => "MoM",
+            GrowthMetric::Yoy => "YoY",
+        }
+    }
+}
+
+/// The change in downloads for one source between two periods.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Growth {
+    pub source: &'static str,
+    pub metric: GrowthMetric,
+    /// Start of the most recent complete period.
+    pub period: NaiveDate,
+    pub downloads: u64,
+    /// Start of the period compared against.
+    pub previous_period: NaiveDate,
+    /// Downloads in the previous period, if there is data for it.
+    pub previous: Option<u64>,
+}
+
+impl Growth {
+    /// The percentage change, or `None` if there's nothing to compare with.
+    pub fn change(&self) -> Option<f64> {
+        percent_change(self.downloads, self.previous)
+    }
+}
+
+/// Compute the percentage change from `previous` to `current`.
+pub fn percent_change(current: u64, previous: Option<u64>) -> Option<f64> {
+    match previous {
+        Some(previous) if previous > 0 => {
+            Some((current as f64 - previous as f64) / previous as f64 * 100.0)
+        }
+        _ => None,
+    }
+}
+
+/// Compute week-over-week, month-over-month, and year-over-year growth for
+/// each source.
+///
+/// Only complete periods are compared: the period containing the most recent
+/// data for a source is skipped unless the data covers it to the end.
+pub fn growth(conn: &Connection) -> Result<Vec<Growth>> {
+    let mut results = Vec::new();
+    for source in SOURCES {
+        let Some(latest) = latest_data_date(conn, source)? else {
+            continue;
+        };
+
+        let weekly = period_totals(conn, "weekly_stats", "week_start", source)?;
+        let week_after = |start: NaiveDate| start + chrono::Duration::days(7);
+        if let Some(week) = latest_complete(&weekly, latest, week_after) {
+            let previous_period = week - chrono::Duration::days(7);
+            results.push(Growth {
+                source,
+                metric: GrowthMetric::Wow,
+                period: week,
+                downloads: weekly[&week],
+                previous_period,
+                previous: weekly.get(&previous_period).copied(),
+            });
+        }
+
+        let monthly = period_totals(conn, "monthly_stats", "month_start", source)?;
+        let month_after = |start: NaiveDate| start + Months::new(1);
+        if let Some(month) = latest_complete(&monthly, latest, month_after) {
+            for (metric, months) in [(GrowthMetric::Mom, 1), (GrowthMetric::Yoy, 12)] {
+                let previous_period = month - Months::new(months);
+                results.push(Growth {
+                    source,
+                    metric,
+                    period: month,
+                    downloads: monthly[&month],
+                    previous_period,
+                    previous: monthly.get(&previous_period).copied(),
+                });
+            }
+        }
+    }
+
+    Ok(results)
+}
+
+/// Downloads from a source over a range of dates.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceTotal {
+    pub source: &'static str,
+    pub downloads: u64,
+    /// The first date with downloads for the source, if any. Ranges starting
+    /// before it are only partly covered.
+    pub first_date: Option<NaiveDate>,
+}
+
+/// Sum the daily downloads of each source over `range`.
+///
+/// GitHub downloads come from `github_daily`, so they're counted on the date
+/// of the snapshot that first saw them.
+pub fn source_totals(conn: &Connection, range: DateRange) -> Result<Vec<SourceTotal>> {
+    SOURCES
+        .into_iter()
+        .map(|source| {
+            let table = match source {
+                "github" => "github_daily",
+                "installer" => "installer_downloads",
+                _ => "crates_downloads",
+            };
+            let (downloads, first_date): (i64, Option<String>) = conn.query_row(
+                &format!(
+                    "SELECT
+                       (SELECT COALESCE(SUM(downloads), 0) FROM {table} WHERE {range}),
+                       (SELECT MIN(date) FROM {table})",
+                    range = range.condition("date"),
+                ),
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+            let first_date = first_date
+                .map(|date| {
+                    NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                        .with_context(|| format!("failed to parse date '{}'", date))
+                })
+                .transpose()?;
+            Ok(SourceTotal {
+                source,
+                downloads: downloads.max(0) as u64,
+                first_date,
+            })
+        })
+        .collect()
+}
+
+/// Gaps in the data collected for a source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceGaps {
+    pub source: &'static str,
+    pub first: NaiveDate,
+    pub latest: NaiveDate,
+    /// Days from the latest date with data to today.
+    pub age_days: i64,
+    /// Runs of days without data between the first and latest dates, as
+    /// inclusive ranges, oldest first.
+    pub missing: Vec<(NaiveDate, NaiveDate)>,
+    /// Weeks with data for fewer than 7 days, with the number of days they
+    /// have, oldest first. The week containing today is still in progress,
+    /// so it's left out.
+    pub incomplete_weeks: Vec<(NaiveDate, u32)>,
+}
+
+impl SourceGaps {
+    /// The number of days without data.
+    pub fn missing_days(&self) -> i64 {
+        self.missing
+            .iter()
+            .map(|(from, to)| (*to - *from).num_days() + 1)
+            .sum()
+    }
+}
+
+/// Find the days each source has no data for, and the weeks those days leave
+/// incomplete.
+///
+/// A day counts as collected if there's a GitHub snapshot, a crates.io
+/// download row, or an install endpoint count for it. Sources without any
+/// data are left out.
+pub fn collection_gaps(conn: &Connection, today: NaiveDate) -> Result<Vec<SourceGaps>> {
+    let week = Period::Week(aggregate::stored_week_start(conn)?);
+    let mut results = Vec::new();
+    for source in SOURCES {
+        let table = match source {
+            "github" => "github_snapshots",
+            "installer" => "installer_downloads",
+            _ => "crates_downloads",
+        };
+        let mut stmt = conn.prepare(&format!(
+            "SELECT DISTINCT date FROM {} ORDER BY date",
+            table
+        ))?;
+        let dates = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|date| {
+                let date = date?;
+                NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                    .with_context(|| format!("failed to parse date '{}'", date))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let (Some(&first), Some(&latest)) = (dates.first(), dates.last()) else {
+            continue;
+        };
+
+        let missing = dates
+            .windows(2)
+            .filter(|pair| (pair[1] - pair[0]).num_days() > 1)
+            .map(|pair| {
+                (
+                    pair[0] + chrono::Days::new(1),
+                    pair[1] - chrono::Days::new(1),
+                )
+            })
+            .collect();
+
+        let mut days_per_week: BTreeMap<NaiveDate, u32> = BTreeMap::new();
+        for date in &dates {
+            *days_per_week.entry(week.start_of(*date)).or_insert(0) += 1;
+        }
+        let current_week = week.start_of(today);
+        let mut incomplete_weeks = Vec::new();
+        let mut week_start = week.start_of(first);
+        while week_start <= latest && week_start < current_week {
+            let days = days_per_week.get(&week_start).copied().unwrap_or(0);
+            if days < 7 {
+                incomplete_weeks.push((week_start, days));
+            }
+            week_start = week_start + chrono::Days::new(7);
+        }
+
+        results.push(SourceGaps {
+            source,
+            first,
+            latest,
+            age_days: (today - latest).num_days(),
+            missing,
+            incomplete_weeks,
+        });
+    }
+    Ok(results)
+}
+
+/// The sources with aggregates in `weekly_stats` and `monthly_stats`.
+pub(crate) const SOURCES: [&str; 3] = ["github", "crates", "installer"];
+
+/// Get the most recent date with raw data for a source.
+pub(crate) fn latest_data_date(conn: &Connection, source: &str) -> Result<Option<NaiveDate>> {
+    let query = match source {
+        "github" => "SELECT MAX(date) FROM github_snapshots",
+        "installer" => "SELECT MAX(date) FROM installer_downloads",
+        _ => "SELECT MAX(date) FROM crates_downloads",
+    };
+    let latest: Option<String> = conn
+        .query_row(query, [], |row| row.get(0))
+        .optional()?
+        .flatten();
+    latest
+        .map(|latest| {
+            NaiveDate::parse_from_str(&latest, "%Y-%m-%d")
+                .with_context(|| format!("failed to parse date '{}'", latest))
+        })
+        .transpose()
+}
+
+/// Get download totals per period start for a source from an aggregate table.
+fn period_totals(
+    conn: &Connection,
+    table: &str,
+    start_column: &str,
+    source: &str,
+) -> Result<BTreeMap<NaiveDate, u64>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {start_column}, SUM(downloads) FROM {table}
+         WHERE source = ?1
+         GROUP BY {start_column}"
+    ))?;
+    let rows = stmt.query_map([source], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+    })?;
+
+    let mut totals = BTreeMap::new();
+    for row in rows {
+        let (date_str, downloads) = row?;
+        let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", date_str))?;
+        totals.insert(date, downloads as u64);
+    }
+    Ok(totals)
+}
+
+/// Weekly downloads for a source, oldest first, through its latest complete
+/// week.
+///
+/// With `all`, the sources are summed over the weeks that every source with
+/// data has completed.
+pub fn weekly_trend(conn: &Connection, source: &str) -> Result<Vec<(NaiveDate, u64)>> {
+    let sources: &[&str] = match source {
+        "all" => &SOURCES,
+        source => &[source],
+    };
+    let mut last = None;
+    let mut combined: BTreeMap<NaiveDate, u64> = BTreeMap::new();
+    for &source in sources {
+        let Some(week) = latest_complete_week(conn, source)? else {
+            continue;
+        };
+        last = Some(last.map_or(week, |last: NaiveDate| last.min(week)));
+        for (week, downloads) in period_totals(conn, "weekly_stats", "week_start", source)? {
+            *combined.entry(week).or_default() += downloads;
+        }
+    }
+    let Some(last) = last else {
+        return Ok(Vec::new());
+    };
+    Ok(combined
+        .into_iter()
+        .filter(|(week, _)| *week <= last)
+        .collect())
+}
+
+/// Summary statistics of a source's complete weeks.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeeklySummary {
+    pub weeks: usize,
+    pub first_week: NaiveDate,
+    pub last_week: NaiveDate,
+    pub total: u64,
+    /// The weeks with the fewest and the most downloads, with their
+    /// downloads. Ties go to the later week.
+    pub min: (NaiveDate, u64),
+    pub max: (NaiveDate, u64),
+    pub mean: f64,
+    pub median: f64,
+    pub p90: f64,
+    /// The longest run of weeks each with more downloads than the one
+    /// before, as its first and last weeks and the number of increases.
+    /// Ties go to the later run.
+    pub growth_streak: Option<(NaiveDate, NaiveDate, usize)>,
+}
+
+/// Summarize the complete weeks of [`weekly_trend`], or `None` if there are
+/// none.
+pub fn weekly_summary(conn: &Connection, source: &str) -> Result<Option<WeeklySummary>> {
+    Ok(summarize_weeks(&weekly_trend(conn, source)?))
+}
+
+fn summarize_weeks(weeks: &[(NaiveDate, u64)]) -> Option<WeeklySummary> {
+    let (&(first_week, _), &(last_week, _)) = (weeks.first()?, weeks.last()?);
+    let total: u64 = weeks.iter().map(|(_, downloads)| downloads).sum();
+    // `min_by_key` keeps the first of equal elements and `max_by_key` the
+    // last, so search from the end for the minimum.
+    let min = *weeks.iter().rev().min_by_key(|(_, downloads)| downloads)?;
+    let max = *weeks.iter().max_by_key(|(_, downloads)| downloads)?;
+
+    let mut sorted: Vec<u64> = weeks.iter().map(|(_, downloads)| *downloads).collect();
+    sorted.sort_unstable();
+    // Linear interpolation between the closest ranks.
+    let percentile = |p: f64| {
+        let rank = p * (sorted.len() - 1) as f64;
+        let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+        sorted[lower] as f64 + (sorted[upper] as f64 - sorted[lower] as f64) * rank.fract()
+    };
+
+    let mut growth_streak = None;
+    let mut start = 0;
+    for idx in 1..weeks.len() {
+        if weeks[idx].1 <= weeks[idx - 1].1 {
+            start = idx;
+            continue;
+        }
+        let increases = idx - start;
+        if growth_streak.is_none_or(|(_, _, longest)| increases >= longest) {
+            growth_streak = Some((weeks[start].0, weeks[idx].0, increases));
+        }
+    }
+
+    Some(WeeklySummary {
+        weeks: weeks.len(),
+        first_week,
+        last_week,
+        total,
+        min,
+        max,
+        mean: total as f64 / weeks.len() as f64,
+        median: percentile(0.5),
+        p90: percentile(0.9),
+        growth_streak,
+    })
+}
+
+/// Number of complete weeks the day-of-week pattern of a week projection is
+/// learned from.
+pub const PROJECTION_WEEKS: i64 = 8;
+
+/// The downloads of a week still in progress, projected to the full week.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WeekProjection {
+    pub week_start: NaiveDate,
+    /// Days of the week with data so far, from 1 to 6.
+    pub days: i64,
+    /// Downloads so far.
+    pub downloads: u64,
+    /// Projected downloads for the full week.
+    pub projected: u64,
+}
+
+/// Project the downloads of a source's in-progress week, or of every source
+/// with `all`, to the full week.
+///
+/// Weekly downloads aren't spread evenly over the days of the week, so the
+/// weekly total so far is divided by the share of weekly downloads the same
+/// days had over the previous [`PROJECTION_WEEKS`] weeks. GitHub downloads are
+/// only known per snapshot interval, and a week's total already includes
+/// whole intervals, so they're counted as they are rather than scaled up.
+/// Returns `None` if the latest week is complete, there's no history to learn
+/// the pattern from, or there's nothing to scale up. With `all`, every source with data must be in the
+/// same week.
+pub fn project_current_week(conn: &Connection, source: &str) -> Result<Option<WeekProjection>> {
+    let sources: &[&str] = match source {
+        "all" => &SOURCES,
+        source => &[source],
+    };
+    let week_start = aggregate::stored_week_start(conn)?;
+    let mut combined: Option<WeekProjection> = None;
+    let mut scaled = false;
+    for &source in sources {
+        let Some(latest) = latest_data_date(conn, source)? else {
+            continue;
+        };
+        let week = Period::Week(week_start).start_of(latest);
+        let days = (latest - week).num_days() + 1;
+        if days >= 7 || combined.as_ref().is_some_and(|c| c.week_start != week) {
+            return Ok(None);
+        }
+        let downloads = period_totals(conn, "weekly_stats", "week_start", source)?
+            .get(&week)
+            .copied()
+            .unwrap_or(0);
+        let projection = combined.get_or_insert(WeekProjection {
+            week_start: week,
+            days,
+            downloads: 0,
+            projected: 0,
+        });
+        projection.days = projection.days.min(days);
+        projection.downloads += downloads;
+        if source == "github" {
+            projection.projected += downloads;
+            continue;
+        }
+
+        let since = week - chrono::Duration::weeks(PROJECTION_WEEKS);
+        let mut stmt = conn.prepare(
+            "SELECT date, SUM(downloads) FROM rolling_stats
+             WHERE source = ?1 AND date >= ?2 AND date < ?3
+             GROUP BY date",
+        )?;
+        let rows = stmt
+            .query_map(
+                rusqlite::params![source, since.to_string(), week.to_string()],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?)),
+            )?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // Sum the downloads of the same leading days of past weeks, and of
+        // the whole weeks.
+        let (mut leading, mut total) = (0.0, 0.0);
+        for (date, value) in rows {
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .with_context(|| format!("failed to parse date '{}'", date))?;
+            let day = (date - Period::Week(week_start).start_of(date)).num_days();
+            total += value;
+            if day < days {
+                leading += value;
+            }
+        }
+        if leading <= 0.0 {
+            return Ok(None);
+        }
+        projection.projected += (downloads as f64 * total / leading).round() as u64;
+        scaled = true;
+    }
+    Ok(combined.filter(|_| scaled))
+}
+
+/// The start of the latest week a source has complete data for.
+pub(crate) fn latest_complete_week(conn: &Connection, source: &str) -> Result<Option<NaiveDate>> {
+    let Some(latest) = latest_data_date(conn, source)? else {
+        return Ok(None);
+    };
+    let weekly = period_totals(conn, "weekly_stats", "week_start", source)?;
+    let week_after = |start: NaiveDate| start + chrono::Duration::days(7);
+    Ok(latest_complete(&weekly, latest, week_after))
+}
+
+/// Find the latest period that ends on or before `latest`, the last day with
+/// data.
+fn latest_complete(
+    totals: &BTreeMap<NaiveDate, u64>,
+    latest: NaiveDate,
+    next_start: impl Fn(NaiveDate) -> NaiveDate,
+) -> Option<NaiveDate> {
+    totals
+        .keys()
+        .rev()
+        .copied()
+        .find(|&start| next_start(start) <= latest + chrono::Duration::days(1))
+}
+
+/// An identifier whose weekly downloads changed from the week before.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Mover {
+    /// What the identifier is: `crate`, `platform`, or `version`.
+    pub category: &'static str,
+    /// The crate name, platform label, or version line with its source, such
+    /// as `0.9 (github)`.
+    pub identifier: String,
+    /// Start of the most recent complete week.
+    pub week_start: NaiveDate,
+    pub previous: u64,
+    pub downloads: u64,
+}
+
+impl Mover {
+    /// The change in downloads from the previous week.
+    pub fn change(&self) -> i64 {
+        self.downloads as i64 - self.previous as i64
+    }
+}
+
+/// The largest week-over-week gains and losses, each sorted by the size of
+/// the change.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Movers {
+    pub gains: Vec<Mover>,
+    pub losses: Vec<Mover>,
+}
+
+/// Minimum weekly downloads for an identifier to count as a mover by default.
+pub const DEFAULT_MOVER_MIN_DOWNLOADS: u64 = 1000;
+
+/// Number of previous weeks each week is compared against by default when
+/// detecting anomalies.
+pub const DEFAULT_ANOMALY_WINDOW: usize = 8;
+
+/// Z-score at which a week is flagged as an anomaly by default.
+pub const DEFAULT_ANOMALY_THRESHOLD: f64 = 3.0;
+
+/// Weekly per-identifier aggregates that movers are drawn from: category,
+/// source for finding complete weeks, and a query returning week start,
+/// identifier, and downloads.
+const MOVER_QUERIES: [(&str, &str, &str); 4] = [
+    (
+        "crate",
+        "crates",
+        "SELECT week_start, identifier, downloads FROM weekly_stats WHERE source = 'crates'",
+    ),
+    (
+        "platform",
+        "github",
+        "SELECT week_start, platform, downloads FROM weekly_platform_stats",
+    ),
+    (
+        "version",
+        "github",
+        "SELECT week_start, version || ' (github)', downloads FROM weekly_version_share
+         WHERE source = 'github'",
+    ),
+    (
+        "version",
+        "crates",
+        "SELECT week_start, version || ' (crates)', downloads FROM weekly_version_share
+         WHERE source = 'crates'",
+    ),
+];
+
+/// Find the crates, platforms, and versions with the largest changes in
+/// downloads between the latest complete week and the one before.
+///
+/// Identifiers with fewer than `min_downloads` in both weeks are ignored, so
+/// that small absolute changes don't dominate. Up to `limit` gains and
+/// `limit` losses are returned.
+pub fn movers(conn: &Connection, min_downloads: u64, limit: usize) -> Result<Movers> {
+    let mut all = Vec::new();
+    for (category, source, query) in MOVER_QUERIES {
+        let Some(latest) = latest_data_date(conn, source)? else {
+            continue;
+        };
+
+        let mut weeks: BTreeMap<NaiveDate, BTreeMap<String, u64>> = BTreeMap::new();
+        let mut stmt = conn.prepare(query)?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?;
+        for row in rows {
+            let (date_str, identifier, downloads) = row?;
+            let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+                .with_context(|| format!("failed to parse date '{}'", date_str))?;
+            *weeks
+                .entry(date)
+                .or_default()
+                .entry(identifier)
+                .or_default() += downloads as u64;
+        }
+
+        let totals: BTreeMap<NaiveDate, u64> = weeks
+            .iter()
+            .map(|(week, counts)| (*week, counts.values().sum()))
+            .collect();
+        let week_after = |start: NaiveDate| start + chrono::Duration::days(7);
+        let Some(week) = latest_complete(&totals, latest, week_after) else {
+            continue;
+        };
+        let empty = BTreeMap::new();
+        let previous = weeks
+            .get(&(week - chrono::Duration::days(7)))
+            .unwrap_or(&empty);
+        all.extend(
+            week_movers(&weeks[&week], previous, min_downloads)
+                .into_iter()
+                .map(|(identifier, previous, downloads)| Mover {
+                    category,
+                    identifier,
+                    week_start: week,
+                    previous,
+                    downloads,
+                }),
+        );
+    }
+
+    let (mut gains, mut losses): (Vec<_>, Vec<_>) =
+        all.into_iter().partition(|mover| mover.change() > 0);
+    gains.sort_by_key(|mover| Reverse(mover.change()));
+    losses.sort_by_key(|mover| mover.change());
+    gains.truncate(limit);
+    losses.truncate(limit);
+    Ok(Movers { gains, losses })
+}
+
+/// Pair up an identifier's downloads in two weeks, keeping those that
+/// changed and reached `min_downloads` in either week.
+fn week_movers(
+    current: &BTreeMap<String, u64>,
+    previous: &BTreeMap<String, u64>,
+    min_downloads: u64,
+) -> Vec<(String, u64, u64)> {
+    let identifiers: BTreeSet<&String> = current.keys().chain(previous.keys()).collect();
+    identifiers
+        .into_iter()
+        .filter_map(|identifier| {
+            let downloads = current.get(identifier).copied().unwrap_or(0);
+            let before = previous.get(identifier).copied().unwrap_or(0);
+            (downloads != before && downloads.max(before) >= min_downloads)
+                .then(|| (identifier.clone(), before, downloads))
+        })
+        .collect()
+}
+
+/// A week whose downloads deviate strongly from the trailing average.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Anomaly {
+    pub source: &'static str,
+    pub week_start: NaiveDate,
+    pub downloads: u64,
+    /// Mean weekly downloads over the trailing window.
+    pub mean: f64,
+    /// Standard deviation of weekly downloads over the trailing window.
+    pub stddev: f64,
+    pub z_score: f64,
+    pub kind: AnomalyKind,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnomalyKind {
+    Spike,
+    Drop,
+}
+
+impl AnomalyKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AnomalyKind::Spike => "spike",
+            AnomalyKind::Drop => "drop",
+        }
+    }
+}
+
+/// Find weeks where downloads deviate from the trailing `window` weeks by at
+/// least `threshold` standard deviations, per source.
+///
+/// If `recent` is set, only the most recent that many weeks are checked.
+/// Incomplete weeks at the end of the data are skipped, since they would
+/// always look like drops.
+pub fn detect_anomalies(
+    conn: &Connection,
+    window: usize,
+    threshold: f64,
+    recent: Option<usize>,
+) -> Result<Vec<Anomaly>> {
+    let mut anomalies = Vec::new();
+    for source in SOURCES {
+        let Some(latest) = latest_data_date(conn, source)? else {
+            continue;
+        };
+        let weekly = period_totals(conn, "weekly_stats", "week_start", source)?;
+        let series: Vec<(NaiveDate, u64)> = weekly
+            .into_iter()
+            .filter(|(start, _)| {
+                *start + chrono::Duration::days(7) <= latest + chrono::Duration::days(1)
+            })
+            .collect();
+
+        let first_checked = recent.map_or(0, |recent| series.len().saturating_sub(recent));
+        for (idx, mean, stddev, z_score) in find_anomalies(&series, window, threshold) {
+            if idx < first_checked {
+                continue;
+            }
+            anomalies.push(Anomaly {
+                source,
+                week_start: series[idx].0,
+                downloads: series[idx].1,
+                mean,
+                stddev,
+                z_score,
+                kind: if z_score > 0.0 {
+                    AnomalyKind::Spike
+                } else {
+                    AnomalyKind::Drop
+                },
+            });
+        }
+    }
+    Ok(anomalies)
+}
+
+/// Return (index, mean, stddev, z-score) for each point that deviates from
+/// the previous `window` points by at least `threshold` standard deviations.
+///
+/// Points without a full window of history, or whose window has no variance,
+/// are never flagged.
+fn find_anomalies(
+    series: &[(NaiveDate, u64)],
+    window: usize,
+    threshold: f64,
+) -> Vec<(usize, f64, f64, f64)> {
+    let window = window.max(2);
+    (window..series.len())
+        .filter_map(|idx| {
+            let trailing = &series[idx - window..idx];
+            let mean = trailing.iter().map(|(_, n)| *n as f64).sum::<f64>() / window as f64;
+            let variance = trailing
+                .iter()
+                .map(|(_, n)| (*n as f64 - mean).powi(2))
+                .sum::<f64>()
+                / window as f64;
+            let stddev = variance.sqrt();
+            if stddev == 0.0 {
+                return None;
+            }
+            let z_score = (series[idx].1 as f64 - mean) / stddev;
+            (z_score.abs() >= threshold).then_some((idx, mean, stddev, z_score))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 11, day).unwrap()
+    }
+
+    #[test]
+    fn test_week_movers() {
+        let week = |counts: &[(&str, u64)]| -> BTreeMap<String, u64> {
+            counts.iter().map(|(i, d)| (i.to_string(), *d)).collect()
+        };
+        let current = week(&[
+            ("linux", 5000),
+            ("macos", 900),
+            ("windows", 2000),
+            ("new", 1500),
+        ]);
+        let previous = week(&[
+            ("linux", 4000),
+            ("macos", 100),
+            ("windows", 2000),
+            ("gone", 1200),
+        ]);
+
+        assert_eq!(
+            week_movers(&current, &previous, 1000),
+            [
+                ("gone".to_string(), 1200, 0),
+                ("linux".to_string(), 4000, 5000),
+                ("new".to_string(), 0, 1500),
+            ]
+        );
+        // macos moved a lot relatively, but stays under the minimum volume.
+        assert_eq!(week_movers(&current, &previous, 0).len(), 4);
+    }
+
+    #[test]
+    fn test_weekly_trend() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        assert_eq!(weekly_trend(&conn, "all").unwrap(), []);
+
+        // GitHub has complete weeks from Monday 2025-11-03 and 10, crates
+        // only the first one, and the installer has no data.
+        db::insert_weekly_stat(&conn, date(3), "github", "releases", 10).unwrap();
+        db::insert_weekly_stat(&conn, date(10), "github", "releases", 20).unwrap();
+        db::insert_github_snapshot(
+            &conn,
+            date(16),
+            Some(Utc::now()),
+            "nextest-rs/nextest",
+            "cargo-nextest-0.9.1",
+            "a",
+            1,
+        )
+        .unwrap();
+        db::insert_weekly_stat(&conn, date(3), "crates", "cargo-nextest", 1).unwrap();
+        db::insert_weekly_stat(&conn, date(10), "crates", "cargo-nextest", 5).unwrap();
+        db::insert_crates_download(&conn, date(12), "cargo-nextest", None, 5).unwrap();
+
+        assert_eq!(
+            weekly_trend(&conn, "github").unwrap(),
+            [(date(3), 10), (date(10), 20)]
+        );
+        assert_eq!(weekly_trend(&conn, "crates").unwrap(), [(date(3), 1)]);
+        // Sources are summed over the weeks every source with data completed.
+        assert_eq!(weekly_trend(&conn, "all").unwrap(), [(date(3), 11)]);
+        assert_eq!(weekly_trend(&conn, "installer").unwrap(), []);
+    }
+
+    #[test]
+    fn test_project_current_week() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        // Two complete weeks from Monday 2025-11-03, busier on weekdays, then
+        // the Monday and Tuesday of the next week.
+        for day in 3..=18 {
+            let weekday = chrono::Datelike::weekday(&date(day)).num_days_from_monday();
+            let downloads = if weekday < 5 { 100.0 } else { 20.0 };
+            db::insert_rolling_stat(&conn, date(day), "crates", downloads, 0.0, 0.0).unwrap();
+        }
+        db::insert_crates_download(&conn, date(18), "cargo-nextest", None, 90).unwrap();
+        db::insert_weekly_stat(&conn, date(17), "crates", "cargo-nextest", 180).unwrap();
+        db::insert_weekly_stat(&conn, date(17), "github", "releases", 50).unwrap();
+        db::insert_github_snapshot(
+            &conn,
+            date(18),
+            Some(Utc::now()),
+            "nextest-rs/nextest",
+            "cargo-nextest-0.9.1",
+            "a",
+            1,
+        )
+        .unwrap();
+
+        // Mondays and Tuesdays had 400 of the 1080 downloads.
+        let projection = project_current_week(&conn, "crates").unwrap().unwrap();
+        assert_eq!(projection.week_start, date(17));
+        assert_eq!(projection.days, 2);
+        assert_eq!(projection.downloads, 180);
+        assert_eq!(projection.projected, 486);
+
+        // GitHub downloads are counted but not scaled.
+        let projection = project_current_week(&conn, "all").unwrap().unwrap();
+        assert_eq!((projection.downloads, projection.projected), (230, 536));
+        assert_eq!(project_current_week(&conn, "github").unwrap(), None);
+    }
+
+    #[test]
+    fn test_version_downloads() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        let crate_download = |day, version, downloads| {
+            db::insert_crates_download(&conn, date(day), "cargo-nextest", Some(version), downloads)
+                .unwrap()
+        };
+        // Only the last 7 days of each source count.
+        crate_download(1, "0.9.1", 1000);
+        crate_download(10, "0.9.1", 30);
+        crate_download(14, "0.9.2", 40);
+        crate_download(14, "0.9.3", 0);
+        db::insert_crates_download(&conn, date(14), "cargo-nextest", None, 70).unwrap();
+        let github = |day, tag: &str, downloads| {
+            db::insert_github_daily(
+                &conn,
+                &db::GithubDaily {
+                    date: date(day),
+                    repo: "nextest-rs/nextest".to_string(),
+                    release_tag: tag.to_string(),
+                    asset_name: "a.tar.gz".to_string(),
+                    days: 1,
+                    downloads,
+                    raw_delta: 0,
+                },
+            )
+            .unwrap()
+        };
+        github(2, "cargo-nextest-0.9.2", 500);
+        github(9, "cargo-nextest-0.9.1", 10);
+        github(9, "not-a-version", 10);
+
+        let versions = version_downloads(&conn, "all", 7).unwrap();
+        let summary: Vec<_> = versions
+            .iter()
+            .map(|v| (v.version.to_string(), v.crates, v.github))
+            .collect();
+        // Ties go to the newer version.
+        assert_eq!(
+            summary,
+            [("0.9.2".to_string(), 40, 0), ("0.9.1".to_string(), 30, 10),]
+        );
+        assert_eq!(version_downloads(&conn, "github", 7).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_version_growth() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        let crate_download = |day, version, downloads| {
+            db::insert_crates_download(&conn, date(day), "cargo-nextest", Some(version), downloads)
+                .unwrap()
+        };
+        // Weeks start on Mondays: Nov 3 and 10. Nov 17 isn't complete.
+        crate_download(3, "0.8.5", 50);
+        crate_download(4, "0.9.1", 100);
+        crate_download(12, "0.9.1", 30);
+        crate_download(13, "0.9.2", 80);
+        crate_download(16, "0.8.5", 20);
+        crate_download(17, "0.9.2", 1000);
+        db::insert_weekly_stat(&conn, date(3), "crates", "cargo-nextest", 150).unwrap();
+        db::insert_weekly_stat(&conn, date(10), "crates", "cargo-nextest", 130).unwrap();
+        db::insert_weekly_stat(&conn, date(17), "crates", "cargo-nextest", 1000).unwrap();
+
+        let growth = version_growth(&conn, "crates", 1, false).unwrap().unwrap();
+        assert_eq!(growth.recent.since, Some(date(10)));
+        assert_eq!(growth.recent.until, Some(date(16)));
+        assert_eq!(growth.previous.since, Some(date(3)));
+        let summary: Vec<_> = growth
+            .versions
+            .iter()
+            .map(|v| (v.version.as_str(), v.recent, v.previous))
+            .collect();
+        assert_eq!(
+            summary,
+            [("0.9.2", 80, 0), ("0.8.5", 20, 50), ("0.9.1", 30, 100)]
+        );
+
+        let lines = version_growth(&conn, "crates", 1, true).unwrap().unwrap();
+        let summary: Vec<_> = lines
+            .versions
+            .iter()
+            .map(|v| (v.version.as_str(), v.change()))
+            .collect();
+        assert_eq!(summary, [("0.9", 10), ("0.8", -30)]);
+
+        // A version with fewer downloads that's growing comes before a more
+        // downloaded one that's fading, and equal growth goes to the one with
+        // more downloads.
+        crate_download(11, "0.9.3", 60);
+        crate_download(11, "0.9.4", 40);
+        crate_download(4, "0.9.4", 10);
+        crate_download(6, "0.9.1", 200);
+        crate_download(14, "0.9.1", 300);
+        let growth = version_growth(&conn, "crates", 1, false).unwrap().unwrap();
+        let summary: Vec<_> = growth
+            .versions
+            .iter()
+            .map(|v| (v.version.as_str(), v.change()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("0.9.2", 80),
+                ("0.9.3", 60),
+                ("0.9.1", 30),
+                ("0.9.4", 30),
+                ("0.8.5", -30)
+            ]
+        );
+
+        assert!(version_growth(&conn, "crates", MAX_GROWTH_WEEKS, false).is_ok());
+        assert!(version_growth(&conn, "crates", MAX_GROWTH_WEEKS + 1, false).is_err());
+    }
+
+    #[test]
+    fn test_platform_downloads() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        let github = |day, asset: &str, downloads| {
+            db::insert_github_daily(
+                &conn,
+                &db::GithubDaily {
+                    date: date(day),
+                    repo: "nextest-rs/nextest".to_string(),
+                    release_tag: "cargo-nextest-0.9.1".to_string(),
+                    asset_name: asset.to_string(),
+                    days: 1,
+                    downloads,
+                    raw_delta: 0,
+                },
+            )
+            .unwrap()
+        };
+        let linux = "cargo-nextest-0.9.1-x86_64-unknown-linux-gnu.tar.gz";
+        github(1, linux, 1000);
+        github(9, linux, 30);
+        github(
+            9,
+            "cargo-nextest-0.9.1-x86_64-unknown-linux-gnu.tar.gz.sha256",
+            30,
+        );
+        github(
+            9,
+            "cargo-nextest-0.9.1-aarch64-unknown-linux-gnu.tar.gz",
+            20,
+        );
+        github(10, "cargo-nextest-0.9.1-x86_64-pc-windows-msvc.zip", 20);
+        github(10, "install.sh", 5);
+
+        let range = DateRange::new(Some(date(2)), None).unwrap();
+        let summary = |by| -> Vec<(String, u64)> {
+            platform_downloads(&conn, by, range)
+                .unwrap()
+                .into_iter()
+                .map(|group| (group.group, group.downloads))
+                .collect()
+        };
+        assert_eq!(
+            summary(PlatformGroup::Os),
+            [
+                ("linux".to_string(), 50),
+                ("windows".to_string(), 20),
+                ("other".to_string(), 5),
+            ]
+        );
+        // Ties are broken by name.
+        assert_eq!(
+            summary(PlatformGroup::Target)[..2],
+            [
+                ("x86_64-unknown-linux-gnu".to_string(), 30),
+                ("aarch64-unknown-linux-gnu".to_string(), 20),
+            ]
+        );
+        assert_eq!(summary(PlatformGroup::Arch)[0], ("x86_64".to_string(), 50));
+    }
+
+    #[test]
+    fn test_group_version_shares() {
+        let week = |versions: &[(&str, u64)]| -> BTreeMap<String, u64> {
+            versions.iter().map(|(v, d)| (v.to_string(), *d)).collect()
+        };
+        let weeks: BTreeMap<NaiveDate, BTreeMap<String, u64>> = [
+            (date(3), week(&[("0.8", 30), ("0.9", 70)])),
+            (date(10), week(&[("0.8", 10), ("0.9", 40), ("0.10", 50)])),
+            (date(17), week(&[("0.9", 0)])),
+        ]
+        .into_iter()
+        .collect();
+
+        let share = group_version_shares(weeks.clone(), 5);
+        assert_eq!(share.versions, ["0.10", "0.9", "0.8"]);
+        assert_eq!(
+            share.weeks,
+            [
+                (date(3), vec![0.0, 0.7, 0.3]),
+                (date(10), vec![0.5, 0.4, 0.1]),
+            ]
+        );
+
+        let share = group_version_shares(weeks, 1);
+        assert_eq!(share.versions, ["0.10", OLDER_VERSIONS]);
+        assert_eq!(share.weeks[1], (date(10), vec![0.5, 0.5]));
+    }
+
+    #[test]
+    fn test_rate_of_change() {
+        let snapshot = |day, total| SnapshotTotal {
+            date: date(day),
+            collected_at: None,
+            total,
+        };
+        let totals = [
+            snapshot(1, 100),
+            snapshot(2, 110),
+            snapshot(5, 140),
+            snapshot(6, 135),
+        ];
+        let rates = rate_of_change(&totals, 2);
+
+        assert_eq!(rates.len(), 3);
+        assert_eq!(rates[0].per_day, 10.0);
+        assert_eq!(rates[0].smoothed_per_day, 10.0);
+        assert_eq!(rates[1].days, 3.0);
+        assert_eq!(rates[1].per_day, 10.0);
+        // (10 + 30) downloads over 4 days.
+        assert_eq!(rates[1].smoothed_per_day, 10.0);
+        // Decreases are clamped to zero.
+        assert_eq!(rates[2].downloads, 0);
+        // (30 + 0) downloads over 4 days.
+        assert_eq!(rates[2].smoothed_per_day, 7.5);
+
+        // With timestamps, a snapshot taken late in the day covers more than
+        // a day since the previous one.
+        let at = |day, hour| Some(date(day).and_hms_opt(hour, 0, 0).unwrap().and_utc());
+        let totals = [
+            SnapshotTotal {
+                collected_at: at(1, 0),
+                ..snapshot(1, 100)
+            },
+            SnapshotTotal {
+                collected_at: at(2, 12),
+                ..snapshot(2, 115)
+            },
+            // Without a timestamp, the interval falls back to whole days.
+            snapshot(3, 125),
+        ];
+        let rates = rate_of_change(&totals, 1);
+        assert_eq!(rates[0].days, 1.5);
+        assert_eq!(rates[0].per_day, 10.0);
+        assert_eq!(rates[1].days, 1.0);
+    }
+
+    #[test]
+    fn test_source_totals() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        db::insert_crates_download(&conn, date(3), "cargo-nextest", None, 100).unwrap();
+        db::insert_crates_download(&conn, date(10), "cargo-nextest", None, 40).unwrap();
+        db::insert_github_daily(
+            &conn,
+            &db::GithubDaily {
+                date: date(10),
+                repo: "nextest-rs/nextest".to_string(),
+                release_tag: "cargo-nextest-0.9.1".to_string(),
+                asset_name: "a.tar.gz".to_string(),
+                days: 1,
+                downloads: 7,
+                raw_delta: 0,
+            },
+        )
+        .unwrap();
+
+        let range = DateRange::new(Some(date(4)), Some(date(10))).unwrap();
+        let totals: Vec<_> = source_totals(&conn, range)
+            .unwrap()
+            .into_iter()
+            .map(|total| (total.source, total.downloads, total.first_date))
+            .collect();
+        assert_eq!(
+            totals,
+            [
+                ("github", 7, Some(date(10))),
+                ("crates", 40, Some(date(3))),
+                ("installer", 0, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collection_gaps() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        // Weeks start on Mondays: Nov 3, 10, and 17.
+        for day in (3..=9).chain([12, 13, 17, 18]) {
+            db::insert_crates_download(&conn, date(day), "cargo-nextest", None, 10).unwrap();
+        }
+
+        let gaps = collection_gaps(&conn, date(19)).unwrap();
+        assert_eq!(gaps.len(), 1);
+        let crates = &gaps[0];
+        assert_eq!(crates.source, "crates");
+        assert_eq!(
+            (crates.first, crates.latest, crates.age_days),
+            (date(3), date(18), 1)
+        );
+        assert_eq!(crates.missing, [(date(10), date(11)), (date(14), date(16))]);
+        assert_eq!(crates.missing_days(), 5);
+        // The week of Nov 17 is still in progress.
+        assert_eq!(crates.incomplete_weeks, [(date(10), 2)]);
+    }
+
+    #[test]
+    fn test_summarize_weeks() {
+        assert_eq!(summarize_weeks(&[]), None);
+
+        let downloads = [10, 20, 30, 5, 10, 10, 40, 50, 60, 5];
+        let weeks: Vec<(NaiveDate, u64)> = downloads
+            .iter()
+            .enumerate()
+            .map(|(idx, downloads)| (date(3) + chrono::Days::new(idx as u64 * 7), *downloads))
+            .collect();
+        let week = |idx: usize| weeks[idx].0;
+        let summary = summarize_weeks(&weeks).unwrap();
+        assert_eq!(summary.weeks, 10);
+        assert_eq!((summary.first_week, summary.last_week), (week(0), week(9)));
+        assert_eq!(summary.total, 240);
+        assert_eq!(summary.min, (week(9), 5));
+        assert_eq!(summary.max, (week(8), 60));
+        assert_eq!(summary.mean, 24.0);
+        assert_eq!(summary.median, 15.0);
+        assert_eq!(summary.p90, 51.0);
+        // 10 -> 40 -> 50 -> 60 beats 10 -> 20 -> 30, and a flat week ends a
+        // streak.
+        assert_eq!(summary.growth_streak, Some((week(5), week(8), 3)));
+    }
+
+    #[test]
+    fn test_growth_helpers() {
+        assert_eq!(percent_change(150, Some(100)), Some(50.0));
+        assert_eq!(percent_change(50, Some(100)), Some(-50.0));
+        assert_eq!(percent_change(50, Some(0)), None);
+        assert_eq!(percent_change(50, None), None);
+
+        // Weeks starting Nov 3, 10, and 17; data runs through Sunday Nov 16.
+        let totals: BTreeMap<NaiveDate, u64> = [(date(3), 1), (date(10), 2), (date(17), 3)]
+            .into_iter()
+            .collect();
+        let week_after = |start: NaiveDate| start + chrono::Duration::days(7);
+        assert_eq!(
+            latest_complete(&totals, date(16), week_after),
+            Some(date(10))
+        );
+        assert_eq!(
+            latest_complete(&totals, date(15), week_after),
+            Some(date(3))
+        );
+        assert_eq!(
+            latest_complete(&totals, date(23), week_after),
+            Some(date(17))
+        );
+    }
+
+    #[test]
+    fn test_find_anomalies() {
+        let values = [100, 110, 90, 100, 105, 95, 400, 100, 20];
+        let series: Vec<(NaiveDate, u64)> = values
+            .iter()
+            .enumerate()
+            .map(|(idx, &n)| (date(idx as u32 + 1), n))
+            .collect();
+
+        let anomalies = find_anomalies(&series, 4, 3.0);
+        let flagged: Vec<usize> = anomalies.iter().map(|a| a.0).collect();
+        // The spike is flagged, but it inflates the variance enough that the
+        // later drop isn't.
+        assert_eq!(flagged, [6]);
+        assert!(anomalies[0].3 > 3.0);
+
+        let anomalies = find_anomalies(&series[..6], 4, 3.0);
+        assert!(anomalies.is_empty());
+
+        // A perfectly flat history has no variance, so nothing is flagged.
+        let flat: Vec<(NaiveDate, u64)> = (1..=5).map(|day| (date(day), 100)).collect();
+        assert!(find_anomalies(&flat, 4, 3.0).is_empty());
+    }
+
+    #[test]
+    fn test_diversity() {
+        assert_eq!(diversity(&[0, 0], 0.01), None);
+
+        // A single version has zero entropy.
+        assert_eq!(diversity(&[100, 0], 0.01), Some((1, 1, 0.0)));
+
+        // Four equal versions have two bits of entropy.
+        let (versions, active, entropy) = diversity(&[25, 25, 25, 25], 0.01).unwrap();
+        assert_eq!((versions, active), (4, 4));
+        assert!((entropy - 2.0).abs() < 1e-9);
+
+        // Versions below the minimum share aren't active.
+        let (versions, active, _) = diversity(&[990, 5, 5], 0.01).unwrap();
+        assert_eq!((versions, active), (3, 1));
+    }
+
+    #[test]
+    fn test_target_matrix() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        let linux = "x86_64-unknown-linux-gnu";
+        let mac = "universal-apple-darwin";
+        let windows = "x86_64-pc-windows-msvc";
+        let releases: &[(&str, &[(&str, &str)])] = &[
+            (
+                "0.9.1",
+                &[
+                    (linux, "tar.gz"),
+                    (linux, "tar.gz.sha256"),
+                    (windows, "zip"),
+                ],
+            ),
+            ("0.9.2", &[(linux, "tar.gz"), (mac, "tar.gz")]),
+            // Pre-releases don't count as dropping Windows.
+            ("0.9.3-rc.1", &[(linux, "tar.gz")]),
+            ("0.9.3", &[(linux, "tar.gz"), (mac, "tar.gz")]),
+        ];
+        for (day, count) in [(1, 100), (2, 150)] {
+            for (version, assets) in releases {
+                let tag = format!("cargo-nextest-{}", version);
+                for (target, extension) in *assets {
+                    let asset = format!("cargo-nextest-{}-{}.{}", version, target, extension);
+                    db::insert_github_snapshot(
+                        &conn,
+                        date(day),
+                        Some(Utc::now()),
+                        "nextest-rs/nextest",
+                        &tag,
+                        &asset,
+                        count,
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        aggregate::compute_github_daily(&conn, &Default::default()).unwrap();
+        aggregate::compute_release_targets(&conn).unwrap();
+
+        let matrix = target_matrix(&conn).unwrap();
+        let summary: Vec<_> = matrix
+            .targets
+            .iter()
+            .map(|t| {
+                (
+                    t.target.as_str(),
+                    t.added_in.as_str(),
+                    t.dropped_in.as_deref(),
+                    t.releases,
+                    t.downloads,
+                    t.recent_downloads,
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (linux, "0.9.1", None, 3, 600, 200),
+                (mac, "0.9.2", None, 2, 300, 100),
+                (windows, "0.9.1", Some("0.9.2"), 1, 150, 50),
+            ]
+        );
+        assert_eq!(
+            matrix.changes,
+            [TargetChange {
+                version: "0.9.2".to_string(),
+                published_on: None,
+                added: vec![mac.to_string()],
+                dropped: vec![windows.to_string()],
+            }]
+        );
+    }
+}
@@ -0,0 +1,500 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Archival of old raw data into per-year databases.
+//!
+//! Raw snapshot and download rows from whole years before a cutoff are moved
+//! into `<database stem>-<year>.db` next to the main database, keeping the
+//! main database small. Archives are recorded in the `archives` table and
+//! attached whenever the database is opened.
+//!
+//! While archives are attached, each archived table is shadowed by a
+//! temporary view of the same name combining the main table with its
+//! archived rows, and the main database's views are recreated as temporary
+//! views on top of them. Reads therefore see the full history, so aggregates
+//! are unchanged by archiving. Writes to archived tables must name
+//! `main.<table>` explicitly, since views can't be written to.
+//!
+//! Tools opening the main database on its own only see unarchived rows.
+
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{Connection, params};
+use std::collections::{BTreeMap, HashSet};
+
+/// Tables whose rows are moved into archives, each keyed by a `date` column.
+pub const ARCHIVED_TABLES: [&str; 4] = [
+    "github_snapshots",
+    "crates_downloads",
+    "crates_metadata",
+    "installer_downloads",
+];
+
+/// Rows moved into (or, for a dry run, due to move into) one year's archive.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ArchivedYear {
+    pub year: i32,
+    pub path: Utf8PathBuf,
+    /// Rows per archived table, in the order of [`ARCHIVED_TABLES`].
+    pub rows: Vec<(&'static str, usize)>,
+}
+
+/// The first day of the earliest year that is kept in the main database when
+/// archiving rows older than `years` years as of `today`.
+///
+/// Only whole years are archived, so an archive stops changing once written.
+pub fn cutoff(today: NaiveDate, years: u32) -> NaiveDate {
+    let year = today.year() - years as i32;
+    NaiveDate::from_ymd_opt(year, 1, 1).expect("January 1st is a valid date")
+}
+
+/// Attach the archives recorded in the `archives` table and create the
+/// unified views over them. Does nothing if there are no archives.
+///
+/// Archives that are missing are left out with a warning, so the database can
+/// still be opened and collected into, but their rows aren't counted.
+pub fn attach_archives(conn: &Connection, db_path: &Utf8Path) -> Result<()> {
+    let mut stmt = conn.prepare("SELECT year, path FROM main.archives ORDER BY year")?;
+    let archives = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, i32>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read archives")?;
+    if archives.is_empty() {
+        return Ok(());
+    }
+
+    let attached = attached_schemas(conn)?;
+    let mut schemas = Vec::new();
+    for (year, file) in archives {
+        let schema = schema_name(year);
+        if !attached.contains(&schema) {
+            let path = archive_dir(db_path).join(&file);
+            if !path.exists() {
+                eprintln!(
+                    "Warning: archive for {} is missing at {}; its rows are left out",
+                    year, path
+                );
+                continue;
+            }
+            attach(conn, &path, &schema)?;
+        }
+        schemas.push(schema);
+    }
+
+    create_unified_views(conn, &schemas)
+}
+
+/// Move rows dated before `cutoff` from the main database into per-year
+/// archives, creating and attaching archives as needed.
+///
+/// With `dry_run`, nothing is changed and the rows that would be moved are
+/// returned.
+pub fn archive_before(
+    conn: &Connection,
+    db_path: &Utf8Path,
+    cutoff: NaiveDate,
+    dry_run: bool,
+) -> Result<Vec<ArchivedYear>> {
+    let cutoff = cutoff.to_string();
+    let mut years: BTreeMap<i32, Vec<(&'static str, usize)>> = BTreeMap::new();
+    for table in ARCHIVED_TABLES {
+        let mut stmt = conn.prepare(&format!(
+            "SELECT CAST(substr(date, 1, 4) AS INTEGER), COUNT(*) FROM main.{table}
+             WHERE date < ?1
+             GROUP BY 1"
+        ))?;
+        let counts = stmt
+            .query_map([&cutoff], |row| Ok((row.get::<_, i32>(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to count rows to archive in {}", table))?;
+        for (year, count) in counts {
+            years.entry(year).or_default().push((table, count));
+        }
+    }
+
+    let stem = db_path.file_stem().unwrap_or("download-stats");
+    let archived: Vec<ArchivedYear> = years
+        .into_iter()
+        .map(|(year, rows)| ArchivedYear {
+            year,
+            path: archive_dir(db_path).join(format!("{}-{}.db", stem, year)),
+            rows,
+        })
+        .collect();
+    if dry_run || archived.is_empty() {
+        return Ok(archived);
+    }
+
+    // ATTACH isn't allowed inside a transaction, so set up every archive
+    // first.
+    let attached = attached_schemas(conn)?;
+    for year in &archived {
+        let schema = schema_name(year.year);
+        if !attached.contains(&schema) {
+            attach(conn, &year.path, &schema)?;
+        }
+        for (table, _) in &year.rows {
+            create_archive_table(conn, &schema, table)?;
+        }
+    }
+
+    // In WAL mode, a transaction spanning several databases is atomic per
+    // database only, so rows are copied and committed to the archives before
+    // they're removed from the main database. An interrupted run leaves rows
+    // in both, which the unified views only count once, and can simply be
+    // repeated: rows are copied with INSERT OR IGNORE.
+    let range = |year: i32| {
+        let start = format!("{}-01-01", year);
+        let end = format!("{}-01-01", year + 1).min(cutoff.clone());
+        (start, end)
+    };
+    let tx = conn.unchecked_transaction()?;
+    for year in &archived {
+        let schema = schema_name(year.year);
+        let (start, end) = range(year.year);
+        for (table, _) in &year.rows {
+            let columns = table_columns(&tx, "main", table)?.join(", ");
+            tx.execute(
+                &format!(
+                    "INSERT OR IGNORE INTO {schema}.{table} ({columns})
+                     SELECT {columns} FROM main.{table} WHERE date >= ?1 AND date < ?2"
+                ),
+                [&start, &end],
+            )
+            .with_context(|| format!("failed to copy {} rows to {}", table, year.path))?;
+        }
+    }
+    tx.commit().context("failed to commit archived rows")?;
+
+    let tx = conn.unchecked_transaction()?;
+    for year in &archived {
+        let (start, end) = range(year.year);
+        for (table, _) in &year.rows {
+            tx.execute(
+                &format!("DELETE FROM main.{table} WHERE date >= ?1 AND date < ?2"),
+                [&start, &end],
+            )
+            .with_context(|| format!("failed to remove archived {} rows", table))?;
+        }
+        let file = year.path.file_name().unwrap_or(year.path.as_str());
+        tx.execute(
+            "INSERT OR REPLACE INTO main.archives (year, path) VALUES (?1, ?2)",
+            params![year.year, file],
+        )
+        .context("failed to record archive")?;
+    }
+    tx.commit().context("failed to commit archival")?;
+
+    // Give the space back so the main database actually shrinks.
+    conn.execute_batch("VACUUM main")
+        .context("failed to vacuum database")?;
+
+    attach_archives(conn, db_path)?;
+    Ok(archived)
+}
+
+/// The attach name of the archive for `year`.
+fn schema_name(year: i32) -> String {
+    format!("archive_{}", year)
+}
+
+/// Archives are stored alongside the main database.
+fn archive_dir(db_path: &Utf8Path) -> &Utf8Path {
+    match db_path.parent() {
+        Some(dir) if !dir.as_str().is_empty() => dir,
+        _ => Utf8Path::new("."),
+    }
+}
+
+/// The attach names of the archives attached to `conn` that hold rows of
+/// `table`, oldest first.
+pub fn archives_with(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut archives = Vec::new();
+    for schema in attached_schemas(conn)? {
+        if schema.starts_with("archive_") && !table_columns(conn, &schema, table)?.is_empty() {
+            archives.push(schema);
+        }
+    }
+    archives.sort();
+    Ok(archives)
+}
+
+fn attached_schemas(conn: &Connection) -> Result<HashSet<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_database_list")?;
+    let names = stmt
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<HashSet<_>, _>>()?;
+    Ok(names)
+}
+
+fn attach(conn: &Connection, path: &Utf8Path, schema: &str) -> Result<()> {
+    conn.execute("ATTACH DATABASE ?1 AS ?2", params![path.as_str(), schema])
+        .with_context(|| format!("failed to attach archive at {}", path))?;
+    Ok(())
+}
+
+/// Column names of `schema.table`, empty if it doesn't exist.
+pub(crate) fn table_columns(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1, ?2) ORDER BY cid")?;
+    let columns = stmt
+        .query_map([table, schema], |row| row.get(0))?
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("failed to inspect table {}.{}", schema, table))?;
+    Ok(columns)
+}
+
+/// Create `table` in an archive with the main table's definition, or add
+/// columns the main table gained since the archive was created.
+fn create_archive_table(conn: &Connection, schema: &str, table: &str) -> Result<()> {
+    let existing = table_columns(conn, schema, table)?;
+    if existing.is_empty() {
+        let sql: String = conn
+            .query_row(
+                "SELECT sql FROM main.sqlite_master WHERE type = 'table' AND name = ?1",
+                [table],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("failed to read the definition of {}", table))?;
+        let Some(rest) = sql.strip_prefix(&format!("CREATE TABLE {table}")) else {
+            bail!("unexpected definition of {}: {}", table, sql);
+        };
+        conn.execute_batch(&format!("CREATE TABLE {schema}.{table}{rest}"))
+            .with_context(|| format!("failed to create {}.{}", schema, table))?;
+        return Ok(());
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT name, type, \"notnull\", dflt_value FROM pragma_table_info(?1, 'main')
+         ORDER BY cid",
+    )?;
+    let columns = stmt
+        .query_map([table], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (name, ty, not_null, default) in columns {
+        if existing.contains(&name) {
+            continue;
+        }
+        let mut definition = ty;
+        if not_null {
+            definition.push_str(" NOT NULL");
+        }
+        if let Some(default) = default {
+            definition.push_str(&format!(" DEFAULT {}", default));
+        }
+        conn.execute_batch(&format!(
+            "ALTER TABLE {schema}.{table} ADD COLUMN {name} {definition}"
+        ))
+        .with_context(|| format!("failed to add column {}.{}.{}", schema, table, name))?;
+    }
+    Ok(())
+}
+
+/// Shadow each archived table with a temporary view over it and its archived
+/// rows, and recreate the main database's views as temporary views so that
+/// they read through the shadows.
+fn create_unified_views(conn: &Connection, schemas: &[String]) -> Result<()> {
+    for table in ARCHIVED_TABLES {
+        let mut stmt = conn
+            .prepare("SELECT name, dflt_value FROM pragma_table_info(?1, 'main') ORDER BY cid")?;
+        let defaults = stmt
+            .query_map([table], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to inspect table main.{}", table))?;
+        let columns: Vec<&str> = defaults.iter().map(|(column, _)| column.as_str()).collect();
+        let mut selects = vec![format!("SELECT {} FROM main.{table}", columns.join(", "))];
+        for schema in schemas {
+            let archived = table_columns(conn, schema, table)?;
+            if archived.is_empty() {
+                continue;
+            }
+            // Archives written before a column was added don't have it, so
+            // their rows get its default.
+            let list: Vec<String> = defaults
+                .iter()
+                .map(|(column, default)| match archived.contains(column) {
+                    true => column.clone(),
+                    false => format!("{} AS {}", default.as_deref().unwrap_or("NULL"), column),
+                })
+                .collect();
+            // Rows of a date that's also in the main database, left by an
+            // interrupted archival or collected again since, are counted
+            // from the main database only.
+            selects.push(format!(
+                "SELECT {} FROM {schema}.{table} a
+                 WHERE NOT EXISTS (SELECT 1 FROM main.{table} m WHERE m.date = a.date)",
+                list.join(", ")
+            ));
+        }
+        conn.execute_batch(&format!(
+            "DROP VIEW IF EXISTS temp.{table};
+             CREATE TEMP VIEW {table} AS {};",
+            selects.join(" UNION ALL ")
+        ))
+        .with_context(|| format!("failed to create unified view of {}", table))?;
+    }
+
+    // Views in the main database can only see main tables, so recreate them
+    // in the temp schema, where names resolve to the shadows first. Creation
+    // order keeps views that depend on other views working.
+    let mut stmt = conn
+        .prepare("SELECT name, sql FROM main.sqlite_master WHERE type = 'view' ORDER BY rowid")?;
+    let views = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    for (name, sql) in views {
+        let Some(rest) = sql.strip_prefix("CREATE VIEW ") else {
+            bail!("unexpected definition of view {}: {}", name, sql);
+        };
+        conn.execute_batch(&format!(
+            "DROP VIEW IF EXISTS temp.{name};
+             CREATE TEMP VIEW {rest};"
+        ))
+        .with_context(|| format!("failed to create unified view {}", name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn test_cutoff() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        assert_eq!(cutoff(today, 0).to_string(), "2026-01-01");
+        assert_eq!(cutoff(today, 2).to_string(), "2024-01-01");
+    }
+
+    #[test]
+    fn test_archive_before() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-archive-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("stats.db");
+
+        let conn = db::init_db(&db_path).unwrap();
+        let date = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap();
+        for (day, count) in [("2023-06-01", 10), ("2024-12-31", 20), ("2025-01-01", 30)] {
+            let collected_at = date(day).and_hms_opt(12, 0, 0).unwrap().and_utc();
+            db::insert_github_snapshot(
+                &conn,
+                date(day),
+                Some(collected_at),
+                "nextest-rs/nextest",
+                "v1",
+                "a.tar.gz",
+                count,
+            )
+            .unwrap();
+        }
+        db::insert_installer_download(&conn, date("2024-03-01"), "get", 5).unwrap();
+        db::insert_crates_download(&conn, date("2024-03-01"), "foo", Some("100"), 7).unwrap();
+
+        let cutoff = date("2025-01-01");
+        let planned = archive_before(&conn, &db_path, cutoff, true).unwrap();
+        assert_eq!(
+            planned
+                .iter()
+                .map(|year| (year.year, year.rows.clone()))
+                .collect::<Vec<_>>(),
+            [
+                (2023, vec![("github_snapshots", 1)]),
+                (
+                    2024,
+                    vec![
+                        ("github_snapshots", 1),
+                        ("crates_downloads", 1),
+                        ("installer_downloads", 1)
+                    ]
+                ),
+            ]
+        );
+        assert!(!planned[0].path.exists());
+
+        archive_before(&conn, &db_path, cutoff, false).unwrap();
+        assert!(dir.join("stats-2023.db").exists());
+        let count = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM main.github_snapshots"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM github_snapshots"), 3);
+        assert_eq!(count("SELECT COUNT(*) FROM github_cumulative"), 3);
+
+        // A day in both the archive and the main database, as an interrupted
+        // archival leaves it, is counted once, from the main database.
+        db::insert_installer_download(&conn, date("2024-03-01"), "get", 6).unwrap();
+        assert_eq!(count("SELECT SUM(downloads) FROM installer_downloads"), 6);
+
+        // Version IDs are rewritten in archives too.
+        let version: crate::crates_io::CrateVersion = serde_json::from_value(serde_json::json!(
+            {"id": 100, "num": "1.0.0", "created_at": "2024-01-01T00:00:00Z"}
+        ))
+        .unwrap();
+        db::update_crate_versions(&conn, date("2025-01-01"), "foo", &[version]).unwrap();
+        assert_eq!(
+            db::reattribute_crate_versions(&conn, false).unwrap(),
+            db::Reattribution {
+                updated: 1,
+                duplicates: 0
+            }
+        );
+        assert_eq!(
+            count("SELECT COUNT(*) FROM archive_2024.crates_downloads WHERE version = '1.0.0'"),
+            1
+        );
+        drop(conn);
+
+        // Archives are attached again on open, and new rows go to the main
+        // database.
+        let conn = db::init_db(&db_path).unwrap();
+        let collected_at = date("2025-01-02").and_hms_opt(12, 0, 0).unwrap().and_utc();
+        db::insert_github_snapshot(
+            &conn,
+            date("2025-01-02"),
+            Some(collected_at),
+            "nextest-rs/nextest",
+            "v1",
+            "a.tar.gz",
+            40,
+        )
+        .unwrap();
+        let total: i64 = conn
+            .query_row(
+                "SELECT SUM(download_count) FROM github_snapshots",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(total, 100);
+        drop(conn);
+
+        // A missing archive's rows are left out rather than failing.
+        std::fs::remove_file(dir.join("stats-2023.db")).unwrap();
+        let conn = db::init_db(&db_path).unwrap();
+        let total: i64 = conn
+            .query_row(
+                "SELECT SUM(download_count) FROM github_snapshots",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(total, 90);
+        drop(conn);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
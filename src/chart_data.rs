@@ -0,0 +1,202 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Data sidecars for charts.
+//!
+//! With `data = true` on a chart output, each chart is written along with a
+//! `.json` file holding the series it was drawn from, so that other renderers
+//! (such as interactive charts on the website) show the same numbers as the
+//! images. Each sidecar records the provenance of the chart too, so that its
+//! numbers can be traced to the tool commit and data they came from.
+//!
+//! The format is versioned by [`SCHEMA_VERSION`]. Adding fields doesn't change
+//! the version; removing, renaming, or changing the meaning of one does.
+
+use crate::{config::ChartResolution, provenance::Provenance};
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use chrono::NaiveDate;
+use serde::Serialize;
+
+/// The version of the sidecar format.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The data a chart was drawn from.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ChartData {
+    pub title: String,
+    pub layout: Layout,
+    pub unit: Unit,
+    /// The period each point covers, for charts of downloads per period.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<ChartResolution>,
+    /// Whether the y axis is logarithmic.
+    pub log_scale: bool,
+    pub series: Vec<DataSeries>,
+    /// Releases marked on the chart.
+    pub markers: Vec<DataMarker>,
+}
+
+/// How a chart's series are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// A line per series.
+    Lines,
+    /// An area per series, each filled down to zero and overlapping.
+    Areas,
+    /// Areas stacked in order, the first series at the bottom.
+    Stacked,
+    /// Days in a grid of weeks by weekday for each year, shaded by value.
+    Calendar,
+    /// A line per calendar year, overlaid on a January–December axis. Points
+    /// keep their actual dates.
+    Years,
+    /// A horizontal bar per series, ranked from the top, each series holding a
+    /// single point.
+    Bars,
+}
+
+/// What a chart's values measure.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Unit {
+    /// Downloads per period, or cumulative downloads.
+    Downloads,
+    DownloadsPerDay,
+    /// A number of versions.
+    Versions,
+    /// A fraction, such as 0.25 for 25%.
+    Ratio,
+    Number,
+}
+
+/// A named series of values.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct DataSeries {
+    pub label: String,
+    /// The color the series is drawn in, as `#rrggbb`.
+    pub color: String,
+    /// `[date, value]` pairs, ordered by date.
+    pub points: Vec<(NaiveDate, f64)>,
+}
+
+/// A release marked on a chart.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DataMarker {
+    pub date: NaiveDate,
+    pub label: String,
+}
+
+impl ChartData {
+    pub fn new(title: impl Into<String>, layout: Layout, unit: Unit) -> Self {
+        Self {
+            title: title.into(),
+            layout,
+            unit,
+            resolution: None,
+            log_scale: false,
+            series: Vec::new(),
+            markers: Vec::new(),
+        }
+    }
+
+    /// Add a series.
+    pub fn push(
+        &mut self,
+        label: impl Into<String>,
+        (r, g, b): (u8, u8, u8),
+        points: impl IntoIterator<Item = (NaiveDate, f64)>,
+    ) {
+        self.series.push(DataSeries {
+            label: label.into(),
+            color: format!("#{:02x}{:02x}{:02x}", r, g, b),
+            points: points.into_iter().collect(),
+        });
+    }
+
+    /// Write the data for the chart named `chart` to `path`.
+    pub fn write(&self, chart: &str, provenance: &Provenance, path: &Utf8Path) -> Result<()> {
+        std::fs::write(path.as_std_path(), self.to_json(chart, provenance)?)
+            .with_context(|| format!("failed to write chart data to {}", path))
+    }
+
+    /// The sidecar for the chart named `chart`.
+    fn to_json(&self, chart: &str, provenance: &Provenance) -> Result<String> {
+        #[derive(Serialize)]
+        struct Sidecar<'a> {
+            schema_version: u32,
+            chart: &'a str,
+            #[serde(flatten)]
+            data: &'a ChartData,
+            provenance: &'a Provenance,
+        }
+
+        serde_json::to_string_pretty(&Sidecar {
+            schema_version: SCHEMA_VERSION,
+            chart,
+            data: self,
+            provenance,
+        })
+        .context("failed to serialize chart data")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sidecar_format() {
+        let date = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        let mut data = ChartData::new("Weekly Downloads", Layout::Lines, Unit::Downloads);
+        data.resolution = Some(ChartResolution::Weekly);
+        data.push(
+            "crates.io",
+            (255, 128, 0),
+            [(date(1), 10.0), (date(8), 12.5)],
+        );
+        data.markers.push(DataMarker {
+            date: date(3),
+            label: "0.9.70".to_string(),
+        });
+
+        let provenance = Provenance {
+            tool_version: "0.1.0".to_string(),
+            git_commit: Some("0123456789abcdef".to_string()),
+            config_hash: "cafe".to_string(),
+            data_watermark: [("crates".to_string(), date(8))].into(),
+            fingerprint: "f00d".to_string(),
+            generated_at: "2024-01-09T03:00:00+00:00".to_string(),
+        };
+
+        let written: serde_json::Value =
+            serde_json::from_str(&data.to_json("weekly-trends", &provenance).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            serde_json::json!({
+                "schema_version": 1,
+                "chart": "weekly-trends",
+                "title": "Weekly Downloads",
+                "layout": "lines",
+                "unit": "downloads",
+                "resolution": "weekly",
+                "log_scale": false,
+                "series": [{
+                    "label": "crates.io",
+                    "color": "#ff8000",
+                    "points": [["2024-01-01", 10.0], ["2024-01-08", 12.5]],
+                }],
+                "markers": [{"date": "2024-01-03", "label": "0.9.70"}],
+                "provenance": {
+                    "tool_version": "0.1.0",
+                    "git_commit": "0123456789abcdef",
+                    "config_hash": "cafe",
+                    "data_watermark": {"crates": "2024-01-08"},
+                    "fingerprint": "f00d",
+                    "generated_at": "2024-01-09T03:00:00+00:00",
+                },
+            })
+        );
+    }
+}
@@ -3,143 +3,814 @@
 
 //! Chart generation for download statistics visualization.
 
-use anyhow::{Context, Result};
-use camino::Utf8Path;
-use chrono::NaiveDate;
-use plotters::{coord::types::RangedCoordi64, prelude::*};
+use crate::{
+    aggregate, analysis,
+    chart_data::{ChartData, DataMarker, Layout, Unit},
+    config::{
+        ChartColor, ChartKind, ChartOutputConfig, ChartResolution, ChartSize, ChartTheme,
+        ChartsConfig, CustomChartConfig, HeadlineConfig, MetricFormat, ReleaseAnnotations,
+        TopAssetsBy,
+    },
+    db,
+    format::{self, Target},
+    headline,
+    metrics::{self, Metric, Metrics},
+    output::{self, status},
+    platform,
+    provenance::Provenance,
+    stats::{self, Measure, SeriesSpec},
+};
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{Datelike, NaiveDate, Utc, Weekday};
+use plotters::{
+    coord::ranged1d::{DefaultFormatting, ValueFormatter},
+    element::DashedPathElement,
+    prelude::*,
+    style::text_anchor::{HPos, Pos, VPos},
+};
 use rusqlite::Connection;
+use std::sync::Mutex;
+
+/// Everything that affects how charts look: the theme's background, text,
+/// grid, and axis colors, the `[charts]` settings shared by all themes, and
+/// the output's date range and resolution.
+#[derive(Debug)]
+struct Style {
+    background: RGBColor,
+    text_primary: RGBColor,
+    text_secondary: RGBColor,
+    grid: RGBColor,
+    axis: RGBColor,
+    width: u32,
+    height: u32,
+    sparkline_size: ChartSize,
+    /// The factor pixel lengths are multiplied by.
+    scale: u32,
+    font_family: String,
+    title_size: u32,
+    label_size: u32,
+    axis_size: u32,
+    margin: u32,
+    legend_margin: u32,
+    colors: Vec<RGBColor>,
+    other_color: RGBColor,
+    top_versions: usize,
+    top_platforms: usize,
+    top_assets: usize,
+    top_assets_by: TopAssetsBy,
+    version_min_share: f64,
+    version_min_downloads: u64,
+    release_annotations: ReleaseAnnotations,
+    moving_average: bool,
+    log_scale: bool,
+    since: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    resolution: ChartResolution,
+}
+
+impl Style {
+    fn new(charts: &ChartsConfig, output: &ChartOutputConfig, today: NaiveDate) -> Self {
+        let rgb = |color: &ChartColor| RGBColor(color.0, color.1, color.2);
+        let (background, text_primary, text_secondary, grid, axis) = match output.theme {
+            ChartTheme::Light => (
+                RGBColor(250, 250, 252), // Off-white.
+                RGBColor(15, 23, 42),    // Slate 900.
+                RGBColor(100, 116, 139), // Slate 500.
+                RGBColor(226, 232, 240), // Slate 200.
+                BLACK,
+            ),
+            ChartTheme::Dark => (
+                RGBColor(15, 23, 42),    // Slate 900.
+                RGBColor(241, 245, 249), // Slate 100.
+                RGBColor(148, 163, 184), // Slate 400.
+                RGBColor(51, 65, 85),    // Slate 700.
+                RGBColor(148, 163, 184), // Slate 400.
+            ),
+        };
+        let scale = output.scale;
+        let sparkline_size = output.size.unwrap_or(ChartSize::SPARKLINE);
+        Self {
+            background,
+            text_primary,
+            text_secondary,
+            grid,
+            axis,
+            width: scale * output.size.map_or(charts.width, |size| size.width),
+            height: scale * output.size.map_or(charts.height, |size| size.height),
+            sparkline_size: ChartSize {
+                width: scale * sparkline_size.width,
+                height: scale * sparkline_size.height,
+            },
+            scale,
+            font_family: charts.font_family.clone(),
+            title_size: scale * charts.title_size,
+            label_size: scale * charts.label_size,
+            axis_size: scale * charts.axis_size,
+            margin: scale * charts.margin,
+            legend_margin: scale * charts.legend_margin,
+            colors: charts.colors.iter().map(rgb).collect(),
+            other_color: rgb(&charts.other_color),
+            top_versions: charts.top_versions,
+            top_platforms: charts.top_platforms,
+            top_assets: charts.top_assets,
+            top_assets_by: charts.top_assets_by,
+            version_min_share: charts.version_min_share,
+            version_min_downloads: charts.version_min_downloads,
+            release_annotations: charts.release_annotations,
+            moving_average: charts.moving_average,
+            log_scale: charts.log_scale,
+            since: output.since.map(|since| since.resolve(today)),
+            until: output.until.map(|until| until.resolve(today)),
+            resolution: output.resolution,
+        }
+    }
+
+    /// Whether a date is within the output's date range.
+    fn in_range(&self, date: NaiveDate) -> bool {
+        self.since.is_none_or(|since| date >= since) && self.until.is_none_or(|until| date <= until)
+    }
+
+    /// Scale a length in pixels by the output's scale factor.
+    fn px(&self, pixels: u32) -> u32 {
+        pixels * self.scale
+    }
+
+    /// A legend swatch: a box `width` pixels wide, centered vertically on the
+    /// legend entry.
+    fn swatch(
+        &self,
+        (x, y): (i32, i32),
+        width: u32,
+        color: impl Into<ShapeStyle>,
+    ) -> Rectangle<(i32, i32)> {
+        let (width, half) = (self.px(width) as i32, self.px(5) as i32);
+        Rectangle::new([(x, y - half), (x + width, y + half)], color)
+    }
+
+    /// The color of the `idx`th series, cycling through the configured colors.
+    fn color(&self, idx: usize) -> RGBColor {
+        self.colors[idx % self.colors.len()]
+    }
+
+    /// The color of a stacked series, where the catch-all series (`other`)
+    /// gets its own color.
+    fn stack_color(&self, idx: usize, other: bool) -> RGBColor {
+        if other {
+            self.other_color
+        } else {
+            self.color(idx)
+        }
+    }
+
+    fn title_font(&self) -> TextStyle<'_> {
+        (self.font_family.as_str(), self.title_size)
+            .into_font()
+            .color(&self.text_primary)
+    }
+
+    fn label_font(&self) -> TextStyle<'_> {
+        (self.font_family.as_str(), self.label_size)
+            .into_font()
+            .color(&self.text_primary)
+    }
+
+    fn axis_font(&self) -> TextStyle<'_> {
+        (self.font_family.as_str(), self.axis_size)
+            .into_font()
+            .color(&self.text_secondary)
+    }
+}
 
-const CHART_WIDTH: u32 = 1600;
-const CHART_HEIGHT: u32 = 900;
-
-// Typography: Inter font family (must be installed on the system).
-const FONT_FAMILY: &str = "Inter";
-const TITLE_SIZE: i32 = 24;
-const LABEL_SIZE: i32 = 16;
-const AXIS_SIZE: i32 = 14;
-
-// Colors: modern, minimal palette.
-const BACKGROUND: RGBColor = RGBColor(250, 250, 252); // Off-white.
-const TEXT_PRIMARY: RGBColor = RGBColor(15, 23, 42); // Slate 900.
-const TEXT_SECONDARY: RGBColor = RGBColor(100, 116, 139); // Slate 500.
-const GRID_COLOR: RGBColor = RGBColor(226, 232, 240); // Slate 200.
-const ACCENT_BLUE: RGBColor = RGBColor(59, 130, 246); // Blue 500.
-const ACCENT_GREEN: RGBColor = RGBColor(34, 197, 94); // Green 500.
-
-/// Generate all charts from the database.
-pub fn generate_all_charts(conn: &Connection, output_dir: &Utf8Path) -> Result<()> {
+/// Generate the charts in a chart output from the database, styled by the
+/// `[charts]` settings, followed by a chart per derived metric.
+pub fn generate_charts(
+    conn: &Connection,
+    charts: &ChartsConfig,
+    metrics: &Metrics,
+    headline: &HeadlineConfig,
+    output: &ChartOutputConfig,
+    provenance: &Provenance,
+) -> Result<()> {
+    let style = &Style::new(charts, output, Utc::now().date_naive());
+    match (style.since, style.until) {
+        (Some(since), Some(until)) if since > until => bail!(
+            "chart output '{}' starts on {}, after it ends on {}",
+            output.name,
+            since,
+            until
+        ),
+        _ => {}
+    }
+    // The sparkline and badge have layouts of their own, so they fit any size.
+    let framed = output
+        .charts
+        .iter()
+        .any(|kind| !matches!(kind, ChartKind::Sparkline | ChartKind::DownloadsBadge));
+    if let Some(size) = output.size
+        && framed
+        && charts.margin.saturating_mul(2) >= size.width.min(size.height)
+    {
+        bail!(
+            "chart output '{}' is {}, which leaves no room inside charts.margin ({})",
+            output.name,
+            String::from(size),
+            charts.margin
+        );
+    }
+
+    prepare_font(charts)?;
+
+    let output_dir = &output.path;
     std::fs::create_dir_all(output_dir.as_std_path())
         .with_context(|| format!("failed to create output directory at {}", output_dir))?;
 
-    println!("\nGenerating charts...");
+    status!("\nGenerating {} charts...", output.name);
+
+    for &kind in &output.charts {
+        let path = output_dir.join(kind.file_name(output.format));
+        let data = match kind {
+            ChartKind::WeeklyTrends => generate_weekly_trends(conn, &path, style)?,
+            ChartKind::CumulativeTotal => generate_cumulative_github(conn, &path, style)?,
+            ChartKind::GithubByVersion => generate_github_by_version(conn, &path, style)?,
+            ChartKind::SourceComparison => generate_source_comparison(conn, &path, style)?,
+            ChartKind::GithubByPlatform => generate_github_by_platform(conn, &path, style)?,
+            ChartKind::MonthlyTrends => generate_monthly_trends(conn, &path, style)?,
+            ChartKind::DownloadRate => generate_download_rate(conn, &path, style)?,
+            ChartKind::VersionDiversity => generate_version_diversity(conn, &path, style)?,
+            ChartKind::VersionShare => generate_version_share(conn, &path, style)?,
+            ChartKind::ReleaseChannels => generate_release_channels(conn, &path, style)?,
+            ChartKind::ProjectTrends => generate_project_trends(conn, &path, style)?,
+            ChartKind::Verification => generate_verification(conn, &path, style)?,
+            ChartKind::CalendarHeatmap => generate_calendar_heatmap(conn, &path, style)?,
+            ChartKind::YearOverYear => generate_year_over_year(conn, &path, style)?,
+            ChartKind::TopAssets => generate_top_assets(conn, &path, style)?,
+            ChartKind::Sparkline => generate_sparkline(conn, &path, style)?,
+            ChartKind::DownloadsBadge => {
+                generate_downloads_badge(conn, headline, &path)?;
+                None
+            }
+        };
+        if let Some(data) = data.filter(|_| output.data) {
+            data.write(kind.as_str(), provenance, &path.with_extension("json"))?;
+        }
+    }
+
+    let selected: Vec<&Metric> = match &output.metrics {
+        Some(names) => names.iter().filter_map(|name| metrics.get(name)).collect(),
+        None => metrics.iter().collect(),
+    };
+    for metric in selected {
+        let path = output_dir.join(format!(
+            "metric-{}.{}",
+            metric.name,
+            output.format.extension()
+        ));
+        if let Some(data) = generate_metric(conn, metrics, metric, &path, style)?
+            && output.data
+        {
+            data.write(
+                &format!("metric-{}", metric.name),
+                provenance,
+                &path.with_extension("json"),
+            )?;
+        }
+    }
 
-    generate_weekly_trends(conn, &output_dir.join("weekly-trends.png"))?;
-    generate_cumulative_github(conn, &output_dir.join("cumulative-total.png"))?;
-    generate_github_by_version(conn, &output_dir.join("github-by-version.png"))?;
-    generate_source_comparison(conn, &output_dir.join("source-comparison.png"))?;
-    generate_downloads_badge(conn, &output_dir.join("downloads-badge.svg"))?;
+    let selected: Vec<(&String, &CustomChartConfig)> = match &output.custom {
+        Some(names) => names
+            .iter()
+            .filter_map(|name| charts.custom.get_key_value(name))
+            .collect(),
+        None => charts.custom.iter().collect(),
+    };
+    for (name, custom) in selected {
+        let chart = format!("custom-{}", name);
+        let path = output_dir.join(format!("{}.{}", chart, output.format.extension()));
+        if let Some(data) = generate_custom(conn, name, custom, &path, style)?
+            && output.data
+        {
+            data.write(&chart, provenance, &path.with_extension("json"))?;
+        }
+    }
 
-    println!("  Charts saved to {}.", output_dir);
+    status!("  Charts saved to {}.", output_dir);
     Ok(())
 }
 
-/// Create a styled drawing area with background.
-fn create_drawing_area(
-    output_path: &Utf8Path,
-) -> Result<DrawingArea<BitMapBackend<'_>, plotters::coord::Shift>> {
-    let root = BitMapBackend::new(output_path.as_std_path(), (CHART_WIDTH, CHART_HEIGHT))
+/// The font family built into the binary, drawn with unless
+/// `charts.font_file` provides another.
+const BUILTIN_FONT_FAMILY: &str = "Inter";
+
+const BUILTIN_FONT: &[u8] = include_bytes!("../fonts/Inter-Regular.ttf");
+
+/// Register the configured font with plotters, unless it already is.
+///
+/// Charts are only drawn with registered fonts, never system ones, so they
+/// look the same on every machine. The font is registered as plotters'
+/// default `sans-serif` family too, for text drawn without a family.
+fn prepare_font(charts: &ChartsConfig) -> Result<()> {
+    static REGISTERED: Mutex<Option<(String, Option<Utf8PathBuf>)>> = Mutex::new(None);
+    let mut registered = REGISTERED.lock().unwrap_or_else(|err| err.into_inner());
+    let font = (charts.font_family.clone(), charts.font_file.clone());
+    if registered.as_ref() == Some(&font) {
+        return Ok(());
+    }
+
+    let bytes = font_bytes(charts)?;
+    for family in [charts.font_family.as_str(), "sans-serif"] {
+        plotters::style::register_font(family, FontStyle::Normal, bytes).map_err(|_| {
+            anyhow::anyhow!("font family '{}' can't be drawn with", charts.font_family)
+        })?;
+    }
+    *registered = Some(font);
+    Ok(())
+}
+
+/// Get the font file of the configured font family: `font_file`, or the
+/// built-in font.
+fn font_bytes(charts: &ChartsConfig) -> Result<&'static [u8]> {
+    let Some(path) = &charts.font_file else {
+        if charts.font_family != BUILTIN_FONT_FAMILY {
+            bail!(
+                "font family '{}' isn't built in, so charts.font_file must name a font file \
+                 of it (the built-in font is '{}')",
+                charts.font_family,
+                BUILTIN_FONT_FAMILY
+            );
+        }
+        return Ok(BUILTIN_FONT);
+    };
+
+    let bytes =
+        std::fs::read(path.as_std_path()).with_context(|| format!("failed to read {}", path))?;
+    let face = ttf_parser::Face::parse(&bytes, 0)
+        .with_context(|| format!("failed to load font file {}", path))?;
+    let family = family_name(&face)
+        .with_context(|| format!("font file {} doesn't name its font family", path))?;
+    if family != charts.font_family {
+        bail!(
+            "font file {} is of font family '{}', not the configured '{}'",
+            path,
+            family,
+            charts.font_family
+        );
+    }
+    // Fonts are registered for the rest of the run.
+    Ok(Vec::leak(bytes))
+}
+
+/// Get the family of a font from its `name` table, preferring the
+/// typographic family that groups more than the four basic styles.
+fn family_name(face: &ttf_parser::Face<'_>) -> Option<String> {
+    let name = |id| {
+        face.names()
+            .into_iter()
+            .filter(|name| name.name_id == id && name.is_unicode())
+            .find_map(|name| name.to_string())
+    };
+    name(ttf_parser::name_id::TYPOGRAPHIC_FAMILY).or_else(|| name(ttf_parser::name_id::FAMILY))
+}
+
+/// Create a styled drawing area with background. The image format is chosen
+/// by the file extension.
+fn create_drawing_area<'a>(
+    output_path: &'a Utf8Path,
+    style: &Style,
+) -> Result<DrawingArea<BitMapBackend<'a>, plotters::coord::Shift>> {
+    let root = BitMapBackend::new(output_path.as_std_path(), (style.width, style.height))
         .into_drawing_area();
-    root.fill(&BACKGROUND)?;
+    root.fill(&style.background)?;
     Ok(root)
 }
 
+/// Print the name of a chart that was written.
+fn report_written(output_path: &Utf8Path) {
+    output::written(
+        output_path,
+        format!(
+            "  • {}",
+            output_path.file_name().unwrap_or(output_path.as_str())
+        ),
+    );
+}
+
 /// Configure common mesh styling for date-based charts.
-fn configure_date_mesh<DB: DrawingBackend>(
-    chart: &mut ChartContext<DB, Cartesian2d<RangedDate<NaiveDate>, RangedCoordi64>>,
+fn configure_date_mesh<DB: DrawingBackend, Y>(
+    chart: &mut ChartContext<DB, Cartesian2d<RangedDate<NaiveDate>, Y>>,
+    style: &Style,
+) -> Result<()>
+where
+    <DB as DrawingBackend>::ErrorType: 'static,
+    Y: Ranged<ValueType = i64, FormatOption = DefaultFormatting> + ValueFormatter<i64>,
+{
+    configure_date_mesh_with(chart, style, &|y| {
+        format::number_for(Target::Charts, *y as u64)
+    })
+}
+
+/// Configure common mesh styling for date-based charts, with a custom y-axis
+/// label formatter.
+fn configure_date_mesh_with<DB: DrawingBackend, Y>(
+    chart: &mut ChartContext<DB, Cartesian2d<RangedDate<NaiveDate>, Y>>,
+    style: &Style,
+    y_label_formatter: &dyn Fn(&i64) -> String,
 ) -> Result<()>
 where
     <DB as DrawingBackend>::ErrorType: 'static,
+    Y: Ranged<ValueType = i64, FormatOption = DefaultFormatting> + ValueFormatter<i64>,
 {
     chart
         .configure_mesh()
-        .bold_line_style(&GRID_COLOR.mix(0.3))
-        .light_line_style(&TRANSPARENT)
+        .bold_line_style(style.grid.mix(0.3).stroke_width(style.px(1)))
+        .light_line_style(TRANSPARENT)
+        .axis_style(style.axis.stroke_width(style.px(1)))
         .x_labels(8)
         .y_labels(6)
-        .x_label_style((FONT_FAMILY, AXIS_SIZE).into_font().color(&TEXT_SECONDARY))
-        .y_label_style((FONT_FAMILY, AXIS_SIZE).into_font().color(&TEXT_SECONDARY))
+        .x_label_style(style.axis_font())
+        .y_label_style(style.axis_font())
         .x_label_formatter(&|date| date.format("%Y-%m-%d").to_string())
-        .y_label_formatter(&|y| format_number(*y as u64))
+        .y_label_formatter(y_label_formatter)
         .disable_x_mesh()
         .draw()?;
     Ok(())
 }
 
-/// Generate weekly download trends chart (line chart).
-fn generate_weekly_trends(conn: &Connection, output_path: &Utf8Path) -> Result<()> {
-    let mut stmt = conn.prepare(
-        "SELECT week_start, SUM(downloads) as total
-         FROM weekly_stats
-         WHERE source = 'crates'
-         GROUP BY week_start
-         ORDER BY week_start ASC",
-    )?;
+/// A release marked on date charts.
+#[derive(Debug, PartialEq, Eq)]
+struct ReleaseMarker {
+    date: NaiveDate,
+    label: String,
+}
 
-    let data: Vec<(NaiveDate, i64)> = stmt
-        .query_map([], |row| {
-            let date_str: String = row.get(0)?;
-            let downloads: i64 = row.get(1)?;
-            let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
-                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-            Ok((date, downloads))
-        })?
-        .collect::<Result<Vec<_>, _>>()?;
+/// Load the GitHub releases to mark on date charts, labeled with their
+/// version.
+fn load_release_markers(conn: &Connection, style: &Style) -> Result<Vec<ReleaseMarker>> {
+    if style.release_annotations == ReleaseAnnotations::None {
+        return Ok(Vec::new());
+    }
+    let releases = db::get_github_releases(conn).context("failed to load release dates")?;
+    let markers = releases
+        .into_iter()
+        .filter_map(|release| {
+            let version = aggregate::parse_version(&release.release_tag);
+            let prerelease =
+                release.prerelease || version.as_ref().is_some_and(|v| !v.pre.is_empty());
+            let marked = match style.release_annotations {
+                ReleaseAnnotations::None => false,
+                ReleaseAnnotations::Minor => {
+                    !prerelease && version.as_ref().is_some_and(|v| v.patch == 0)
+                }
+                ReleaseAnnotations::Stable => !prerelease,
+                ReleaseAnnotations::All => true,
+            };
+            marked.then(|| ReleaseMarker {
+                date: release.published_on,
+                label: version.map_or(release.release_tag, |v| v.to_string()),
+            })
+        })
+        .collect();
+    Ok(markers)
+}
 
-    if data.is_empty() {
-        return Ok(());
+/// Draw a dashed vertical line at each release within the chart's date
+/// range, labeled with the version at the top.
+///
+/// Frequent releases would have overlapping labels, so a label is skipped if
+/// it would run into the previous one. Every release still gets its line.
+fn draw_release_markers<DB: DrawingBackend, Y: Ranged<ValueType = i64>>(
+    chart: &mut ChartContext<DB, Cartesian2d<RangedDate<NaiveDate>, Y>>,
+    markers: &[ReleaseMarker],
+    style: &Style,
+) -> Result<()>
+where
+    <DB as DrawingBackend>::ErrorType: 'static,
+{
+    let dates = chart.x_range();
+    let values = chart.y_range();
+    let line_style = style.text_secondary.mix(0.5).stroke_width(style.px(1));
+    let offset = style.px(4) as i32;
+    let font = style.axis_font();
+
+    let mut next_label_x = i32::MIN;
+    for marker in markers
+        .iter()
+        .filter(|marker| dates.start <= marker.date && marker.date <= dates.end)
+    {
+        chart.draw_series(std::iter::once(DashedPathElement::new(
+            vec![(marker.date, values.start), (marker.date, values.end)],
+            style.px(6),
+            style.px(4),
+            line_style,
+        )))?;
+
+        let (x, _) = chart.backend_coord(&(marker.date, values.end));
+        if x < next_label_x {
+            continue;
+        }
+        let (label_width, _) = chart
+            .plotting_area()
+            .estimate_text_size(&marker.label, &font)?;
+        chart.draw_series(std::iter::once(
+            EmptyElement::at((marker.date, values.end))
+                + Text::new(marker.label.clone(), (offset, offset), font.clone()),
+        ))?;
+        next_label_x = x + (label_width + style.px(12)) as i32;
     }
+    Ok(())
+}
+
+/// The releases marked on a chart spanning the given dates, for its data.
+fn data_markers(
+    markers: &[ReleaseMarker],
+    min_date: NaiveDate,
+    max_date: NaiveDate,
+) -> Vec<DataMarker> {
+    markers
+        .iter()
+        .filter(|marker| min_date <= marker.date && marker.date <= max_date)
+        .map(|marker| DataMarker {
+            date: marker.date,
+            label: marker.label.clone(),
+        })
+        .collect()
+}
+
+/// Load downloads per period and source at a resolution, within the output's
+/// date range, ordered by date.
+fn load_downloads(
+    conn: &Connection,
+    resolution: ChartResolution,
+    style: &Style,
+) -> Result<Vec<(NaiveDate, String, i64)>> {
+    let specs = analysis::SOURCES
+        .iter()
+        .map(|source| {
+            SeriesSpec::new(*source, Measure::Downloads(resolution))
+                .source(source)
+                .range(style.since, style.until)
+        })
+        .collect();
+    let mut rows: Vec<(NaiveDate, String, i64)> = stats::batch(conn, specs)?
+        .into_iter()
+        .flat_map(|(id, series)| {
+            series
+                .rounded()
+                .into_iter()
+                .map(move |(date, downloads)| (date, id.0.clone(), downloads))
+        })
+        .collect();
+    rows.sort();
+    Ok(rows)
+}
+
+/// Generate the crates.io download trends chart (line chart), at the output's
+/// resolution.
+fn generate_weekly_trends(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut series = stats::batch(
+        conn,
+        vec![
+            SeriesSpec::new("downloads", Measure::Downloads(style.resolution))
+                .source("crates")
+                .range(style.since, style.until),
+            SeriesSpec::new("average", Measure::Average30d)
+                .source("crates")
+                .range(style.since, style.until),
+        ],
+    )?;
+    let data = series
+        .remove(&"downloads".into())
+        .unwrap_or_default()
+        .rounded();
 
-    let root = create_drawing_area(output_path)?;
+    if data.is_empty() {
+        return Ok(None);
+    }
 
     let min_date = data.first().unwrap().0;
     let max_date = data.last().unwrap().0;
-    let max_downloads = data.iter().map(|(_, d)| *d).max().unwrap();
 
-    let mut chart = ChartBuilder::on(&root)
-        .caption(
-            "Weekly Downloads - crates.io",
-            (FONT_FAMILY, TITLE_SIZE).into_font().color(&TEXT_PRIMARY),
-        )
-        .margin(60)
-        .x_label_area_size(70)
-        .y_label_area_size(100)
-        .build_cartesian_2d(min_date..max_date, 0i64..max_downloads)?;
+    // Weekly releases make the raw series spiky, so overlay the 30-day
+    // average scaled to the chart's period.
+    let (days_per_period, period) = match style.resolution {
+        ChartResolution::Daily => (1.0, "day"),
+        ChartResolution::Weekly => (7.0, "week"),
+        ChartResolution::Monthly => (30.0, "month"),
+    };
+    let smoothed: Vec<(NaiveDate, i64)> = series
+        .remove(&"average".into())
+        .unwrap_or_default()
+        .points
+        .into_iter()
+        .filter(|(date, _)| (min_date..=max_date).contains(date))
+        .map(|(date, average)| (date, (average * days_per_period).round() as i64))
+        .collect();
+
+    let mut lines = vec![DownloadLine {
+        label: format!("Downloads per {}", period),
+        points: data,
+        color: style.color(0).to_rgba(),
+        stroke_width: style.px(3),
+        swatch: false,
+    }];
+    if !smoothed.is_empty() {
+        lines.push(DownloadLine {
+            label: format!("30-day average (per {})", period),
+            points: smoothed,
+            color: style.text_secondary.to_rgba(),
+            stroke_width: style.px(2),
+            swatch: false,
+        });
+    }
+    // Monthly periods are longer than the moving average's window, so it's
+    // only drawn at daily and weekly resolution.
+    let window = match style.resolution {
+        ChartResolution::Daily => Some(MOVING_AVERAGE_DAYS),
+        ChartResolution::Weekly => Some(MOVING_AVERAGE_DAYS / 7),
+        ChartResolution::Monthly => None,
+    };
+    if let Some(window) = window.filter(|_| style.moving_average) {
+        let points = moving_average(&lines[0].points, window);
+        if !points.is_empty() {
+            lines.push(DownloadLine {
+                label: "4-week moving average".to_string(),
+                points,
+                color: style.color(1).to_rgba(),
+                stroke_width: style.px(2),
+                swatch: false,
+            });
+        }
+    }
 
-    configure_date_mesh(&mut chart)?;
+    let data = draw_download_lines(
+        output_path,
+        style,
+        &format!("{} Downloads - crates.io", style.resolution.label()),
+        style.resolution,
+        &lines,
+        &load_release_markers(conn, style)?,
+    )?;
+    report_written(output_path);
+    Ok(data)
+}
 
-    chart.draw_series(LineSeries::new(
-        data.iter().map(|(d, v)| (*d, *v)),
-        ShapeStyle {
-            color: ACCENT_BLUE.to_rgba(),
-            filled: true,
-            stroke_width: 3,
-        },
-    ))?;
+/// Number of days the moving average on the download trends chart spans.
+const MOVING_AVERAGE_DAYS: usize = 28;
+
+/// The trailing mean of each run of `window` points, for the points with a
+/// full window before them.
+fn moving_average(points: &[(NaiveDate, i64)], window: usize) -> Vec<(NaiveDate, i64)> {
+    points
+        .windows(window)
+        .map(|run| {
+            let total: i64 = run.iter().map(|(_, downloads)| downloads).sum();
+            let (date, _) = run[window - 1];
+            (date, (total as f64 / window as f64).round() as i64)
+        })
+        .collect()
+}
+
+/// A series drawn as a line on a download chart.
+struct DownloadLine {
+    label: String,
+    points: Vec<(NaiveDate, i64)>,
+    color: RGBAColor,
+    stroke_width: u32,
+    /// Whether the legend shows a color swatch rather than a line.
+    swatch: bool,
+}
+
+/// Draw lines of downloads over time, with release markers.
+///
+/// The y axis starts at zero, or is logarithmic with `log_scale`. Zero can't
+/// be shown on a logarithmic axis, so it then starts at the power of ten
+/// below the smallest value, and smaller values are drawn on the axis.
+///
+/// Returns the data drawn, or `None` if there was no data to draw.
+fn draw_download_lines(
+    output_path: &Utf8Path,
+    style: &Style,
+    caption: &str,
+    resolution: ChartResolution,
+    lines: &[DownloadLine],
+    markers: &[ReleaseMarker],
+) -> Result<Option<ChartData>> {
+    let points = || lines.iter().flat_map(|line| &line.points);
+    let (Some(min_date), Some(max_date)) = (
+        points().map(|(date, _)| *date).min(),
+        points().map(|(date, _)| *date).max(),
+    ) else {
+        return Ok(None);
+    };
+    let max_downloads = points().map(|(_, downloads)| *downloads).max().unwrap_or(0);
+
+    let root = create_drawing_area(output_path, style)?;
+    let mut builder = ChartBuilder::on(&root);
+    builder
+        .caption(caption, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100));
+
+    if style.log_scale {
+        let smallest = points()
+            .map(|(_, downloads)| *downloads)
+            .filter(|downloads| *downloads > 0)
+            .min()
+            .unwrap_or(1);
+        let floor = 10i64.pow(smallest.ilog10());
+        let chart = builder.build_cartesian_2d(
+            min_date..max_date,
+            (floor..max_downloads.max(floor * 10)).log_scale(),
+        )?;
+        draw_lines_on(chart, style, lines, markers, floor)?;
+    } else {
+        let chart = builder.build_cartesian_2d(min_date..max_date, 0i64..max_downloads.max(1))?;
+        draw_lines_on(chart, style, lines, markers, 0)?;
+    }
 
     root.present()?;
-    println!("  • weekly-trends.png");
+
+    let mut data = ChartData::new(caption, Layout::Lines, Unit::Downloads);
+    data.resolution = Some(resolution);
+    data.log_scale = style.log_scale;
+    for line in lines.iter().filter(|line| !line.points.is_empty()) {
+        let points = line.points.iter().map(|&(date, v)| (date, v as f64));
+        data.push(&line.label, line.color.rgb(), points);
+    }
+    data.markers = data_markers(markers, min_date, max_date);
+    Ok(Some(data))
+}
+
+/// Draw lines of downloads on a chart with either kind of y axis, raising
+/// values below `floor` to it.
+fn draw_lines_on<'a, Y>(
+    mut chart: ChartContext<'a, BitMapBackend<'a>, Cartesian2d<RangedDate<NaiveDate>, Y>>,
+    style: &'a Style,
+    lines: &[DownloadLine],
+    markers: &[ReleaseMarker],
+    floor: i64,
+) -> Result<()>
+where
+    Y: Ranged<ValueType = i64, FormatOption = DefaultFormatting> + ValueFormatter<i64>,
+{
+    configure_date_mesh(&mut chart, style)?;
+
+    for line in lines.iter().filter(|line| !line.points.is_empty()) {
+        let (color, swatch) = (line.color, line.swatch);
+        chart
+            .draw_series(LineSeries::new(
+                line.points
+                    .iter()
+                    .map(|&(date, downloads)| (date, downloads.max(floor))),
+                ShapeStyle {
+                    color,
+                    filled: true,
+                    stroke_width: line.stroke_width,
+                },
+            ))?
+            .label(line.label.as_str())
+            .legend(move |(x, y)| {
+                if swatch {
+                    style.swatch((x, y), 15, color.filled()).into_dyn()
+                } else {
+                    let end = x + style.px(20) as i32;
+                    PathElement::new(vec![(x, y), (end, y)], color.stroke_width(style.px(3)))
+                        .into_dyn()
+                }
+            });
+    }
+
+    draw_release_markers(&mut chart, markers, style)?;
+
+    chart
+        .configure_series_labels()
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background.mix(0.9))
+        .border_style(style.grid)
+        .margin(style.legend_margin)
+        .draw()?;
     Ok(())
 }
 
 /// Generate cumulative GitHub downloads chart.
-fn generate_cumulative_github(conn: &Connection, output_path: &Utf8Path) -> Result<()> {
+fn generate_cumulative_github(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
     use std::collections::{HashMap, HashSet};
 
     let mut dates_set: HashSet<NaiveDate> = HashSet::new();
 
     let mut github_stmt = conn.prepare(
         "SELECT date, SUM(download_count) as total
-         FROM github_snapshots
+         FROM github_cumulative
          GROUP BY date
          ORDER BY date ASC",
     )?;
@@ -158,7 +829,7 @@ fn generate_cumulative_github(conn: &Connection, output_path: &Utf8Path) -> Resu
 
     let mut crates_stmt = conn.prepare(
         "SELECT date, SUM(total_downloads) as total
-         FROM crates_metadata
+         FROM crates_cumulative
          GROUP BY date
          ORDER BY date ASC",
     )?;
@@ -175,11 +846,13 @@ fn generate_cumulative_github(conn: &Connection, output_path: &Utf8Path) -> Resu
 
     dates_set.extend(crates_data.keys());
 
-    if dates_set.is_empty() {
-        return Ok(());
+    let mut dates: Vec<NaiveDate> = dates_set
+        .into_iter()
+        .filter(|date| style.in_range(*date))
+        .collect();
+    if dates.is_empty() {
+        return Ok(None);
     }
-
-    let mut dates: Vec<NaiveDate> = dates_set.into_iter().collect();
     dates.sort();
 
     let max_total = dates
@@ -192,22 +865,19 @@ fn generate_cumulative_github(conn: &Connection, output_path: &Utf8Path) -> Resu
         .max()
         .unwrap();
 
-    let root = create_drawing_area(output_path)?;
+    let root = create_drawing_area(output_path, style)?;
 
     let min_date = *dates.first().unwrap();
     let max_date = *dates.last().unwrap();
 
     let mut chart = ChartBuilder::on(&root)
-        .caption(
-            "Cumulative Downloads - All Sources",
-            (FONT_FAMILY, TITLE_SIZE).into_font().color(&TEXT_PRIMARY),
-        )
-        .margin(60)
-        .x_label_area_size(70)
-        .y_label_area_size(100)
+        .caption("Cumulative Downloads - All Sources", style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
         .build_cartesian_2d(min_date..max_date, 0i64..max_total)?;
 
-    configure_date_mesh(&mut chart)?;
+    configure_date_mesh(&mut chart, style)?;
 
     let github_series: Vec<(NaiveDate, i64)> = dates
         .iter()
@@ -218,12 +888,10 @@ fn generate_cumulative_github(conn: &Connection, output_path: &Utf8Path) -> Resu
         .draw_series(AreaSeries::new(
             github_series.iter().copied(),
             0,
-            ACCENT_BLUE.mix(0.3),
+            style.color(0).mix(0.3),
         ))?
         .label("GitHub Releases")
-        .legend(|(x, y)| {
-            plotters::element::Rectangle::new([(x, y - 5), (x + 20, y + 5)], ACCENT_BLUE.mix(0.3))
-        });
+        .legend(|(x, y)| style.swatch((x, y), 20, style.color(0).mix(0.3)));
 
     let stacked_series: Vec<(NaiveDate, i64)> = dates
         .iter()
@@ -238,23 +906,44 @@ fn generate_cumulative_github(conn: &Connection, output_path: &Utf8Path) -> Resu
         .draw_series(AreaSeries::new(
             stacked_series.iter().copied(),
             0,
-            ACCENT_GREEN.mix(0.3),
+            style.color(1).mix(0.3),
         ))?
         .label("crates.io")
-        .legend(|(x, y)| {
-            plotters::element::Rectangle::new([(x, y - 5), (x + 20, y + 5)], ACCENT_GREEN.mix(0.3))
-        });
+        .legend(|(x, y)| style.swatch((x, y), 20, style.color(1).mix(0.3)));
+
+    let markers = load_release_markers(conn, style)?;
+    draw_release_markers(&mut chart, &markers, style)?;
 
     chart
         .configure_series_labels()
-        .background_style(BACKGROUND.mix(0.9))
-        .border_style(TEXT_SECONDARY)
-        .label_font((FONT_FAMILY, LABEL_SIZE))
+        .legend_area_size(style.px(30))
+        .background_style(style.background.mix(0.9))
+        .border_style(style.text_secondary)
+        .label_font(style.label_font())
         .draw()?;
 
     root.present()?;
-    println!("  • cumulative-total.png");
-    Ok(())
+    report_written(output_path);
+
+    let mut data = ChartData::new(
+        "Cumulative Downloads - All Sources",
+        Layout::Stacked,
+        Unit::Downloads,
+    );
+    let by_date = |source: &HashMap<NaiveDate, i64>| {
+        dates
+            .iter()
+            .map(|d| (*d, source.get(d).copied().unwrap_or(0) as f64))
+            .collect::<Vec<_>>()
+    };
+    data.push(
+        "GitHub Releases",
+        style.color(0).rgb(),
+        by_date(&github_data),
+    );
+    data.push("crates.io", style.color(1).rgb(), by_date(&crates_data));
+    data.markers = data_markers(&markers, min_date, max_date);
+    Ok(Some(data))
 }
 
 /// Version info for chart categorization.
@@ -265,12 +954,16 @@ struct VersionInfo {
 }
 
 /// Generate GitHub downloads by version chart (stacked area).
-fn generate_github_by_version(conn: &Connection, output_path: &Utf8Path) -> Result<()> {
+fn generate_github_by_version(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
     use std::collections::{HashMap, HashSet};
 
     let mut tag_stmt = conn.prepare(
         "SELECT release_tag, SUM(download_count) as total
-         FROM github_snapshots
+         FROM github_cumulative
          WHERE date = (SELECT MAX(date) FROM github_snapshots)
            AND release_tag LIKE 'cargo-nextest-%'
          GROUP BY release_tag
@@ -282,28 +975,28 @@ fn generate_github_by_version(conn: &Connection, output_path: &Utf8Path) -> Resu
         .collect::<Result<Vec<_>, _>>()?;
 
     if all_tags.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
     let mut versions: Vec<(VersionInfo, i64)> = all_tags
         .into_iter()
         .filter_map(|(tag, downloads)| {
-            tag.strip_prefix("cargo-nextest-")
-                .and_then(|v| semver::Version::parse(v).ok())
-                .map(|version| (VersionInfo { tag, version }, downloads))
+            aggregate::parse_version(&tag).map(|version| (VersionInfo { tag, version }, downloads))
         })
         .collect();
 
     versions.sort_by(|a, b| b.0.version.cmp(&a.0.version));
 
-    // Filter out versions with trivial downloads (<10k or <0.5% of max), keep top 5.
+    // Filter out versions with trivial downloads, relative to the most
+    // downloaded release and in absolute terms, and keep the newest of the rest.
     let max_downloads = versions.iter().map(|(_, d)| *d).max().unwrap_or(0);
-    let threshold = (max_downloads as f64 * 0.005).max(10_000.0) as i64;
+    let threshold = (max_downloads as f64 * style.version_min_share)
+        .max(style.version_min_downloads as f64) as i64;
 
     let top_versions: Vec<VersionInfo> = versions
         .into_iter()
         .filter(|(_, downloads)| *downloads >= threshold)
-        .take(5)
+        .take(style.top_versions)
         .map(|(info, _)| info)
         .collect();
 
@@ -311,7 +1004,7 @@ fn generate_github_by_version(conn: &Connection, output_path: &Utf8Path) -> Resu
 
     let mut stmt = conn.prepare(
         "SELECT date, release_tag, SUM(download_count) as total
-         FROM github_snapshots
+         FROM github_cumulative
          GROUP BY date, release_tag
          ORDER BY date ASC, release_tag ASC",
     )?;
@@ -330,6 +1023,9 @@ fn generate_github_by_version(conn: &Connection, output_path: &Utf8Path) -> Resu
 
     for row in rows {
         let (date, tag, downloads) = row?;
+        if !style.in_range(date) {
+            continue;
+        }
         all_dates.insert(date);
 
         let category = if top_tags.contains(tag.as_str()) {
@@ -346,7 +1042,7 @@ fn generate_github_by_version(conn: &Connection, output_path: &Utf8Path) -> Resu
     }
 
     if all_dates.is_empty() {
-        return Ok(());
+        return Ok(None);
     }
 
     let mut dates: Vec<NaiveDate> = all_dates.into_iter().collect();
@@ -370,7 +1066,7 @@ fn generate_github_by_version(conn: &Connection, output_path: &Utf8Path) -> Resu
         }
     }
 
-    let root = create_drawing_area(output_path)?;
+    let root = create_drawing_area(output_path, style)?;
 
     let min_date = *dates.first().unwrap();
     let max_date = *dates.last().unwrap();
@@ -380,200 +1076,1477 @@ fn generate_github_by_version(conn: &Connection, output_path: &Utf8Path) -> Resu
         .max()
         .unwrap();
 
+    let title = "Cumulative Downloads by Version - GitHub Releases";
+    let mut data = ChartData::new(title, Layout::Areas, Unit::Downloads);
     let mut chart = ChartBuilder::on(&root)
-        .caption(
-            "Cumulative Downloads by Version - GitHub Releases",
-            (FONT_FAMILY, TITLE_SIZE).into_font().color(&TEXT_PRIMARY),
-        )
-        .margin(60)
-        .x_label_area_size(70)
-        .y_label_area_size(100)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
         .build_cartesian_2d(min_date..max_date, 0i64..max_downloads)?;
 
-    configure_date_mesh(&mut chart)?;
-
-    let colors = [
-        RGBColor(99, 102, 241),
-        RGBColor(59, 130, 246),
-        RGBColor(34, 197, 94),
-        RGBColor(251, 146, 60),
-        RGBColor(236, 72, 153),
-        RGBColor(156, 163, 175),
-    ];
+    configure_date_mesh(&mut chart, style)?;
 
     for (idx, category) in categories.iter().enumerate() {
-        if let Some(data) = series_data.get(category) {
-            let color = colors[idx % colors.len()];
+        if let Some(points) = series_data.get(category) {
+            let color = style.stack_color(idx, idx == categories.len() - 1);
+            data.push(
+                category,
+                color.rgb(),
+                points.iter().map(|(d, v)| (*d, *v as f64)),
+            );
             chart.draw_series(AreaSeries::new(
-                data.iter().map(|(d, v)| (*d, *v)),
+                points.iter().map(|(d, v)| (*d, *v)),
                 0,
                 color.mix(0.3),
             ))?;
 
             chart
                 .draw_series(LineSeries::new(
-                    data.iter().map(|(d, v)| (*d, *v)),
+                    points.iter().map(|(d, v)| (*d, *v)),
                     ShapeStyle {
                         color: color.to_rgba(),
                         filled: true,
-                        stroke_width: 2,
+                        stroke_width: style.px(2),
                     },
                 ))?
                 .label(category)
-                .legend(move |(x, y)| {
-                    Rectangle::new([(x, y - 5), (x + 15, y + 5)], color.filled())
-                });
+                .legend(move |(x, y)| style.swatch((x, y), 15, color.filled()));
         }
     }
 
     chart
         .configure_series_labels()
-        .label_font((FONT_FAMILY, LABEL_SIZE).into_font().color(&TEXT_PRIMARY))
-        .background_style(&BACKGROUND)
-        .border_style(&GRID_COLOR)
-        .margin(15)
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background)
+        .border_style(style.grid)
+        .margin(style.legend_margin)
         .draw()?;
 
     root.present()?;
-    println!("  • github-by-version.png");
-    Ok(())
+    report_written(output_path);
+    Ok(Some(data))
 }
 
-/// Generate source comparison chart (GitHub vs crates.io).
-fn generate_source_comparison(conn: &Connection, output_path: &Utf8Path) -> Result<()> {
+/// Generate a horizontal bar chart of the release assets or platforms with the
+/// most downloads in the latest GitHub snapshot (as of the output's end date),
+/// with the rest grouped as other.
+fn generate_top_assets(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    use std::collections::HashMap;
+
+    let Some(date) = conn.query_row(
+        "SELECT MAX(date) FROM github_snapshots WHERE ?1 IS NULL OR date <= ?1",
+        [style.until.map(|until| until.to_string())],
+        |row| row.get::<_, Option<String>>(0),
+    )?
+    else {
+        return Ok(None);
+    };
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+        .with_context(|| format!("failed to parse date '{}'", date))?;
+
     let mut stmt = conn.prepare(
-        "SELECT week_start, source, SUM(downloads) as total
-         FROM weekly_stats
-         GROUP BY week_start, source
-         ORDER BY week_start ASC, source ASC",
+        "SELECT release_tag, asset_name, SUM(download_count) FROM github_cumulative
+         WHERE date = ?1
+         GROUP BY release_tag, asset_name",
     )?;
+    let mut totals: HashMap<String, i64> = HashMap::new();
+    for row in stmt.query_map([date.to_string()], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, i64>(2)?,
+        ))
+    })? {
+        let (release_tag, asset_name, downloads) = row?;
+        let key = match style.top_assets_by {
+            TopAssetsBy::Asset => unversioned_asset_name(&release_tag, &asset_name),
+            TopAssetsBy::Platform => platform::platform_label(&asset_name),
+        };
+        *totals.entry(key).or_default() += downloads;
+    }
+
+    let mut ranked: Vec<(String, i64)> = totals.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    if ranked.is_empty() {
+        return Ok(None);
+    }
+    let mut bars: Vec<(String, i64)> = ranked.iter().take(style.top_assets).cloned().collect();
+    let has_other = ranked.len() > style.top_assets;
+    if has_other {
+        let other = ranked[style.top_assets..].iter().map(|(_, d)| d).sum();
+        bars.push(("Other".to_string(), other));
+    }
+    let color = |idx| style.stack_color(0, has_other && idx == bars.len() - 1);
+
+    let root = create_drawing_area(output_path, style)?;
+    let title = match style.top_assets_by {
+        TopAssetsBy::Asset => "All-Time Downloads by Asset - GitHub Releases",
+        TopAssetsBy::Platform => "All-Time Downloads by Platform - GitHub Releases",
+    };
+    // Asset names are long, so the label area fits the longest one.
+    let axis_font = style.axis_font();
+    let mut label_width = 0;
+    for (label, _) in &bars {
+        label_width = label_width.max(root.estimate_text_size(label, &axis_font)?.0);
+    }
+    let max_downloads = bars.iter().map(|(_, d)| *d).max().unwrap_or(0).max(1);
+    // Leave room after the longest bar for its value.
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(label_width + style.px(20))
+        .build_cartesian_2d(
+            0i64..max_downloads * 6 / 5,
+            (0..bars.len() as i32 - 1).into_segmented(),
+        )?;
+
+    // Bars are drawn top down from the most downloaded, so the rank counts
+    // down the y axis.
+    let row = |idx: usize| bars.len() as i32 - 1 - idx as i32;
+    chart
+        .configure_mesh()
+        .bold_line_style(style.grid.mix(0.3).stroke_width(style.px(1)))
+        .light_line_style(TRANSPARENT)
+        .axis_style(style.axis.stroke_width(style.px(1)))
+        .x_labels(6)
+        .y_labels(bars.len())
+        .x_label_style(style.axis_font())
+        .y_label_style(style.axis_font())
+        .x_label_formatter(&|x| format::number_for(Target::Charts, *x as u64))
+        .y_label_formatter(&|y| match y {
+            SegmentValue::CenterOf(y) | SegmentValue::Exact(y) => bars
+                .iter()
+                .enumerate()
+                .find(|(idx, _)| row(*idx) == *y)
+                .map(|(_, (label, _))| label.clone())
+                .unwrap_or_default(),
+            SegmentValue::Last => String::new(),
+        })
+        .disable_y_mesh()
+        .draw()?;
 
-    let mut crates_data: Vec<(NaiveDate, i64)> = Vec::new();
-    let mut github_data: Vec<(NaiveDate, i64)> = Vec::new();
+    chart.draw_series(bars.iter().enumerate().map(|(idx, (_, downloads))| {
+        let y = row(idx);
+        let mut bar = Rectangle::new(
+            [
+                (0, SegmentValue::Exact(y)),
+                (*downloads, SegmentValue::Exact(y + 1)),
+            ],
+            color(idx).filled(),
+        );
+        let gap = style.px(4);
+        bar.set_margin(gap, gap, 0, 0);
+        bar
+    }))?;
+    chart.draw_series(bars.iter().enumerate().map(|(idx, (_, downloads))| {
+        Text::new(
+            format!(" {}", format::number_for(Target::Charts, *downloads as u64)),
+            (*downloads, SegmentValue::CenterOf(row(idx))),
+            axis_font.pos(Pos::new(HPos::Left, VPos::Center)),
+        )
+    }))?;
 
-    let rows = stmt.query_map([], |row| {
-        let date_str: String = row.get(0)?;
-        let source: String = row.get(1)?;
-        let downloads: i64 = row.get(2)?;
-        let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
-            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
-        Ok((date, source, downloads))
-    })?;
+    root.present()?;
+    report_written(output_path);
 
-    for row in rows {
-        let (date, source, downloads) = row?;
-        match source.as_str() {
-            "crates" => crates_data.push((date, downloads)),
-            "github" => github_data.push((date, downloads)),
-            _ => {}
-        }
+    let mut data = ChartData::new(title, Layout::Bars, Unit::Downloads);
+    for (idx, (label, downloads)) in bars.iter().enumerate() {
+        data.push(label, color(idx).rgb(), [(date, *downloads as f64)]);
     }
+    Ok(Some(data))
+}
 
-    if crates_data.is_empty() && github_data.is_empty() {
-        return Ok(());
+/// An asset's name with its release's version left out, so that the same
+/// asset of every release has the same name: for example,
+/// `cargo-nextest-0.9.100-x86_64-unknown-linux-gnu.tar.gz` becomes
+/// `cargo-nextest-x86_64-unknown-linux-gnu.tar.gz`.
+fn unversioned_asset_name(release_tag: &str, asset_name: &str) -> String {
+    match aggregate::parse_version(release_tag) {
+        Some(version) => asset_name.replacen(&format!("-{}", version), "", 1),
+        None => asset_name.to_string(),
+    }
+}
+
+/// Generate weekly GitHub downloads by platform chart (stacked area).
+fn generate_github_by_platform(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    use std::collections::{BTreeMap, HashMap};
+
+    let mut stmt = conn.prepare(
+        "SELECT week_start, platform, downloads
+         FROM weekly_platform_stats
+         ORDER BY week_start ASC",
+    )?;
+
+    let mut rows = stmt
+        .query_map([], |row| {
+            let date_str: String = row.get(0)?;
+            let platform: String = row.get(1)?;
+            let downloads: i64 = row.get(2)?;
+            let date = NaiveDate::parse_from_str(&date_str, "%Y-%m-%d")
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            Ok((date, platform, downloads))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    rows.retain(|(date, _, _)| style.in_range(*date));
+
+    if rows.is_empty() {
+        return Ok(None);
     }
 
-    let root = create_drawing_area(output_path)?;
+    // Rank platforms by total downloads, keeping the top few and grouping the rest.
+    let mut totals: HashMap<&str, i64> = HashMap::new();
+    for (_, platform, downloads) in &rows {
+        *totals.entry(platform.as_str()).or_default() += downloads;
+    }
+    let mut ranked: Vec<(&str, i64)> = totals.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
 
-    let all_dates: Vec<_> = crates_data
+    let mut categories: Vec<String> = ranked
         .iter()
-        .chain(github_data.iter())
-        .map(|(d, _)| *d)
+        .take(style.top_platforms)
+        .map(|(p, _)| p.to_string())
         .collect();
-    let min_date = *all_dates.iter().min().unwrap();
-    let max_date = *all_dates.iter().max().unwrap();
+    let has_other = ranked.len() > style.top_platforms;
+    if has_other {
+        categories.push("Other".to_string());
+    }
 
-    let max_downloads = crates_data
-        .iter()
-        .chain(github_data.iter())
-        .map(|(_, d)| *d)
+    let mut by_week: BTreeMap<NaiveDate, Vec<i64>> = BTreeMap::new();
+    for (date, platform, downloads) in &rows {
+        let idx = categories
+            .iter()
+            .position(|c| c == platform)
+            .unwrap_or(categories.len() - 1);
+        by_week
+            .entry(*date)
+            .or_insert_with(|| vec![0; categories.len()])[idx] += downloads;
+    }
+
+    let dates: Vec<NaiveDate> = by_week.keys().copied().collect();
+    let min_date = *dates.first().unwrap();
+    let max_date = *dates.last().unwrap();
+    let max_downloads = by_week
+        .values()
+        .map(|v| v.iter().sum::<i64>())
         .max()
-        .unwrap();
+        .unwrap()
+        .max(1);
 
+    let root = create_drawing_area(output_path, style)?;
+
+    let title = "Weekly Downloads by Platform - GitHub Releases";
     let mut chart = ChartBuilder::on(&root)
-        .caption(
-            "Weekly Downloads by Source",
-            (FONT_FAMILY, TITLE_SIZE).into_font().color(&TEXT_PRIMARY),
-        )
-        .margin(60)
-        .x_label_area_size(70)
-        .y_label_area_size(100)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
         .build_cartesian_2d(min_date..max_date, 0i64..max_downloads)?;
 
-    configure_date_mesh(&mut chart)?;
+    configure_date_mesh(&mut chart, style)?;
 
-    if !crates_data.is_empty() {
-        chart
-            .draw_series(LineSeries::new(
-                crates_data.iter().map(|(d, v)| (*d, *v)),
-                ShapeStyle {
-                    color: ACCENT_BLUE.to_rgba(),
-                    filled: true,
-                    stroke_width: 3,
-                },
-            ))?
-            .label("crates.io")
-            .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 15, y + 5)], ACCENT_BLUE.filled()));
-    }
+    let color = |idx| style.stack_color(idx, has_other && idx == categories.len() - 1);
+    // Draw cumulative stacks from the top down so each band stays visible.
+    for idx in (0..categories.len()).rev() {
+        let color = color(idx);
+        let stacked: Vec<(NaiveDate, i64)> = by_week
+            .iter()
+            .map(|(date, values)| (*date, values[..=idx].iter().sum()))
+            .collect();
 
-    if !github_data.is_empty() {
         chart
-            .draw_series(LineSeries::new(
-                github_data.iter().map(|(d, v)| (*d, *v)),
-                ShapeStyle {
-                    color: ACCENT_GREEN.to_rgba(),
-                    filled: true,
-                    stroke_width: 3,
-                },
-            ))?
-            .label("GitHub")
-            .legend(|(x, y)| Rectangle::new([(x, y - 5), (x + 15, y + 5)], ACCENT_GREEN.filled()));
+            .draw_series(AreaSeries::new(stacked, 0, color).border_style(color))?
+            .label(&categories[idx])
+            .legend(move |(x, y)| style.swatch((x, y), 15, color.filled()));
     }
 
     chart
         .configure_series_labels()
-        .label_font((FONT_FAMILY, LABEL_SIZE).into_font().color(&TEXT_PRIMARY))
-        .background_style(&BACKGROUND)
-        .border_style(&GRID_COLOR)
-        .margin(15)
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background)
+        .border_style(style.grid)
+        .margin(style.legend_margin)
         .draw()?;
 
     root.present()?;
-    println!("  • source-comparison.png");
-    Ok(())
+    report_written(output_path);
+
+    let mut data = ChartData::new(title, Layout::Stacked, Unit::Downloads);
+    data.resolution = Some(ChartResolution::Weekly);
+    for (idx, category) in categories.iter().enumerate() {
+        let points = by_week
+            .iter()
+            .map(|(date, values)| (*date, values[idx] as f64));
+        data.push(category, color(idx).rgb(), points);
+    }
+    Ok(Some(data))
 }
 
-/// Generate a downloads badge SVG showing total downloads across all sources.
-fn generate_downloads_badge(conn: &Connection, output_path: &Utf8Path) -> Result<()> {
-    let github_total: i64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(download_count), 0)
-             FROM github_snapshots
-             WHERE date = (SELECT MAX(date) FROM github_snapshots)",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
-
-    let crates_total: i64 = conn
-        .query_row(
-            "SELECT COALESCE(SUM(total_downloads), 0)
-             FROM crates_metadata
-             WHERE date = (SELECT MAX(date) FROM crates_metadata)",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(0);
+/// Generate source comparison chart (GitHub vs crates.io), at the output's
+/// resolution.
+fn generate_source_comparison(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let data = draw_source_lines(
+        output_path,
+        style,
+        load_downloads(conn, style.resolution, style)?,
+        &format!("{} Downloads by Source", style.resolution.label()),
+        style.resolution,
+    )?;
+    if data.is_some() {
+        report_written(output_path);
+    }
+    Ok(data)
+}
 
-    let total = (github_total + crates_total) as u64;
-    let total_str = format_number(total);
+/// Generate monthly downloads by source chart.
+fn generate_monthly_trends(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let data = draw_source_lines(
+        output_path,
+        style,
+        load_downloads(conn, ChartResolution::Monthly, style)?,
+        "Monthly Downloads by Source",
+        ChartResolution::Monthly,
+    )?;
+    if data.is_some() {
+        report_written(output_path);
+    }
+    Ok(data)
+}
 
-    let label = "downloads";
-    let label_width = 75;
-    let value_width = total_str.len() * 7 + 20;
-    let total_width = label_width + value_width;
-    let label_x = label_width / 2;
-    let value_x = label_width + value_width / 2;
+/// Draw one line per source from (date, source, downloads) rows.
+///
+/// Returns the data drawn, or `None` if there was no data to draw.
+fn draw_source_lines(
+    output_path: &Utf8Path,
+    style: &Style,
+    rows: Vec<(NaiveDate, String, i64)>,
+    caption: &str,
+    resolution: ChartResolution,
+) -> Result<Option<ChartData>> {
+    let sources = [
+        ("crates", "crates.io", style.color(0)),
+        ("github", "GitHub", style.color(1)),
+        ("installer", "Install script", style.color(2)),
+    ];
+    let mut data: Vec<Vec<(NaiveDate, i64)>> = vec![Vec::new(); sources.len()];
+
+    for (date, source, downloads) in rows {
+        if let Some(idx) = sources.iter().position(|(s, _, _)| *s == source) {
+            data[idx].push((date, downloads));
+        }
+    }
+
+    let lines: Vec<DownloadLine> = sources
+        .iter()
+        .zip(data)
+        .map(|((_, label, color), points)| DownloadLine {
+            label: label.to_string(),
+            points,
+            color: color.to_rgba(),
+            stroke_width: style.px(3),
+            swatch: true,
+        })
+        .collect();
+    draw_download_lines(output_path, style, caption, resolution, &lines, &[])
+}
+
+/// Number of week columns a year can span in the calendar heatmap.
+const CALENDAR_COLUMNS: i32 = 54;
+
+/// Generate a calendar heatmap of daily crates.io downloads, with a grid of
+/// weeks by weekday for each year.
+///
+/// Days are shaded by the quartile of their downloads among the days shown,
+/// so weekly cycles and holidays stand out however much downloads grew.
+fn generate_calendar_heatmap(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut series = stats::batch(
+        conn,
+        vec![
+            SeriesSpec::new("crates", Measure::Downloads(ChartResolution::Daily))
+                .source("crates")
+                .range(style.since, style.until),
+        ],
+    )?;
+    let days: std::collections::BTreeMap<NaiveDate, i64> = series
+        .remove(&"crates".into())
+        .unwrap_or_default()
+        .rounded()
+        .into_iter()
+        .collect();
+    let (Some((&first, _)), Some((&last, _))) = (days.first_key_value(), days.last_key_value())
+    else {
+        return Ok(None);
+    };
+
+    let first_day = aggregate::stored_week_start(conn)?.weekday();
+    let thresholds = calendar_thresholds(days.values().copied().filter(|&d| d > 0).collect());
+    let shades: Vec<RGBColor> = (0..5)
+        .map(|level| match level {
+            0 => style.grid,
+            _ => blend(style.background, style.color(0), level as f64 / 4.0),
+        })
+        .collect();
+
+    let root = create_drawing_area(output_path, style)?;
+    let title = "Daily Downloads - crates.io";
+    let area = root.titled(title, style.title_font())?.margin(
+        style.margin / 2,
+        style.margin / 2,
+        style.margin,
+        style.margin,
+    );
+    let (width, height) = area.dim_in_pixel();
+    let (width, height) = (width as i32, height as i32);
+
+    let label_font = style.label_font();
+    let axis_font = style.axis_font();
+    let years: Vec<i32> = (first.year()..=last.year()).collect();
+    let px = |pixels| style.px(pixels) as i32;
+
+    // Each year has a header row for its month labels, seven rows of days,
+    // and a blank row before the next year.
+    let (year_width, _) = area.estimate_text_size("0000", &label_font)?;
+    let (weekday_width, _) = area.estimate_text_size("Wed", &axis_font)?;
+    let left = year_width.max(weekday_width) as i32 + px(12);
+    let header = style.label_size as i32 * 2;
+    let legend = style.label_size as i32 * 3;
+    let per_year = (height - legend) / years.len() as i32;
+    let cell = ((width - left) / CALENDAR_COLUMNS)
+        .min((per_year - header) / 8)
+        .max(2);
+    let gap = (cell / 8).max(1);
+    let x0 = left + (width - left - cell * CALENDAR_COLUMNS) / 2;
+
+    let right_center = Pos::new(HPos::Right, VPos::Center);
+    let left_center = Pos::new(HPos::Left, VPos::Center);
+    for (i, &year) in years.iter().enumerate() {
+        let top = i as i32 * (header + 8 * cell);
+        let grid_top = top + header;
+
+        area.draw(&Text::new(
+            year.to_string(),
+            (x0 - px(8), top + header / 2),
+            label_font.pos(right_center),
+        ))?;
+
+        // Months are skipped when the cells are too small for their labels.
+        let mut next_month_x = i32::MIN;
+        for month in 1..=12 {
+            let start = NaiveDate::from_ymd_opt(year, month, 1).expect("valid month");
+            let (column, _) = calendar_cell(start, first_day);
+            let x = x0 + column * cell;
+            if x < next_month_x {
+                continue;
+            }
+            let name = start.format("%b").to_string();
+            let (name_width, _) = area.estimate_text_size(&name, &axis_font)?;
+            area.draw(&Text::new(
+                name,
+                (x, top + header / 2),
+                axis_font.pos(left_center),
+            ))?;
+            next_month_x = x + name_width as i32 + px(6);
+        }
+
+        // Label every other weekday when the rows are shorter than the text.
+        let mut weekday = first_day;
+        for row in 0..7 {
+            if cell >= style.axis_size as i32 || row % 2 == 0 {
+                area.draw(&Text::new(
+                    weekday.to_string(),
+                    (x0 - px(8), grid_top + row * cell + cell / 2),
+                    axis_font.pos(right_center),
+                ))?;
+            }
+            weekday = weekday.succ();
+        }
+
+        let year_start = NaiveDate::from_ymd_opt(year, 1, 1).expect("valid year");
+        let year_end = NaiveDate::from_ymd_opt(year, 12, 31).expect("valid year");
+        for (&date, &downloads) in days.range(year_start..=year_end) {
+            let (column, row) = calendar_cell(date, first_day);
+            let x = x0 + column * cell;
+            let y = grid_top + row * cell;
+            area.draw(&Rectangle::new(
+                [(x, y), (x + cell - gap, y + cell - gap)],
+                shades[calendar_level(downloads, &thresholds)].filled(),
+            ))?;
+        }
+    }
+
+    // The legend goes under the last year, showing the range of downloads per
+    // day for each shade.
+    let number = |n: i64| format::number_for(Target::Charts, n.max(0) as u64);
+    let labels = [
+        "None".to_string(),
+        format!("≤ {}", number(thresholds[0])),
+        format!("≤ {}", number(thresholds[1])),
+        format!("≤ {}", number(thresholds[2])),
+        format!("> {}", number(thresholds[2])),
+    ];
+    let swatch = cell.min(style.label_size as i32);
+    let y = years.len() as i32 * (header + 8 * cell) + legend / 2;
+    let mut x = x0;
+    let title = "Downloads per day:";
+    area.draw(&Text::new(title, (x, y), label_font.pos(left_center)))?;
+    x += area.estimate_text_size(title, &label_font)?.0 as i32 + px(16);
+    for (shade, label) in shades.iter().zip(labels) {
+        area.draw(&Rectangle::new(
+            [(x, y - swatch / 2), (x + swatch, y + swatch / 2)],
+            shade.filled(),
+        ))?;
+        x += swatch + px(6);
+        let (label_width, _) = area.estimate_text_size(&label, &axis_font)?;
+        area.draw(&Text::new(label, (x, y), axis_font.pos(left_center)))?;
+        x += label_width as i32 + px(20);
+    }
+
+    root.present()?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(title, Layout::Calendar, Unit::Downloads);
+    data.resolution = Some(ChartResolution::Daily);
+    let points = days
+        .iter()
+        .map(|(date, downloads)| (*date, *downloads as f64));
+    data.push("crates.io", style.color(0).rgb(), points);
+    Ok(Some(data))
+}
+
+/// The week column and weekday row of a date in its year's calendar grid,
+/// for weeks starting on `first_day`.
+fn calendar_cell(date: NaiveDate, first_day: Weekday) -> (i32, i32) {
+    let days_into_week = |date: NaiveDate| {
+        (date.weekday().num_days_from_monday() + 7 - first_day.num_days_from_monday()) % 7
+    };
+    let year_start = date.with_ordinal(1).expect("every year has a first day");
+    let column = (date.ordinal0() + days_into_week(year_start)) / 7;
+    (column as i32, days_into_week(date) as i32)
+}
+
+/// The boundaries between the shades of the calendar heatmap: the quartiles
+/// of the days with downloads.
+fn calendar_thresholds(mut downloads: Vec<i64>) -> [i64; 3] {
+    if downloads.is_empty() {
+        return [0; 3];
+    }
+    downloads.sort_unstable();
+    let quartile = |q: usize| downloads[(downloads.len() - 1) * q / 4];
+    [quartile(1), quartile(2), quartile(3)]
+}
+
+/// The shade of a day in the calendar heatmap, from 0 for no downloads to 4
+/// for the top quartile.
+fn calendar_level(downloads: i64, thresholds: &[i64; 3]) -> usize {
+    if downloads <= 0 {
+        return 0;
+    }
+    1 + thresholds.iter().filter(|&&t| downloads > t).count()
+}
+
+/// Mix two colors, from `from` at 0 to `to` at 1.
+fn blend(from: RGBColor, to: RGBColor, t: f64) -> RGBColor {
+    let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+    RGBColor(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2))
+}
+
+/// The leap year each year's weeks are drawn in on the year-over-year chart,
+/// so that every week start has a place on the axis.
+const YEAR_OVER_YEAR_AXIS: i32 = 2000;
+
+/// Generate a chart of weekly downloads from all sources with a line per
+/// calendar year, overlaid on a January–December axis.
+///
+/// Weeks belong to the year they start in. Incomplete weeks at the end of the
+/// data are left out, since they would look like a drop.
+fn generate_year_over_year(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut years: std::collections::BTreeMap<i32, Vec<(NaiveDate, i64)>> = Default::default();
+    for (week, downloads) in analysis::weekly_trend(conn, "all")? {
+        if style.in_range(week) {
+            years
+                .entry(week.year())
+                .or_default()
+                .push((week, downloads as i64));
+        }
+    }
+    let Some(max_downloads) = years.values().flatten().map(|(_, d)| *d).max() else {
+        return Ok(None);
+    };
+
+    let on_axis = |week: NaiveDate| {
+        week.with_year(YEAR_OVER_YEAR_AXIS)
+            .expect("every date is in a leap year")
+    };
+    let axis_start = NaiveDate::from_ymd_opt(YEAR_OVER_YEAR_AXIS, 1, 1).expect("valid date");
+    let axis_end = NaiveDate::from_ymd_opt(YEAR_OVER_YEAR_AXIS, 12, 31).expect("valid date");
+
+    let root = create_drawing_area(output_path, style)?;
+    let title = "Weekly Downloads by Year";
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(axis_start..axis_end, 0i64..max_downloads.max(1))?;
+
+    chart
+        .configure_mesh()
+        .bold_line_style(style.grid.mix(0.3).stroke_width(style.px(1)))
+        .light_line_style(TRANSPARENT)
+        .axis_style(style.axis.stroke_width(style.px(1)))
+        .x_labels(0)
+        .y_labels(6)
+        .x_label_formatter(&|_| String::new())
+        .y_label_style(style.axis_font())
+        .y_label_formatter(&|y| format::number_for(Target::Charts, *y as u64))
+        .disable_x_mesh()
+        .draw()?;
+
+    // Date ticks aren't aligned to months, so month names are drawn under the
+    // middle of each month instead.
+    let axis_font = style.axis_font();
+    for month in 1..=12 {
+        let middle = NaiveDate::from_ymd_opt(YEAR_OVER_YEAR_AXIS, month, 15).expect("valid date");
+        let (x, y) = chart.backend_coord(&(middle, 0));
+        root.draw(&Text::new(
+            middle.format("%b").to_string(),
+            (x, y + style.px(10) as i32),
+            axis_font.pos(Pos::new(HPos::Center, VPos::Top)),
+        ))?;
+    }
+
+    for (idx, (year, weeks)) in years.iter().enumerate() {
+        let color = style.color(idx);
+        chart
+            .draw_series(LineSeries::new(
+                weeks
+                    .iter()
+                    .map(|&(week, downloads)| (on_axis(week), downloads)),
+                ShapeStyle {
+                    color: color.to_rgba(),
+                    filled: true,
+                    stroke_width: style.px(3),
+                },
+            ))?
+            .label(year.to_string())
+            .legend(move |(x, y)| style.swatch((x, y), 15, color.filled()));
+    }
+
+    chart
+        .configure_series_labels()
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background.mix(0.9))
+        .border_style(style.grid)
+        .margin(style.legend_margin)
+        .draw()?;
+
+    root.present()?;
+    report_written(output_path);
+
+    // The data keeps the actual week starts; renderers overlay them by year.
+    let mut data = ChartData::new(title, Layout::Years, Unit::Downloads);
+    data.resolution = Some(ChartResolution::Weekly);
+    for (idx, (year, weeks)) in years.into_iter().enumerate() {
+        let points = weeks.into_iter().map(|(week, d)| (week, d as f64));
+        data.push(year.to_string(), style.color(idx).rgb(), points);
+    }
+    Ok(Some(data))
+}
+
+/// Number of snapshot intervals the download rate chart is smoothed over.
+const RATE_WINDOW: usize = 4;
+
+/// Generate GitHub downloads per day chart (rate of change of cumulative downloads).
+fn generate_download_rate(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut rates = analysis::github_download_rate(conn, RATE_WINDOW)?;
+    rates.retain(|point| style.in_range(point.date));
+
+    if rates.is_empty() {
+        return Ok(None);
+    }
+
+    let root = create_drawing_area(output_path, style)?;
+
+    let min_date = rates.first().unwrap().date;
+    let max_date = rates.last().unwrap().date;
+    let max_rate = rates
+        .iter()
+        .map(|p| p.per_day.max(p.smoothed_per_day).ceil() as i64)
+        .max()
+        .unwrap()
+        .max(1);
+
+    let title = "Downloads per Day - GitHub Releases";
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(min_date..max_date, 0i64..max_rate)?;
+
+    configure_date_mesh(&mut chart, style)?;
+
+    chart
+        .draw_series(LineSeries::new(
+            rates.iter().map(|p| (p.date, p.per_day.round() as i64)),
+            ShapeStyle {
+                color: style.text_secondary.mix(0.5),
+                filled: true,
+                stroke_width: style.px(1),
+            },
+        ))?
+        .label("Per snapshot")
+        .legend(|(x, y)| style.swatch((x, y), 15, style.text_secondary.filled()));
+
+    chart
+        .draw_series(LineSeries::new(
+            rates
+                .iter()
+                .map(|p| (p.date, p.smoothed_per_day.round() as i64)),
+            ShapeStyle {
+                color: style.color(0).to_rgba(),
+                filled: true,
+                stroke_width: style.px(3),
+            },
+        ))?
+        .label(format!("Smoothed ({} snapshots)", RATE_WINDOW))
+        .legend(|(x, y)| style.swatch((x, y), 15, style.color(0).filled()));
+
+    chart
+        .configure_series_labels()
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background)
+        .border_style(style.grid)
+        .margin(style.legend_margin)
+        .draw()?;
+
+    root.present()?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(title, Layout::Lines, Unit::DownloadsPerDay);
+    let per_snapshot = rates.iter().map(|p| (p.date, p.per_day));
+    data.push("Per snapshot", style.text_secondary.rgb(), per_snapshot);
+    let smoothed = rates.iter().map(|p| (p.date, p.smoothed_per_day));
+    data.push(
+        format!("Smoothed ({} snapshots)", RATE_WINDOW),
+        style.color(0).rgb(),
+        smoothed,
+    );
+    Ok(Some(data))
+}
+
+/// Minimum share of weekly downloads for a version to count as active in charts.
+const DIVERSITY_MIN_SHARE: f64 = 0.01;
+
+/// Generate active version diversity chart.
+fn generate_version_diversity(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut points = analysis::version_diversity(conn, DIVERSITY_MIN_SHARE)?;
+    points.retain(|point| style.in_range(point.week_start));
+
+    if points.is_empty() {
+        return Ok(None);
+    }
+
+    let root = create_drawing_area(output_path, style)?;
+
+    let min_date = points.first().unwrap().week_start;
+    let max_date = points.last().unwrap().week_start;
+    let max_versions = points
+        .iter()
+        .map(|p| p.active_versions as i64)
+        .max()
+        .unwrap()
+        .max(1)
+        + 1;
+
+    let title = "Active Versions per Week - GitHub Releases";
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(min_date..max_date, 0i64..max_versions)?;
+
+    configure_date_mesh(&mut chart, style)?;
+
+    let active_label = format!(
+        "Versions with at least {}% of downloads",
+        DIVERSITY_MIN_SHARE * 100.0
+    );
+    let effective_label = "Effective versions (entropy)";
+    chart
+        .draw_series(LineSeries::new(
+            points
+                .iter()
+                .map(|p| (p.week_start, p.active_versions as i64)),
+            ShapeStyle {
+                color: style.color(0).to_rgba(),
+                filled: true,
+                stroke_width: style.px(3),
+            },
+        ))?
+        .label(&active_label)
+        .legend(|(x, y)| style.swatch((x, y), 15, style.color(0).filled()));
+
+    chart
+        .draw_series(LineSeries::new(
+            points
+                .iter()
+                .map(|p| (p.week_start, p.effective_versions.round() as i64)),
+            ShapeStyle {
+                color: style.color(1).to_rgba(),
+                filled: true,
+                stroke_width: style.px(3),
+            },
+        ))?
+        .label(effective_label)
+        .legend(|(x, y)| style.swatch((x, y), 15, style.color(1).filled()));
+
+    chart
+        .configure_series_labels()
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background)
+        .border_style(style.grid)
+        .margin(style.legend_margin)
+        .draw()?;
+
+    root.present()?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(title, Layout::Lines, Unit::Versions);
+    data.resolution = Some(ChartResolution::Weekly);
+    let active = points
+        .iter()
+        .map(|p| (p.week_start, p.active_versions as f64));
+    data.push(active_label, style.color(0).rgb(), active);
+    let effective = points.iter().map(|p| (p.week_start, p.effective_versions));
+    data.push(effective_label, style.color(1).rgb(), effective);
+    Ok(Some(data))
+}
+
+/// Generate the share of weekly downloads per version chart (100% stacked
+/// area).
+fn generate_version_share(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut share = analysis::version_share(conn, "all", style.top_versions)?;
+    share
+        .weeks
+        .retain(|(week_start, _)| style.in_range(*week_start));
+    if share.weeks.is_empty() {
+        return Ok(None);
+    }
+
+    let root = create_drawing_area(output_path, style)?;
+
+    let min_date = share.weeks.first().unwrap().0;
+    let max_date = share.weeks.last().unwrap().0;
+
+    // Shares are plotted in basis points so that stacking stays integral.
+    let title = "Share of Weekly Downloads by Version";
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(min_date..max_date, 0i64..10_000i64)?;
+
+    configure_date_mesh_with(&mut chart, style, &|y| format!("{}%", y / 100))?;
+
+    let color =
+        |idx: usize| style.stack_color(idx, share.versions[idx] == analysis::OLDER_VERSIONS);
+    // Draw cumulative stacks from the top down so each band stays visible.
+    for idx in (0..share.versions.len()).rev() {
+        let color = color(idx);
+        let stacked: Vec<(NaiveDate, i64)> = share
+            .weeks
+            .iter()
+            .map(|(week_start, shares)| {
+                let cumulative: f64 = shares[..=idx].iter().sum();
+                (*week_start, (cumulative * 10_000.0).round() as i64)
+            })
+            .collect();
+
+        chart
+            .draw_series(AreaSeries::new(stacked, 0, color).border_style(color))?
+            .label(&share.versions[idx])
+            .legend(move |(x, y)| style.swatch((x, y), 15, color.filled()));
+    }
+
+    chart
+        .configure_series_labels()
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background)
+        .border_style(style.grid)
+        .margin(style.legend_margin)
+        .draw()?;
+
+    root.present()?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(title, Layout::Stacked, Unit::Ratio);
+    data.resolution = Some(ChartResolution::Weekly);
+    for (idx, version) in share.versions.iter().enumerate() {
+        let points = share
+            .weeks
+            .iter()
+            .map(|(week_start, shares)| (*week_start, shares[idx]));
+        data.push(version, color(idx).rgb(), points);
+    }
+    Ok(Some(data))
+}
+
+/// Generate the share of weekly downloads going to pre-releases per source.
+fn generate_release_channels(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let sources = [
+        ("GitHub releases", "github", style.color(0)),
+        ("crates.io", "crates", style.color(2)),
+    ];
+    // Shares are plotted in basis points so that the axis stays integral.
+    let title = "Share of Weekly Downloads from Pre-releases";
+    let mut data = ChartData::new(title, Layout::Lines, Unit::Ratio);
+    data.resolution = Some(ChartResolution::Weekly);
+    let mut series = Vec::new();
+    for (label, source, color) in sources {
+        let shares: Vec<(NaiveDate, f64)> = analysis::release_channels(conn, source)?
+            .iter()
+            .filter(|week| style.in_range(week.week_start))
+            .filter_map(|week| Some((week.week_start, week.prerelease_share()?)))
+            .collect();
+        if !shares.is_empty() {
+            let points = shares
+                .iter()
+                .map(|(date, share)| (*date, (share * 10_000.0).round() as i64))
+                .collect::<Vec<_>>();
+            data.push(label, color.rgb(), shares);
+            series.push((label, color, points));
+        }
+    }
+    if series.is_empty() {
+        return Ok(None);
+    }
+
+    let root = create_drawing_area(output_path, style)?;
+
+    let all_points = || series.iter().flat_map(|(_, _, points)| points);
+    let min_date = all_points().map(|(date, _)| *date).min().unwrap();
+    let max_date = all_points().map(|(date, _)| *date).max().unwrap();
+    // Leave some headroom, and always show at least 1%.
+    let max_share = (all_points().map(|(_, share)| *share).max().unwrap() * 11 / 10).max(100);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(min_date..max_date, 0i64..max_share)?;
+
+    configure_date_mesh_with(&mut chart, style, &|y| format!("{:.1}%", *y as f64 / 100.0))?;
+
+    for (label, color, points) in &series {
+        let color = *color;
+        chart
+            .draw_series(LineSeries::new(
+                points.iter().copied(),
+                ShapeStyle {
+                    color: color.to_rgba(),
+                    filled: true,
+                    stroke_width: style.px(3),
+                },
+            ))?
+            .label(*label)
+            .legend(move |(x, y)| style.swatch((x, y), 15, color.filled()));
+    }
+
+    chart
+        .configure_series_labels()
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background)
+        .border_style(style.grid)
+        .margin(style.legend_margin)
+        .draw()?;
+
+    root.present()?;
+    report_written(output_path);
+    Ok(Some(data))
+}
+
+/// Generate the signature downloads per artifact download chart.
+fn generate_verification(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let ratios: Vec<(NaiveDate, f64)> = analysis::verification(conn)?
+        .iter()
+        .filter(|week| style.in_range(week.week_start))
+        .filter_map(|week| Some((week.week_start, week.signature_ratio()?)))
+        .collect();
+    if ratios.is_empty() {
+        return Ok(None);
+    }
+    // Ratios are plotted in basis points so that the axis stays integral.
+    let points: Vec<(NaiveDate, i64)> = ratios
+        .iter()
+        .map(|(date, ratio)| (*date, (ratio * 10_000.0).round() as i64))
+        .collect();
+
+    let root = create_drawing_area(output_path, style)?;
+
+    let min_date = points.first().unwrap().0;
+    let max_date = points.last().unwrap().0;
+    // Leave some headroom, and always show at least 1%.
+    let max_ratio = (points.iter().map(|(_, ratio)| *ratio).max().unwrap() * 11 / 10).max(100);
+
+    let title = "Signature Downloads per Artifact Download - Signed GitHub Releases";
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(min_date..max_date, 0i64..max_ratio)?;
+
+    configure_date_mesh_with(&mut chart, style, &|y| format!("{:.1}%", *y as f64 / 100.0))?;
+
+    chart.draw_series(LineSeries::new(
+        points,
+        ShapeStyle {
+            color: style.color(1).to_rgba(),
+            filled: true,
+            stroke_width: style.px(3),
+        },
+    ))?;
+
+    root.present()?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(title, Layout::Lines, Unit::Ratio);
+    data.resolution = Some(ChartResolution::Weekly);
+    data.push(
+        "Signature downloads per artifact download",
+        style.color(1).rgb(),
+        ratios,
+    );
+    Ok(Some(data))
+}
+
+/// Generate a chart of a derived metric's weekly values.
+fn generate_metric(
+    conn: &Connection,
+    metrics: &Metrics,
+    metric: &Metric,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let values: Vec<(NaiveDate, f64)> = metrics
+        .evaluate(conn, &metric.name)?
+        .into_iter()
+        .filter(|(week, _)| style.in_range(*week))
+        .collect();
+    if values.is_empty() {
+        return Ok(None);
+    }
+
+    let caption = match &metric.description {
+        Some(description) => description.clone(),
+        None => metric.name.clone(),
+    };
+    let lines = vec![(metric.name.clone(), values)];
+    draw_value_lines(output_path, style, &caption, metric.format, &lines)?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(caption, Layout::Lines, format_unit(metric.format));
+    data.resolution = Some(ChartResolution::Weekly);
+    for (label, values) in lines {
+        data.push(label, style.color(0).rgb(), values);
+    }
+    Ok(Some(data))
+}
+
+/// Generate a chart of a custom query's results, with a line per series.
+fn generate_custom(
+    conn: &Connection,
+    name: &str,
+    custom: &CustomChartConfig,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut lines = load_custom_series(conn, name, &custom.query)
+        .with_context(|| format!("failed to run the query of custom chart '{}'", name))?;
+    for (_, values) in &mut lines {
+        values.retain(|(date, _)| style.in_range(*date));
+    }
+    lines.retain(|(_, values)| !values.is_empty());
+    if lines.is_empty() {
+        return Ok(None);
+    }
+
+    draw_value_lines(output_path, style, &custom.title, custom.format, &lines)?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(&custom.title, Layout::Lines, format_unit(custom.format));
+    for (idx, (label, values)) in lines.into_iter().enumerate() {
+        data.push(label, style.color(idx).rgb(), values);
+    }
+    Ok(Some(data))
+}
+
+/// A labeled line of values over time.
+type ValueLine = (String, Vec<(NaiveDate, f64)>);
+
+/// Run a custom chart's query, returning its series in the order they first
+/// appear. Rows without a series column belong to a single series named
+/// after the chart. Values for the same date and series are added up, and
+/// null values are skipped.
+fn load_custom_series(conn: &Connection, name: &str, query: &str) -> Result<Vec<ValueLine>> {
+    let mut stmt = conn.prepare(query)?;
+    if !stmt.readonly() {
+        bail!("the query must not modify the database");
+    }
+    let columns = stmt.column_count();
+    if !(2..=3).contains(&columns) {
+        bail!(
+            "the query must return (date, value) or (date, value, series) columns, found {} columns",
+            columns
+        );
+    }
+
+    let mut series: Vec<(String, std::collections::BTreeMap<NaiveDate, f64>)> = Vec::new();
+    let mut rows = stmt.query([])?;
+    while let Some(row) = rows.next()? {
+        let date: String = row.get(0)?;
+        let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .with_context(|| format!("failed to parse date '{}'", date))?;
+        let Some(value) = row.get::<_, Option<f64>>(1)? else {
+            continue;
+        };
+        let label = if columns == 3 {
+            row.get(2)?
+        } else {
+            name.to_string()
+        };
+        let idx = match series.iter().position(|(existing, _)| *existing == label) {
+            Some(idx) => idx,
+            None => {
+                series.push((label, Default::default()));
+                series.len() - 1
+            }
+        };
+        *series[idx].1.entry(date).or_default() += value;
+    }
+    Ok(series
+        .into_iter()
+        .map(|(label, values)| (label, values.into_iter().collect()))
+        .collect())
+}
+
+/// The data sidecar unit of values shown in a metric format.
+fn format_unit(format: MetricFormat) -> Unit {
+    match format {
+        MetricFormat::Percent => Unit::Ratio,
+        MetricFormat::Number => Unit::Number,
+    }
+}
+
+/// Draw lines of arbitrary values over time, such as metrics, with y axis
+/// labels in `format`. The lines must not all be empty. A legend is drawn
+/// when there's more than one line.
+fn draw_value_lines(
+    output_path: &Utf8Path,
+    style: &Style,
+    caption: &str,
+    format: MetricFormat,
+    lines: &[ValueLine],
+) -> Result<()> {
+    // Values are plotted in ten-thousandths so that the axis stays integral
+    // for ratios.
+    const SCALE: f64 = 10_000.0;
+    let scaled: Vec<Vec<(NaiveDate, i64)>> = lines
+        .iter()
+        .map(|(_, values)| {
+            values
+                .iter()
+                .map(|(date, value)| (*date, (value * SCALE).round() as i64))
+                .collect()
+        })
+        .collect();
+    let points = || scaled.iter().flatten();
+
+    let root = create_drawing_area(output_path, style)?;
+
+    let min_date = points()
+        .map(|(date, _)| *date)
+        .min()
+        .expect("lines have points");
+    let max_date = points()
+        .map(|(date, _)| *date)
+        .max()
+        .expect("lines have points");
+    // Leave some headroom beyond the extreme values.
+    let min_value = (points().map(|(_, value)| *value).min().unwrap() * 11 / 10).min(0);
+    let max_value = (points().map(|(_, value)| *value).max().unwrap() * 11 / 10).max(1);
+
+    let mut chart = ChartBuilder::on(&root)
+        .caption(caption, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(min_date..max_date, min_value..max_value)?;
+
+    configure_date_mesh_with(&mut chart, style, &|y| {
+        let value = *y as f64 / SCALE;
+        match format {
+            // Axis labels don't need the precision of query output.
+            MetricFormat::Percent => format!("{:.0}%", value * 100.0),
+            MetricFormat::Number => metrics::format_value_for(Target::Charts, format, value),
+        }
+    })?;
+
+    let legend = lines.len() > 1;
+    for (idx, ((label, _), points)) in lines.iter().zip(scaled).enumerate() {
+        let color = style.color(idx);
+        let series = chart.draw_series(LineSeries::new(
+            points,
+            ShapeStyle {
+                color: color.to_rgba(),
+                filled: true,
+                stroke_width: style.px(3),
+            },
+        ))?;
+        if legend {
+            series
+                .label(label)
+                .legend(move |(x, y)| style.swatch((x, y), 15, color.filled()));
+        }
+    }
+
+    if legend {
+        chart
+            .configure_series_labels()
+            .legend_area_size(style.px(30))
+            .label_font(style.label_font())
+            .background_style(style.background)
+            .border_style(style.grid)
+            .margin(style.legend_margin)
+            .draw()?;
+    }
+
+    root.present()?;
+    Ok(())
+}
+
+/// Generate weekly downloads per configured project chart.
+fn generate_project_trends(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut downloads = analysis::project_downloads(conn, false)?;
+    downloads
+        .periods
+        .retain(|(week_start, _)| style.in_range(*week_start));
+    if downloads.periods.is_empty() {
+        return Ok(None);
+    }
+
+    let root = create_drawing_area(output_path, style)?;
+
+    let min_date = downloads.periods.first().unwrap().0;
+    let max_date = downloads.periods.last().unwrap().0;
+    let max_downloads = downloads
+        .periods
+        .iter()
+        .flat_map(|(_, counts)| counts)
+        .max()
+        .copied()
+        .unwrap_or(0)
+        .max(1) as i64;
+
+    let title = "Weekly Downloads by Project";
+    let mut chart = ChartBuilder::on(&root)
+        .caption(title, style.title_font())
+        .margin(style.margin)
+        .x_label_area_size(style.px(70))
+        .y_label_area_size(style.px(100))
+        .build_cartesian_2d(min_date..max_date, 0i64..max_downloads)?;
+
+    configure_date_mesh(&mut chart, style)?;
+
+    for (idx, project) in downloads.projects.iter().enumerate() {
+        let color = style.color(idx);
+        chart
+            .draw_series(LineSeries::new(
+                downloads
+                    .periods
+                    .iter()
+                    .map(|(week_start, counts)| (*week_start, counts[idx] as i64)),
+                ShapeStyle {
+                    color: color.to_rgba(),
+                    filled: true,
+                    stroke_width: style.px(3),
+                },
+            ))?
+            .label(project)
+            .legend(move |(x, y)| style.swatch((x, y), 15, color.filled()));
+    }
+
+    chart
+        .configure_series_labels()
+        .legend_area_size(style.px(30))
+        .label_font(style.label_font())
+        .background_style(style.background)
+        .border_style(style.grid)
+        .margin(style.legend_margin)
+        .draw()?;
+
+    root.present()?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(title, Layout::Lines, Unit::Downloads);
+    data.resolution = Some(ChartResolution::Weekly);
+    for (idx, project) in downloads.projects.iter().enumerate() {
+        let points = downloads
+            .periods
+            .iter()
+            .map(|(week_start, counts)| (*week_start, counts[idx] as f64));
+        data.push(project, style.color(idx).rgb(), points);
+    }
+    Ok(Some(data))
+}
+
+/// Number of weeks the sparkline covers.
+const SPARKLINE_WEEKS: usize = 12;
+
+/// Generate a sparkline of weekly downloads from all sources over the last
+/// few complete weeks: a bare line with no axes or labels, sized to sit next
+/// to the downloads badge.
+fn generate_sparkline(
+    conn: &Connection,
+    output_path: &Utf8Path,
+    style: &Style,
+) -> Result<Option<ChartData>> {
+    let mut weeks: Vec<(NaiveDate, i64)> = analysis::weekly_trend(conn, "all")?
+        .into_iter()
+        .filter(|(week, _)| style.in_range(*week))
+        .map(|(week, downloads)| (week, downloads as i64))
+        .collect();
+    weeks.drain(..weeks.len().saturating_sub(SPARKLINE_WEEKS));
+    let (Some(&(first, _)), Some(&(last, last_downloads))) = (weeks.first(), weeks.last()) else {
+        return Ok(None);
+    };
+
+    let size = style.sparkline_size;
+    let root = BitMapBackend::new(output_path.as_std_path(), (size.width, size.height))
+        .into_drawing_area();
+    root.fill(&style.background)?;
+
+    // Scale to the range shown, so the shape of recent weeks stands out.
+    let min = weeks.iter().map(|(_, d)| *d).min().unwrap_or(0);
+    let max = weeks
+        .iter()
+        .map(|(_, d)| *d)
+        .max()
+        .unwrap_or(0)
+        .max(min + 1);
+    let padding = (max - min) / 10;
+    // Leave room for the dot marking the latest week.
+    let inset = (size.height / 10).max(style.px(2));
+    let mut chart = ChartBuilder::on(&root).margin(inset).build_cartesian_2d(
+        first..last.max(first.succ_opt().expect("valid date")),
+        min - padding..max + padding,
+    )?;
+
+    let color = style.color(0);
+    chart.draw_series(
+        AreaSeries::new(weeks.iter().copied(), min - padding, color.mix(0.15)).border_style(
+            ShapeStyle {
+                color: color.to_rgba(),
+                filled: false,
+                stroke_width: style.px(2),
+            },
+        ),
+    )?;
+    chart.draw_series(std::iter::once(Circle::new(
+        (last, last_downloads),
+        inset.min(style.px(4)),
+        color.filled(),
+    )))?;
+
+    root.present()?;
+    report_written(output_path);
+
+    let mut data = ChartData::new(
+        format!("Weekly Downloads - Last {} Weeks", SPARKLINE_WEEKS),
+        Layout::Lines,
+        Unit::Downloads,
+    );
+    data.resolution = Some(ChartResolution::Weekly);
+    let points = weeks.iter().map(|&(week, d)| (week, d as f64));
+    data.push("Downloads per week", color.rgb(), points);
+    Ok(Some(data))
+}
+
+/// Generate a downloads badge SVG showing the headline total downloads.
+fn generate_downloads_badge(
+    conn: &Connection,
+    headline: &HeadlineConfig,
+    output_path: &Utf8Path,
+) -> Result<()> {
+    let total = headline::compute(conn, headline)?.total;
+    let total_str = format::number_for(Target::Charts, total);
+
+    let label = "downloads";
+    let label_width = 75;
+    let value_width = total_str.len() * 7 + 20;
+    let total_width = label_width + value_width;
+    let label_x = label_width / 2;
+    let value_x = label_width + value_width / 2;
 
     let svg = format!(
         "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{total_width}\" height=\"20\">
@@ -603,23 +2576,177 @@ fn generate_downloads_badge(conn: &Connection, output_path: &Utf8Path) -> Result
     std::fs::write(output_path.as_std_path(), svg)
         .with_context(|| format!("failed to write badge to {}", output_path))?;
 
-    println!("  • downloads-badge.svg ({} total)", total_str);
+    output::written(
+        output_path,
+        format!("  • downloads-badge.svg ({} total)", total_str),
+    );
     Ok(())
 }
 
-/// Format a number with thousands separators.
-fn format_number(n: u64) -> String {
-    let s = n.to_string();
-    let mut result = String::new();
-    let mut count = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    for c in s.chars().rev() {
-        if count > 0 && count % 3 == 0 {
-            result.push(',');
+    fn charts_config(font_family: &str, font_file: Option<&str>) -> ChartsConfig {
+        ChartsConfig {
+            font_family: font_family.to_string(),
+            font_file: font_file.map(Utf8PathBuf::from),
+            ..ChartsConfig::default()
         }
-        result.push(c);
-        count += 1;
     }
 
-    result.chars().rev().collect()
+    #[test]
+    fn test_font_bytes() {
+        let builtin = font_bytes(&ChartsConfig::default()).unwrap();
+        assert_eq!(builtin, BUILTIN_FONT);
+
+        let from_file = font_bytes(&charts_config("Inter", Some("fonts/Inter-Regular.ttf")));
+        assert_eq!(from_file.unwrap(), BUILTIN_FONT);
+
+        let err = font_bytes(&charts_config("DejaVu Sans", None)).unwrap_err();
+        assert!(err.to_string().contains("isn't built in"), "{err}");
+
+        let err = font_bytes(&charts_config(
+            "DejaVu Sans",
+            Some("fonts/Inter-Regular.ttf"),
+        ))
+        .unwrap_err();
+        assert!(err.to_string().contains("of font family 'Inter'"), "{err}");
+
+        let err = font_bytes(&charts_config("Inter", Some("fonts/missing.ttf"))).unwrap_err();
+        assert!(err.to_string().contains("failed to read"), "{err}");
+    }
+
+    #[test]
+    fn test_load_custom_series() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let error = |query| {
+            format!(
+                "{:#}",
+                load_custom_series(&conn, "custom", query).unwrap_err()
+            )
+        };
+        assert!(error("DELETE FROM weekly_stats").contains("must not modify"));
+        assert!(error("CREATE TABLE t (x)").contains("must not modify"));
+        assert!(error("SELECT '2026-05-04'").contains("found 1 columns"));
+        assert!(error("SELECT 1, 2, 3, 4").contains("found 4 columns"));
+        assert!(error("SELECT '05/04/2026', 1").contains("failed to parse date '05/04/2026'"));
+
+        // Series are in the order they first appear, with values for the same
+        // date added up and null values skipped.
+        let lines = load_custom_series(
+            &conn,
+            "custom",
+            "SELECT * FROM (VALUES
+                ('2026-05-11', 2.0, 'b'),
+                ('2026-05-04', 1.0, 'a'),
+                ('2026-05-04', 3.0, 'a'),
+                ('2026-05-04', NULL, 'b'),
+                ('2026-05-11', 4.0, 'a'))",
+        )
+        .unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2026, 5, day).unwrap();
+        assert_eq!(
+            lines,
+            [
+                ("b".to_string(), vec![(date(11), 2.0)]),
+                ("a".to_string(), vec![(date(4), 4.0), (date(11), 4.0)]),
+            ]
+        );
+
+        // Without a series column, there's one series named after the chart.
+        let lines = load_custom_series(&conn, "custom", "SELECT '2026-05-04', 1.5").unwrap();
+        assert_eq!(lines, [("custom".to_string(), vec![(date(4), 1.5)])]);
+    }
+
+    #[test]
+    fn test_generate_custom() {
+        prepare_font(&ChartsConfig::default()).unwrap();
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-custom-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output_path = dir.join("custom.png");
+
+        let output = ChartOutputConfig {
+            since: Some("2026-05-10".parse().unwrap()),
+            ..ChartOutputConfig::default_at(dir.clone())
+        };
+        let today = NaiveDate::from_ymd_opt(2026, 6, 1).unwrap();
+        let style = Style::new(&ChartsConfig::default(), &output, today);
+        let custom = |query: &str| CustomChartConfig {
+            title: "Custom".to_string(),
+            query: query.to_string(),
+            format: MetricFormat::Number,
+        };
+
+        // Series with no values in range are left out.
+        let data = generate_custom(
+            &conn,
+            "custom",
+            &custom(
+                "SELECT * FROM (VALUES
+                    ('2026-05-04', 1.0, 'old'),
+                    ('2026-05-11', 2.0, 'new'),
+                    ('2026-05-18', 3.0, 'new'))",
+            ),
+            &output_path,
+            &style,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(data.title, "Custom");
+        let labels: Vec<_> = data.series.iter().map(|s| s.label.as_str()).collect();
+        assert_eq!(labels, ["new"]);
+        assert!(output_path.exists());
+
+        // Nothing is drawn without values in range.
+        let none = generate_custom(
+            &conn,
+            "custom",
+            &custom("SELECT '2026-05-04', 1.0"),
+            &dir.join("none.png"),
+            &style,
+        )
+        .unwrap();
+        assert!(none.is_none());
+        assert!(!dir.join("none.png").exists());
+
+        let err = generate_custom(
+            &conn,
+            "broken",
+            &custom("DELETE FROM weekly_stats"),
+            &output_path,
+            &style,
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to run the query of custom chart 'broken'"
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_prepare_font_draws_text() {
+        prepare_font(&ChartsConfig::default()).unwrap();
+
+        let (width, height) = (120, 40);
+        let mut buffer = vec![255; width * height * 3];
+        {
+            let root = BitMapBackend::with_buffer(&mut buffer, (width as u32, height as u32))
+                .into_drawing_area();
+            for family in ["Inter", "sans-serif"] {
+                root.draw(&Text::new(
+                    "1234",
+                    (10, 10),
+                    (family, 20).into_font().color(&BLACK),
+                ))
+                .unwrap();
+            }
+            root.present().unwrap();
+        }
+        assert!(buffer.iter().any(|&byte| byte < 128), "no text was drawn");
+    }
 }
@@ -3,148 +3,1911 @@
 
 //! Command implementations.
 
-use crate::{aggregate, charts, config, crates_io, db, github};
-use anyhow::{Context, Result};
-use camino::Utf8Path;
-use chrono::Utc;
+use crate::{
+    aggregate, alerts, amend, analysis, archive, charts, config, crates_io, db, format, github,
+    headline, installer, issues, metrics, notify,
+    output::status,
+    page,
+    progress::Progress,
+    provenance::{self, Provenance},
+    publish,
+    summary::{CollectionSummary, RefreshSummary, SourceFailure, SourceSummary, StepStatus},
+    upload::{self, S3Location},
+    wayback,
+};
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
+use futures_util::{StreamExt, stream};
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+
+/// Options for the collect command.
+#[derive(Debug)]
+pub struct CollectOptions {
+    pub skip_github: bool,
+    pub skip_crates: bool,
+    pub skip_installer: bool,
+    pub skip_aggregation: bool,
+    /// Stop collecting new sources once this much time has passed.
+    pub time_budget: Option<Duration>,
+    /// How many sources to fetch at once.
+    pub parallelism: usize,
+    /// Only collect these configured sources, if any are given.
+    pub only: Vec<config::SourceRef>,
+    /// Fail the run if any source fails, rather than only if all of them do.
+    pub strict: bool,
+    /// Stop waiting on the network once this much time has passed since the
+    /// run started: sources still being fetched are deferred, and
+    /// notifications and issue filing still to be done are skipped.
+    pub deadline: Option<Duration>,
+    /// Record GitHub snapshots for this date rather than today, such as to
+    /// make up for a run that failed.
+    pub as_of: Option<chrono::NaiveDate>,
+}
+
+/// The number of sources fetched at once unless configured otherwise.
+pub const DEFAULT_PARALLELISM: usize = 4;
+
+impl Default for CollectOptions {
+    fn default() -> Self {
+        Self {
+            skip_github: false,
+            skip_crates: false,
+            skip_installer: false,
+            skip_aggregation: false,
+            time_budget: None,
+            parallelism: DEFAULT_PARALLELISM,
+            only: Vec::new(),
+            strict: false,
+            deadline: None,
+            as_of: None,
+        }
+    }
+}
 
 /// Run the collect command.
 pub async fn run_collect(
     database: &Utf8Path,
     config: &config::Config,
-    skip_github: bool,
-    skip_crates: bool,
-    skip_aggregation: bool,
+    options: &CollectOptions,
+) -> Result<CollectionSummary> {
+    let deadline = options.deadline.map(|deadline| Instant::now() + deadline);
+    let channels = notify::load_channels(&config.notification)?;
+
+    status!("Initializing database at {}", database);
+    let conn = db::init_db(database).context("failed to initialize database")?;
+    claim_unattributed_github_rows(&conn, config)?;
+
+    let started_at = Utc::now();
+    let today = match options.as_of {
+        Some(as_of) => {
+            check_as_of(&conn, as_of, started_at.date_naive())?;
+            status!("Recording GitHub snapshots as of {}", as_of);
+            as_of
+        }
+        None => started_at.date_naive(),
+    };
+    let result = collect_all(&conn, config, today, options, deadline).await;
+
+    let error = result.as_ref().err().map(|err| format!("{:#}", err));
+    db::insert_collection_run(&conn, started_at, Utc::now(), error.as_deref())?;
+    if let Ok(summary) = &result {
+        for source in &summary.sources {
+            let name = format!("{}:{}", source.kind, source.name);
+            db::insert_run_skips(&conn, started_at, &name, &source.skipped)?;
+        }
+    }
+
+    let summary = match result {
+        Ok(summary) => summary,
+        Err(err) => {
+            if let Some(issue_config) = &config.issue_filing
+                && let Some(Err(issue_err)) = before_deadline(
+                    deadline,
+                    "filing a collection failure issue",
+                    issues::report_failure(&conn, issue_config),
+                )
+                .await
+            {
+                eprintln!(
+                    "\nWarning: failed to file collection failure issue: {:#}",
+                    issue_err
+                );
+            }
+            if !channels.is_empty() {
+                let error = error.as_deref().unwrap_or_default();
+                // This run has been recorded, so it's counted.
+                let consecutive_failures =
+                    db::get_consecutive_failures(&conn).map_or(1, |failures| failures.len());
+                let messages = notify::render_failure(&channels, error, consecutive_failures);
+                let event = notify::Event::Failed {
+                    error,
+                    consecutive_failures,
+                };
+                before_deadline(
+                    deadline,
+                    "notifications",
+                    notify::deliver_all(&messages, &event),
+                )
+                .await;
+            }
+            return Err(err);
+        }
+    };
+
+    if !summary.deferred.is_empty() {
+        status!(
+            "\nOut of time; deferred to the next run: {}",
+            summary.deferred.join(", ")
+        );
+    }
+    if !summary.failures.is_empty() {
+        status!(
+            "\nCollection finished with {} failed sources.",
+            summary.failures.len()
+        );
+    } else if summary.deferred.is_empty() {
+        status!("\nCollection complete.");
+    }
+
+    let archived: Vec<_> = summary
+        .sources
+        .iter()
+        .flat_map(|source| &source.archived)
+        .collect();
+    if !archived.is_empty() {
+        status!("\nNo longer listed by GitHub (history kept):");
+        for asset in archived {
+            status!(
+                "  {}/{} (last seen {})",
+                asset.release_tag,
+                asset.asset_name,
+                asset.last_seen
+            );
+        }
+    }
+
+    let tombstones: Vec<_> = summary
+        .sources
+        .iter()
+        .flat_map(|source| &source.tombstones)
+        .collect();
+    if !tombstones.is_empty() {
+        status!("\ncrates.io versions deleted, yanked, or restored (history kept):");
+        for tombstone in tombstones {
+            status!(
+                "  {} {}: {} ({} downloads when last listed)",
+                tombstone.identifier,
+                tombstone.artifact,
+                tombstone.event.as_str(),
+                format::number(tombstone.last_count)
+            );
+        }
+    }
+
+    let owner_changes: Vec<_> = summary
+        .sources
+        .iter()
+        .flat_map(|source| &source.owner_changes)
+        .collect();
+    if !owner_changes.is_empty() {
+        status!("\ncrates.io owner changes:");
+        for change in owner_changes {
+            let unexpected = if summary.unexpected_owners.contains(change) {
+                " (unexpected)"
+            } else {
+                ""
+            };
+            status!(
+                "  {}: {} {} ({}){}",
+                change.crate_name,
+                change.change.as_str(),
+                change.login,
+                change.kind,
+                unexpected
+            );
+        }
+    }
+
+    if !summary.alerts.is_empty() {
+        status!("\nAlerts:");
+        for alert in &summary.alerts {
+            status!("  {}: {}", alert.name, alert.message);
+        }
+    }
+
+    if !channels.is_empty() {
+        status!("\nNotifications:");
+        let messages = notify::render_all(&channels, &summary)?;
+        for message in &messages {
+            status!("  [{}]", message.channel);
+            for line in message.body.lines() {
+                status!("    {}", line);
+            }
+        }
+        let event = notify::Event::Collected { summary: &summary };
+        before_deadline(
+            deadline,
+            "notifications",
+            notify::deliver_all(&messages, &event),
+        )
+        .await;
+    }
+
+    Ok(summary)
+}
+
+/// Check that GitHub snapshots can be recorded for `as_of`.
+///
+/// GitHub download counts are cumulative, so today's counts recorded before
+/// a later snapshot would look like the counts went backwards.
+fn check_as_of(
+    conn: &rusqlite::Connection,
+    as_of: chrono::NaiveDate,
+    today: chrono::NaiveDate,
 ) -> Result<()> {
-    println!("Initializing database at {}", database);
+    if as_of > today {
+        bail!("--as-of {} is in the future", as_of);
+    }
+    if let Some(latest) = db::get_latest_github_snapshot_date(conn)?
+        && latest > as_of
+    {
+        bail!(
+            "can't record GitHub snapshots as of {}, since there are snapshots from {}; \
+             delete those with `db amend github --date {} --delete` first",
+            as_of,
+            latest,
+            latest
+        );
+    }
+    Ok(())
+}
+
+/// Wait for `future` unless `deadline` passes first, in which case `what` is
+/// reported as skipped.
+async fn before_deadline<T>(
+    deadline: Option<Instant>,
+    what: &str,
+    future: impl Future<Output = T>,
+) -> Option<T> {
+    let Some(deadline) = deadline else {
+        return Some(future.await);
+    };
+    match tokio::time::timeout_at(deadline.into(), future).await {
+        Ok(output) => Some(output),
+        Err(_) => {
+            eprintln!("\nWarning: deadline reached, skipping {}", what);
+            None
+        }
+    }
+}
+
+/// Run `collect --releases-only`: check the most recent releases of each
+/// GitHub source, record releases published since the last check, and take a
+/// snapshot of the newest release's downloads.
+///
+/// This is meant to run far more often than a full collection, so it leaves
+/// daily snapshots, aggregates, and collector state alone.
+pub async fn run_collect_releases(database: &Utf8Path, config: &config::Config) -> Result<()> {
+    let repos: Vec<_> = config
+        .source
+        .iter()
+        .filter(|source| source.schedule().enabled)
+        .filter_map(|source| match source {
+            config::CollectionSource::Github {
+                owner,
+                repo,
+                api,
+                releases,
+                ..
+            } => Some((owner, repo, api, releases)),
+            _ => None,
+        })
+        .collect();
+    if repos.is_empty() {
+        bail!("no enabled GitHub sources configured");
+    }
+
+    status!("Initializing database at {}", database);
     let conn = db::init_db(database).context("failed to initialize database")?;
 
-    let today = Utc::now().date_naive();
+    for (owner, repo, api, filter) in repos {
+        status!("\nChecking recent releases of {}/{}...", owner, repo);
+        let filter = github::ReleaseFilter::new(filter)?;
+        collect_recent_releases(&conn, owner, repo, api, &filter).await?;
+    }
+    Ok(())
+}
+
+/// Collect all sources and compute aggregates.
+///
+/// Up to `options.parallelism` sources are fetched at once, started in order
+/// of staleness, least recently collected first, so that if the time budget
+/// runs out the next run picks up where this one left off. Each source is
+/// written to the database as its fetches complete, one at a time.
+///
+/// A source that fails doesn't stop the others from being collected. The run
+/// fails once every source has been tried if all of them failed, or with
+/// `options.strict` if any did.
+///
+/// Fetches stop at the earlier of the end of the time budget and `deadline`.
+async fn collect_all(
+    conn: &rusqlite::Connection,
+    config: &config::Config,
+    today: chrono::NaiveDate,
+    options: &CollectOptions,
+    deadline: Option<Instant>,
+) -> Result<CollectionSummary> {
+    let mut summary = CollectionSummary::new(today);
+    let budget_end = options.time_budget.map(|budget| Instant::now() + budget);
+    let deadline = budget_end.into_iter().chain(deadline).min();
+    for only in &options.only {
+        if !config.source.iter().any(|source| only.matches(source)) {
+            bail!("'{}' is not a configured source", only);
+        }
+    }
+    // Excluded assets are counted as they're collected.
+    db::replace_asset_exclusions(conn, &config.aggregation.exclude_assets)?;
+
+    let now = Utc::now();
+    let mut sources = Vec::new();
+    let mut disabled = Vec::new();
+    let mut not_due = Vec::new();
+    for source in &config.source {
+        let skipped = match source {
+            config::CollectionSource::Github { .. } => options.skip_github,
+            config::CollectionSource::Crates { .. } => options.skip_crates,
+            config::CollectionSource::Installer { .. } => options.skip_installer,
+        };
+        let named = options.only.iter().any(|only| only.matches(source));
+        if skipped || (!options.only.is_empty() && !named) {
+            continue;
+        }
+        let last_success = db::get_source_last_success(conn, source.kind(), &source.name())?;
 
-    if !skip_github {
-        println!("\nCollecting GitHub release statistics...");
-        for (owner, repo) in config.github_sources() {
-            println!("  {}/{}", owner, repo);
-            collect_github_stats(&conn, today, owner, repo).await?;
+        // Sources named with `--only` are collected whatever their schedule.
+        let schedule = source.schedule();
+        if !named && !schedule.enabled {
+            disabled.push(format!("{}:{}", source.kind(), source.name()));
+            continue;
+        }
+        if !named
+            && let Some(frequency) = schedule.frequency
+            && let Some(last) = last_success
+                .as_deref()
+                .and_then(|last| DateTime::parse_from_rfc3339(last).ok())
+            && !frequency.is_due(last.to_utc(), now)
+        {
+            not_due.push(format!(
+                "{}:{} ({})",
+                source.kind(),
+                source.name(),
+                frequency.as_str()
+            ));
+            continue;
         }
+        sources.push((last_success, source));
+    }
+    if !disabled.is_empty() {
+        status!("\nSkipping disabled sources: {}", disabled.join(", "));
+    }
+    if !not_due.is_empty() {
+        status!(
+            "\nSkipping sources already collected this period: {}",
+            not_due.join(", ")
+        );
+    }
+    // `None` (never collected) sorts first. The sort is stable, so sources
+    // with equal staleness keep their configured order.
+    sources.sort_by(|a, b| a.0.cmp(&b.0));
+
+    if !sources.is_empty() {
+        status!("\nCollecting statistics...");
+    }
+    let bars = Progress::new(sources.len() as u64, "Collecting");
+    let progress = &bars;
+    // Fetches don't touch the database, so they can run at once. `None` means
+    // the source was deferred for lack of time.
+    let mut fetches = stream::iter(sources)
+        .map(|(_, source)| async move {
+            let spinner = progress.start(format!("{}:{}", source.kind(), source.name()));
+            let fetch = fetch_source(source, &config.github);
+            let fetched = match deadline {
+                // Nothing is written until a source's fetches complete, so
+                // cancelling a fetch partway through leaves the database
+                // intact.
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => {
+                        tokio::time::timeout(remaining, fetch).await.ok()
+                    }
+                    _ => None,
+                },
+                None => Some(fetch.await),
+            };
+            progress.finish(spinner);
+            (source, fetched)
+        })
+        .buffer_unordered(options.parallelism.max(1));
+
+    while let Some((source, fetched)) = fetches.next().await {
+        let kind = source.kind();
+        let name = source.name();
+        status!("  {}:{}", kind, name);
+        let Some(fetched) = fetched else {
+            status!("    Out of time, deferring");
+            summary.deferred.push(format!("{}:{}", kind, name));
+            continue;
+        };
+        // Snapshots recorded for an earlier date weren't taken then, so
+        // they're left without a timestamp.
+        let collected_at = options.as_of.is_none().then(Utc::now);
+        let result = fetched.and_then(|fetched| record_source(conn, today, collected_at, fetched));
+
+        let error;
+        let attempt = match &result {
+            Ok(source) => db::SourceAttempt::Success {
+                latest_date: source.latest_date,
+                etag: source.etag.as_deref(),
+            },
+            Err(err) => {
+                error = format!("{:#}", err);
+                db::SourceAttempt::Failure { error: &error }
+            }
+        };
+        db::record_source_attempt(conn, kind, &name, Utc::now(), attempt)?;
+        match result {
+            Ok(source) => summary.push(source),
+            Err(err) => {
+                status!("    Failed: {:#}", err);
+                summary.failures.push(SourceFailure {
+                    kind,
+                    name,
+                    error: format!("{:#}", err),
+                });
+            }
+        }
+    }
+    drop(fetches);
+    drop(bars);
+
+    print_source_results(&summary);
+    if !summary.failures.is_empty() && (options.strict || summary.sources.is_empty()) {
+        let failures: Vec<_> = summary
+            .failures
+            .iter()
+            .map(|failure| format!("{}:{}: {}", failure.kind, failure.name, failure.error))
+            .collect();
+        bail!(
+            "{} of {} sources failed: {}",
+            failures.len(),
+            failures.len() + summary.sources.len(),
+            failures.join("; ")
+        );
     }
 
-    if !skip_crates {
-        println!("\nCollecting crates.io statistics...");
-        for crate_name in config.crates_sources() {
-            println!("  {}", crate_name);
-            collect_crates_stats(&conn, crate_name).await?;
+    summary.unexpected_owners = summary
+        .sources
+        .iter()
+        .flat_map(|source| &source.owner_changes)
+        .filter(|change| {
+            change.change == db::OwnerChangeKind::Added && !config.owners.is_expected(&change.login)
+        })
+        .cloned()
+        .collect();
+
+    if !options.skip_aggregation {
+        summary.resets = compute_aggregates(conn, config)?;
+        summary.movers = analysis::movers(conn, analysis::DEFAULT_MOVER_MIN_DOWNLOADS, 3)?;
+        summary.metrics = metrics::Metrics::new(&config.metrics)?.latest(conn)?;
+        summary.anomalies = analysis::detect_anomalies(
+            conn,
+            analysis::DEFAULT_ANOMALY_WINDOW,
+            analysis::DEFAULT_ANOMALY_THRESHOLD,
+            Some(1),
+        )?;
+        summary.alerts = alerts::evaluate(conn, &config.alert)?;
+    }
+    summary.headline = headline::compute(conn, &config.headline)?.total;
+    summary.provenance = Provenance::collect(conn, config)?.footer();
+
+    Ok(summary)
+}
+
+/// Print whether each source was collected, failed, or was deferred.
+fn print_source_results(summary: &CollectionSummary) {
+    if summary.failures.is_empty() && summary.deferred.is_empty() {
+        return;
+    }
+    status!(
+        "\nSources: {} collected, {} failed, {} deferred",
+        summary.sources.len(),
+        summary.failures.len(),
+        summary.deferred.len()
+    );
+    for source in &summary.sources {
+        status!(
+            "  ok        {}:{} ({} records)",
+            source.kind,
+            source.name,
+            format::number(source.records)
+        );
+    }
+    for failure in &summary.failures {
+        status!(
+            "  failed    {}:{}: {}",
+            failure.kind,
+            failure.name,
+            failure.error
+        );
+    }
+    for name in &summary.deferred {
+        status!("  deferred  {}", name);
+    }
+}
+
+/// What a source's fetches returned, yet to be written to the database.
+enum Fetched<'a> {
+    Github {
+        owner: &'a str,
+        repo: &'a str,
+        releases: github::ReleaseList,
+        filter: github::ReleaseFilter,
+    },
+    Crates {
+        name: &'a str,
+        crate_data: CrateData,
+    },
+    Installer {
+        name: &'a str,
+        stats: installer::InstallerStats,
+    },
+}
+
+/// Everything fetched from crates.io for a crate.
+struct CrateData {
+    metadata: crates_io::CrateInfo,
+    versions: Vec<crates_io::CrateVersion>,
+    downloads: crates_io::DownloadsResponse,
+    /// `None` if the owners couldn't be fetched, which leaves the recorded
+    /// owners as they were.
+    owners: Option<Vec<crates_io::CrateOwner>>,
+}
+
+async fn fetch_source<'a>(
+    source: &'a config::CollectionSource,
+    github: &config::GithubConfig,
+) -> Result<Fetched<'a>> {
+    Ok(match source {
+        config::CollectionSource::Github {
+            owner,
+            repo,
+            api,
+            releases: filter,
+            ..
+        } => {
+            let filter = github::ReleaseFilter::new(filter)?;
+            let api = github::Api::new(api)?.with_kind(github.api);
+            let releases = github::fetch_releases(&api, owner, repo)
+                .await
+                .context("failed to fetch GitHub releases")?;
+            Fetched::Github {
+                owner,
+                repo,
+                releases,
+                filter,
+            }
+        }
+        config::CollectionSource::Crates { name, registry, .. } => Fetched::Crates {
+            name,
+            crate_data: fetch_crate_data(registry, name).await?,
+        },
+        config::CollectionSource::Installer {
+            name, stats_url, ..
+        } => {
+            let stats = installer::fetch_daily_downloads(stats_url)
+                .await
+                .with_context(|| format!("failed to fetch installer stats for '{}'", name))?;
+            Fetched::Installer { name, stats }
+        }
+    })
+}
+
+fn record_source(
+    conn: &rusqlite::Connection,
+    today: chrono::NaiveDate,
+    collected_at: Option<DateTime<Utc>>,
+    fetched: Fetched<'_>,
+) -> Result<SourceSummary> {
+    match fetched {
+        Fetched::Github {
+            owner,
+            repo,
+            releases,
+            filter,
+        } => record_github_stats(conn, today, collected_at, owner, repo, releases, &filter),
+        Fetched::Crates { name, crate_data } => record_crates_stats(conn, name, crate_data),
+        Fetched::Installer { name, stats } => record_installer_stats(conn, name, stats),
+    }
+}
+
+/// Attribute GitHub rows recorded before rows were stored per repository to
+/// the configured GitHub repository, if there's only one.
+fn claim_unattributed_github_rows(
+    conn: &rusqlite::Connection,
+    config: &config::Config,
+) -> Result<()> {
+    let mut sources = config.github_sources();
+    let (Some((owner, repo)), None) = (sources.next(), sources.next()) else {
+        return Ok(());
+    };
+    let repo = format!("{}/{}", owner, repo);
+    let claimed = db::claim_unattributed_github_rows(conn, &repo)?;
+    if claimed > 0 {
+        status!(
+            "Attributed {} GitHub rows recorded before repositories were to {}",
+            format::number(claimed as u64),
+            repo
+        );
+    }
+    Ok(())
+}
+
+/// Compute all derived tables, returning the GitHub count resets detected
+/// for the first time.
+fn compute_aggregates(
+    conn: &rusqlite::Connection,
+    config: &config::Config,
+) -> Result<Vec<db::CountReset>> {
+    let aggregation = &config.aggregation;
+    claim_unattributed_github_rows(conn, config)?;
+    db::replace_asset_exclusions(conn, &aggregation.exclude_assets)?;
+    db::replace_project_sources(conn, &config.project)?;
+    status!("\nComputing GitHub daily deltas...");
+    let resets = aggregate::compute_github_daily(conn, aggregation)?;
+    if !resets.is_empty() {
+        let handling = match aggregation.resets {
+            config::ResetHandling::Discard => "discarding the interval",
+            config::ResetHandling::Baseline => "counting the new total as a baseline",
+        };
+        eprintln!(
+            "  Warning: {} assets had decreasing download counts (deleted or re-uploaded?), {}:",
+            resets.len(),
+            handling
+        );
+        for reset in &resets {
+            eprintln!(
+                "    {}/{} on {}: {} -> {}",
+                reset.release_tag,
+                reset.asset_name,
+                reset.date,
+                format::number(reset.previous_count as u64),
+                format::number(reset.count as u64)
+            );
+        }
+    }
+    status!("Computing weekly aggregates...");
+    if let Some(previous) = aggregate::compute_all_weekly(conn, aggregation)? {
+        status!(
+            "  Week convention changed from '{}' to '{}', rebuilt all weekly aggregates",
+            previous,
+            aggregation.week_start.as_str()
+        );
+    }
+    status!("Computing monthly aggregates...");
+    aggregate::compute_all_monthly(conn, aggregation)?;
+    status!("Computing rolling averages...");
+    aggregate::compute_rolling_averages(conn, aggregation)?;
+    Ok(resets)
+}
+
+/// Run the reattribute command: rewrite numeric crates.io version IDs to
+/// semver strings now that mappings are known, then recompute aggregates.
+pub fn run_reattribute(
+    database: &Utf8Path,
+    config: &config::Config,
+    dry_run: bool,
+    skip_aggregation: bool,
+) -> Result<()> {
+    let conn = db::init_db(database).context("failed to open database")?;
+
+    let result = db::reattribute_crate_versions(&conn, dry_run)?;
+    let verb = if dry_run { "Would rewrite" } else { "Rewrote" };
+    status!(
+        "{} {} crates.io download rows to semver versions",
+        verb,
+        format::number(result.updated as u64)
+    );
+    if result.duplicates > 0 {
+        let verb = if dry_run { "Would remove" } else { "Removed" };
+        status!(
+            "{} {} rows already recorded under their semver version",
+            verb,
+            format::number(result.duplicates as u64)
+        );
+    }
+
+    if !dry_run && !skip_aggregation && result != db::Reattribution::default() {
+        compute_aggregates(&conn, config)?;
+    }
+
+    Ok(())
+}
+
+/// Run the `db amend` command: delete or overwrite the data recorded for a
+/// source on one date, then recompute aggregates.
+pub fn run_amend(
+    database: &Utf8Path,
+    config: &config::Config,
+    amendment: &amend::Amendment,
+    dry_run: bool,
+    skip_aggregation: bool,
+) -> Result<()> {
+    let conn = db::init_db(database).context("failed to open database")?;
+
+    let amended = amend::amend(&conn, amendment, config.aggregation.week_start, dry_run)?;
+    let described = match (&amendment.release, &amendment.asset, &amendment.version) {
+        (Some(release), Some(asset), _) => format!("{} {}/{}", amendment.source, release, asset),
+        (Some(release), None, _) => format!("{} {}", amendment.source, release),
+        (None, Some(asset), _) => format!("{} asset {}", amendment.source, asset),
+        (None, None, Some(version)) => format!("{} {}", amendment.source, version),
+        (None, None, None) => amendment.source.to_string(),
+    };
+    match amendment.change {
+        amend::Change::Delete if amended.total_rows() == 0 => {
+            status!("No data for {} on {}", described, amendment.date);
+            return Ok(());
+        }
+        amend::Change::Delete => {
+            let rows: Vec<String> = amended
+                .rows
+                .iter()
+                .filter(|(_, count)| *count > 0)
+                .map(|(table, count)| format!("{} {}", format::number(*count as u64), table))
+                .collect();
+            status!(
+                "{} {} rows for {} on {} ({})",
+                if dry_run { "Would delete" } else { "Deleted" },
+                format::number(amended.total_rows() as u64),
+                described,
+                amendment.date,
+                rows.join(", ")
+            );
+        }
+        amend::Change::Set(count) => {
+            status!(
+                "{} {} on {} to {} ({})",
+                if dry_run { "Would set" } else { "Set" },
+                described,
+                amendment.date,
+                format::number(count),
+                match amended.previous {
+                    Some(previous) => format!("was {}", format::number(previous)),
+                    None => "new row".to_string(),
+                }
+            );
+        }
+    }
+
+    if dry_run || skip_aggregation {
+        return Ok(());
+    }
+    let weeks = match (amended.weeks.first(), amended.weeks.last()) {
+        (Some(first), Some(last)) if first != last => {
+            format!("the weeks of {} through {}", first, last)
+        }
+        (Some(week), _) => format!("the week of {}", week),
+        (None, _) => "all weeks".to_string(),
+    };
+    status!("Recomputing aggregates for {}...", weeks);
+    compute_aggregates(&conn, config)?;
+    Ok(())
+}
+
+/// Run the archive command: move raw rows from years that ended more than
+/// `older_than` years ago into per-year archives.
+pub fn run_archive(database: &Utf8Path, older_than: u32, dry_run: bool) -> Result<()> {
+    let conn = db::init_db(database).context("failed to open database")?;
+
+    let cutoff = archive::cutoff(Utc::now().date_naive(), older_than);
+    let archived = archive::archive_before(&conn, database, cutoff, dry_run)?;
+    if archived.is_empty() {
+        status!("No rows before {} to archive", cutoff);
+        return Ok(());
+    }
+
+    let verb = if dry_run { "Would move" } else { "Moved" };
+    for year in &archived {
+        let rows: Vec<String> = year
+            .rows
+            .iter()
+            .map(|(table, count)| format!("{} {}", format::number(*count as u64), table))
+            .collect();
+        status!(
+            "{} {}: {} rows ({}) to {}",
+            verb,
+            year.year,
+            format::number(year.rows.iter().map(|(_, count)| *count as u64).sum()),
+            rows.join(", "),
+            year.path
+        );
+    }
+    Ok(())
+}
+
+/// Run the wayback backfill command: import GitHub download counts from
+/// archived API responses taken before `before`, which defaults to the first
+/// snapshot the collector took.
+///
+/// This is best-effort: captures that fail to download or parse are skipped,
+/// and existing snapshots are never overwritten.
+pub async fn run_backfill_wayback(
+    database: &Utf8Path,
+    config: &config::Config,
+    before: Option<chrono::NaiveDate>,
+    dry_run: bool,
+    skip_aggregation: bool,
+) -> Result<()> {
+    let conn = db::init_db(database).context("failed to open database")?;
+    let before = match before {
+        Some(before) => Some(before),
+        None => db::first_collected_github_date(&conn)?,
+    };
+
+    let mut inserted = 0;
+    for source in &config.source {
+        let config::CollectionSource::Github {
+            owner,
+            repo,
+            releases: filter,
+            ..
+        } = source
+        else {
+            continue;
+        };
+        status!("github:{}/{}", owner, repo);
+        let filter = github::ReleaseFilter::new(filter)?;
+        let full_name = format!("{}/{}", owner, repo);
+
+        let captures: Vec<_> = wayback::list_captures(owner, repo)
+            .await?
+            .into_iter()
+            .filter(|capture| match (capture.date(), before) {
+                (Ok(date), Some(before)) => date < before,
+                (Ok(_), None) => true,
+                (Err(_), _) => false,
+            })
+            .collect();
+        match before {
+            Some(before) => status!("  Found {} captures before {}", captures.len(), before),
+            None => status!("  Found {} captures", captures.len()),
+        }
+
+        let progress = Progress::new(captures.len() as u64, "Fetching captures");
+        let mut fetched = Vec::new();
+        for capture in &captures {
+            let spinner = progress.start(format!("capture {}", capture.timestamp));
+            match wayback::fetch_capture(capture).await {
+                Ok(captured) => fetched.push(captured),
+                Err(err) => status!("  Skipping capture: {:#}", err),
+            }
+            progress.finish(spinner);
+        }
+        drop(progress);
+
+        let days = wayback::merge_by_day(&fetched)?;
+        let tx = conn.unchecked_transaction()?;
+        for (date, assets) in &days {
+            let mut day_inserted = 0;
+            for ((release_tag, asset_name), count) in assets {
+                // Skip releases and assets the source doesn't collect, as
+                // collection does.
+                if !filter.tracks_release(release_tag) || !filter.tracks_asset(asset_name) {
+                    continue;
+                }
+                if db::insert_backfilled_snapshot(
+                    &tx,
+                    *date,
+                    &full_name,
+                    release_tag,
+                    asset_name,
+                    count.download_count,
+                    db::BackfillOrigin {
+                        source: "wayback",
+                        url: &count.url,
+                    },
+                )? {
+                    day_inserted += 1;
+                }
+            }
+            if day_inserted > 0 {
+                status!("  {}: {} assets", date, day_inserted);
+            }
+            inserted += day_inserted;
+        }
+        if dry_run {
+            tx.rollback()
+                .context("failed to roll back backfilled snapshots")?;
+        } else {
+            tx.commit()
+                .context("failed to commit backfilled snapshots")?;
         }
     }
 
-    if !skip_aggregation {
-        println!("\nComputing weekly aggregates...");
-        aggregate::compute_all_weekly(&conn)?;
+    let verb = if dry_run {
+        "Would backfill"
+    } else {
+        "Backfilled"
+    };
+    status!(
+        "{} {} low-confidence GitHub snapshot rows",
+        verb,
+        format::number(inserted as u64)
+    );
+
+    if !dry_run && !skip_aggregation && inserted > 0 {
+        compute_aggregates(&conn, config)?;
     }
 
-    println!("\nCollection complete.");
     Ok(())
 }
 
+/// Options for the refresh command.
+#[derive(Debug)]
+pub struct RefreshOptions {
+    pub skip_collect: bool,
+    pub skip_github: bool,
+    pub skip_crates: bool,
+    pub skip_installer: bool,
+    pub skip_aggregation: bool,
+    pub skip_charts: bool,
+    pub skip_report: bool,
+    pub skip_publish: bool,
+    pub time_budget: Option<Duration>,
+    /// Stop waiting on the network once this much time has passed since the
+    /// refresh started, as with [`CollectOptions::deadline`]. An upload that
+    /// hasn't finished by then is skipped.
+    pub deadline: Option<Duration>,
+    pub parallelism: usize,
+    pub strict: bool,
+    pub charts: config::ChartOutputConfig,
+    /// The HTML report to write.
+    pub report: page::PageOptions,
+    pub public_output: Utf8PathBuf,
+    /// Where to upload charts and the public snapshot, if anywhere.
+    pub upload: Option<S3Location>,
+}
+
+/// Run the refresh command: collect, aggregate, generate charts, write the
+/// HTML report, publish the public snapshot, and upload, stopping at the first
+/// failure.
+pub async fn run_refresh(
+    database: &Utf8Path,
+    config: &config::Config,
+    options: &RefreshOptions,
+) -> Result<RefreshSummary> {
+    let mut summary = RefreshSummary::default();
+    let result = refresh_steps(database, config, options, &mut summary).await;
+
+    status!("\nRefresh summary:");
+    for step in &summary.steps {
+        let seconds = match step.status {
+            StepStatus::Skipped => String::new(),
+            _ => format!("{:.1}s", step.seconds),
+        };
+        let line = format!(
+            "  {:<10} {:<10} {:>7}  {}",
+            step.step,
+            step.status.as_str(),
+            seconds,
+            step.detail
+        );
+        status!("{}", line.trim_end());
+    }
+
+    result.map(|()| summary)
+}
+
+async fn refresh_steps(
+    database: &Utf8Path,
+    config: &config::Config,
+    options: &RefreshOptions,
+    summary: &mut RefreshSummary,
+) -> Result<()> {
+    let deadline = options.deadline.map(|deadline| Instant::now() + deadline);
+    if options.skip_collect {
+        summary.skip("collect");
+    } else {
+        let started = Instant::now();
+        let collect_options = CollectOptions {
+            skip_github: options.skip_github,
+            skip_crates: options.skip_crates,
+            skip_installer: options.skip_installer,
+            skip_aggregation: true,
+            time_budget: options.time_budget,
+            parallelism: options.parallelism,
+            only: Vec::new(),
+            strict: options.strict,
+            // Collection is the first step, so its deadline is the refresh's.
+            deadline: options.deadline,
+            as_of: None,
+        };
+        let result = run_collect(database, config, &collect_options)
+            .await
+            .map(|collected| {
+                let mut detail = format!(
+                    "{} records from {} sources",
+                    format::number(collected.records),
+                    collected.sources.len()
+                );
+                if !collected.failures.is_empty() {
+                    detail.push_str(&format!(", {} failed", collected.failures.len()));
+                }
+                if !collected.deferred.is_empty() {
+                    detail.push_str(&format!(", {} deferred", collected.deferred.len()));
+                }
+                detail
+            });
+        summary.record("collect", started, result)?;
+    }
+
+    let conn = db::init_db(database).context("failed to open database")?;
+
+    if options.skip_aggregation {
+        summary.skip("aggregate");
+    } else {
+        let started = Instant::now();
+        let result = compute_aggregates(&conn, config).map(|resets| match resets.len() {
+            0 => String::new(),
+            n => format!("{} new count resets", n),
+        });
+        summary.record("aggregate", started, result)?;
+    }
+
+    if options.skip_charts {
+        summary.skip("charts");
+    } else {
+        let started = Instant::now();
+        let result = metrics::Metrics::new(&config.metrics)
+            .and_then(|metrics| {
+                let provenance = Provenance::collect(&conn, config)?;
+                generate_charts(&conn, config, &metrics, &options.charts, &provenance)
+            })
+            .map(|()| format!("written to {}", options.charts.path));
+        summary.record("charts", started, result)?;
+    }
+
+    if options.skip_report {
+        summary.skip("report");
+    } else {
+        let started = Instant::now();
+        let result = page::run_html(&conn, config, &options.report)
+            .map(|()| format!("written to {}", options.report.output));
+        summary.record("report", started, result)?;
+    }
+
+    if options.skip_publish {
+        summary.skip("publish");
+    } else {
+        let started = Instant::now();
+        status!();
+        let result = Provenance::collect(&conn, config)
+            .and_then(|provenance| {
+                publish::export_public(
+                    &conn,
+                    &config.public_export,
+                    &provenance,
+                    &options.public_output,
+                )
+            })
+            .map(|manifest| {
+                format!(
+                    "{} tables written to {}",
+                    manifest.tables.len(),
+                    options.public_output
+                )
+            });
+        summary.record("publish", started, result)?;
+    }
+
+    match &options.upload {
+        Some(location) => {
+            let started = Instant::now();
+            match before_deadline(deadline, "upload", upload_outputs(options, location)).await {
+                Some(result) => summary.record("upload", started, result)?,
+                None => summary.skip("upload"),
+            }
+        }
+        None => summary.skip("upload"),
+    }
+
+    Ok(())
+}
+
+/// Upload the outputs of the steps that ran.
+async fn upload_outputs(options: &RefreshOptions, location: &S3Location) -> Result<String> {
+    let mut uploaded = 0;
+    if !options.skip_charts {
+        uploaded += upload::upload_dir(&options.charts.path, &location.join("charts")).await?;
+    }
+    if !options.skip_publish {
+        uploaded += upload::upload_dir(&options.public_output, &location.join("public")).await?;
+    }
+    Ok(format!("{} files uploaded to {}", uploaded, location))
+}
+
 /// Run the charts command.
-pub fn run_charts(database: &Utf8Path, output_dir: &Utf8Path) -> Result<()> {
+pub fn run_charts(
+    database: &Utf8Path,
+    config: &config::Config,
+    outputs: &[config::ChartOutputConfig],
+) -> Result<()> {
     let conn = db::init_db(database).context("failed to open database")?;
-    charts::generate_all_charts(&conn, output_dir)?;
+    let metrics = metrics::Metrics::new(&config.metrics)?;
+    let provenance = Provenance::collect(&conn, config)?;
+    for output in outputs {
+        generate_charts(&conn, config, &metrics, output, &provenance)?;
+    }
     Ok(())
 }
 
-async fn collect_github_stats(
+/// Generate a set of charts, recording their provenance in the output
+/// directory.
+fn generate_charts(
+    conn: &rusqlite::Connection,
+    config: &config::Config,
+    metrics: &metrics::Metrics,
+    output: &config::ChartOutputConfig,
+    provenance: &Provenance,
+) -> Result<()> {
+    charts::generate_charts(
+        conn,
+        &config.charts,
+        metrics,
+        &config.headline,
+        output,
+        provenance,
+    )?;
+    provenance.write(&output.path.join(provenance::PROVENANCE_FILE))
+}
+
+fn record_github_stats(
     conn: &rusqlite::Connection,
     today: chrono::NaiveDate,
+    collected_at: Option<DateTime<Utc>>,
     owner: &str,
     repo: &str,
-) -> Result<()> {
-    let releases = github::fetch_releases(owner, repo)
-        .await
-        .context("failed to fetch GitHub releases")?;
-
-    println!("  Found {} releases", releases.len());
+    github::ReleaseList { releases, etag }: github::ReleaseList,
+    filter: &github::ReleaseFilter,
+) -> Result<SourceSummary> {
+    status!("  Found {} releases", releases.len());
 
     let mut total_assets = 0;
     let mut total_downloads = 0;
+    let mut superseded = 0;
+    let mut untracked = db::FilterSkip::new("tag-filter");
+    let mut unmatched = db::FilterSkip::new("asset-filter");
+    let mut excluded = db::FilterSkip::new("exclude-assets");
+    let full_name = format!("{}/{}", owner, repo);
 
+    let tx = conn.unchecked_transaction()?;
     for release in releases {
-        // Skip non-cargo-nextest releases.
-        if !release.tag_name.starts_with("cargo-nextest-") {
+        if !filter.tracks_release(&release.tag_name) {
+            untracked.skipped += 1;
+            untracked.downloads += release.assets.iter().map(|a| a.download_count).sum::<u64>();
             continue;
         }
+        untracked.kept += 1;
 
+        if let Some(published_at) = release.published_at {
+            db::upsert_github_release(&tx, &release.tag_name, published_at, release.prerelease)?;
+        }
         for asset in release.assets {
-            db::insert_github_snapshot(
-                conn,
+            if !filter.tracks_asset(&asset.name) {
+                unmatched.skipped += 1;
+                unmatched.downloads += asset.download_count;
+                continue;
+            }
+            unmatched.kept += 1;
+            if db::insert_github_snapshot(
+                &tx,
                 today,
+                collected_at,
+                &full_name,
                 &release.tag_name,
                 &asset.name,
                 asset.download_count,
-            )?;
+            )? {
+                superseded += 1;
+            }
             total_assets += 1;
             total_downloads += asset.download_count;
+            // Excluded assets are still collected, but left out of totals.
+            if db::is_excluded_asset(&tx, &asset.name)? {
+                excluded.skipped += 1;
+                excluded.downloads += asset.download_count;
+            } else {
+                excluded.kept += 1;
+            }
         }
     }
+    let archived = db::update_github_assets(&tx, today, &full_name)?;
+    tx.commit().context("failed to commit GitHub snapshots")?;
 
-    println!(
+    status!(
         "  Recorded {} assets with {} total downloads",
-        total_assets, total_downloads
+        total_assets,
+        total_downloads
+    );
+    if superseded > 0 {
+        status!(
+            "  {} assets already had a snapshot on {}; kept the latest, the others are in github_superseded",
+            superseded,
+            today
+        );
+    }
+    if untracked.excludes_everything() {
+        eprintln!(
+            "  Warning: none of the {} releases match the source's tag filter",
+            untracked.skipped
+        );
+    }
+    if unmatched.excludes_everything() {
+        eprintln!(
+            "  Warning: none of the {} assets match the source's asset filter",
+            unmatched.skipped
+        );
+    }
+    if excluded.excludes_everything() {
+        eprintln!(
+            "  Warning: all {} assets match aggregation.exclude_assets, so no downloads are counted",
+            excluded.skipped
+        );
+    }
+    if !archived.is_empty() {
+        status!(
+            "  {} assets no longer listed, archived with their history:",
+            archived.len()
+        );
+        for asset in &archived {
+            status!(
+                "    {}/{} (last seen {})",
+                asset.release_tag,
+                asset.asset_name,
+                asset.last_seen
+            );
+        }
+    }
+    Ok(SourceSummary {
+        kind: "github",
+        name: full_name,
+        records: total_assets,
+        downloads: total_downloads,
+        archived,
+        owner_changes: Vec::new(),
+        tombstones: Vec::new(),
+        latest_date: Some(today),
+        etag,
+        skipped: vec![untracked, unmatched, excluded],
+    })
+}
+
+/// Record releases among the most recent ones not seen before, and snapshot
+/// the downloads of the newest.
+async fn collect_recent_releases(
+    conn: &rusqlite::Connection,
+    owner: &str,
+    repo: &str,
+    api: &config::GithubApiConfig,
+    filter: &github::ReleaseFilter,
+) -> Result<()> {
+    let api = github::Api::new(api)?;
+    let releases = github::fetch_recent_releases(&api, owner, repo)
+        .await
+        .context("failed to fetch recent GitHub releases")?;
+    let known: HashSet<String> = db::get_github_releases(conn)?
+        .into_iter()
+        .map(|release| release.release_tag)
+        .collect();
+
+    let collected_at = Utc::now();
+    let tx = conn.unchecked_transaction()?;
+    let mut newest: Option<(DateTime<Utc>, &github::Release)> = None;
+    for release in &releases {
+        // Drafts aren't recorded until they're published.
+        let published_at = match release.published_at {
+            Some(published_at) if filter.tracks_release(&release.tag_name) => published_at,
+            _ => continue,
+        };
+        if !known.contains(&release.tag_name) {
+            status!(
+                "  New release: {} (published {})",
+                release.tag_name,
+                published_at.format("%Y-%m-%d %H:%M UTC")
+            );
+        }
+        db::upsert_github_release(&tx, &release.tag_name, published_at, release.prerelease)?;
+        if newest.is_none_or(|(newest_at, _)| published_at > newest_at) {
+            newest = Some((published_at, release));
+        }
+    }
+
+    let Some((_, release)) = newest else {
+        tx.commit().context("failed to commit GitHub releases")?;
+        status!(
+            "  No published releases among the latest {}",
+            releases.len()
+        );
+        return Ok(());
+    };
+    let full_name = format!("{}/{}", owner, repo);
+    let previous = db::get_github_release_snapshots(&tx, &full_name, &release.tag_name)?.pop();
+    let mut downloads = 0;
+    let mut assets = 0;
+    for asset in release
+        .assets
+        .iter()
+        .filter(|asset| filter.tracks_asset(&asset.name))
+    {
+        db::insert_github_release_snapshot(
+            &tx,
+            &full_name,
+            &release.tag_name,
+            collected_at,
+            &asset.name,
+            asset.download_count,
+        )?;
+        downloads += asset.download_count;
+        assets += 1;
+    }
+    tx.commit()
+        .context("failed to commit GitHub release snapshot")?;
+
+    let change = match previous {
+        Some(previous) => format!(
+            " ({:+} since {})",
+            downloads as i64 - previous.downloads as i64,
+            previous.collected_at.format("%Y-%m-%d %H:%M UTC")
+        ),
+        None => String::new(),
+    };
+    status!(
+        "  {}: {} downloads across {} assets{}",
+        release.tag_name,
+        format::number(downloads),
+        assets,
+        change
     );
     Ok(())
 }
 
-async fn collect_crates_stats(conn: &rusqlite::Connection, crate_name: &str) -> Result<()> {
-    let metadata = crates_io::fetch_crate_metadata(crate_name)
+async fn fetch_crate_data(
+    registry: &config::RegistryConfig,
+    crate_name: &str,
+) -> Result<CrateData> {
+    let registry = crates_io::Registry::new(registry)?;
+    let metadata = crates_io::fetch_crate_metadata(&registry, crate_name)
         .await
         .with_context(|| format!("failed to fetch metadata for '{}'", crate_name))?;
 
-    let today = Utc::now().date_naive();
+    let versions = crates_io::fetch_versions(&registry, crate_name)
+        .await
+        .with_context(|| format!("failed to fetch versions for '{}'", crate_name))?;
+
+    let downloads = crates_io::fetch_downloads(&registry, crate_name)
+        .await
+        .with_context(|| format!("failed to fetch downloads for '{}'", crate_name))?;
+
+    // Owners are only audited, so downloads are still recorded without them.
+    let owners = match crates_io::fetch_owners(&registry, crate_name).await {
+        Ok(owners) => Some(owners),
+        Err(err) => {
+            eprintln!(
+                "  Warning: failed to fetch owners for '{}', leaving them unchecked: {:#}",
+                crate_name, err
+            );
+            None
+        }
+    };
+
+    Ok(CrateData {
+        metadata,
+        versions,
+        downloads,
+        owners,
+    })
+}
+
+fn record_crates_stats(
+    conn: &rusqlite::Connection,
+    crate_name: &str,
+    CrateData {
+        metadata,
+        versions,
+        downloads,
+        owners,
+    }: CrateData,
+) -> Result<SourceSummary> {
+    let tx = conn.unchecked_transaction()?;
+
+    let collected_at = Utc::now();
+    let tombstones =
+        db::update_crate_versions(&tx, collected_at.date_naive(), crate_name, &versions)?;
+    let version_map = db::get_crate_version_map(&tx, crate_name)?;
+    // Days collected before versions were mapped are reported again below.
+    db::reattribute_crate(&tx, crate_name)?;
+
     db::insert_crates_metadata(
-        conn,
-        today,
+        &tx,
+        collected_at.date_naive(),
+        collected_at,
         crate_name,
         metadata.downloads,
         metadata.recent_downloads,
     )?;
 
-    println!(
+    let owner_changes = match &owners {
+        Some(owners) => {
+            let owners: Vec<_> = owners
+                .iter()
+                .map(|owner| (owner.login.as_str(), owner.kind.as_str()))
+                .collect();
+            db::update_crate_owners(&tx, collected_at.date_naive(), crate_name, &owners)?
+        }
+        None => Vec::new(),
+    };
+
+    status!(
         "    Total: {} downloads ({} recent)",
-        format_number(metadata.downloads),
-        format_number(metadata.recent_downloads)
+        format::number(metadata.downloads),
+        format::number(metadata.recent_downloads)
     );
 
-    let downloads = crates_io::fetch_downloads(crate_name)
-        .await
-        .with_context(|| format!("failed to fetch downloads for '{}'", crate_name))?;
-
     let mut records_inserted = 0;
+    let mut latest_date = None;
 
     for vd in downloads.version_downloads {
         let date = crates_io::parse_date(&vd.date)?;
-        let version_str = vd.version.to_string();
-        db::insert_crates_download(conn, date, crate_name, Some(&version_str), vd.downloads)?;
+        latest_date = latest_date.max(Some(date));
+        // Store the semver string where the ID is known, falling back to the ID.
+        let version_str = version_map
+            .get(&vd.version)
+            .cloned()
+            .unwrap_or_else(|| vd.version.to_string());
+        db::insert_crates_download(&tx, date, crate_name, Some(&version_str), vd.downloads)?;
         records_inserted += 1;
     }
 
     for ed in downloads.meta.extra_downloads {
         let date = crates_io::parse_date(&ed.date)?;
-        db::insert_crates_download(conn, date, crate_name, None, ed.downloads)?;
+        latest_date = latest_date.max(Some(date));
+        db::insert_crates_download(&tx, date, crate_name, None, ed.downloads)?;
         records_inserted += 1;
     }
 
-    println!("    Inserted {} daily records", records_inserted);
-    Ok(())
+    tx.commit()
+        .with_context(|| format!("failed to commit downloads for '{}'", crate_name))?;
+
+    status!("    Inserted {} daily records", records_inserted);
+    for tombstone in &tombstones {
+        eprintln!(
+            "    Warning: version {} was {} ({} downloads when last listed)",
+            tombstone.artifact,
+            tombstone.event.as_str(),
+            format::number(tombstone.last_count)
+        );
+    }
+    Ok(SourceSummary {
+        kind: "crates",
+        name: crate_name.to_string(),
+        records: records_inserted,
+        downloads: metadata.downloads,
+        archived: Vec::new(),
+        owner_changes,
+        tombstones,
+        latest_date,
+        etag: None,
+        skipped: Vec::new(),
+    })
 }
 
-fn format_number(n: u64) -> String {
-    let s = n.to_string();
-    let mut result = String::new();
-    for (i, c) in s.chars().rev().enumerate() {
-        if i > 0 && i % 3 == 0 {
-            result.push(',');
+fn record_installer_stats(
+    conn: &rusqlite::Connection,
+    name: &str,
+    installer::InstallerStats { days, etag }: installer::InstallerStats,
+) -> Result<SourceSummary> {
+    let tx = conn.unchecked_transaction()?;
+    let mut downloads = 0;
+    for day in &days {
+        db::insert_installer_download(&tx, day.date, name, day.downloads)?;
+        downloads += day.downloads;
+    }
+    tx.commit()
+        .with_context(|| format!("failed to commit installer downloads for '{}'", name))?;
+
+    status!(
+        "    Inserted {} daily records ({} downloads)",
+        days.len(),
+        format::number(downloads)
+    );
+    Ok(SourceSummary {
+        kind: "installer",
+        name: name.to_string(),
+        records: days.len() as u64,
+        downloads,
+        archived: Vec::new(),
+        owner_changes: Vec::new(),
+        tombstones: Vec::new(),
+        latest_date: days.iter().map(|day| day.date).max(),
+        etag,
+        skipped: Vec::new(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use camino::Utf8PathBuf;
+    use chrono::NaiveDate;
+
+    #[tokio::test]
+    async fn test_before_deadline() {
+        assert_eq!(before_deadline(None, "work", async { 1 }).await, Some(1));
+        let later = Instant::now() + Duration::from_secs(60);
+        assert_eq!(
+            before_deadline(Some(later), "work", async { 2 }).await,
+            Some(2)
+        );
+
+        // Work that outlasts the deadline is abandoned, even once it's passed.
+        let soon = Instant::now() + Duration::from_millis(10);
+        let slow = tokio::time::sleep(Duration::from_secs(60));
+        assert_eq!(before_deadline(Some(soon), "work", slow).await, None);
+        let passed = Instant::now();
+        let never = std::future::pending::<()>();
+        assert_eq!(before_deadline(Some(passed), "work", never).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_refresh_steps() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-refresh-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let database = dir.join("stats.db");
+        let config = config::Config::default();
+        let mut options = RefreshOptions {
+            skip_collect: true,
+            skip_github: false,
+            skip_crates: false,
+            skip_installer: false,
+            skip_aggregation: false,
+            skip_charts: true,
+            skip_report: false,
+            skip_publish: true,
+            time_budget: None,
+            deadline: None,
+            parallelism: DEFAULT_PARALLELISM,
+            strict: false,
+            charts: config::ChartOutputConfig::default_at(dir.join("charts")),
+            report: page::PageOptions {
+                output: dir.join("report/index.html"),
+                title: "Download statistics".to_string(),
+                template: None,
+                charts: Vec::new(),
+                theme: config::ChartTheme::default(),
+            },
+            public_output: dir.join("public"),
+            upload: None,
+        };
+        let steps = |summary: &RefreshSummary| -> Vec<(&str, StepStatus)> {
+            summary
+                .steps
+                .iter()
+                .map(|step| (step.step, step.status))
+                .collect()
+        };
+
+        let summary = run_refresh(&database, &config, &options).await.unwrap();
+        assert_eq!(
+            steps(&summary),
+            [
+                ("collect", StepStatus::Skipped),
+                ("aggregate", StepStatus::Completed),
+                ("charts", StepStatus::Skipped),
+                ("report", StepStatus::Completed),
+                ("publish", StepStatus::Skipped),
+                ("upload", StepStatus::Skipped),
+            ]
+        );
+        assert!(options.report.output.exists());
+
+        // A failed step stops the steps after it.
+        options.skip_aggregation = true;
+        options.skip_publish = false;
+        options.report.output = dir.clone();
+        let mut summary = RefreshSummary::default();
+        refresh_steps(&database, &config, &options, &mut summary)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            steps(&summary),
+            [
+                ("collect", StepStatus::Skipped),
+                ("aggregate", StepStatus::Skipped),
+                ("charts", StepStatus::Skipped),
+                ("report", StepStatus::Failed),
+            ]
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_collect_partial_failure() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-collect-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let stats = dir.join("stats.json");
+        std::fs::write(
+            &stats,
+            r#"{"downloads": [{"date": "2025-11-17", "downloads": 12}]}"#,
+        )
+        .unwrap();
+        let installer = |name: &str, stats_url: &Utf8Path| config::CollectionSource::Installer {
+            name: name.to_string(),
+            stats_url: stats_url.to_string(),
+            schedule: config::SourceSchedule::default(),
+        };
+
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let mut config = config::Config {
+            source: vec![
+                installer("good", &stats),
+                installer("bad", &dir.join("missing.json")),
+            ],
+            ..Default::default()
+        };
+        let today = NaiveDate::from_ymd_opt(2025, 11, 18).unwrap();
+        let mut options = CollectOptions {
+            skip_aggregation: true,
+            ..Default::default()
+        };
+
+        let summary = collect_all(&conn, &config, today, &options, None)
+            .await
+            .unwrap();
+        assert_eq!(summary.sources.len(), 1);
+        assert_eq!(summary.sources[0].name, "good");
+        assert_eq!(summary.failures.len(), 1);
+        assert_eq!(summary.failures[0].name, "bad");
+
+        options.strict = true;
+        let err = collect_all(&conn, &config, today, &options, None)
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("1 of 2 sources failed: installer:bad: ")
+        );
+
+        options.strict = false;
+        options.only = vec!["installer:good".parse().unwrap()];
+        let summary = collect_all(&conn, &config, today, &options, None)
+            .await
+            .unwrap();
+        assert!(summary.failures.is_empty());
+        options.only = vec!["installer:missing".parse().unwrap()];
+        let err = collect_all(&conn, &config, today, &options, None)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "'installer:missing' is not a configured source"
+        );
+
+        // "good" has just been collected, so it isn't due again today, and
+        // "bad" is left out altogether, unless named.
+        options.only.clear();
+        if let config::CollectionSource::Installer { schedule, .. } = &mut config.source[0] {
+            schedule.frequency = Some(config::Frequency::Daily);
+        }
+        if let config::CollectionSource::Installer { schedule, .. } = &mut config.source[1] {
+            schedule.enabled = false;
+        }
+        let summary = collect_all(&conn, &config, today, &options, None)
+            .await
+            .unwrap();
+        assert!(summary.sources.is_empty() && summary.failures.is_empty());
+        options.only = vec!["installer:good".parse().unwrap()];
+        let summary = collect_all(&conn, &config, today, &options, None)
+            .await
+            .unwrap();
+        assert_eq!(summary.sources.len(), 1);
+
+        options.only.clear();
+        config.source.remove(0);
+        if let config::CollectionSource::Installer { schedule, .. } = &mut config.source[0] {
+            schedule.enabled = true;
+        }
+        assert!(
+            collect_all(&conn, &config, today, &options, None)
+                .await
+                .is_err()
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failed_runs_accumulate() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-failures-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let database = dir.join("stats.db");
+        let config = config::Config {
+            source: vec![config::CollectionSource::Installer {
+                name: "bad".to_string(),
+                stats_url: dir.join("missing.json").to_string(),
+                schedule: config::SourceSchedule::default(),
+            }],
+            ..Default::default()
+        };
+        let options = CollectOptions {
+            skip_aggregation: true,
+            ..Default::default()
+        };
+
+        // Each failed run is recorded before the next, so an issue is due
+        // once the threshold is reached.
+        for run in 1..=3 {
+            assert!(run_collect(&database, &config, &options).await.is_err());
+            let conn = db::init_db(&database).unwrap();
+            let due = issues::failures_to_report(&conn, 3).unwrap();
+            assert_eq!(due.is_some(), run == 3, "after {} failed runs", run);
         }
-        result.push(c);
+        let conn = db::init_db(&database).unwrap();
+        assert_eq!(db::get_consecutive_failures(&conn).unwrap().len(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_as_of() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 3, day).unwrap();
+        check_as_of(&conn, date(1), date(2)).unwrap();
+        assert_eq!(
+            check_as_of(&conn, date(3), date(2))
+                .unwrap_err()
+                .to_string(),
+            "--as-of 2025-03-03 is in the future"
+        );
+
+        db::insert_github_snapshot(
+            &conn,
+            date(2),
+            Some(Utc::now()),
+            "nextest-rs/nextest",
+            "v1",
+            "a.tar.gz",
+            1,
+        )
+        .unwrap();
+        // Re-collecting the latest date replaces its snapshots.
+        check_as_of(&conn, date(2), date(2)).unwrap();
+        assert!(check_as_of(&conn, date(1), date(2)).is_err());
+    }
+
+    /// Crate data with two days of downloads of version 1.0.0 (ID 100).
+    fn crate_data(owners: Option<serde_json::Value>) -> CrateData {
+        CrateData {
+            metadata: serde_json::from_value(
+                serde_json::json!({"downloads": 20, "recent_downloads": 20}),
+            )
+            .unwrap(),
+            versions: serde_json::from_value(serde_json::json!([
+                {"id": 100, "num": "1.0.0", "created_at": "2025-01-01T00:00:00Z"}
+            ]))
+            .unwrap(),
+            downloads: serde_json::from_value(serde_json::json!({
+                "version_downloads": [
+                    {"version": 100, "downloads": 5, "date": "2025-11-01"},
+                    {"version": 100, "downloads": 7, "date": "2025-11-02"}
+                ],
+                "meta": {"extra_downloads": []}
+            }))
+            .unwrap(),
+            owners: owners.map(|owners| serde_json::from_value(owners).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_record_crates_stats_reattributes() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let date = NaiveDate::from_ymd_opt(2025, 11, 1).unwrap();
+        // Collected before the version mapping was known.
+        db::insert_crates_download(&conn, date, "foo", Some("100"), 5).unwrap();
+
+        record_crates_stats(&conn, "foo", crate_data(Some(serde_json::json!([])))).unwrap();
+
+        let rows: Vec<(String, String, i64)> = conn
+            .prepare("SELECT date, version, downloads FROM crates_downloads ORDER BY date")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("2025-11-01".to_string(), "1.0.0".to_string(), 5),
+                ("2025-11-02".to_string(), "1.0.0".to_string(), 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_crates_stats_without_owners() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let owners = serde_json::json!([{"login": "alice", "kind": "user"}]);
+        record_crates_stats(&conn, "foo", crate_data(Some(owners))).unwrap();
+
+        // Downloads are recorded when the owners couldn't be fetched, and the
+        // recorded owners are left as they were rather than removed.
+        let summary = record_crates_stats(&conn, "foo", crate_data(None)).unwrap();
+        assert!(summary.owner_changes.is_empty());
+        assert_eq!(summary.downloads, 20);
+        let owners: i64 = conn
+            .query_row("SELECT COUNT(*) FROM crate_owners", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(owners, 1);
+    }
+
+    #[test]
+    fn test_record_github_stats_as_of() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let filter = github::ReleaseFilter::new(&Default::default()).unwrap();
+        let releases = |download_count| github::ReleaseList {
+            releases: vec![github::Release {
+                tag_name: "v1.0".to_string(),
+                published_at: None,
+                prerelease: false,
+                assets: vec![github::Asset {
+                    name: "linux.tar.gz".to_string(),
+                    download_count,
+                }],
+            }],
+            etag: None,
+        };
+        let now = Utc::now();
+        let today = now.date_naive();
+        let yesterday = today.pred_opt().unwrap();
+
+        // `collect --as-of` for yesterday, then today's normal run an hour
+        // later. The interval between them is a day, not an hour.
+        record_github_stats(&conn, yesterday, None, "o", "r", releases(100), &filter).unwrap();
+        record_github_stats(&conn, today, Some(now), "o", "r", releases(124), &filter).unwrap();
+
+        let rates = analysis::github_download_rate(&conn, 1).unwrap();
+        let last = rates.last().unwrap();
+        assert_eq!(last.date, today);
+        assert_eq!(last.days, 1.0);
+        assert_eq!(last.downloads, 24);
+        assert_eq!(last.per_day, 24.0);
+
+        // A snapshot taken on the day wins over one recorded for it later.
+        record_github_stats(&conn, today, None, "o", "r", releases(130), &filter).unwrap();
+        let count: i64 = conn
+            .query_row(
+                "SELECT download_count FROM github_snapshots WHERE date = ?1",
+                [today.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 124);
     }
-    result.chars().rev().collect()
 }
@@ -3,37 +3,1744 @@
 
 //! Configuration for download statistics collection.
 
-use anyhow::{Context, Result};
-use camino::Utf8Path;
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Days, NaiveDate, Timelike, Utc, Weekday};
 use serde::{Deserialize, Serialize};
-use std::fs;
+use std::{collections::BTreeMap, fmt, fs, io, str::FromStr};
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Config {
     #[serde(default)]
     pub source: Vec<CollectionSource>,
+
+    #[serde(default)]
+    pub notification: Vec<NotificationConfig>,
+
+    /// Rules flagging changes in weekly downloads, checked after aggregation.
+    #[serde(default)]
+    pub alert: Vec<AlertConfig>,
+
+    /// File a GitHub issue when collection fails repeatedly.
+    #[serde(default)]
+    pub issue_filing: Option<IssueFilingConfig>,
+
+    /// Tables and columns included in `export public`.
+    #[serde(default)]
+    pub public_export: PublicExportConfig,
+
+    /// How numbers are formatted for each kind of output.
+    #[serde(default)]
+    pub formatting: FormattingConfig,
+
+    /// How computed aggregates are derived from raw data.
+    #[serde(default)]
+    pub aggregation: AggregationConfig,
+
+    /// Named groups of sources whose downloads are also reported together.
+    #[serde(default)]
+    pub project: Vec<ProjectConfig>,
+
+    /// Sets of charts to generate.
+    #[serde(default)]
+    pub charts: ChartsConfig,
+
+    /// Auditing of crates.io crate owners.
+    #[serde(default)]
+    pub owners: OwnersConfig,
+
+    /// Derived metrics, by name. See [`crate::metrics`] for the expression
+    /// syntax.
+    #[serde(default)]
+    pub metrics: BTreeMap<String, MetricConfig>,
+
+    /// How the headline total downloads figure is computed.
+    #[serde(default)]
+    pub headline: HeadlineConfig,
+
+    /// Retrying of HTTP requests that fail transiently.
+    #[serde(default)]
+    pub retry: RetryConfig,
+
+    /// Timeouts for HTTP requests.
+    #[serde(default)]
+    pub http: HttpConfig,
+
+    /// Settings shared by every GitHub source.
+    #[serde(default)]
+    pub github: GithubConfig,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum CollectionSource {
+    Github {
+        owner: String,
+        repo: String,
+        /// How to reach the GitHub API, for deployments behind a mirror or
+        /// gateway.
+        #[serde(default, skip_serializing_if = "GithubApiConfig::is_default")]
+        api: GithubApiConfig,
+        #[serde(flatten)]
+        releases: Box<ReleaseFilterConfig>,
+        #[serde(flatten)]
+        schedule: SourceSchedule,
+    },
+    Crates {
+        name: String,
+        /// The registry the crate is published to, for crates on a private
+        /// or alternative registry rather than crates.io.
+        #[serde(default, skip_serializing_if = "RegistryConfig::is_default")]
+        registry: RegistryConfig,
+        #[serde(flatten)]
+        schedule: SourceSchedule,
+    },
+    /// An install endpoint such as `get.nexte.st`. See [`crate::installer`]
+    /// for the format of the statistics at `stats_url`.
+    Installer {
+        name: String,
+        stats_url: String,
+        #[serde(flatten)]
+        schedule: SourceSchedule,
+    },
+}
+
+/// Whether and how often a source is collected.
+///
+/// The defaults collect the source on every run.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct SourceSchedule {
+    /// Whether the source is collected. A disabled source keeps its data, and
+    /// can still be collected with `collect --only`.
+    #[serde(skip_serializing_if = "is_true")]
+    pub enabled: bool,
+    /// Collect the source at most once per UTC day or hour, however often
+    /// collection runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency: Option<Frequency>,
+}
+
+impl Default for SourceSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            frequency: None,
+        }
+    }
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Frequency {
+    Daily,
+    Hourly,
+}
+
+impl Frequency {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Frequency::Daily => "daily",
+            Frequency::Hourly => "hourly",
+        }
+    }
+
+    /// Whether a source last collected at `last` is due to be collected
+    /// again at `now`: if it hasn't been yet this UTC day, or hour.
+    pub fn is_due(self, last: DateTime<Utc>, now: DateTime<Utc>) -> bool {
+        let same_day = last.date_naive() == now.date_naive();
+        match self {
+            Frequency::Daily => !same_day,
+            Frequency::Hourly => !same_day || last.hour() != now.hour(),
+        }
+    }
+}
+
+/// Where GitHub API requests are sent and how they're authenticated.
+///
+/// The defaults talk to `api.github.com` directly with `GITHUB_TOKEN`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct GithubApiConfig {
+    /// Base URL of the API or a mirror of it, such as
+    /// `https://gateway.example.com/github`, or
+    /// `https://github.example.com/api/v3` for GitHub Enterprise Server.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// URL of the GraphQL API. Defaults to `/api/graphql` on the same host
+    /// for base URLs ending in `/api/v3`, as GitHub Enterprise Server lays
+    /// them out, and to `{base_url}/graphql` otherwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub graphql_url: Option<String>,
+    /// Environment variable holding the token. Defaults to `GITHUB_TOKEN`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+    /// Environment variables holding more tokens, used in order once the
+    /// ones before them are rate-limited. Each must be set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_envs: Vec<String>,
+    /// A file of more tokens, one per line, used after those in
+    /// environment variables. Blank lines and lines starting with `#` are
+    /// ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_file: Option<Utf8PathBuf>,
+    /// Authenticate as a GitHub App installation instead of with tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app: Option<Box<GithubAppConfig>>,
+    /// How the token is sent.
+    #[serde(default, skip_serializing_if = "GithubAuthStyle::is_default")]
+    pub auth: GithubAuthStyle,
+    /// Extra headers sent with every request. Values written `env:NAME` are
+    /// read from the environment variable `NAME`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    /// Sign every request with a shared secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signing: Option<RequestSigningConfig>,
+}
+
+impl GithubApiConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Where a crate's registry API is and how it's authenticated.
+///
+/// The defaults talk to crates.io without credentials. Other registries must
+/// serve crates.io's API for crate metadata, versions, downloads, and owners.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RegistryConfig {
+    /// Base URL of the registry's web API, such as
+    /// `https://kellnr.example.com/api/v1`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub base_url: Option<String>,
+    /// Environment variable holding a token sent as the `Authorization`
+    /// header, as Cargo sends registry tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub token_env: Option<String>,
+    /// Extra headers sent with every request. Values written `env:NAME` are
+    /// read from the environment variable `NAME`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+impl RegistryConfig {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Which releases and assets of a GitHub source are collected.
+///
+/// The defaults collect every release and asset. See
+/// [`crate::github::ReleaseFilter`] for how they're matched.
+#[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct ReleaseFilterConfig {
+    /// Only collect releases whose tags start with this, such as
+    /// `cargo-nextest-`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_prefix: Option<String>,
+    /// Only collect releases whose tags match this regular expression, which
+    /// can match anywhere in the tag unless anchored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_regex: Option<String>,
+    /// Only collect assets whose names match one of these patterns (`*` and
+    /// `?` wildcards). Empty collects every asset.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include_assets: Vec<String>,
+    /// Don't collect assets whose names match any of these patterns. Unlike
+    /// `aggregation.exclude_assets`, no snapshots of them are taken at all.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude_assets: Vec<String>,
+}
+
+/// Settings shared by every GitHub source.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct GithubConfig {
+    /// The API releases are fetched with.
+    pub api: GithubApiKind,
+}
+
+/// The GitHub API releases and their assets are fetched with.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GithubApiKind {
+    /// The REST API: a request for every 100 releases, with their assets.
+    #[default]
+    Rest,
+    /// The GraphQL API: also a request for every 100 releases, but with only
+    /// the fields collected, so responses are a fraction of the size.
+    /// Requires a token.
+    Graphql,
+}
+
+/// Authentication as a GitHub App. See [`crate::github_app`].
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct GithubAppConfig {
+    /// The app's ID, from its settings page.
+    pub app_id: u64,
+    /// The installation to act as. Defaults to the app's only installation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub installation_id: Option<u64>,
+    /// Environment variable holding the app's PEM-encoded private key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_env: Option<String>,
+    /// File holding the app's PEM-encoded private key.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub private_key_file: Option<Utf8PathBuf>,
+}
+
+/// How a GitHub API token is sent in the `Authorization` header.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum GithubAuthStyle {
+    /// `Bearer {token}`, which `api.github.com` and current GitHub
+    /// Enterprise Server releases accept.
+    #[default]
+    Bearer,
+    /// `token {token}`, for older GitHub Enterprise Server releases.
+    Token,
+    /// HTTP basic authentication, with the token variable holding
+    /// `username:token`, for instances behind proxies that require it.
+    Basic,
+}
+
+impl GithubAuthStyle {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// HMAC-SHA256 request signing, as required by some API gateways.
+///
+/// See [`crate::github`] for what is signed.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub struct RequestSigningConfig {
+    /// Environment variable holding the shared secret.
+    pub secret_env: String,
+    /// Header carrying the hex-encoded signature. The timestamp that was
+    /// signed is sent in the same header with a `-Timestamp` suffix.
+    #[serde(default = "default_signature_header")]
+    pub header: String,
+}
+
+fn default_signature_header() -> String {
+    "X-Signature".to_string()
+}
+
+/// A named group of sources, such as a tool's GitHub releases and the crates
+/// it's published as.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ProjectConfig {
+    pub name: String,
+    /// The sources in the project, each of which must be configured.
+    pub sources: Vec<SourceRef>,
+}
+
+/// A reference to a configured source, written `kind:name` as in
+/// `github:nextest-rs/nextest` or `crates:cargo-nextest`.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct SourceRef {
+    pub kind: String,
+    pub name: String,
+}
+
+impl SourceRef {
+    /// Whether this refers to `source`.
+    pub fn matches(&self, source: &CollectionSource) -> bool {
+        self.kind == source.kind() && self.name == source.name()
+    }
+}
+
+impl TryFrom<String> for SourceRef {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        match s.split_once(':') {
+            Some((kind @ ("github" | "crates" | "installer"), name)) if !name.is_empty() => {
+                Ok(SourceRef {
+                    kind: kind.to_string(),
+                    name: name.to_string(),
+                })
+            }
+            _ => bail!(
+                "expected 'github:owner/repo', 'crates:name', or 'installer:name', found '{}'",
+                s
+            ),
+        }
+    }
+}
+
+impl FromStr for SourceRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        s.to_string().try_into()
+    }
+}
+
+impl From<SourceRef> for String {
+    fn from(source: SourceRef) -> Self {
+        source.to_string()
+    }
+}
+
+impl fmt::Display for SourceRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.kind, self.name)
+    }
+}
+
+/// A notification channel that receives a message after collection.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct NotificationConfig {
+    /// A name for the channel, used in output and error messages.
+    pub name: String,
+    /// The kind of channel.
+    pub kind: NotificationKind,
+    /// The message template. Defaults to a template suited to the channel kind.
+    #[serde(default)]
+    pub template: Option<String>,
+    /// The URL messages are posted to: a Slack or Discord incoming webhook,
+    /// or any endpoint accepting JSON for `webhook` channels. Values written
+    /// `env:NAME` are read from the environment variable `NAME`. Without a
+    /// URL, messages are only printed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationKind {
+    Slack,
+    Discord,
+    Email,
+    /// A generic HTTP endpoint, sent the message and the full summary as
+    /// JSON.
+    Webhook,
+}
+
+/// A rule comparing a source's downloads in its latest complete week against
+/// the average of the weeks before.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AlertConfig {
+    /// A name for the rule, used in output and notifications.
+    pub name: String,
+    /// The source whose weekly downloads are checked: `github`, `crates`,
+    /// `installer`, or `all` for their sum.
+    #[serde(default = "default_alert_source")]
+    pub source: String,
+    /// Whether the rule fires on a drop or a rise.
+    pub direction: AlertDirection,
+    /// How far the latest week must be from the average to fire, in percent.
+    pub percent: f64,
+    /// The number of complete weeks before the latest that are averaged.
+    #[serde(default = "default_alert_baseline_weeks")]
+    pub baseline_weeks: usize,
+}
+
+fn default_alert_source() -> String {
+    "all".to_string()
+}
+
+fn default_alert_baseline_weeks() -> usize {
+    4
+}
+
+/// The most complete weeks an alert can average over.
+pub const MAX_ALERT_BASELINE_WEEKS: usize = 520;
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertDirection {
+    Drop,
+    Rise,
+}
+
+impl AlertDirection {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            AlertDirection::Drop => "drop",
+            AlertDirection::Rise => "rise",
+        }
+    }
+}
+
+/// Where and when to file an issue about repeated collection failures.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct IssueFilingConfig {
+    /// Owner of the repository to file the issue in.
+    pub owner: String,
+    /// Repository to file the issue in.
+    pub repo: String,
+    /// Number of consecutive failed runs before an issue is filed.
+    #[serde(default = "default_failure_threshold")]
+    pub after_failures: usize,
+    /// Labels to apply to the issue. Also used to find an existing issue.
+    #[serde(default)]
+    pub labels: Vec<String>,
+    /// How to reach the GitHub API.
+    #[serde(default, skip_serializing_if = "GithubApiConfig::is_default")]
+    pub api: GithubApiConfig,
+}
+
+fn default_failure_threshold() -> usize {
+    3
+}
+
+/// Which crates.io owner additions are expected.
+///
+/// Owner additions and removals are always recorded; additions of owners not
+/// listed here are also reported as unexpected.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct OwnersConfig {
+    /// User logins, or team logins such as `github:nextest-rs:core`.
+    #[serde(default)]
+    pub expected: Vec<String>,
+}
+
+impl OwnersConfig {
+    /// Whether `login` may be added as an owner without being flagged.
+    pub fn is_expected(&self, login: &str) -> bool {
+        self.expected.iter().any(|expected| expected == login)
+    }
+}
+
+/// A metric computed from other weekly series when it's queried.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct MetricConfig {
+    /// The expression, such as `github / (github + crates)`.
+    pub expr: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub format: MetricFormat,
+}
+
+/// How metric values are displayed.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum MetricFormat {
+    /// Counts and plain numbers.
+    #[default]
+    Number,
+    /// Ratios, shown multiplied by 100 with a percent sign.
+    Percent,
+}
+
+/// The definition of the headline total downloads figure, shared by every
+/// output that reports it.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HeadlineConfig {
+    /// The kinds of sources counted: `github`, `crates`, and `installer`.
+    pub sources: Vec<String>,
+    /// Downloads left out, as `kind:name`: a GitHub release tag, a crate, or
+    /// an install endpoint.
+    pub exclude: Vec<SourceRef>,
+    /// Added to the total for downloads the database doesn't know about,
+    /// such as mirrors. May be negative.
+    pub adjustment: i64,
+    /// Round the total down to a multiple of this, so it never overstates.
+    pub round_down_to: u64,
+}
+
+impl Default for HeadlineConfig {
+    fn default() -> Self {
+        Self {
+            sources: ["github", "crates", "installer"].map(String::from).to_vec(),
+            exclude: Vec::new(),
+            adjustment: 0,
+            round_down_to: 1,
+        }
+    }
+}
+
+impl HeadlineConfig {
+    /// Check that the definition can be computed.
+    fn check(&self) -> Result<()> {
+        for source in &self.sources {
+            if !matches!(source.as_str(), "github" | "crates" | "installer") {
+                bail!(
+                    "unknown headline source '{}', expected 'github', 'crates', or 'installer'",
+                    source
+                );
+            }
+        }
+        if self.round_down_to == 0 {
+            bail!("headline round_down_to must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// Whether the downloads of `name` from a source of `kind` are left out.
+    pub fn is_excluded(&self, kind: &str, name: &str) -> bool {
+        self.exclude
+            .iter()
+            .any(|exclusion| exclusion.kind == kind && exclusion.name == name)
+    }
+}
+
+/// How HTTP requests to GitHub, crates.io, and install endpoints are retried
+/// after timeouts, connection failures, server errors, and rate limiting.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RetryConfig {
+    /// Attempts per request, including the first. 1 disables retries.
+    pub max_attempts: u32,
+    /// The delay before the first retry, in milliseconds. Each retry after
+    /// that waits twice as long as the one before, with jitter.
+    pub initial_delay_ms: u64,
+    /// The longest delay between attempts, in milliseconds.
+    pub max_delay_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_delay_ms: 1000,
+            max_delay_ms: 30_000,
+        }
+    }
+}
+
+impl RetryConfig {
+    fn check(&self) -> Result<()> {
+        if self.max_attempts == 0 {
+            bail!("retry max_attempts must be at least 1");
+        }
+        Ok(())
+    }
+}
+
+/// How HTTP requests reach servers, and how long they wait on them before
+/// giving up.
+///
+/// A request that times out is retried like any other transient failure.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// How long to wait for a connection to be established, in seconds.
+    pub connect_timeout_secs: u64,
+    /// How long to wait for the response, or for more of it once it has
+    /// started, in seconds.
+    pub read_timeout_secs: u64,
+    /// The proxy for every request, such as `http://proxy.example.com:3128`.
+    /// Defaults to `HTTPS_PROXY` or `ALL_PROXY`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Hosts reached without the proxy, in addition to those in `NO_PROXY`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+    /// A PEM file of certificate authorities to trust besides the system's,
+    /// such as that of a proxy that intercepts TLS.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ca_bundle: Option<Utf8PathBuf>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout_secs: 10,
+            read_timeout_secs: 60,
+            proxy: None,
+            no_proxy: Vec::new(),
+            ca_bundle: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    fn check(&self) -> Result<()> {
+        if self.connect_timeout_secs == 0 || self.read_timeout_secs == 0 {
+            bail!("http timeouts must be at least 1 second");
+        }
+        if let Some(proxy) = &self.proxy {
+            reqwest::Proxy::all(proxy.as_str())
+                .with_context(|| format!("invalid http.proxy '{}'", proxy))?;
+        }
+        Ok(())
+    }
+}
+
+/// The allowlist of tables and columns for public snapshots.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublicExportConfig {
+    #[serde(default = "default_public_tables")]
+    pub table: Vec<PublicTableConfig>,
+}
+
+/// A table (or view) included in public snapshots.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublicTableConfig {
+    pub name: String,
+    /// Columns to include. Defaults to all columns.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+}
+
+impl Default for PublicExportConfig {
+    fn default() -> Self {
+        Self {
+            table: default_public_tables(),
+        }
+    }
+}
+
+fn default_public_tables() -> Vec<PublicTableConfig> {
+    let table = |name: &str, columns: Option<&[&str]>| PublicTableConfig {
+        name: name.to_string(),
+        columns: columns.map(|c| c.iter().map(|s| s.to_string()).collect()),
+    };
+    vec![
+        table("weekly_stats", None),
+        table("weekly_platform_stats", None),
+        table("weekly_format_stats", None),
+        table("weekly_version_share", None),
+        table("weekly_verification_stats", None),
+        table("release_targets", None),
+        table("monthly_stats", None),
+        table(
+            "github_daily",
+            Some(&["date", "release_tag", "asset_name", "days", "downloads"]),
+        ),
+        table("crates_downloads_named", None),
+    ]
+}
+
+/// Chart outputs, so that jobs sharing a config file can each produce their
+/// own set of charts, and the appearance shared by all of them.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ChartsConfig {
+    pub output: Vec<ChartOutputConfig>,
+    /// Image width in pixels.
+    pub width: u32,
+    /// Image height in pixels.
+    pub height: u32,
+    /// Font family for all text. It must be the built-in Inter, or provided
+    /// by `font_file`.
+    pub font_family: String,
+    /// A TrueType or OpenType file of `font_family`, loaded instead of the
+    /// built-in font. System fonts are never used, so that charts look the
+    /// same on every machine.
+    pub font_file: Option<Utf8PathBuf>,
+    pub title_size: u32,
+    pub label_size: u32,
+    pub axis_size: u32,
+    /// Space around each chart, in pixels.
+    pub margin: u32,
+    /// Space inside legend boxes, in pixels.
+    pub legend_margin: u32,
+    /// Series colors, used in order and repeated as needed.
+    pub colors: Vec<ChartColor>,
+    /// Color of series that group everything else, such as other platforms.
+    pub other_color: ChartColor,
+    /// Number of versions shown individually in version charts.
+    pub top_versions: usize,
+    /// Number of platforms shown individually in the platform chart.
+    pub top_platforms: usize,
+    /// Number of bars shown individually in the top assets chart.
+    pub top_assets: usize,
+    /// What the top assets chart ranks.
+    pub top_assets_by: TopAssetsBy,
+    /// Releases with less than this share of the most downloaded release's
+    /// downloads are grouped as other in the by-version chart.
+    pub version_min_share: f64,
+    /// Releases with fewer downloads than this are grouped as other in the
+    /// by-version chart.
+    pub version_min_downloads: u64,
+    /// Releases marked on the weekly trends and cumulative charts.
+    pub release_annotations: ReleaseAnnotations,
+    /// Overlay a 4-week moving average on the weekly trends chart.
+    pub moving_average: bool,
+    /// Draw the download trend charts with a logarithmic y axis.
+    pub log_scale: bool,
+    /// Charts of SQL query results, by name.
+    pub custom: BTreeMap<String, CustomChartConfig>,
+}
+
+impl Default for ChartsConfig {
+    fn default() -> Self {
+        Self {
+            output: Vec::new(),
+            width: 1600,
+            height: 900,
+            font_family: "Inter".to_string(),
+            font_file: None,
+            title_size: 24,
+            label_size: 16,
+            axis_size: 14,
+            margin: 60,
+            legend_margin: 15,
+            colors: vec![
+                ChartColor(59, 130, 246), // Blue 500.
+                ChartColor(34, 197, 94),  // Green 500.
+                ChartColor(251, 146, 60), // Orange 400.
+                ChartColor(99, 102, 241), // Indigo 500.
+                ChartColor(236, 72, 153), // Pink 500.
+                ChartColor(234, 179, 8),  // Yellow 500.
+            ],
+            other_color: ChartColor(156, 163, 175), // Gray 400.
+            top_versions: 5,
+            top_platforms: 6,
+            top_assets: 10,
+            top_assets_by: TopAssetsBy::default(),
+            version_min_share: 0.005,
+            version_min_downloads: 10_000,
+            release_annotations: ReleaseAnnotations::default(),
+            moving_average: false,
+            log_scale: false,
+            custom: BTreeMap::new(),
+        }
+    }
+}
+
+impl ChartsConfig {
+    /// Check that the chart settings can be drawn with.
+    fn check(&self) -> Result<()> {
+        for (idx, output) in self.output.iter().enumerate() {
+            if self.output[..idx]
+                .iter()
+                .any(|other| other.name == output.name)
+            {
+                bail!("chart output '{}' is defined more than once", output.name);
+            }
+            if !(1..=MAX_CHART_SCALE).contains(&output.scale) {
+                bail!(
+                    "chart output '{}' has scale {}, but it must be between 1 and {}",
+                    output.name,
+                    output.scale,
+                    MAX_CHART_SCALE
+                );
+            }
+            for name in output.custom.iter().flatten() {
+                if !self.custom.contains_key(name) {
+                    bail!(
+                        "chart output '{}' refers to custom chart '{}', which is not defined",
+                        output.name,
+                        name
+                    );
+                }
+            }
+        }
+        for (name, custom) in &self.custom {
+            if name.is_empty()
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+            {
+                bail!(
+                    "custom chart name '{}' must only contain letters, digits, '-', and '_'",
+                    name
+                );
+            }
+            if custom.query.trim().is_empty() {
+                bail!("custom chart '{}' has an empty query", name);
+            }
+        }
+        for (name, value) in [
+            ("width", self.width),
+            ("height", self.height),
+            ("title_size", self.title_size),
+            ("label_size", self.label_size),
+            ("axis_size", self.axis_size),
+        ] {
+            if value == 0 {
+                bail!("charts.{} must be greater than zero", name);
+            }
+        }
+        if self.margin.saturating_mul(2) >= self.width.min(self.height) {
+            bail!(
+                "charts.margin ({}) leaves no room in a {}x{} chart",
+                self.margin,
+                self.width,
+                self.height
+            );
+        }
+        if self.colors.is_empty() {
+            bail!("charts.colors must list at least one color");
+        }
+        if self.top_versions == 0 || self.top_platforms == 0 || self.top_assets == 0 {
+            bail!(
+                "charts.top_versions, charts.top_platforms, and charts.top_assets must be at least 1"
+            );
+        }
+        if !(0.0..=1.0).contains(&self.version_min_share) {
+            bail!(
+                "charts.version_min_share must be between 0 and 1, found {}",
+                self.version_min_share
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A chart of the results of a SQL query, drawn like the built-in charts.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct CustomChartConfig {
+    pub title: String,
+    /// A read-only query returning `(date, value)` rows, or
+    /// `(date, value, series)` rows for a line per series.
+    pub query: String,
+    #[serde(default)]
+    pub format: MetricFormat,
+}
+
+/// An RGB color, written `#rrggbb`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ChartColor(pub u8, pub u8, pub u8);
+
+impl TryFrom<String> for ChartColor {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        let channel = |idx: usize| {
+            s.get(idx..idx + 2)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        };
+        match (
+            s.len(),
+            s.starts_with('#'),
+            channel(1),
+            channel(3),
+            channel(5),
+        ) {
+            (7, true, Some(r), Some(g), Some(b)) => Ok(ChartColor(r, g, b)),
+            _ => bail!("expected a color like '#3b82f6', found '{}'", s),
+        }
+    }
+}
+
+impl From<ChartColor> for String {
+    fn from(color: ChartColor) -> Self {
+        format!("#{:02x}{:02x}{:02x}", color.0, color.1, color.2)
+    }
+}
+
+/// A set of charts written to one directory in one format and theme.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ChartOutputConfig {
+    /// The name used to select this output with `--profile`.
+    pub name: String,
+    /// The directory charts are written to.
+    pub path: Utf8PathBuf,
+    /// The charts to generate. Defaults to all of them.
+    #[serde(default = "ChartKind::all")]
+    pub charts: Vec<ChartKind>,
+    #[serde(default)]
+    pub format: ChartFormat,
+    #[serde(default)]
+    pub theme: ChartTheme,
+    /// Leave out data before this date. Defaults to the start of the data.
+    #[serde(default)]
+    pub since: Option<ChartDate>,
+    /// Leave out data after this date. Defaults to the end of the data.
+    #[serde(default)]
+    pub until: Option<ChartDate>,
+    /// Period of the download trend charts.
+    #[serde(default)]
+    pub resolution: ChartResolution,
+    /// The size of every chart in this output, instead of `[charts]` `width`
+    /// and `height`. The sparkline is [`ChartSize::SPARKLINE`] unless this is
+    /// set.
+    #[serde(default)]
+    pub size: Option<ChartSize>,
+    /// Multiply the pixel size of every image by this, along with its text
+    /// and lines, for high-DPI displays. SVG badges are left as they are.
+    #[serde(default = "default_chart_scale")]
+    pub scale: u32,
+    /// Derived metrics to chart, each written to `metric-<name>`. Defaults to
+    /// all of them.
+    #[serde(default)]
+    pub metrics: Option<Vec<String>>,
+    /// Custom charts to generate, each written to `custom-<name>`. Defaults
+    /// to all of them.
+    #[serde(default)]
+    pub custom: Option<Vec<String>>,
+    /// Write the data each chart was drawn from to a `.json` file alongside
+    /// it.
+    #[serde(default)]
+    pub data: bool,
+}
+
+fn default_chart_scale() -> u32 {
+    1
+}
+
+/// The largest scale a chart output can be drawn at.
+pub const MAX_CHART_SCALE: u32 = 8;
+
+impl ChartOutputConfig {
+    /// Every chart, as light PNGs in `path`.
+    pub fn default_at(path: Utf8PathBuf) -> Self {
+        Self {
+            name: "default".to_string(),
+            path,
+            charts: ChartKind::all(),
+            format: ChartFormat::default(),
+            theme: ChartTheme::default(),
+            since: None,
+            until: None,
+            resolution: ChartResolution::default(),
+            size: None,
+            scale: default_chart_scale(),
+            metrics: None,
+            custom: None,
+            data: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ChartKind {
+    WeeklyTrends,
+    CumulativeTotal,
+    GithubByVersion,
+    SourceComparison,
+    GithubByPlatform,
+    MonthlyTrends,
+    DownloadRate,
+    VersionDiversity,
+    VersionShare,
+    ReleaseChannels,
+    ProjectTrends,
+    Verification,
+    CalendarHeatmap,
+    YearOverYear,
+    TopAssets,
+    Sparkline,
+    DownloadsBadge,
+}
+
+impl ChartKind {
+    const ALL: [ChartKind; 17] = [
+        ChartKind::WeeklyTrends,
+        ChartKind::CumulativeTotal,
+        ChartKind::GithubByVersion,
+        ChartKind::SourceComparison,
+        ChartKind::GithubByPlatform,
+        ChartKind::MonthlyTrends,
+        ChartKind::DownloadRate,
+        ChartKind::VersionDiversity,
+        ChartKind::VersionShare,
+        ChartKind::ReleaseChannels,
+        ChartKind::ProjectTrends,
+        ChartKind::Verification,
+        ChartKind::CalendarHeatmap,
+        ChartKind::YearOverYear,
+        ChartKind::TopAssets,
+        ChartKind::Sparkline,
+        ChartKind::DownloadsBadge,
+    ];
+
+    /// Every chart, in the order they're generated by default.
+    pub fn all() -> Vec<ChartKind> {
+        Self::ALL.to_vec()
+    }
+
+    /// The name used in configuration, which is also the file stem.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ChartKind::WeeklyTrends => "weekly-trends",
+            ChartKind::CumulativeTotal => "cumulative-total",
+            ChartKind::GithubByVersion => "github-by-version",
+            ChartKind::SourceComparison => "source-comparison",
+            ChartKind::GithubByPlatform => "github-by-platform",
+            ChartKind::MonthlyTrends => "monthly-trends",
+            ChartKind::DownloadRate => "download-rate",
+            ChartKind::VersionDiversity => "version-diversity",
+            ChartKind::VersionShare => "version-share",
+            ChartKind::ReleaseChannels => "release-channels",
+            ChartKind::ProjectTrends => "project-trends",
+            ChartKind::Verification => "verification",
+            ChartKind::CalendarHeatmap => "calendar-heatmap",
+            ChartKind::YearOverYear => "year-over-year",
+            ChartKind::TopAssets => "top-assets",
+            ChartKind::Sparkline => "sparkline",
+            ChartKind::DownloadsBadge => "downloads-badge",
+        }
+    }
+
+    /// The file name the chart is written to. The badge is always an SVG.
+    pub fn file_name(self, format: ChartFormat) -> String {
+        let extension = match self {
+            ChartKind::DownloadsBadge => "svg",
+            _ => format.extension(),
+        };
+        format!("{}.{}", self.as_str(), extension)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartFormat {
+    #[default]
+    Png,
+    Jpeg,
+}
+
+impl ChartFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ChartFormat::Png => "png",
+            ChartFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartTheme {
+    /// Dark text on an off-white background.
+    #[default]
+    Light,
+    /// Light text on a dark slate background.
+    Dark,
+}
+
+impl FromStr for ChartTheme {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "light" => Ok(ChartTheme::Light),
+            "dark" => Ok(ChartTheme::Dark),
+            _ => bail!("unknown chart theme '{}' (expected 'light' or 'dark')", s),
+        }
+    }
+}
+
+impl FromStr for ChartKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match Self::ALL.into_iter().find(|kind| kind.as_str() == s) {
+            Some(kind) => Ok(kind),
+            None => {
+                let names: Vec<&str> = Self::ALL.iter().map(|kind| kind.as_str()).collect();
+                bail!(
+                    "unknown chart '{}' (expected one of: {})",
+                    s,
+                    names.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// The size of a chart in pixels, written `WIDTHxHEIGHT` such as `300x80`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ChartSize {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ChartSize {
+    /// The default size of the sparkline, small enough to sit next to a
+    /// README badge.
+    pub const SPARKLINE: ChartSize = ChartSize {
+        width: 300,
+        height: 80,
+    };
+}
+
+impl FromStr for ChartSize {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let size = s.split_once('x').and_then(|(width, height)| {
+            Some(ChartSize {
+                width: width.parse().ok()?,
+                height: height.parse().ok()?,
+            })
+        });
+        match size {
+            Some(size) if size.width > 0 && size.height > 0 => Ok(size),
+            _ => bail!("expected a size in pixels like '300x80', found '{}'", s),
+        }
+    }
+}
+
+impl TryFrom<String> for ChartSize {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<ChartSize> for String {
+    fn from(size: ChartSize) -> Self {
+        format!("{}x{}", size.width, size.height)
+    }
+}
+
+/// A bound of the date range of a chart output: a date such as `2025-01-01`,
+/// or a number of days before today such as `90d`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum ChartDate {
+    Date(NaiveDate),
+    DaysAgo(u64),
+}
+
+impl ChartDate {
+    /// The most days a relative bound can reach back, so that it always
+    /// resolves to a valid date.
+    pub const MAX_DAYS_AGO: u64 = 100 * 366;
+
+    /// Get the date this bound refers to, with relative bounds counted back
+    /// from `today`.
+    pub fn resolve(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            ChartDate::Date(date) => date,
+            ChartDate::DaysAgo(days) => today
+                .checked_sub_days(Days::new(days))
+                .unwrap_or(NaiveDate::MIN),
+        }
+    }
+}
+
+impl FromStr for ChartDate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Some(days) = s
+            .strip_suffix('d')
+            .and_then(|days| days.parse::<u64>().ok())
+        {
+            if days > Self::MAX_DAYS_AGO {
+                bail!(
+                    "'{}' reaches too far back: at most {}d is allowed",
+                    s,
+                    Self::MAX_DAYS_AGO
+                );
+            }
+            return Ok(ChartDate::DaysAgo(days));
+        }
+        match NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            Ok(date) => Ok(ChartDate::Date(date)),
+            Err(_) => bail!(
+                "expected a date like '2025-01-01' or a number of days like '90d', found '{}'",
+                s
+            ),
+        }
+    }
+}
+
+impl TryFrom<String> for ChartDate {
+    type Error = anyhow::Error;
+
+    fn try_from(s: String) -> Result<Self> {
+        s.parse()
+    }
+}
+
+impl From<ChartDate> for String {
+    fn from(date: ChartDate) -> Self {
+        match date {
+            ChartDate::Date(date) => date.to_string(),
+            ChartDate::DaysAgo(days) => format!("{}d", days),
+        }
+    }
+}
+
+/// The period download trend charts are drawn at.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChartResolution {
+    /// Downloads per day, with GitHub downloads spread evenly between
+    /// snapshots.
+    Daily,
+    #[default]
+    Weekly,
+    Monthly,
+}
+
+impl ChartResolution {
+    /// The adjective used in chart captions, such as "Weekly".
+    pub fn label(self) -> &'static str {
+        match self {
+            ChartResolution::Daily => "Daily",
+            ChartResolution::Weekly => "Weekly",
+            ChartResolution::Monthly => "Monthly",
+        }
+    }
+}
+
+impl FromStr for ChartResolution {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "daily" => Ok(ChartResolution::Daily),
+            "weekly" => Ok(ChartResolution::Weekly),
+            "monthly" => Ok(ChartResolution::Monthly),
+            _ => bail!(
+                "unknown chart resolution '{}' (expected 'daily', 'weekly', or 'monthly')",
+                s
+            ),
+        }
+    }
+}
+
+/// Which GitHub releases to mark on date charts.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReleaseAnnotations {
+    /// No release markers.
+    None,
+    /// Releases starting a new major or minor version, such as `0.10.0`.
+    Minor,
+    /// Every release except pre-releases.
+    #[default]
+    Stable,
+    /// Every release, including pre-releases.
+    All,
+}
+
+/// What the top assets chart ranks by downloads.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TopAssetsBy {
+    /// Release assets with the version left out, such as
+    /// `cargo-nextest-x86_64-unknown-linux-gnu.tar.gz`, combining every
+    /// release.
+    #[default]
+    Asset,
+    /// Platforms, combining the assets of every release.
+    Platform,
+}
+
+/// Settings for computing aggregates.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct AggregationConfig {
+    /// How GitHub download deltas are attributed when snapshots are more than
+    /// a day apart.
+    pub interpolation: Interpolation,
+    /// How a GitHub asset's cumulative count going backwards is handled.
+    pub resets: ResetHandling,
+    /// The day weekly aggregates start on, or ISO-8601 weeks.
+    pub week_start: WeekStart,
+    /// GitHub asset name patterns (`*` and `?` wildcards) left out of download
+    /// totals. Snapshots of these assets are still collected.
+    pub exclude_assets: Vec<String>,
+    /// Count excluded assets in the per-format breakdown, as a separate
+    /// `excluded` format.
+    pub report_excluded: bool,
+}
+
+impl Default for AggregationConfig {
+    fn default() -> Self {
+        Self {
+            interpolation: Interpolation::default(),
+            resets: ResetHandling::default(),
+            week_start: WeekStart::default(),
+            // Checksums and signatures are downloaded alongside archives, so
+            // counting them would inflate totals.
+            exclude_assets: [
+                "*.sha256",
+                "*.sha512",
+                "*.b2",
+                "*.sig",
+                "*.asc",
+                "*.minisig",
+                "*.sigstore",
+                "*.sigstore.json",
+                "*.intoto.jsonl",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            report_excluded: false,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// Attribute the whole delta to the date of the later snapshot.
+    #[default]
+    None,
+    /// Spread the delta evenly across the days since the previous snapshot.
+    Linear,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ResetHandling {
+    /// Count nothing for the interval in which the count went backwards.
+    #[default]
+    Discard,
+    /// Treat the reset as a re-upload starting from zero, so the new count is
+    /// counted as downloads for the interval.
+    Baseline,
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+    /// ISO-8601 weeks: Monday-Sunday, labeled by week number (`2026-W03`).
+    Iso,
+}
+
+impl WeekStart {
+    const ALL: [WeekStart; 8] = [
+        WeekStart::Monday,
+        WeekStart::Tuesday,
+        WeekStart::Wednesday,
+        WeekStart::Thursday,
+        WeekStart::Friday,
+        WeekStart::Saturday,
+        WeekStart::Sunday,
+        WeekStart::Iso,
+    ];
+
+    /// The name used in configuration and stored alongside the aggregates.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            WeekStart::Monday => "monday",
+            WeekStart::Tuesday => "tuesday",
+            WeekStart::Wednesday => "wednesday",
+            WeekStart::Thursday => "thursday",
+            WeekStart::Friday => "friday",
+            WeekStart::Saturday => "saturday",
+            WeekStart::Sunday => "sunday",
+            WeekStart::Iso => "iso",
+        }
+    }
+
+    /// Parse a name produced by [`Self::as_str`].
+    pub fn parse(s: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|week_start| week_start.as_str() == s)
+    }
+
+    /// The first day of each week.
+    pub fn weekday(self) -> Weekday {
+        match self {
+            WeekStart::Monday | WeekStart::Iso => Weekday::Mon,
+            WeekStart::Tuesday => Weekday::Tue,
+            WeekStart::Wednesday => Weekday::Wed,
+            WeekStart::Thursday => Weekday::Thu,
+            WeekStart::Friday => Weekday::Fri,
+            WeekStart::Saturday => Weekday::Sat,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(tag = "kind", rename_all = "lowercase")]
-pub enum CollectionSource {
-    Github { owner: String, repo: String },
-    Crates { name: String },
+/// Number formatting policies, one per output target.
+///
+/// Public snapshots always contain raw values, so they have no policy.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FormattingConfig {
+    /// Command output printed to the terminal.
+    pub terminal: NumberFormat,
+    /// Chart axis labels and the downloads badge.
+    pub charts: NumberFormat,
+    /// The `number` filter in notification templates.
+    pub notifications: NumberFormat,
+    /// The HTML report and the Markdown digest.
+    pub reports: NumberFormat,
+}
+
+/// How to format numbers for an output target.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct NumberFormat {
+    pub style: NumberStyle,
+    /// Separator between groups of three digits, for the `grouped` style.
+    pub thousands_separator: String,
+    /// Separator before the fractional part, for the `compact` style.
+    pub decimal_separator: String,
+    /// Number of fractional digits for the `compact` style.
+    pub precision: usize,
+    /// Suffixes for thousands, millions, billions, and so on, for the
+    /// `compact` style.
+    pub suffixes: Vec<String>,
+}
+
+impl Default for NumberFormat {
+    fn default() -> Self {
+        Self {
+            style: NumberStyle::Grouped,
+            thousands_separator: ",".to_string(),
+            decimal_separator: ".".to_string(),
+            precision: 1,
+            suffixes: ["K", "M", "B", "T"].iter().map(|s| s.to_string()).collect(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum NumberStyle {
+    /// Plain digits: `1234567`.
+    Raw,
+    /// Digits with thousands separators: `1,234,567`.
+    Grouped,
+    /// Scaled with a unit suffix: `1.2M`.
+    Compact,
+}
+
+impl CollectionSource {
+    /// The source kind: `github`, `crates`, or `installer`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CollectionSource::Github { .. } => "github",
+            CollectionSource::Crates { .. } => "crates",
+            CollectionSource::Installer { .. } => "installer",
+        }
+    }
+
+    /// The source name: `owner/repo` for GitHub, the crate name for crates.io,
+    /// and the configured name for install endpoints.
+    pub fn name(&self) -> String {
+        match self {
+            CollectionSource::Github { owner, repo, .. } => format!("{}/{}", owner, repo),
+            CollectionSource::Crates { name, .. } | CollectionSource::Installer { name, .. } => {
+                name.clone()
+            }
+        }
+    }
+
+    /// Whether and how often the source is collected.
+    pub fn schedule(&self) -> &SourceSchedule {
+        match self {
+            CollectionSource::Github { schedule, .. }
+            | CollectionSource::Crates { schedule, .. }
+            | CollectionSource::Installer { schedule, .. } => schedule,
+        }
+    }
+}
+
+/// Parse a source from a command-line spec: `github:owner/repo`,
+/// `crates:name`, or `installer:name=stats_url`.
+impl FromStr for CollectionSource {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let Some((kind, rest)) = s.split_once(':') else {
+            bail!("expected 'kind:name' in source spec '{}'", s);
+        };
+        let source = match kind {
+            "github" => match rest.split_once('/') {
+                Some((owner, repo)) if !owner.is_empty() && !repo.is_empty() => {
+                    CollectionSource::Github {
+                        owner: owner.to_string(),
+                        repo: repo.to_string(),
+                        api: GithubApiConfig::default(),
+                        releases: Box::default(),
+                        schedule: SourceSchedule::default(),
+                    }
+                }
+                _ => bail!("expected 'github:owner/repo', found '{}'", s),
+            },
+            "crates" if !rest.is_empty() => CollectionSource::Crates {
+                name: rest.to_string(),
+                registry: RegistryConfig::default(),
+                schedule: SourceSchedule::default(),
+            },
+            "crates" => bail!("expected 'crates:name', found '{}'", s),
+            "installer" => match rest.split_once('=') {
+                Some((name, stats_url)) if !name.is_empty() && !stats_url.is_empty() => {
+                    CollectionSource::Installer {
+                        name: name.to_string(),
+                        stats_url: stats_url.to_string(),
+                        schedule: SourceSchedule::default(),
+                    }
+                }
+                _ => bail!("expected 'installer:name=stats_url', found '{}'", s),
+            },
+            _ => bail!(
+                "unknown source kind '{}' in '{}' (expected 'github', 'crates', or 'installer')",
+                kind,
+                s
+            ),
+        };
+        Ok(source)
+    }
 }
 
 impl Config {
-    /// Load configuration from a TOML file.
+    /// Load configuration from a TOML file, or from standard input if `path`
+    /// is `-`.
     pub fn load(path: &Utf8Path) -> Result<Self> {
-        let content = fs::read_to_string(path.as_std_path())
-            .with_context(|| format!("failed to read config file at {}", path))?;
+        let content = if path == "-" {
+            io::read_to_string(io::stdin()).context("failed to read config from standard input")?
+        } else {
+            fs::read_to_string(path.as_std_path())
+                .with_context(|| format!("failed to read config file at {}", path))?
+        };
+
+        let config: Config = toml::from_str(&content)
+            .with_context(|| format!("failed to parse config file at {}", path))?;
+        config
+            .check_projects()
+            .and_then(|()| config.charts.check())
+            .and_then(|()| config.check_metrics())
+            .and_then(|()| config.headline.check())
+            .and_then(|()| config.retry.check())
+            .and_then(|()| config.http.check())
+            .and_then(|()| config.check_alerts())
+            .and_then(|()| config.check_release_filters())
+            .and_then(|()| config.check_crate_sources())
+            .with_context(|| format!("invalid config file at {}", path))?;
+        Ok(config)
+    }
+
+    /// Check that metric expressions are valid, and that chart outputs only
+    /// refer to defined metrics.
+    fn check_metrics(&self) -> Result<()> {
+        crate::metrics::Metrics::new(&self.metrics)?;
+        for output in &self.charts.output {
+            for name in output.metrics.iter().flatten() {
+                if !self.metrics.contains_key(name) {
+                    bail!(
+                        "chart output '{}' refers to metric '{}', which is not defined",
+                        output.name,
+                        name
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the release filters of GitHub sources are valid.
+    fn check_release_filters(&self) -> Result<()> {
+        for source in &self.source {
+            if let CollectionSource::Github { releases, .. } = source {
+                crate::github::ReleaseFilter::new(releases).with_context(|| {
+                    format!("invalid release filter for source '{}'", source.name())
+                })?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that crate sources have distinct names. Crates are stored by
+    /// name whatever their registry, so two crates of the same name would
+    /// mix their downloads.
+    fn check_crate_sources(&self) -> Result<()> {
+        let crates: Vec<&str> = self
+            .source
+            .iter()
+            .filter_map(|source| match source {
+                CollectionSource::Crates { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        for (idx, name) in crates.iter().enumerate() {
+            if crates[..idx].contains(name) {
+                bail!(
+                    "crate '{}' is configured more than once (crates are stored by name, \
+                     whatever their registry)",
+                    name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that alert rules have distinct names and sensible thresholds.
+    fn check_alerts(&self) -> Result<()> {
+        for (idx, alert) in self.alert.iter().enumerate() {
+            if self.alert[..idx]
+                .iter()
+                .any(|other| other.name == alert.name)
+            {
+                bail!("alert '{}' is defined more than once", alert.name);
+            }
+            if !matches!(
+                alert.source.as_str(),
+                "github" | "crates" | "installer" | "all"
+            ) {
+                bail!(
+                    "alert '{}' has unknown source '{}', expected 'github', 'crates', \
+                     'installer', or 'all'",
+                    alert.name,
+                    alert.source
+                );
+            }
+            if !alert.percent.is_finite() || alert.percent <= 0.0 {
+                bail!("alert '{}' must have a percent above 0", alert.name);
+            }
+            if !(1..=MAX_ALERT_BASELINE_WEEKS).contains(&alert.baseline_weeks) {
+                bail!(
+                    "alert '{}' must have baseline_weeks between 1 and {}",
+                    alert.name,
+                    MAX_ALERT_BASELINE_WEEKS
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that projects only refer to configured sources.
+    fn check_projects(&self) -> Result<()> {
+        for project in &self.project {
+            for member in &project.sources {
+                if !self.source.iter().any(|source| member.matches(source)) {
+                    bail!(
+                        "project '{}' refers to '{}', which is not a configured source",
+                        project.name,
+                        member
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
 
-        toml::from_str(&content).with_context(|| format!("failed to parse config file at {}", path))
+    /// The chart outputs with the given names, or all of them if `names` is
+    /// empty.
+    pub fn chart_outputs(&self, names: &[String]) -> Result<Vec<ChartOutputConfig>> {
+        if names.is_empty() {
+            return Ok(self.charts.output.clone());
+        }
+        names
+            .iter()
+            .map(|name| {
+                self.charts
+                    .output
+                    .iter()
+                    .find(|output| &output.name == name)
+                    .cloned()
+                    .with_context(|| format!("no chart output named '{}' is configured", name))
+            })
+            .collect()
     }
 
     /// Get all GitHub sources.
     pub fn github_sources(&self) -> impl Iterator<Item = (&str, &str)> {
         self.source.iter().filter_map(|s| match s {
-            CollectionSource::Github { owner, repo } => Some((owner.as_str(), repo.as_str())),
+            CollectionSource::Github { owner, repo, .. } => Some((owner.as_str(), repo.as_str())),
             _ => None,
         })
     }
@@ -41,7 +1748,7 @@ impl Config {
     /// Get all crates.io sources.
     pub fn crates_sources(&self) -> impl Iterator<Item = &str> {
         self.source.iter().filter_map(|s| match s {
-            CollectionSource::Crates { name } => Some(name.as_str()),
+            CollectionSource::Crates { name, .. } => Some(name.as_str()),
             _ => None,
         })
     }
@@ -54,11 +1761,33 @@ impl Default for Config {
                 CollectionSource::Github {
                     owner: "nextest-rs".to_string(),
                     repo: "nextest".to_string(),
+                    api: GithubApiConfig::default(),
+                    releases: Box::new(ReleaseFilterConfig {
+                        tag_prefix: Some("cargo-nextest-".to_string()),
+                        ..ReleaseFilterConfig::default()
+                    }),
+                    schedule: SourceSchedule::default(),
                 },
                 CollectionSource::Crates {
                     name: "cargo-nextest".to_string(),
+                    registry: RegistryConfig::default(),
+                    schedule: SourceSchedule::default(),
                 },
             ],
+            notification: Vec::new(),
+            alert: Vec::new(),
+            issue_filing: None,
+            public_export: PublicExportConfig::default(),
+            formatting: FormattingConfig::default(),
+            aggregation: AggregationConfig::default(),
+            project: Vec::new(),
+            charts: ChartsConfig::default(),
+            owners: OwnersConfig::default(),
+            metrics: BTreeMap::new(),
+            headline: HeadlineConfig::default(),
+            retry: RetryConfig::default(),
+            http: HttpConfig::default(),
+            github: GithubConfig::default(),
         }
     }
 }
@@ -76,6 +1805,43 @@ mod tests {
         assert_eq!(config.source.len(), parsed.source.len());
     }
 
+    #[test]
+    fn test_parse_source_spec() {
+        let source: CollectionSource = "github:nextest-rs/nextest".parse().unwrap();
+        assert_eq!(
+            (source.kind(), source.name()),
+            ("github", "nextest-rs/nextest".to_string())
+        );
+        let source: CollectionSource = "crates:cargo-nextest".parse().unwrap();
+        assert_eq!(
+            (source.kind(), source.name()),
+            ("crates", "cargo-nextest".to_string())
+        );
+        let source: CollectionSource = "installer:get.nexte.st=https://get.nexte.st/stats.json"
+            .parse()
+            .unwrap();
+        assert!(matches!(
+            source,
+            CollectionSource::Installer { ref name, ref stats_url, .. }
+                if name == "get.nexte.st" && stats_url == "https://get.nexte.st/stats.json"
+        ));
+
+        for invalid in [
+            "nextest-rs/nextest",
+            "github:nextest",
+            "github:/nextest",
+            "crates:",
+            "installer:get.nexte.st",
+            "pypi:foo",
+        ] {
+            assert!(
+                invalid.parse::<CollectionSource>().is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
+    }
+
     #[test]
     fn test_parse_config() {
         let toml = r#"
@@ -99,5 +1865,711 @@ name = "cargo-nextest"
         let crates: Vec<_> = config.crates_sources().collect();
         assert_eq!(crates.len(), 1);
         assert_eq!(crates[0], "cargo-nextest");
+        assert!(config.notification.is_empty());
+        assert_eq!(config.formatting.terminal.style, NumberStyle::Grouped);
+    }
+
+    #[test]
+    fn test_parse_formatting() {
+        let toml = r#"
+[formatting.terminal]
+thousands_separator = "."
+
+[formatting.charts]
+style = "compact"
+suffixes = ["k", "m"]
+
+[formatting.reports]
+style = "compact"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let formatting = config.formatting;
+        assert_eq!(formatting.terminal.style, NumberStyle::Grouped);
+        assert_eq!(formatting.terminal.thousands_separator, ".");
+        assert_eq!(formatting.charts.style, NumberStyle::Compact);
+        assert_eq!(formatting.charts.suffixes, ["k", "m"]);
+        assert_eq!(formatting.charts.precision, 1);
+        assert_eq!(formatting.notifications.style, NumberStyle::Grouped);
+        assert_eq!(formatting.reports.style, NumberStyle::Compact);
+        assert_eq!(formatting.reports.suffixes, ["K", "M", "B", "T"]);
+    }
+
+    #[test]
+    fn test_parse_aggregation() {
+        let config: Config = toml::from_str("").unwrap();
+        assert_eq!(config.aggregation.interpolation, Interpolation::None);
+        assert_eq!(config.aggregation.resets, ResetHandling::Discard);
+
+        let config: Config =
+            toml::from_str("[aggregation]\ninterpolation = \"linear\"\nresets = \"baseline\"")
+                .unwrap();
+        assert_eq!(config.aggregation.interpolation, Interpolation::Linear);
+        assert_eq!(config.aggregation.resets, ResetHandling::Baseline);
+
+        let config: Config = toml::from_str("[aggregation]\nweek_start = \"sunday\"").unwrap();
+        assert_eq!(config.aggregation.week_start, WeekStart::Sunday);
+        assert!(
+            config
+                .aggregation
+                .exclude_assets
+                .contains(&"*.sha256".to_string())
+        );
+
+        let config: Config = toml::from_str("[aggregation]\nexclude_assets = []").unwrap();
+        assert!(config.aggregation.exclude_assets.is_empty());
+        for week_start in WeekStart::ALL {
+            assert_eq!(WeekStart::parse(week_start.as_str()), Some(week_start));
+        }
+    }
+
+    #[test]
+    fn test_parse_notifications() {
+        let toml = r#"
+[[notification]]
+name = "team-slack"
+kind = "slack"
+
+[[notification]]
+name = "weekly-email"
+kind = "email"
+template = "Collected {{records}} records on {{date}}."
+
+[[notification]]
+name = "dashboard"
+kind = "webhook"
+url = "env:DASHBOARD_WEBHOOK_URL"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(config.notification.len(), 3);
+        assert_eq!(config.notification[0].kind, NotificationKind::Slack);
+        assert!(config.notification[0].template.is_none());
+        assert!(config.notification[0].url.is_none());
+        assert_eq!(config.notification[1].kind, NotificationKind::Email);
+        assert!(config.notification[1].template.is_some());
+        assert_eq!(config.notification[2].kind, NotificationKind::Webhook);
+        assert_eq!(
+            config.notification[2].url.as_deref(),
+            Some("env:DASHBOARD_WEBHOOK_URL")
+        );
+    }
+
+    #[test]
+    fn test_source_schedule() {
+        let toml = r#"
+[[source]]
+kind = "crates"
+name = "cargo-nextest"
+frequency = "hourly"
+
+[[source]]
+kind = "github"
+owner = "nextest-rs"
+repo = "nextest"
+enabled = false
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        assert_eq!(
+            config.source[0].schedule(),
+            &SourceSchedule {
+                enabled: true,
+                frequency: Some(Frequency::Hourly),
+            }
+        );
+        assert!(!config.source[1].schedule().enabled);
+        // Defaults are left out, so configurations without schedules
+        // serialize as before.
+        let serialized = toml::to_string(&Config::default()).unwrap();
+        assert!(!serialized.contains("enabled") && !serialized.contains("frequency"));
+
+        let time = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        let last = time("2025-11-18T03:00:00Z");
+        assert!(!Frequency::Daily.is_due(last, time("2025-11-18T23:59:00Z")));
+        assert!(Frequency::Daily.is_due(last, time("2025-11-19T00:00:00Z")));
+        assert!(!Frequency::Hourly.is_due(last, time("2025-11-18T03:59:00Z")));
+        assert!(Frequency::Hourly.is_due(last, time("2025-11-18T04:00:00Z")));
+        assert!(Frequency::Hourly.is_due(last, time("2025-11-19T03:30:00Z")));
+    }
+
+    #[test]
+    fn test_parse_alerts() {
+        let toml = r#"
+[[alert]]
+name = "crates-drop"
+source = "crates"
+direction = "drop"
+percent = 30
+
+[[alert]]
+name = "surge"
+direction = "rise"
+percent = 50
+baseline_weeks = 8
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.check_alerts().unwrap();
+        assert_eq!(config.alert[0].baseline_weeks, 4);
+        assert_eq!(config.alert[1].source, "all");
+        assert_eq!(config.alert[1].direction, AlertDirection::Rise);
+
+        for bad in [
+            toml.replace("surge", "crates-drop"),
+            toml.replace("\"crates\"", "\"homebrew\""),
+            toml.replace("percent = 50", "percent = 0"),
+            toml.replace("baseline_weeks = 8", "baseline_weeks = 0"),
+            toml.replace("baseline_weeks = 8", "baseline_weeks = 521"),
+            toml.replace(
+                "baseline_weeks = 8",
+                "baseline_weeks = 18446744073709551615",
+            ),
+        ] {
+            let config: Config = toml::from_str(&bad).unwrap();
+            assert!(config.check_alerts().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_github_api() {
+        let toml = r#"
+[[source]]
+kind = "github"
+owner = "nextest-rs"
+repo = "nextest"
+api.base_url = "https://gateway.example.com/github"
+api.token_env = "GATEWAY_GITHUB_TOKEN"
+api.headers = { "X-Api-Key" = "env:GATEWAY_API_KEY" }
+api.signing = { secret_env = "GATEWAY_SIGNING_SECRET" }
+
+[[source]]
+kind = "github"
+owner = "nextest-rs"
+repo = "quick-junit"
+
+[[source]]
+kind = "github"
+owner = "tools"
+repo = "builder"
+api.base_url = "https://github.example.com/api/v3"
+api.auth = "token"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let CollectionSource::Github { api, .. } = &config.source[0] else {
+            panic!("expected a GitHub source");
+        };
+        assert_eq!(
+            api.base_url.as_deref(),
+            Some("https://gateway.example.com/github")
+        );
+        assert_eq!(api.headers["X-Api-Key"], "env:GATEWAY_API_KEY");
+        let signing = api.signing.as_ref().unwrap();
+        assert_eq!(signing.secret_env, "GATEWAY_SIGNING_SECRET");
+        assert_eq!(signing.header, "X-Signature");
+        assert_eq!(api.auth, GithubAuthStyle::Bearer);
+
+        let CollectionSource::Github { api, .. } = &config.source[1] else {
+            panic!("expected a GitHub source");
+        };
+        assert!(api.is_default());
+
+        let CollectionSource::Github { api, .. } = &config.source[2] else {
+            panic!("expected a GitHub source");
+        };
+        assert_eq!(api.auth, GithubAuthStyle::Token);
+        assert_eq!(api.graphql_url, None);
+        assert!(toml::from_str::<Config>(&toml.replace("\"token\"", "\"oauth\"")).is_err());
+    }
+
+    #[test]
+    fn test_parse_github_app() {
+        let toml = r#"
+[[source]]
+kind = "github"
+owner = "nextest-rs"
+repo = "nextest"
+api.app = { app_id = 12345, installation_id = 678, private_key_env = "COLLECTOR_APP_KEY" }
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let CollectionSource::Github { api, .. } = &config.source[0] else {
+            panic!("expected a GitHub source");
+        };
+        let app = api.app.as_ref().unwrap();
+        assert_eq!(app.app_id, 12345);
+        assert_eq!(app.installation_id, Some(678));
+        assert_eq!(app.private_key_env.as_deref(), Some("COLLECTOR_APP_KEY"));
+        assert_eq!(app.private_key_file, None);
+
+        let config: Config =
+            toml::from_str(&format!("{}api.token_envs = [\"TOKEN_2\"]\n", toml)).unwrap();
+        let CollectionSource::Github { api, .. } = &config.source[0] else {
+            panic!("expected a GitHub source");
+        };
+        let err = crate::github::Api::new(api).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "api.app can't be combined with api.token_envs or api.tokens_file"
+        );
+    }
+
+    #[test]
+    fn test_parse_release_filters() {
+        let toml = r#"
+[[source]]
+kind = "github"
+owner = "nextest-rs"
+repo = "nextest"
+tag_prefix = "cargo-nextest-"
+exclude_assets = ["*.sha256"]
+
+[[source]]
+kind = "github"
+owner = "o"
+repo = "r"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        config.check_release_filters().unwrap();
+        let CollectionSource::Github { releases, .. } = &config.source[0] else {
+            panic!("expected a GitHub source");
+        };
+        assert_eq!(releases.tag_prefix.as_deref(), Some("cargo-nextest-"));
+        assert_eq!(releases.exclude_assets, ["*.sha256"]);
+        let CollectionSource::Github { releases, .. } = &config.source[1] else {
+            panic!("expected a GitHub source");
+        };
+        assert_eq!(**releases, ReleaseFilterConfig::default());
+
+        let invalid = toml.replace("tag_prefix = \"cargo-nextest-\"", "tag_regex = \"[\"");
+        let config: Config = toml::from_str(&invalid).unwrap();
+        assert!(config.check_release_filters().is_err());
+    }
+
+    #[test]
+    fn test_check_crate_sources() {
+        let toml = r#"
+[[source]]
+kind = "crates"
+name = "cargo-nextest"
+
+[[source]]
+kind = "crates"
+name = "internal-tool"
+registry.base_url = "https://kellnr.example.com/api/v1"
+registry.token_env = "KELLNR_TOKEN"
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        config.check_crate_sources().unwrap();
+        let CollectionSource::Crates { registry, .. } = &config.source[1] else {
+            panic!("expected a crates source");
+        };
+        assert_eq!(
+            registry.base_url.as_deref(),
+            Some("https://kellnr.example.com/api/v1")
+        );
+
+        // The same name from another registry is still the same crate.
+        let duplicate = toml.replace("internal-tool", "cargo-nextest");
+        let config: Config = toml::from_str(&duplicate).unwrap();
+        let err = config.check_crate_sources().unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("crate 'cargo-nextest' is configured more than once"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_parse_issue_filing() {
+        let toml = r#"
+[issue_filing]
+owner = "nextest-rs"
+repo = "download-stats"
+labels = ["collection-failure"]
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        let issue_filing = config.issue_filing.unwrap();
+        assert_eq!(issue_filing.after_failures, 3);
+        assert_eq!(issue_filing.labels, ["collection-failure"]);
+    }
+
+    #[test]
+    fn test_parse_projects() {
+        let toml = r#"
+[[source]]
+kind = "github"
+owner = "nextest-rs"
+repo = "nextest"
+
+[[source]]
+kind = "crates"
+name = "cargo-nextest"
+
+[[project]]
+name = "nextest"
+sources = ["github:nextest-rs/nextest", "crates:cargo-nextest"]
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        config.check_projects().unwrap();
+        let project = &config.project[0];
+        assert_eq!(project.name, "nextest");
+        assert_eq!(
+            project.sources[1],
+            SourceRef {
+                kind: "crates".to_string(),
+                name: "cargo-nextest".to_string(),
+            }
+        );
+        assert_eq!(project.sources[0].to_string(), "github:nextest-rs/nextest");
+
+        // Members must be configured sources.
+        let unknown = toml.replace("crates:cargo-nextest", "crates:nextest-runner");
+        let config: Config = toml::from_str(&unknown).unwrap();
+        assert!(config.check_projects().is_err());
+
+        let invalid = toml.replace("crates:cargo-nextest", "cargo-nextest");
+        assert!(toml::from_str::<Config>(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_chart_theme() {
+        assert_eq!("light".parse::<ChartTheme>().unwrap(), ChartTheme::Light);
+        assert_eq!("dark".parse::<ChartTheme>().unwrap(), ChartTheme::Dark);
+        for bad in ["", "Dark", "solarized"] {
+            let err = bad.parse::<ChartTheme>().unwrap_err();
+            assert_eq!(
+                err.to_string(),
+                format!("unknown chart theme '{}' (expected 'light' or 'dark')", bad)
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_chart_outputs() {
+        let toml = r#"
+[[charts.output]]
+name = "website"
+path = "charts"
+
+[[charts.output]]
+name = "sponsors"
+path = "report/charts"
+charts = ["weekly-trends", "downloads-badge"]
+format = "jpeg"
+theme = "dark"
+since = "90d"
+until = "2025-12-31"
+resolution = "daily"
+size = "800x450"
+scale = 2
+        "#;
+
+        let config: Config = toml::from_str(toml).unwrap();
+        config.charts.check().unwrap();
+        let website = &config.charts.output[0];
+        assert_eq!(website.charts, ChartKind::all());
+        assert_eq!(
+            (website.format, website.theme),
+            (ChartFormat::Png, ChartTheme::Light)
+        );
+
+        let outputs = config.chart_outputs(&["sponsors".to_string()]).unwrap();
+        assert_eq!(outputs.len(), 1);
+        let sponsors = &outputs[0];
+        assert_eq!(sponsors.path, "report/charts");
+        assert_eq!(
+            sponsors
+                .charts
+                .iter()
+                .map(|kind| kind.file_name(sponsors.format))
+                .collect::<Vec<_>>(),
+            ["weekly-trends.jpg", "downloads-badge.svg"]
+        );
+        assert_eq!(sponsors.theme, ChartTheme::Dark);
+        assert_eq!(
+            (website.since, website.until, website.resolution),
+            (None, None, ChartResolution::Weekly)
+        );
+        let today = NaiveDate::from_ymd_opt(2025, 6, 30).unwrap();
+        assert_eq!(
+            sponsors.since.map(|since| since.resolve(today)),
+            NaiveDate::from_ymd_opt(2025, 4, 1)
+        );
+        assert_eq!(
+            sponsors.until,
+            Some(ChartDate::Date(
+                NaiveDate::from_ymd_opt(2025, 12, 31).unwrap()
+            ))
+        );
+        assert_eq!(sponsors.resolution, ChartResolution::Daily);
+        assert_eq!(
+            (website.size, sponsors.size),
+            (
+                None,
+                Some(ChartSize {
+                    width: 800,
+                    height: 450
+                })
+            )
+        );
+        assert_eq!((website.scale, sponsors.scale), (1, 2));
+        for bad in ["scale = 0", "scale = 9", "scale = 4294967295"] {
+            let config: Config = toml::from_str(&toml.replace("scale = 2", bad)).unwrap();
+            assert!(config.charts.check().is_err(), "{} should be rejected", bad);
+        }
+        for bad in ["800", "800x", "0x450", "800X450"] {
+            assert!(
+                bad.parse::<ChartSize>().is_err(),
+                "{} should be rejected",
+                bad
+            );
+        }
+        assert_eq!(
+            "sparkline".parse::<ChartKind>().unwrap(),
+            ChartKind::Sparkline
+        );
+        assert!("sparklines".parse::<ChartKind>().is_err());
+        for bad in ["90", "d", "-5d", "2025-13-01"] {
+            assert!(
+                bad.parse::<ChartDate>().is_err(),
+                "{} should be rejected",
+                bad
+            );
+        }
+
+        assert_eq!(config.chart_outputs(&[]).unwrap().len(), 2);
+        assert!(config.chart_outputs(&["blog".to_string()]).is_err());
+
+        let duplicate = toml.replace("sponsors", "website");
+        let config: Config = toml::from_str(&duplicate).unwrap();
+        assert!(config.charts.check().is_err());
+
+        let unknown = toml.replace("weekly-trends", "weekly-totals");
+        assert!(toml::from_str::<Config>(&unknown).is_err());
+    }
+
+    #[test]
+    fn test_chart_date() {
+        let today = NaiveDate::from_ymd_opt(2025, 6, 30).unwrap();
+        let resolve = |s: &str| s.parse::<ChartDate>().unwrap().resolve(today);
+        assert_eq!(resolve("0d"), today);
+        assert_eq!(
+            resolve("30d"),
+            NaiveDate::from_ymd_opt(2025, 5, 31).unwrap()
+        );
+        assert_eq!(
+            resolve("2024-02-29"),
+            NaiveDate::from_ymd_opt(2024, 2, 29).unwrap()
+        );
+
+        let max = format!("{}d", ChartDate::MAX_DAYS_AGO);
+        assert!(resolve(&max) < NaiveDate::from_ymd_opt(1926, 1, 1).unwrap());
+
+        let too_far = format!("{}d", ChartDate::MAX_DAYS_AGO + 1);
+        let err = too_far.parse::<ChartDate>().unwrap_err();
+        assert!(err.to_string().contains("too far back"), "{}", err);
+        assert!("18446744073709551615d".parse::<ChartDate>().is_err());
+        assert!("99999999999999999999d".parse::<ChartDate>().is_err());
+    }
+
+    #[test]
+    fn test_parse_metrics() {
+        let toml = r#"
+[metrics.github_share]
+expr = "github / total"
+description = "Share of downloads from GitHub releases"
+format = "percent"
+
+[metrics.crates_4w]
+expr = "sum(crates, 4)"
+
+[[charts.output]]
+name = "website"
+path = "charts"
+metrics = ["github_share"]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.check_metrics().unwrap();
+        assert_eq!(config.metrics["github_share"].format, MetricFormat::Percent);
+        assert_eq!(config.metrics["crates_4w"].format, MetricFormat::Number);
+
+        let unknown = toml.replace(r#"metrics = ["github_share"]"#, r#"metrics = ["share"]"#);
+        let config: Config = toml::from_str(&unknown).unwrap();
+        assert!(config.check_metrics().is_err());
+
+        let invalid = toml.replace("sum(crates, 4)", "sum(crates)");
+        let config: Config = toml::from_str(&invalid).unwrap();
+        assert!(config.check_metrics().is_err());
+    }
+
+    #[test]
+    fn test_parse_custom_charts() {
+        let toml = r#"
+[charts.custom.platform-share]
+title = "Share of GitHub downloads by platform"
+query = "SELECT week_start, share, platform FROM platform_share"
+format = "percent"
+
+[charts.custom.installs]
+title = "Installs"
+query = "SELECT date, downloads FROM installer_downloads"
+
+[[charts.output]]
+name = "website"
+path = "charts"
+custom = ["installs"]
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.charts.check().unwrap();
+        let share = &config.charts.custom["platform-share"];
+        assert_eq!(share.format, MetricFormat::Percent);
+        assert_eq!(
+            config.charts.custom["installs"].format,
+            MetricFormat::Number
+        );
+        assert_eq!(
+            config.charts.output[0].custom.as_deref(),
+            Some(&["installs".to_string()][..])
+        );
+
+        for bad in [
+            toml.replace(r#"custom = ["installs"]"#, r#"custom = ["downloads"]"#),
+            toml.replace(
+                "[charts.custom.installs]",
+                "[charts.custom.\"all installs\"]",
+            ),
+            toml.replace("SELECT date, downloads FROM installer_downloads", " "),
+        ] {
+            let config: Config = toml::from_str(&bad).unwrap();
+            assert!(config.charts.check().is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_headline() {
+        let toml = r#"
+[headline]
+sources = ["github", "crates"]
+exclude = ["github:cargo-nextest-0.9.0", "crates:nextest-runner"]
+adjustment = -500
+round_down_to = 1000
+        "#;
+        let config: Config = toml::from_str(toml).unwrap();
+        config.headline.check().unwrap();
+        assert!(config.headline.is_excluded("crates", "nextest-runner"));
+        assert!(!config.headline.is_excluded("github", "nextest-runner"));
+        assert_eq!(config.headline.adjustment, -500);
+
+        for bad in [
+            toml.replace(r#""github", "crates""#, r#""github", "docs""#),
+            toml.replace("round_down_to = 1000", "round_down_to = 0"),
+        ] {
+            let config: Config = toml::from_str(&bad).unwrap();
+            assert!(config.headline.check().is_err(), "{}", bad);
+        }
+        assert!(toml::from_str::<Config>(&toml.replace("crates:nextest", "nextest")).is_err());
+    }
+
+    #[test]
+    fn test_retry_config() {
+        let config: Config = toml::from_str("[retry]\nmax_attempts = 2").unwrap();
+        config.retry.check().unwrap();
+        assert_eq!(config.retry.max_attempts, 2);
+        assert_eq!(config.retry.initial_delay_ms, 1000);
+
+        let config: Config = toml::from_str("[retry]\nmax_attempts = 0").unwrap();
+        assert!(config.retry.check().is_err());
+
+        let config: Config = toml::from_str("[http]\nread_timeout_secs = 5").unwrap();
+        config.http.check().unwrap();
+        assert_eq!(config.http.connect_timeout_secs, 10);
+        assert_eq!(config.http.read_timeout_secs, 5);
+        let config: Config = toml::from_str("[http]\nconnect_timeout_secs = 0").unwrap();
+        assert!(config.http.check().is_err());
+
+        let config: Config = toml::from_str(
+            r#"
+[http]
+proxy = "http://proxy.example.com:3128"
+no_proxy = ["ghe.example.com"]
+ca_bundle = "certs/proxy-ca.pem"
+"#,
+        )
+        .unwrap();
+        config.http.check().unwrap();
+        assert_eq!(config.http.no_proxy, ["ghe.example.com"]);
+        assert_eq!(
+            config.http.ca_bundle.as_deref(),
+            Some(Utf8Path::new("certs/proxy-ca.pem"))
+        );
+        let config: Config = toml::from_str("[http]\nproxy = \"not a url\"").unwrap();
+        assert_eq!(
+            config.http.check().unwrap_err().to_string(),
+            "invalid http.proxy 'not a url'"
+        );
+    }
+
+    #[test]
+    fn test_parse_chart_style() {
+        let config: Config = toml::from_str(
+            r##"
+[charts]
+width = 1200
+font_family = "DejaVu Sans"
+font_file = "fonts/DejaVuSans.ttf"
+colors = ["#0f172a", "#F97316"]
+top_versions = 8
+top_assets_by = "platform"
+release_annotations = "minor"
+log_scale = true
+"##,
+        )
+        .unwrap();
+        config.charts.check().unwrap();
+        assert_eq!((config.charts.width, config.charts.height), (1200, 900));
+        assert_eq!(config.charts.font_family, "DejaVu Sans");
+        assert_eq!(
+            config.charts.font_file.as_deref(),
+            Some(Utf8Path::new("fonts/DejaVuSans.ttf"))
+        );
+        assert_eq!(
+            config.charts.colors,
+            [ChartColor(15, 23, 42), ChartColor(249, 115, 22)]
+        );
+        assert_eq!(String::from(config.charts.other_color), "#9ca3af");
+        assert_eq!(config.charts.top_versions, 8);
+        assert_eq!(config.charts.top_assets, 10);
+        assert_eq!(config.charts.top_assets_by, TopAssetsBy::Platform);
+        assert_eq!(config.charts.release_annotations, ReleaseAnnotations::Minor);
+        assert!(config.charts.log_scale);
+        assert!(!config.charts.moving_average);
+
+        for bad in [
+            "colors = [\"blue\"]",
+            "colors = [\"#12345\"]",
+            "release_annotations = \"major\"",
+        ] {
+            assert!(toml::from_str::<Config>(&format!("[charts]\n{}", bad)).is_err());
+        }
+        for invalid in [
+            "width = 0",
+            "colors = []",
+            "top_platforms = 0",
+            "top_assets = 0",
+            "version_min_share = 1.5",
+            "margin = 500",
+            "margin = 4294967295",
+        ] {
+            let config: Config = toml::from_str(&format!("[charts]\n{}", invalid)).unwrap();
+            assert!(
+                config.charts.check().is_err(),
+                "{} should be rejected",
+                invalid
+            );
+        }
     }
 }
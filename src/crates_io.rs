@@ -2,13 +2,70 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 //! crates.io API client for fetching download statistics.
+//!
+//! Crates on other registries, such as a private registry or Kellnr, are
+//! fetched the same way from the registry's base URL, as configured with
+//! [`RegistryConfig`], as long as it serves a compatible API.
 
+use crate::{config::RegistryConfig, http, retry};
 use anyhow::{Context, Result};
 use chrono::NaiveDate;
 use serde::Deserialize;
+use std::env;
 
 const CRATES_IO_API_BASE: &str = "https://crates.io/api/v1";
 
+/// A registry API endpoint with its credentials resolved from the
+/// environment.
+#[derive(Debug)]
+pub struct Registry {
+    base_url: String,
+    /// The registry's host, such as `crates.io`, for messages.
+    host: String,
+    headers: Vec<(String, String)>,
+}
+
+impl Registry {
+    /// Resolve a registry configuration, reading secrets from the
+    /// environment.
+    pub fn new(config: &RegistryConfig) -> Result<Self> {
+        let base_url = config
+            .base_url
+            .as_deref()
+            .unwrap_or(CRATES_IO_API_BASE)
+            .trim_end_matches('/')
+            .to_string();
+        let host = reqwest::Url::parse(&base_url)
+            .with_context(|| format!("invalid registry URL '{}'", base_url))?
+            .host_str()
+            .unwrap_or_default()
+            .to_string();
+
+        let mut headers = Vec::new();
+        if let Some(token_env) = &config.token_env {
+            let token = env::var(token_env).with_context(|| {
+                format!("{} must be set to authenticate with {}", token_env, host)
+            })?;
+            headers.push(("Authorization".to_string(), token));
+        }
+        for (name, value) in &config.headers {
+            let value = match value.strip_prefix("env:") {
+                Some(var) => env::var(var).with_context(|| {
+                    format!("{} must be set for the '{}' registry header", var, name)
+                })?,
+                None => value.clone(),
+            };
+            headers.push((name.clone(), value));
+        }
+
+        Ok(Self {
+            base_url,
+            host,
+            headers,
+        })
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct CrateResponse {
     #[serde(rename = "crate")]
@@ -48,74 +105,133 @@ pub struct ExtraDownload {
     pub downloads: u64,
 }
 
-/// Fetch crate metadata including cumulative download totals.
-pub async fn fetch_crate_metadata(crate_name: &str) -> Result<CrateInfo> {
-    let url = format!("{}/crates/{}", CRATES_IO_API_BASE, crate_name);
+/// A published version of a crate.
+#[derive(Debug, Deserialize)]
+pub struct CrateVersion {
+    /// Numeric version ID, as used in download statistics.
+    pub id: u64,
+    /// The semver version string.
+    pub num: String,
+    /// RFC3339 timestamp of when the version was published.
+    pub created_at: String,
+    #[serde(default)]
+    pub yanked: bool,
+    /// All-time downloads of the version.
+    #[serde(default)]
+    pub downloads: u64,
+}
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .header(
+/// An owner of a crate: either a user or a team.
+#[derive(Debug, Deserialize)]
+pub struct CrateOwner {
+    /// The user's login, or `github:org:team` for teams.
+    pub login: String,
+    /// `user` or `team`.
+    pub kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct OwnersResponse {
+    users: Vec<CrateOwner>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionsResponse {
+    versions: Vec<CrateVersion>,
+    meta: VersionsMeta,
+}
+
+#[derive(Debug, Deserialize)]
+struct VersionsMeta {
+    /// Query string for the next page, if any.
+    next_page: Option<String>,
+}
+
+/// Send a GET request to a registry API and parse the JSON response.
+async fn get_json<T: serde::de::DeserializeOwned>(
+    registry: &Registry,
+    url: &str,
+    crate_name: &str,
+    what: &str,
+) -> Result<T> {
+    let client = http::client();
+    let description = format!(
+        "{} request for the {} of '{}'",
+        registry.host, what, crate_name
+    );
+    let response = retry::send(&description, || {
+        let mut request = client.get(url).header(
             "User-Agent",
             "nextest-download-stats-collector (contact: opensource@nexte.st)",
-        )
-        .send()
-        .await
-        .with_context(|| format!("failed to fetch metadata for crate '{}'", crate_name))?;
+        );
+        for (name, value) in &registry.headers {
+            request = request.header(name, value);
+        }
+        Ok(request)
+    })
+    .await
+    .with_context(|| format!("failed to fetch {} for crate '{}'", what, crate_name))?;
 
     if !response.status().is_success() {
         let status = response.status();
         let body = response.text().await.unwrap_or_default();
         anyhow::bail!(
-            "crates.io API request failed with status {} for crate '{}': {}",
+            "{} API request failed with status {} for crate '{}': {}",
+            registry.host,
             status,
             crate_name,
             body
         );
     }
 
-    let crate_response = response
-        .json::<CrateResponse>()
+    response
+        .json::<T>()
         .await
-        .context("failed to parse crates.io API response")?;
+        .with_context(|| format!("failed to parse {} API response", registry.host))
+}
 
+/// Fetch crate metadata including cumulative download totals.
+pub async fn fetch_crate_metadata(registry: &Registry, crate_name: &str) -> Result<CrateInfo> {
+    let url = format!("{}/crates/{}", registry.base_url, crate_name);
+    let crate_response: CrateResponse = get_json(registry, &url, crate_name, "metadata").await?;
     Ok(crate_response.crate_info)
 }
 
-/// Fetch download statistics for a crate from crates.io.
+/// Fetch download statistics for a crate.
 ///
 /// Note: The crates.io API only provides the last year of data.
-pub async fn fetch_downloads(crate_name: &str) -> Result<DownloadsResponse> {
-    let url = format!("{}/crates/{}/downloads", CRATES_IO_API_BASE, crate_name);
+pub async fn fetch_downloads(registry: &Registry, crate_name: &str) -> Result<DownloadsResponse> {
+    let url = format!("{}/crates/{}/downloads", registry.base_url, crate_name);
+    get_json(registry, &url, crate_name, "downloads").await
+}
 
-    let client = reqwest::Client::new();
-    let response = client
-        .get(&url)
-        .header(
-            "User-Agent",
-            "nextest-download-stats-collector (contact: opensource@nexte.st)",
-        )
-        .send()
-        .await
-        .with_context(|| format!("failed to fetch downloads for crate '{}'", crate_name))?;
+/// Fetch all published versions of a crate, following pagination.
+///
+/// This is used to map the numeric version IDs in download statistics to
+/// semver strings.
+pub async fn fetch_versions(registry: &Registry, crate_name: &str) -> Result<Vec<CrateVersion>> {
+    let base = format!("{}/crates/{}/versions", registry.base_url, crate_name);
+    let mut url = format!("{}?per_page=100", base);
+    let mut all_versions = Vec::new();
 
-    if !response.status().is_success() {
-        let status = response.status();
-        let body = response.text().await.unwrap_or_default();
-        anyhow::bail!(
-            "crates.io API request failed with status {} for crate '{}': {}",
-            status,
-            crate_name,
-            body
-        );
+    loop {
+        let page: VersionsResponse = get_json(registry, &url, crate_name, "versions").await?;
+        all_versions.extend(page.versions);
+
+        match page.meta.next_page {
+            Some(next_page) => url = format!("{}{}", base, next_page),
+            None => break,
+        }
     }
 
-    let downloads = response
-        .json::<DownloadsResponse>()
-        .await
-        .context("failed to parse crates.io API response")?;
+    Ok(all_versions)
+}
 
-    Ok(downloads)
+/// Fetch the users and teams that own a crate.
+pub async fn fetch_owners(registry: &Registry, crate_name: &str) -> Result<Vec<CrateOwner>> {
+    let url = format!("{}/crates/{}/owners", registry.base_url, crate_name);
+    let owners: OwnersResponse = get_json(registry, &url, crate_name, "owners").await?;
+    Ok(owners.users)
 }
 
 /// Parse a date string from crates.io (YYYY-MM-DD format).
@@ -131,7 +247,8 @@ mod tests {
 
     #[tokio::test]
     async fn test_fetch_downloads() {
-        let downloads = fetch_downloads("cargo-nextest").await.unwrap();
+        let registry = Registry::new(&RegistryConfig::default()).unwrap();
+        let downloads = fetch_downloads(&registry, "cargo-nextest").await.unwrap();
         assert!(
             !downloads.version_downloads.is_empty(),
             "should have version downloads"
@@ -146,6 +263,54 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_versions_response() {
+        let json = r#"{
+            "versions": [
+                {"id": 2148511, "num": "0.9.100", "created_at": "2025-06-01T00:00:00Z", "yanked": false},
+                {"id": 2100000, "num": "0.9.99", "created_at": "2025-05-01T00:00:00Z"}
+            ],
+            "meta": {"total": 2, "next_page": "?per_page=100&seek=abc"}
+        }"#;
+        let response: VersionsResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(response.versions.len(), 2);
+        assert_eq!(response.versions[0].id, 2148511);
+        assert_eq!(response.versions[0].num, "0.9.100");
+        assert!(!response.versions[1].yanked);
+        assert_eq!(
+            response.meta.next_page.as_deref(),
+            Some("?per_page=100&seek=abc")
+        );
+    }
+
+    #[test]
+    fn test_registry() {
+        let registry = Registry::new(&RegistryConfig::default()).unwrap();
+        assert_eq!(registry.base_url, CRATES_IO_API_BASE);
+        assert_eq!(registry.host, "crates.io");
+        assert!(registry.headers.is_empty());
+
+        let mut config = RegistryConfig {
+            base_url: Some("https://kellnr.example.com/api/v1/".to_string()),
+            headers: [("X-Team".to_string(), "tools".to_string())].into(),
+            ..RegistryConfig::default()
+        };
+        let registry = Registry::new(&config).unwrap();
+        assert_eq!(registry.base_url, "https://kellnr.example.com/api/v1");
+        assert_eq!(registry.host, "kellnr.example.com");
+        assert_eq!(
+            registry.headers,
+            [("X-Team".to_string(), "tools".to_string())]
+        );
+
+        config.token_env = Some("DOWNLOAD_STATS_TEST_UNSET_TOKEN".to_string());
+        let err = Registry::new(&config).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "DOWNLOAD_STATS_TEST_UNSET_TOKEN must be set to authenticate with kellnr.example.com"
+        );
+    }
+
     #[test]
     fn test_parse_date() {
         let date = parse_date("2025-11-19").unwrap();
@@ -0,0 +1,217 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Scheduled collection.
+//!
+//! `collect --daemon` keeps running and collects on a cron schedule, such as
+//! `0 3 * * *` for 03:00 UTC every day, regenerating the configured charts
+//! after each collection. A failed run is logged and retried at the next
+//! scheduled time rather than stopping the daemon, so deployments need
+//! nothing but a process supervisor.
+//!
+//! Every run logs when it started, how it went and how long it took, how
+//! many runs in a row have failed, and when the next run is due.
+
+use crate::{
+    commands::{self, CollectOptions},
+    config::{ChartOutputConfig, Config},
+    format,
+    output::{self, status},
+    summary::CollectionSummary,
+};
+use anyhow::{Result, anyhow, bail};
+use camino::Utf8Path;
+use chrono::{DateTime, SecondsFormat, Utc};
+use croner::Cron;
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, Instant},
+};
+
+/// The schedule used unless `--schedule` is given: daily at 03:00 UTC.
+pub const DEFAULT_SCHEDULE: &str = "0 3 * * *";
+
+/// A cron schedule, in UTC: minute, hour, day of month, month, and day of
+/// week.
+#[derive(Clone, Debug)]
+pub struct Schedule(Cron);
+
+impl Schedule {
+    /// The first scheduled time after `time`.
+    pub fn next_after(&self, time: DateTime<Utc>) -> Result<DateTime<Utc>> {
+        self.0
+            .find_next_occurrence(&time, false)
+            .map_err(|err| anyhow!("schedule '{}' has no next run: {}", self, err))
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.split_whitespace().count() != 5 {
+            bail!(
+                "invalid schedule '{}' (expected 5 fields: minute, hour, day of month, month, \
+                 and day of week)",
+                s
+            );
+        }
+        Cron::new(s)
+            .parse()
+            .map(Self)
+            .map_err(|err| anyhow!("invalid schedule '{}': {}", s, err))
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0.as_str())
+    }
+}
+
+/// Options for `collect --daemon`.
+#[derive(Debug)]
+pub struct DaemonOptions {
+    pub schedule: Schedule,
+    /// The chart outputs regenerated after each successful collection.
+    pub charts: Vec<ChartOutputConfig>,
+}
+
+/// Collect on `options.schedule` until interrupted.
+pub async fn run_daemon(
+    database: &Utf8Path,
+    config: &Config,
+    collect: &CollectOptions,
+    options: &DaemonOptions,
+) -> Result<()> {
+    log(format_args!(
+        "Collecting on schedule '{}' (UTC); stop with Ctrl-C",
+        options.schedule
+    ));
+    let mut failures = 0;
+    loop {
+        let next = options.schedule.next_after(Utc::now())?;
+        log(format_args!("Next run at {}", timestamp(next)));
+        if !wait_until(next).await {
+            log(format_args!("Interrupted, stopping"));
+            return Ok(());
+        }
+
+        log(format_args!("Starting collection"));
+        let started = Instant::now();
+        // Once Ctrl-C is listened for, it no longer stops the process by
+        // itself. Stopping partway through a run is as safe as running out of
+        // time budget.
+        let result = tokio::select! {
+            result = run_once(database, config, collect, options) => result,
+            _ = tokio::signal::ctrl_c() => {
+                log(format_args!("Interrupted, stopping"));
+                return Ok(());
+            }
+        };
+        match result {
+            Ok(summary) => {
+                failures = 0;
+                log(format_args!(
+                    "Run succeeded in {:.0}s: {} records from {} sources{}, headline {}",
+                    started.elapsed().as_secs_f64(),
+                    format::number(summary.records),
+                    summary.sources.len(),
+                    match summary.failures.len() {
+                        0 => String::new(),
+                        failed => format!(" ({} failed)", failed),
+                    },
+                    format::number(summary.headline)
+                ));
+                output::summary(&summary)?;
+            }
+            Err(err) => {
+                failures += 1;
+                eprintln!(
+                    "[{}] Run failed after {:.0}s ({} in a row): {:#}",
+                    timestamp(Utc::now()),
+                    started.elapsed().as_secs_f64(),
+                    failures,
+                    err
+                );
+            }
+        }
+    }
+}
+
+async fn run_once(
+    database: &Utf8Path,
+    config: &Config,
+    collect: &CollectOptions,
+    options: &DaemonOptions,
+) -> Result<CollectionSummary> {
+    let summary = commands::run_collect(database, config, collect).await?;
+    // Charts drawn without fresh aggregates would be stale.
+    if !collect.skip_aggregation && !options.charts.is_empty() {
+        status!("\nGenerating charts...");
+        commands::run_charts(database, config, &options.charts)?;
+    }
+    Ok(summary)
+}
+
+/// Sleep until `time`, returning false if interrupted first.
+///
+/// The wait is checked against the clock at least once an hour, so a clock
+/// that jumps, such as after a laptop wakes up, doesn't throw the schedule
+/// off for long.
+async fn wait_until(time: DateTime<Utc>) -> bool {
+    const MAX_SLEEP: Duration = Duration::from_secs(60 * 60);
+    loop {
+        let Ok(remaining) = (time - Utc::now()).to_std() else {
+            return true;
+        };
+        if remaining.is_zero() {
+            return true;
+        }
+        tokio::select! {
+            _ = tokio::time::sleep(remaining.min(MAX_SLEEP)) => {}
+            _ = tokio::signal::ctrl_c() => return false,
+        }
+    }
+}
+
+/// Log a line of the daemon's health, with the time.
+fn log(message: fmt::Arguments<'_>) {
+    status!("[{}] {}", timestamp(Utc::now()), message);
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_schedule() {
+        let schedule: Schedule = DEFAULT_SCHEDULE.parse().unwrap();
+        assert_eq!(schedule.to_string(), "0 3 * * *");
+        let time = |s: &str| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        assert_eq!(
+            schedule.next_after(time("2025-11-17T02:59:00Z")).unwrap(),
+            time("2025-11-17T03:00:00Z")
+        );
+        // A run due right now is the next day's.
+        assert_eq!(
+            schedule.next_after(time("2025-11-17T03:00:00Z")).unwrap(),
+            time("2025-11-18T03:00:00Z")
+        );
+
+        let mondays: Schedule = "30 6 * * MON".parse().unwrap();
+        assert_eq!(
+            mondays.next_after(time("2025-11-18T00:00:00Z")).unwrap(),
+            time("2025-11-24T06:30:00Z")
+        );
+
+        for bad in ["0 3 * *", "0 3 * * * *", "61 3 * * *", "daily"] {
+            assert!(bad.parse::<Schedule>().is_err(), "{}", bad);
+        }
+    }
+}
@@ -2,11 +2,17 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 //! Database operations for download statistics.
+//!
+//! Insert helpers use cached prepared statements. Callers inserting many rows
+//! should wrap them in a single transaction.
 
+use crate::{archive, config::ProjectConfig, crates_io::CrateVersion};
 use anyhow::{Context, Result};
 use camino::Utf8Path;
-use chrono::NaiveDate;
-use rusqlite::{Connection, params};
+use chrono::{DateTime, NaiveDate, Utc};
+use rusqlite::{Connection, OpenFlags, OptionalExtension, params};
+use serde::Serialize;
+use std::collections::HashMap;
 
 /// Initialize the database schema.
 pub fn init_db(path: &Utf8Path) -> Result<Connection> {
@@ -29,17 +35,152 @@ pub fn init_db(path: &Utf8Path) -> Result<Connection> {
     )
     .context("failed to set database pragmas")?;
 
+    // GitHub tables created before rows were stored per repository are set
+    // aside, recreated below, and their rows copied back.
+    let tx = conn.unchecked_transaction()?;
+    let legacy_github = set_aside_legacy_github_tables(&conn)?;
     conn.execute_batch(
         r#"
-        -- GitHub release asset downloads (snapshot-based)
+        -- GitHub release asset downloads (snapshot-based). If several
+        -- snapshots are taken on the same day, the latest is kept here and
+        -- the others are moved to github_superseded. The repo of rows
+        -- recorded before repositories were is empty until it's known.
         CREATE TABLE IF NOT EXISTS github_snapshots (
             date TEXT NOT NULL,              -- ISO8601 date (YYYY-MM-DD)
+            repo TEXT NOT NULL DEFAULT '',   -- 'owner/repo', empty if unknown
             release_tag TEXT NOT NULL,
             asset_name TEXT NOT NULL,
             download_count INTEGER NOT NULL,
-            PRIMARY KEY (date, release_tag, asset_name)
+            collected_at TEXT,               -- RFC3339 timestamp, NULL if unknown
+            PRIMARY KEY (date, repo, release_tag, asset_name)
         ) WITHOUT ROWID;  -- Optimization for tables with composite primary keys
 
+        -- GitHub snapshots replaced by a later snapshot taken the same day
+        CREATE TABLE IF NOT EXISTS github_superseded (
+            date TEXT NOT NULL,              -- ISO8601 date (YYYY-MM-DD)
+            repo TEXT NOT NULL DEFAULT '',   -- 'owner/repo', empty if unknown
+            release_tag TEXT NOT NULL,
+            asset_name TEXT NOT NULL,
+            download_count INTEGER NOT NULL,
+            collected_at TEXT,               -- RFC3339 timestamp, NULL if unknown
+            superseded_by TEXT NOT NULL      -- collected_at of the snapshot kept instead
+        );
+
+        -- Every GitHub release asset ever seen, including ones since deleted
+        -- or renamed
+        CREATE TABLE IF NOT EXISTS github_assets (
+            repo TEXT NOT NULL DEFAULT '',   -- 'owner/repo', empty if unknown
+            release_tag TEXT NOT NULL,
+            asset_name TEXT NOT NULL,
+            first_seen TEXT NOT NULL,        -- Date of the first snapshot (YYYY-MM-DD)
+            last_seen TEXT NOT NULL,         -- Date of the latest snapshot (YYYY-MM-DD)
+            last_download_count INTEGER NOT NULL,
+            archived_on TEXT,                -- Date it was found missing, NULL if listed
+            PRIMARY KEY (repo, release_tag, asset_name)
+        ) WITHOUT ROWID;
+
+        -- GitHub snapshots imported after the fact rather than collected, such
+        -- as from archived API responses. Backfilled counts are best-effort.
+        CREATE TABLE IF NOT EXISTS github_backfill (
+            date TEXT NOT NULL,              -- Date of the snapshot (YYYY-MM-DD)
+            repo TEXT NOT NULL DEFAULT '',   -- 'owner/repo', empty if unknown
+            release_tag TEXT NOT NULL,
+            asset_name TEXT NOT NULL,
+            source TEXT NOT NULL,            -- Where the count came from, e.g. 'wayback'
+            confidence TEXT NOT NULL,        -- 'low' for counts that may be stale or partial
+            url TEXT NOT NULL,               -- The archived response the count was read from
+            PRIMARY KEY (date, repo, release_tag, asset_name)
+        ) WITHOUT ROWID;
+
+        -- Publish dates of GitHub releases, used to mark releases on charts.
+        -- Drafts aren't recorded until they're published.
+        CREATE TABLE IF NOT EXISTS github_releases (
+            release_tag TEXT NOT NULL PRIMARY KEY,
+            published_at TEXT NOT NULL,      -- RFC3339 publish timestamp
+            prerelease INTEGER NOT NULL
+        ) WITHOUT ROWID;
+
+        -- Frequent snapshots of the newest GitHub release, taken by
+        -- `collect --releases-only` between full collections
+        CREATE TABLE IF NOT EXISTS github_release_snapshots (
+            repo TEXT NOT NULL DEFAULT '',   -- 'owner/repo', empty if unknown
+            release_tag TEXT NOT NULL,
+            collected_at TEXT NOT NULL,      -- RFC3339 timestamp
+            asset_name TEXT NOT NULL,
+            download_count INTEGER NOT NULL,
+            PRIMARY KEY (repo, release_tag, collected_at, asset_name)
+        ) WITHOUT ROWID;
+
+        -- Asset name patterns (SQLite GLOB syntax) left out of GitHub download
+        -- totals, such as checksums and signatures. Replaced from the
+        -- configuration on each aggregation.
+        CREATE TABLE IF NOT EXISTS asset_exclusions (
+            pattern TEXT NOT NULL PRIMARY KEY
+        ) WITHOUT ROWID;
+
+        -- The GitHub views below are recreated so that changes to their
+        -- definitions apply to existing databases.
+
+        -- GitHub snapshots of assets counted towards download totals
+        DROP VIEW IF EXISTS github_included;
+        CREATE VIEW github_included AS
+            SELECT s.date, s.repo, s.release_tag, s.asset_name, s.download_count
+            FROM github_snapshots s
+            WHERE NOT EXISTS (
+                SELECT 1 FROM asset_exclusions e WHERE s.asset_name GLOB e.pattern
+            );
+
+        -- GitHub snapshots of excluded assets
+        DROP VIEW IF EXISTS github_excluded;
+        CREATE VIEW github_excluded AS
+            SELECT s.date, s.repo, s.release_tag, s.asset_name, s.download_count
+            FROM github_snapshots s
+            WHERE EXISTS (
+                SELECT 1 FROM asset_exclusions e WHERE s.asset_name GLOB e.pattern
+            );
+
+        -- GitHub cumulative counts of included assets, with archived assets
+        -- carried forward at their last seen count so deletions don't look
+        -- like lost downloads
+        DROP VIEW IF EXISTS github_cumulative;
+        CREATE VIEW github_cumulative AS
+            SELECT date, repo, release_tag, asset_name, download_count
+            FROM github_included
+            UNION ALL
+            SELECT d.date, a.repo, a.release_tag, a.asset_name, a.last_download_count
+            FROM github_assets a
+            JOIN (SELECT DISTINCT date, repo FROM github_snapshots) d
+              ON d.repo = a.repo AND d.date > a.last_seen
+            WHERE a.archived_on IS NOT NULL
+              AND NOT EXISTS (
+                  SELECT 1 FROM asset_exclusions e WHERE a.asset_name GLOB e.pattern
+              );
+
+        -- GitHub per-asset deltas between consecutive snapshots (computed)
+        CREATE TABLE IF NOT EXISTS github_daily (
+            date TEXT NOT NULL,              -- Date of the later snapshot (YYYY-MM-DD)
+            repo TEXT NOT NULL DEFAULT '',   -- 'owner/repo', empty if unknown
+            release_tag TEXT NOT NULL,
+            asset_name TEXT NOT NULL,
+            days INTEGER NOT NULL,           -- Days since the previous snapshot
+            downloads INTEGER NOT NULL,      -- Delta, or the handled reset
+            raw_delta INTEGER NOT NULL,      -- Unclamped delta
+            negative INTEGER NOT NULL,       -- 1 if the cumulative count went backwards
+            PRIMARY KEY (date, repo, release_tag, asset_name)
+        ) WITHOUT ROWID;
+
+        -- GitHub assets whose cumulative count went backwards between
+        -- consecutive snapshots, e.g. because they were deleted and re-uploaded
+        CREATE TABLE IF NOT EXISTS github_resets (
+            date TEXT NOT NULL,              -- Date of the later snapshot (YYYY-MM-DD)
+            repo TEXT NOT NULL DEFAULT '',   -- 'owner/repo', empty if unknown
+            release_tag TEXT NOT NULL,
+            asset_name TEXT NOT NULL,
+            previous_count INTEGER NOT NULL,
+            count INTEGER NOT NULL,
+            PRIMARY KEY (date, repo, release_tag, asset_name)
+        ) WITHOUT ROWID;
+
         -- crates.io daily downloads (native time-series)
         CREATE TABLE IF NOT EXISTS crates_downloads (
             date TEXT NOT NULL,              -- ISO8601 date (YYYY-MM-DD)
@@ -49,54 +190,961 @@ pub fn init_db(path: &Utf8Path) -> Result<Connection> {
             PRIMARY KEY (date, crate_name, version)
         ) WITHOUT ROWID;
 
+        -- crates.io version ID to semver mapping
+        CREATE TABLE IF NOT EXISTS crate_versions (
+            crate_name TEXT NOT NULL,
+            version_id INTEGER NOT NULL,     -- Numeric ID used by the downloads API
+            num TEXT NOT NULL,               -- Semver version string
+            created_at TEXT NOT NULL,        -- RFC3339 publish timestamp
+            yanked INTEGER NOT NULL,
+            downloads INTEGER NOT NULL DEFAULT 0, -- All-time downloads when last listed
+            deleted_on TEXT,                 -- Date it was found missing, NULL if listed
+            PRIMARY KEY (crate_name, version_id)
+        ) WITHOUT ROWID;
+
+        -- crates.io downloads with version IDs resolved to semver strings where
+        -- known. Rows collected before the mapping existed store the numeric ID.
+        CREATE VIEW IF NOT EXISTS crates_downloads_named AS
+            SELECT d.date, d.crate_name, COALESCE(v.num, d.version) AS version, d.downloads
+            FROM crates_downloads d
+            LEFT JOIN crate_versions v
+                ON v.crate_name = d.crate_name AND CAST(v.version_id AS TEXT) = d.version;
+
+        -- Current crates.io owners (users and teams) of each tracked crate
+        CREATE TABLE IF NOT EXISTS crate_owners (
+            crate_name TEXT NOT NULL,
+            login TEXT NOT NULL,             -- User login, or 'github:org:team' for teams
+            kind TEXT NOT NULL,              -- 'user' or 'team'
+            first_seen TEXT NOT NULL,        -- ISO8601 date (YYYY-MM-DD)
+            PRIMARY KEY (crate_name, login)
+        ) WITHOUT ROWID;
+
+        -- Owner additions and removals observed between collections. The
+        -- first snapshot of a crate is a baseline and isn't logged.
+        CREATE TABLE IF NOT EXISTS crate_owner_changes (
+            date TEXT NOT NULL,              -- ISO8601 date (YYYY-MM-DD)
+            crate_name TEXT NOT NULL,
+            login TEXT NOT NULL,
+            kind TEXT NOT NULL,              -- 'user' or 'team'
+            change TEXT NOT NULL,            -- 'added' or 'removed'
+            PRIMARY KEY (date, crate_name, login)
+        ) WITHOUT ROWID;
+
         -- crates.io cumulative metadata snapshots
         CREATE TABLE IF NOT EXISTS crates_metadata (
             date TEXT NOT NULL,              -- ISO8601 date (YYYY-MM-DD)
             crate_name TEXT NOT NULL,
             total_downloads INTEGER NOT NULL,
             recent_downloads INTEGER NOT NULL,
+            collected_at TEXT,               -- RFC3339 timestamp, NULL if unknown
             PRIMARY KEY (date, crate_name)
         ) WITHOUT ROWID;
 
+        -- Release assets and crate versions that disappeared from the API or
+        -- were yanked, and ones that came back
+        CREATE TABLE IF NOT EXISTS tombstones (
+            date TEXT NOT NULL,              -- Date it was noticed (YYYY-MM-DD)
+            source TEXT NOT NULL,            -- 'github' or 'crates'
+            identifier TEXT NOT NULL,        -- Release tag or crate name
+            artifact TEXT NOT NULL,          -- Asset name or version
+            event TEXT NOT NULL,             -- 'deleted', 'yanked', 'unyanked', or 'restored'
+            last_count INTEGER NOT NULL,     -- Cumulative downloads when last listed
+            PRIMARY KEY (date, source, identifier, artifact)
+        ) WITHOUT ROWID;
+
+        -- Daily downloads through install endpoints such as get.nexte.st
+        CREATE TABLE IF NOT EXISTS installer_downloads (
+            date TEXT NOT NULL,              -- ISO8601 date (YYYY-MM-DD)
+            name TEXT NOT NULL,              -- Configured source name
+            downloads INTEGER NOT NULL,
+            PRIMARY KEY (date, name)
+        ) WITHOUT ROWID;
+
         -- Computed weekly aggregates for graphing
         CREATE TABLE IF NOT EXISTS weekly_stats (
-            week_start TEXT NOT NULL,        -- Monday of week (YYYY-MM-DD)
+            week_start TEXT NOT NULL,        -- First day of week (YYYY-MM-DD)
             source TEXT NOT NULL,            -- 'github' or 'crates'
-            identifier TEXT NOT NULL,        -- crate name or 'releases'
+            identifier TEXT NOT NULL,        -- crate, 'owner/repo' or 'releases', or source name
             downloads INTEGER NOT NULL,
             PRIMARY KEY (week_start, source, identifier)
         ) WITHOUT ROWID;
 
+        -- Computed weekly GitHub aggregates per platform (parsed from asset names)
+        CREATE TABLE IF NOT EXISTS weekly_platform_stats (
+            week_start TEXT NOT NULL,        -- First day of week (YYYY-MM-DD)
+            platform TEXT NOT NULL,          -- e.g. 'linux-x86_64', or 'other'
+            downloads INTEGER NOT NULL,
+            PRIMARY KEY (week_start, platform)
+        ) WITHOUT ROWID;
+
+        -- Computed weekly GitHub aggregates per platform and asset format
+        CREATE TABLE IF NOT EXISTS weekly_format_stats (
+            week_start TEXT NOT NULL,        -- First day of week (YYYY-MM-DD)
+            platform TEXT NOT NULL,          -- e.g. 'linux-x86_64', or 'other'
+            format TEXT NOT NULL,            -- e.g. 'tar.gz', 'zip', 'binary', 'checksum'
+            downloads INTEGER NOT NULL,
+            PRIMARY KEY (week_start, platform, format)
+        ) WITHOUT ROWID;
+
+        -- Computed weekly GitHub downloads of artifacts and of the signatures
+        -- and attestations published for them, for signed releases only
+        CREATE TABLE IF NOT EXISTS weekly_verification_stats (
+            week_start TEXT NOT NULL,        -- First day of week (YYYY-MM-DD)
+            artifacts INTEGER NOT NULL,      -- Archive and binary downloads
+            signatures INTEGER NOT NULL,     -- Signature and attestation downloads
+            PRIMARY KEY (week_start)
+        ) WITHOUT ROWID;
+
+        -- Computed target triples each GitHub release shipped artifacts for,
+        -- from every asset ever seen on the release
+        CREATE TABLE IF NOT EXISTS release_targets (
+            release_tag TEXT NOT NULL,
+            target TEXT NOT NULL,            -- e.g. x86_64-unknown-linux-gnu
+            platform TEXT NOT NULL,          -- Short label, e.g. linux-x86_64
+            artifacts INTEGER NOT NULL,      -- Archives and binaries for the target
+            downloads INTEGER NOT NULL,      -- Latest cumulative count of those artifacts
+            first_seen TEXT NOT NULL,        -- Date the first of them was seen (YYYY-MM-DD)
+            PRIMARY KEY (release_tag, target)
+        ) WITHOUT ROWID;
+
+        -- Share of each week's downloads per major.minor version
+        CREATE TABLE IF NOT EXISTS weekly_version_share (
+            week_start TEXT NOT NULL,        -- First day of week (YYYY-MM-DD)
+            source TEXT NOT NULL,            -- 'github' or 'crates'
+            version TEXT NOT NULL,           -- major.minor, e.g. '0.9'
+            downloads INTEGER NOT NULL,
+            share REAL NOT NULL,             -- fraction of the source's weekly downloads
+            PRIMARY KEY (week_start, source, version)
+        ) WITHOUT ROWID;
+
+        -- Computed weekly downloads of stable and pre-release versions
+        CREATE TABLE IF NOT EXISTS weekly_channel_stats (
+            week_start TEXT NOT NULL,        -- First day of week (YYYY-MM-DD)
+            source TEXT NOT NULL,            -- 'github' or 'crates'
+            channel TEXT NOT NULL,           -- 'stable' or 'prerelease'
+            downloads INTEGER NOT NULL,
+            PRIMARY KEY (week_start, source, channel)
+        ) WITHOUT ROWID;
+
+        -- Computed monthly aggregates for long-term trends
+        CREATE TABLE IF NOT EXISTS monthly_stats (
+            month_start TEXT NOT NULL,       -- First day of month (YYYY-MM-DD)
+            source TEXT NOT NULL,            -- 'github' or 'crates'
+            identifier TEXT NOT NULL,        -- crate, 'owner/repo' or 'releases', or source name
+            downloads INTEGER NOT NULL,
+            PRIMARY KEY (month_start, source, identifier)
+        ) WITHOUT ROWID;
+
+        -- The aggregate identifiers grouped under each configured project.
+        -- Replaced from the configuration on each aggregation.
+        CREATE TABLE IF NOT EXISTS project_sources (
+            project TEXT NOT NULL,
+            source TEXT NOT NULL,            -- 'github', 'crates', or 'installer'
+            identifier TEXT NOT NULL,        -- As in weekly_stats and monthly_stats
+            PRIMARY KEY (project, source, identifier)
+        ) WITHOUT ROWID;
+
+        -- Weekly and monthly aggregates summed per project
+        DROP VIEW IF EXISTS weekly_project_stats;
+        CREATE VIEW weekly_project_stats AS
+            SELECT w.week_start, p.project, SUM(w.downloads) AS downloads
+            FROM weekly_stats w
+            JOIN project_sources p ON p.source = w.source AND p.identifier = w.identifier
+            GROUP BY w.week_start, p.project;
+        DROP VIEW IF EXISTS monthly_project_stats;
+        CREATE VIEW monthly_project_stats AS
+            SELECT m.month_start, p.project, SUM(m.downloads) AS downloads
+            FROM monthly_stats m
+            JOIN project_sources p ON p.source = m.source AND p.identifier = m.identifier
+            GROUP BY m.month_start, p.project;
+
+        -- Computed daily downloads per source with trailing averages. GitHub
+        -- deltas are spread evenly over the days between snapshots.
+        CREATE TABLE IF NOT EXISTS rolling_stats (
+            date TEXT NOT NULL,              -- ISO8601 date (YYYY-MM-DD)
+            source TEXT NOT NULL,            -- 'github' or 'crates'
+            downloads REAL NOT NULL,         -- Downloads attributed to this day
+            avg_7d REAL NOT NULL,            -- Mean over the trailing 7 days
+            avg_30d REAL NOT NULL,           -- Mean over the trailing 30 days
+            PRIMARY KEY (date, source)
+        ) WITHOUT ROWID;
+
+        -- One row per collect run, used to detect repeated failures
+        CREATE TABLE IF NOT EXISTS collection_runs (
+            started_at TEXT NOT NULL PRIMARY KEY, -- RFC3339 timestamp
+            finished_at TEXT NOT NULL,            -- RFC3339 timestamp
+            success INTEGER NOT NULL,
+            error TEXT                            -- Error chain if the run failed
+        ) WITHOUT ROWID;
+
+        -- What collection filters left out of each source in a run, so that
+        -- a filter excluding everything is noticed
+        CREATE TABLE IF NOT EXISTS collection_run_skips (
+            started_at TEXT NOT NULL,        -- The run's started_at
+            source TEXT NOT NULL,            -- kind:name
+            filter TEXT NOT NULL,            -- 'tag-filter', 'asset-filter', or 'exclude-assets'
+            skipped INTEGER NOT NULL,        -- Releases or assets left out
+            downloads INTEGER NOT NULL,      -- Downloads of those left out
+            kept INTEGER NOT NULL,           -- Releases or assets that passed
+            PRIMARY KEY (started_at, source, filter)
+        ) WITHOUT ROWID;
+
+        -- Per-source collection progress, used to collect the stalest sources
+        -- first when time is limited. Columns added later are also added to
+        -- existing databases below.
+        CREATE TABLE IF NOT EXISTS collector_state (
+            kind TEXT NOT NULL,              -- 'github', 'crates', or 'installer'
+            name TEXT NOT NULL,              -- 'owner/repo', crate name, or source name
+            last_attempt_at TEXT NOT NULL,   -- RFC3339 timestamp
+            last_success_at TEXT,            -- RFC3339 timestamp, NULL if never
+            last_collected_date TEXT,        -- Latest date with collected data (YYYY-MM-DD)
+            etag TEXT,                       -- ETag of the last successful response
+            consecutive_failures INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,                 -- Error of the last attempt, NULL if it succeeded
+            PRIMARY KEY (kind, name)
+        ) WITHOUT ROWID;
+
+        -- Conventions the computed aggregates were built with, so that a
+        -- change in configuration can be detected
+        CREATE TABLE IF NOT EXISTS aggregation_settings (
+            key TEXT NOT NULL PRIMARY KEY,   -- e.g. 'week_start'
+            value TEXT NOT NULL
+        ) WITHOUT ROWID;
+
+        -- Per-year archives of old raw rows, attached when the database is
+        -- opened. See the archive module.
+        CREATE TABLE IF NOT EXISTS archives (
+            year INTEGER NOT NULL PRIMARY KEY,
+            path TEXT NOT NULL               -- File name, relative to the main database
+        );
+
         -- Indexes for efficient queries
         -- Note: PRIMARY KEY (date, ...) already provides an index on date, so no need for separate index
         CREATE INDEX IF NOT EXISTS idx_crates_crate ON crates_downloads(crate_name, date);
         CREATE INDEX IF NOT EXISTS idx_weekly_source ON weekly_stats(source, week_start);
+        CREATE INDEX IF NOT EXISTS idx_monthly_source ON monthly_stats(source, month_start);
         "#,
     )
     .context("failed to initialize database schema")?;
+    if legacy_github {
+        restore_legacy_github_rows(&conn)?;
+    }
+    tx.commit().context("failed to commit database schema")?;
+
+    for (column, definition) in [
+        ("last_collected_date", "TEXT"),
+        ("etag", "TEXT"),
+        ("consecutive_failures", "INTEGER NOT NULL DEFAULT 0"),
+        ("last_error", "TEXT"),
+    ] {
+        add_column_if_missing(&conn, "collector_state", column, definition)?;
+    }
+    add_column_if_missing(&conn, "crates_metadata", "collected_at", "TEXT")?;
+    add_column_if_missing(
+        &conn,
+        "crate_versions",
+        "downloads",
+        "INTEGER NOT NULL DEFAULT 0",
+    )?;
+    add_column_if_missing(&conn, "crate_versions", "deleted_on", "TEXT")?;
+
+    // Created once the columns it reads exist in older databases.
+    conn.execute_batch(
+        r#"
+        -- crates.io all-time totals, with deleted versions carried forward at
+        -- their last listed downloads, as crates.io takes them off the total
+        DROP VIEW IF EXISTS crates_cumulative;
+        CREATE VIEW crates_cumulative AS
+            SELECT m.date, m.crate_name, m.total_downloads + COALESCE((
+                SELECT SUM(v.downloads) FROM crate_versions v
+                WHERE v.crate_name = m.crate_name AND v.deleted_on <= m.date
+            ), 0) AS total_downloads
+            FROM crates_metadata m;
+        "#,
+    )
+    .context("failed to create crates_cumulative view")?;
+
+    archive::attach_archives(&conn, path).context("failed to attach archives")?;
 
     Ok(conn)
 }
 
-/// Insert a GitHub release asset snapshot.
+/// Open an existing database without write access, for running statements
+/// that aren't ours.
+///
+/// Unlike [`init_db`], the schema is left as it is. Archives are attached the
+/// same way, and are read-only too.
+pub fn open_read_only(path: &Utf8Path) -> Result<Connection> {
+    let conn = Connection::open_with_flags(
+        path.as_std_path(),
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX,
+    )
+    .with_context(|| format!("failed to open database at {} read-only", path))?;
+    conn.pragma_update(None, "query_only", true)
+        .context("failed to set database pragmas")?;
+    archive::attach_archives(&conn, path).context("failed to attach archives")?;
+    Ok(conn)
+}
+
+/// Add a column to a table created before the column existed.
+fn add_column_if_missing(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    definition: &str,
+) -> Result<()> {
+    let exists: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM pragma_table_info(?1) WHERE name = ?2)",
+            [table, column],
+            |row| row.get(0),
+        )
+        .with_context(|| format!("failed to inspect table {}", table))?;
+    if !exists {
+        conn.execute_batch(&format!(
+            "ALTER TABLE {} ADD COLUMN {} {}",
+            table, column, definition
+        ))
+        .with_context(|| format!("failed to add column {}.{}", table, column))?;
+    }
+    Ok(())
+}
+
+/// GitHub tables whose rows are stored per repository. Databases created
+/// before they were have these tables without a `repo` column.
+const REPO_TABLES: [&str; 7] = [
+    "github_snapshots",
+    "github_superseded",
+    "github_assets",
+    "github_backfill",
+    "github_release_snapshots",
+    "github_daily",
+    "github_resets",
+];
+
+/// Rename the GitHub tables of a database created before rows were stored
+/// per repository to `legacy_<table>`, so that they're created again with
+/// `repo` in their primary keys. Returns whether there were any.
+fn set_aside_legacy_github_tables(conn: &Connection) -> Result<bool> {
+    let columns = archive::table_columns(conn, "main", "github_snapshots")?;
+    if columns.is_empty() || columns.iter().any(|column| column == "repo") {
+        return Ok(false);
+    }
+
+    // Renaming would rewrite the views to read the legacy tables.
+    conn.execute_batch(
+        "DROP VIEW IF EXISTS github_included;
+         DROP VIEW IF EXISTS github_excluded;
+         DROP VIEW IF EXISTS github_cumulative;",
+    )
+    .context("failed to drop GitHub views")?;
+    for table in REPO_TABLES {
+        if !archive::table_columns(conn, "main", table)?.is_empty() {
+            conn.execute_batch(&format!("ALTER TABLE {table} RENAME TO legacy_{table}"))
+                .with_context(|| format!("failed to set aside {}", table))?;
+        }
+    }
+    Ok(true)
+}
+
+/// Copy the rows of the tables set aside by
+/// [`set_aside_legacy_github_tables`] into the new ones, and drop them.
+///
+/// Rows are attributed to the repository `github_assets` recorded for their
+/// asset, if there is one. The others are left with an empty `repo` until
+/// [`claim_unattributed_github_rows`] is called.
+fn restore_legacy_github_rows(conn: &Connection) -> Result<()> {
+    let known = archive::table_columns(conn, "main", "legacy_github_assets")?
+        .iter()
+        .any(|column| column == "repo");
+    for table in REPO_TABLES {
+        let columns: Vec<String> =
+            archive::table_columns(conn, "main", &format!("legacy_{table}"))?
+                .into_iter()
+                .filter(|column| column != "repo")
+                .collect();
+        if columns.is_empty() {
+            continue;
+        }
+        let repo = match (known, table) {
+            (false, _) => "''",
+            (true, "github_assets") => "COALESCE(l.repo, '')",
+            (true, _) => {
+                "COALESCE((
+                     SELECT a.repo FROM legacy_github_assets a
+                     WHERE a.release_tag = l.release_tag AND a.asset_name = l.asset_name
+                 ), '')"
+            }
+        };
+        let selected: Vec<String> = columns.iter().map(|column| format!("l.{column}")).collect();
+        conn.execute_batch(&format!(
+            "INSERT INTO {table} (repo, {}) SELECT {repo}, {} FROM legacy_{table} l",
+            columns.join(", "),
+            selected.join(", ")
+        ))
+        .with_context(|| format!("failed to copy {} rows", table))?;
+    }
+    for table in REPO_TABLES {
+        conn.execute_batch(&format!("DROP TABLE IF EXISTS legacy_{table}"))
+            .with_context(|| format!("failed to drop legacy {}", table))?;
+    }
+    Ok(())
+}
+
+/// Attribute the GitHub rows whose repository isn't known, left by a
+/// database created before rows were stored per repository, to `repo`.
+///
+/// This is only right if `repo` is the only GitHub repository collected
+/// before then. Rows already recorded for `repo` too are left as they are.
+/// Returns the number of rows attributed.
+pub fn claim_unattributed_github_rows(conn: &Connection, repo: &str) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut claimed = 0;
+    for table in REPO_TABLES {
+        claimed += tx
+            .execute(
+                &format!("UPDATE OR IGNORE main.{table} SET repo = ?1 WHERE repo = ''"),
+                [repo],
+            )
+            .with_context(|| format!("failed to attribute {} rows to {}", table, repo))?;
+    }
+    tx.commit()
+        .context("failed to commit attributed GitHub rows")?;
+    Ok(claimed)
+}
+
+/// Insert a GitHub release asset snapshot taken at `collected_at`.
+///
+/// Only the latest snapshot of each day is kept in `github_snapshots`, so
+/// that aggregation doesn't depend on the order of runs. If there already is
+/// a snapshot of the asset of `repo` for `date`, the earlier of the two is
+/// moved to `github_superseded` and `true` is returned. Snapshots recorded
+/// for an earlier date have no `collected_at`, and count as older than any
+/// snapshot that has one.
 pub fn insert_github_snapshot(
     conn: &Connection,
     date: NaiveDate,
+    collected_at: Option<DateTime<Utc>>,
+    repo: &str,
     release_tag: &str,
     asset_name: &str,
     download_count: u64,
-) -> Result<()> {
+) -> Result<bool> {
+    let date = date.to_string();
+    let existing: Option<(i64, Option<String>)> = conn
+        .prepare_cached(
+            "SELECT download_count, collected_at FROM github_snapshots
+             WHERE date = ?1 AND repo = ?2 AND release_tag = ?3 AND asset_name = ?4",
+        )?
+        .query_row(params![date, repo, release_tag, asset_name], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .optional()
+        .context("failed to look up GitHub snapshot")?;
+
+    let collected_at_str = collected_at.map(|at| at.to_rfc3339());
+    if let Some((existing_count, existing_at)) = &existing {
+        let existing_time = existing_at
+            .as_deref()
+            .map(|at| {
+                DateTime::parse_from_rfc3339(at)
+                    .with_context(|| format!("invalid snapshot timestamp '{}'", at))
+            })
+            .transpose()?;
+        let keep_existing = match (existing_time, collected_at) {
+            (Some(existing_time), Some(collected_at)) => existing_time > collected_at,
+            (existing_time, _) => existing_time.is_some(),
+        };
+        let (count, at, kept_at) = if keep_existing {
+            (
+                download_count as i64,
+                collected_at_str.as_deref(),
+                existing_at.as_deref().unwrap_or_default(),
+            )
+        } else {
+            (
+                *existing_count,
+                existing_at.as_deref(),
+                collected_at_str.as_deref().unwrap_or_default(),
+            )
+        };
+        conn.prepare_cached(
+            "INSERT INTO github_superseded
+             (date, repo, release_tag, asset_name, download_count, collected_at, superseded_by)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        )?
+        .execute(params![
+            date,
+            repo,
+            release_tag,
+            asset_name,
+            count,
+            at,
+            kept_at
+        ])
+        .context("failed to record superseded GitHub snapshot")?;
+        if keep_existing {
+            return Ok(true);
+        }
+    }
+
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO main.github_snapshots
+         (date, repo, release_tag, asset_name, download_count, collected_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(params![
+        date,
+        repo,
+        release_tag,
+        asset_name,
+        download_count as i64,
+        collected_at_str
+    ])
+    .context("failed to insert GitHub snapshot")?;
+    Ok(existing.is_some())
+}
+
+/// A GitHub release asset that is no longer listed by the API.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ArchivedAsset {
+    pub release_tag: String,
+    pub asset_name: String,
+    pub last_seen: NaiveDate,
+}
+
+/// Bring `github_assets` up to date with the snapshots after collecting
+/// `repo`, archiving the repository's assets that weren't collected.
+///
+/// Assets of other repositories are left alone, since they may not have been
+/// collected yet, or at all, today.
+///
+/// Returns the assets archived by this call. Assets that reappear are
+/// unarchived. Both are logged to `tombstones`.
+pub fn update_github_assets(
+    conn: &Connection,
+    today: NaiveDate,
+    repo: &str,
+) -> Result<Vec<ArchivedAsset>> {
+    let date = today;
+    let today = today.to_string();
+    let tombstone = |release_tag: String, asset_name: String, event, last_count: i64| Tombstone {
+        date,
+        source: "github".to_string(),
+        identifier: release_tag,
+        artifact: asset_name,
+        event,
+        last_count: last_count as u64,
+    };
+    let mut stmt = conn.prepare(
+        "SELECT a.release_tag, a.asset_name, s.download_count
+         FROM github_assets a
+         JOIN github_snapshots s
+           ON s.repo = a.repo
+          AND s.release_tag = a.release_tag
+          AND s.asset_name = a.asset_name
+          AND s.date = ?1
+         WHERE a.archived_on IS NOT NULL AND a.repo = ?2
+         ORDER BY a.release_tag, a.asset_name",
+    )?;
+    let mut tombstones = stmt
+        .query_map([&today, repo], |row| {
+            Ok(tombstone(
+                row.get(0)?,
+                row.get(1)?,
+                TombstoneEvent::Restored,
+                row.get(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read restored GitHub assets")?;
+
     conn.execute(
-        "INSERT OR REPLACE INTO github_snapshots (date, release_tag, asset_name, download_count)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![
+        "INSERT INTO github_assets
+             (repo, release_tag, asset_name, first_seen, last_seen, last_download_count,
+              archived_on)
+         SELECT s.repo, s.release_tag, s.asset_name, r.first_seen, r.last_seen,
+                s.download_count, NULL
+         FROM (
+             SELECT repo, release_tag, asset_name,
+                    MIN(date) AS first_seen, MAX(date) AS last_seen
+             FROM github_snapshots
+             GROUP BY repo, release_tag, asset_name
+         ) r
+         JOIN github_snapshots s
+           ON s.repo = r.repo
+          AND s.release_tag = r.release_tag
+          AND s.asset_name = r.asset_name
+          AND s.date = r.last_seen
+         WHERE true
+         ON CONFLICT (repo, release_tag, asset_name) DO UPDATE SET
+             first_seen = excluded.first_seen,
+             last_seen = excluded.last_seen,
+             last_download_count = excluded.last_download_count,
+             archived_on = CASE WHEN excluded.last_seen = ?1 THEN NULL ELSE archived_on END",
+        [&today],
+    )
+    .context("failed to update GitHub assets")?;
+
+    let mut stmt = conn.prepare(
+        "SELECT release_tag, asset_name, last_seen, last_download_count FROM github_assets
+         WHERE archived_on IS NULL AND last_seen < ?1 AND repo = ?2
+         ORDER BY release_tag, asset_name",
+    )?;
+    let mut archived = Vec::new();
+    let rows = stmt.query_map([&today, repo], |row| {
+        let last_seen: String = row.get(2)?;
+        let last_seen = NaiveDate::parse_from_str(&last_seen, "%Y-%m-%d")
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let asset = ArchivedAsset {
+            release_tag: row.get(0)?,
+            asset_name: row.get(1)?,
+            last_seen,
+        };
+        Ok((asset, row.get::<_, i64>(3)?))
+    })?;
+    for row in rows {
+        let (asset, last_count) = row?;
+        tombstones.push(tombstone(
+            asset.release_tag.clone(),
+            asset.asset_name.clone(),
+            TombstoneEvent::Deleted,
+            last_count,
+        ));
+        archived.push(asset);
+    }
+
+    conn.execute(
+        "UPDATE github_assets SET archived_on = ?1
+         WHERE archived_on IS NULL AND last_seen < ?1 AND repo = ?2",
+        [&today, repo],
+    )
+    .context("failed to archive GitHub assets")?;
+    insert_tombstones(conn, &tombstones)?;
+
+    Ok(archived)
+}
+
+/// A published GitHub release.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GithubRelease {
+    pub release_tag: String,
+    /// The UTC date the release was published.
+    pub published_on: NaiveDate,
+    pub prerelease: bool,
+}
+
+/// Record when a GitHub release was published, replacing what was recorded
+/// before in case the release was edited.
+pub fn upsert_github_release(
+    conn: &Connection,
+    release_tag: &str,
+    published_at: DateTime<Utc>,
+    prerelease: bool,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO github_releases (release_tag, published_at, prerelease)
+         VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![release_tag, published_at.to_rfc3339(), prerelease])
+    .context("failed to record GitHub release")?;
+    Ok(())
+}
+
+/// Get all recorded GitHub releases, oldest first.
+pub fn get_github_releases(conn: &Connection) -> Result<Vec<GithubRelease>> {
+    let mut stmt = conn.prepare(
+        "SELECT release_tag, date(published_at), prerelease FROM github_releases
+         ORDER BY published_at, release_tag",
+    )?;
+    let releases = stmt
+        .query_map([], |row| {
+            let published_on: String = row.get(1)?;
+            let published_on = NaiveDate::parse_from_str(&published_on, "%Y-%m-%d")
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            Ok(GithubRelease {
+                release_tag: row.get(0)?,
+                published_on,
+                prerelease: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read GitHub releases")?;
+    Ok(releases)
+}
+
+/// Record an asset's download count in a frequent snapshot of a release of
+/// `repo`.
+pub fn insert_github_release_snapshot(
+    conn: &Connection,
+    repo: &str,
+    release_tag: &str,
+    collected_at: DateTime<Utc>,
+    asset_name: &str,
+    download_count: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO github_release_snapshots
+         (repo, release_tag, collected_at, asset_name, download_count)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?
+    .execute(params![
+        repo,
+        release_tag,
+        collected_at.to_rfc3339(),
+        asset_name,
+        download_count as i64
+    ])
+    .context("failed to record GitHub release snapshot")?;
+    Ok(())
+}
+
+/// The total downloads of a release in one of its frequent snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReleaseSnapshot {
+    pub collected_at: DateTime<Utc>,
+    pub downloads: u64,
+}
+
+/// The repository and tag of the release with the most recent frequent
+/// snapshot, among releases tagged `release_tag` if given, if any.
+pub fn get_latest_snapshotted_release(
+    conn: &Connection,
+    release_tag: Option<&str>,
+) -> Result<Option<(String, String)>> {
+    conn.query_row(
+        "SELECT repo, release_tag FROM github_release_snapshots
+         WHERE ?1 IS NULL OR release_tag = ?1
+         ORDER BY collected_at DESC LIMIT 1",
+        [release_tag],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .context("failed to find the latest snapshotted release")
+}
+
+/// Get the frequent snapshots of a release of `repo`, oldest first.
+pub fn get_github_release_snapshots(
+    conn: &Connection,
+    repo: &str,
+    release_tag: &str,
+) -> Result<Vec<ReleaseSnapshot>> {
+    let mut stmt = conn.prepare(
+        "SELECT collected_at, SUM(download_count) FROM github_release_snapshots
+         WHERE repo = ?1 AND release_tag = ?2
+         GROUP BY collected_at
+         ORDER BY collected_at",
+    )?;
+    let snapshots = stmt
+        .query_map([repo, release_tag], |row| {
+            let collected_at: String = row.get(0)?;
+            let collected_at = DateTime::parse_from_rfc3339(&collected_at)
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?
+                .with_timezone(&Utc);
+            Ok(ReleaseSnapshot {
+                collected_at,
+                downloads: row.get::<_, i64>(1)?.max(0) as u64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read GitHub release snapshots")?;
+    Ok(snapshots)
+}
+
+/// The date of the earliest GitHub snapshot taken by the collector itself,
+/// ignoring backfilled snapshots.
+pub fn first_collected_github_date(conn: &Connection) -> Result<Option<NaiveDate>> {
+    let date: Option<String> = conn
+        .query_row(
+            "SELECT MIN(s.date) FROM github_snapshots s
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM github_backfill b
+                 WHERE b.date = s.date
+                   AND b.repo = s.repo
+                   AND b.release_tag = s.release_tag
+                   AND b.asset_name = s.asset_name
+             )",
+            [],
+            |row| row.get(0),
+        )
+        .context("failed to find the first GitHub snapshot")?;
+    date.map(|date| {
+        NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+            .with_context(|| format!("invalid snapshot date '{}'", date))
+    })
+    .transpose()
+}
+
+/// Where a backfilled GitHub snapshot came from.
+#[derive(Clone, Copy, Debug)]
+pub struct BackfillOrigin<'a> {
+    /// The kind of source, e.g. `wayback`.
+    pub source: &'a str,
+    /// The archived response the count was read from.
+    pub url: &'a str,
+}
+
+/// Insert a backfilled GitHub snapshot of an asset of `repo` along with
+/// where it came from.
+///
+/// Existing snapshots are never overwritten. Returns whether the snapshot was
+/// inserted.
+pub fn insert_backfilled_snapshot(
+    conn: &Connection,
+    date: NaiveDate,
+    repo: &str,
+    release_tag: &str,
+    asset_name: &str,
+    download_count: u64,
+    origin: BackfillOrigin<'_>,
+) -> Result<bool> {
+    let inserted = conn
+        .prepare_cached(
+            // The existence check also covers archived snapshots.
+            "INSERT INTO main.github_snapshots
+             (date, repo, release_tag, asset_name, download_count)
+             SELECT ?1, ?2, ?3, ?4, ?5
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM github_snapshots
+                 WHERE date = ?1 AND repo = ?2 AND release_tag = ?3 AND asset_name = ?4
+             )",
+        )?
+        .execute(params![
             date.to_string(),
+            repo,
             release_tag,
             asset_name,
             download_count as i64
-        ],
-    )
-    .context("failed to insert GitHub snapshot")?;
+        ])
+        .context("failed to insert backfilled GitHub snapshot")?;
+    if inserted == 0 {
+        return Ok(false);
+    }
+
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO github_backfill
+         (date, repo, release_tag, asset_name, source, confidence, url)
+         VALUES (?1, ?2, ?3, ?4, ?5, 'low', ?6)",
+    )?
+    .execute(params![
+        date.to_string(),
+        repo,
+        release_tag,
+        asset_name,
+        origin.source,
+        origin.url
+    ])
+    .context("failed to record GitHub backfill provenance")?;
+    Ok(true)
+}
+
+/// A GitHub asset whose cumulative count went backwards between consecutive
+/// snapshots.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct CountReset {
+    /// Date of the later snapshot.
+    pub date: NaiveDate,
+    /// `owner/repo`, empty if unknown.
+    pub repo: String,
+    pub release_tag: String,
+    pub asset_name: String,
+    pub previous_count: i64,
+    pub count: i64,
+}
+
+/// Record a count reset, returning whether it wasn't already recorded.
+pub fn insert_github_reset(conn: &Connection, reset: &CountReset) -> Result<bool> {
+    let inserted = conn
+        .prepare_cached(
+            "INSERT OR IGNORE INTO github_resets
+             (date, repo, release_tag, asset_name, previous_count, count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            reset.date.to_string(),
+            reset.repo,
+            reset.release_tag,
+            reset.asset_name,
+            reset.previous_count,
+            reset.count
+        ])
+        .context("failed to record GitHub count reset")?;
+    Ok(inserted > 0)
+}
+
+/// Remove all computed GitHub daily deltas.
+pub fn clear_github_daily(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM github_daily", [])
+        .context("failed to clear GitHub daily deltas")?;
+    Ok(())
+}
+
+/// Clear the rolling averages table before it is recomputed.
+pub fn clear_rolling_stats(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM rolling_stats", [])
+        .context("failed to clear rolling averages")?;
+    Ok(())
+}
+
+/// Insert a daily rolling average row.
+pub fn insert_rolling_stat(
+    conn: &Connection,
+    date: NaiveDate,
+    source: &str,
+    downloads: f64,
+    avg_7d: f64,
+    avg_30d: f64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO rolling_stats (date, source, downloads, avg_7d, avg_30d)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?
+    .execute(params![
+        date.to_string(),
+        source,
+        downloads,
+        avg_7d,
+        avg_30d
+    ])
+    .context("failed to insert rolling average")?;
+    Ok(())
+}
+
+/// A GitHub asset's downloads between consecutive snapshots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GithubDaily {
+    /// Date of the later snapshot.
+    pub date: NaiveDate,
+    /// `owner/repo`, empty if unknown.
+    pub repo: String,
+    pub release_tag: String,
+    pub asset_name: String,
+    /// Days since the previous snapshot.
+    pub days: i64,
+    /// The delta, or the handled reset.
+    pub downloads: u64,
+    /// The unclamped delta.
+    pub raw_delta: i64,
+}
+
+/// Insert a GitHub per-asset delta.
+pub fn insert_github_daily(conn: &Connection, daily: &GithubDaily) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO github_daily
+         (date, repo, release_tag, asset_name, days, downloads, raw_delta, negative)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+    )?
+    .execute(params![
+        daily.date.to_string(),
+        daily.repo,
+        daily.release_tag,
+        daily.asset_name,
+        daily.days,
+        daily.downloads as i64,
+        daily.raw_delta,
+        daily.raw_delta < 0
+    ])
+    .context("failed to insert GitHub daily delta")?;
     Ok(())
 }
 
@@ -109,59 +1157,1225 @@ pub fn insert_crates_download(
     downloads: u64,
 ) -> Result<()> {
     let version_str = version.unwrap_or("");
-    conn.execute(
-        "INSERT OR REPLACE INTO crates_downloads (date, crate_name, version, downloads)
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO main.crates_downloads (date, crate_name, version, downloads)
          VALUES (?1, ?2, ?3, ?4)",
-        params![date.to_string(), crate_name, version_str, downloads as i64],
-    )
+    )?
+    .execute(params![
+        date.to_string(),
+        crate_name,
+        version_str,
+        downloads as i64
+    ])
     .context("failed to insert crates.io download")?;
     Ok(())
 }
 
-/// Insert a crates.io metadata snapshot.
-pub fn insert_crates_metadata(
+/// Bring the recorded versions of a crate up to date with `versions`, the
+/// versions crates.io lists now, logging and returning tombstones for
+/// versions that were deleted, yanked, unyanked, or listed again after being
+/// deleted, ordered by version ID.
+///
+/// Deleted versions are kept, with their last listed downloads. The first
+/// listing of a crate is recorded as a baseline with no tombstones, and an
+/// empty listing is taken to be a bad response rather than every version
+/// having been deleted.
+pub fn update_crate_versions(
     conn: &Connection,
     date: NaiveDate,
     crate_name: &str,
-    total_downloads: u64,
-    recent_downloads: u64,
-) -> Result<()> {
-    conn.execute(
-        "INSERT OR REPLACE INTO crates_metadata (date, crate_name, total_downloads, recent_downloads)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![
-            date.to_string(),
+    versions: &[CrateVersion],
+) -> Result<Vec<Tombstone>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT version_id, num, yanked, downloads, deleted_on FROM crate_versions
+         WHERE crate_name = ?1",
+    )?;
+    let previous = stmt
+        .query_map([crate_name], |row| {
+            let recorded = RecordedVersion {
+                num: row.get(1)?,
+                yanked: row.get(2)?,
+                downloads: row.get::<_, i64>(3)? as u64,
+                deleted: row.get::<_, Option<String>>(4)?.is_some(),
+            };
+            Ok((row.get::<_, i64>(0)? as u64, recorded))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()
+        .context("failed to read crate versions")?;
+    let baseline = previous.is_empty();
+
+    let tombstone = |num: &str, event, last_count| Tombstone {
+        date,
+        source: "crates".to_string(),
+        identifier: crate_name.to_string(),
+        artifact: num.to_string(),
+        event,
+        last_count,
+    };
+    let mut tombstones = Vec::new();
+    for version in versions {
+        conn.prepare_cached(
+            "INSERT INTO crate_versions
+                 (crate_name, version_id, num, created_at, yanked, downloads, deleted_on)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, NULL)
+             ON CONFLICT (crate_name, version_id) DO UPDATE SET
+                 num = excluded.num,
+                 created_at = excluded.created_at,
+                 yanked = excluded.yanked,
+                 downloads = excluded.downloads,
+                 deleted_on = NULL",
+        )?
+        .execute(params![
             crate_name,
-            total_downloads as i64,
-            recent_downloads as i64
-        ],
-    )
-    .context("failed to insert crates.io metadata")?;
-    Ok(())
+            version.id as i64,
+            version.num,
+            version.created_at,
+            version.yanked,
+            version.downloads as i64
+        ])
+        .context("failed to insert crate version")?;
+
+        let event = match previous.get(&version.id) {
+            Some(recorded) if recorded.deleted => Some(TombstoneEvent::Restored),
+            Some(recorded) if !recorded.yanked && version.yanked => Some(TombstoneEvent::Yanked),
+            Some(recorded) if recorded.yanked && !version.yanked => Some(TombstoneEvent::Unyanked),
+            _ => None,
+        };
+        if let Some(event) = event.filter(|_| !baseline) {
+            tombstones.push((
+                version.id,
+                tombstone(&version.num, event, version.downloads),
+            ));
+        }
+    }
+    if !versions.is_empty() {
+        for (&id, recorded) in &previous {
+            if recorded.deleted || versions.iter().any(|version| version.id == id) {
+                continue;
+            }
+            conn.prepare_cached(
+                "UPDATE crate_versions SET deleted_on = ?1
+                 WHERE crate_name = ?2 AND version_id = ?3",
+            )?
+            .execute(params![date.to_string(), crate_name, id as i64])
+            .context("failed to mark crate version deleted")?;
+            tombstones.push((
+                id,
+                tombstone(&recorded.num, TombstoneEvent::Deleted, recorded.downloads),
+            ));
+        }
+    }
+    tombstones.sort_by_key(|(id, _)| *id);
+    let tombstones: Vec<_> = tombstones
+        .into_iter()
+        .map(|(_, tombstone)| tombstone)
+        .collect();
+
+    insert_tombstones(conn, &tombstones)?;
+    Ok(tombstones)
 }
 
-/// Insert a weekly aggregate statistic.
-pub fn insert_weekly_stat(
-    conn: &Connection,
-    week_start: NaiveDate,
-    source: &str,
-    identifier: &str,
+/// A crate version as recorded in `crate_versions`.
+struct RecordedVersion {
+    num: String,
+    yanked: bool,
     downloads: u64,
-) -> Result<()> {
-    conn.execute(
-        "INSERT OR REPLACE INTO weekly_stats (week_start, source, identifier, downloads)
-         VALUES (?1, ?2, ?3, ?4)",
-        params![week_start.to_string(), source, identifier, downloads as i64],
-    )
-    .context("failed to insert weekly stat")?;
-    Ok(())
+    deleted: bool,
 }
 
-/// Get the latest date for which we have GitHub snapshots.
-#[allow(dead_code)]
-pub fn get_latest_github_snapshot_date(conn: &Connection) -> Result<Option<NaiveDate>> {
-    let mut stmt = conn.prepare("SELECT MAX(date) FROM github_snapshots")?;
-    let result: Option<String> = stmt.query_row([], |row| row.get(0))?;
+/// Get the version ID to semver mapping for a crate.
+pub fn get_crate_version_map(conn: &Connection, crate_name: &str) -> Result<HashMap<u64, String>> {
+    let mut stmt =
+        conn.prepare("SELECT version_id, num FROM crate_versions WHERE crate_name = ?1")?;
+    let map = stmt
+        .query_map([crate_name], |row| {
+            Ok((row.get::<_, i64>(0)? as u64, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(map)
+}
+
+/// What happened to a release asset or crate version.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TombstoneEvent {
+    /// No longer listed by the API.
+    Deleted,
+    Yanked,
+    Unyanked,
+    /// Listed again after being deleted.
+    Restored,
+}
+
+impl TombstoneEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TombstoneEvent::Deleted => "deleted",
+            TombstoneEvent::Yanked => "yanked",
+            TombstoneEvent::Unyanked => "unyanked",
+            TombstoneEvent::Restored => "restored",
+        }
+    }
+
+    fn from_db(s: &str) -> rusqlite::Result<Self> {
+        match s {
+            "deleted" => Ok(TombstoneEvent::Deleted),
+            "yanked" => Ok(TombstoneEvent::Yanked),
+            "unyanked" => Ok(TombstoneEvent::Unyanked),
+            "restored" => Ok(TombstoneEvent::Restored),
+            _ => Err(rusqlite::Error::InvalidColumnType(
+                0,
+                format!("unknown tombstone event '{}'", s),
+                rusqlite::types::Type::Text,
+            )),
+        }
+    }
+}
+
+/// A release asset or crate version that was deleted, yanked, or came back,
+/// as logged to `tombstones`.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Tombstone {
+    pub date: NaiveDate,
+    /// `github` or `crates`.
+    pub source: String,
+    /// The release tag, or the crate name.
+    pub identifier: String,
+    /// The asset name, or the version.
+    pub artifact: String,
+    pub event: TombstoneEvent,
+    /// Cumulative downloads when the artifact was last listed.
+    pub last_count: u64,
+}
+
+fn insert_tombstones(conn: &Connection, tombstones: &[Tombstone]) -> Result<()> {
+    for tombstone in tombstones {
+        conn.prepare_cached(
+            "INSERT OR REPLACE INTO main.tombstones
+                 (date, source, identifier, artifact, event, last_count)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            tombstone.date.to_string(),
+            tombstone.source,
+            tombstone.identifier,
+            tombstone.artifact,
+            tombstone.event.as_str(),
+            tombstone.last_count as i64
+        ])
+        .context("failed to record tombstone")?;
+    }
+    Ok(())
+}
+
+/// Logged tombstones, optionally for a single source kind, oldest first.
+pub fn get_tombstones(conn: &Connection, source: Option<&str>) -> Result<Vec<Tombstone>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, source, identifier, artifact, event, last_count FROM tombstones
+         WHERE ?1 IS NULL OR source = ?1
+         ORDER BY date, source, identifier, artifact",
+    )?;
+    let tombstones = stmt
+        .query_map([source], |row| {
+            let date: String = row.get(0)?;
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            let event: String = row.get(4)?;
+            Ok(Tombstone {
+                date,
+                source: row.get(1)?,
+                identifier: row.get(2)?,
+                artifact: row.get(3)?,
+                event: TombstoneEvent::from_db(&event)?,
+                last_count: row.get::<_, i64>(5)? as u64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read tombstones")?;
+    Ok(tombstones)
+}
+
+/// Whether an owner was added to or removed from a crate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OwnerChangeKind {
+    Added,
+    Removed,
+}
+
+impl OwnerChangeKind {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            OwnerChangeKind::Added => "added",
+            OwnerChangeKind::Removed => "removed",
+        }
+    }
+
+    fn from_db(s: &str) -> rusqlite::Result<Self> {
+        match s {
+            "added" => Ok(OwnerChangeKind::Added),
+            "removed" => Ok(OwnerChangeKind::Removed),
+            _ => Err(rusqlite::Error::InvalidColumnType(
+                0,
+                format!("unknown owner change '{}'", s),
+                rusqlite::types::Type::Text,
+            )),
+        }
+    }
+}
+
+/// A change to the owners of a crate.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct OwnerChange {
+    pub date: NaiveDate,
+    pub crate_name: String,
+    pub login: String,
+    /// `user` or `team`.
+    pub kind: String,
+    pub change: OwnerChangeKind,
+}
+
+/// Replace the recorded owners of a crate with `owners` (login and kind
+/// pairs), logging and returning the changes ordered by login.
+///
+/// The first snapshot of a crate is recorded as a baseline with no changes.
+pub fn update_crate_owners(
+    conn: &Connection,
+    date: NaiveDate,
+    crate_name: &str,
+    owners: &[(&str, &str)],
+) -> Result<Vec<OwnerChange>> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT login, kind FROM crate_owners WHERE crate_name = ?1 ORDER BY login",
+    )?;
+    let previous = stmt
+        .query_map([crate_name], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read crate owners")?;
+    let baseline = previous.is_empty();
+
+    let mut changes = Vec::new();
+    for &(login, kind) in owners {
+        if !previous.iter().any(|(previous, _)| previous == login) {
+            conn.prepare_cached(
+                "INSERT INTO crate_owners (crate_name, login, kind, first_seen)
+                 VALUES (?1, ?2, ?3, ?4)",
+            )?
+            .execute(params![crate_name, login, kind, date.to_string()])
+            .context("failed to insert crate owner")?;
+            if !baseline {
+                changes.push(OwnerChange {
+                    date,
+                    crate_name: crate_name.to_string(),
+                    login: login.to_string(),
+                    kind: kind.to_string(),
+                    change: OwnerChangeKind::Added,
+                });
+            }
+        }
+    }
+    for (login, kind) in previous {
+        if !owners.iter().any(|&(current, _)| current == login) {
+            conn.prepare_cached("DELETE FROM crate_owners WHERE crate_name = ?1 AND login = ?2")?
+                .execute(params![crate_name, login])
+                .context("failed to delete crate owner")?;
+            changes.push(OwnerChange {
+                date,
+                crate_name: crate_name.to_string(),
+                login,
+                kind,
+                change: OwnerChangeKind::Removed,
+            });
+        }
+    }
+    changes.sort_by(|a, b| a.login.cmp(&b.login));
+
+    for change in &changes {
+        conn.prepare_cached(
+            "INSERT OR REPLACE INTO crate_owner_changes (date, crate_name, login, kind, change)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?
+        .execute(params![
+            change.date.to_string(),
+            change.crate_name,
+            change.login,
+            change.kind,
+            change.change.as_str()
+        ])
+        .context("failed to record crate owner change")?;
+    }
+    Ok(changes)
+}
+
+/// Logged owner changes, optionally for a single crate, oldest first.
+pub fn get_crate_owner_changes(
+    conn: &Connection,
+    crate_name: Option<&str>,
+) -> Result<Vec<OwnerChange>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, crate_name, login, kind, change FROM crate_owner_changes
+         WHERE ?1 IS NULL OR crate_name = ?1
+         ORDER BY date, crate_name, login",
+    )?;
+    let changes = stmt
+        .query_map([crate_name], |row| {
+            let date: String = row.get(0)?;
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            let change: String = row.get(4)?;
+            Ok(OwnerChange {
+                date,
+                crate_name: row.get(1)?,
+                login: row.get(2)?,
+                kind: row.get(3)?,
+                change: OwnerChangeKind::from_db(&change)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read crate owner changes")?;
+    Ok(changes)
+}
+
+/// The result of rewriting numeric crates.io version IDs to semver strings.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Reattribution {
+    /// Rows whose version was rewritten.
+    pub updated: usize,
+    /// Rows dropped because a row with the semver string already existed for
+    /// the same date, e.g. when a day was collected both before and after the
+    /// mapping was learned.
+    pub duplicates: usize,
+}
+
+/// Condition matching `crates_downloads` rows whose version is a numeric ID
+/// with a known semver mapping.
+const MAPPED_VERSION_ID: &str = "EXISTS (
+    SELECT 1 FROM crate_versions v
+    WHERE v.crate_name = crates_downloads.crate_name
+      AND CAST(v.version_id AS TEXT) = crates_downloads.version
+)";
+
+/// Rewrite `crates_downloads.version` values that are numeric version IDs to
+/// their semver strings, using the `crate_versions` mapping.
+///
+/// Rows in attached archives are rewritten too. With `dry_run`, nothing is
+/// changed and only the counts are returned.
+pub fn reattribute_crate_versions(conn: &Connection, dry_run: bool) -> Result<Reattribution> {
+    let tx = conn.unchecked_transaction()?;
+    let mut result = Reattribution::default();
+    for schema in ["main".to_string()]
+        .into_iter()
+        .chain(archive::archives_with(&tx, "crates_downloads")?)
+    {
+        let rewritten = rewrite_crate_versions(&tx, &schema, None, dry_run)?;
+        result.updated += rewritten.updated;
+        result.duplicates += rewritten.duplicates;
+    }
+    if !dry_run {
+        tx.commit()
+            .context("failed to commit crates.io version reattribution")?;
+    }
+    Ok(result)
+}
+
+/// Rewrite numeric version IDs to semver strings in `crates_downloads` rows
+/// of one crate, within the caller's transaction.
+///
+/// Collection calls this before inserting the days crates.io reports, so that
+/// days stored under an ID before the mapping was known are replaced rather
+/// than counted again under the semver string.
+pub fn reattribute_crate(conn: &Connection, crate_name: &str) -> Result<Reattribution> {
+    rewrite_crate_versions(conn, "main", Some(crate_name), false)
+}
+
+fn rewrite_crate_versions(
+    conn: &Connection,
+    schema: &str,
+    crate_name: Option<&str>,
+    dry_run: bool,
+) -> Result<Reattribution> {
+    let mapped_filter =
+        format!("{MAPPED_VERSION_ID} AND (?1 IS NULL OR crates_downloads.crate_name = ?1)");
+    let duplicates_filter = format!(
+        "{mapped_filter}
+         AND EXISTS (
+             SELECT 1 FROM {schema}.crates_downloads d2
+             JOIN crate_versions v
+               ON v.crate_name = d2.crate_name AND v.num = d2.version
+             WHERE d2.date = crates_downloads.date
+               AND d2.crate_name = crates_downloads.crate_name
+               AND CAST(v.version_id AS TEXT) = crates_downloads.version
+         )"
+    );
+    let duplicates: usize = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {schema}.crates_downloads WHERE {duplicates_filter}"),
+        [crate_name],
+        |row| row.get(0),
+    )?;
+    let mapped: usize = conn.query_row(
+        &format!("SELECT COUNT(*) FROM {schema}.crates_downloads WHERE {mapped_filter}"),
+        [crate_name],
+        |row| row.get(0),
+    )?;
+    let result = Reattribution {
+        updated: mapped - duplicates,
+        duplicates,
+    };
+
+    if dry_run {
+        return Ok(result);
+    }
+
+    conn.execute(
+        &format!("DELETE FROM {schema}.crates_downloads WHERE {duplicates_filter}"),
+        [crate_name],
+    )
+    .context("failed to remove duplicate crates.io downloads")?;
+    conn.execute(
+        &format!(
+            "UPDATE {schema}.crates_downloads SET version = (
+                 SELECT v.num FROM crate_versions v
+                 WHERE v.crate_name = crates_downloads.crate_name
+                   AND CAST(v.version_id AS TEXT) = crates_downloads.version
+             )
+             WHERE {mapped_filter}"
+        ),
+        [crate_name],
+    )
+    .context("failed to rewrite crates.io versions")?;
+
+    Ok(result)
+}
+
+/// Insert or update a day of install endpoint downloads.
+///
+/// Counts for recent days may be revised as late logs arrive, so existing
+/// rows are replaced.
+pub fn insert_installer_download(
+    conn: &Connection,
+    date: NaiveDate,
+    name: &str,
+    downloads: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO main.installer_downloads (date, name, downloads)
+         VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![date.to_string(), name, downloads as i64])
+    .context("failed to insert installer download")?;
+    Ok(())
+}
+
+/// Insert a crates.io metadata snapshot taken at `collected_at`.
+pub fn insert_crates_metadata(
+    conn: &Connection,
+    date: NaiveDate,
+    collected_at: DateTime<Utc>,
+    crate_name: &str,
+    total_downloads: u64,
+    recent_downloads: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO main.crates_metadata
+         (date, crate_name, total_downloads, recent_downloads, collected_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?
+    .execute(params![
+        date.to_string(),
+        crate_name,
+        total_downloads as i64,
+        recent_downloads as i64,
+        collected_at.to_rfc3339()
+    ])
+    .context("failed to insert crates.io metadata")?;
+    Ok(())
+}
+
+/// Clear the weekly aggregates for a source before they are recomputed.
+pub fn clear_weekly_stats(conn: &Connection, source: &str) -> Result<()> {
+    conn.execute("DELETE FROM weekly_stats WHERE source = ?1", [source])
+        .context("failed to clear weekly aggregates")?;
+    Ok(())
+}
+
+/// Replace the asset name patterns excluded from GitHub download totals.
+pub fn replace_asset_exclusions(conn: &Connection, patterns: &[String]) -> Result<()> {
+    conn.execute("DELETE FROM asset_exclusions", [])
+        .context("failed to clear asset exclusions")?;
+    for pattern in patterns {
+        conn.execute(
+            "INSERT OR IGNORE INTO asset_exclusions (pattern) VALUES (?1)",
+            [pattern],
+        )
+        .with_context(|| format!("failed to store asset exclusion '{}'", pattern))?;
+    }
+    Ok(())
+}
+
+/// Replace the sources grouped under each project.
+///
+/// Aggregates are identified by the source's name: the crate, install
+/// endpoint, or GitHub repository (`owner/repo`).
+pub fn replace_project_sources(conn: &Connection, projects: &[ProjectConfig]) -> Result<()> {
+    conn.execute("DELETE FROM project_sources", [])
+        .context("failed to clear project sources")?;
+    for project in projects {
+        for source in &project.sources {
+            conn.execute(
+                "INSERT OR IGNORE INTO project_sources (project, source, identifier)
+                 VALUES (?1, ?2, ?3)",
+                params![project.name, source.kind, source.name],
+            )
+            .with_context(|| {
+                format!(
+                    "failed to store source '{}' of project '{}'",
+                    source, project.name
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Clear the weekly aggregates for all sources.
+pub fn clear_all_weekly_stats(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM weekly_stats", [])
+        .context("failed to clear weekly aggregates")?;
+    Ok(())
+}
+
+/// Clear the monthly aggregates for a source before they are recomputed.
+pub fn clear_monthly_stats(conn: &Connection, source: &str) -> Result<()> {
+    conn.execute("DELETE FROM monthly_stats WHERE source = ?1", [source])
+        .context("failed to clear monthly aggregates")?;
+    Ok(())
+}
+
+/// Clear the weekly per-platform GitHub aggregates before they are recomputed.
+pub fn clear_weekly_platform_stats(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM weekly_platform_stats", [])
+        .context("failed to clear weekly platform aggregates")?;
+    Ok(())
+}
+
+/// Clear the weekly per-format GitHub aggregates before they are recomputed.
+pub fn clear_weekly_format_stats(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM weekly_format_stats", [])
+        .context("failed to clear weekly format aggregates")?;
+    Ok(())
+}
+
+/// Insert a weekly aggregate statistic.
+pub fn insert_weekly_stat(
+    conn: &Connection,
+    week_start: NaiveDate,
+    source: &str,
+    identifier: &str,
+    downloads: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO weekly_stats (week_start, source, identifier, downloads)
+         VALUES (?1, ?2, ?3, ?4)",
+    )?
+    .execute(params![
+        week_start.to_string(),
+        source,
+        identifier,
+        downloads as i64
+    ])
+    .context("failed to insert weekly stat")?;
+    Ok(())
+}
+
+/// Insert a weekly per-platform GitHub aggregate.
+pub fn insert_weekly_platform_stat(
+    conn: &Connection,
+    week_start: NaiveDate,
+    platform: &str,
+    downloads: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO weekly_platform_stats (week_start, platform, downloads)
+         VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![week_start.to_string(), platform, downloads as i64])
+    .context("failed to insert weekly platform stat")?;
+    Ok(())
+}
+
+/// Insert a weekly GitHub per-platform, per-format aggregate.
+pub fn insert_weekly_format_stat(
+    conn: &Connection,
+    week_start: NaiveDate,
+    platform: &str,
+    format: &str,
+    downloads: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO weekly_format_stats (week_start, platform, format, downloads)
+         VALUES (?1, ?2, ?3, ?4)",
+    )?
+    .execute(params![
+        week_start.to_string(),
+        platform,
+        format,
+        downloads as i64
+    ])
+    .context("failed to insert weekly format stat")?;
+    Ok(())
+}
+
+/// Clear the weekly version shares before recomputing them.
+pub fn clear_weekly_version_share(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM weekly_version_share", [])
+        .context("failed to clear weekly version shares")?;
+    Ok(())
+}
+
+/// Insert a weekly version share.
+pub fn insert_weekly_version_share(
+    conn: &Connection,
+    week_start: NaiveDate,
+    source: &str,
+    version: &str,
+    downloads: u64,
+    share: f64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO weekly_version_share (week_start, source, version, downloads, share)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?
+    .execute(params![
+        week_start.to_string(),
+        source,
+        version,
+        downloads as i64,
+        share
+    ])
+    .context("failed to insert weekly version share")?;
+    Ok(())
+}
+
+/// Clear the weekly release channel aggregates before they are recomputed.
+pub fn clear_weekly_channel_stats(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM weekly_channel_stats", [])
+        .context("failed to clear weekly release channel aggregates")?;
+    Ok(())
+}
+
+/// Insert a weekly release channel aggregate.
+pub fn insert_weekly_channel_stat(
+    conn: &Connection,
+    week_start: NaiveDate,
+    source: &str,
+    channel: &str,
+    downloads: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO weekly_channel_stats (week_start, source, channel, downloads)
+         VALUES (?1, ?2, ?3, ?4)",
+    )?
+    .execute(params![
+        week_start.to_string(),
+        source,
+        channel,
+        downloads as i64
+    ])
+    .context("failed to insert weekly release channel aggregate")?;
+    Ok(())
+}
+
+/// Clear the weekly verification aggregates before they are recomputed.
+pub fn clear_weekly_verification_stats(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM weekly_verification_stats", [])
+        .context("failed to clear weekly verification aggregates")?;
+    Ok(())
+}
+
+/// Insert a weekly verification aggregate.
+pub fn insert_weekly_verification_stat(
+    conn: &Connection,
+    week_start: NaiveDate,
+    artifacts: u64,
+    signatures: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO weekly_verification_stats (week_start, artifacts, signatures)
+         VALUES (?1, ?2, ?3)",
+    )?
+    .execute(params![
+        week_start.to_string(),
+        artifacts as i64,
+        signatures as i64
+    ])
+    .context("failed to insert weekly verification aggregate")?;
+    Ok(())
+}
+
+/// A GitHub release asset with its first and latest snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GithubAsset {
+    /// `owner/repo`, empty if unknown.
+    pub repo: String,
+    pub release_tag: String,
+    pub asset_name: String,
+    pub first_seen: NaiveDate,
+    pub last_download_count: u64,
+}
+
+/// Get every GitHub release asset ever seen, including ones no longer listed.
+///
+/// Unlike `github_assets`, which is only updated by collection, this reads
+/// the snapshots directly, so it covers backfilled snapshots too.
+pub fn get_github_assets(conn: &Connection) -> Result<Vec<GithubAsset>> {
+    let mut stmt = conn.prepare(
+        "SELECT s.repo, s.release_tag, s.asset_name, r.first_seen, s.download_count
+         FROM (
+             SELECT repo, release_tag, asset_name,
+                    MIN(date) AS first_seen, MAX(date) AS last_seen
+             FROM github_snapshots
+             GROUP BY repo, release_tag, asset_name
+         ) r
+         JOIN github_snapshots s
+           ON s.repo = r.repo
+          AND s.release_tag = r.release_tag
+          AND s.asset_name = r.asset_name
+          AND s.date = r.last_seen
+         ORDER BY s.release_tag, s.asset_name, s.repo",
+    )?;
+    let assets = stmt
+        .query_map([], |row| {
+            let first_seen: String = row.get(3)?;
+            let first_seen = NaiveDate::parse_from_str(&first_seen, "%Y-%m-%d")
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            Ok(GithubAsset {
+                repo: row.get(0)?,
+                release_tag: row.get(1)?,
+                asset_name: row.get(2)?,
+                first_seen,
+                last_download_count: row.get::<_, i64>(4)? as u64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read GitHub assets")?;
+    Ok(assets)
+}
+
+/// Clear the release targets before they are recomputed.
+pub fn clear_release_targets(conn: &Connection) -> Result<()> {
+    conn.execute("DELETE FROM release_targets", [])
+        .context("failed to clear release targets")?;
+    Ok(())
+}
+
+/// The artifacts a GitHub release shipped for one target triple.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReleaseTarget {
+    pub release_tag: String,
+    pub target: String,
+    pub platform: String,
+    pub artifacts: u64,
+    pub downloads: u64,
+    pub first_seen: NaiveDate,
+}
+
+/// Insert a release target.
+pub fn insert_release_target(conn: &Connection, target: &ReleaseTarget) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO release_targets
+         (release_tag, target, platform, artifacts, downloads, first_seen)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+    )?
+    .execute(params![
+        target.release_tag,
+        target.target,
+        target.platform,
+        target.artifacts as i64,
+        target.downloads as i64,
+        target.first_seen.to_string()
+    ])
+    .context("failed to insert release target")?;
+    Ok(())
+}
+
+/// Get all release targets.
+pub fn get_release_targets(conn: &Connection) -> Result<Vec<ReleaseTarget>> {
+    let mut stmt = conn.prepare(
+        "SELECT release_tag, target, platform, artifacts, downloads, first_seen
+         FROM release_targets
+         ORDER BY release_tag, target",
+    )?;
+    let targets = stmt
+        .query_map([], |row| {
+            let first_seen: String = row.get(5)?;
+            let first_seen = NaiveDate::parse_from_str(&first_seen, "%Y-%m-%d")
+                .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+            Ok(ReleaseTarget {
+                release_tag: row.get(0)?,
+                target: row.get(1)?,
+                platform: row.get(2)?,
+                artifacts: row.get::<_, i64>(3)? as u64,
+                downloads: row.get::<_, i64>(4)? as u64,
+                first_seen,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read release targets")?;
+    Ok(targets)
+}
+
+/// Insert a monthly aggregate statistic.
+pub fn insert_monthly_stat(
+    conn: &Connection,
+    month_start: NaiveDate,
+    source: &str,
+    identifier: &str,
+    downloads: u64,
+) -> Result<()> {
+    conn.prepare_cached(
+        "INSERT OR REPLACE INTO monthly_stats (month_start, source, identifier, downloads)
+         VALUES (?1, ?2, ?3, ?4)",
+    )?
+    .execute(params![
+        month_start.to_string(),
+        source,
+        identifier,
+        downloads as i64
+    ])
+    .context("failed to insert monthly stat")?;
+    Ok(())
+}
+
+/// Record the outcome of a collect run. `error` is `None` for successful runs.
+pub fn insert_collection_run(
+    conn: &Connection,
+    started_at: DateTime<Utc>,
+    finished_at: DateTime<Utc>,
+    error: Option<&str>,
+) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO collection_runs (started_at, finished_at, success, error)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            started_at.to_rfc3339(),
+            finished_at.to_rfc3339(),
+            error.is_none(),
+            error
+        ],
+    )
+    .context("failed to record collection run")?;
+    Ok(())
+}
+
+/// The outcome of an attempt to collect a single source.
+#[derive(Clone, Copy, Debug)]
+pub enum SourceAttempt<'a> {
+    Success {
+        /// The latest date the source reported data for.
+        latest_date: Option<NaiveDate>,
+        /// The ETag of the source's response, if it sent one.
+        etag: Option<&'a str>,
+    },
+    Failure {
+        error: &'a str,
+    },
+}
+
+/// Record an attempt to collect a single source.
+pub fn record_source_attempt(
+    conn: &Connection,
+    kind: &str,
+    name: &str,
+    at: DateTime<Utc>,
+    attempt: SourceAttempt<'_>,
+) -> Result<()> {
+    match attempt {
+        SourceAttempt::Success { latest_date, etag } => conn.execute(
+            "INSERT INTO collector_state
+                 (kind, name, last_attempt_at, last_success_at, last_collected_date, etag,
+                  consecutive_failures, last_error)
+             VALUES (?1, ?2, ?3, ?3, ?4, ?5, 0, NULL)
+             ON CONFLICT (kind, name) DO UPDATE SET
+                 last_attempt_at = excluded.last_attempt_at,
+                 last_success_at = excluded.last_success_at,
+                 last_collected_date =
+                     COALESCE(excluded.last_collected_date, last_collected_date),
+                 etag = excluded.etag,
+                 consecutive_failures = 0,
+                 last_error = NULL",
+            params![
+                kind,
+                name,
+                at.to_rfc3339(),
+                latest_date.map(|date| date.to_string()),
+                etag
+            ],
+        ),
+        SourceAttempt::Failure { error } => conn.execute(
+            "INSERT INTO collector_state
+                 (kind, name, last_attempt_at, consecutive_failures, last_error)
+             VALUES (?1, ?2, ?3, 1, ?4)
+             ON CONFLICT (kind, name) DO UPDATE SET
+                 last_attempt_at = excluded.last_attempt_at,
+                 consecutive_failures = consecutive_failures + 1,
+                 last_error = excluded.last_error",
+            params![kind, name, at.to_rfc3339(), error],
+        ),
+    }
+    .context("failed to record source collection attempt")?;
+    Ok(())
+}
+
+/// A source's collection progress, as stored in `collector_state`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct SourceState {
+    pub kind: String,
+    pub name: String,
+    /// RFC3339 timestamp of the last attempt.
+    pub last_attempt_at: String,
+    /// RFC3339 timestamp of the last successful attempt.
+    pub last_success_at: Option<String>,
+    pub last_collected_date: Option<NaiveDate>,
+    pub etag: Option<String>,
+    pub consecutive_failures: u32,
+    pub last_error: Option<String>,
+}
+
+/// Get the collection progress of every source ever collected.
+pub fn get_collector_state(conn: &Connection) -> Result<Vec<SourceState>> {
+    let mut stmt = conn.prepare(
+        "SELECT kind, name, last_attempt_at, last_success_at, last_collected_date, etag,
+                consecutive_failures, last_error
+         FROM collector_state
+         ORDER BY kind, name",
+    )?;
+    let states = stmt
+        .query_map([], |row| {
+            let last_collected_date = row
+                .get::<_, Option<String>>(4)?
+                .map(|date| {
+                    NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                        .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+                })
+                .transpose()?;
+            Ok(SourceState {
+                kind: row.get(0)?,
+                name: row.get(1)?,
+                last_attempt_at: row.get(2)?,
+                last_success_at: row.get(3)?,
+                last_collected_date,
+                etag: row.get(5)?,
+                consecutive_failures: row.get(6)?,
+                last_error: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read collector state")?;
+    Ok(states)
+}
+
+/// Get when a source was last collected successfully, as an RFC3339 string.
+pub fn get_source_last_success(
+    conn: &Connection,
+    kind: &str,
+    name: &str,
+) -> Result<Option<String>> {
+    let result = conn
+        .query_row(
+            "SELECT last_success_at FROM collector_state WHERE kind = ?1 AND name = ?2",
+            params![kind, name],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(result.flatten())
+}
+
+/// Get a convention the aggregates were computed with.
+pub fn get_aggregation_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM aggregation_settings WHERE key = ?1",
+        [key],
+        |row| row.get(0),
+    )
+    .optional()
+    .with_context(|| format!("failed to read aggregation setting '{}'", key))
+}
+
+/// Record a convention the aggregates were computed with.
+pub fn set_aggregation_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO aggregation_settings (key, value) VALUES (?1, ?2)",
+        [key, value],
+    )
+    .with_context(|| format!("failed to store aggregation setting '{}'", key))?;
+    Ok(())
+}
+
+/// A failed collect run.
+#[derive(Debug)]
+pub struct FailedRun {
+    pub started_at: String,
+    pub error: String,
+}
+
+/// What a collection filter left out of a source.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct FilterSkip {
+    /// `tag-filter` or `asset-filter` for GitHub releases or assets that a
+    /// source's release filter leaves out, or `exclude-assets` for GitHub
+    /// assets left out of download totals.
+    pub filter: String,
+    /// Releases or assets left out.
+    pub skipped: u64,
+    /// Downloads of the releases or assets left out.
+    pub downloads: u64,
+    /// Releases or assets that passed the filter.
+    pub kept: u64,
+}
+
+impl FilterSkip {
+    pub fn new(filter: &str) -> Self {
+        Self {
+            filter: filter.to_string(),
+            skipped: 0,
+            downloads: 0,
+            kept: 0,
+        }
+    }
+
+    /// Whether the filter left out everything there was.
+    pub fn excludes_everything(&self) -> bool {
+        self.kept == 0 && self.skipped > 0
+    }
+}
+
+/// Record what filters left out of a source in the run started at
+/// `started_at`.
+pub fn insert_run_skips(
+    conn: &Connection,
+    started_at: DateTime<Utc>,
+    source: &str,
+    skips: &[FilterSkip],
+) -> Result<()> {
+    for skip in skips {
+        conn.prepare_cached(
+            "INSERT OR REPLACE INTO collection_run_skips
+             (started_at, source, filter, skipped, downloads, kept)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?
+        .execute(params![
+            started_at.to_rfc3339(),
+            source,
+            skip.filter,
+            skip.skipped as i64,
+            skip.downloads as i64,
+            skip.kept as i64
+        ])
+        .context("failed to record skipped items")?;
+    }
+    Ok(())
+}
+
+/// A collect run, with what filters left out of each source.
+#[derive(Debug)]
+pub struct CollectionRun {
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub error: Option<String>,
+    /// Skips by source (as `kind:name`).
+    pub skips: Vec<(String, FilterSkip)>,
+}
+
+/// Get the most recent collect runs, most recent first.
+pub fn get_collection_runs(conn: &Connection, limit: usize) -> Result<Vec<CollectionRun>> {
+    let parse = |s: String| {
+        DateTime::parse_from_rfc3339(&s)
+            .map(|t| t.with_timezone(&Utc))
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+    };
+    let mut stmt = conn.prepare(
+        "SELECT started_at, finished_at, error FROM collection_runs
+         ORDER BY started_at DESC LIMIT ?1",
+    )?;
+    let mut runs = stmt
+        .query_map([limit as i64], |row| {
+            Ok(CollectionRun {
+                started_at: parse(row.get(0)?)?,
+                finished_at: parse(row.get(1)?)?,
+                error: row.get(2)?,
+                skips: Vec::new(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read collection runs")?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT source, filter, skipped, downloads, kept FROM collection_run_skips
+         WHERE started_at = ?1
+         ORDER BY source, filter",
+    )?;
+    for run in &mut runs {
+        run.skips = stmt
+            .query_map([run.started_at.to_rfc3339()], |row| {
+                Ok((
+                    row.get(0)?,
+                    FilterSkip {
+                        filter: row.get(1)?,
+                        skipped: row.get::<_, i64>(2)? as u64,
+                        downloads: row.get::<_, i64>(3)? as u64,
+                        kept: row.get::<_, i64>(4)? as u64,
+                    },
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .context("failed to read skipped items")?;
+    }
+    Ok(runs)
+}
+
+/// Whether an asset name matches one of the stored asset exclusions.
+pub fn is_excluded_asset(conn: &Connection, asset_name: &str) -> Result<bool> {
+    conn.prepare_cached("SELECT EXISTS (SELECT 1 FROM asset_exclusions WHERE ?1 GLOB pattern)")?
+        .query_row([asset_name], |row| row.get(0))
+        .context("failed to match asset exclusions")
+}
+
+/// Get the failed runs since the last successful one, most recent first.
+pub fn get_consecutive_failures(conn: &Connection) -> Result<Vec<FailedRun>> {
+    let mut stmt = conn.prepare(
+        "SELECT started_at, COALESCE(error, '') FROM collection_runs
+         WHERE success = 0
+           AND started_at > COALESCE(
+               (SELECT MAX(started_at) FROM collection_runs WHERE success = 1), '')
+         ORDER BY started_at DESC",
+    )?;
+    let runs = stmt
+        .query_map([], |row| {
+            Ok(FailedRun {
+                started_at: row.get(0)?,
+                error: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(runs)
+}
+
+/// Get the start time of the last successful collect run.
+pub fn get_last_successful_run(conn: &Connection) -> Result<Option<String>> {
+    let result = conn.query_row(
+        "SELECT MAX(started_at) FROM collection_runs WHERE success = 1",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(result)
+}
+
+/// Get the latest date for which we have GitHub snapshots.
+pub fn get_latest_github_snapshot_date(conn: &Connection) -> Result<Option<NaiveDate>> {
+    let mut stmt = conn.prepare("SELECT MAX(date) FROM github_snapshots")?;
+    let result: Option<String> = stmt.query_row([], |row| row.get(0))?;
 
     match result {
         Some(date_str) => {
@@ -191,3 +2405,723 @@ pub fn get_latest_crates_download_date(
         None => Ok(None),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noon(date: NaiveDate) -> DateTime<Utc> {
+        date.and_hms_opt(12, 0, 0).unwrap().and_utc()
+    }
+
+    fn version(id: u64, num: &str, yanked: bool, downloads: u64) -> CrateVersion {
+        CrateVersion {
+            id,
+            num: num.to_string(),
+            created_at: "2025-01-01T00:00:00Z".to_string(),
+            yanked,
+            downloads,
+        }
+    }
+
+    #[test]
+    fn test_reattribute_crate_versions() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+
+        update_crate_versions(&conn, day(1), "foo", &[version(100, "1.0.0", false, 0)]).unwrap();
+        insert_crates_download(&conn, day(1), "foo", Some("100"), 5).unwrap();
+        // Collected both before and after the mapping was known.
+        insert_crates_download(&conn, day(2), "foo", Some("100"), 6).unwrap();
+        insert_crates_download(&conn, day(2), "foo", Some("1.0.0"), 6).unwrap();
+        // No mapping for this ID yet.
+        insert_crates_download(&conn, day(2), "foo", Some("200"), 7).unwrap();
+        insert_crates_download(&conn, day(2), "foo", None, 8).unwrap();
+
+        let expected = Reattribution {
+            updated: 1,
+            duplicates: 1,
+        };
+        assert_eq!(reattribute_crate_versions(&conn, true).unwrap(), expected);
+        assert_eq!(reattribute_crate_versions(&conn, false).unwrap(), expected);
+        assert_eq!(
+            reattribute_crate_versions(&conn, false).unwrap(),
+            Reattribution::default()
+        );
+
+        let mut stmt = conn
+            .prepare("SELECT date, version, downloads FROM crates_downloads ORDER BY date, version")
+            .unwrap();
+        let rows: Vec<(String, String, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("2025-11-01".to_string(), "1.0.0".to_string(), 5),
+                ("2025-11-02".to_string(), "".to_string(), 8),
+                ("2025-11-02".to_string(), "1.0.0".to_string(), 6),
+                ("2025-11-02".to_string(), "200".to_string(), 7),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_crate_owners() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        let team = "github:nextest-rs:core";
+
+        // The first snapshot is a baseline.
+        let owners = [("alice", "user"), (team, "team")];
+        assert_eq!(
+            update_crate_owners(&conn, day(1), "foo", &owners).unwrap(),
+            []
+        );
+        assert_eq!(
+            update_crate_owners(&conn, day(2), "foo", &owners).unwrap(),
+            []
+        );
+
+        let owners = [("bob", "user"), (team, "team")];
+        let changes = update_crate_owners(&conn, day(3), "foo", &owners).unwrap();
+        let summary: Vec<_> = changes
+            .iter()
+            .map(|change| (change.login.as_str(), change.change))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("alice", OwnerChangeKind::Removed),
+                ("bob", OwnerChangeKind::Added),
+            ]
+        );
+        assert_eq!(
+            get_crate_owner_changes(&conn, Some("foo")).unwrap(),
+            changes
+        );
+        assert_eq!(get_crate_owner_changes(&conn, Some("bar")).unwrap(), []);
+
+        let first_seen: Vec<(String, String)> = conn
+            .prepare("SELECT login, first_seen FROM crate_owners ORDER BY login")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            first_seen,
+            [
+                ("bob".to_string(), "2025-11-03".to_string()),
+                (team.to_string(), "2025-11-01".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_github_assets() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        let collect = |d: u32, repo: &str, assets: &[(&str, &str, u64)]| {
+            for &(tag, name, count) in assets {
+                insert_github_snapshot(&conn, day(d), Some(noon(day(d))), repo, tag, name, count)
+                    .unwrap();
+            }
+            update_github_assets(&conn, day(d), repo).unwrap()
+        };
+
+        let other = collect(1, "o/other", &[("w1", "c.zip", 3)]);
+        assert_eq!(other, []);
+        let first = collect(1, "o/r", &[("v1", "a.tar.gz", 10), ("v1", "b.zip", 5)]);
+        assert_eq!(first, []);
+
+        // b.zip was deleted. o/other wasn't collected, so c.zip is left alone.
+        let expected = ArchivedAsset {
+            release_tag: "v1".to_string(),
+            asset_name: "b.zip".to_string(),
+            last_seen: day(1),
+        };
+        assert_eq!(collect(2, "o/r", &[("v1", "a.tar.gz", 12)]), [expected]);
+        // Only reported once.
+        assert_eq!(collect(3, "o/r", &[("v1", "a.tar.gz", 15)]), []);
+
+        // The archived asset keeps counting towards cumulative totals.
+        let total: i64 = conn
+            .query_row(
+                "SELECT SUM(download_count) FROM github_cumulative WHERE date = ?1",
+                [day(3).to_string()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(total, 20);
+
+        // Reuploaded under the same name.
+        let assets = [("v1", "a.tar.gz", 16), ("v1", "b.zip", 0)];
+        assert_eq!(collect(4, "o/r", &assets), []);
+        assert_eq!(collect(4, "o/other", &[("w1", "c.zip", 4)]), []);
+        let archived: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM github_assets WHERE archived_on IS NOT NULL",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(archived, 0);
+
+        let events: Vec<_> = get_tombstones(&conn, Some("github"))
+            .unwrap()
+            .into_iter()
+            .map(|t| (t.date, t.artifact, t.event, t.last_count))
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (day(2), "b.zip".to_string(), TombstoneEvent::Deleted, 5),
+                (day(4), "b.zip".to_string(), TombstoneEvent::Restored, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_update_crate_versions() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        let events = |tombstones: Vec<Tombstone>| -> Vec<(String, TombstoneEvent, u64)> {
+            tombstones
+                .into_iter()
+                .map(|t| (t.artifact, t.event, t.last_count))
+                .collect()
+        };
+        let total = |date: NaiveDate| -> i64 {
+            conn.query_row(
+                "SELECT total_downloads FROM crates_cumulative WHERE date = ?1",
+                [date.to_string()],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        // The first listing is a baseline, even with yanked versions.
+        let listed = [
+            version(1, "1.0.0", true, 100),
+            version(2, "1.0.1", false, 200),
+            version(3, "1.1.0", false, 50),
+        ];
+        assert_eq!(
+            update_crate_versions(&conn, day(1), "foo", &listed).unwrap(),
+            []
+        );
+        insert_crates_metadata(&conn, day(1), noon(day(1)), "foo", 350, 0).unwrap();
+
+        // 1.1.0 was deleted, and 1.0.1 yanked while 1.0.0 was unyanked.
+        let listed = [
+            version(1, "1.0.0", false, 110),
+            version(2, "1.0.1", true, 210),
+        ];
+        assert_eq!(
+            events(update_crate_versions(&conn, day(2), "foo", &listed).unwrap()),
+            [
+                ("1.0.0".to_string(), TombstoneEvent::Unyanked, 110),
+                ("1.0.1".to_string(), TombstoneEvent::Yanked, 210),
+                ("1.1.0".to_string(), TombstoneEvent::Deleted, 50),
+            ]
+        );
+        // crates.io took the deleted version's downloads off the total, but
+        // they're still counted.
+        insert_crates_metadata(&conn, day(2), noon(day(2)), "foo", 320, 0).unwrap();
+        assert_eq!(total(day(1)), 350);
+        assert_eq!(total(day(2)), 370);
+
+        // Tombstones are only logged once, and an empty listing doesn't mark
+        // everything deleted.
+        assert_eq!(
+            update_crate_versions(&conn, day(3), "foo", &listed).unwrap(),
+            []
+        );
+        assert_eq!(
+            update_crate_versions(&conn, day(3), "foo", &[]).unwrap(),
+            []
+        );
+
+        // Listed again.
+        let listed = [version(3, "1.1.0", false, 50)];
+        assert_eq!(
+            events(update_crate_versions(&conn, day(4), "foo", &listed[..]).unwrap()),
+            [
+                ("1.0.0".to_string(), TombstoneEvent::Deleted, 110),
+                ("1.0.1".to_string(), TombstoneEvent::Deleted, 210),
+                ("1.1.0".to_string(), TombstoneEvent::Restored, 50),
+            ]
+        );
+        assert_eq!(get_tombstones(&conn, Some("crates")).unwrap().len(), 6);
+        assert_eq!(get_tombstones(&conn, Some("github")).unwrap(), []);
+    }
+
+    #[test]
+    fn test_github_releases() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let at = |s| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        upsert_github_release(
+            &conn,
+            "cargo-nextest-0.9.99",
+            at("2025-06-10T23:30:00Z"),
+            false,
+        )
+        .unwrap();
+        upsert_github_release(
+            &conn,
+            "cargo-nextest-0.9.98",
+            at("2025-06-01T12:00:00Z"),
+            true,
+        )
+        .unwrap();
+        // Editing a release replaces what was recorded.
+        upsert_github_release(
+            &conn,
+            "cargo-nextest-0.9.98",
+            at("2025-06-02T08:00:00+02:00"),
+            false,
+        )
+        .unwrap();
+
+        let releases = get_github_releases(&conn).unwrap();
+        assert_eq!(
+            releases,
+            [
+                GithubRelease {
+                    release_tag: "cargo-nextest-0.9.98".to_string(),
+                    published_on: NaiveDate::from_ymd_opt(2025, 6, 2).unwrap(),
+                    prerelease: false,
+                },
+                GithubRelease {
+                    release_tag: "cargo-nextest-0.9.99".to_string(),
+                    published_on: NaiveDate::from_ymd_opt(2025, 6, 10).unwrap(),
+                    prerelease: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_github_release_snapshots() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let at = |s| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        assert_eq!(get_latest_snapshotted_release(&conn, None).unwrap(), None);
+
+        for (collected_at, a, b) in [
+            ("2025-06-10T10:00:00Z", 5, 2),
+            ("2025-06-10T11:00:00Z", 9, 4),
+        ] {
+            let collected_at = at(collected_at);
+            insert_github_release_snapshot(&conn, "o/r", "v1", collected_at, "a.tar.gz", a)
+                .unwrap();
+            insert_github_release_snapshot(&conn, "o/r", "v1", collected_at, "b.zip", b).unwrap();
+        }
+        // Another repository's release with the same tag.
+        insert_github_release_snapshot(&conn, "o/other", "v1", at("2025-06-10T10:30:00Z"), "x", 50)
+            .unwrap();
+        insert_github_release_snapshot(
+            &conn,
+            "o/r",
+            "v2",
+            at("2025-06-11T10:00:00Z"),
+            "a.tar.gz",
+            1,
+        )
+        .unwrap();
+
+        let latest = |tag| get_latest_snapshotted_release(&conn, tag).unwrap();
+        assert_eq!(latest(None), Some(("o/r".to_string(), "v2".to_string())));
+        assert_eq!(
+            latest(Some("v1")),
+            Some(("o/r".to_string(), "v1".to_string()))
+        );
+        assert_eq!(
+            get_github_release_snapshots(&conn, "o/r", "v1").unwrap(),
+            [
+                ReleaseSnapshot {
+                    collected_at: at("2025-06-10T10:00:00Z"),
+                    downloads: 7,
+                },
+                ReleaseSnapshot {
+                    collected_at: at("2025-06-10T11:00:00Z"),
+                    downloads: 13,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_collection_runs() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let at = |s| DateTime::parse_from_rfc3339(s).unwrap().to_utc();
+        replace_asset_exclusions(&conn, &["*.sha256".to_string()]).unwrap();
+        assert!(is_excluded_asset(&conn, "a.tar.gz.sha256").unwrap());
+        assert!(!is_excluded_asset(&conn, "a.tar.gz").unwrap());
+
+        let first = at("2025-06-01T02:00:00.123456Z");
+        insert_collection_run(&conn, first, at("2025-06-01T02:01:00Z"), None).unwrap();
+        let mut excluded = FilterSkip::new("exclude-assets");
+        excluded.skipped = 4;
+        excluded.downloads = 10;
+        let untracked = FilterSkip::new("tag-filter");
+        let skips = [excluded.clone(), untracked.clone()];
+        insert_run_skips(&conn, first, "github:o/r", &skips).unwrap();
+        let second = at("2025-06-02T02:00:00Z");
+        insert_collection_run(&conn, second, second, Some("boom")).unwrap();
+
+        let runs = get_collection_runs(&conn, 10).unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[0].started_at, second);
+        assert_eq!(runs[0].error.as_deref(), Some("boom"));
+        assert!(runs[0].skips.is_empty());
+        assert_eq!(runs[1].started_at, first);
+        assert_eq!(
+            runs[1].skips,
+            [
+                ("github:o/r".to_string(), excluded.clone()),
+                ("github:o/r".to_string(), untracked),
+            ]
+        );
+        assert!(excluded.excludes_everything());
+        assert_eq!(get_collection_runs(&conn, 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_insert_backfilled_snapshot() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 11, d).unwrap();
+        let url = "https://web.archive.org/web/20251101000000id_/https://api.github.com/";
+
+        let origin = BackfillOrigin {
+            source: "wayback",
+            url,
+        };
+
+        insert_github_snapshot(
+            &conn,
+            day(5),
+            Some(noon(day(5))),
+            "o/r",
+            "v1",
+            "a.tar.gz",
+            50,
+        )
+        .unwrap();
+        assert!(
+            insert_backfilled_snapshot(&conn, day(1), "o/r", "v1", "a.tar.gz", 10, origin).unwrap()
+        );
+        // Collected snapshots are never overwritten.
+        assert!(
+            !insert_backfilled_snapshot(&conn, day(5), "o/r", "v1", "a.tar.gz", 40, origin)
+                .unwrap()
+        );
+        // Another repository's asset of the same name is a different asset.
+        assert!(
+            insert_backfilled_snapshot(&conn, day(5), "o/other", "v1", "a.tar.gz", 40, origin)
+                .unwrap()
+        );
+
+        let count: i64 = conn
+            .query_row(
+                "SELECT download_count FROM github_snapshots WHERE date = ?1 AND repo = 'o/r'",
+                [day(5).to_string()],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 50);
+        let backfilled: i64 = conn
+            .query_row("SELECT COUNT(*) FROM github_backfill", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(backfilled, 2);
+
+        assert_eq!(first_collected_github_date(&conn).unwrap(), Some(day(5)));
+    }
+
+    #[test]
+    fn test_record_source_attempt() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let at = |hour| {
+            NaiveDate::from_ymd_opt(2025, 11, 19)
+                .unwrap()
+                .and_hms_opt(hour, 0, 0)
+                .unwrap()
+                .and_utc()
+        };
+        let success = |etag| SourceAttempt::Success {
+            latest_date: Some(NaiveDate::from_ymd_opt(2025, 11, 19).unwrap()),
+            etag,
+        };
+
+        record_source_attempt(&conn, "github", "o/r", at(1), success(Some("\"v1\""))).unwrap();
+        for hour in [2, 3] {
+            let failure = SourceAttempt::Failure { error: "timed out" };
+            record_source_attempt(&conn, "github", "o/r", at(hour), failure).unwrap();
+        }
+
+        let [state] = &get_collector_state(&conn).unwrap()[..] else {
+            panic!("expected a single source");
+        };
+        assert_eq!(state.last_attempt_at, at(3).to_rfc3339());
+        assert_eq!(state.last_success_at, Some(at(1).to_rfc3339()));
+        assert_eq!(state.etag.as_deref(), Some("\"v1\""));
+        assert_eq!(state.consecutive_failures, 2);
+        assert_eq!(state.last_error.as_deref(), Some("timed out"));
+
+        record_source_attempt(&conn, "github", "o/r", at(4), success(None)).unwrap();
+        let state = &get_collector_state(&conn).unwrap()[0];
+        assert_eq!(state.consecutive_failures, 0);
+        assert_eq!(state.last_error, None);
+        assert_eq!(state.etag, None);
+    }
+
+    #[test]
+    fn test_add_column_if_missing() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (a TEXT NOT NULL); INSERT INTO t VALUES ('x');")
+            .unwrap();
+
+        for _ in 0..2 {
+            add_column_if_missing(&conn, "t", "b", "INTEGER NOT NULL DEFAULT 0").unwrap();
+        }
+        let b: i64 = conn
+            .query_row("SELECT b FROM t", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(b, 0);
+    }
+
+    #[test]
+    fn test_project_stats() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let week = NaiveDate::from_ymd_opt(2025, 11, 17).unwrap();
+        insert_weekly_stat(&conn, week, "github", "nextest-rs/nextest", 100).unwrap();
+        insert_weekly_stat(&conn, week, "github", "nextest-rs/other", 1000).unwrap();
+        insert_weekly_stat(&conn, week, "github", "releases", 10000).unwrap();
+        insert_weekly_stat(&conn, week, "crates", "cargo-nextest", 50).unwrap();
+        insert_weekly_stat(&conn, week, "crates", "nextest-runner", 7).unwrap();
+
+        let project = |name: &str, sources: &[&str]| ProjectConfig {
+            name: name.to_string(),
+            sources: sources
+                .iter()
+                .map(|s| s.to_string().try_into().unwrap())
+                .collect(),
+        };
+        replace_project_sources(
+            &conn,
+            &[
+                project(
+                    "nextest",
+                    &["github:nextest-rs/nextest", "crates:cargo-nextest"],
+                ),
+                project("runner", &["crates:nextest-runner"]),
+                project("other", &["github:nextest-rs/other"]),
+            ],
+        )
+        .unwrap();
+
+        let mut stmt = conn
+            .prepare("SELECT project, downloads FROM weekly_project_stats ORDER BY project")
+            .unwrap();
+        let rows: Vec<(String, i64)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            rows,
+            [
+                ("nextest".to_string(), 150),
+                ("other".to_string(), 1000),
+                ("runner".to_string(), 7)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_superseded_snapshots() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let day = NaiveDate::from_ymd_opt(2025, 11, 19).unwrap();
+        let at = |hour| day.and_hms_opt(hour, 0, 0).unwrap().and_utc();
+        let canonical = || -> (i64, String) {
+            conn.query_row(
+                "SELECT download_count, collected_at FROM github_snapshots",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap()
+        };
+
+        assert!(
+            !insert_github_snapshot(
+                &conn,
+                day,
+                Some(at(6)),
+                "nextest-rs/nextest",
+                "v1",
+                "a.tar.gz",
+                10
+            )
+            .unwrap()
+        );
+        assert!(
+            insert_github_snapshot(
+                &conn,
+                day,
+                Some(at(18)),
+                "nextest-rs/nextest",
+                "v1",
+                "a.tar.gz",
+                15
+            )
+            .unwrap()
+        );
+        assert_eq!(canonical(), (15, at(18).to_rfc3339()));
+
+        // A snapshot taken earlier than the stored one doesn't replace it.
+        assert!(
+            insert_github_snapshot(
+                &conn,
+                day,
+                Some(at(12)),
+                "nextest-rs/nextest",
+                "v1",
+                "a.tar.gz",
+                12
+            )
+            .unwrap()
+        );
+        assert_eq!(canonical(), (15, at(18).to_rfc3339()));
+
+        let mut stmt = conn
+            .prepare(
+                "SELECT download_count, superseded_by FROM github_superseded
+                 ORDER BY download_count",
+            )
+            .unwrap();
+        let superseded: Vec<(i64, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(
+            superseded,
+            [(10, at(18).to_rfc3339()), (12, at(18).to_rfc3339())]
+        );
+    }
+
+    #[test]
+    fn test_snapshots_per_repo() {
+        let conn = init_db(Utf8Path::new(":memory:")).unwrap();
+        let day = NaiveDate::from_ymd_opt(2025, 11, 19).unwrap();
+        let at = |hour| day.and_hms_opt(hour, 0, 0).unwrap().and_utc();
+
+        // The same tag and asset name in two repositories are different
+        // assets, not a re-collection of one.
+        assert!(
+            !insert_github_snapshot(&conn, day, Some(at(6)), "o/a", "v1", "a.tar.gz", 10).unwrap()
+        );
+        assert!(
+            !insert_github_snapshot(&conn, day, Some(at(7)), "o/b", "v1", "a.tar.gz", 20).unwrap()
+        );
+        let superseded: i64 = conn
+            .query_row("SELECT COUNT(*) FROM github_superseded", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(superseded, 0);
+        let total: i64 = conn
+            .query_row(
+                "SELECT SUM(download_count) FROM github_cumulative",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(total, 30);
+    }
+
+    #[test]
+    fn test_legacy_github_tables() {
+        let dir = camino::Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-legacy-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("stats.db");
+
+        // The GitHub tables as they were before rows were stored per
+        // repository, once github_assets recorded the repository.
+        Connection::open(db_path.as_std_path())
+            .unwrap()
+            .execute_batch(
+                "CREATE TABLE github_snapshots (
+                     date TEXT NOT NULL,
+                     release_tag TEXT NOT NULL,
+                     asset_name TEXT NOT NULL,
+                     download_count INTEGER NOT NULL,
+                     collected_at TEXT,
+                     PRIMARY KEY (date, release_tag, asset_name)
+                 ) WITHOUT ROWID;
+                 CREATE TABLE github_assets (
+                     release_tag TEXT NOT NULL,
+                     asset_name TEXT NOT NULL,
+                     first_seen TEXT NOT NULL,
+                     last_seen TEXT NOT NULL,
+                     last_download_count INTEGER NOT NULL,
+                     archived_on TEXT,
+                     repo TEXT,
+                     PRIMARY KEY (release_tag, asset_name)
+                 ) WITHOUT ROWID;
+                 CREATE VIEW github_included AS SELECT * FROM github_snapshots;
+                 INSERT INTO github_snapshots VALUES
+                     ('2025-11-01', 'v1', 'a.tar.gz', 10, NULL),
+                     ('2025-11-01', 'old', 'c.zip', 3, NULL);
+                 INSERT INTO github_assets VALUES
+                     ('v1', 'a.tar.gz', '2025-11-01', '2025-11-01', 10, NULL, 'o/r'),
+                     ('old', 'c.zip', '2025-11-01', '2025-11-01', 3, NULL, NULL);",
+            )
+            .unwrap();
+
+        let conn = init_db(&db_path).unwrap();
+        let repos = |table: &str| -> Vec<(String, String)> {
+            conn.prepare(&format!(
+                "SELECT release_tag, repo FROM {table} ORDER BY release_tag"
+            ))
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+        };
+        let pair = |tag: &str, repo: &str| (tag.to_string(), repo.to_string());
+        // Repositories recorded for assets are kept; the rest are unknown.
+        let expected = [pair("old", ""), pair("v1", "o/r")];
+        assert_eq!(repos("github_snapshots"), expected);
+        assert_eq!(repos("github_assets"), expected);
+        let legacy: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name LIKE 'legacy_%'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(legacy, 0);
+
+        assert_eq!(claim_unattributed_github_rows(&conn, "o/r").unwrap(), 2);
+        let expected = [pair("old", "o/r"), pair("v1", "o/r")];
+        assert_eq!(repos("github_snapshots"), expected);
+        assert_eq!(repos("github_assets"), expected);
+        assert_eq!(claim_unattributed_github_rows(&conn, "o/r").unwrap(), 0);
+
+        // Opening the database again leaves it as it is.
+        drop(conn);
+        let conn = init_db(&db_path).unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM github_included", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+        drop(conn);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,209 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The weekly Markdown digest.
+//!
+//! `report markdown` sums up the latest complete week in a few lines: its
+//! downloads from each source against the week before, the most downloaded
+//! version line, the projection of the week in progress, and any anomalies,
+//! for pasting into a team update or a GitHub Discussions post. In a
+//! scheduled workflow, `--github-step-summary` adds it to the job summary
+//! instead.
+
+use crate::{
+    analysis,
+    format::{self, Target},
+    output,
+    table::{Cell, Column, OutputFormat, Report, Table},
+};
+use anyhow::{Context, Result};
+use chrono::{Days, NaiveDate};
+use rusqlite::Connection;
+use std::{cmp::Reverse, fs::OpenOptions, io::Write};
+
+/// The environment variable GitHub Actions sets to the job summary file.
+pub const STEP_SUMMARY_ENV: &str = "GITHUB_STEP_SUMMARY";
+
+/// Write the digest of the latest complete week as Markdown.
+///
+/// Weeks are flagged as anomalies as by `analyze anomalies`, against the
+/// previous `window` weeks at a z-score of `threshold`.
+pub fn weekly_digest(conn: &Connection, window: usize, threshold: f64) -> Result<String> {
+    let trend = analysis::weekly_trend(conn, "all")?;
+    let Some(&(week, total)) = trend.last() else {
+        return Ok("No complete weeks of downloads yet.\n".to_string());
+    };
+    let week_before = week - Days::new(7);
+    let downloads_in = |series: &[(NaiveDate, u64)], week| {
+        series
+            .iter()
+            .find(|(start, _)| *start == week)
+            .map(|(_, downloads)| *downloads)
+    };
+    let previous_total = downloads_in(&trend, week_before);
+
+    let mut table = Table::new([
+        Column::left("Source", 0),
+        Column::right("Downloads", 0),
+        Column::right("Week before", 0),
+        Column::right("Change %", 0),
+    ]);
+    let mut row = |source: &str, downloads: u64, previous: Option<u64>| {
+        table.push([
+            Cell::text(source),
+            Cell::Count(downloads),
+            Cell::optional(previous, Cell::Count),
+            Cell::percent_change(analysis::percent_change(downloads, previous)),
+        ]);
+    };
+    for source in analysis::SOURCES {
+        let series = analysis::weekly_trend(conn, source)?;
+        if let Some(downloads) = downloads_in(&series, week) {
+            row(source, downloads, downloads_in(&series, week_before));
+        }
+    }
+    row("total", total, previous_total);
+
+    let summary = match analysis::percent_change(total, previous_total) {
+        Some(change) => format!(
+            "**{}** downloads, {:+.1}% from the week before.",
+            format::number_for(Target::Reports, total),
+            change
+        ),
+        None => format!(
+            "**{}** downloads.",
+            format::number_for(Target::Reports, total)
+        ),
+    };
+    let mut out = format!("## Downloads in the week of {}\n\n{}\n\n", week, summary);
+    out += &Report::default()
+        .table(table)
+        .render_for(OutputFormat::Markdown, Target::Reports)?;
+
+    if let Some(growth) = analysis::version_growth(conn, "all", 1, true)?
+        && let Some(top) = growth
+            .versions
+            .iter()
+            .min_by_key(|version| Reverse(version.recent))
+    {
+        let versioned: u64 = growth.versions.iter().map(|v| v.recent).sum();
+        out += &format!(
+            "\nTop version: **{}**, with {} downloads ({:.1}% of downloads with a known \
+             version).\n",
+            top.version,
+            format::number_for(Target::Reports, top.recent),
+            top.recent as f64 / versioned.max(1) as f64 * 100.0
+        );
+    }
+
+    if let Some(projection) = analysis::project_current_week(conn, "all")? {
+        out += &format!(
+            "\nThe week of {} is in progress, with {} downloads in {} of 7 days: \
+             **{}** projected for the full week.\n",
+            projection.week_start,
+            format::number_for(Target::Reports, projection.downloads),
+            projection.days,
+            format::number_for(Target::Reports, projection.projected)
+        );
+    }
+
+    let anomalies: Vec<String> = analysis::detect_anomalies(conn, window, threshold, Some(2))?
+        .into_iter()
+        .filter(|anomaly| anomaly.week_start == week)
+        .map(|anomaly| {
+            format!(
+                "- {}: a {} to {} downloads, against a mean of {} (z = {:.2})",
+                anomaly.source,
+                anomaly.kind.as_str(),
+                format::number_for(Target::Reports, anomaly.downloads),
+                format::number_for(Target::Reports, anomaly.mean.round() as u64),
+                anomaly.z_score
+            )
+        })
+        .collect();
+    if anomalies.is_empty() {
+        out += &format!("\nNo anomalies against the previous {} weeks.\n", window);
+    } else {
+        out += &format!(
+            "\nAnomalies against the previous {} weeks:\n\n{}\n",
+            window,
+            anomalies.join("\n")
+        );
+    }
+    Ok(out)
+}
+
+/// Append the digest to the job summary of the running GitHub Actions job.
+pub fn write_step_summary(digest: &str) -> Result<()> {
+    let path = std::env::var(STEP_SUMMARY_ENV).with_context(|| {
+        format!(
+            "{} isn't set, so this doesn't look like a GitHub Actions job",
+            STEP_SUMMARY_ENV
+        )
+    })?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("failed to open job summary at {}", path))?;
+    writeln!(file, "{}", digest).with_context(|| format!("failed to write {}", path))?;
+    output::written(&path, "Added the digest to the job summary.");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use camino::Utf8Path;
+
+    #[test]
+    fn test_weekly_digest() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let empty = weekly_digest(&conn, 8, 3.0).unwrap();
+        assert_eq!(empty, "No complete weeks of downloads yet.\n");
+
+        let date = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        // Weeks start on Mondays: Nov 3 and 10. Nov 17 isn't complete.
+        let crate_download = |day, version, downloads| {
+            db::insert_crates_download(&conn, date(day), "cargo-nextest", Some(version), downloads)
+                .unwrap()
+        };
+        crate_download(4, "0.9.1", 100);
+        crate_download(12, "0.9.2", 90);
+        crate_download(13, "0.8.5", 30);
+        crate_download(17, "0.9.2", 10);
+        db::insert_weekly_stat(&conn, date(3), "crates", "cargo-nextest", 100).unwrap();
+        db::insert_weekly_stat(&conn, date(10), "crates", "cargo-nextest", 120).unwrap();
+        db::insert_weekly_stat(&conn, date(17), "crates", "cargo-nextest", 10).unwrap();
+
+        let digest = weekly_digest(&conn, 8, 3.0).unwrap();
+        assert_eq!(
+            digest,
+            "## Downloads in the week of 2025-11-10\n\
+             \n**120** downloads, +20.0% from the week before.\n\
+             \n| Source | Downloads | Week before | Change % |\n\
+             | :--- | ---: | ---: | ---: |\n\
+             | crates | 120 | 100 | +20.0% |\n\
+             | total | 120 | 100 | +20.0% |\n\
+             \nTop version: **0.9**, with 90 downloads (75.0% of downloads with a known \
+             version).\n\
+             \nNo anomalies against the previous 8 weeks.\n"
+        );
+
+        // With a day-of-week pattern to learn from, the week in progress is
+        // projected: Mondays had 200 of the 1400 downloads of the last two
+        // weeks.
+        for day in 3..=16 {
+            db::insert_rolling_stat(&conn, date(day), "crates", 100.0, 0.0, 0.0).unwrap();
+        }
+        let digest = weekly_digest(&conn, 8, 3.0).unwrap();
+        assert!(
+            digest.contains(
+                "\nThe week of 2025-11-17 is in progress, with 10 downloads in 1 of 7 days: \
+                 **70** projected for the full week.\n"
+            ),
+            "{digest}"
+        );
+    }
+}
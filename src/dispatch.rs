@@ -3,10 +3,24 @@
 
 //! CLI argument parsing and command dispatch.
 
-use crate::{commands, config, db, query};
-use anyhow::{Context, Result};
+use crate::{
+    amend, analysis, commands, config, daemon, db, digest,
+    fixtures::Fixtures,
+    format, http, metrics, onboard, output, page,
+    platform::PlatformGroup,
+    provenance::Provenance,
+    publish, query, report, retry, split, table, tui,
+    upload::{self, S3Location},
+    website,
+};
+use anyhow::{Context, Result, bail};
 use camino::Utf8PathBuf;
+use chrono::NaiveDate;
 use clap::Parser;
+use std::{
+    io::{self, IsTerminal},
+    time::Duration,
+};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -15,10 +29,20 @@ pub struct Args {
     #[arg(short, long, default_value = "download-stats.db", global = true)]
     database: Utf8PathBuf,
 
-    /// Path to the configuration file
+    /// Path to the configuration file, or '-' to read it from standard input
     #[arg(short, long, default_value = "config.toml", global = true)]
     config: Utf8PathBuf,
 
+    /// Collect from this source instead of the configured ones: 'github:OWNER/REPO',
+    /// 'crates:NAME', or 'installer:NAME=STATS_URL' (can be repeated)
+    #[arg(long = "source-spec", value_name = "SPEC", global = true)]
+    source_specs: Vec<config::CollectionSource>,
+
+    /// Leave out progress messages, so that standard output only holds data: a
+    /// JSON summary for collect and refresh, or the path of each file written
+    #[arg(short, long, visible_alias = "machine", global = true)]
+    quiet: bool,
+
     #[command(subcommand)]
     command: Command,
 }
@@ -35,29 +59,389 @@ enum Command {
         #[arg(long)]
         skip_crates: bool,
 
+        /// Skip install endpoint statistics collection
+        #[arg(long)]
+        skip_installer: bool,
+
         /// Skip weekly aggregation computation
         #[arg(long)]
         skip_aggregation: bool,
+
+        /// Only collect this configured source, such as 'github:OWNER/REPO' or
+        /// 'crates:NAME' (can be repeated)
+        #[arg(
+            long,
+            value_name = "SOURCE",
+            conflicts_with_all = ["skip_github", "skip_crates", "skip_installer"],
+        )]
+        only: Vec<config::SourceRef>,
+
+        /// Stop collecting once this much time has passed (e.g. '90s', '5m', '1h')
+        #[arg(long, value_parser = parse_duration)]
+        time_budget: Option<Duration>,
+
+        /// Stop waiting on the network once this much time has passed (e.g.
+        /// '10m'), deferring sources still being fetched and skipping
+        /// notifications and issue filing still to be done
+        #[arg(long, value_parser = parse_duration)]
+        deadline: Option<Duration>,
+
+        /// Number of sources to fetch at once
+        #[arg(
+            long,
+            default_value_t = commands::DEFAULT_PARALLELISM,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        )]
+        parallelism: usize,
+
+        /// Fail if any source fails to be collected, rather than only if all
+        /// of them do
+        #[arg(long)]
+        strict: bool,
+
+        /// Keep running, collecting on a schedule and regenerating the
+        /// configured charts after each collection
+        #[arg(long)]
+        daemon: bool,
+
+        /// When to collect with --daemon, as a cron expression in UTC
+        /// (minute, hour, day of month, month, day of week) [default: '0 3 * *
+        /// *']
+        #[arg(long, value_name = "CRON", requires = "daemon")]
+        schedule: Option<daemon::Schedule>,
+
+        /// Exit with an error if any configured alert fires
+        #[arg(long, conflicts_with = "daemon")]
+        fail_on_alert: bool,
+
+        /// Only check the most recent GitHub releases, recording new ones and
+        /// a snapshot of the newest release's downloads. Meant to run hourly
+        /// between full collections.
+        #[arg(long, conflicts_with_all = [
+            "skip_github",
+            "skip_crates",
+            "skip_installer",
+            "skip_aggregation",
+            "only",
+            "time_budget",
+            "deadline",
+            "parallelism",
+            "strict",
+            "daemon",
+            "fail_on_alert",
+            "as_of",
+        ])]
+        releases_only: bool,
+
+        /// Record GitHub snapshots for this date (YYYY-MM-DD) rather than
+        /// today, such as to make up for yesterday's failed run. There must be
+        /// no later snapshots.
+        #[arg(long, value_name = "DATE", conflicts_with_all = ["daemon", "skip_github"])]
+        as_of: Option<NaiveDate>,
+
+        /// Answer requests from the files in --fixtures rather than the
+        /// network, for tests and development without network access or
+        /// tokens
+        #[arg(long, requires = "fixtures")]
+        offline: bool,
+
+        /// The directory of fixture files used with --offline
+        #[arg(long, value_name = "DIR", requires = "offline")]
+        fixtures: Option<Utf8PathBuf>,
     },
 
     /// Generate charts from collected statistics
     Charts {
-        /// Output directory for charts
-        #[arg(short, long, default_value = "charts")]
-        output: Utf8PathBuf,
+        /// Output directory for every chart, instead of the configured chart
+        /// outputs [default: charts]
+        #[arg(short, long, conflicts_with = "profile")]
+        output: Option<Utf8PathBuf>,
+
+        /// Generate only this configured chart output (can be repeated)
+        #[arg(long)]
+        profile: Vec<String>,
+
+        /// Color theme for every chart, overriding the configured themes:
+        /// 'light' or 'dark'
+        #[arg(long)]
+        theme: Option<config::ChartTheme>,
+
+        /// Leave out data before this date (YYYY-MM-DD), or this many days
+        /// before today (e.g. 90d), overriding the configured range
+        #[arg(long)]
+        since: Option<config::ChartDate>,
+
+        /// Leave out data after this date (YYYY-MM-DD), or this many days
+        /// before today, overriding the configured range
+        #[arg(long)]
+        until: Option<config::ChartDate>,
+
+        /// Period of the download trend charts, overriding the configured
+        /// resolution: 'daily', 'weekly', or 'monthly'
+        #[arg(long)]
+        resolution: Option<config::ChartResolution>,
+
+        /// Generate only this chart, such as 'sparkline' or 'weekly-trends',
+        /// leaving out metric and custom charts (can be repeated)
+        #[arg(long, value_name = "CHART")]
+        only: Vec<config::ChartKind>,
+
+        /// Size of every chart in pixels, such as '300x80', overriding the
+        /// configured size
+        #[arg(long, value_name = "WIDTHxHEIGHT")]
+        size: Option<config::ChartSize>,
+
+        /// Multiply the pixel size of every chart, along with its text and
+        /// lines, such as 2 for high-DPI displays, overriding the configured
+        /// scale (at most 8)
+        #[arg(long, value_name = "FACTOR", value_parser = clap::value_parser!(u32).range(1..=config::MAX_CHART_SCALE as i64))]
+        scale: Option<u32>,
+
+        /// Overlay a 4-week moving average on the weekly trends chart
+        #[arg(long)]
+        moving_average: bool,
+
+        /// Draw the download trend charts with a logarithmic y axis
+        #[arg(long)]
+        log_scale: bool,
+
+        /// Write the data each chart was drawn from to a `.json` file
+        /// alongside it
+        #[arg(long)]
+        data: bool,
+
+        /// Upload the generated charts to object storage (s3://bucket/prefix)
+        #[arg(long, value_name = "S3_URL")]
+        upload: Option<S3Location>,
+    },
+
+    /// Collect, aggregate, generate charts, write the HTML report, and publish
+    /// in one go
+    Refresh {
+        /// Skip collection entirely
+        #[arg(long)]
+        skip_collect: bool,
+
+        /// Skip GitHub release statistics collection
+        #[arg(long)]
+        skip_github: bool,
+
+        /// Skip crates.io statistics collection
+        #[arg(long)]
+        skip_crates: bool,
+
+        /// Skip install endpoint statistics collection
+        #[arg(long)]
+        skip_installer: bool,
+
+        /// Skip computing aggregates
+        #[arg(long)]
+        skip_aggregation: bool,
+
+        /// Skip chart generation
+        #[arg(long)]
+        skip_charts: bool,
+
+        /// Skip writing the HTML report
+        #[arg(long)]
+        skip_report: bool,
+
+        /// Skip exporting the public snapshot
+        #[arg(long)]
+        skip_publish: bool,
+
+        /// Stop collecting once this much time has passed (e.g. '90s', '5m', '1h')
+        #[arg(long, value_parser = parse_duration)]
+        time_budget: Option<Duration>,
+
+        /// Stop waiting on the network once this much time has passed (e.g.
+        /// '10m'), deferring sources still being fetched and skipping
+        /// notifications, issue filing, and the upload still to be done
+        #[arg(long, value_parser = parse_duration)]
+        deadline: Option<Duration>,
+
+        /// Number of sources to fetch at once
+        #[arg(
+            long,
+            default_value_t = commands::DEFAULT_PARALLELISM,
+            value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..),
+        )]
+        parallelism: usize,
+
+        /// Fail if any source fails to be collected, rather than only if all
+        /// of them do
+        #[arg(long)]
+        strict: bool,
+
+        /// Output directory for every chart [default: charts]
+        #[arg(long, conflicts_with = "charts_profile")]
+        charts_output: Option<Utf8PathBuf>,
+
+        /// Generate this configured chart output instead
+        #[arg(long)]
+        charts_profile: Option<String>,
+
+        /// Color theme for the charts, overriding the configured theme:
+        /// 'light' or 'dark'
+        #[arg(long)]
+        charts_theme: Option<config::ChartTheme>,
+
+        /// Output file path for the HTML report
+        #[arg(long, default_value = "report/index.html")]
+        report_output: Utf8PathBuf,
+
+        /// Output directory for the public snapshot
+        #[arg(long, default_value = "public")]
+        public_output: Utf8PathBuf,
+
+        /// Upload charts and the public snapshot to object storage
+        /// (s3://bucket/prefix), under `charts/` and `public/`
+        #[arg(long, value_name = "S3_URL")]
+        upload: Option<S3Location>,
+    },
+
+    /// Rewrite crates.io version IDs to semver strings using learned mappings
+    Reattribute {
+        /// Show what would change without modifying the database
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Don't recompute aggregates afterwards
+        #[arg(long)]
+        skip_aggregation: bool,
+    },
+
+    /// Move raw rows from old years into per-year archive databases
+    Archive {
+        /// Archive whole years that ended more than this many years ago
+        #[arg(long, value_name = "YEARS")]
+        older_than: u32,
+
+        /// Show what would be archived without modifying the database
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Correct data in the database
+    Db {
+        #[command(subcommand)]
+        db_type: DbType,
+    },
+
+    /// Import historical statistics from other sources
+    Backfill {
+        #[command(subcommand)]
+        backfill_type: BackfillType,
     },
 
     /// Query download statistics
     Query {
+        /// How to print results: 'table', 'json', 'csv', 'markdown', or 'html'
+        #[arg(long, global = true, default_value = "table")]
+        format: table::OutputFormat,
+
         #[command(subcommand)]
         query_type: QueryType,
     },
 
+    /// Analyze download statistics
+    Analyze {
+        #[command(subcommand)]
+        analyze_type: AnalyzeType,
+    },
+
     /// Export statistics to various formats
     Export {
         #[command(subcommand)]
         export_type: ExportType,
     },
+
+    /// Report on published snapshots, or write an HTML report or weekly
+    /// digest
+    Report {
+        #[command(subcommand)]
+        report_type: ReportType,
+    },
+
+    /// Browse the latest statistics in an interactive terminal dashboard
+    Tui,
+
+    /// Set up a configuration file and database, and collect for the first
+    /// time
+    Init {
+        /// Take every step without asking
+        #[arg(short, long)]
+        yes: bool,
+
+        /// Don't collect after setting up
+        #[arg(long)]
+        skip_collect: bool,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum DbType {
+    /// Delete or overwrite the data recorded for a source on one date, such
+    /// as after a bad run, and recompute the aggregates it affects
+    Amend {
+        /// The data to amend: 'github' for release snapshots (or
+        /// 'github:OWNER/REPO' for one repository's), 'crates:NAME', or
+        /// 'installer:NAME'
+        source: amend::Source,
+
+        /// The date the data was recorded for
+        #[arg(long)]
+        date: NaiveDate,
+
+        /// Delete the data
+        #[arg(long, required_unless_present = "set")]
+        delete: bool,
+
+        /// Overwrite the data with this count: an asset's cumulative
+        /// downloads for GitHub, or the day's downloads otherwise
+        #[arg(long, value_name = "COUNT", conflicts_with = "delete")]
+        set: Option<u64>,
+
+        /// Only amend GitHub snapshots of this release
+        #[arg(long, value_name = "TAG")]
+        release: Option<String>,
+
+        /// Only amend GitHub snapshots of this asset
+        #[arg(long, value_name = "NAME")]
+        asset: Option<String>,
+
+        /// Only amend crates.io downloads of this version
+        #[arg(long)]
+        version: Option<String>,
+
+        /// Show what would change without modifying the database
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Don't recompute aggregates afterwards
+        #[arg(long)]
+        skip_aggregation: bool,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum BackfillType {
+    /// Import GitHub download counts from Wayback Machine captures of the
+    /// releases API (best-effort, recorded as low confidence)
+    Wayback {
+        /// Only import captures from before this date (default: the first
+        /// collected snapshot)
+        #[arg(long)]
+        before: Option<NaiveDate>,
+
+        /// Show what would be imported without modifying the database
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Don't recompute aggregates afterwards
+        #[arg(long)]
+        skip_aggregation: bool,
+    },
 }
 
 #[derive(Parser, Debug)]
@@ -68,95 +452,1398 @@ enum QueryType {
         #[arg(short = 'n', long, default_value = "12")]
         limit: usize,
 
-        /// Source to query: 'github', 'crates', or 'all'
+        /// Source to query: 'github', 'crates', 'installer', or 'all'
         #[arg(short, long, default_value = "all")]
         source: String,
+
+        /// Show GitHub downloads for a single platform (e.g. 'linux-x86_64')
+        #[arg(short, long, conflicts_with_all = ["source", "crate_name", "identifier", "by_crate"])]
+        platform: Option<String>,
+
+        /// Add a column with the change from the previous week
+        #[arg(long, conflicts_with = "by_crate")]
+        delta: bool,
+
+        /// Only show this crate (implies --source crates)
+        #[arg(long = "crate", conflicts_with_all = ["source", "identifier", "by_crate"])]
+        crate_name: Option<String>,
+
+        /// Only show this identifier of the source: a crate, 'owner/repo' for
+        /// GitHub releases, or an install endpoint
+        #[arg(long, conflicts_with = "by_crate")]
+        identifier: Option<String>,
+
+        /// Show a column per crate and their total (implies --source crates)
+        #[arg(long, conflicts_with = "source")]
+        by_crate: bool,
+
+        /// Only include weeks starting on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// Only include weeks starting on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+
+    /// Show monthly (or quarterly) download statistics
+    Monthly {
+        /// Number of months (or quarters) to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+
+        /// Source to query: 'github', 'crates', 'installer', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Group months into calendar quarters
+        #[arg(long)]
+        quarterly: bool,
+
+        /// Only show this crate (implies --source crates)
+        #[arg(long = "crate", conflicts_with_all = ["source", "identifier", "by_crate"])]
+        crate_name: Option<String>,
+
+        /// Only show this identifier of the source: a crate, 'owner/repo' for
+        /// GitHub releases, or an install endpoint
+        #[arg(long, conflicts_with = "by_crate")]
+        identifier: Option<String>,
+
+        /// Show a column per crate and their total (implies --source crates)
+        #[arg(long, conflicts_with = "source")]
+        by_crate: bool,
     },
 
     /// Show total downloads
     Total {
+        /// Source to query: 'github', 'crates', 'installer', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Only show this crate (implies --source crates)
+        #[arg(long = "crate", conflicts_with_all = ["source", "identifier", "by_crate"])]
+        crate_name: Option<String>,
+
+        /// Only show this identifier of the source: a crate, 'owner/repo' for
+        /// GitHub releases, or an install endpoint
+        #[arg(long, conflicts_with = "by_crate")]
+        identifier: Option<String>,
+
+        /// Show a row per crate as well as their total (implies --source crates)
+        #[arg(long, conflicts_with = "source")]
+        by_crate: bool,
+
+        /// Only include weeks starting on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// Only include weeks starting on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+
+    /// Show daily download statistics
+    Daily {
+        /// Number of days to show
+        #[arg(short = 'n', long, default_value = "14")]
+        limit: usize,
+
+        /// Source to query: 'github', 'crates', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Only show this crate (implies --source crates)
+        #[arg(long = "crate", conflicts_with_all = ["source", "by_crate"])]
+        crate_name: Option<String>,
+
+        /// Show a column per crate and their total (implies --source crates)
+        #[arg(long, conflicts_with = "source")]
+        by_crate: bool,
+    },
+
+    /// Show the rate of change of GitHub downloads (downloads per day)
+    Rate {
+        /// Number of snapshots to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+
+        /// Number of snapshot intervals to smooth over
+        #[arg(short, long, default_value = "4")]
+        window: usize,
+    },
+
+    /// Show how many release versions are actively downloaded each week
+    Diversity {
+        /// Number of weeks to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+
+        /// Minimum share of weekly downloads (in percent) for a version to count as active
+        #[arg(long, default_value = "1.0")]
+        min_share: f64,
+    },
+
+    /// Show daily downloads with trailing 7-day and 30-day averages
+    Rolling {
+        /// Number of days to show
+        #[arg(short = 'n', long, default_value = "14")]
+        limit: usize,
+
+        /// Source to query: 'github', 'crates', 'installer', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+    },
+
+    /// Show week-over-week, month-over-month, and year-over-year growth
+    Growth,
+
+    /// Show the share of weekly downloads per major.minor version
+    VersionShare {
+        /// Number of weeks to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+
+        /// Source to query: 'github', 'crates', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Number of newest versions to show; older ones are grouped together
+        #[arg(long, default_value = "5")]
+        versions: usize,
+    },
+
+    /// Show downloads of each version over a recent window, most downloaded
+    /// first
+    Versions {
+        /// Source to query: 'github', 'crates', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Number of days of data to count, up to the latest collected date
+        #[arg(long, default_value = "30")]
+        days: u32,
+
+        /// Number of versions to show; the rest are grouped together
+        #[arg(long, default_value = "20")]
+        top: usize,
+    },
+
+    /// Show downloads of each version over the last weeks against the weeks
+    /// before, to see which releases are still being installed
+    VersionGrowth {
+        /// Source to query: 'github', 'crates', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+
+        /// Number of complete weeks in each window
+        #[arg(
+            long,
+            default_value = "4",
+            value_parser = clap::value_parser!(u32).range(1..=analysis::MAX_GROWTH_WEEKS as i64)
+        )]
+        weeks: u32,
+
+        /// Number of versions to show, fastest growing first
+        #[arg(long, default_value = "20")]
+        top: usize,
+
+        /// Group versions by major.minor line
+        #[arg(long)]
+        lines: bool,
+    },
+
+    /// Show GitHub release downloads per target, OS, or architecture over a
+    /// period, with each one's share
+    Platforms {
+        /// What to group downloads by: 'target', 'os', or 'arch'
+        #[arg(long, default_value = "target")]
+        by: PlatformGroup,
+
+        /// Number of days of data to count, up to the latest collected date
+        #[arg(
+            long,
+            default_value = "30",
+            conflicts_with_all = ["since", "until"],
+            value_parser = clap::value_parser!(u32).range(1..=query::DateRange::MAX_DAYS as i64)
+        )]
+        days: u32,
+
+        /// Only count downloads on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// Only count downloads on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+
+    /// Compare downloads from each source in two periods
+    Compare {
+        /// The period to look at: a year (2024), quarter (2024-Q1), month
+        /// (2024-03), or dates (2024-01-01..2024-03-31)
+        #[arg(long, conflicts_with = "last")]
+        period: Option<query::DateRange>,
+
+        /// Look at the last this many days of data instead, such as '30d'
+        /// [default: 30d]
+        #[arg(long, value_parser = parse_duration)]
+        last: Option<Duration>,
+
+        /// What to compare against: 'previous' for as many days just before,
+        /// 'year' for the same dates a year earlier, or another period
+        #[arg(long, default_value = "previous")]
+        against: query::CompareBaseline,
+    },
+
+    /// Show summary statistics of weekly downloads for each source
+    Stats,
+
+    /// Show days without collected data, the age of each source's latest
+    /// data, and weeks with incomplete data
+    Gaps {
+        /// Number of gaps and of incomplete weeks to show
+        #[arg(short = 'n', long, default_value = "20")]
+        limit: usize,
+
+        /// Exit with an error if any source's latest data is older than this,
+        /// such as '3d'
+        #[arg(long, value_parser = parse_duration)]
+        max_staleness: Option<Duration>,
+    },
+
+    /// Show weekly downloads of stable versions and pre-releases
+    Channel {
+        /// Number of weeks to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+
         /// Source to query: 'github', 'crates', or 'all'
         #[arg(short, long, default_value = "all")]
         source: String,
     },
 
+    /// Show weekly downloads of signatures and attestations relative to the
+    /// artifacts they sign, as a proxy for verification adoption
+    Verification {
+        /// Number of weeks to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+    },
+
+    /// Show GitHub downloads by asset format (tar.gz, zip, ...) per platform
+    Formats {
+        /// Number of weeks to show
+        #[arg(short = 'n', long, default_value = "4")]
+        limit: usize,
+
+        /// Only show a single platform (e.g. 'windows-x86_64')
+        #[arg(short, long)]
+        platform: Option<String>,
+    },
+
+    /// Show when release targets were added and dropped, with their downloads
+    Targets,
+
+    /// Show the crates, platforms, and versions with the largest week-over-week changes
+    Movers {
+        /// Number of gains and of losses to show
+        #[arg(short = 'n', long, default_value = "5")]
+        limit: usize,
+
+        /// Ignore identifiers with fewer downloads than this in both weeks
+        #[arg(long, default_value_t = analysis::DEFAULT_MOVER_MIN_DOWNLOADS)]
+        min_downloads: u64,
+    },
+
+    /// Show downloads summed per project, as grouped in the configuration
+    Projects {
+        /// Number of weeks (or months) to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+
+        /// Show monthly rather than weekly downloads
+        #[arg(long)]
+        monthly: bool,
+    },
+
+    /// Show each source's collection progress: last success, latest data,
+    /// ETag, and consecutive failures
+    State,
+
+    /// Show recent collect runs and what filters left out of each source
+    Runs {
+        /// Number of runs to show
+        #[arg(short = 'n', long, default_value = "10")]
+        limit: usize,
+    },
+
+    /// Show the current crates.io owners of each crate and the changes
+    /// recorded over time
+    Owners {
+        /// Only show this crate
+        #[arg(long = "crate")]
+        crate_name: Option<String>,
+    },
+
+    /// Show release assets and crate versions that were deleted, yanked, or
+    /// restored
+    Tombstones {
+        /// Source to query: 'github', 'crates', or 'all'
+        #[arg(short, long, default_value = "all")]
+        source: String,
+    },
+
+    /// Show the derived metrics defined in the configuration, or the weekly
+    /// values of one of them
+    Metrics {
+        /// The metric to show weekly values for
+        name: Option<String>,
+
+        /// Number of weeks to show
+        #[arg(short = 'n', long, default_value = "12")]
+        limit: usize,
+    },
+
+    /// Show the headline total downloads figure and how it's made up
+    Headline {
+        /// Print the figure and its breakdown as JSON
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Show the frequent snapshots of a release taken by `collect --releases-only`
+    ReleaseSnapshots {
+        /// The release tag [default: the most recently snapshotted release]
+        tag: Option<String>,
+
+        /// Number of snapshots to show
+        #[arg(short = 'n', long, default_value = "24")]
+        limit: usize,
+    },
+
     /// Show latest statistics
     Latest,
+
+    /// Run a read-only SQL statement against the database
+    Sql {
+        /// The statement to run, such as a SELECT
+        #[arg(required_unless_present = "list_tables")]
+        statement: Option<String>,
+
+        /// List the tables and views with their columns instead
+        #[arg(long, conflicts_with = "statement")]
+        list_tables: bool,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum AnalyzeType {
+    /// Flag weeks where downloads spike or drop relative to the trailing average
+    Anomalies {
+        /// Number of previous weeks to compare each week against
+        #[arg(short, long, default_value_t = analysis::DEFAULT_ANOMALY_WINDOW)]
+        window: usize,
+
+        /// Z-score (standard deviations from the mean) at which a week is flagged
+        #[arg(short = 'z', long, default_value_t = analysis::DEFAULT_ANOMALY_THRESHOLD)]
+        threshold: f64,
+
+        /// Only check the most recent N complete weeks
+        #[arg(long)]
+        recent: Option<usize>,
+
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Exit with an error if any anomalies are found
+        #[arg(long)]
+        fail_on_anomaly: bool,
+    },
+
+    /// Check the alert rules in the configuration against the latest
+    /// complete week
+    Alerts {
+        /// Print machine-readable JSON
+        #[arg(long)]
+        json: bool,
+
+        /// Exit with an error if any alert fires
+        #[arg(long)]
+        fail_on_alert: bool,
+    },
+}
+
+#[derive(Parser, Debug)]
+enum ReportType {
+    /// Summarize what changed between two public snapshots
+    Diff {
+        /// Directory containing the new snapshot
+        #[arg(default_value = "public")]
+        current: Utf8PathBuf,
+
+        /// Directory containing the previously published snapshot
+        #[arg(long)]
+        against: Utf8PathBuf,
+
+        /// Maximum number of recomputed or removed rows to show per table
+        #[arg(short = 'n', long, default_value = "10")]
+        limit: usize,
+    },
+
+    /// Write a self-contained HTML page with the latest statistics and charts
+    Html {
+        /// Output file path
+        #[arg(short, long, default_value = "report/index.html")]
+        output: Utf8PathBuf,
+
+        /// Title of the page
+        #[arg(long, default_value = "Download statistics")]
+        title: String,
+
+        /// Render the page from this template instead of the built-in one
+        #[arg(long)]
+        template: Option<Utf8PathBuf>,
+
+        /// Embed this chart, such as 'weekly-trends', instead of the default
+        /// set (can be repeated)
+        #[arg(long, value_name = "CHART")]
+        chart: Vec<config::ChartKind>,
+
+        /// Leave out charts
+        #[arg(long, conflicts_with = "chart")]
+        no_charts: bool,
+
+        /// Color theme of the charts: 'light' or 'dark'
+        #[arg(long, default_value = "light")]
+        theme: config::ChartTheme,
+    },
+
+    /// Print a short Markdown digest of the latest complete week
+    Markdown {
+        /// Number of previous weeks to compare the week against for anomalies
+        #[arg(short, long, default_value_t = analysis::DEFAULT_ANOMALY_WINDOW)]
+        window: usize,
+
+        /// Z-score (standard deviations from the mean) at which the week is
+        /// flagged as an anomaly
+        #[arg(short = 'z', long, default_value_t = analysis::DEFAULT_ANOMALY_THRESHOLD)]
+        threshold: f64,
+
+        /// Append the digest to the GitHub Actions job summary
+        /// ($GITHUB_STEP_SUMMARY) instead of printing it
+        #[arg(long)]
+        github_step_summary: bool,
+    },
 }
 
 #[derive(Parser, Debug)]
 enum ExportType {
     /// Export to CSV format
     Csv {
-        /// Output file path
+        /// Output file path, or '-' for standard output
         #[arg(short, long)]
         output: Utf8PathBuf,
 
-        /// What to export: 'weekly', 'daily', 'all'
+        /// What to export: 'weekly', 'daily', 'github', or 'github-daily'
         #[arg(short = 't', long, default_value = "weekly")]
         table: String,
+
+        /// Only export rows dated on or after this date (YYYY-MM-DD); weekly
+        /// rows are dated by the start of their week
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// Only export rows dated on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+
+        /// Only export rows from this source: 'github', 'crates', or
+        /// 'installer'
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// Only export downloads of this crate
+        #[arg(long = "crate", value_name = "NAME")]
+        crate_name: Option<String>,
+
+        /// Compress the output: 'gzip', 'zstd' or 'none'
+        #[arg(long, default_value = "none")]
+        compress: query::Compress,
     },
 
     /// Export to JSON format
     Json {
-        /// Output file path
+        /// Output file path, or '-' for standard output
         #[arg(short, long)]
         output: Utf8PathBuf,
 
-        /// What to export: 'weekly', 'daily', 'all'
+        /// What to export: 'weekly', 'daily', 'github', or 'github-daily'
         #[arg(short = 't', long, default_value = "weekly")]
         table: String,
+
+        /// Only export rows dated on or after this date (YYYY-MM-DD); weekly
+        /// rows are dated by the start of their week
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// Only export rows dated on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+
+        /// Only export rows from this source: 'github', 'crates', or
+        /// 'installer'
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// Only export downloads of this crate
+        #[arg(long = "crate", value_name = "NAME")]
+        crate_name: Option<String>,
+
+        /// Compress the output: 'gzip', 'zstd' or 'none'
+        #[arg(long, default_value = "none")]
+        compress: query::Compress,
+    },
+
+    /// Export each series to a file of its own, with a manifest listing them
+    Split {
+        /// Output directory
+        #[arg(short, long)]
+        output: Utf8PathBuf,
+
+        /// What each file holds: 'identifier' (a crate, release, or install
+        /// endpoint), 'platform', or 'version'
+        #[arg(long, default_value = "identifier")]
+        by: split::SplitBy,
+
+        /// Format of each file: 'csv' or 'json'
+        #[arg(long, default_value = "csv")]
+        format: split::SplitFormat,
+
+        /// Only include weeks starting on or after this date (YYYY-MM-DD)
+        #[arg(long)]
+        since: Option<NaiveDate>,
+
+        /// Only include weeks starting on or before this date (YYYY-MM-DD)
+        #[arg(long)]
+        until: Option<NaiveDate>,
+    },
+
+    /// Export a sanitized public snapshot using the configured allowlist
+    Public {
+        /// Output directory
+        #[arg(short, long)]
+        output: Utf8PathBuf,
+
+        /// Upload the snapshot to object storage (s3://bucket/prefix)
+        #[arg(long, value_name = "S3_URL")]
+        upload: Option<S3Location>,
+    },
+
+    /// Export versioned JSON files for the website: weekly downloads,
+    /// totals, and platform shares
+    Website {
+        /// Output directory
+        #[arg(short, long)]
+        output: Utf8PathBuf,
     },
 }
 
+impl Command {
+    /// Whether the command can't run without a configuration file.
+    ///
+    /// Other commands only use the configuration for formatting, and fall back
+    /// to the defaults if the file doesn't exist.
+    fn requires_config(&self) -> bool {
+        matches!(
+            self,
+            Command::Collect { .. }
+                | Command::Refresh { .. }
+                | Command::Backfill { .. }
+                | Command::Export {
+                    export_type: ExportType::Public { .. }
+                }
+        )
+    }
+
+    /// Whether the command ends with a summary, which is printed as JSON in
+    /// quiet mode.
+    fn has_summary(&self) -> bool {
+        matches!(
+            self,
+            Command::Collect {
+                releases_only: false,
+                ..
+            } | Command::Refresh { .. }
+        )
+    }
+}
+
 /// Parse arguments and dispatch to the appropriate command.
 pub async fn dispatch() -> Result<()> {
     let args = Args::parse();
 
+    // Sources given on the command line stand in for a configuration file.
+    let requires_config = args.command.requires_config() && args.source_specs.is_empty();
+
+    // On a first run, with nothing set up yet, offer to set up instead of
+    // failing for lack of a configuration file.
+    let first_run = requires_config
+        && !args.quiet
+        && args.config != "-"
+        && !args.config.exists()
+        && !args.database.exists()
+        && io::stdin().is_terminal();
+    if let Command::Init { yes, skip_collect } = args.command {
+        let options = onboard::InitOptions { yes, skip_collect };
+        return onboard::run_init(&args.config, &args.database, &options).await;
+    } else if first_run {
+        println!(
+            "Neither {} nor {} exists yet, so this looks like a first run.",
+            args.config, args.database
+        );
+        return onboard::run_init(&args.config, &args.database, &Default::default()).await;
+    }
+    let mut config = if requires_config || args.config == "-" || args.config.exists() {
+        config::Config::load(&args.config).context("failed to load configuration")?
+    } else {
+        config::Config::default()
+    };
+    if !args.source_specs.is_empty() {
+        config.source = args.source_specs.clone();
+    }
+    format::set_policies(config.formatting.clone());
+    retry::set_policy(config.retry.clone());
+    http::set_config(&config.http)?;
+    if let Command::Collect {
+        fixtures: Some(dir),
+        ..
+    } = &args.command
+    {
+        http::set_transport(Box::new(Fixtures::new(dir)?));
+    }
+    output::set_mode(match (args.quiet, args.command.has_summary()) {
+        (false, _) => output::Mode::Narrate,
+        (true, false) => output::Mode::Paths,
+        (true, true) => output::Mode::Summary,
+    });
+
     match args.command {
+        Command::Collect {
+            releases_only: true,
+            ..
+        } => {
+            commands::run_collect_releases(&args.database, &config).await?;
+        }
         Command::Collect {
             skip_github,
             skip_crates,
+            skip_installer,
             skip_aggregation,
+            only,
+            time_budget,
+            deadline,
+            parallelism,
+            strict,
+            daemon,
+            schedule,
+            fail_on_alert,
+            releases_only: false,
+            as_of,
+            offline: _,
+            fixtures: _,
         } => {
-            let config =
-                config::Config::load(&args.config).context("failed to load configuration")?;
-            commands::run_collect(
-                &args.database,
-                &config,
+            let options = commands::CollectOptions {
                 skip_github,
                 skip_crates,
+                skip_installer,
                 skip_aggregation,
-            )
-            .await?;
+                time_budget,
+                parallelism,
+                only,
+                strict,
+                deadline,
+                as_of,
+            };
+            if daemon {
+                let mut charts = config.chart_outputs(&[])?;
+                if charts.is_empty() {
+                    charts.push(config::ChartOutputConfig::default_at("charts".into()));
+                }
+                let daemon_options = daemon::DaemonOptions {
+                    schedule: match schedule {
+                        Some(schedule) => schedule,
+                        None => daemon::DEFAULT_SCHEDULE.parse()?,
+                    },
+                    charts,
+                };
+                daemon::run_daemon(&args.database, &config, &options, &daemon_options).await?;
+            } else {
+                let summary = commands::run_collect(&args.database, &config, &options).await?;
+                output::summary(&summary)?;
+                if fail_on_alert && !summary.alerts.is_empty() {
+                    let names: Vec<_> = summary
+                        .alerts
+                        .iter()
+                        .map(|alert| alert.name.as_str())
+                        .collect();
+                    bail!("alerts fired: {}", names.join(", "));
+                }
+            }
         }
-        Command::Charts { output } => {
-            commands::run_charts(&args.database, &output)?;
+        Command::Refresh {
+            skip_collect,
+            skip_github,
+            skip_crates,
+            skip_installer,
+            skip_aggregation,
+            skip_charts,
+            skip_report,
+            skip_publish,
+            time_budget,
+            deadline,
+            parallelism,
+            strict,
+            charts_output,
+            charts_profile,
+            charts_theme,
+            report_output,
+            public_output,
+            upload,
+        } => {
+            let mut charts = match charts_profile {
+                Some(name) => config.chart_outputs(&[name])?.remove(0),
+                None => config::ChartOutputConfig::default_at(
+                    charts_output.unwrap_or_else(|| "charts".into()),
+                ),
+            };
+            if let Some(theme) = charts_theme {
+                charts.theme = theme;
+            }
+            let report = page::PageOptions {
+                output: report_output,
+                title: "Download statistics".to_string(),
+                template: None,
+                charts: page::DEFAULT_CHARTS.to_vec(),
+                theme: charts.theme,
+            };
+            let options = commands::RefreshOptions {
+                skip_collect,
+                skip_github,
+                skip_crates,
+                skip_installer,
+                skip_aggregation,
+                skip_charts,
+                skip_report,
+                skip_publish,
+                time_budget,
+                deadline,
+                parallelism,
+                strict,
+                charts,
+                report,
+                public_output,
+                upload,
+            };
+            let summary = commands::run_refresh(&args.database, &config, &options).await?;
+            output::summary(&summary)?;
         }
-        Command::Query { query_type } => {
-            let conn = db::init_db(&args.database).context("failed to open database")?;
+        Command::Reattribute {
+            dry_run,
+            skip_aggregation,
+        } => {
+            commands::run_reattribute(&args.database, &config, dry_run, skip_aggregation)?;
+        }
+        Command::Archive {
+            older_than,
+            dry_run,
+        } => {
+            commands::run_archive(&args.database, older_than, dry_run)?;
+        }
+        Command::Db {
+            db_type:
+                DbType::Amend {
+                    source,
+                    date,
+                    delete: _,
+                    set,
+                    release,
+                    asset,
+                    version,
+                    dry_run,
+                    skip_aggregation,
+                },
+        } => {
+            let amendment = amend::Amendment {
+                source,
+                date,
+                release,
+                asset,
+                version,
+                change: match set {
+                    Some(count) => amend::Change::Set(count),
+                    None => amend::Change::Delete,
+                },
+            };
+            commands::run_amend(
+                &args.database,
+                &config,
+                &amendment,
+                dry_run,
+                skip_aggregation,
+            )?;
+        }
+        Command::Charts {
+            output,
+            profile,
+            theme,
+            since,
+            until,
+            resolution,
+            only,
+            size,
+            scale,
+            moving_average,
+            log_scale,
+            data,
+            upload,
+        } => {
+            config.charts.moving_average |= moving_average;
+            config.charts.log_scale |= log_scale;
+            let mut outputs = match output {
+                Some(path) => vec![config::ChartOutputConfig::default_at(path)],
+                None => config.chart_outputs(&profile)?,
+            };
+            if outputs.is_empty() {
+                outputs.push(config::ChartOutputConfig::default_at("charts".into()));
+            }
+            for output in &mut outputs {
+                if let Some(theme) = theme {
+                    output.theme = theme;
+                }
+                if since.is_some() {
+                    output.since = since;
+                }
+                if until.is_some() {
+                    output.until = until;
+                }
+                if let Some(resolution) = resolution {
+                    output.resolution = resolution;
+                }
+                if !only.is_empty() {
+                    output.charts = only.clone();
+                    output.metrics = Some(Vec::new());
+                    output.custom = Some(Vec::new());
+                }
+                if size.is_some() {
+                    output.size = size;
+                }
+                if let Some(scale) = scale {
+                    output.scale = scale;
+                }
+                output.data |= data;
+            }
+            if upload.is_some() && outputs.len() > 1 {
+                bail!(
+                    "--upload requires a single chart output, but {} are configured \
+                     (select one with --profile)",
+                    outputs.len()
+                );
+            }
+            commands::run_charts(&args.database, &config, &outputs)?;
+            if let Some(location) = upload {
+                upload::upload_dir(&outputs[0].path, &location).await?;
+            }
+        }
+        Command::Backfill { backfill_type } => match backfill_type {
+            BackfillType::Wayback {
+                before,
+                dry_run,
+                skip_aggregation,
+            } => {
+                commands::run_backfill_wayback(
+                    &args.database,
+                    &config,
+                    before,
+                    dry_run,
+                    skip_aggregation,
+                )
+                .await?
+            }
+        },
+        Command::Query { format, query_type } => {
+            let conn = if matches!(query_type, QueryType::Sql { .. }) {
+                db::open_read_only(&args.database)?
+            } else {
+                db::init_db(&args.database).context("failed to open database")?
+            };
             let query_kind = match query_type {
-                QueryType::Weekly { limit, source } => query::QueryKind::Weekly { limit, source },
-                QueryType::Total { source } => query::QueryKind::Total { source },
+                QueryType::Weekly {
+                    limit,
+                    source,
+                    platform,
+                    delta,
+                    crate_name,
+                    identifier,
+                    by_crate,
+                    since,
+                    until,
+                } => {
+                    let (source, identifiers) =
+                        select_identifiers(source, crate_name, identifier, by_crate);
+                    query::QueryKind::Weekly {
+                        limit,
+                        source,
+                        platform,
+                        delta,
+                        range: query::DateRange::new(since, until)?,
+                        identifiers,
+                    }
+                }
+                QueryType::Monthly {
+                    limit,
+                    source,
+                    quarterly,
+                    crate_name,
+                    identifier,
+                    by_crate,
+                } => {
+                    let (source, identifiers) =
+                        select_identifiers(source, crate_name, identifier, by_crate);
+                    query::QueryKind::Monthly {
+                        limit,
+                        source,
+                        quarterly,
+                        identifiers,
+                    }
+                }
+                QueryType::Total {
+                    source,
+                    crate_name,
+                    identifier,
+                    by_crate,
+                    since,
+                    until,
+                } => {
+                    let (source, identifiers) =
+                        select_identifiers(source, crate_name, identifier, by_crate);
+                    query::QueryKind::Total {
+                        source,
+                        range: query::DateRange::new(since, until)?,
+                        identifiers,
+                    }
+                }
+                QueryType::Daily {
+                    limit,
+                    source,
+                    crate_name,
+                    by_crate,
+                } => {
+                    let (source, identifiers) =
+                        select_identifiers(source, crate_name, None, by_crate);
+                    query::QueryKind::Daily {
+                        limit,
+                        source,
+                        identifiers,
+                    }
+                }
+                QueryType::Rate { limit, window } => query::QueryKind::Rate { limit, window },
+                QueryType::Diversity { limit, min_share } => {
+                    query::QueryKind::Diversity { limit, min_share }
+                }
+                QueryType::Rolling { limit, source } => query::QueryKind::Rolling { limit, source },
+                QueryType::Growth => query::QueryKind::Growth,
+                QueryType::VersionShare {
+                    limit,
+                    source,
+                    versions,
+                } => query::QueryKind::VersionShare {
+                    limit,
+                    source,
+                    versions,
+                },
+                QueryType::Versions { source, days, top } => {
+                    query::QueryKind::Versions { source, days, top }
+                }
+                QueryType::VersionGrowth {
+                    source,
+                    weeks,
+                    top,
+                    lines,
+                } => query::QueryKind::VersionGrowth {
+                    source,
+                    weeks,
+                    top,
+                    lines,
+                },
+                QueryType::Platforms {
+                    by,
+                    days,
+                    since,
+                    until,
+                } => query::QueryKind::Platforms {
+                    by,
+                    days,
+                    range: query::DateRange::new(since, until)?,
+                },
+                QueryType::Compare {
+                    period,
+                    last,
+                    against,
+                } => {
+                    let period = match (period, last) {
+                        (Some(range), _) => query::ComparePeriod::Dates(range),
+                        (None, Some(last)) => {
+                            let day = 24 * 60 * 60;
+                            if last.as_secs() % day != 0 {
+                                bail!("--last has to be a whole number of days, such as 30d");
+                            }
+                            let days = u32::try_from(last.as_secs() / day)
+                                .ok()
+                                .filter(|days| *days <= query::DateRange::MAX_DAYS)
+                                .with_context(|| {
+                                    format!("--last can be at most {}d", query::DateRange::MAX_DAYS)
+                                })?;
+                            query::ComparePeriod::Last(days)
+                        }
+                        (None, None) => query::ComparePeriod::Last(30),
+                    };
+                    query::QueryKind::Compare { period, against }
+                }
+                QueryType::Stats => query::QueryKind::Stats,
+                QueryType::Gaps {
+                    limit,
+                    max_staleness,
+                } => query::QueryKind::Gaps {
+                    limit,
+                    max_staleness,
+                },
+                QueryType::Channel { limit, source } => query::QueryKind::Channel { limit, source },
+                QueryType::Verification { limit } => query::QueryKind::Verification { limit },
+                QueryType::Formats { limit, platform } => {
+                    query::QueryKind::Formats { limit, platform }
+                }
+                QueryType::Targets => query::QueryKind::Targets,
+                QueryType::Movers {
+                    limit,
+                    min_downloads,
+                } => query::QueryKind::Movers {
+                    limit,
+                    min_downloads,
+                },
+                QueryType::Projects { limit, monthly } => {
+                    query::QueryKind::Projects { limit, monthly }
+                }
+                QueryType::State => query::QueryKind::State,
+                QueryType::Runs { limit } => query::QueryKind::Runs { limit },
+                QueryType::Owners { crate_name } => query::QueryKind::Owners { crate_name },
+                QueryType::Tombstones { source } => query::QueryKind::Tombstones { source },
+                QueryType::Metrics { name, limit } => query::QueryKind::Metrics {
+                    metrics: metrics::Metrics::new(&config.metrics)?,
+                    name,
+                    limit,
+                },
+                QueryType::Headline { json } => query::QueryKind::Headline {
+                    headline: config.headline.clone(),
+                    json,
+                },
+                QueryType::ReleaseSnapshots { tag, limit } => {
+                    query::QueryKind::ReleaseSnapshots { tag, limit }
+                }
                 QueryType::Latest => query::QueryKind::Latest,
+                QueryType::Sql {
+                    statement: Some(statement),
+                    ..
+                } => query::QueryKind::Sql { statement },
+                QueryType::Sql {
+                    statement: None, ..
+                } => query::QueryKind::Tables,
             };
-            query::run_query(&conn, query_kind)?;
+            query::run_query(&conn, query_kind, format)?;
+        }
+        Command::Analyze { analyze_type } => {
+            let conn = db::init_db(&args.database).context("failed to open database")?;
+            let analyze_kind = match analyze_type {
+                AnalyzeType::Anomalies {
+                    window,
+                    threshold,
+                    recent,
+                    json,
+                    fail_on_anomaly,
+                } => query::AnalyzeKind::Anomalies {
+                    window,
+                    threshold,
+                    recent,
+                    json,
+                    fail_on_anomaly,
+                },
+                AnalyzeType::Alerts {
+                    json,
+                    fail_on_alert,
+                } => query::AnalyzeKind::Alerts {
+                    rules: config.alert.clone(),
+                    json,
+                    fail_on_alert,
+                },
+            };
+            query::run_analyze(&conn, analyze_kind)?;
         }
         Command::Export { export_type } => {
             let conn = db::init_db(&args.database).context("failed to open database")?;
+            let provenance = Provenance::collect(&conn, &config)?;
             let export_kind = match export_type {
-                ExportType::Csv { output, table } => query::ExportKind::Csv {
+                ExportType::Public { output, upload } => {
+                    publish::export_public(&conn, &config.public_export, &provenance, &output)?;
+                    if let Some(location) = upload {
+                        upload::upload_dir(&output, &location).await?;
+                    }
+                    return Ok(());
+                }
+                ExportType::Website { output } => {
+                    website::export_website(&conn, &config.headline, &provenance, &output)?;
+                    return Ok(());
+                }
+                ExportType::Split {
+                    output,
+                    by,
+                    format,
+                    since,
+                    until,
+                } => {
+                    let range = query::DateRange::new(since, until)?;
+                    split::export_split(&conn, by, format, range, &provenance, &output)?;
+                    return Ok(());
+                }
+                ExportType::Csv {
+                    output,
+                    table,
+                    since,
+                    until,
+                    source,
+                    crate_name,
+                    compress,
+                } => query::ExportKind::Csv {
                     output: output.to_string(),
                     table,
+                    filter: query::ExportFilter {
+                        range: query::DateRange::new(since, until)?,
+                        source,
+                        crate_name,
+                    },
+                    compress,
                 },
-                ExportType::Json { output, table } => query::ExportKind::Json {
+                ExportType::Json {
+                    output,
+                    table,
+                    since,
+                    until,
+                    source,
+                    crate_name,
+                    compress,
+                } => query::ExportKind::Json {
                     output: output.to_string(),
                     table,
+                    filter: query::ExportFilter {
+                        range: query::DateRange::new(since, until)?,
+                        source,
+                        crate_name,
+                    },
+                    compress,
                 },
             };
-            query::run_export(&conn, export_kind)?;
+            query::run_export(&conn, export_kind, &provenance)?;
+        }
+        Command::Report { report_type } => match report_type {
+            ReportType::Diff {
+                current,
+                against,
+                limit,
+            } => report::run_diff(&current, &against, limit)?,
+            ReportType::Html {
+                output,
+                title,
+                template,
+                chart,
+                no_charts,
+                theme,
+            } => {
+                let conn = db::init_db(&args.database).context("failed to open database")?;
+                let charts = match (no_charts, chart.is_empty()) {
+                    (true, _) => Vec::new(),
+                    (false, true) => page::DEFAULT_CHARTS.to_vec(),
+                    (false, false) => chart,
+                };
+                let options = page::PageOptions {
+                    output,
+                    title,
+                    template,
+                    charts,
+                    theme,
+                };
+                page::run_html(&conn, &config, &options)?;
+            }
+            ReportType::Markdown {
+                window,
+                threshold,
+                github_step_summary,
+            } => {
+                let conn = db::init_db(&args.database).context("failed to open database")?;
+                let digest = digest::weekly_digest(&conn, window, threshold)?;
+                if github_step_summary {
+                    digest::write_step_summary(&digest)?;
+                } else {
+                    print!("{}", digest);
+                }
+            }
+        },
+        Command::Tui => {
+            let conn = db::init_db(&args.database).context("failed to open database")?;
+            tui::run(&conn)?;
         }
+        Command::Init { .. } => unreachable!("init is run before the configuration is loaded"),
     }
 
     Ok(())
 }
+
+/// The source and identifiers selected by `--source`, `--crate`,
+/// `--identifier`, and `--by-crate`. Selecting crates implies the crates.io
+/// source.
+fn select_identifiers(
+    source: String,
+    crate_name: Option<String>,
+    identifier: Option<String>,
+    by_crate: bool,
+) -> (String, query::Identifiers) {
+    let source = if crate_name.is_some() || by_crate {
+        "crates".to_string()
+    } else {
+        source
+    };
+    let identifiers = query::Identifiers {
+        only: crate_name.or(identifier),
+        breakdown: by_crate,
+    };
+    (source, identifiers)
+}
+
+/// Parse a duration such as `90s`, `5m`, `1h30m`, or `2d`.
+fn parse_duration(s: &str) -> Result<Duration> {
+    let mut total = 0u64;
+    let mut digits = String::new();
+    for c in s.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 60 * 60,
+            'd' => 24 * 60 * 60,
+            _ => anyhow::bail!("invalid duration '{}': unknown unit '{}'", s, c),
+        };
+        let value: u64 = digits.parse().with_context(|| {
+            format!("invalid duration '{}': expected a number before '{}'", s, c)
+        })?;
+        total += value * unit;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        anyhow::bail!("invalid duration '{}': missing unit (s, m, h, or d)", s);
+    }
+    if total == 0 {
+        anyhow::bail!("invalid duration '{}': must be greater than zero", s);
+    }
+    Ok(Duration::from_secs(total))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("90s").unwrap(), Duration::from_secs(90));
+        assert_eq!(parse_duration("5m").unwrap(), Duration::from_secs(300));
+        assert_eq!(parse_duration("1h30m").unwrap(), Duration::from_secs(5400));
+        assert_eq!(parse_duration("2d").unwrap(), Duration::from_secs(172_800));
+
+        for bad in ["", "5", "m", "5x", "0s"] {
+            assert!(parse_duration(bad).is_err(), "for {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_chart_scale() {
+        let scale = |value: &str| {
+            Args::try_parse_from(["download-stats", "charts", "--scale", value]).map(|args| {
+                match args.command {
+                    Command::Charts { scale, .. } => scale,
+                    _ => panic!("expected the charts command"),
+                }
+            })
+        };
+        assert_eq!(scale("1").unwrap(), Some(1));
+        assert_eq!(scale("8").unwrap(), Some(8));
+        for bad in ["0", "9", "4294967295", "-1"] {
+            assert!(scale(bad).is_err(), "for {:?}", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_chart_theme() {
+        let theme = |value: &str| {
+            Args::try_parse_from(["download-stats", "charts", "--theme", value]).map(|args| {
+                match args.command {
+                    Command::Charts { theme, .. } => theme,
+                    _ => panic!("expected the charts command"),
+                }
+            })
+        };
+        assert_eq!(theme("dark").unwrap(), Some(config::ChartTheme::Dark));
+        let err = theme("sepia").unwrap_err().to_string();
+        assert!(
+            err.contains("unknown chart theme 'sepia' (expected 'light' or 'dark')"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_parse_chart_range() {
+        let args = Args::try_parse_from(["download-stats", "charts", "--since", "90d"]).unwrap();
+        let Command::Charts { since, until, .. } = args.command else {
+            panic!("expected the charts command");
+        };
+        assert_eq!(since, Some(config::ChartDate::DaysAgo(90)));
+        assert_eq!(until, None);
+
+        let args = Args::try_parse_from([
+            "download-stats",
+            "charts",
+            "--since",
+            "2025-01-01",
+            "--until",
+            "7d",
+        ])
+        .unwrap();
+        let Command::Charts { since, until, .. } = args.command else {
+            panic!("expected the charts command");
+        };
+        assert_eq!(
+            since,
+            Some(config::ChartDate::Date(
+                chrono::NaiveDate::from_ymd_opt(2025, 1, 1).unwrap()
+            ))
+        );
+        assert_eq!(until, Some(config::ChartDate::DaysAgo(7)));
+
+        for bad in ["90", "9999999999d", "18446744073709551615d"] {
+            assert!(
+                Args::try_parse_from(["download-stats", "charts", "--since", bad]).is_err(),
+                "for {:?}",
+                bad
+            );
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Responses read from files rather than the network.
+//!
+//! `collect --offline --fixtures DIR` answers every request from files in
+//! `DIR`, so collection can be run without network access or tokens, with the
+//! same results each time. A request's fixture is named after its URL: the
+//! host, then the path, then `@` and the query string if there is one, and
+//! `.json`. Requests other than GETs add their method, and requests with a
+//! body add the start of its SHA-256 hash, as in
+//! `api.github.com/graphql.POST-1a2b3c4d5e6f.json`.
+//!
+//! The fixture holds the response body. The response's status and headers,
+//! if they matter, go in a file of the same name ending in `.headers`
+//! instead of `.json`, with a `Name: value` header on each line, and a
+//! `Status:` line for statuses other than 200. Requests without a fixture
+//! get a 404 response naming the file that was looked for.
+
+use crate::http::Transport;
+use anyhow::{Context, Result, bail};
+use camino::{Utf8Path, Utf8PathBuf};
+use futures_util::future::BoxFuture;
+use reqwest::{Method, Request, Response, StatusCode};
+use sha2::{Digest, Sha256};
+use std::{fs, io};
+
+/// A directory of fixture files.
+#[derive(Debug)]
+pub struct Fixtures {
+    dir: Utf8PathBuf,
+}
+
+impl Fixtures {
+    pub fn new(dir: &Utf8Path) -> Result<Self> {
+        if !dir.is_dir() {
+            bail!("fixtures directory {} doesn't exist", dir);
+        }
+        Ok(Self {
+            dir: dir.to_owned(),
+        })
+    }
+
+    /// The fixture file for a request.
+    pub fn path(&self, method: &Method, url: &reqwest::Url, body: Option<&[u8]>) -> Utf8PathBuf {
+        let mut path = self.dir.join(match (url.host_str(), url.port()) {
+            (Some(host), Some(port)) => format!("{}_{}", host, port),
+            (Some(host), None) => host.to_string(),
+            (None, _) => "localhost".to_string(),
+        });
+        let mut segments: Vec<&str> = url
+            .path_segments()
+            .into_iter()
+            .flatten()
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let mut name = segments.pop().unwrap_or("index").to_string();
+        for segment in segments {
+            path.push(segment);
+        }
+        if let Some(query) = url.query() {
+            name = format!("{}@{}", name, query);
+        }
+        if method != Method::GET {
+            name = format!("{}.{}", name, method);
+        }
+        if let Some(body) = body.filter(|body| !body.is_empty()) {
+            let hash = hex::encode(Sha256::digest(body));
+            name = format!("{}-{}", name, &hash[..12]);
+        }
+        path.join(format!("{}.json", name))
+    }
+
+    fn respond(&self, request: &Request) -> Response {
+        let body = request.body().and_then(|body| body.as_bytes());
+        let path = self.path(request.method(), request.url(), body);
+        let response = read_fixture(&path).and_then(|fixture| {
+            fixture.with_context(|| {
+                format!(
+                    "no fixture for {} {} (expected {})",
+                    request.method(),
+                    request.url(),
+                    path
+                )
+            })
+        });
+        match response {
+            Ok(response) => response.into(),
+            Err(err) => http::Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(format!("{:#}", err))
+                .expect("a response with only a status and body is valid")
+                .into(),
+        }
+    }
+}
+
+impl Transport for Fixtures {
+    fn execute(&self, request: Request) -> BoxFuture<'_, reqwest::Result<Response>> {
+        let response = self.respond(&request);
+        Box::pin(async move { Ok(response) })
+    }
+}
+
+/// Read the fixture at `path`, and its headers, if it exists.
+fn read_fixture(path: &Utf8Path) -> Result<Option<http::Response<Vec<u8>>>> {
+    let headers_path = path.with_extension("headers");
+    let body = read_if_exists(path)?;
+    let headers = read_if_exists(&headers_path)?;
+    if body.is_none() && headers.is_none() {
+        return Ok(None);
+    }
+
+    let mut response = http::Response::builder();
+    let headers = String::from_utf8(headers.unwrap_or_default())
+        .with_context(|| format!("fixture headers in {} aren't UTF-8", headers_path))?;
+    for line in headers.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let Some((name, value)) = line.split_once(':') else {
+            bail!(
+                "expected 'Name: value' in {}, found '{}'",
+                headers_path,
+                line
+            );
+        };
+        let value = value.trim();
+        if name.eq_ignore_ascii_case("status") {
+            let status = value.split_whitespace().next().unwrap_or_default();
+            let status: StatusCode = status
+                .parse()
+                .with_context(|| format!("invalid status '{}' in {}", value, headers_path))?;
+            response = response.status(status);
+        } else {
+            response = response.header(name.trim(), value);
+        }
+    }
+    let response = response
+        .body(body.unwrap_or_default())
+        .with_context(|| format!("invalid fixture headers in {}", headers_path))?;
+    Ok(Some(response))
+}
+
+fn read_if_exists(path: &Utf8Path) -> Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(contents) => Ok(Some(contents)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err).with_context(|| format!("failed to read fixture {}", path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fixture_path() {
+        let fixtures = Fixtures {
+            dir: "fixtures".into(),
+        };
+        let url = |s: &str| reqwest::Url::parse(s).unwrap();
+        assert_eq!(
+            fixtures.path(
+                &Method::GET,
+                &url(
+                    "https://api.github.com/repos/nextest-rs/nextest/releases?per_page=100&page=1"
+                ),
+                None
+            ),
+            "fixtures/api.github.com/repos/nextest-rs/nextest/releases@per_page=100&page=1.json"
+        );
+        assert_eq!(
+            fixtures.path(&Method::GET, &url("http://127.0.0.1:8769/"), None),
+            "fixtures/127.0.0.1_8769/index.json"
+        );
+        assert_eq!(
+            fixtures.path(
+                &Method::POST,
+                &url("https://api.github.com/graphql"),
+                Some(b"{}")
+            ),
+            "fixtures/api.github.com/graphql.POST-44136fa355b3.json"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fixtures() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-fixtures-{}", std::process::id()));
+        let crates = dir.join("crates.io/api/v1/crates");
+        fs::create_dir_all(&crates).unwrap();
+        fs::write(crates.join("cargo-nextest.json"), r#"{"ok": true}"#).unwrap();
+        fs::write(
+            crates.join("cargo-nextest.headers"),
+            "# Sent with conditional requests\nStatus: 304 Not Modified\nETag: \"abc\"\n",
+        )
+        .unwrap();
+        fs::write(crates.join("bad.headers"), "Status: teapot\n").unwrap();
+        let fixtures = Fixtures::new(&dir).unwrap();
+        let get = |path: &str| {
+            let url = format!("https://crates.io/api/v1/crates/{}", path);
+            Request::new(Method::GET, reqwest::Url::parse(&url).unwrap())
+        };
+
+        let response = fixtures.execute(get("cargo-nextest")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()["etag"], "\"abc\"");
+        assert_eq!(response.text().await.unwrap(), r#"{"ok": true}"#);
+
+        let response = fixtures.execute(get("cargo-nextest/owners")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(
+            response.text().await.unwrap(),
+            format!(
+                "no fixture for GET https://crates.io/api/v1/crates/cargo-nextest/owners \
+                 (expected {}/cargo-nextest/owners.json)",
+                crates
+            )
+        );
+        let response = fixtures.execute(get("bad")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert!(
+            response
+                .text()
+                .await
+                .unwrap()
+                .starts_with("invalid status 'teapot'")
+        );
+
+        assert!(Fixtures::new(&dir.join("missing")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Number formatting shared by every kind of output.
+//!
+//! Each output target has its own [`NumberFormat`] policy, configured in the
+//! `[formatting]` section of `config.toml`. The policies are installed once at
+//! startup with [`set_policies`]; until then the defaults apply.
+
+use crate::config::{FormattingConfig, NumberFormat, NumberStyle};
+use std::sync::OnceLock;
+
+static POLICIES: OnceLock<FormattingConfig> = OnceLock::new();
+
+/// Where a formatted number ends up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Terminal,
+    Charts,
+    Notifications,
+    Reports,
+}
+
+/// Install the formatting policies for this process.
+///
+/// Only the first call has an effect.
+pub fn set_policies(config: FormattingConfig) {
+    _ = POLICIES.set(config);
+}
+
+fn policy(target: Target) -> &'static NumberFormat {
+    let config = POLICIES.get_or_init(FormattingConfig::default);
+    match target {
+        Target::Terminal => &config.terminal,
+        Target::Charts => &config.charts,
+        Target::Notifications => &config.notifications,
+        Target::Reports => &config.reports,
+    }
+}
+
+/// Format a number for terminal output.
+pub fn number(n: u64) -> String {
+    number_for(Target::Terminal, n)
+}
+
+/// Format a number according to the policy for `target`.
+pub fn number_for(target: Target, n: u64) -> String {
+    format_with(policy(target), n)
+}
+
+/// Format a number according to `format`.
+pub fn format_with(format: &NumberFormat, n: u64) -> String {
+    match format.style {
+        NumberStyle::Raw => n.to_string(),
+        NumberStyle::Grouped => group_digits(&n.to_string(), &format.thousands_separator),
+        NumberStyle::Compact => compact(format, n),
+    }
+}
+
+fn group_digits(digits: &str, separator: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            result.push_str(separator);
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn compact(format: &NumberFormat, n: u64) -> String {
+    let rounded = |value: f64| format!("{:.*}", format.precision, value);
+    let mut scaled = n as f64;
+    let mut suffix = None;
+    for candidate in &format.suffixes {
+        // Compare the value as shown, so that rounding carries into the next
+        // unit: 999,950 is 1M rather than 1000K.
+        if rounded(scaled).parse::<f64>().unwrap_or(scaled) < 1000.0 {
+            break;
+        }
+        scaled /= 1000.0;
+        suffix = Some(candidate.as_str());
+    }
+    let Some(suffix) = suffix else {
+        return n.to_string();
+    };
+
+    let mut value = rounded(scaled);
+    // Trailing zeros are dropped.
+    if value.contains('.') {
+        value = value
+            .trim_end_matches('0')
+            .trim_end_matches('.')
+            .to_string();
+    }
+    format!(
+        "{}{}",
+        value.replacen('.', &format.decimal_separator, 1),
+        suffix
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_with() {
+        let grouped = NumberFormat::default();
+        assert_eq!(format_with(&grouped, 0), "0");
+        assert_eq!(format_with(&grouped, 999), "999");
+        assert_eq!(format_with(&grouped, 1000), "1,000");
+        assert_eq!(format_with(&grouped, 1234567), "1,234,567");
+
+        let raw = NumberFormat {
+            style: NumberStyle::Raw,
+            ..NumberFormat::default()
+        };
+        assert_eq!(format_with(&raw, 1234567), "1234567");
+
+        let dotted = NumberFormat {
+            thousands_separator: ".".to_string(),
+            ..NumberFormat::default()
+        };
+        assert_eq!(format_with(&dotted, 1234567), "1.234.567");
+
+        let compact = NumberFormat {
+            style: NumberStyle::Compact,
+            ..NumberFormat::default()
+        };
+        assert_eq!(format_with(&compact, 950), "950");
+        assert_eq!(format_with(&compact, 1000), "1K");
+        assert_eq!(format_with(&compact, 1234567), "1.2M");
+        assert_eq!(format_with(&compact, 5_600_000_000), "5.6B");
+        // Rounding carries into the next unit.
+        assert_eq!(format_with(&compact, 999_949), "999.9K");
+        assert_eq!(format_with(&compact, 999_950), "1M");
+        assert_eq!(format_with(&compact, 999_999_999), "1B");
+        let whole = NumberFormat {
+            precision: 0,
+            ..compact.clone()
+        };
+        assert_eq!(format_with(&whole, 999_499), "999K");
+        assert_eq!(format_with(&whole, 999_500), "1M");
+
+        let european = NumberFormat {
+            style: NumberStyle::Compact,
+            decimal_separator: ",".to_string(),
+            precision: 2,
+            suffixes: vec![" k".to_string(), " Mio.".to_string()],
+            ..NumberFormat::default()
+        };
+        assert_eq!(format_with(&european, 1234567), "1,23 Mio.");
+        assert_eq!(format_with(&european, 2_500_000_000), "2500 Mio.");
+        // Past the last suffix, there's nothing to carry into.
+        assert_eq!(format_with(&european, 999_999_999_999), "1000000 Mio.");
+    }
+}
@@ -2,69 +2,709 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 //! GitHub API client for fetching release download statistics.
+//!
+//! Requests go to `api.github.com` unless a source configures a mirror,
+//! gateway, or GitHub Enterprise Server instance with [`GithubApiConfig`],
+//! along with how its token is sent. Several tokens can be configured, in
+//! which case requests use the first one that isn't rate-limited: when
+//! GitHub says a token is out of requests, the request is sent again with the
+//! next one, and the token is passed over, by every source, until its limit
+//! resets. Sources can also authenticate as a GitHub App instead, with
+//! installation tokens from [`crate::github_app`]. When request signing is
+//! configured, each request carries an HMAC-SHA256 signature of
+//! `{timestamp}\n{METHOD}\n{path and query}`, hex-encoded, along with the Unix
+//! timestamp that was signed.
+//!
+//! Releases are listed with the REST API unless `github.api = "graphql"` is
+//! configured, in which case they're queried at `{base URL}/graphql` (or
+//! `/api/graphql` on GitHub Enterprise Server), fetching only the fields
+//! collected.
+//!
+//! Which releases and assets are collected is up to each source's
+//! [`ReleaseFilter`]: by default, all of them.
 
-use anyhow::{Context, Result};
+use crate::{
+    config::{GithubApiConfig, GithubApiKind, GithubAuthStyle, ReleaseFilterConfig},
+    github_app::App,
+    http, progress, retry,
+};
+use anyhow::{Context, Result, bail};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use reqwest::{Method, RequestBuilder, Response, StatusCode, header::HeaderMap};
 use serde::Deserialize;
+use sha2::Sha256;
+use std::{
+    collections::{BTreeMap, HashSet},
+    env, fs,
+    sync::{
+        Mutex,
+        atomic::{AtomicUsize, Ordering},
+    },
+};
 
 const GITHUB_API_BASE: &str = "https://api.github.com";
 
+/// Environment variable holding the token, unless configured otherwise.
+const DEFAULT_TOKEN_ENV: &str = "GITHUB_TOKEN";
+
+/// When the rate limits of tokens that ran out of requests reset, by
+/// `Authorization` header value, shared by every [`Api`].
+static RATE_LIMITED: Mutex<BTreeMap<String, DateTime<Utc>>> = Mutex::new(BTreeMap::new());
+
 #[derive(Debug, Deserialize)]
 pub struct Release {
     pub tag_name: String,
+    /// When the release was published, or `None` for drafts.
+    #[serde(default)]
+    pub published_at: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub prerelease: bool,
     pub assets: Vec<Asset>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Asset {
     pub name: String,
+    #[serde(alias = "downloadCount")]
     pub download_count: u64,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct Issue {
+    pub number: u64,
+    pub title: String,
+    pub html_url: String,
+}
+
+/// An installation of a GitHub App.
+#[derive(Debug, Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+/// A GitHub API endpoint with its credentials resolved from the environment.
+#[derive(Debug)]
+pub struct Api {
+    base_url: String,
+    graphql_url: String,
+    token_env: String,
+    /// The `Authorization` header value for each token, in the order
+    /// they're used.
+    tokens: Vec<String>,
+    /// The index of the token the last request was built with.
+    last_used: AtomicUsize,
+    /// The GitHub App to authenticate as, in place of tokens.
+    app: Option<App>,
+    headers: Vec<(String, String)>,
+    signing: Option<Signing>,
+    /// The API releases are listed with.
+    kind: GithubApiKind,
+}
+
+#[derive(Debug)]
+struct Signing {
+    header: String,
+    secret: String,
+}
+
+impl Api {
+    /// Resolve an API configuration, reading secrets from the environment.
+    pub fn new(config: &GithubApiConfig) -> Result<Self> {
+        let token_env = config.token_env.as_deref().unwrap_or(DEFAULT_TOKEN_ENV);
+        if config.app.is_some() && (!config.token_envs.is_empty() || config.tokens_file.is_some()) {
+            bail!("api.app can't be combined with api.token_envs or api.tokens_file");
+        }
+        let mut tokens = Vec::new();
+        // Apps only use their installation tokens, even where GITHUB_TOKEN is
+        // set, as it is in GitHub Actions.
+        if config.app.is_none()
+            && let Ok(token) = env::var(token_env)
+        {
+            tokens.push(authorization(config.auth, token_env, &token)?);
+        }
+        for var in &config.token_envs {
+            let token =
+                env::var(var).with_context(|| format!("{} must be set for the GitHub API", var))?;
+            tokens.push(authorization(config.auth, var, &token)?);
+        }
+        if let Some(path) = &config.tokens_file {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("failed to read GitHub tokens from {}", path))?;
+            for line in content.lines().map(str::trim) {
+                if !line.is_empty() && !line.starts_with('#') {
+                    tokens.push(authorization(config.auth, path.as_str(), line)?);
+                }
+            }
+        }
+        let mut seen = HashSet::new();
+        tokens.retain(|token| seen.insert(token.clone()));
+        let headers = config
+            .headers
+            .iter()
+            .map(|(name, value)| {
+                let value = match value.strip_prefix("env:") {
+                    Some(var) => env::var(var).with_context(|| {
+                        format!("{} must be set for the '{}' GitHub API header", var, name)
+                    })?,
+                    None => value.clone(),
+                };
+                Ok((name.clone(), value))
+            })
+            .collect::<Result<_>>()?;
+        let signing = match &config.signing {
+            Some(signing) => Some(Signing {
+                header: signing.header.clone(),
+                secret: env::var(&signing.secret_env).with_context(|| {
+                    format!(
+                        "{} must be set to sign GitHub API requests",
+                        signing.secret_env
+                    )
+                })?,
+            }),
+            None => None,
+        };
+
+        let base_url = config
+            .base_url
+            .as_deref()
+            .unwrap_or(GITHUB_API_BASE)
+            .trim_end_matches('/')
+            .to_string();
+        let graphql_url = match &config.graphql_url {
+            Some(url) => url.clone(),
+            None => default_graphql_url(&base_url),
+        };
+        let app = match &config.app {
+            Some(app) => Some(App::new(app, &base_url)?),
+            None => None,
+        };
+        Ok(Self {
+            base_url,
+            graphql_url,
+            token_env: token_env.to_string(),
+            tokens,
+            last_used: AtomicUsize::new(0),
+            app,
+            headers,
+            signing,
+            kind: GithubApiKind::default(),
+        })
+    }
+
+    /// List releases with the API of `kind`.
+    pub fn with_kind(self, kind: GithubApiKind) -> Self {
+        Self { kind, ..self }
+    }
+
+    /// Build a request for a path such as `/repos/owner/repo/releases`, with
+    /// the standard, configured, and signature headers.
+    fn request(
+        &self,
+        client: &reqwest::Client,
+        method: Method,
+        path: &str,
+        query: &[(&str, &str)],
+    ) -> Result<reqwest::RequestBuilder> {
+        let mut url = reqwest::Url::parse(&format!("{}{}", self.base_url, path))
+            .with_context(|| format!("invalid GitHub API URL for '{}'", path))?;
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+        Ok(self.request_to(client, method, url))
+    }
+
+    /// Build a POST request to the GraphQL API.
+    fn graphql_request(&self, client: &reqwest::Client) -> Result<reqwest::RequestBuilder> {
+        let url = reqwest::Url::parse(&self.graphql_url)
+            .with_context(|| format!("invalid GitHub GraphQL URL '{}'", self.graphql_url))?;
+        Ok(self.request_to(client, Method::POST, url))
+    }
+
+    fn request_to(
+        &self,
+        client: &reqwest::Client,
+        method: Method,
+        url: reqwest::Url,
+    ) -> reqwest::RequestBuilder {
+        let authorization = if let Some(app) = &self.app {
+            app.token(Utc::now())
+                .map(|token| format!("Bearer {}", token))
+                .unwrap_or_default()
+        } else if let Some(idx) = self.current_token() {
+            self.last_used.store(idx, Ordering::Relaxed);
+            self.tokens[idx].clone()
+        } else {
+            String::new()
+        };
+        self.request_with(client, method, url, &authorization)
+    }
+
+    /// Build a request with the standard, configured, and signature headers,
+    /// sending `authorization` as the `Authorization` header.
+    fn request_with(
+        &self,
+        client: &reqwest::Client,
+        method: Method,
+        url: reqwest::Url,
+        authorization: &str,
+    ) -> reqwest::RequestBuilder {
+        let mut request = client
+            .request(method.clone(), url.clone())
+            .header("User-Agent", "nextest-download-stats-collector")
+            .header("Accept", "application/vnd.github.v3+json")
+            .header("Authorization", authorization);
+        for (name, value) in &self.headers {
+            request = request.header(name, value);
+        }
+        if let Some(signing) = &self.signing {
+            let timestamp = chrono::Utc::now().timestamp();
+            let target = match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            };
+            request = request
+                .header(
+                    &signing.header,
+                    signature(&signing.secret, timestamp, method.as_str(), &target),
+                )
+                .header(format!("{}-Timestamp", signing.header), timestamp);
+        }
+        request
+    }
+
+    /// Whether requests are authenticated, with a token or as an app.
+    fn has_credentials(&self) -> bool {
+        !self.tokens.is_empty() || self.app.is_some()
+    }
+
+    /// Request an installation token for the GitHub App, if there is one and
+    /// its current token is about to expire.
+    async fn refresh_app_token(&self) -> Result<()> {
+        let Some(app) = &self.app else {
+            return Ok(());
+        };
+        if app.token(Utc::now()).is_some() {
+            return Ok(());
+        }
+        let _refreshing = app.lock_refresh().await;
+        if app.token(Utc::now()).is_some() {
+            return Ok(());
+        }
+
+        let client = http::client();
+        let jwt = format!("Bearer {}", app.jwt(Utc::now())?);
+        let send = |method: Method, path: String, what: &'static str| {
+            let jwt = &jwt;
+            let client = &client;
+            async move {
+                let url = reqwest::Url::parse(&format!("{}{}", self.base_url, path))
+                    .with_context(|| format!("invalid GitHub API URL for '{}'", path))?;
+                let response = retry::send(&format!("GitHub App request to {}", what), || {
+                    Ok(self.request_with(client, method.clone(), url.clone(), jwt))
+                })
+                .await
+                .with_context(|| format!("failed to {} for GitHub App {}", what, app.app_id))?;
+                if !response.status().is_success() {
+                    let status = response.status();
+                    let body = response.text().await.unwrap_or_default();
+                    bail!(
+                        "GitHub App request to {} failed with status {}: {}",
+                        what,
+                        status,
+                        body
+                    );
+                }
+                Ok(response)
+            }
+        };
+
+        let installation_id = match app.installation_id {
+            Some(id) => id,
+            None => {
+                let installations: Vec<Installation> = send(
+                    Method::GET,
+                    "/app/installations".to_string(),
+                    "list installations",
+                )
+                .await?
+                .json()
+                .await
+                .context("failed to parse GitHub App installations")?;
+                match installations[..] {
+                    [Installation { id }] => id,
+                    _ => bail!(
+                        "GitHub App {} has {} installations, so api.app.installation_id must \
+                         say which to use",
+                        app.app_id,
+                        installations.len()
+                    ),
+                }
+            }
+        };
+        let token: InstallationToken = send(
+            Method::POST,
+            format!("/app/installations/{}/access_tokens", installation_id),
+            "create an installation token",
+        )
+        .await?
+        .json()
+        .await
+        .context("failed to parse GitHub App installation token")?;
+        app.set_token(token.token, token.expires_at);
+        Ok(())
+    }
+
+    /// The index of the token to send: the first one that isn't
+    /// rate-limited, or if they all are, the one whose limit resets first.
+    fn current_token(&self) -> Option<usize> {
+        let limited = RATE_LIMITED.lock().unwrap();
+        let now = Utc::now();
+        let reset = |idx: usize| {
+            limited
+                .get(&self.tokens[idx])
+                .filter(|reset| **reset > now)
+                .copied()
+        };
+        (0..self.tokens.len())
+            .find(|&idx| reset(idx).is_none())
+            .or_else(|| (0..self.tokens.len()).min_by_key(|&idx| reset(idx)))
+    }
+
+    /// Whether the token at `idx` has run out of requests.
+    fn is_rate_limited(&self, idx: usize) -> bool {
+        RATE_LIMITED
+            .lock()
+            .unwrap()
+            .get(&self.tokens[idx])
+            .is_some_and(|reset| *reset > Utc::now())
+    }
+
+    /// Send the request `build` returns with [`retry::send`], or
+    /// [`retry::send_read_only`] for queries, sending it again with the next
+    /// token whenever GitHub says the one used is out of requests.
+    async fn send(
+        &self,
+        what: &str,
+        read_only: bool,
+        mut build: impl FnMut() -> Result<RequestBuilder>,
+    ) -> Result<Response> {
+        loop {
+            self.refresh_app_token().await?;
+            let response = if read_only {
+                retry::send_read_only(what, &mut build).await?
+            } else {
+                retry::send(what, &mut build).await?
+            };
+            let Some(reset) = rate_limit_reset(response.status(), response.headers()) else {
+                return Ok(response);
+            };
+            if self.tokens.is_empty() {
+                return Ok(response);
+            }
+            let used = self.last_used.load(Ordering::Relaxed);
+            RATE_LIMITED
+                .lock()
+                .unwrap()
+                .insert(self.tokens[used].clone(), reset);
+            match self.current_token() {
+                Some(next) if !self.is_rate_limited(next) => {
+                    progress::suspend(|| {
+                        eprintln!(
+                            "  {}: token {} of {} is rate-limited until {}, switching to token {}",
+                            what,
+                            used + 1,
+                            self.tokens.len(),
+                            reset.format("%Y-%m-%d %H:%M UTC"),
+                            next + 1
+                        )
+                    });
+                }
+                _ => return Ok(response),
+            }
+        }
+    }
+
+    /// Fail unless a token is set, for requests that write to GitHub.
+    fn require_token(&self) -> Result<()> {
+        if !self.has_credentials() {
+            anyhow::bail!("{} must be set to write to GitHub", self.token_env);
+        }
+        Ok(())
+    }
+}
+
+/// Which releases and assets of a source are collected, compiled from a
+/// [`ReleaseFilterConfig`].
+#[derive(Debug)]
+pub struct ReleaseFilter {
+    tag_prefix: Option<String>,
+    tag_regex: Option<Regex>,
+    include_assets: Option<Regex>,
+    exclude_assets: Option<Regex>,
+}
+
+impl ReleaseFilter {
+    /// Compile a filter, checking that its patterns are valid.
+    pub fn new(config: &ReleaseFilterConfig) -> Result<Self> {
+        if config.tag_prefix.is_some() && config.tag_regex.is_some() {
+            bail!("tag_prefix and tag_regex can't both be set");
+        }
+        let tag_regex = match &config.tag_regex {
+            Some(regex) => {
+                Some(Regex::new(regex).with_context(|| format!("invalid tag_regex '{}'", regex))?)
+            }
+            None => None,
+        };
+        Ok(Self {
+            tag_prefix: config.tag_prefix.clone(),
+            tag_regex,
+            include_assets: wildcards(&config.include_assets)?,
+            exclude_assets: wildcards(&config.exclude_assets)?,
+        })
+    }
+
+    /// Whether the release tagged `tag` is collected.
+    pub fn tracks_release(&self, tag: &str) -> bool {
+        self.tag_prefix
+            .as_ref()
+            .is_none_or(|prefix| tag.starts_with(prefix.as_str()))
+            && self
+                .tag_regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(tag))
+    }
+
+    /// Whether the asset named `name` is collected.
+    pub fn tracks_asset(&self, name: &str) -> bool {
+        self.include_assets
+            .as_ref()
+            .is_none_or(|include| include.is_match(name))
+            && !self
+                .exclude_assets
+                .as_ref()
+                .is_some_and(|exclude| exclude.is_match(name))
+    }
+}
+
+/// Compile patterns with `*` and `?` wildcards into a regex matching names
+/// that match any of them, or `None` if there are no patterns.
+fn wildcards(patterns: &[String]) -> Result<Option<Regex>> {
+    if patterns.is_empty() {
+        return Ok(None);
+    }
+    let alternatives: Vec<String> = patterns
+        .iter()
+        .map(|pattern| {
+            pattern
+                .chars()
+                .map(|c| match c {
+                    '*' => ".*".to_string(),
+                    '?' => ".".to_string(),
+                    c => regex::escape(c.encode_utf8(&mut [0; 4])),
+                })
+                .collect()
+        })
+        .collect();
+    let regex = Regex::new(&format!("(?s)^(?:{})$", alternatives.join("|")))
+        .with_context(|| format!("invalid asset patterns {:?}", patterns))?;
+    Ok(Some(regex))
+}
+
+/// The `Authorization` header value for a token read from `token_env`.
+fn authorization(style: GithubAuthStyle, token_env: &str, token: &str) -> Result<String> {
+    Ok(match style {
+        GithubAuthStyle::Bearer => format!("Bearer {}", token),
+        GithubAuthStyle::Token => format!("token {}", token),
+        GithubAuthStyle::Basic => {
+            if !token.contains(':') {
+                bail!(
+                    "{} must hold 'username:token' for basic authentication",
+                    token_env
+                );
+            }
+            format!("Basic {}", STANDARD.encode(token))
+        }
+    })
+}
+
+/// When the rate limit that a response says is exhausted resets, if the
+/// response says so.
+fn rate_limit_reset(status: StatusCode, headers: &HeaderMap) -> Option<DateTime<Utc>> {
+    if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+        return None;
+    }
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    if header("x-ratelimit-remaining")?.trim() != "0" {
+        return None;
+    }
+    let reset = header("x-ratelimit-reset")
+        .and_then(|reset| reset.trim().parse().ok())
+        .and_then(|reset| DateTime::from_timestamp(reset, 0));
+    // Primary rate limits reset hourly.
+    Some(reset.unwrap_or_else(|| Utc::now() + chrono::TimeDelta::hours(1)))
+}
+
+/// The GraphQL API URL for a REST API base URL. GitHub Enterprise Server
+/// serves the REST API at `/api/v3` and GraphQL at `/api/graphql`.
+fn default_graphql_url(base_url: &str) -> String {
+    match base_url.strip_suffix("/api/v3") {
+        Some(host) => format!("{}/api/graphql", host),
+        None => format!("{}/graphql", base_url),
+    }
+}
+
+/// Compute the hex-encoded HMAC-SHA256 signature of a request.
+fn signature(secret: &str, timestamp: i64, method: &str, path_and_query: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}\n{}\n{}", timestamp, method, path_and_query).as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Send the request `build` returns and parse a JSON response, failing on
+/// non-success statuses.
+async fn send_json<T: serde::de::DeserializeOwned>(
+    api: &Api,
+    build: impl FnMut() -> Result<RequestBuilder>,
+    what: &str,
+) -> Result<T> {
+    let response = api
+        .send(&format!("GitHub API request to {}", what), false, build)
+        .await
+        .with_context(|| format!("failed to {}", what))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "GitHub API request to {} failed with status {}: {}",
+            what,
+            status,
+            body
+        );
+    }
+
+    response
+        .json()
+        .await
+        .with_context(|| format!("failed to parse GitHub API response to {}", what))
+}
+
+/// List open issues in a repository, optionally filtered by labels.
+pub async fn list_open_issues(
+    api: &Api,
+    owner: &str,
+    repo: &str,
+    labels: &[String],
+) -> Result<Vec<Issue>> {
+    let client = http::client();
+    let labels = labels.join(",");
+    let mut query = vec![("state", "open"), ("per_page", "100")];
+    if !labels.is_empty() {
+        query.push(("labels", &labels));
+    }
+    let path = format!("/repos/{}/{}/issues", owner, repo);
+
+    send_json(
+        api,
+        || api.request(&client, Method::GET, &path, &query),
+        "list issues",
+    )
+    .await
+}
+
+/// Create an issue in a repository.
+pub async fn create_issue(
+    api: &Api,
+    owner: &str,
+    repo: &str,
+    title: &str,
+    body: &str,
+    labels: &[String],
+) -> Result<Issue> {
+    api.require_token()?;
+    let client = http::client();
+    let path = format!("/repos/{}/{}/issues", owner, repo);
+
+    send_json(
+        api,
+        || {
+            Ok(api
+                .request(&client, Method::POST, &path, &[])?
+                .json(&serde_json::json!({
+                    "title": title,
+                    "body": body,
+                    "labels": labels,
+                })))
+        },
+        "create issue",
+    )
+    .await
+}
+
+/// Add a comment to an existing issue.
+pub async fn create_issue_comment(
+    api: &Api,
+    owner: &str,
+    repo: &str,
+    number: u64,
+    body: &str,
+) -> Result<()> {
+    api.require_token()?;
+    let client = http::client();
+    let path = format!("/repos/{}/{}/issues/{}/comments", owner, repo, number);
+
+    let _: serde_json::Value = send_json(
+        api,
+        || {
+            Ok(api
+                .request(&client, Method::POST, &path, &[])?
+                .json(&serde_json::json!({ "body": body })))
+        },
+        "comment on issue",
+    )
+    .await?;
+    Ok(())
+}
+
+/// All releases of a repository.
+#[derive(Debug)]
+pub struct ReleaseList {
+    pub releases: Vec<Release>,
+    /// The ETag of the first page of the listing, if GitHub sent one.
+    pub etag: Option<String>,
+}
+
 /// Fetch ALL releases from GitHub for a given repository using pagination.
 ///
 /// This ensures we capture download stats for all releases, not just recent ones.
 /// Old releases can continue getting downloads and we need to track that.
-pub async fn fetch_releases(owner: &str, repo: &str) -> Result<Vec<Release>> {
-    let client = reqwest::Client::new();
+pub async fn fetch_releases(api: &Api, owner: &str, repo: &str) -> Result<ReleaseList> {
+    if api.kind == GithubApiKind::Graphql {
+        return fetch_releases_graphql(api, owner, repo).await;
+    }
+    let client = http::client();
     let mut all_releases = Vec::new();
+    let mut etag = None;
     let mut page = 1;
     let per_page = 100;
 
-    let auth_header = std::env::var("GITHUB_TOKEN")
-        .map(|token| format!("Bearer {}", token))
-        .unwrap_or_default();
-
     loop {
-        let url = format!(
-            "{}/repos/{}/{}/releases?per_page={}&page={}",
-            GITHUB_API_BASE, owner, repo, per_page, page
-        );
-
-        let response = client
-            .get(&url)
-            .header("User-Agent", "nextest-download-stats-collector")
-            .header("Accept", "application/vnd.github.v3+json")
-            .header("Authorization", &auth_header)
-            .send()
-            .await
-            .with_context(|| format!("failed to fetch releases page {} from GitHub", page))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!(
-                "GitHub API request failed with status {} on page {}: {}",
-                status,
-                page,
-                body
-            );
+        let (releases, page_etag) =
+            fetch_releases_page(api, &client, owner, repo, page, per_page).await?;
+        if page == 1 {
+            etag = page_etag;
         }
 
-        let releases: Vec<Release> = response
-            .json()
-            .await
-            .with_context(|| format!("failed to parse GitHub API response for page {}", page))?;
-
         let is_last_page = releases.len() < per_page;
         all_releases.extend(releases);
 
@@ -75,16 +715,522 @@ pub async fn fetch_releases(owner: &str, repo: &str) -> Result<Vec<Release>> {
         page += 1;
     }
 
-    Ok(all_releases)
+    Ok(ReleaseList {
+        releases: all_releases,
+        etag,
+    })
+}
+
+/// The number of releases fetched by [`fetch_recent_releases`].
+pub const RECENT_RELEASES: usize = 10;
+
+/// Fetch only the most recent releases of a repository, newest first.
+///
+/// This is a single request, cheap enough to make every hour to notice new
+/// releases between full collections.
+pub async fn fetch_recent_releases(api: &Api, owner: &str, repo: &str) -> Result<Vec<Release>> {
+    let client = http::client();
+    let (releases, _) = fetch_releases_page(api, &client, owner, repo, 1, RECENT_RELEASES).await?;
+    Ok(releases)
+}
+
+/// Fetch a page of the releases listing, along with its ETag.
+async fn fetch_releases_page(
+    api: &Api,
+    client: &reqwest::Client,
+    owner: &str,
+    repo: &str,
+    page: usize,
+    per_page: usize,
+) -> Result<(Vec<Release>, Option<String>)> {
+    let path = format!("/repos/{}/{}/releases", owner, repo);
+    let per_page_param = per_page.to_string();
+    let page_param = page.to_string();
+    let query = [("per_page", per_page_param.as_str()), ("page", &page_param)];
+
+    let response = api
+        .send(
+            &format!("GitHub request for releases page {}", page),
+            false,
+            || api.request(client, Method::GET, &path, &query),
+        )
+        .await
+        .with_context(|| format!("failed to fetch releases page {} from GitHub", page))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "GitHub API request failed with status {} on page {}: {}",
+            status,
+            page,
+            body
+        );
+    }
+
+    let etag = response
+        .headers()
+        .get(reqwest::header::ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_owned);
+
+    let releases: Vec<Release> = response
+        .json()
+        .await
+        .with_context(|| format!("failed to parse GitHub API response for page {}", page))?;
+    Ok((releases, etag))
+}
+
+/// Releases, newest first, with the first 100 assets of each.
+const RELEASES_QUERY: &str = "
+query($owner: String!, $repo: String!, $cursor: String) {
+  repository(owner: $owner, name: $repo) {
+    releases(first: 100, after: $cursor, orderBy: {field: CREATED_AT, direction: DESC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes {
+        tagName
+        publishedAt
+        isPrerelease
+        releaseAssets(first: 100) {
+          pageInfo { hasNextPage endCursor }
+          nodes { name downloadCount }
+        }
+      }
+    }
+  }
+}";
+
+/// The rest of the assets of a release with more than 100.
+const RELEASE_ASSETS_QUERY: &str = "
+query($owner: String!, $repo: String!, $tag: String!, $cursor: String) {
+  repository(owner: $owner, name: $repo) {
+    release(tagName: $tag) {
+      releaseAssets(first: 100, after: $cursor) {
+        pageInfo { hasNextPage endCursor }
+        nodes { name downloadCount }
+      }
+    }
+  }
+}";
+
+#[derive(Debug, Deserialize)]
+struct GraphqlResponse<T> {
+    data: Option<T>,
+    #[serde(default)]
+    errors: Vec<GraphqlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphqlError {
+    message: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RepositoryData<T> {
+    /// `None` if the repository doesn't exist or isn't visible.
+    repository: Option<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleasesRepository {
+    releases: GraphqlConnection<GraphqlRelease>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReleaseRepository {
+    release: Option<ReleaseAssets>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlRelease {
+    tag_name: String,
+    published_at: Option<DateTime<Utc>>,
+    is_prerelease: bool,
+    #[serde(flatten)]
+    assets: ReleaseAssets,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ReleaseAssets {
+    release_assets: GraphqlConnection<Asset>,
+}
+
+/// A page of a paginated list.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GraphqlConnection<T> {
+    page_info: PageInfo,
+    nodes: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PageInfo {
+    has_next_page: bool,
+    end_cursor: Option<String>,
+}
+
+/// Fetch all releases with the GraphQL API, following pagination of both
+/// releases and their assets.
+async fn fetch_releases_graphql(api: &Api, owner: &str, repo: &str) -> Result<ReleaseList> {
+    if !api.has_credentials() {
+        anyhow::bail!(
+            "{} must be set to use the GitHub GraphQL API",
+            api.token_env
+        );
+    }
+    let client = http::client();
+    let not_found = || format!("GitHub repository {}/{} not found", owner, repo);
+    let mut all_releases = Vec::new();
+    let mut cursor: Option<String> = None;
+
+    loop {
+        let variables = serde_json::json!({ "owner": owner, "repo": repo, "cursor": cursor });
+        let data: RepositoryData<ReleasesRepository> =
+            graphql(api, &client, RELEASES_QUERY, variables, "releases").await?;
+        let releases = data.repository.with_context(not_found)?.releases;
+
+        for release in releases.nodes {
+            let GraphqlConnection {
+                nodes: mut assets,
+                mut page_info,
+            } = release.assets.release_assets;
+            while page_info.has_next_page {
+                let variables = serde_json::json!({
+                    "owner": owner,
+                    "repo": repo,
+                    "tag": release.tag_name,
+                    "cursor": page_info.end_cursor,
+                });
+                let what = format!("assets of {}", release.tag_name);
+                let data: RepositoryData<ReleaseRepository> =
+                    graphql(api, &client, RELEASE_ASSETS_QUERY, variables, &what).await?;
+                let more = data
+                    .repository
+                    .with_context(not_found)?
+                    .release
+                    .with_context(|| format!("GitHub release {} not found", release.tag_name))?
+                    .release_assets;
+                assets.extend(more.nodes);
+                page_info = more.page_info;
+            }
+            all_releases.push(Release {
+                tag_name: release.tag_name,
+                published_at: release.published_at,
+                prerelease: release.is_prerelease,
+                assets,
+            });
+        }
+
+        if !releases.page_info.has_next_page {
+            break;
+        }
+        cursor = releases.page_info.end_cursor;
+    }
+
+    // GraphQL responses have no ETag.
+    Ok(ReleaseList {
+        releases: all_releases,
+        etag: None,
+    })
+}
+
+/// Run a GraphQL query, failing on non-success statuses and query errors.
+async fn graphql<T: serde::de::DeserializeOwned>(
+    api: &Api,
+    client: &reqwest::Client,
+    query: &str,
+    variables: serde_json::Value,
+    what: &str,
+) -> Result<T> {
+    let body = serde_json::json!({ "query": query, "variables": variables });
+    let response = api
+        .send(&format!("GitHub GraphQL query for {}", what), true, || {
+            Ok(api.graphql_request(client)?.json(&body))
+        })
+        .await
+        .with_context(|| format!("failed to query {} from GitHub", what))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "GitHub GraphQL query for {} failed with status {}: {}",
+            what,
+            status,
+            body
+        );
+    }
+
+    let response: GraphqlResponse<T> = response
+        .json()
+        .await
+        .with_context(|| format!("failed to parse GitHub GraphQL response for {}", what))?;
+    parse_graphql(response, what)
+}
+
+fn parse_graphql<T>(response: GraphqlResponse<T>, what: &str) -> Result<T> {
+    if !response.errors.is_empty() {
+        let messages: Vec<_> = response.errors.into_iter().map(|e| e.message).collect();
+        anyhow::bail!(
+            "GitHub GraphQL query for {} failed: {}",
+            what,
+            messages.join("; ")
+        );
+    }
+    response
+        .data
+        .with_context(|| format!("GitHub GraphQL response for {} has no data", what))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_request_signing() {
+        let target = "/github/repos/o/r/releases?per_page=100&page=1";
+        assert_eq!(
+            signature("secret", 1_700_000_000, "GET", target),
+            "16b22653cdf27428236fc96c16420d08258dc4853eab41ec16194d754f08c273"
+        );
+
+        let api = Api {
+            base_url: "https://gateway.example.com/github".to_string(),
+            graphql_url: "https://gateway.example.com/github/graphql".to_string(),
+            token_env: DEFAULT_TOKEN_ENV.to_string(),
+            tokens: Vec::new(),
+            last_used: AtomicUsize::new(0),
+            app: None,
+            headers: vec![("X-Api-Key".to_string(), "key".to_string())],
+            signing: Some(Signing {
+                header: "X-Signature".to_string(),
+                secret: "secret".to_string(),
+            }),
+            kind: GithubApiKind::Rest,
+        };
+        let request = api
+            .request(
+                &http::client(),
+                Method::GET,
+                "/repos/o/r/releases",
+                &[("per_page", "100"), ("page", "1")],
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(
+            request.url().as_str(),
+            format!("https://gateway.example.com{}", target)
+        );
+        let headers = request.headers();
+        assert_eq!(headers["X-Api-Key"], "key");
+        let timestamp: i64 = headers["X-Signature-Timestamp"]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            headers["X-Signature"],
+            signature("secret", timestamp, "GET", target).as_str()
+        );
+        assert!(api.require_token().is_err());
+    }
+
+    #[test]
+    fn test_parse_graphql_releases() {
+        let json = r#"{"data": {"repository": {"releases": {
+            "pageInfo": {"hasNextPage": true, "endCursor": "Y3Vyc29y"},
+            "nodes": [{
+                "tagName": "cargo-nextest-0.9.100",
+                "publishedAt": "2025-06-01T00:00:00Z",
+                "isPrerelease": false,
+                "releaseAssets": {
+                    "pageInfo": {"hasNextPage": false, "endCursor": null},
+                    "nodes": [{"name": "cargo-nextest-0.9.100-x86_64-unknown-linux-gnu.tar.gz", "downloadCount": 42}]
+                }
+            }]
+        }}}}"#;
+        let response: GraphqlResponse<RepositoryData<ReleasesRepository>> =
+            serde_json::from_str(json).unwrap();
+        let releases = parse_graphql(response, "releases")
+            .unwrap()
+            .repository
+            .unwrap()
+            .releases;
+        assert!(releases.page_info.has_next_page);
+        assert_eq!(releases.page_info.end_cursor.as_deref(), Some("Y3Vyc29y"));
+        let release = &releases.nodes[0];
+        assert_eq!(release.tag_name, "cargo-nextest-0.9.100");
+        assert!(!release.is_prerelease);
+        assert_eq!(release.assets.release_assets.nodes[0].download_count, 42);
+
+        let json = r#"{"data": null, "errors": [{"message": "Bad credentials"}]}"#;
+        let response: GraphqlResponse<RepositoryData<ReleasesRepository>> =
+            serde_json::from_str(json).unwrap();
+        let err = parse_graphql(response, "releases").unwrap_err();
+        assert!(err.to_string().ends_with("Bad credentials"), "{}", err);
+    }
+
+    #[test]
+    fn test_enterprise_server() {
+        let api = Api::new(&GithubApiConfig {
+            base_url: Some("https://github.example.com/api/v3/".to_string()),
+            token_env: Some("DOWNLOAD_STATS_TEST_UNSET_TOKEN".to_string()),
+            ..GithubApiConfig::default()
+        })
+        .unwrap();
+        assert_eq!(api.base_url, "https://github.example.com/api/v3");
+        assert_eq!(api.graphql_url, "https://github.example.com/api/graphql");
+        assert!(api.tokens.is_empty());
+        let request = api
+            .graphql_request(&http::client())
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.method(), Method::POST);
+        assert_eq!(
+            request.url().as_str(),
+            "https://github.example.com/api/graphql"
+        );
+        assert_eq!(
+            default_graphql_url("https://gateway.example.com/github"),
+            "https://gateway.example.com/github/graphql"
+        );
+
+        let auth = |style| authorization(style, "GHE_TOKEN", "octocat:abc");
+        assert_eq!(auth(GithubAuthStyle::Bearer).unwrap(), "Bearer octocat:abc");
+        assert_eq!(auth(GithubAuthStyle::Token).unwrap(), "token octocat:abc");
+        assert_eq!(
+            auth(GithubAuthStyle::Basic).unwrap(),
+            "Basic b2N0b2NhdDphYmM="
+        );
+        let err = authorization(GithubAuthStyle::Basic, "GHE_TOKEN", "abc").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "GHE_TOKEN must hold 'username:token' for basic authentication"
+        );
+    }
+
+    #[test]
+    fn test_token_rotation() {
+        let dir = camino::Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-tokens-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let tokens_file = dir.join("tokens");
+        fs::write(
+            &tokens_file,
+            "# org-wide collection\ntest-rotation-a\n\n  test-rotation-b\ntest-rotation-a\n",
+        )
+        .unwrap();
+        let api = Api::new(&GithubApiConfig {
+            token_env: Some("DOWNLOAD_STATS_TEST_UNSET_TOKEN".to_string()),
+            tokens_file: Some(tokens_file),
+            ..GithubApiConfig::default()
+        })
+        .unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(
+            api.tokens,
+            ["Bearer test-rotation-a", "Bearer test-rotation-b"]
+        );
+        assert_eq!(api.current_token(), Some(0));
+
+        // The first token that isn't rate-limited is used, or once they all
+        // are, the one that resets first.
+        let now = Utc::now();
+        let limit = |idx: usize, reset| {
+            RATE_LIMITED
+                .lock()
+                .unwrap()
+                .insert(api.tokens[idx].clone(), reset);
+        };
+        limit(0, now + chrono::TimeDelta::minutes(30));
+        assert_eq!(api.current_token(), Some(1));
+        assert!(api.is_rate_limited(0));
+        limit(1, now + chrono::TimeDelta::minutes(10));
+        assert_eq!(api.current_token(), Some(1));
+        limit(0, now - chrono::TimeDelta::minutes(1));
+        assert_eq!(api.current_token(), Some(0));
+
+        let request = api
+            .request(&http::client(), Method::GET, "/rate_limit", &[])
+            .unwrap()
+            .build()
+            .unwrap();
+        assert_eq!(request.headers()["Authorization"], "Bearer test-rotation-a");
+        assert_eq!(api.last_used.load(Ordering::Relaxed), 0);
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-ratelimit-remaining", "0".parse().unwrap());
+        headers.insert("x-ratelimit-reset", "1700000000".parse().unwrap());
+        assert_eq!(
+            rate_limit_reset(StatusCode::FORBIDDEN, &headers),
+            DateTime::from_timestamp(1_700_000_000, 0)
+        );
+        assert_eq!(rate_limit_reset(StatusCode::OK, &headers), None);
+        headers.insert("x-ratelimit-remaining", "12".parse().unwrap());
+        assert_eq!(
+            rate_limit_reset(StatusCode::TOO_MANY_REQUESTS, &headers),
+            None
+        );
+    }
+
+    #[test]
+    fn test_release_filter() {
+        let all = ReleaseFilter::new(&ReleaseFilterConfig::default()).unwrap();
+        assert!(all.tracks_release("v1.0.0"));
+        assert!(all.tracks_asset("anything.tar.gz"));
+
+        let prefix = ReleaseFilter::new(&ReleaseFilterConfig {
+            tag_prefix: Some("cargo-nextest-".to_string()),
+            include_assets: vec!["*.tar.gz".to_string(), "*.zip".to_string()],
+            exclude_assets: vec!["*-musl.tar.gz".to_string()],
+            ..ReleaseFilterConfig::default()
+        })
+        .unwrap();
+        assert!(prefix.tracks_release("cargo-nextest-0.9.100"));
+        assert!(!prefix.tracks_release("nextest-runner-0.1.0"));
+        assert!(prefix.tracks_asset("cargo-nextest-0.9.100-x86_64-pc-windows-msvc.zip"));
+        assert!(!prefix.tracks_asset("cargo-nextest-0.9.100-x86_64-unknown-linux-musl.tar.gz"));
+        assert!(!prefix.tracks_asset("cargo-nextest-0.9.100.tar.gz.sha256"));
+
+        let regex = ReleaseFilter::new(&ReleaseFilterConfig {
+            tag_regex: Some(r"^v\d+\.\d+\.\d+$".to_string()),
+            include_assets: vec!["tool-?.*".to_string()],
+            ..ReleaseFilterConfig::default()
+        })
+        .unwrap();
+        assert!(regex.tracks_release("v1.2.3"));
+        assert!(!regex.tracks_release("v1.2.3-rc.1"));
+        // Only wildcards are special in asset patterns.
+        assert!(regex.tracks_asset("tool-a.tar.gz"));
+        assert!(!regex.tracks_asset("tool-ab.tar.gz"));
+        assert!(!regex.tracks_asset("tool-a+tar"));
+
+        let both = ReleaseFilterConfig {
+            tag_prefix: Some("v".to_string()),
+            tag_regex: Some("^v".to_string()),
+            ..ReleaseFilterConfig::default()
+        };
+        assert!(ReleaseFilter::new(&both).is_err());
+        let invalid = ReleaseFilterConfig {
+            tag_regex: Some("(".to_string()),
+            ..ReleaseFilterConfig::default()
+        };
+        assert!(ReleaseFilter::new(&invalid).is_err());
+    }
+
     #[tokio::test]
     async fn test_fetch_releases() {
-        let releases = fetch_releases("nextest-rs", "nextest").await.unwrap();
+        let api = Api::new(&GithubApiConfig::default()).unwrap();
+        let releases = fetch_releases(&api, "nextest-rs", "nextest")
+            .await
+            .unwrap()
+            .releases;
         assert!(!releases.is_empty(), "should have at least one release");
 
         let has_assets = releases.iter().any(|r| !r.assets.is_empty());
@@ -0,0 +1,145 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The headline total downloads figure.
+//!
+//! The project reports a single all-time total combining its sources. The
+//! definition lives in the `[headline]` configuration so that the badge,
+//! `query headline`, and notifications all report the same number.
+
+use crate::config::HeadlineConfig;
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use serde::Serialize;
+
+/// The headline figure, with how it was arrived at.
+#[derive(Debug, Serialize)]
+pub struct Headline {
+    /// All-time downloads from each counted source.
+    pub sources: Vec<HeadlineSource>,
+    /// The configured manual adjustment.
+    pub adjustment: i64,
+    /// The sum of the sources and the adjustment, before rounding.
+    pub exact: u64,
+    /// The headline figure.
+    pub total: u64,
+}
+
+/// A source's contribution to the headline figure.
+#[derive(Debug, Serialize)]
+pub struct HeadlineSource {
+    pub kind: &'static str,
+    /// Downloads counted.
+    pub downloads: u64,
+    /// Downloads of excluded release tags, crates, or install endpoints.
+    pub excluded: u64,
+}
+
+/// Compute the headline figure as of the latest collected data.
+pub fn compute(conn: &Connection, config: &HeadlineConfig) -> Result<Headline> {
+    let mut sources = Vec::new();
+    for kind in ["github", "crates", "installer"] {
+        if !config.sources.iter().any(|source| source == kind) {
+            continue;
+        }
+        let mut source = HeadlineSource {
+            kind,
+            downloads: 0,
+            excluded: 0,
+        };
+        for (name, downloads) in all_time_downloads(conn, kind)
+            .with_context(|| format!("failed to get all-time {} downloads", kind))?
+        {
+            if config.is_excluded(kind, &name) {
+                source.excluded += downloads;
+            } else {
+                source.downloads += downloads;
+            }
+        }
+        sources.push(source);
+    }
+
+    let counted: u64 = sources.iter().map(|source| source.downloads).sum();
+    let exact = counted.saturating_add_signed(config.adjustment);
+    Ok(Headline {
+        sources,
+        adjustment: config.adjustment,
+        exact,
+        total: exact - exact % config.round_down_to,
+    })
+}
+
+/// All-time downloads from a kind of source, by release tag, crate, or
+/// install endpoint.
+///
+/// GitHub and crates.io report cumulative counts, so these are the counts in
+/// the latest snapshot; install endpoints report downloads per day, which are
+/// summed.
+fn all_time_downloads(conn: &Connection, kind: &str) -> Result<Vec<(String, u64)>> {
+    let query = match kind {
+        "github" => {
+            "SELECT release_tag, SUM(download_count) FROM github_cumulative
+             WHERE date = (SELECT MAX(date) FROM github_snapshots)
+             GROUP BY release_tag"
+        }
+        "crates" => {
+            "SELECT crate_name, total_downloads FROM crates_cumulative
+             WHERE date = (SELECT MAX(date) FROM crates_metadata)"
+        }
+        _ => "SELECT name, SUM(downloads) FROM installer_downloads GROUP BY name",
+    };
+    let mut stmt = conn.prepare(query)?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?.max(0) as u64,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use camino::Utf8Path;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_compute() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO crates_metadata (date, crate_name, total_downloads, recent_downloads)
+             VALUES ('2024-01-01', 'cargo-nextest', 900, 0),
+                    ('2024-01-02', 'cargo-nextest', 1000, 0),
+                    ('2024-01-02', 'nextest-runner', 300, 0);",
+        )
+        .unwrap();
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        db::insert_installer_download(&conn, date, "get.nexte.st", 40).unwrap();
+        db::insert_installer_download(&conn, date.succ_opt().unwrap(), "get.nexte.st", 27).unwrap();
+
+        let headline = compute(&conn, &HeadlineConfig::default()).unwrap();
+        assert_eq!(headline.exact, 1367);
+        assert_eq!(headline.total, 1367);
+
+        let config: HeadlineConfig = toml::from_str(
+            r#"
+sources = ["crates", "installer"]
+exclude = ["crates:nextest-runner"]
+adjustment = 35
+round_down_to = 100
+"#,
+        )
+        .unwrap();
+        let headline = compute(&conn, &config).unwrap();
+        let kinds: Vec<_> = headline.sources.iter().map(|source| source.kind).collect();
+        assert_eq!(kinds, ["crates", "installer"]);
+        assert_eq!(headline.sources[0].downloads, 1000);
+        assert_eq!(headline.sources[0].excluded, 300);
+        assert_eq!(headline.exact, 1102);
+        assert_eq!(headline.total, 1100);
+    }
+}
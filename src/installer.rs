@@ -0,0 +1,125 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Client for install endpoint statistics, such as `get.nexte.st`.
+//!
+//! The install endpoint serves pre-built binaries directly rather than through
+//! GitHub or crates.io, so its downloads don't show up anywhere else. It has
+//! no public API: statistics come from a JSON document of daily counts,
+//! produced either by a stats API or by a job that ingests the CDN's access
+//! logs:
+//!
+//! ```json
+//! {"downloads": [{"date": "2025-11-17", "downloads": 1234}]}
+//! ```
+//!
+//! The document can be fetched over HTTP(S) or read from a local file.
+
+use crate::{http, retry};
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::fs;
+
+/// Downloads through the install endpoint on a single day.
+#[derive(Debug, Deserialize, PartialEq, Eq)]
+pub struct InstallerDay {
+    pub date: NaiveDate,
+    pub downloads: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatsResponse {
+    downloads: Vec<InstallerDay>,
+}
+
+/// The statistics document fetched from an install endpoint.
+#[derive(Debug)]
+pub struct InstallerStats {
+    pub days: Vec<InstallerDay>,
+    /// The ETag of the HTTP response, if the server sent one.
+    pub etag: Option<String>,
+}
+
+/// Fetch daily download counts from `stats_url`.
+///
+/// `stats_url` is either an `http://` or `https://` URL, or a path to a local
+/// file (optionally prefixed with `file://`).
+pub async fn fetch_daily_downloads(stats_url: &str) -> Result<InstallerStats> {
+    let mut etag = None;
+    let body = if stats_url.starts_with("http://") || stats_url.starts_with("https://") {
+        let client = http::client();
+        let description = format!("installer stats request to {}", stats_url);
+        let response = retry::send(&description, || {
+            Ok(client.get(stats_url).header(
+                "User-Agent",
+                "nextest-download-stats-collector (contact: opensource@nexte.st)",
+            ))
+        })
+        .await
+        .with_context(|| format!("failed to fetch installer stats from {}", stats_url))?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "installer stats request to {} failed with status {}: {}",
+                stats_url,
+                status,
+                body
+            );
+        }
+        etag = response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_owned);
+        response
+            .text()
+            .await
+            .context("failed to read installer stats response")?
+    } else {
+        let path = stats_url.strip_prefix("file://").unwrap_or(stats_url);
+        fs::read_to_string(path).with_context(|| format!("failed to read {}", path))?
+    };
+
+    Ok(InstallerStats {
+        days: parse_stats(&body)?,
+        etag,
+    })
+}
+
+fn parse_stats(body: &str) -> Result<Vec<InstallerDay>> {
+    let response: StatsResponse =
+        serde_json::from_str(body).context("failed to parse installer stats")?;
+    Ok(response.downloads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_stats() {
+        let body = r#"{"downloads": [
+            {"date": "2025-11-17", "downloads": 1234},
+            {"date": "2025-11-18", "downloads": 0}
+        ]}"#;
+        let days = parse_stats(body).unwrap();
+        assert_eq!(
+            days,
+            [
+                InstallerDay {
+                    date: NaiveDate::from_ymd_opt(2025, 11, 17).unwrap(),
+                    downloads: 1234,
+                },
+                InstallerDay {
+                    date: NaiveDate::from_ymd_opt(2025, 11, 18).unwrap(),
+                    downloads: 0,
+                },
+            ]
+        );
+
+        assert!(parse_stats(r#"{"downloads": [{"date": "yesterday", "downloads": 1}]}"#).is_err());
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Automatic issue filing when collection fails repeatedly.
+
+use crate::{
+    config::IssueFilingConfig,
+    db::{self, FailedRun},
+    github,
+    output::status,
+};
+use anyhow::Result;
+use rusqlite::Connection;
+
+/// Title of the issue filed for repeated failures, also used to find it again.
+const ISSUE_TITLE: &str = "Download statistics collection is failing";
+
+/// Number of recent errors included in the issue body.
+const MAX_ERRORS: usize = 5;
+
+/// File or update an issue if collection has failed enough times in a row.
+///
+/// Should be called after the failed run has been recorded. Opens a new issue
+/// once the threshold is reached, and comments on the existing open issue for
+/// every subsequent failure.
+pub async fn report_failure(conn: &Connection, config: &IssueFilingConfig) -> Result<()> {
+    let Some(failures) = failures_to_report(conn, config.after_failures)? else {
+        return Ok(());
+    };
+
+    let last_success = db::get_last_successful_run(conn)?;
+    let body = failure_summary(&failures, last_success.as_deref());
+
+    let api = github::Api::new(&config.api)?;
+    let existing = github::list_open_issues(&api, &config.owner, &config.repo, &config.labels)
+        .await?
+        .into_iter()
+        .find(|issue| issue.title == ISSUE_TITLE);
+
+    match existing {
+        Some(issue) => {
+            github::create_issue_comment(&api, &config.owner, &config.repo, issue.number, &body)
+                .await?;
+            status!("\nCommented on collection failure issue {}", issue.html_url);
+        }
+        None => {
+            let issue = github::create_issue(
+                &api,
+                &config.owner,
+                &config.repo,
+                ISSUE_TITLE,
+                &body,
+                &config.labels,
+            )
+            .await?;
+            status!("\nFiled collection failure issue {}", issue.html_url);
+        }
+    }
+
+    Ok(())
+}
+
+/// The failed runs since the last successful one, if there are at least
+/// `after_failures` of them.
+pub fn failures_to_report(
+    conn: &Connection,
+    after_failures: usize,
+) -> Result<Option<Vec<FailedRun>>> {
+    let failures = db::get_consecutive_failures(conn)?;
+    Ok((failures.len() >= after_failures).then_some(failures))
+}
+
+/// Build the Markdown summary of consecutive failures.
+fn failure_summary(failures: &[FailedRun], last_success: Option<&str>) -> String {
+    let mut body = format!(
+        "Download statistics collection has failed {} consecutive times.\n\n\
+         - Latest failure: {}\n\
+         - Last success: {}\n",
+        failures.len(),
+        failures
+            .first()
+            .map_or("unknown", |f| f.started_at.as_str()),
+        last_success.unwrap_or("never"),
+    );
+
+    body.push_str("\n### Recent errors\n");
+    for failure in failures.iter().take(MAX_ERRORS) {
+        body.push_str(&format!(
+            "\n**{}**\n\n```\n{}\n```\n",
+            failure.started_at, failure.error
+        ));
+    }
+    if failures.len() > MAX_ERRORS {
+        body.push_str(&format!(
+            "\n...and {} earlier failures.\n",
+            failures.len() - MAX_ERRORS
+        ));
+    }
+
+    body
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_failure_summary() {
+        let failures: Vec<_> = (0..7)
+            .rev()
+            .map(|i| FailedRun {
+                started_at: format!("2025-11-{:02}T02:00:00+00:00", 10 + i),
+                error: format!("error {}", i),
+            })
+            .collect();
+
+        let body = failure_summary(&failures, None);
+        assert!(body.contains("failed 7 consecutive times"));
+        assert!(body.contains("Latest failure: 2025-11-16T02:00:00+00:00"));
+        assert!(body.contains("Last success: never"));
+        assert!(body.contains("error 6"));
+        assert!(!body.contains("error 1"));
+        assert!(body.contains("...and 2 earlier failures."));
+    }
+}
@@ -4,11 +4,44 @@
 //! Download statistics collector for nextest releases and crates.
 
 pub mod aggregate;
+pub mod alerts;
+pub mod amend;
+pub mod analysis;
+pub mod archive;
+pub mod chart_data;
 pub mod charts;
 pub mod commands;
 pub mod config;
 pub mod crates_io;
+pub mod daemon;
 pub mod db;
+pub mod digest;
 pub mod dispatch;
+pub mod fixtures;
+pub mod format;
 pub mod github;
+pub mod github_app;
+pub mod headline;
+pub mod http;
+pub mod installer;
+pub mod issues;
+pub mod metrics;
+pub mod notify;
+pub mod onboard;
+pub mod output;
+pub mod page;
+pub mod platform;
+pub mod progress;
+pub mod provenance;
+pub mod publish;
 pub mod query;
+pub mod report;
+pub mod retry;
+pub mod split;
+pub mod stats;
+pub mod summary;
+pub mod table;
+pub mod tui;
+pub mod upload;
+pub mod wayback;
+pub mod website;
@@ -0,0 +1,816 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Derived metrics: expressions over weekly series, evaluated when queried.
+//!
+//! Metrics are defined in the `[metrics]` section of the configuration:
+//!
+//! ```toml
+//! [metrics.github_share]
+//! expr = "github / total"
+//! format = "percent"
+//! ```
+//!
+//! Expressions combine series and numbers with `+`, `-`, `*`, `/`, and
+//! parentheses. The series are:
+//!
+//! - `github`, `crates`, and `installer`: weekly downloads from each source.
+//! - `total`: weekly downloads from all sources.
+//! - `crate("name")`: weekly downloads of one crate.
+//! - `platform("name")`: weekly GitHub downloads for one platform.
+//! - `lag(expr, n)`: `expr` as of `n` weeks earlier.
+//! - `sum(expr, n)`: `expr` summed over the last `n` weeks.
+//! - The name of another metric.
+//!
+//! Series only include weeks through their source's latest complete week.
+//! Arithmetic between two series keeps the weeks both have, and weeks where
+//! a division would be by zero are left out.
+
+use crate::{
+    analysis,
+    config::{MetricConfig, MetricFormat},
+    format::{self, Target},
+};
+use anyhow::{Context, Result, bail};
+use chrono::{Duration, NaiveDate};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+/// Names that can't be used for metrics, since expressions use them.
+const RESERVED: [&str; 8] = [
+    "github",
+    "crates",
+    "installer",
+    "total",
+    "crate",
+    "platform",
+    "lag",
+    "sum",
+];
+
+/// Weekly values keyed by week start.
+type Series = BTreeMap<NaiveDate, f64>;
+
+/// The validated metric definitions from the configuration.
+#[derive(Debug, Default)]
+pub struct Metrics {
+    metrics: BTreeMap<String, Metric>,
+}
+
+/// A single derived metric.
+#[derive(Debug)]
+pub struct Metric {
+    pub name: String,
+    /// The expression as written in the configuration.
+    pub expr: String,
+    pub description: Option<String>,
+    pub format: MetricFormat,
+    parsed: Expr,
+}
+
+/// The most recent value of a metric.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct MetricValue {
+    pub name: String,
+    pub week_start: NaiveDate,
+    pub value: f64,
+    /// The value formatted for display, such as `42.5%`.
+    pub display: String,
+}
+
+impl Metrics {
+    /// Parse and check metric definitions: names must not clash with the
+    /// expression syntax, expressions must parse and depend on at least one
+    /// series, and metrics may refer to each other but not in a cycle.
+    pub fn new(config: &BTreeMap<String, MetricConfig>) -> Result<Self> {
+        let mut metrics = BTreeMap::new();
+        for (name, metric) in config {
+            if !is_identifier(name) || RESERVED.contains(&name.as_str()) {
+                bail!(
+                    "invalid metric name '{}': names must be identifiers other than {}",
+                    name,
+                    RESERVED.join(", ")
+                );
+            }
+            let parsed = Parser::parse(&metric.expr)
+                .with_context(|| format!("invalid expression for metric '{}'", name))?;
+            if !parsed.has_series() {
+                bail!("metric '{}' doesn't depend on any series", name);
+            }
+            metrics.insert(
+                name.clone(),
+                Metric {
+                    name: name.clone(),
+                    expr: metric.expr.clone(),
+                    description: metric.description.clone(),
+                    format: metric.format,
+                    parsed,
+                },
+            );
+        }
+
+        let metrics = Self { metrics };
+        for name in metrics.metrics.keys() {
+            metrics.check_references(name, &mut Vec::new())?;
+        }
+        Ok(metrics)
+    }
+
+    /// Check that the metrics `name` refers to exist, and that none of them
+    /// lead back to a metric on `path`.
+    fn check_references<'a>(&'a self, name: &'a str, path: &mut Vec<&'a str>) -> Result<()> {
+        if path.contains(&name) {
+            path.push(name);
+            bail!(
+                "metrics refer to each other in a cycle: {}",
+                path.join(" -> ")
+            );
+        }
+        let metric = &self.metrics[name];
+        path.push(name);
+        let mut references = Vec::new();
+        metric.parsed.metric_references(&mut references);
+        for reference in references {
+            if !self.metrics.contains_key(reference) {
+                bail!(
+                    "metric '{}' refers to '{}', which is not a series or a defined metric",
+                    name,
+                    reference
+                );
+            }
+            self.check_references(reference, path)?;
+        }
+        path.pop();
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// All metrics, by name.
+    pub fn iter(&self) -> impl Iterator<Item = &Metric> {
+        self.metrics.values()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Metric> {
+        self.metrics.get(name)
+    }
+
+    /// Compute a metric's weekly values, oldest first.
+    pub fn evaluate(&self, conn: &Connection, name: &str) -> Result<Vec<(NaiveDate, f64)>> {
+        let Some(metric) = self.metrics.get(name) else {
+            let known: Vec<&str> = self.metrics.keys().map(String::as_str).collect();
+            bail!(
+                "unknown metric '{}'. Defined metrics: {}",
+                name,
+                if known.is_empty() {
+                    "none".to_string()
+                } else {
+                    known.join(", ")
+                }
+            );
+        };
+        let mut evaluator = Evaluator {
+            conn,
+            metrics: self,
+            series: HashMap::new(),
+        };
+        let series = evaluator.series(&metric.parsed)?;
+        Ok(series.into_iter().collect())
+    }
+
+    /// The most recent value of every metric that has one.
+    pub fn latest(&self, conn: &Connection) -> Result<Vec<MetricValue>> {
+        let mut values = Vec::new();
+        for metric in self.iter() {
+            let series = self.evaluate(conn, &metric.name)?;
+            if let Some(&(week_start, value)) = series.last() {
+                values.push(MetricValue {
+                    name: metric.name.clone(),
+                    week_start,
+                    value,
+                    display: format_value(metric.format, value),
+                });
+            }
+        }
+        Ok(values)
+    }
+}
+
+/// Format a metric value for the terminal.
+pub fn format_value(format: MetricFormat, value: f64) -> String {
+    format_value_for(Target::Terminal, format, value)
+}
+
+/// Format a metric value using the number formatting policy for `target`.
+///
+/// Whole numbers and large values use the policy; small fractional values
+/// keep two decimal places.
+pub fn format_value_for(target: Target, format: MetricFormat, value: f64) -> String {
+    match format {
+        MetricFormat::Percent => format!("{:.1}%", value * 100.0),
+        MetricFormat::Number => {
+            let rounded = value.round();
+            if (value - rounded).abs() < 1e-9 || value.abs() >= 1000.0 {
+                let n = format::number_for(target, rounded.abs() as u64);
+                if rounded < 0.0 { format!("-{}", n) } else { n }
+            } else {
+                format!("{:.2}", value)
+            }
+        }
+    }
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Expr {
+    Number(f64),
+    Series(SeriesRef),
+    Metric(String),
+    Neg(Box<Expr>),
+    Binary(Op, Box<Expr>, Box<Expr>),
+    Lag(Box<Expr>, u32),
+    Sum(Box<Expr>, u32),
+}
+
+/// A weekly series read from the aggregates.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+enum SeriesRef {
+    /// `github`, `crates`, `installer`, or `all`.
+    Source(&'static str),
+    Crate(String),
+    Platform(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Expr {
+    fn has_series(&self) -> bool {
+        match self {
+            Expr::Number(_) => false,
+            Expr::Series(_) | Expr::Metric(_) => true,
+            Expr::Neg(expr) | Expr::Lag(expr, _) | Expr::Sum(expr, _) => expr.has_series(),
+            Expr::Binary(_, lhs, rhs) => lhs.has_series() || rhs.has_series(),
+        }
+    }
+
+    fn metric_references<'a>(&'a self, out: &mut Vec<&'a str>) {
+        match self {
+            Expr::Number(_) | Expr::Series(_) => {}
+            Expr::Metric(name) => out.push(name),
+            Expr::Neg(expr) | Expr::Lag(expr, _) | Expr::Sum(expr, _) => {
+                expr.metric_references(out)
+            }
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.metric_references(out);
+                rhs.metric_references(out);
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Str(String),
+    Punct(char),
+}
+
+/// A recursive descent parser for metric expressions.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn parse(input: &str) -> Result<Expr> {
+        let mut parser = Parser {
+            tokens: tokenize(input)?,
+            pos: 0,
+        };
+        let expr = parser.expr()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            bail!(
+                "unexpected {} after the end of the expression",
+                describe(token)
+            );
+        }
+        Ok(expr)
+    }
+
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Result<Token> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .cloned()
+            .context("unexpected end of expression")?;
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<()> {
+        match self.next()? {
+            Token::Punct(p) if p == c => Ok(()),
+            token => bail!("expected '{}', found {}", c, describe(&token)),
+        }
+    }
+
+    /// expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr> {
+        let mut lhs = self.term()?;
+        loop {
+            let op = if self.eat('+') {
+                Op::Add
+            } else if self.eat('-') {
+                Op::Sub
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.term()?));
+        }
+    }
+
+    /// term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expr> {
+        let mut lhs = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                Op::Mul
+            } else if self.eat('/') {
+                Op::Div
+            } else {
+                return Ok(lhs);
+            };
+            lhs = Expr::Binary(op, Box::new(lhs), Box::new(self.unary()?));
+        }
+    }
+
+    /// unary := '-' unary | atom
+    fn unary(&mut self) -> Result<Expr> {
+        if self.eat('-') {
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    /// atom := number | name | name '(' arguments ')' | '(' expr ')'
+    fn atom(&mut self) -> Result<Expr> {
+        match self.next()? {
+            Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Punct('(') => {
+                let expr = self.expr()?;
+                self.expect(')')?;
+                Ok(expr)
+            }
+            Token::Ident(name) if self.eat('(') => self.call(&name),
+            Token::Ident(name) => Ok(match name.as_str() {
+                "github" => Expr::Series(SeriesRef::Source("github")),
+                "crates" => Expr::Series(SeriesRef::Source("crates")),
+                "installer" => Expr::Series(SeriesRef::Source("installer")),
+                "total" => Expr::Series(SeriesRef::Source("all")),
+                _ => Expr::Metric(name),
+            }),
+            token => bail!("expected a value, found {}", describe(&token)),
+        }
+    }
+
+    /// Parse the arguments of a function call, after the opening parenthesis.
+    fn call(&mut self, name: &str) -> Result<Expr> {
+        let expr = match name {
+            "crate" | "platform" => {
+                let arg = match self.next()? {
+                    Token::Str(arg) => arg,
+                    token => bail!("{}() takes a quoted name, found {}", name, describe(&token)),
+                };
+                Expr::Series(if name == "crate" {
+                    SeriesRef::Crate(arg)
+                } else {
+                    SeriesRef::Platform(arg)
+                })
+            }
+            "lag" | "sum" => {
+                let expr = Box::new(self.expr()?);
+                self.expect(',')?;
+                let weeks = match self.next()? {
+                    Token::Number(n) if n.fract() == 0.0 && n >= 1.0 => n as u32,
+                    token => bail!(
+                        "{}() takes a whole number of weeks, found {}",
+                        name,
+                        describe(&token)
+                    ),
+                };
+                if name == "lag" {
+                    Expr::Lag(expr, weeks)
+                } else {
+                    Expr::Sum(expr, weeks)
+                }
+            }
+            _ => bail!("unknown function '{}'", name),
+        };
+        self.expect(')')?;
+        Ok(expr)
+    }
+}
+
+fn describe(token: &Token) -> String {
+    match token {
+        Token::Number(n) => format!("number {}", n),
+        Token::Ident(name) => format!("'{}'", name),
+        Token::Str(s) => format!("string \"{}\"", s),
+        Token::Punct(c) => format!("'{}'", c),
+    }
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = input.char_indices().peekable();
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() || c == '.' {
+            let mut end = start;
+            while let Some(&(idx, c)) = chars.peek() {
+                if !(c.is_ascii_digit() || c == '.') {
+                    break;
+                }
+                end = idx + c.len_utf8();
+                chars.next();
+            }
+            let text = &input[start..end];
+            let n = text
+                .parse()
+                .with_context(|| format!("invalid number '{}'", text))?;
+            tokens.push(Token::Number(n));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start;
+            while let Some(&(idx, c)) = chars.peek() {
+                if !(c.is_ascii_alphanumeric() || c == '_') {
+                    break;
+                }
+                end = idx + 1;
+                chars.next();
+            }
+            tokens.push(Token::Ident(input[start..end].to_string()));
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, c)) => s.push(c),
+                    None => bail!("unterminated string starting at position {}", start),
+                }
+            }
+            tokens.push(Token::Str(s));
+        } else if "+-*/(),".contains(c) {
+            chars.next();
+            tokens.push(Token::Punct(c));
+        } else {
+            bail!("unexpected '{}' at position {}", c, start);
+        }
+    }
+    Ok(tokens)
+}
+
+/// An intermediate result: numbers apply to every week of the series they're
+/// combined with.
+enum Value {
+    Constant(f64),
+    Series(Series),
+}
+
+struct Evaluator<'a> {
+    conn: &'a Connection,
+    metrics: &'a Metrics,
+    /// Series read so far, since metrics often use the same ones repeatedly.
+    series: HashMap<SeriesRef, Series>,
+}
+
+impl Evaluator<'_> {
+    fn series(&mut self, expr: &Expr) -> Result<Series> {
+        match self.value(expr)? {
+            Value::Series(series) => Ok(series),
+            // Definitions are checked to depend on a series.
+            Value::Constant(_) => unreachable!("metric expressions depend on a series"),
+        }
+    }
+
+    fn value(&mut self, expr: &Expr) -> Result<Value> {
+        Ok(match expr {
+            Expr::Number(n) => Value::Constant(*n),
+            Expr::Series(series) => Value::Series(self.load(series)?),
+            Expr::Metric(name) => {
+                let metrics = self.metrics;
+                Value::Series(self.series(&metrics.metrics[name].parsed)?)
+            }
+            Expr::Neg(expr) => match self.value(expr)? {
+                Value::Constant(n) => Value::Constant(-n),
+                Value::Series(series) => Value::Series(
+                    series
+                        .into_iter()
+                        .map(|(week, value)| (week, -value))
+                        .collect(),
+                ),
+            },
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (self.value(lhs)?, self.value(rhs)?);
+                binary(*op, lhs, rhs)
+            }
+            Expr::Lag(expr, weeks) => match self.value(expr)? {
+                Value::Constant(n) => Value::Constant(n),
+                Value::Series(series) => Value::Series(lag(&series, *weeks)),
+            },
+            Expr::Sum(expr, weeks) => match self.value(expr)? {
+                Value::Constant(n) => Value::Constant(n * *weeks as f64),
+                Value::Series(series) => Value::Series(trailing_sum(&series, *weeks)),
+            },
+        })
+    }
+
+    fn load(&mut self, series: &SeriesRef) -> Result<Series> {
+        if let Some(cached) = self.series.get(series) {
+            return Ok(cached.clone());
+        }
+        let loaded: Series = match series {
+            SeriesRef::Source(source) => analysis::weekly_trend(self.conn, source)?
+                .into_iter()
+                .map(|(week, downloads)| (week, downloads as f64))
+                .collect(),
+            SeriesRef::Crate(name) => self.query(
+                "crates",
+                "SELECT week_start, SUM(downloads) FROM weekly_stats
+                 WHERE source = 'crates' AND identifier = ?1
+                 GROUP BY week_start",
+                name,
+            )?,
+            SeriesRef::Platform(name) => self.query(
+                "github",
+                "SELECT week_start, downloads FROM weekly_platform_stats WHERE platform = ?1",
+                name,
+            )?,
+        };
+        self.series.insert(series.clone(), loaded.clone());
+        Ok(loaded)
+    }
+
+    /// Read a series for `name`, through `source`'s latest complete week.
+    fn query(&self, source: &str, query: &str, name: &str) -> Result<Series> {
+        let Some(last) = analysis::latest_complete_week(self.conn, source)? else {
+            return Ok(Series::new());
+        };
+        let mut stmt = self.conn.prepare(query)?;
+        let rows = stmt
+            .query_map([name], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed to read weekly downloads for '{}'", name))?;
+
+        let mut series = Series::new();
+        for (week, downloads) in rows {
+            let week = NaiveDate::parse_from_str(&week, "%Y-%m-%d")
+                .with_context(|| format!("failed to parse week '{}'", week))?;
+            if week <= last {
+                series.insert(week, downloads as f64);
+            }
+        }
+        Ok(series)
+    }
+}
+
+fn binary(op: Op, lhs: Value, rhs: Value) -> Value {
+    let apply = |a: f64, b: f64| match op {
+        Op::Add => Some(a + b),
+        Op::Sub => Some(a - b),
+        Op::Mul => Some(a * b),
+        Op::Div if b == 0.0 => None,
+        Op::Div => Some(a / b),
+    };
+    match (lhs, rhs) {
+        // Division of constants by zero can only come from a literal, so
+        // produce infinity like Rust does rather than a missing week.
+        (Value::Constant(a), Value::Constant(b)) => Value::Constant(apply(a, b).unwrap_or(a / b)),
+        (Value::Series(a), Value::Constant(b)) => Value::Series(
+            a.into_iter()
+                .filter_map(|(week, a)| Some((week, apply(a, b)?)))
+                .collect(),
+        ),
+        (Value::Constant(a), Value::Series(b)) => Value::Series(
+            b.into_iter()
+                .filter_map(|(week, b)| Some((week, apply(a, b)?)))
+                .collect(),
+        ),
+        (Value::Series(a), Value::Series(b)) => Value::Series(
+            a.into_iter()
+                .filter_map(|(week, a)| Some((week, apply(a, *b.get(&week)?)?)))
+                .collect(),
+        ),
+    }
+}
+
+/// Shift a series `weeks` weeks later, dropping weeks past its end.
+fn lag(series: &Series, weeks: u32) -> Series {
+    let Some(&last) = series.keys().next_back() else {
+        return Series::new();
+    };
+    let offset = Duration::weeks(weeks.into());
+    series
+        .iter()
+        .filter_map(|(&week, &value)| Some((week.checked_add_signed(offset)?, value)))
+        .filter(|(week, _)| *week <= last)
+        .collect()
+}
+
+/// Sum each week with the `weeks - 1` weeks before it, for weeks where all of
+/// them have values.
+fn trailing_sum(series: &Series, weeks: u32) -> Series {
+    series
+        .keys()
+        .filter_map(|&week| {
+            let sum = (0..weeks)
+                .map(|back| {
+                    let earlier = week.checked_sub_signed(Duration::weeks(back.into()))?;
+                    series.get(&earlier)
+                })
+                .sum::<Option<f64>>()?;
+            Some((week, sum))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{aggregate, db};
+
+    fn metrics(definitions: &[(&str, &str)]) -> Result<Metrics> {
+        let config = definitions
+            .iter()
+            .map(|(name, expr)| {
+                (
+                    name.to_string(),
+                    MetricConfig {
+                        expr: expr.to_string(),
+                        description: None,
+                        format: MetricFormat::Number,
+                    },
+                )
+            })
+            .collect();
+        Metrics::new(&config)
+    }
+
+    #[test]
+    fn test_parse() {
+        let series = |source| Box::new(Expr::Series(SeriesRef::Source(source)));
+        assert_eq!(
+            Parser::parse("github - crates / 2").unwrap(),
+            Expr::Binary(
+                Op::Sub,
+                series("github"),
+                Box::new(Expr::Binary(
+                    Op::Div,
+                    series("crates"),
+                    Box::new(Expr::Number(2.0))
+                )),
+            )
+        );
+        assert_eq!(
+            Parser::parse("lag(crate(\"cargo-nextest\"), 4)").unwrap(),
+            Expr::Lag(
+                Box::new(Expr::Series(SeriesRef::Crate("cargo-nextest".to_string()))),
+                4
+            )
+        );
+
+        for invalid in [
+            "github +",
+            "(github",
+            "github crates",
+            "lag(github, 1.5)",
+            "crate(nextest)",
+            "median(github)",
+            "github % 2",
+        ] {
+            assert!(
+                Parser::parse(invalid).is_err(),
+                "{} should not parse",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn test_check_definitions() {
+        metrics(&[
+            ("share", "github / total"),
+            ("share_4w", "sum(share, 4) / 4"),
+        ])
+        .unwrap();
+
+        let error = |definitions| metrics(definitions).unwrap_err().to_string();
+        assert!(error(&[("a", "b + 1"), ("b", "a")]).contains("a -> b -> a"));
+        assert!(error(&[("a", "c")]).contains("not a series or a defined metric"));
+        assert!(error(&[("a", "1 + 2")]).contains("doesn't depend on any series"));
+        assert!(error(&[("total", "github")]).contains("invalid metric name"));
+        assert!(error(&[("a-b", "github")]).contains("invalid metric name"));
+    }
+
+    #[test]
+    fn test_evaluate() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        // Three complete weeks starting Monday May 4, 2026, with the crate's
+        // daily downloads doubling each week.
+        for day in 0..21 {
+            let date = NaiveDate::from_ymd_opt(2026, 5, 4).unwrap() + Duration::days(day);
+            let downloads = 10 << (day / 7);
+            db::insert_crates_download(&conn, date, "cargo-nextest", Some("0.9.100"), downloads)
+                .unwrap();
+            db::insert_crates_download(&conn, date, "nextest-runner", Some("0.1.0"), 10).unwrap();
+        }
+        aggregate::compute_all_weekly(&conn, &Default::default()).unwrap();
+
+        // Weeks shifted past the last representable date are dropped.
+        let far = metrics(&[
+            ("lag_far", "lag(crates, 4000000000)"),
+            ("sum_far", "sum(crates, 4000000000)"),
+        ])
+        .unwrap();
+        assert_eq!(far.evaluate(&conn, "lag_far").unwrap(), []);
+        assert_eq!(far.evaluate(&conn, "sum_far").unwrap(), []);
+
+        let metrics = metrics(&[
+            ("share", "crate(\"cargo-nextest\") / crates"),
+            (
+                "growth",
+                "crate(\"cargo-nextest\") / lag(crate(\"cargo-nextest\"), 1) - 1",
+            ),
+            ("two_weeks", "sum(crates, 2)"),
+            ("missing", "crate(\"nope\") + 1"),
+        ])
+        .unwrap();
+        let week = |day| NaiveDate::from_ymd_opt(2026, 5, day).unwrap();
+
+        let share = metrics.evaluate(&conn, "share").unwrap();
+        assert_eq!(
+            share,
+            [(week(4), 0.5), (week(11), 20.0 / 30.0), (week(18), 0.8)]
+        );
+        assert_eq!(
+            metrics.evaluate(&conn, "growth").unwrap(),
+            [(week(11), 1.0), (week(18), 1.0)]
+        );
+        assert_eq!(
+            metrics.evaluate(&conn, "two_weeks").unwrap(),
+            [(week(11), 350.0), (week(18), 560.0)]
+        );
+        assert_eq!(metrics.evaluate(&conn, "missing").unwrap(), []);
+        assert!(metrics.evaluate(&conn, "unknown").is_err());
+
+        let latest = metrics.latest(&conn).unwrap();
+        assert_eq!(
+            latest
+                .iter()
+                .map(|value| (value.name.as_str(), value.display.as_str()))
+                .collect::<Vec<_>>(),
+            [("growth", "1"), ("share", "0.80"), ("two_weeks", "560")]
+        );
+    }
+
+    #[test]
+    fn test_format_value() {
+        assert_eq!(format_value(MetricFormat::Percent, 0.4251), "42.5%");
+        assert_eq!(format_value(MetricFormat::Number, 1234.4), "1,234");
+        assert_eq!(format_value(MetricFormat::Number, -20.0), "-20");
+        assert_eq!(format_value(MetricFormat::Number, 0.1234), "0.12");
+    }
+}
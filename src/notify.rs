@@ -0,0 +1,738 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Notifications after collection.
+//!
+//! Each configured notification channel renders a message from the collection
+//! summary using a small template language:
+//!
+//! - `{{ path.to.field }}` inserts a field from the summary.
+//! - `{{ field | number }}` inserts a number formatted with the
+//!   `notifications` formatting policy.
+//! - `{{#each sources}} ... {{/each}}` repeats a block for each element of an
+//!   array, with the element's fields in scope.
+//!
+//! The same language is used for the HTML report, where inserted values are
+//! escaped unless they're given the `raw` filter, as in `{{ growth | raw }}`.
+//!
+//! Channels with a URL have their messages posted to it: Slack and Discord
+//! incoming webhooks get the message in the shape they expect, and generic
+//! webhooks get the message along with the full summary as JSON. Failed
+//! collections are notified too, with a fixed message rather than the
+//! template, so broken collection doesn't go unnoticed.
+
+use crate::{
+    config::{NotificationConfig, NotificationKind},
+    format::{self, Target},
+    http,
+    output::status,
+    retry,
+    summary::CollectionSummary,
+    table::html_escape,
+};
+use anyhow::{Context, Result, bail};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::env;
+
+/// The longest message Discord accepts, in characters.
+const DISCORD_MAX_LENGTH: usize = 2000;
+
+const SLACK_TEMPLATE: &str = "Download stats for {{date}}: {{records | number}} records collected from \
+     {{#each sources}}{{kind}}:{{name}} {{/each}}\
+     {{#each unexpected_owners}}\n:warning: unexpected owner {{login}} added to {{crate_name}}{{/each}}\
+     {{#each anomalies}}\n:bar_chart: {{source}}: a {{kind}} to {{downloads | number}} downloads \
+     in the week of {{week_start}}{{/each}}\
+     {{#each alerts}}\n:rotating_light: {{name}}: {{message}}{{/each}}\
+     {{#each failures}}\n:x: {{kind}}:{{name}} failed: {{error}}{{/each}}";
+
+const DISCORD_TEMPLATE: &str = "**Download stats for {{date}}**\n\
+     {{#each sources}}- {{kind}}:{{name}}: {{downloads | number}} downloads\n{{/each}}\
+     {{#each unexpected_owners}}:warning: unexpected owner {{login}} added to {{crate_name}}\n{{/each}}\
+     {{#each anomalies}}:bar_chart: {{source}}: a {{kind}} to {{downloads | number}} downloads \
+     in the week of {{week_start}}\n{{/each}}\
+     {{#each alerts}}:rotating_light: **{{name}}**: {{message}}\n{{/each}}\
+     {{#each failures}}:x: {{kind}}:{{name}} failed: {{error}}\n{{/each}}";
+
+const EMAIL_TEMPLATE: &str = "Download statistics collected on {{date}}.\n\
+     \n\
+     Records inserted: {{records | number}}\n\
+     Downloads reported: {{downloads | number}}\n\
+     \n\
+     Per source:\n\
+     {{#each sources}}  {{kind}}:{{name}}: {{records | number}} records, \
+     {{downloads | number}} downloads\n\
+     {{#each archived}}    no longer listed: {{release_tag}}/{{asset_name}} \
+     (last seen {{last_seen}})\n{{/each}}\
+     {{#each owner_changes}}    owner {{change}}: {{login}} ({{kind}})\n{{/each}}\
+     {{#each tombstones}}    version {{event}}: {{artifact}}\n{{/each}}{{/each}}\
+     {{#each failures}}  {{kind}}:{{name}}: failed: {{error}}\n{{/each}}\
+     \n\
+     Largest weekly gains:\n\
+     {{#each movers.gains}}  {{category}} {{identifier}}: {{previous | number}} -> \
+     {{downloads | number}}\n{{/each}}\
+     Largest weekly losses:\n\
+     {{#each movers.losses}}  {{category}} {{identifier}}: {{previous | number}} -> \
+     {{downloads | number}}\n{{/each}}\
+     Metrics:\n\
+     {{#each metrics}}  {{name}}: {{display}} (week of {{week_start}})\n{{/each}}\
+     Anomalies:\n\
+     {{#each anomalies}}  {{source}}: a {{kind}} to {{downloads | number}} downloads in the week \
+     of {{week_start}}\n{{/each}}\
+     Alerts:\n\
+     {{#each alerts}}  {{name}}: {{message}}\n{{/each}}\
+     \n\
+     --\n\
+     {{provenance}}\n";
+
+const WEBHOOK_TEMPLATE: &str = "Download stats for {{date}}: {{records | number}} records \
+     collected, {{headline | number}} downloads in total\
+     {{#each alerts}}\nAlert {{name}}: {{message}}{{/each}}\
+     {{#each failures}}\n{{kind}}:{{name}} failed: {{error}}{{/each}}";
+
+impl NotificationKind {
+    /// The template used when a channel doesn't specify one.
+    pub fn default_template(self) -> &'static str {
+        match self {
+            NotificationKind::Slack => SLACK_TEMPLATE,
+            NotificationKind::Discord => DISCORD_TEMPLATE,
+            NotificationKind::Email => EMAIL_TEMPLATE,
+            NotificationKind::Webhook => WEBHOOK_TEMPLATE,
+        }
+    }
+}
+
+/// A notification channel with its parsed template.
+#[derive(Debug)]
+pub struct Channel {
+    pub name: String,
+    pub kind: NotificationKind,
+    template: Template,
+    /// The URL messages are posted to, with any environment variable read.
+    url: Option<String>,
+}
+
+/// A rendered notification ready to be delivered.
+#[derive(Debug)]
+pub struct Message {
+    pub channel: String,
+    pub kind: NotificationKind,
+    pub body: String,
+    url: Option<String>,
+}
+
+/// What a notification is about, as sent to generic webhooks.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "lowercase")]
+pub enum Event<'a> {
+    /// Collection succeeded.
+    Collected { summary: &'a CollectionSummary },
+    /// Collection failed, as it has for `consecutive_failures` runs in a row.
+    Failed {
+        error: &'a str,
+        consecutive_failures: usize,
+    },
+}
+
+/// Parse the templates for all configured notification channels.
+///
+/// This is done up front so template errors and missing URLs are reported
+/// before collection starts.
+pub fn load_channels(configs: &[NotificationConfig]) -> Result<Vec<Channel>> {
+    configs
+        .iter()
+        .map(|config| {
+            let url = match config.url.as_deref() {
+                Some(_) if config.kind == NotificationKind::Email => {
+                    bail!(
+                        "notification '{}' is an email and can't be posted to a URL",
+                        config.name
+                    );
+                }
+                Some(url) => match url.strip_prefix("env:") {
+                    Some(var) => Some(env::var(var).with_context(|| {
+                        format!("{} must be set for notification '{}'", var, config.name)
+                    })?),
+                    None => Some(url.to_owned()),
+                },
+                None => None,
+            };
+            let source = config
+                .template
+                .as_deref()
+                .unwrap_or_else(|| config.kind.default_template());
+            let template = Template::parse(source).with_context(|| {
+                format!(
+                    "failed to parse template for notification '{}'",
+                    config.name
+                )
+            })?;
+            Ok(Channel {
+                name: config.name.clone(),
+                kind: config.kind,
+                template,
+                url,
+            })
+        })
+        .collect()
+}
+
+/// Render messages for all channels from a collection summary.
+pub fn render_all(channels: &[Channel], summary: &CollectionSummary) -> Result<Vec<Message>> {
+    let data = serde_json::to_value(summary).context("failed to serialize summary")?;
+    channels
+        .iter()
+        .map(|channel| {
+            let body = channel
+                .template
+                .render(&data)
+                .with_context(|| format!("failed to render notification '{}'", channel.name))?;
+            Ok(Message {
+                channel: channel.name.clone(),
+                kind: channel.kind,
+                body,
+                url: channel.url.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Render messages about a failed collection for all channels.
+///
+/// Templates describe successful runs, so every channel gets the same
+/// message, with the error and how many runs in a row have failed.
+pub fn render_failure(
+    channels: &[Channel],
+    error: &str,
+    consecutive_failures: usize,
+) -> Vec<Message> {
+    let text = format!(
+        "Download stats collection failed ({} in a row): {}",
+        consecutive_failures, error
+    );
+    channels
+        .iter()
+        .map(|channel| {
+            let body = match channel.kind {
+                NotificationKind::Slack | NotificationKind::Discord => {
+                    format!(":rotating_light: {}", text)
+                }
+                NotificationKind::Email | NotificationKind::Webhook => text.clone(),
+            };
+            Message {
+                channel: channel.name.clone(),
+                kind: channel.kind,
+                body,
+                url: channel.url.clone(),
+            }
+        })
+        .collect()
+}
+
+/// Post each message with a URL to its channel.
+///
+/// Notifications are a side effect of collection, so failing to deliver one
+/// is a warning rather than an error.
+pub async fn deliver_all(messages: &[Message], event: &Event<'_>) {
+    let client = http::client();
+    for message in messages {
+        let Some(url) = &message.url else {
+            continue;
+        };
+        match deliver(&client, url, message, event).await {
+            Ok(()) => status!("  Sent notification '{}'", message.channel),
+            Err(err) => eprintln!(
+                "\nWarning: failed to send notification '{}': {:#}",
+                message.channel, err
+            ),
+        }
+    }
+}
+
+async fn deliver(
+    client: &reqwest::Client,
+    url: &str,
+    message: &Message,
+    event: &Event<'_>,
+) -> Result<()> {
+    let payload = payload(message, event)?;
+    let description = format!("notification '{}'", message.channel);
+    let response = retry::send_secret(&description, || Ok(client.post(url).json(&payload)))
+        .await
+        .context("failed to post notification")?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        bail!("webhook responded with status {}: {}", status, body);
+    }
+    Ok(())
+}
+
+/// The JSON body posted for a message.
+fn payload(message: &Message, event: &Event<'_>) -> Result<Value> {
+    Ok(match message.kind {
+        NotificationKind::Slack => json!({ "text": message.body }),
+        NotificationKind::Discord => {
+            let content = match message.body.char_indices().nth(DISCORD_MAX_LENGTH) {
+                Some((idx, _)) => {
+                    let end = message.body[..idx]
+                        .char_indices()
+                        .next_back()
+                        .map_or(0, |(idx, _)| idx);
+                    format!("{}…", &message.body[..end])
+                }
+                None => message.body.clone(),
+            };
+            json!({ "content": content })
+        }
+        NotificationKind::Webhook => {
+            let mut payload = serde_json::to_value(event).context("failed to serialize summary")?;
+            payload["message"] = Value::String(message.body.clone());
+            payload
+        }
+        NotificationKind::Email => bail!("email notifications can't be posted to a URL"),
+    })
+}
+
+/// A parsed message template.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Field {
+        path: String,
+        filter: Option<Filter>,
+    },
+    Each {
+        path: String,
+        body: Vec<Node>,
+    },
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Filter {
+    Number,
+    /// Insert the value without escaping it, in HTML.
+    Raw,
+}
+
+impl Template {
+    /// Parse a template string.
+    pub fn parse(source: &str) -> Result<Self> {
+        // Stack of open `each` blocks: (path, nodes collected so far).
+        let mut stack: Vec<(Option<String>, Vec<Node>)> = vec![(None, Vec::new())];
+        let mut rest = source;
+
+        while let Some(start) = rest.find("{{") {
+            if start > 0 {
+                push_node(&mut stack, Node::Text(rest[..start].to_string()));
+            }
+            let after = &rest[start + 2..];
+            let end = after
+                .find("}}")
+                .with_context(|| format!("unclosed tag near '{}'", truncate(&rest[start..])))?;
+            let tag = after[..end].trim();
+            rest = &after[end + 2..];
+
+            if let Some(path) = tag.strip_prefix("#each ") {
+                stack.push((Some(path.trim().to_string()), Vec::new()));
+            } else if tag == "/each" {
+                if stack.len() < 2 {
+                    bail!("'{{{{/each}}}}' without matching '{{{{#each}}}}'");
+                }
+                let (path, body) = stack.pop().expect("stack has at least two entries");
+                let path = path.expect("only the root entry has no path");
+                push_node(&mut stack, Node::Each { path, body });
+            } else {
+                let (path, filter) = match tag.split_once('|') {
+                    Some((path, filter)) => (path.trim(), Some(parse_filter(filter.trim())?)),
+                    None => (tag, None),
+                };
+                if path.is_empty() {
+                    bail!("empty field name in template");
+                }
+                push_node(
+                    &mut stack,
+                    Node::Field {
+                        path: path.to_string(),
+                        filter,
+                    },
+                );
+            }
+        }
+
+        if !rest.is_empty() {
+            push_node(&mut stack, Node::Text(rest.to_string()));
+        }
+
+        if stack.len() != 1 {
+            bail!("unclosed '{{{{#each}}}}' block");
+        }
+        let (_, nodes) = stack.pop().expect("root entry is present");
+        Ok(Self { nodes })
+    }
+
+    /// Render the template against a JSON data model.
+    pub fn render(&self, data: &Value) -> Result<String> {
+        let mut out = String::new();
+        render_nodes(&self.nodes, &mut vec![data], false, &mut out)?;
+        Ok(out)
+    }
+
+    /// Render the template as HTML, escaping every inserted value that isn't
+    /// given the `raw` filter.
+    pub fn render_html(&self, data: &Value) -> Result<String> {
+        let mut out = String::new();
+        render_nodes(&self.nodes, &mut vec![data], true, &mut out)?;
+        Ok(out)
+    }
+}
+
+fn push_node(stack: &mut [(Option<String>, Vec<Node>)], node: Node) {
+    stack
+        .last_mut()
+        .expect("template stack is never empty")
+        .1
+        .push(node);
+}
+
+fn parse_filter(name: &str) -> Result<Filter> {
+    match name {
+        "number" => Ok(Filter::Number),
+        "raw" => Ok(Filter::Raw),
+        _ => bail!("unknown template filter '{}'", name),
+    }
+}
+
+fn truncate(s: &str) -> &str {
+    match s.char_indices().nth(20) {
+        Some((idx, _)) => &s[..idx],
+        None => s,
+    }
+}
+
+fn render_nodes<'a>(
+    nodes: &'a [Node],
+    scopes: &mut Vec<&'a Value>,
+    escape: bool,
+    out: &mut String,
+) -> Result<()> {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Field { path, filter } => {
+                let value = lookup(scopes, path)?;
+                let text = format_value(value, *filter, path)?;
+                if escape && !matches!(filter, Some(Filter::Raw)) {
+                    out.push_str(&html_escape(&text));
+                } else {
+                    out.push_str(&text);
+                }
+            }
+            Node::Each { path, body } => {
+                let value = lookup(scopes, path)?;
+                let items = value
+                    .as_array()
+                    .with_context(|| format!("field '{}' is not a list", path))?;
+                for item in items {
+                    scopes.push(item);
+                    render_nodes(body, scopes, escape, out)?;
+                    scopes.pop();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Look up a dotted path, searching from the innermost scope outwards.
+fn lookup<'a>(scopes: &[&'a Value], path: &str) -> Result<&'a Value> {
+    for scope in scopes.iter().rev() {
+        let mut current = Some(*scope);
+        for segment in path.split('.') {
+            current = current.and_then(|v| v.get(segment));
+        }
+        if let Some(value) = current {
+            return Ok(value);
+        }
+    }
+    bail!("unknown field '{}'", path)
+}
+
+fn format_value(value: &Value, filter: Option<Filter>, path: &str) -> Result<String> {
+    match (value, filter) {
+        (Value::Number(n), Some(Filter::Number)) => match n.as_u64() {
+            Some(n) => Ok(format::number_for(Target::Notifications, n)),
+            None => Ok(n.to_string()),
+        },
+        (_, Some(Filter::Number)) => bail!("field '{}' is not a number", path),
+        (Value::String(s), _) => Ok(s.clone()),
+        (Value::Number(n), _) => Ok(n.to_string()),
+        (Value::Bool(b), _) => Ok(b.to_string()),
+        (Value::Null, _) => Ok(String::new()),
+        (Value::Array(_) | Value::Object(_), _) => {
+            bail!("field '{}' is not a scalar value", path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::summary::SourceSummary;
+    use chrono::NaiveDate;
+
+    fn sample_summary() -> CollectionSummary {
+        let mut summary = CollectionSummary::new(NaiveDate::from_ymd_opt(2025, 11, 19).unwrap());
+        let owner_change = crate::db::OwnerChange {
+            date: NaiveDate::from_ymd_opt(2025, 11, 19).unwrap(),
+            crate_name: "cargo-nextest".to_string(),
+            login: "someone".to_string(),
+            kind: "user".to_string(),
+            change: crate::db::OwnerChangeKind::Added,
+        };
+        summary.push(SourceSummary {
+            kind: "github",
+            name: "nextest-rs/nextest".to_string(),
+            records: 120,
+            downloads: 1_234_567,
+            archived: vec![crate::db::ArchivedAsset {
+                release_tag: "cargo-nextest-0.9.1".to_string(),
+                asset_name: "cargo-nextest-0.9.1-x86_64-unknown-linux-gnu.tar.gz".to_string(),
+                last_seen: NaiveDate::from_ymd_opt(2025, 11, 18).unwrap(),
+            }],
+            owner_changes: Vec::new(),
+            tombstones: Vec::new(),
+            latest_date: Some(NaiveDate::from_ymd_opt(2025, 11, 19).unwrap()),
+            etag: Some("\"abc123\"".to_string()),
+            skipped: Vec::new(),
+        });
+        summary.push(SourceSummary {
+            kind: "crates",
+            name: "cargo-nextest".to_string(),
+            records: 365,
+            downloads: 8_000,
+            archived: Vec::new(),
+            owner_changes: vec![owner_change.clone()],
+            tombstones: Vec::new(),
+            latest_date: Some(NaiveDate::from_ymd_opt(2025, 11, 18).unwrap()),
+            etag: None,
+            skipped: Vec::new(),
+        });
+        summary.unexpected_owners.push(owner_change);
+        summary.metrics.push(crate::metrics::MetricValue {
+            name: "github_share".to_string(),
+            week_start: NaiveDate::from_ymd_opt(2025, 11, 10).unwrap(),
+            value: 0.75,
+            display: "75.0%".to_string(),
+        });
+        summary.anomalies.push(crate::analysis::Anomaly {
+            source: "github",
+            week_start: NaiveDate::from_ymd_opt(2025, 11, 10).unwrap(),
+            downloads: 90_000,
+            mean: 30_000.0,
+            stddev: 5_000.0,
+            z_score: 12.0,
+            kind: crate::analysis::AnomalyKind::Spike,
+        });
+        summary.alerts.push(crate::alerts::Alert {
+            name: "crates-drop".to_string(),
+            source: "crates".to_string(),
+            direction: crate::config::AlertDirection::Drop,
+            week_start: NaiveDate::from_ymd_opt(2025, 11, 10).unwrap(),
+            downloads: 650,
+            baseline: 1000,
+            baseline_weeks: 4,
+            change_percent: -35.0,
+            message: "crates downloads dropped".to_string(),
+        });
+        summary.failures.push(crate::summary::SourceFailure {
+            kind: "crates",
+            name: "nextest-runner".to_string(),
+            error: "crates.io is down".to_string(),
+        });
+        summary
+    }
+
+    #[test]
+    fn test_render_fields_and_each() {
+        let channels = load_channels(&[NotificationConfig {
+            name: "test".to_string(),
+            kind: NotificationKind::Slack,
+            template: Some(
+                "{{date}} {{ records | number }}:{{#each sources}} {{name}}={{downloads|number}}{{/each}}"
+                    .to_string(),
+            ),
+            url: None,
+        }])
+        .unwrap();
+        let messages = render_all(&channels, &sample_summary()).unwrap();
+        assert_eq!(
+            messages[0].body,
+            "2025-11-19 485: nextest-rs/nextest=1,234,567 cargo-nextest=8,000"
+        );
+    }
+
+    #[test]
+    fn test_default_templates_render() {
+        let summary = serde_json::to_value(sample_summary()).unwrap();
+        for kind in [
+            NotificationKind::Slack,
+            NotificationKind::Discord,
+            NotificationKind::Email,
+        ] {
+            let template = Template::parse(kind.default_template()).unwrap();
+            let body = template.render(&summary).unwrap();
+            assert!(
+                body.contains("someone"),
+                "{:?} template mentions the new owner",
+                kind
+            );
+            assert!(
+                body.contains("github: a spike to 90,000 downloads in the week of 2025-11-10"),
+                "{:?} template mentions the anomaly",
+                kind
+            );
+            assert!(
+                body.contains("crates-drop"),
+                "{:?} template mentions the alert",
+                kind
+            );
+            assert!(
+                body.contains("crates:nextest-runner") && body.contains("crates.io is down"),
+                "{:?} template mentions the failed source",
+                kind
+            );
+        }
+        let body = Template::parse(NotificationKind::Webhook.default_template())
+            .unwrap()
+            .render(&summary)
+            .unwrap();
+        assert!(body.starts_with("Download stats for 2025-11-19: 485 records collected"));
+        assert!(body.ends_with(
+            "\nAlert crates-drop: crates downloads dropped\n\
+             crates:nextest-runner failed: crates.io is down"
+        ));
+
+        let body = Template::parse(NotificationKind::Email.default_template())
+            .unwrap()
+            .render(&summary)
+            .unwrap();
+        assert!(body.contains("github_share: 75.0% (week of 2025-11-10)"));
+    }
+
+    #[test]
+    fn test_load_channels_urls() {
+        let config = |kind, url: &str| NotificationConfig {
+            name: "test".to_string(),
+            kind,
+            template: None,
+            url: Some(url.to_string()),
+        };
+        let channels = load_channels(&[config(
+            NotificationKind::Slack,
+            "https://hooks.slack.com/services/T0/B0/x",
+        )])
+        .unwrap();
+        assert_eq!(
+            channels[0].url.as_deref(),
+            Some("https://hooks.slack.com/services/T0/B0/x")
+        );
+
+        let err = load_channels(&[config(
+            NotificationKind::Webhook,
+            "env:DOWNLOAD_STATS_TEST_UNSET_WEBHOOK",
+        )])
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("DOWNLOAD_STATS_TEST_UNSET_WEBHOOK must be set"),
+            "{}",
+            err
+        );
+        assert!(load_channels(&[config(NotificationKind::Email, "https://example.com")]).is_err());
+    }
+
+    #[test]
+    fn test_payloads() {
+        let message = |kind, body: &str| Message {
+            channel: "test".to_string(),
+            kind,
+            body: body.to_string(),
+            url: Some("https://example.com".to_string()),
+        };
+        let summary = sample_summary();
+        let collected = Event::Collected { summary: &summary };
+
+        assert_eq!(
+            payload(&message(NotificationKind::Slack, "hi"), &collected).unwrap(),
+            json!({ "text": "hi" })
+        );
+        let long = "é".repeat(DISCORD_MAX_LENGTH + 1);
+        let content = payload(&message(NotificationKind::Discord, &long), &collected).unwrap();
+        let content = content["content"].as_str().unwrap();
+        assert_eq!(content.chars().count(), DISCORD_MAX_LENGTH);
+        assert!(content.ends_with("é…"));
+
+        let webhook = payload(&message(NotificationKind::Webhook, "hi"), &collected).unwrap();
+        assert_eq!(webhook["event"], "collected");
+        assert_eq!(webhook["message"], "hi");
+        assert_eq!(webhook["summary"]["records"], 485);
+        assert_eq!(webhook["summary"]["anomalies"][0]["kind"], "spike");
+
+        let channels = load_channels(&[NotificationConfig {
+            name: "test".to_string(),
+            kind: NotificationKind::Webhook,
+            template: None,
+            url: None,
+        }])
+        .unwrap();
+        let messages = render_failure(&channels, "crates.io is down", 3);
+        assert_eq!(
+            messages[0].body,
+            "Download stats collection failed (3 in a row): crates.io is down"
+        );
+        let failed = Event::Failed {
+            error: "crates.io is down",
+            consecutive_failures: 3,
+        };
+        assert_eq!(
+            payload(&messages[0], &failed).unwrap(),
+            json!({
+                "event": "failed",
+                "error": "crates.io is down",
+                "consecutive_failures": 3,
+                "message": messages[0].body,
+            })
+        );
+    }
+
+    #[test]
+    fn test_template_errors() {
+        assert!(Template::parse("{{#each sources}}").is_err());
+        assert!(Template::parse("{{/each}}").is_err());
+        assert!(Template::parse("{{date").is_err());
+        assert!(Template::parse("{{date | bogus}}").is_err());
+
+        let summary = serde_json::to_value(sample_summary()).unwrap();
+        let template = Template::parse("{{missing}}").unwrap();
+        assert!(template.render(&summary).is_err());
+        let template = Template::parse("{{sources}}").unwrap();
+        assert!(template.render(&summary).is_err());
+    }
+
+    #[test]
+    fn test_render_html() {
+        let data = serde_json::json!({"name": "<b>&</b>", "count": 1200});
+        let template = Template::parse("{{name}} {{name | raw}} {{count | number}}").unwrap();
+        assert_eq!(
+            template.render_html(&data).unwrap(),
+            "&lt;b&gt;&amp;&lt;/b&gt; <b>&</b> 1,200"
+        );
+        assert_eq!(template.render(&data).unwrap(), "<b>&</b> <b>&</b> 1,200");
+    }
+}
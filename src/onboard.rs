@@ -0,0 +1,129 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Setting up a new directory: the configuration, the database, and a first
+//! collection.
+//!
+//! `init` walks through each step, asking before it does anything unless
+//! `--yes` is given. Commands that need a configuration file offer the same
+//! steps when run in a terminal with neither a configuration file nor a
+//! database.
+
+use crate::{
+    commands::{self, CollectOptions},
+    config::Config,
+    db,
+};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use std::{
+    fs,
+    io::{self, IsTerminal, Write},
+};
+
+/// The configuration written by `init`, tracking nextest itself.
+pub const DEFAULT_CONFIG: &str = include_str!("../config.toml");
+
+/// Options for `init`.
+#[derive(Debug, Default)]
+pub struct InitOptions {
+    /// Take every step without asking.
+    pub yes: bool,
+    /// Leave out the first collection, which needs network access.
+    pub skip_collect: bool,
+}
+
+/// Set up the configuration file and database, then collect for the first
+/// time, asking before each step unless `options.yes` is set.
+pub async fn run_init(
+    config_path: &Utf8Path,
+    database: &Utf8Path,
+    options: &InitOptions,
+) -> Result<()> {
+    if !options.yes && !io::stdin().is_terminal() {
+        bail!("standard input isn't a terminal, so pass --yes to set up without asking");
+    }
+    let confirm = |question: &str| options.yes || ask(question);
+
+    let have_config = if config_path.exists() {
+        println!("Using the existing configuration at {}.", config_path);
+        true
+    } else if confirm(&format!(
+        "Create {} tracking nextest's GitHub releases and crates.io downloads?",
+        config_path
+    )) {
+        fs::write(config_path.as_std_path(), DEFAULT_CONFIG)
+            .with_context(|| format!("failed to write config file at {}", config_path))?;
+        println!("Created {}.", config_path);
+        true
+    } else {
+        false
+    };
+
+    let have_database = if database.exists() {
+        println!("Using the existing database at {}.", database);
+        true
+    } else if confirm(&format!("Create the database at {}?", database)) {
+        db::init_db(database)?;
+        println!("Created {}.", database);
+        true
+    } else {
+        false
+    };
+
+    let collected = if !have_config || !have_database || options.skip_collect {
+        false
+    } else if confirm("Collect download statistics now? This needs network access.") {
+        let config = Config::load(config_path).context("failed to load configuration")?;
+        commands::run_collect(database, &config, &CollectOptions::default()).await?;
+        true
+    } else {
+        false
+    };
+
+    println!("\nNext steps:");
+    if !have_config {
+        println!(
+            "  • Write a configuration file at {} (see the README)",
+            config_path
+        );
+    } else {
+        println!(
+            "  • Edit {} to track your own repositories and crates",
+            config_path
+        );
+    }
+    if !collected {
+        println!("  • Collect statistics with `collect`");
+    }
+    println!("  • Run `collect` daily, for example from cron or a scheduled workflow");
+    println!("  • Look at the latest weeks with `query weekly`");
+    println!("  • Draw charts with `charts --output charts`");
+    Ok(())
+}
+
+/// Ask a yes-or-no question on the terminal, defaulting to yes.
+fn ask(question: &str) -> bool {
+    print!("{} [Y/n] ", question);
+    _ = io::stdout().flush();
+    let mut answer = String::new();
+    // Without an answer, such as at the end of input, nothing is done.
+    if !matches!(io::stdin().read_line(&mut answer), Ok(read) if read > 0) {
+        return false;
+    }
+    matches!(
+        answer.trim().to_ascii_lowercase().as_str(),
+        "" | "y" | "yes"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_config() {
+        let config: Config = toml::from_str(DEFAULT_CONFIG).unwrap();
+        assert!(!config.source.is_empty());
+    }
+}
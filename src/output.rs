@@ -0,0 +1,72 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Standard output for people or for scripts.
+//!
+//! By default, commands narrate their progress on standard output. With the
+//! global `--quiet` (or `--machine`) flag the narration is left out, so that
+//! wrapper scripts only see data: a JSON summary for commands that produce
+//! one, or otherwise the path of each file written, one per line. Warnings go
+//! to standard error either way.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::{fmt, sync::OnceLock};
+
+static MODE: OnceLock<Mode> = OnceLock::new();
+
+/// What commands print on standard output.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// Progress messages for people.
+    #[default]
+    Narrate,
+    /// The path of each file written, one per line.
+    Paths,
+    /// Only the command's summary, as JSON.
+    Summary,
+}
+
+/// Set what commands print. Only the first call has an effect.
+pub fn set_mode(mode: Mode) {
+    _ = MODE.set(mode);
+}
+
+fn mode() -> Mode {
+    MODE.get().copied().unwrap_or_default()
+}
+
+/// Whether narration is left out.
+pub fn is_quiet() -> bool {
+    mode() != Mode::Narrate
+}
+
+/// Print a line of narration to standard output, unless in quiet mode.
+///
+/// Progress bars are hidden while the line is printed.
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if !$crate::output::is_quiet() {
+            $crate::progress::suspend(|| println!($($arg)*));
+        }
+    };
+}
+pub(crate) use status;
+
+/// Report a file that was written: `message` when narrating, or just the
+/// path when printing paths.
+pub fn written(path: impl fmt::Display, message: impl fmt::Display) {
+    match mode() {
+        Mode::Narrate => println!("{}", message),
+        Mode::Paths => println!("{}", path),
+        Mode::Summary => {}
+    }
+}
+
+/// Print a command's summary as JSON, if it's the only output.
+pub fn summary<T: Serialize>(summary: &T) -> Result<()> {
+    if mode() == Mode::Summary {
+        println!("{}", serde_json::to_string_pretty(summary)?);
+    }
+    Ok(())
+}
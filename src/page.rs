@@ -0,0 +1,304 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The static HTML report.
+//!
+//! `report html` writes a single page with the headline figure, growth and
+//! weekly statistics, charts, and data coverage. Charts are embedded as data
+//! URLs, so the page can be published as is, such as to GitHub Pages after
+//! each collection run. The page is rendered from a template in the
+//! notification template language, which `--template` replaces.
+
+use crate::{
+    analysis, charts,
+    config::{ChartFormat, ChartKind, ChartOutputConfig, ChartTheme, Config},
+    format::{self, Target},
+    headline,
+    metrics::Metrics,
+    notify::Template,
+    output,
+    provenance::Provenance,
+    query,
+    table::{OutputFormat, Report},
+};
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::Serialize;
+use std::fs;
+
+/// The template pages are rendered from unless `--template` is given.
+pub const DEFAULT_TEMPLATE: &str = include_str!("page.html");
+
+/// The charts on the page unless others are chosen.
+pub const DEFAULT_CHARTS: [ChartKind; 4] = [
+    ChartKind::WeeklyTrends,
+    ChartKind::SourceComparison,
+    ChartKind::VersionShare,
+    ChartKind::GithubByPlatform,
+];
+
+/// Options for `report html`.
+#[derive(Debug)]
+pub struct PageOptions {
+    /// Where the page is written.
+    pub output: Utf8PathBuf,
+    pub title: String,
+    /// A template to render instead of [`DEFAULT_TEMPLATE`].
+    pub template: Option<Utf8PathBuf>,
+    /// The charts to embed, in order.
+    pub charts: Vec<ChartKind>,
+    pub theme: ChartTheme,
+}
+
+/// What page templates are rendered from.
+#[derive(Debug, Serialize)]
+pub struct Page {
+    pub title: String,
+    /// When the page was generated, in UTC.
+    pub generated_at: String,
+    /// The headline total downloads.
+    pub headline: String,
+    /// Week-over-week and month-over-month growth of each source, as HTML.
+    pub growth: String,
+    /// Summary statistics of the weekly downloads of each source, as HTML.
+    pub weekly: String,
+    /// The downloads of the week in progress, projected to the full week, as
+    /// HTML, or empty if the latest week is complete.
+    pub current_week: String,
+    /// The first and latest dates with data for each source, and the days
+    /// missing, as HTML.
+    pub coverage: String,
+    pub charts: Vec<PageChart>,
+    pub provenance: Provenance,
+    /// The provenance as a single line.
+    pub footer: String,
+}
+
+/// A chart embedded in the page.
+#[derive(Debug, Serialize)]
+pub struct PageChart {
+    pub name: &'static str,
+    /// The image as a data URL, for the `src` of an `<img>`.
+    pub src: String,
+}
+
+/// Write the HTML report to `options.output`.
+pub fn run_html(conn: &Connection, config: &Config, options: &PageOptions) -> Result<()> {
+    // Read the template first, so mistakes in it are reported before charts
+    // are drawn.
+    let template = match &options.template {
+        Some(path) => {
+            let source = fs::read_to_string(path.as_std_path())
+                .with_context(|| format!("failed to read template at {}", path))?;
+            Template::parse(&source)
+                .with_context(|| format!("failed to parse template at {}", path))?
+        }
+        None => Template::parse(DEFAULT_TEMPLATE).expect("the default template is valid"),
+    };
+
+    let page = build_page(conn, config, options)?;
+    let html = template
+        .render_html(&serde_json::to_value(&page)?)
+        .context("failed to render report")?;
+
+    if let Some(parent) = options.output.parent()
+        && !parent.as_str().is_empty()
+    {
+        fs::create_dir_all(parent.as_std_path())
+            .with_context(|| format!("failed to create output directory at {}", parent))?;
+    }
+    fs::write(options.output.as_std_path(), html)
+        .with_context(|| format!("failed to write {}", options.output))?;
+    output::written(
+        &options.output,
+        format!("Wrote report to {}.", options.output),
+    );
+    Ok(())
+}
+
+/// Gather everything on the page.
+pub fn build_page(conn: &Connection, config: &Config, options: &PageOptions) -> Result<Page> {
+    let headline = headline::compute(conn, &config.headline)?;
+    let section = |title: &str, report: Report| {
+        Report {
+            title: Some(title.to_string()),
+            ..report
+        }
+        .render_for(OutputFormat::Html, Target::Reports)
+    };
+
+    let gaps = analysis::collection_gaps(conn, Utc::now().date_naive())?;
+    let coverage = if gaps.is_empty() {
+        Report::empty("No downloads collected yet.")
+    } else {
+        let mut table = query::freshness_table(&gaps);
+        table.title = None;
+        Report::default().table(table)
+    };
+
+    let current_week = match analysis::project_current_week(conn, "all")? {
+        Some(projection) => {
+            let mut table = query::projection_table(conn, &projection)?;
+            table.title = None;
+            let report = Report::default().table(table).note(format!(
+                "Projected from the day-of-week pattern of the previous {} weeks.",
+                analysis::PROJECTION_WEEKS
+            ));
+            section("Week in progress", report)?
+        }
+        None => String::new(),
+    };
+
+    let provenance = Provenance::collect(conn, config)?;
+    Ok(Page {
+        title: options.title.clone(),
+        generated_at: Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+        headline: format::number_for(Target::Reports, headline.total),
+        growth: section("Growth", query::query_growth(conn)?)?,
+        weekly: section("Weekly downloads", query::query_stats(conn)?)?,
+        current_week,
+        coverage: section("Data coverage", coverage)?,
+        charts: embed_charts(conn, config, options, &provenance)?,
+        footer: provenance.footer(),
+        provenance,
+    })
+}
+
+/// Draw the charts in a scratch directory and read them back as data URLs.
+fn embed_charts(
+    conn: &Connection,
+    config: &Config,
+    options: &PageOptions,
+    provenance: &Provenance,
+) -> Result<Vec<PageChart>> {
+    if options.charts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+        .map_err(|path| anyhow::anyhow!("temporary directory {:?} isn't UTF-8", path))?
+        .join(format!("download-stats-report-{}", std::process::id()));
+    let output = ChartOutputConfig {
+        name: "report".to_string(),
+        charts: options.charts.clone(),
+        theme: options.theme,
+        metrics: Some(Vec::new()),
+        custom: Some(Vec::new()),
+        ..ChartOutputConfig::default_at(dir.clone())
+    };
+    let result = Metrics::new(&config.metrics)
+        .and_then(|metrics| {
+            charts::generate_charts(
+                conn,
+                &config.charts,
+                &metrics,
+                &config.headline,
+                &output,
+                provenance,
+            )
+        })
+        .and_then(|()| read_charts(&dir, &options.charts));
+    _ = fs::remove_dir_all(dir.as_std_path());
+    result
+}
+
+fn read_charts(dir: &Utf8Path, kinds: &[ChartKind]) -> Result<Vec<PageChart>> {
+    let mut charts = Vec::new();
+    for &kind in kinds {
+        let file_name = kind.file_name(ChartFormat::Png);
+        let path = dir.join(&file_name);
+        // Charts without any data to draw aren't written.
+        if !path.exists() {
+            continue;
+        }
+        let bytes =
+            fs::read(path.as_std_path()).with_context(|| format!("failed to read {}", path))?;
+        // The downloads badge is always an SVG.
+        let mime = if file_name.ends_with(".svg") {
+            "image/svg+xml"
+        } else {
+            "image/png"
+        };
+        charts.push(PageChart {
+            name: kind.as_str(),
+            src: format!("data:{};base64,{}", mime, STANDARD.encode(bytes)),
+        });
+    }
+    Ok(charts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_build_page() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let week = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        db::insert_weekly_stat(&conn, week(3), "crates", "cargo-nextest", 100).unwrap();
+        db::insert_weekly_stat(&conn, week(10), "crates", "cargo-nextest", 120).unwrap();
+        db::insert_installer_download(&conn, week(12), "get.nexte.st", 5).unwrap();
+
+        let options = PageOptions {
+            output: "index.html".into(),
+            title: "Stats <&> more".to_string(),
+            template: None,
+            charts: Vec::new(),
+            theme: ChartTheme::default(),
+        };
+        let page = build_page(&conn, &Config::default(), &options).unwrap();
+        assert!(page.weekly.starts_with("<h2>Weekly downloads</h2>"));
+        assert_eq!(page.current_week, "");
+        assert!(
+            page.coverage
+                .contains("<td>installer</td><td>2025-11-12</td>")
+        );
+
+        let html = Template::parse(DEFAULT_TEMPLATE)
+            .unwrap()
+            .render_html(&serde_json::to_value(&page).unwrap())
+            .unwrap();
+        assert!(html.contains("<title>Stats &lt;&amp;&gt; more</title>"));
+        assert!(html.contains("<h2>Growth</h2>"));
+        assert!(html.contains(&page.footer));
+
+        // Once the week in progress can be projected, it's shown.
+        db::insert_crates_download(&conn, week(11), "cargo-nextest", None, 20).unwrap();
+        for day in 3..=9 {
+            db::insert_rolling_stat(&conn, week(day), "crates", 10.0, 0.0, 0.0).unwrap();
+            db::insert_rolling_stat(&conn, week(day), "installer", 1.0, 0.0, 0.0).unwrap();
+        }
+        let page = build_page(&conn, &Config::default(), &options).unwrap();
+        assert!(
+            page.current_week.starts_with("<h2>Week in progress</h2>"),
+            "{}",
+            page.current_week
+        );
+        // Mondays and Tuesdays had 20 of 70 crate downloads.
+        assert!(page.current_week.contains("420"), "{}", page.current_week);
+
+        // Charts are embedded with their image type.
+        let options = PageOptions {
+            charts: vec![ChartKind::DownloadsBadge, ChartKind::WeeklyTrends],
+            ..options
+        };
+        let page = build_page(&conn, &Config::default(), &options).unwrap();
+        let embedded: Vec<_> = page
+            .charts
+            .iter()
+            .map(|chart| (chart.name, chart.src.split_once(',').unwrap().0))
+            .collect();
+        assert_eq!(
+            embedded,
+            [
+                ("downloads-badge", "data:image/svg+xml;base64"),
+                ("weekly-trends", "data:image/png;base64"),
+            ]
+        );
+    }
+}
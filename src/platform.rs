@@ -0,0 +1,379 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Mapping of release asset names to target platforms.
+//!
+//! Release assets are named like `cargo-nextest-0.9.100-x86_64-unknown-linux-gnu.tar.gz`.
+//! The target triple is extracted from the name and mapped to a short platform
+//! label such as `linux-x86_64` or `mac-universal`. The file extension
+//! determines the asset's [`AssetFormat`].
+
+use anyhow::{Result, bail};
+use std::str::FromStr;
+
+/// Label used for assets whose platform can't be determined.
+pub const UNKNOWN_PLATFORM: &str = "other";
+
+/// Architectures that can start a target triple in an asset name.
+const ARCHES: &[&str] = &[
+    "x86_64",
+    "aarch64",
+    "i686",
+    "i586",
+    "armv7",
+    "arm",
+    "riscv64gc",
+    "powerpc64le",
+    "s390x",
+    "universal",
+];
+
+/// Known file extensions, longest first so `.tar.gz` wins over `.gz`.
+const EXTENSIONS: &[&str] = &[
+    ".sigstore.json",
+    ".intoto.jsonl",
+    ".sigstore",
+    ".minisig",
+    ".tar.gz",
+    ".tar.xz",
+    ".tar.zst",
+    ".tgz",
+    ".zip",
+    ".sha256",
+    ".sha512",
+    ".b2",
+    ".sig",
+    ".asc",
+    ".exe",
+    ".gz",
+];
+
+/// The platform an asset was built for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Platform {
+    /// The full target triple, e.g. `x86_64-unknown-linux-gnu`.
+    pub target: String,
+    /// The operating system, e.g. `linux`, `linux-musl`, `mac`, or `windows`.
+    pub os: &'static str,
+    /// The architecture, e.g. `x86_64`, `aarch64`, or `universal`.
+    pub arch: &'static str,
+}
+
+impl Platform {
+    /// Parse the platform from a release asset name.
+    pub fn from_asset_name(asset_name: &str) -> Option<Self> {
+        let stem = strip_extension(asset_name);
+        let target = find_target(stem)?;
+        let arch = ARCHES
+            .iter()
+            .copied()
+            .find(|arch| target.starts_with(&format!("{}-", arch)))?;
+        let os = os_for_target(target)?;
+
+        Some(Self {
+            target: target.to_string(),
+            os,
+            arch: normalize_arch(arch),
+        })
+    }
+
+    /// A short label combining the OS and architecture, e.g. `linux-x86_64`.
+    pub fn label(&self) -> String {
+        format!("{}-{}", self.os, self.arch)
+    }
+}
+
+/// Get the platform label for an asset name, or [`UNKNOWN_PLATFORM`].
+pub fn platform_label(asset_name: &str) -> String {
+    Platform::from_asset_name(asset_name)
+        .map(|p| p.label())
+        .unwrap_or_else(|| UNKNOWN_PLATFORM.to_string())
+}
+
+/// What release asset downloads are grouped by in `query platforms`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PlatformGroup {
+    /// The full target triple, e.g. `x86_64-unknown-linux-gnu`.
+    #[default]
+    Target,
+    /// The operating system, e.g. `linux`.
+    Os,
+    /// The architecture, e.g. `aarch64`.
+    Arch,
+}
+
+impl PlatformGroup {
+    /// The group an asset's downloads are counted in, or
+    /// [`UNKNOWN_PLATFORM`].
+    pub fn group_of(self, asset_name: &str) -> String {
+        let Some(platform) = Platform::from_asset_name(asset_name) else {
+            return UNKNOWN_PLATFORM.to_string();
+        };
+        match self {
+            PlatformGroup::Target => platform.target,
+            PlatformGroup::Os => platform.os.to_string(),
+            PlatformGroup::Arch => platform.arch.to_string(),
+        }
+    }
+
+    /// What the group is called in a sentence.
+    pub fn noun(self) -> &'static str {
+        match self {
+            PlatformGroup::Target => "target",
+            PlatformGroup::Os => "OS",
+            PlatformGroup::Arch => "architecture",
+        }
+    }
+
+    /// A column header for the group.
+    pub fn header(self) -> &'static str {
+        match self {
+            PlatformGroup::Target => "Target",
+            PlatformGroup::Os => "OS",
+            PlatformGroup::Arch => "Architecture",
+        }
+    }
+}
+
+impl FromStr for PlatformGroup {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "target" => Ok(PlatformGroup::Target),
+            "os" => Ok(PlatformGroup::Os),
+            "arch" => Ok(PlatformGroup::Arch),
+            _ => bail!(
+                "unknown grouping '{}' (expected 'target', 'os', or 'arch')",
+                s
+            ),
+        }
+    }
+}
+
+/// The packaging format of a release asset.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum AssetFormat {
+    TarGz,
+    TarXz,
+    TarZst,
+    Zip,
+    /// A bare executable, either `.exe` or with no extension.
+    Binary,
+    /// A checksum file such as `.sha256` or `.b2`.
+    Checksum,
+    /// A detached signature such as `.sig` or `.asc`, or an attestation
+    /// bundle such as `.sigstore.json`.
+    Signature,
+    Other,
+}
+
+impl AssetFormat {
+    /// All formats, in display order.
+    pub const ALL: &[AssetFormat] = &[
+        AssetFormat::TarGz,
+        AssetFormat::TarXz,
+        AssetFormat::TarZst,
+        AssetFormat::Zip,
+        AssetFormat::Binary,
+        AssetFormat::Checksum,
+        AssetFormat::Signature,
+        AssetFormat::Other,
+    ];
+
+    /// Classify a release asset by its file extension.
+    pub fn from_asset_name(asset_name: &str) -> Self {
+        let ext = EXTENSIONS
+            .iter()
+            .copied()
+            .find(|ext| asset_name.ends_with(ext));
+        match ext {
+            Some(".tar.gz" | ".tgz") => AssetFormat::TarGz,
+            Some(".tar.xz") => AssetFormat::TarXz,
+            Some(".tar.zst") => AssetFormat::TarZst,
+            Some(".zip") => AssetFormat::Zip,
+            Some(".exe") => AssetFormat::Binary,
+            Some(".sha256" | ".sha512" | ".b2") => AssetFormat::Checksum,
+            Some(
+                ".sig" | ".asc" | ".minisig" | ".sigstore" | ".sigstore.json" | ".intoto.jsonl",
+            ) => AssetFormat::Signature,
+            Some(_) => AssetFormat::Other,
+            // Without a known extension, an asset built for a platform is a
+            // bare binary.
+            None if Platform::from_asset_name(asset_name).is_some() => AssetFormat::Binary,
+            None => AssetFormat::Other,
+        }
+    }
+
+    /// A short label, e.g. `tar.gz` or `checksum`.
+    pub fn label(self) -> &'static str {
+        match self {
+            AssetFormat::TarGz => "tar.gz",
+            AssetFormat::TarXz => "tar.xz",
+            AssetFormat::TarZst => "tar.zst",
+            AssetFormat::Zip => "zip",
+            AssetFormat::Binary => "binary",
+            AssetFormat::Checksum => "checksum",
+            AssetFormat::Signature => "signature",
+            AssetFormat::Other => "other",
+        }
+    }
+
+    /// Whether this format is the artifact itself, rather than metadata used
+    /// to verify it.
+    pub fn is_artifact(self) -> bool {
+        !matches!(self, AssetFormat::Checksum | AssetFormat::Signature)
+    }
+}
+
+fn strip_extension(name: &str) -> &str {
+    EXTENSIONS
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(name)
+}
+
+/// Find the target triple at the end of an asset name stem.
+fn find_target(stem: &str) -> Option<&str> {
+    // The target starts after a '-' with a known architecture. Search from the
+    // left so the whole triple is included.
+    stem.match_indices('-')
+        .map(|(idx, _)| idx + 1)
+        .find_map(|start| {
+            let candidate = &stem[start..];
+            ARCHES
+                .iter()
+                .any(|arch| candidate.starts_with(&format!("{}-", arch)))
+                .then_some(candidate)
+        })
+}
+
+fn os_for_target(target: &str) -> Option<&'static str> {
+    let os = if target.contains("-linux-musl") {
+        "linux-musl"
+    } else if target.contains("-linux") {
+        "linux"
+    } else if target.contains("-apple-darwin") {
+        "mac"
+    } else if target.contains("-windows") {
+        "windows"
+    } else if target.contains("-freebsd") {
+        "freebsd"
+    } else if target.contains("-netbsd") {
+        "netbsd"
+    } else if target.contains("-illumos") {
+        "illumos"
+    } else {
+        return None;
+    };
+    Some(os)
+}
+
+fn normalize_arch(arch: &'static str) -> &'static str {
+    match arch {
+        "riscv64gc" => "riscv64",
+        "i586" => "i686",
+        "arm" => "armv7",
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_labels() {
+        let cases = [
+            (
+                "cargo-nextest-0.9.100-x86_64-unknown-linux-gnu.tar.gz",
+                "linux-x86_64",
+            ),
+            (
+                "cargo-nextest-0.9.100-aarch64-unknown-linux-musl.sha256",
+                "linux-musl-aarch64",
+            ),
+            (
+                "cargo-nextest-0.9.100-universal-apple-darwin.tar.gz",
+                "mac-universal",
+            ),
+            (
+                "cargo-nextest-0.9.100-i686-pc-windows-msvc.zip",
+                "windows-i686",
+            ),
+            (
+                "cargo-nextest-0.9.100-riscv64gc-unknown-linux-gnu.b2",
+                "linux-riscv64",
+            ),
+            (
+                "cargo-nextest-0.9.100-x86_64-unknown-illumos.tar.gz",
+                "illumos-x86_64",
+            ),
+            (
+                "cargo-nextest-0.9.100-rc.1-x86_64-pc-windows-msvc.zip",
+                "windows-x86_64",
+            ),
+            ("source.tar.gz", UNKNOWN_PLATFORM),
+            (
+                "cargo-nextest-0.9.100-x86_64-unknown-plan9.tar.gz",
+                UNKNOWN_PLATFORM,
+            ),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(platform_label(name), expected, "for asset {}", name);
+        }
+    }
+
+    #[test]
+    fn test_asset_formats() {
+        let cases = [
+            (
+                "cargo-nextest-0.9.100-x86_64-unknown-linux-gnu.tar.gz",
+                AssetFormat::TarGz,
+            ),
+            (
+                "cargo-nextest-0.9.100-x86_64-pc-windows-msvc.zip",
+                AssetFormat::Zip,
+            ),
+            (
+                "cargo-nextest-0.9.100-x86_64-pc-windows-msvc.exe",
+                AssetFormat::Binary,
+            ),
+            (
+                "cargo-nextest-0.9.100-x86_64-unknown-linux-gnu",
+                AssetFormat::Binary,
+            ),
+            (
+                "cargo-nextest-0.9.100-aarch64-pc-windows-msvc.b2",
+                AssetFormat::Checksum,
+            ),
+            ("cargo-nextest-0.9.100.tar.gz.sig", AssetFormat::Signature),
+            (
+                "cargo-nextest-0.9.100-x86_64-unknown-linux-gnu.tar.gz.sigstore.json",
+                AssetFormat::Signature,
+            ),
+            ("multiple.intoto.jsonl", AssetFormat::Signature),
+            ("README.md", AssetFormat::Other),
+        ];
+
+        for (name, expected) in cases {
+            assert_eq!(
+                AssetFormat::from_asset_name(name),
+                expected,
+                "for asset {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn test_platform_target() {
+        let platform =
+            Platform::from_asset_name("cargo-nextest-0.9.100-x86_64-unknown-linux-musl.tar.gz")
+                .unwrap();
+        assert_eq!(platform.target, "x86_64-unknown-linux-musl");
+        assert_eq!(platform.os, "linux-musl");
+        assert_eq!(platform.arch, "x86_64");
+    }
+}
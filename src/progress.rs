@@ -0,0 +1,120 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Progress bars for long-running work.
+//!
+//! Collection and backfills show an overall bar counting finished work, and
+//! a spinner for each piece in flight, such as each source being fetched.
+//! Bars are drawn on standard error, and only when standard output and
+//! standard error are terminals and narration is on. Otherwise nothing is
+//! drawn, and the narration, with a line at every tenth of the way through,
+//! serves as the log.
+
+use crate::output::{self, status};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use std::{
+    io::{IsTerminal, stderr, stdout},
+    sync::Mutex,
+    time::Duration,
+};
+
+/// The bars being drawn, if any, so narration can be printed above them.
+static ACTIVE: Mutex<Option<MultiProgress>> = Mutex::new(None);
+
+/// Progress through `len` pieces of work.
+///
+/// Dropping it clears the bars.
+#[derive(Debug)]
+pub struct Progress {
+    multi: Option<MultiProgress>,
+    overall: ProgressBar,
+    message: String,
+    len: u64,
+}
+
+impl Progress {
+    /// Start showing progress through `len` pieces of work, described by
+    /// `message`, such as "Collecting".
+    pub fn new(len: u64, message: impl Into<String>) -> Self {
+        let message = message.into();
+        let enabled = !output::is_quiet() && stdout().is_terminal() && stderr().is_terminal();
+        let (multi, overall) = if enabled {
+            let multi = MultiProgress::new();
+            let overall = multi.add(
+                ProgressBar::new(len)
+                    .with_style(
+                        ProgressStyle::with_template("{msg} [{bar:30}] {pos}/{len} ({elapsed})")
+                            .expect("the template is valid")
+                            .progress_chars("=> "),
+                    )
+                    .with_message(message.clone()),
+            );
+            *ACTIVE.lock().unwrap() = Some(multi.clone());
+            (Some(multi), overall)
+        } else {
+            (None, ProgressBar::hidden())
+        };
+        Self {
+            multi,
+            overall,
+            message,
+            len,
+        }
+    }
+
+    /// Show a spinner for a piece of work that has started, to be passed to
+    /// [`Progress::finish`] once it's done.
+    pub fn start(&self, name: impl Into<String>) -> ProgressBar {
+        let Some(multi) = &self.multi else {
+            return ProgressBar::hidden();
+        };
+        let spinner = multi.insert_before(
+            &self.overall,
+            ProgressBar::new_spinner()
+                .with_style(
+                    ProgressStyle::with_template("  {spinner} {msg} ({elapsed})")
+                        .expect("the template is valid"),
+                )
+                .with_message(name.into()),
+        );
+        spinner.enable_steady_tick(Duration::from_millis(100));
+        spinner
+    }
+
+    /// Count a piece of work as done, clearing its spinner.
+    pub fn finish(&self, spinner: ProgressBar) {
+        spinner.finish_and_clear();
+        self.inc();
+    }
+
+    /// Count a piece of work without a spinner as done.
+    pub fn inc(&self) {
+        self.overall.inc(1);
+        if self.multi.is_none() {
+            let done = self.overall.position();
+            if self.len >= 10 && done.is_multiple_of(self.len.div_ceil(10)) && done < self.len {
+                status!("  {}: {} of {}", self.message, done, self.len);
+            }
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.overall.finish_and_clear();
+        if self.multi.is_some() {
+            *ACTIVE.lock().unwrap() = None;
+        }
+    }
+}
+
+/// Run `f`, which prints output, with any bars hidden so the output isn't
+/// drawn over.
+pub fn suspend<R>(f: impl FnOnce() -> R) -> R {
+    // Clone the handle so the lock isn't held while printing.
+    let active = ACTIVE.lock().unwrap().clone();
+    match active {
+        Some(multi) => multi.suspend(f),
+        None => f(),
+    }
+}
@@ -0,0 +1,201 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Provenance metadata for published outputs.
+//!
+//! Exports, chart outputs, public snapshots, and reports record the version
+//! and commit of the tool that produced them, a hash of the configuration,
+//! and the latest date with data for each source. Together these identify
+//! the inputs behind any published number.
+//!
+//! The fingerprint combines everything except the generation time, so
+//! outputs produced from the same inputs share a fingerprint. It's suitable
+//! as a cache-busting key for published URLs.
+
+use crate::{analysis, config::Config};
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{NaiveDate, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{collections::BTreeMap, fs};
+
+/// The file name of the provenance written alongside chart outputs.
+pub const PROVENANCE_FILE: &str = "provenance.json";
+
+/// The number of hex digits kept from hashes.
+const HASH_LEN: usize = 16;
+
+/// Where an output came from.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Provenance {
+    /// The version of the tool.
+    pub tool_version: String,
+    /// The commit the tool was built from, if it was built from a git
+    /// checkout.
+    pub git_commit: Option<String>,
+    /// A hash of the effective configuration.
+    pub config_hash: String,
+    /// The latest date with raw data, for each source that has any.
+    pub data_watermark: BTreeMap<String, NaiveDate>,
+    /// A hash of all of the above.
+    pub fingerprint: String,
+    pub generated_at: String,
+}
+
+impl Provenance {
+    /// Collect the provenance of outputs generated now from `conn` with
+    /// `config`.
+    pub fn collect(conn: &Connection, config: &Config) -> Result<Self> {
+        let config_hash = config_hash(config)?;
+        let mut data_watermark = BTreeMap::new();
+        for source in analysis::SOURCES {
+            if let Some(latest) = analysis::latest_data_date(conn, source)? {
+                data_watermark.insert(source.to_string(), latest);
+            }
+        }
+        Ok(Self::new(
+            option_env!("DOWNLOAD_STATS_GIT_COMMIT").map(String::from),
+            config_hash,
+            data_watermark,
+        ))
+    }
+
+    fn new(
+        git_commit: Option<String>,
+        config_hash: String,
+        data_watermark: BTreeMap<String, NaiveDate>,
+    ) -> Self {
+        let tool_version = env!("CARGO_PKG_VERSION").to_string();
+
+        let mut hasher = Sha256::new();
+        for part in [
+            tool_version.as_str(),
+            git_commit.as_deref().unwrap_or(""),
+            config_hash.as_str(),
+        ] {
+            hasher.update(part.as_bytes());
+            hasher.update([0]);
+        }
+        for (source, date) in &data_watermark {
+            hasher.update(format!("{}={}", source, date).as_bytes());
+            hasher.update([0]);
+        }
+
+        Provenance {
+            tool_version,
+            git_commit,
+            config_hash,
+            data_watermark,
+            fingerprint: short_hash(hasher),
+            generated_at: Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// A one-line description for report footers.
+    pub fn footer(&self) -> String {
+        let commit = match &self.git_commit {
+            // The commit comes from the build environment, so it isn't
+            // necessarily an ASCII hash.
+            Some(commit) => format!(" ({})", commit.chars().take(12).collect::<String>()),
+            None => String::new(),
+        };
+        let watermark = if self.data_watermark.is_empty() {
+            "no data".to_string()
+        } else {
+            let sources: Vec<String> = self
+                .data_watermark
+                .iter()
+                .map(|(source, date)| format!("{} {}", source, date))
+                .collect();
+            format!("data through {}", sources.join(", "))
+        };
+        format!(
+            "download-stats-collector {}{}, config {}, {}, fingerprint {}",
+            self.tool_version, commit, self.config_hash, watermark, self.fingerprint
+        )
+    }
+
+    /// Write the provenance as JSON to `path`.
+    pub fn write(&self, path: &Utf8Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        fs::write(path.as_std_path(), json).with_context(|| format!("failed to write {}", path))
+    }
+
+    /// Write the provenance alongside the single-file output at `output`, as
+    /// `<output>.provenance.json`.
+    pub fn write_sidecar(&self, output: &Utf8Path) -> Result<Utf8PathBuf> {
+        let path = Utf8PathBuf::from(format!("{}.provenance.json", output));
+        self.write(&path)?;
+        Ok(path)
+    }
+}
+
+/// Hash the configuration as it was resolved, including defaults and
+/// command-line overrides.
+fn config_hash(config: &Config) -> Result<String> {
+    let serialized = toml::to_string(config).context("failed to serialize configuration")?;
+    Ok(short_hash(Sha256::new_with_prefix(serialized.as_bytes())))
+}
+
+fn short_hash(hasher: Sha256) -> String {
+    let mut hash = hex::encode(hasher.finalize());
+    hash.truncate(HASH_LEN);
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+
+    #[test]
+    fn test_provenance() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let config = Config::default();
+
+        let empty = Provenance::collect(&conn, &config).unwrap();
+        assert!(empty.data_watermark.is_empty());
+        assert_eq!(empty.config_hash.len(), HASH_LEN);
+        assert!(empty.footer().contains("no data"));
+
+        let date = NaiveDate::from_ymd_opt(2024, 3, 4).unwrap();
+        db::insert_installer_download(&conn, date, "get.nexte.st", 5).unwrap();
+        let provenance = Provenance::collect(&conn, &config).unwrap();
+        assert_eq!(provenance.config_hash, empty.config_hash);
+        assert_eq!(provenance.data_watermark.get("installer"), Some(&date));
+        assert_ne!(provenance.fingerprint, empty.fingerprint);
+        assert!(
+            provenance
+                .footer()
+                .contains("data through installer 2024-03-04")
+        );
+
+        // The fingerprint doesn't depend on when the outputs were generated.
+        let again = Provenance::collect(&conn, &config).unwrap();
+        assert_eq!(again.fingerprint, provenance.fingerprint);
+
+        let mut changed = Config::default();
+        changed.charts.release_annotations = crate::config::ReleaseAnnotations::All;
+        let changed = Provenance::collect(&conn, &changed).unwrap();
+        assert_ne!(changed.config_hash, provenance.config_hash);
+        assert_ne!(changed.fingerprint, provenance.fingerprint);
+    }
+
+    #[test]
+    fn test_footer_commit() {
+        let footer = |commit: &str| {
+            Provenance::new(
+                Some(commit.to_string()),
+                "cafe".to_string(),
+                BTreeMap::new(),
+            )
+            .footer()
+        };
+        assert!(footer("0123456789abcdef0123").contains(" (0123456789ab), "));
+        assert!(footer("abc").contains(" (abc), "));
+        // Commits are cut off by characters, not bytes.
+        assert!(footer("ééééééééééééé").contains(" (éééééééééééé), "));
+    }
+}
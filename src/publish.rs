@@ -0,0 +1,259 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Sanitized public snapshots of the database.
+//!
+//! Only the tables and columns in the configured allowlist are exported.
+//! Tables that may contain private information, such as collection error
+//! messages, can never be exported even if they are allowlisted.
+
+use crate::{
+    config::PublicExportConfig,
+    output::{self, status},
+    provenance::Provenance,
+    query,
+};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use chrono::Utc;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+
+/// Tables that must never be published.
+const PRIVATE_TABLES: &[&str] = &["collection_runs"];
+
+/// A description of a published snapshot, written as `manifest.json`.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublicManifest {
+    pub generated_at: String,
+    /// Where the snapshot came from.
+    ///
+    /// Missing from manifests written by older versions.
+    #[serde(default)]
+    pub provenance: Option<Provenance>,
+    pub tables: Vec<PublishedTable>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct PublishedTable {
+    pub name: String,
+    pub file: String,
+    pub columns: Vec<String>,
+    /// Columns that identify a row, used to compare snapshots.
+    ///
+    /// Missing from manifests written by older versions.
+    #[serde(default)]
+    pub key: Vec<String>,
+    pub rows: usize,
+}
+
+/// Export the allowlisted tables and columns to `output_dir`.
+///
+/// Each table is written as `<table>.csv`, alongside a `manifest.json`
+/// describing the snapshot and its provenance.
+pub fn export_public(
+    conn: &Connection,
+    config: &PublicExportConfig,
+    provenance: &Provenance,
+    output_dir: &Utf8Path,
+) -> Result<PublicManifest> {
+    let plan = plan_tables(conn, config)?;
+
+    fs::create_dir_all(output_dir.as_std_path())
+        .with_context(|| format!("failed to create output directory {}", output_dir))?;
+
+    status!("Exporting public snapshot to {}", output_dir);
+
+    let mut tables = Vec::new();
+    for (name, columns) in plan {
+        let quoted: Vec<String> = columns.iter().map(|c| quote_ident(c)).collect();
+        let order: Vec<String> = (1..=columns.len()).map(|i| i.to_string()).collect();
+        let sql = format!(
+            "SELECT {} FROM {} ORDER BY {}",
+            quoted.join(", "),
+            quote_ident(&name),
+            order.join(", ")
+        );
+
+        let file_name = format!("{}.csv", name);
+        let path = output_dir.join(&file_name);
+        let mut stmt = conn.prepare(&sql)?;
+        let mut file = File::create(path.as_std_path())
+            .with_context(|| format!("failed to create file at {}", path))?;
+        let rows = query::write_csv(&mut stmt, [], &mut file)
+            .with_context(|| format!("failed to export table '{}'", name))?;
+
+        output::written(&path, format!("  • {} ({} rows)", file_name, rows));
+        let key = table_key(conn, &name, &columns)?;
+        tables.push(PublishedTable {
+            name,
+            file: file_name,
+            columns,
+            key,
+            rows,
+        });
+    }
+
+    let manifest = PublicManifest {
+        generated_at: Utc::now().to_rfc3339(),
+        provenance: Some(provenance.clone()),
+        tables,
+    };
+    let manifest_path = output_dir.join("manifest.json");
+    let json = serde_json::to_string_pretty(&manifest)?;
+    fs::write(manifest_path.as_std_path(), json)
+        .with_context(|| format!("failed to write {}", manifest_path))?;
+
+    Ok(manifest)
+}
+
+/// Validate the allowlist against the database, returning the columns to
+/// export for each table.
+fn plan_tables(
+    conn: &Connection,
+    config: &PublicExportConfig,
+) -> Result<Vec<(String, Vec<String>)>> {
+    config
+        .table
+        .iter()
+        .map(|table| {
+            if PRIVATE_TABLES.contains(&table.name.as_str()) {
+                bail!("table '{}' is private and can't be published", table.name);
+            }
+
+            let available = table_columns(conn, &table.name)?;
+            if available.is_empty() {
+                bail!("unknown table '{}' in public export allowlist", table.name);
+            }
+
+            let columns = match &table.columns {
+                Some(columns) => {
+                    if let Some(missing) = columns.iter().find(|c| !available.contains(c)) {
+                        bail!(
+                            "unknown column '{}' for table '{}' in public export allowlist",
+                            missing,
+                            table.name
+                        );
+                    }
+                    columns.clone()
+                }
+                None => available,
+            };
+            Ok((table.name.clone(), columns))
+        })
+        .collect()
+}
+
+/// Get the column names of a table or view, or an empty list if it doesn't
+/// exist.
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name FROM pragma_table_info(?1)")?;
+    let columns = stmt
+        .query_map([table], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(columns)
+}
+
+/// The exported columns that identify a row of a table.
+///
+/// This is the primary key for tables. Views don't have one, so use their
+/// non-numeric columns instead.
+fn table_key(conn: &Connection, table: &str, columns: &[String]) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT name, type, pk FROM pragma_table_info(?1) ORDER BY pk")?;
+    let info = stmt
+        .query_map([table], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let exported = |name: &String| columns.contains(name);
+    let primary: Vec<String> = info
+        .iter()
+        .filter(|(name, _, pk)| *pk > 0 && exported(name))
+        .map(|(name, _, _)| name.clone())
+        .collect();
+    if !primary.is_empty() {
+        return Ok(primary);
+    }
+
+    Ok(columns
+        .iter()
+        .filter(|name| {
+            info.iter().any(|(column, ty, _)| {
+                column == *name && !matches!(ty.to_ascii_uppercase().as_str(), "INTEGER" | "REAL")
+            })
+        })
+        .cloned()
+        .collect())
+}
+
+fn quote_ident(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::PublicTableConfig, db};
+
+    fn table(name: &str, columns: Option<&[&str]>) -> PublicTableConfig {
+        PublicTableConfig {
+            name: name.to_string(),
+            columns: columns.map(|c| c.iter().map(|s| s.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn test_plan_tables() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+
+        let plan = plan_tables(&conn, &PublicExportConfig::default()).unwrap();
+        let github_daily = plan
+            .iter()
+            .find(|(name, _)| name == "github_daily")
+            .unwrap();
+        assert!(!github_daily.1.contains(&"raw_delta".to_string()));
+
+        let config = PublicExportConfig {
+            table: vec![table("weekly_stats", Some(&["week_start", "downloads"]))],
+        };
+        let plan = plan_tables(&conn, &config).unwrap();
+        assert_eq!(plan[0].1, ["week_start", "downloads"]);
+
+        for bad in [
+            table("collection_runs", None),
+            table("no_such_table", None),
+            table("weekly_stats", Some(&["secret"])),
+        ] {
+            let config = PublicExportConfig { table: vec![bad] };
+            assert!(plan_tables(&conn, &config).is_err());
+        }
+    }
+
+    #[test]
+    fn test_table_key() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let columns = |names: &[&str]| names.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        let key = table_key(
+            &conn,
+            "weekly_stats",
+            &columns(&["week_start", "source", "identifier", "downloads"]),
+        )
+        .unwrap();
+        assert_eq!(key, ["week_start", "source", "identifier"]);
+
+        let key = table_key(
+            &conn,
+            "crates_downloads_named",
+            &columns(&["date", "crate_name", "version", "downloads"]),
+        )
+        .unwrap();
+        assert_eq!(key, ["date", "crate_name", "version"]);
+    }
+}
@@ -3,112 +3,2366 @@
 
 //! Query and export functionality for download statistics.
 
-use anyhow::{Context, Result};
+use crate::{
+    aggregate,
+    alerts::{self, Alert},
+    analysis,
+    config::{AlertConfig, HeadlineConfig, WeekStart},
+    db, format, headline,
+    metrics::{self, Metrics},
+    output,
+    platform::{AssetFormat, PlatformGroup, UNKNOWN_PLATFORM},
+    provenance::Provenance,
+    table::{Cell, Column, OutputFormat, Report, Table, csv_field},
+};
+use anyhow::{Context, Result, bail};
 use camino::Utf8Path;
-use rusqlite::Connection;
-use std::{fs::File, io::Write};
+use chrono::{Months, NaiveDate, Utc};
+use flate2::write::GzEncoder;
+use rusqlite::{Connection, Params, Statement, params_from_iter, types::ValueRef};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{BufWriter, Write},
+    str::FromStr,
+    time::Duration,
+};
 
 pub enum QueryKind {
-    Weekly { limit: usize, source: String },
-    Total { source: String },
+    Weekly {
+        limit: usize,
+        source: String,
+        platform: Option<String>,
+        delta: bool,
+        range: DateRange,
+        identifiers: Identifiers,
+    },
+    Monthly {
+        limit: usize,
+        source: String,
+        quarterly: bool,
+        identifiers: Identifiers,
+    },
+    Total {
+        source: String,
+        range: DateRange,
+        identifiers: Identifiers,
+    },
+    Daily {
+        limit: usize,
+        source: String,
+        identifiers: Identifiers,
+    },
+    Rate {
+        limit: usize,
+        window: usize,
+    },
+    Diversity {
+        limit: usize,
+        min_share: f64,
+    },
+    Rolling {
+        limit: usize,
+        source: String,
+    },
+    Growth,
+    VersionShare {
+        limit: usize,
+        source: String,
+        versions: usize,
+    },
+    Versions {
+        source: String,
+        days: u32,
+        top: usize,
+    },
+    VersionGrowth {
+        source: String,
+        weeks: u32,
+        top: usize,
+        lines: bool,
+    },
+    Platforms {
+        by: PlatformGroup,
+        days: u32,
+        range: DateRange,
+    },
+    Compare {
+        period: ComparePeriod,
+        against: CompareBaseline,
+    },
+    Gaps {
+        limit: usize,
+        max_staleness: Option<Duration>,
+    },
+    Stats,
+    Channel {
+        limit: usize,
+        source: String,
+    },
+    Verification {
+        limit: usize,
+    },
+    State,
+    Runs {
+        limit: usize,
+    },
+    Owners {
+        crate_name: Option<String>,
+    },
+    Tombstones {
+        source: String,
+    },
+    Projects {
+        limit: usize,
+        monthly: bool,
+    },
+    Formats {
+        limit: usize,
+        platform: Option<String>,
+    },
+    Targets,
+    Movers {
+        limit: usize,
+        min_downloads: u64,
+    },
+    Metrics {
+        metrics: Metrics,
+        name: Option<String>,
+        limit: usize,
+    },
+    Headline {
+        headline: HeadlineConfig,
+        json: bool,
+    },
+    ReleaseSnapshots {
+        tag: Option<String>,
+        limit: usize,
+    },
     Latest,
+    Sql {
+        statement: String,
+    },
+    Tables,
+}
+
+pub enum AnalyzeKind {
+    Anomalies {
+        window: usize,
+        threshold: f64,
+        recent: Option<usize>,
+        json: bool,
+        fail_on_anomaly: bool,
+    },
+    Alerts {
+        rules: Vec<AlertConfig>,
+        json: bool,
+        fail_on_alert: bool,
+    },
 }
 
 pub enum ExportKind {
-    Csv { output: String, table: String },
-    Json { output: String, table: String },
+    Csv {
+        output: String,
+        table: String,
+        filter: ExportFilter,
+        compress: Compress,
+    },
+    Json {
+        output: String,
+        table: String,
+        filter: ExportFilter,
+        compress: Compress,
+    },
+}
+
+/// The rows of a table an export is limited to.
+#[derive(Clone, Debug, Default)]
+pub struct ExportFilter {
+    pub range: DateRange,
+    /// Only rows from this source: `github`, `crates`, or `installer`.
+    pub source: Option<String>,
+    /// Only rows for this crate.
+    pub crate_name: Option<String>,
+}
+
+/// Which identifiers of a source a query covers: crate names, `releases` for
+/// GitHub releases, or install endpoints.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Identifiers {
+    /// Only this identifier, rather than all of them.
+    pub only: Option<String>,
+    /// A column per identifier, rather than their sum.
+    pub breakdown: bool,
+}
+
+/// An inclusive range of dates that results are limited to. Either end may be
+/// open.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct DateRange {
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+}
+
+impl DateRange {
+    /// The most days a range counted back from a date can span.
+    pub const MAX_DAYS: u32 = 100 * 366;
+
+    pub fn new(since: Option<NaiveDate>, until: Option<NaiveDate>) -> Result<Self> {
+        if let (Some(since), Some(until)) = (since, until)
+            && since > until
+        {
+            bail!("--since {} is after --until {}", since, until);
+        }
+        Ok(Self { since, until })
+    }
+
+    /// The last `days` days up to and including `until`.
+    pub fn last_days(until: NaiveDate, days: u32) -> Result<Self> {
+        if days > Self::MAX_DAYS {
+            bail!(
+                "{} days is too long a period: at most {} is allowed",
+                days,
+                Self::MAX_DAYS
+            );
+        }
+        let since = until
+            .checked_sub_days(chrono::Days::new(u64::from(days.max(1)) - 1))
+            .with_context(|| format!("{} days before {} is out of range", days, until))?;
+        DateRange::new(Some(since), Some(until))
+    }
+
+    pub fn is_unbounded(&self) -> bool {
+        self.since.is_none() && self.until.is_none()
+    }
+
+    /// An SQL condition limiting `column`, which holds `YYYY-MM-DD` dates, to
+    /// the range.
+    ///
+    /// The dates are written into the condition: they're formatted from
+    /// `NaiveDate`s, so they can't contain anything but digits and dashes.
+    pub fn condition(&self, column: &str) -> String {
+        let mut conditions = Vec::new();
+        if let Some(since) = self.since {
+            conditions.push(format!("{} >= '{}'", column, since));
+        }
+        if let Some(until) = self.until {
+            conditions.push(format!("{} <= '{}'", column, until));
+        }
+        if conditions.is_empty() {
+            "1".to_string()
+        } else {
+            conditions.join(" AND ")
+        }
+    }
+
+    /// The number of days in the range, if it's bounded at both ends.
+    fn days(&self) -> Option<i64> {
+        Some((self.until? - self.since?).num_days() + 1)
+    }
+
+    /// A description of the range, such as `2024-01-01 to 2024-12-31`.
+    fn describe(&self) -> String {
+        match (self.since, self.until) {
+            (Some(since), Some(until)) => format!("{} to {}", since, until),
+            (Some(since), None) => format!("since {}", since),
+            (None, Some(until)) => format!("until {}", until),
+            (None, None) => "all time".to_string(),
+        }
+    }
+}
+
+/// Parse a period: a year (`2024`), a quarter (`2024-Q1`), a month
+/// (`2024-03`), a day (`2024-03-15`), or dates (`2024-01-01..2024-03-31`).
+impl FromStr for DateRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow::anyhow!(
+                "invalid period '{}' (expected a year such as 2024, a quarter such as \
+                 2024-Q1, a month such as 2024-03, or dates such as 2024-01-01..2024-03-31)",
+                s
+            )
+        };
+        if let Some((since, until)) = s.split_once("..") {
+            let parse = |date: &str| NaiveDate::parse_from_str(date, "%Y-%m-%d");
+            let (Ok(since), Ok(until)) = (parse(since), parse(until)) else {
+                return Err(invalid());
+            };
+            return DateRange::new(Some(since), Some(until));
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return DateRange::new(Some(date), Some(date));
+        }
+
+        let (year, rest) = s.split_once('-').unwrap_or((s, ""));
+        if year.len() != 4 || !year.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        let year: i32 = year.parse().map_err(|_| invalid())?;
+        let (month, months) = match rest {
+            "" => (1, 12),
+            "Q1" | "q1" => (1, 3),
+            "Q2" | "q2" => (4, 3),
+            "Q3" | "q3" => (7, 3),
+            "Q4" | "q4" => (10, 3),
+            month => (month.parse().map_err(|_| invalid())?, 1),
+        };
+        let since = NaiveDate::from_ymd_opt(year, month, 1).ok_or_else(invalid)?;
+        let until = (since + Months::new(months))
+            .pred_opt()
+            .ok_or_else(invalid)?;
+        DateRange::new(Some(since), Some(until))
+    }
+}
+
+/// The period `query compare` looks at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ComparePeriod {
+    /// The last this many days of data, up to the latest collected date of
+    /// any source.
+    Last(u32),
+    Dates(DateRange),
+}
+
+/// The period `query compare` compares against.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CompareBaseline {
+    /// As many days, ending the day before the period starts.
+    #[default]
+    Previous,
+    /// The same dates a year earlier.
+    Year,
+    Dates(DateRange),
+}
+
+impl FromStr for CompareBaseline {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "previous" => Ok(CompareBaseline::Previous),
+            "year" => Ok(CompareBaseline::Year),
+            _ => s.parse().map(CompareBaseline::Dates).map_err(|_| {
+                anyhow::anyhow!(
+                    "unknown baseline '{}' (expected 'previous', 'year', or a period such as 2024-Q1)",
+                    s
+                )
+            }),
+        }
+    }
 }
 
-pub fn run_query(conn: &Connection, query: QueryKind) -> Result<()> {
-    match query {
-        QueryKind::Weekly { limit, source } => query_weekly(conn, limit, &source)?,
-        QueryKind::Total { source } => query_total(conn, &source)?,
+/// Run a query, printing its results in `format`.
+pub fn run_query(conn: &Connection, query: QueryKind, format: OutputFormat) -> Result<()> {
+    let report = match query {
+        QueryKind::Weekly {
+            limit,
+            source: _,
+            platform: Some(platform),
+            delta,
+            range,
+            identifiers: _,
+        } => query_weekly_platform(conn, limit, &platform, delta, range)?,
+        QueryKind::Weekly {
+            limit,
+            source,
+            platform: None,
+            delta,
+            range,
+            identifiers,
+        } => query_weekly(conn, limit, &source, delta, range, &identifiers)?,
+        QueryKind::Monthly {
+            limit,
+            source,
+            quarterly,
+            identifiers,
+        } => query_monthly(conn, limit, &source, quarterly, &identifiers)?,
+        QueryKind::Total {
+            source,
+            range,
+            identifiers,
+        } => query_total(conn, &source, range, &identifiers)?,
+        QueryKind::Daily {
+            limit,
+            source,
+            identifiers,
+        } => query_daily(conn, limit, &source, &identifiers)?,
+        QueryKind::Rate { limit, window } => query_rate(conn, limit, window)?,
+        QueryKind::Diversity { limit, min_share } => query_diversity(conn, limit, min_share)?,
+        QueryKind::Rolling { limit, source } => query_rolling(conn, limit, &source)?,
+        QueryKind::Growth => query_growth(conn)?,
+        QueryKind::VersionShare {
+            limit,
+            source,
+            versions,
+        } => query_version_share(conn, limit, &source, versions)?,
+        QueryKind::Versions { source, days, top } => query_versions(conn, &source, days, top)?,
+        QueryKind::VersionGrowth {
+            source,
+            weeks,
+            top,
+            lines,
+        } => query_version_growth(conn, &source, weeks, top, lines)?,
+        QueryKind::Platforms { by, days, range } => query_platforms(conn, by, days, range)?,
+        QueryKind::Compare { period, against } => query_compare(conn, period, against)?,
+        QueryKind::Stats => query_stats(conn)?,
+        QueryKind::Gaps {
+            limit,
+            max_staleness,
+        } => {
+            let gaps = analysis::collection_gaps(conn, Utc::now().date_naive())?;
+            gaps_report(&gaps, limit).print(format)?;
+            return check_staleness(&gaps, max_staleness);
+        }
+        QueryKind::Channel { limit, source } => query_channel(conn, limit, &source)?,
+        QueryKind::Verification { limit } => query_verification(conn, limit)?,
+        QueryKind::State => query_state(conn)?,
+        QueryKind::Runs { limit } => query_runs(conn, limit)?,
+        QueryKind::Owners { crate_name } => query_owners(conn, crate_name.as_deref())?,
+        QueryKind::Tombstones { source } => query_tombstones(conn, &source)?,
+        QueryKind::Projects { limit, monthly } => query_projects(conn, limit, monthly)?,
+        QueryKind::Formats { limit, platform } => query_formats(conn, limit, platform.as_deref())?,
+        QueryKind::Targets => query_targets(conn)?,
+        QueryKind::Movers {
+            limit,
+            min_downloads,
+        } => query_movers(conn, limit, min_downloads)?,
+        QueryKind::Metrics {
+            metrics,
+            name: Some(name),
+            limit,
+        } => query_metric(conn, &metrics, &name, limit)?,
+        QueryKind::Metrics {
+            metrics,
+            name: None,
+            limit: _,
+        } => query_metrics(conn, &metrics)?,
+        QueryKind::Headline {
+            headline,
+            json: true,
+        } => {
+            let headline = headline::compute(conn, &headline)?;
+            println!("{}", serde_json::to_string_pretty(&headline)?);
+            return Ok(());
+        }
+        QueryKind::Headline {
+            headline,
+            json: false,
+        } => query_headline(conn, &headline)?,
+        QueryKind::ReleaseSnapshots { tag, limit } => {
+            query_release_snapshots(conn, tag.as_deref(), limit)?
+        }
         QueryKind::Latest => query_latest(conn)?,
+        QueryKind::Sql { statement } => query_sql(conn, &statement)?,
+        QueryKind::Tables => query_tables(conn)?,
+    };
+    report.print(format)
+}
+
+pub fn run_analyze(conn: &Connection, analyze: AnalyzeKind) -> Result<()> {
+    match analyze {
+        AnalyzeKind::Anomalies {
+            window,
+            threshold,
+            recent,
+            json,
+            fail_on_anomaly,
+        } => {
+            let anomalies = analysis::detect_anomalies(conn, window, threshold, recent)?;
+            if json {
+                let report = serde_json::json!({
+                    "window": window,
+                    "threshold": threshold,
+                    "anomalies": anomalies,
+                });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_anomalies(&anomalies, window, threshold);
+            }
+            if fail_on_anomaly && !anomalies.is_empty() {
+                anyhow::bail!("{} anomalous weeks detected", anomalies.len());
+            }
+        }
+        AnalyzeKind::Alerts {
+            rules,
+            json,
+            fail_on_alert,
+        } => {
+            if rules.is_empty() {
+                bail!("no alert rules configured");
+            }
+            let fired = alerts::evaluate(conn, &rules)?;
+            if json {
+                let report = serde_json::json!({
+                    "rules": rules.len(),
+                    "alerts": fired,
+                });
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_alerts(&fired, rules.len());
+            }
+            if fail_on_alert && !fired.is_empty() {
+                bail!("{} of {} alerts fired", fired.len(), rules.len());
+            }
+        }
     }
     Ok(())
 }
 
-pub fn run_export(conn: &Connection, export: ExportKind) -> Result<()> {
-    match export {
-        ExportKind::Csv { output, table } => export_csv(conn, output.as_ref(), &table)?,
-        ExportKind::Json { output, table } => export_json(conn, output.as_ref(), &table)?,
+fn print_anomalies(anomalies: &[analysis::Anomaly], window: usize, threshold: f64) {
+    println!(
+        "\nWeeks deviating {} or more standard deviations from the previous {} weeks",
+        threshold, window
+    );
+    println!(
+        "\n{:<10} {:<12} {:>12} {:>12} {:>8} Kind",
+        "Source", "Week", "Downloads", "Mean", "Z"
+    );
+    println!("{}", "=".repeat(65));
+
+    for anomaly in anomalies {
+        println!(
+            "{:<10} {:<12} {:>12} {:>12} {:>8.2} {}",
+            anomaly.source,
+            anomaly.week_start,
+            format::number(anomaly.downloads),
+            format::number(anomaly.mean.round() as u64),
+            anomaly.z_score,
+            anomaly.kind.as_str(),
+        );
+    }
+
+    if anomalies.is_empty() {
+        println!("No anomalies found.");
+    }
+}
+
+fn print_alerts(alerts: &[Alert], rules: usize) {
+    println!("\n{} of {} alert rules fired", alerts.len(), rules);
+    for alert in alerts {
+        println!("  {}: {}", alert.name, alert.message);
     }
-    Ok(())
 }
 
-fn query_weekly(conn: &Connection, limit: usize, source: &str) -> Result<()> {
-    let query = match source {
-        "github" => {
-            "SELECT week_start, downloads FROM weekly_stats
-             WHERE source = 'github'
-             ORDER BY week_start DESC LIMIT ?1"
+/// Run an export, writing the provenance of the output alongside it.
+pub fn run_export(conn: &Connection, export: ExportKind, provenance: &Provenance) -> Result<()> {
+    let output = match export {
+        ExportKind::Csv {
+            output,
+            table,
+            filter,
+            compress,
+        } => {
+            export_csv(conn, output.as_ref(), &table, &filter, compress)?;
+            output
         }
-        "crates" => {
-            "SELECT week_start, SUM(downloads) as downloads FROM weekly_stats
-             WHERE source = 'crates'
-             GROUP BY week_start
-             ORDER BY week_start DESC LIMIT ?1"
+        ExportKind::Json {
+            output,
+            table,
+            filter,
+            compress,
+        } => {
+            export_json(conn, output.as_ref(), &table, &filter, compress)?;
+            output
         }
-        "all" | _ => {
-            "SELECT week_start, SUM(downloads) as downloads FROM weekly_stats
-             GROUP BY week_start
-             ORDER BY week_start DESC LIMIT ?1"
+    };
+    // There's nowhere to put the provenance of standard output.
+    if output != STDOUT_PATH {
+        provenance.write_sidecar(output.as_ref())?;
+    }
+    Ok(())
+}
+
+fn query_weekly(
+    conn: &Connection,
+    limit: usize,
+    source: &str,
+    delta: bool,
+    range: DateRange,
+    identifiers: &Identifiers,
+) -> Result<Report> {
+    check_identifier(conn, source, identifiers.only.as_deref())?;
+    let rows = identifier_rows(
+        conn,
+        &format!(
+            "SELECT week_start, identifier, SUM(downloads) FROM weekly_stats
+             WHERE {} AND (?1 IS NULL OR identifier = ?1) AND {}
+             GROUP BY week_start, identifier",
+            source_condition(source),
+            range.condition("week_start")
+        ),
+        identifiers.only.as_deref(),
+    )?;
+    if identifiers.breakdown {
+        let convention = aggregate::stored_week_start(conn)?;
+        return Ok(
+            Report::default().table(breakdown_table("Week", &rows, limit, |week| {
+                label_week(convention, week)
+            })?),
+        );
+    }
+    // Fetch one extra week so the oldest row shown has a delta.
+    let rows = sum_periods(&rows, limit + 1);
+
+    // A week still in progress is flagged, and projected to the full week
+    // after the table.
+    let source = match source {
+        "github" | "crates" | "installer" => source,
+        _ => "all",
+    };
+    let projection = match identifiers.only {
+        // The projection is only computed for whole sources.
+        Some(_) => None,
+        None => analysis::project_current_week(conn, source)?,
+    }
+    .filter(|projection| {
+        rows.first()
+            .is_some_and(|(week, _)| *week == projection.week_start.to_string())
+    });
+    let mut table = weekly_table(&label_weeks(conn, rows)?, limit, delta);
+    if projection.is_some() {
+        table.columns.push(Column::left("In progress", 0));
+        for (idx, row) in table.rows.iter_mut().enumerate() {
+            row.push(Cell::Flag(idx == 0));
         }
+    }
+    let mut report = Report::default().table(table);
+
+    if let Some(projection) = projection {
+        report = report.note(format!(
+            "* In progress ({} of 7 days). Projected full week: {} (from the day-of-week \
+             pattern of the previous {} weeks)",
+            projection.days,
+            format::number(projection.projected),
+            analysis::PROJECTION_WEEKS
+        ));
+    }
+    Ok(report)
+}
+
+/// Replace week start dates with labels for the week convention the
+/// aggregates were computed with.
+fn label_weeks(conn: &Connection, rows: Vec<(String, i64)>) -> Result<Vec<(String, i64)>> {
+    let week_start = aggregate::stored_week_start(conn)?;
+    rows.into_iter()
+        .map(|(week, downloads)| Ok((label_week(week_start, &week)?, downloads)))
+        .collect()
+}
+
+fn label_week(week_start: WeekStart, week: &str) -> Result<String> {
+    let date = NaiveDate::parse_from_str(week, "%Y-%m-%d")
+        .with_context(|| format!("failed to parse week '{}'", week))?;
+    Ok(aggregate::week_label(week_start, date))
+}
+
+/// The condition on the `source` column of the aggregates for a `--source`
+/// value.
+fn source_condition(source: &str) -> &'static str {
+    match source {
+        "github" => "source = 'github'",
+        "crates" => "source = 'crates'",
+        "installer" => "source = 'installer'",
+        _ => "1",
+    }
+}
+
+/// Fail, listing the known identifiers, if `identifier` has no aggregates
+/// for `source`.
+fn check_identifier(conn: &Connection, source: &str, identifier: Option<&str>) -> Result<()> {
+    let Some(identifier) = identifier else {
+        return Ok(());
     };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT DISTINCT identifier FROM weekly_stats WHERE {} ORDER BY identifier",
+        source_condition(source)
+    ))?;
+    let known = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if !known.iter().any(|known| known == identifier) {
+        bail!(
+            "no data for identifier '{}'. Known identifiers: {}",
+            identifier,
+            known.join(", ")
+        );
+    }
+    Ok(())
+}
 
+/// Read `(period, identifier, downloads)` rows from `query`, which binds the
+/// identifier to select (or `NULL` for all of them) as `?1`.
+fn identifier_rows(
+    conn: &Connection,
+    query: &str,
+    identifier: Option<&str>,
+) -> Result<Vec<(String, String, i64)>> {
     let mut stmt = conn.prepare(query)?;
-    let rows = stmt.query_map([limit], |row| {
-        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
-    })?;
+    let rows = stmt
+        .query_map([identifier], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows)
+}
+
+/// Sum `(period, identifier, downloads)` rows per period, returning the
+/// newest `limit` periods first.
+fn sum_periods(rows: &[(String, String, i64)], limit: usize) -> Vec<(String, i64)> {
+    let mut periods: BTreeMap<&str, i64> = BTreeMap::new();
+    for (period, _, downloads) in rows {
+        *periods.entry(period).or_default() += downloads;
+    }
+    periods
+        .into_iter()
+        .rev()
+        .take(limit)
+        .map(|(period, downloads)| (period.to_string(), downloads))
+        .collect()
+}
+
+/// A table with a column per identifier and their total, for the newest
+/// `limit` periods of `(period, identifier, downloads)` rows. Identifiers
+/// with the most downloads over the periods shown come first.
+fn breakdown_table(
+    header: &str,
+    rows: &[(String, String, i64)],
+    limit: usize,
+    label: impl Fn(&str) -> Result<String>,
+) -> Result<Table> {
+    let mut periods: BTreeMap<&str, BTreeMap<&str, u64>> = BTreeMap::new();
+    for (period, identifier, downloads) in rows {
+        *periods
+            .entry(period)
+            .or_default()
+            .entry(identifier)
+            .or_default() += *downloads as u64;
+    }
+    let shown: Vec<_> = periods.into_iter().rev().take(limit).collect();
+
+    let mut totals: BTreeMap<&str, u64> = BTreeMap::new();
+    for (_, counts) in &shown {
+        for (identifier, downloads) in counts {
+            *totals.entry(identifier).or_default() += downloads;
+        }
+    }
+    let mut identifiers: Vec<(&str, u64)> = totals.into_iter().collect();
+    identifiers.sort_by(|(a, a_total), (b, b_total)| b_total.cmp(a_total).then(a.cmp(b)));
+
+    let mut table = Table::new(
+        std::iter::once(Column::left(header, 12))
+            .chain(
+                identifiers
+                    .iter()
+                    .map(|(identifier, _)| Column::right(*identifier, 12)),
+            )
+            .chain([Column::right("Total", 12)]),
+    );
+    for (period, counts) in shown {
+        table.push(
+            std::iter::once(Cell::Text(label(period)?))
+                .chain(identifiers.iter().map(|(identifier, _)| {
+                    Cell::Count(counts.get(identifier).copied().unwrap_or(0))
+                }))
+                .chain([Cell::Count(counts.values().sum())]),
+        );
+    }
+    Ok(table)
+}
+
+/// A table of weekly rows (newest first), optionally with the change from
+/// the previous week. `rows` may contain one more row than `limit`, which is
+/// only used for the delta.
+fn weekly_table(rows: &[(String, i64)], limit: usize, delta: bool) -> Table {
+    let mut columns = vec![Column::left("Week", 12), Column::right("Downloads", 15)];
+    if delta {
+        columns.push(Column::right("Change", 10));
+    }
+    let mut table = Table::new(columns);
+
+    for (idx, (week, downloads)) in rows.iter().take(limit).enumerate() {
+        let mut row = vec![Cell::text(week), Cell::Count(*downloads as u64)];
+        if delta {
+            let previous = rows.get(idx + 1).map(|(_, d)| *d as u64);
+            row.push(Cell::percent_change(analysis::percent_change(
+                *downloads as u64,
+                previous,
+            )));
+        }
+        table.push(row);
+    }
+    table
+}
+
+fn query_weekly_platform(
+    conn: &Connection,
+    limit: usize,
+    platform: &str,
+    delta: bool,
+    range: DateRange,
+) -> Result<Report> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT week_start, downloads FROM weekly_platform_stats
+         WHERE platform = ?1 AND {}
+         ORDER BY week_start DESC LIMIT ?2",
+        range.condition("week_start")
+    ))?;
+    let rows = stmt
+        .query_map(rusqlite::params![platform, limit + 1], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if rows.is_empty() {
+        let mut stmt =
+            conn.prepare("SELECT DISTINCT platform FROM weekly_platform_stats ORDER BY platform")?;
+        let known = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if known.iter().any(|known| known == platform) {
+            return Ok(Report::empty(format!(
+                "No GitHub downloads for {} {}.",
+                platform,
+                range.describe()
+            )));
+        }
+        anyhow::bail!(
+            "no data for platform '{}'. Known platforms: {}",
+            platform,
+            known.join(", ")
+        );
+    }
+
+    Ok(
+        Report::new(format!("GitHub downloads for {}", platform)).table(weekly_table(
+            &label_weeks(conn, rows)?,
+            limit,
+            delta,
+        )),
+    )
+}
+
+fn query_monthly(
+    conn: &Connection,
+    limit: usize,
+    source: &str,
+    quarterly: bool,
+    identifiers: &Identifiers,
+) -> Result<Report> {
+    check_identifier(conn, source, identifiers.only.as_deref())?;
+    let (bucket, header) = if quarterly {
+        (
+            "strftime('%Y', month_start) || '-Q' || ((CAST(strftime('%m', month_start) AS INTEGER) + 2) / 3)",
+            "Quarter",
+        )
+    } else {
+        ("substr(month_start, 1, 7)", "Month")
+    };
+    let rows = identifier_rows(
+        conn,
+        &format!(
+            "SELECT {bucket} AS bucket, identifier, SUM(downloads) FROM monthly_stats
+             WHERE {} AND (?1 IS NULL OR identifier = ?1)
+             GROUP BY bucket, identifier",
+            source_condition(source)
+        ),
+        identifiers.only.as_deref(),
+    )?;
+    if identifiers.breakdown {
+        return Ok(
+            Report::default().table(breakdown_table(header, &rows, limit, |bucket| {
+                Ok(bucket.to_string())
+            })?),
+        );
+    }
+
+    let mut table = Table::new([Column::left(header, 12), Column::right("Downloads", 15)]);
+    for (bucket, downloads) in sum_periods(&rows, limit) {
+        table.push([Cell::Text(bucket), Cell::Count(downloads as u64)]);
+    }
+
+    Ok(Report::default().table(table))
+}
+
+fn query_total(
+    conn: &Connection,
+    source: &str,
+    range: DateRange,
+    identifiers: &Identifiers,
+) -> Result<Report> {
+    check_identifier(conn, source, identifiers.only.as_deref())?;
+    let description = match source {
+        "github" => "GitHub releases (tracked period)",
+        "crates" => "crates.io (last year)",
+        "installer" => "Install endpoints",
+        _ => "All sources",
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT identifier, SUM(downloads) FROM weekly_stats
+         WHERE {} AND (?1 IS NULL OR identifier = ?1) AND {}
+         GROUP BY identifier
+         ORDER BY SUM(downloads) DESC, identifier",
+        source_condition(source),
+        range.condition("week_start")
+    ))?;
+    let totals = stmt
+        .query_map([identifiers.only.as_deref()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let total_downloads: i64 = totals.iter().map(|(_, downloads)| downloads).sum();
+
+    let mut table = Table::new([Column::left("Source", 12), Column::right("Downloads", 15)]);
+    if identifiers.breakdown {
+        for (identifier, downloads) in totals {
+            table.push([Cell::Text(identifier), Cell::Count(downloads as u64)]);
+        }
+    }
+    match &identifiers.only {
+        Some(identifier) => {
+            table.push([Cell::text(identifier), Cell::Count(total_downloads as u64)])
+        }
+        None => table.push([Cell::text(description), Cell::Count(total_downloads as u64)]),
+    }
+    if !range.is_unbounded() {
+        return Ok(
+            Report::new(format!("Total downloads, {}", range.describe()))
+                .table(table)
+                .note("Weeks are counted in the range if they start in it."),
+        );
+    }
+    let mut report = Report::new("Total downloads").table(table);
 
-    println!("\n{:<12} {:>15}", "Week", "Downloads");
-    println!("{}", "=".repeat(30));
+    if !matches!(source, "crates" | "installer") && identifiers.only.is_none() {
+        let excluded: i64 = conn.query_row(
+            "SELECT COALESCE(SUM(download_count), 0) FROM github_excluded
+             WHERE date = (SELECT MAX(date) FROM github_snapshots)",
+            [],
+            |row| row.get(0),
+        )?;
+        if excluded > 0 {
+            report = report.note(format!(
+                "Not counted: {} cumulative downloads of excluded GitHub assets",
+                format::number(excluded as u64)
+            ));
+        }
+    }
+
+    Ok(report)
+}
 
+fn query_daily(
+    conn: &Connection,
+    limit: usize,
+    source: &str,
+    identifiers: &Identifiers,
+) -> Result<Report> {
+    check_identifier(conn, source, identifiers.only.as_deref())?;
+    if identifiers.breakdown {
+        let rows = identifier_rows(
+            conn,
+            "SELECT date, crate_name, SUM(downloads) FROM crates_downloads
+             WHERE ?1 IS NULL OR crate_name = ?1
+             GROUP BY date, crate_name",
+            identifiers.only.as_deref(),
+        )?;
+        return Ok(
+            Report::default().table(breakdown_table("Date", &rows, limit, |date| {
+                Ok(date.to_string())
+            })?),
+        );
+    }
+
+    let github = "SELECT date, downloads AS github, 0 AS crates, negative AS flagged
+                  FROM github_daily";
+    let crates = "SELECT date, 0 AS github, downloads AS crates, 0 AS flagged
+                  FROM crates_downloads
+                  WHERE ?2 IS NULL OR crate_name = ?2";
+    let inner = match source {
+        "github" => github.to_string(),
+        "crates" => crates.to_string(),
+        _ => format!("{} UNION ALL {}", github, crates),
+    };
+    let query = format!(
+        "SELECT date, SUM(github), SUM(crates), SUM(flagged) FROM ({})
+         GROUP BY date
+         ORDER BY date DESC LIMIT ?1",
+        inner
+    );
+
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(
+        rusqlite::params![limit, identifiers.only.as_deref()],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        },
+    )?;
+
+    let mut table = Table::new([
+        Column::left("Date", 12),
+        Column::right("GitHub", 15),
+        Column::right("crates.io", 15),
+        Column::left("Decreasing", 0),
+    ]);
+    let mut any_flagged = false;
     for row in rows {
-        let (week, downloads) = row?;
-        println!("{:<12} {:>15}", week, format_number(downloads as u64));
+        let (date, github, crates, flagged) = row?;
+        any_flagged |= flagged > 0;
+        table.push([
+            Cell::Text(date),
+            Cell::Count(github as u64),
+            Cell::Count(crates as u64),
+            Cell::Flag(flagged > 0),
+        ]);
     }
 
-    Ok(())
+    let mut report = Report::default()
+        .table(table)
+        .note("GitHub deltas cover the days since the previous snapshot.");
+    if any_flagged {
+        report = report.note("* Some assets had decreasing download counts on this date.");
+    }
+
+    Ok(report)
 }
 
-fn query_total(conn: &Connection, source: &str) -> Result<()> {
-    let (total_downloads, description) = match source {
-        "github" => {
-            let total: i64 = conn.query_row(
-                "SELECT SUM(downloads) FROM weekly_stats WHERE source = 'github'",
-                [],
-                |row| row.get(0),
-            )?;
-            (total, "GitHub releases (tracked period)")
+fn query_version_share(
+    conn: &Connection,
+    limit: usize,
+    source: &str,
+    versions: usize,
+) -> Result<Report> {
+    let (source, label) = match source {
+        "github" => ("github", "GitHub releases"),
+        "crates" => ("crates", "crates.io"),
+        _ => ("all", "all sources"),
+    };
+    let share = analysis::version_share(conn, source, versions)?;
+    if share.weeks.is_empty() {
+        return Ok(Report::empty(format!(
+            "No downloads with a known version for {}.",
+            label
+        )));
+    }
+
+    let mut table = Table::new(
+        std::iter::once(Column::left("Week", 12)).chain(
+            share
+                .versions
+                .iter()
+                .map(|version| Column::right(version, 8)),
+        ),
+    );
+    let convention = aggregate::stored_week_start(conn)?;
+    for (week_start, shares) in share.weeks.iter().rev().take(limit) {
+        table.push(
+            std::iter::once(Cell::text(aggregate::week_label(convention, *week_start)))
+                .chain(shares.iter().map(|share| Cell::percent(*share, 1))),
+        );
+    }
+
+    Ok(Report::new(format!("Share of weekly downloads by version ({})", label)).table(table))
+}
+
+fn query_versions(conn: &Connection, source: &str, days: u32, top: usize) -> Result<Report> {
+    let (source, label) = match source {
+        "github" => ("github", "GitHub releases"),
+        "crates" => ("crates", "crates.io"),
+        _ => ("all", "all sources"),
+    };
+    let versions = analysis::version_downloads(conn, source, days)?;
+    if versions.is_empty() {
+        return Ok(Report::empty(format!(
+            "No downloads with a known version for {} in the last {} days of data.",
+            label, days
+        )));
+    }
+
+    let by_source = source == "all";
+    let mut columns = vec![Column::left("Version", 12)];
+    if by_source {
+        columns.push(Column::right("crates.io", 12));
+        columns.push(Column::right("GitHub", 12));
+    }
+    columns.extend([
+        Column::right("Downloads", 12),
+        Column::right("Share", 8),
+        Column::right("Cumulative", 10),
+    ]);
+    let mut table = Table::new(columns);
+
+    let total: u64 = versions.iter().map(|version| version.total()).sum();
+    let share = |downloads: u64| downloads as f64 / total.max(1) as f64;
+    let mut cumulative = 0;
+    let mut row = |name: String, crates: u64, github: u64| {
+        cumulative += crates + github;
+        let mut row = vec![Cell::Text(name)];
+        if by_source {
+            row.extend([Cell::Count(crates), Cell::Count(github)]);
         }
-        "crates" => {
-            let total: i64 = conn.query_row(
-                "SELECT SUM(downloads) FROM weekly_stats WHERE source = 'crates'",
-                [],
-                |row| row.get(0),
-            )?;
-            (total, "crates.io (last year)")
+        row.extend([
+            Cell::Count(crates + github),
+            Cell::percent(share(crates + github), 1),
+            Cell::percent(share(cumulative), 1),
+        ]);
+        row
+    };
+    for version in versions.iter().take(top) {
+        table.push(row(
+            version.version.to_string(),
+            version.crates,
+            version.github,
+        ));
+    }
+    let rest = &versions[top.min(versions.len())..];
+    if !rest.is_empty() {
+        table.push(row(
+            format!("{} others", rest.len()),
+            rest.iter().map(|version| version.crates).sum(),
+            rest.iter().map(|version| version.github).sum(),
+        ));
+    }
+
+    Ok(Report::new(format!(
+        "Downloads by version over the last {} days of data ({})",
+        days, label
+    ))
+    .table(table)
+    .note(
+        "Downloads without a known version are left out. GitHub downloads are counted on \
+         the date of the snapshot that first saw them.",
+    ))
+}
+
+fn query_version_growth(
+    conn: &Connection,
+    source: &str,
+    weeks: u32,
+    top: usize,
+    lines: bool,
+) -> Result<Report> {
+    let (source, label) = match source {
+        "github" => ("github", "GitHub releases"),
+        "crates" => ("crates", "crates.io"),
+        _ => ("all", "all sources"),
+    };
+    let Some(growth) = analysis::version_growth(conn, source, weeks, lines)? else {
+        return Ok(Report::empty(format!(
+            "No complete weeks of downloads for {}.",
+            label
+        )));
+    };
+    if growth.versions.is_empty() {
+        return Ok(Report::empty(format!(
+            "No downloads with a known version for {} {}.",
+            label,
+            growth.recent.describe()
+        )));
+    }
+
+    let mut table = Table::new([
+        Column::left(if lines { "Line" } else { "Version" }, 12),
+        Column::right("Downloads", 12),
+        Column::right("Previous", 12),
+        Column::right("Change", 12),
+        Column::right("Change %", 9),
+        Column::right("Share", 8),
+    ]);
+    let total: u64 = growth.versions.iter().map(|version| version.recent).sum();
+    for version in growth.versions.iter().take(top) {
+        table.push([
+            Cell::text(&version.version),
+            Cell::Count(version.recent),
+            Cell::Count(version.previous),
+            Cell::Change(version.change()),
+            Cell::percent_change(analysis::percent_change(
+                version.recent,
+                Some(version.previous),
+            )),
+            Cell::percent(version.recent as f64 / total.max(1) as f64, 1),
+        ]);
+    }
+
+    let mut report = Report::new(format!(
+        "Downloads by {} {} against {} ({})",
+        if lines { "line" } else { "version" },
+        growth.recent.describe(),
+        growth.previous.describe(),
+        label
+    ))
+    .table(table)
+    .note(
+        "Only complete weeks are counted. Downloads without a known version are left out. \
+         GitHub downloads are counted on the date of the snapshot that first saw them.",
+    );
+    if growth.versions.len() > top {
+        report = report.note(format!(
+            "{} more that grew less aren't shown; raise --top to see them.",
+            growth.versions.len() - top
+        ));
+    }
+    Ok(report)
+}
+
+/// Show GitHub release downloads per platform over `range`, or over the last
+/// `days` days of data if the range is unbounded.
+fn query_platforms(
+    conn: &Connection,
+    by: PlatformGroup,
+    days: u32,
+    range: DateRange,
+) -> Result<Report> {
+    let range = if range.is_unbounded() {
+        let Some(latest) = analysis::latest_data_date(conn, "github")? else {
+            return Ok(Report::empty("No GitHub release downloads found."));
+        };
+        DateRange::last_days(latest, days)?
+    } else {
+        range
+    };
+    let groups = analysis::platform_downloads(conn, by, range)?;
+    if groups.is_empty() {
+        return Ok(Report::empty(format!(
+            "No GitHub release downloads found ({}).",
+            range.describe()
+        )));
+    }
+
+    let total: u64 = groups.iter().map(|group| group.downloads).sum();
+    let mut table = Table::new([
+        Column::left(by.header(), 32),
+        Column::right("Downloads", 12),
+        Column::right("Share", 8),
+    ]);
+    for group in &groups {
+        table.push([
+            Cell::text(&group.group),
+            Cell::Count(group.downloads),
+            Cell::percent(group.downloads as f64 / total as f64, 1),
+        ]);
+    }
+    Ok(Report::new(format!(
+        "GitHub release downloads by {}, {}",
+        by.noun(),
+        range.describe()
+    ))
+    .table(table)
+    .note(format!(
+        "Checksums and signatures are left out. Assets without a recognized target \
+         are counted as '{}'. Downloads are counted on the date of the snapshot that \
+         first saw them.",
+        UNKNOWN_PLATFORM
+    )))
+}
+
+fn query_channel(conn: &Connection, limit: usize, source: &str) -> Result<Report> {
+    let (source, label) = match source {
+        "github" => ("github", "GitHub releases"),
+        "crates" => ("crates", "crates.io"),
+        _ => ("all", "all sources"),
+    };
+    let weeks = analysis::release_channels(conn, source)?;
+    if weeks.is_empty() {
+        return Ok(Report::empty(format!(
+            "No downloads with a known version for {}.",
+            label
+        )));
+    }
+
+    let mut table = Table::new([
+        Column::left("Week", 12),
+        Column::right("Stable", 12),
+        Column::right("Pre-release", 12),
+        Column::right("Pre-release %", 14),
+    ]);
+    let convention = aggregate::stored_week_start(conn)?;
+    for week in weeks.iter().rev().take(limit) {
+        table.push([
+            Cell::text(aggregate::week_label(convention, week.week_start)),
+            Cell::Count(week.stable),
+            Cell::Count(week.prerelease),
+            Cell::optional(week.prerelease_share(), |share| Cell::percent(share, 2)),
+        ]);
+    }
+
+    Ok(Report::new(format!(
+        "Weekly stable and pre-release downloads ({})",
+        label
+    ))
+    .table(table))
+}
+
+fn query_verification(conn: &Connection, limit: usize) -> Result<Report> {
+    let weeks = analysis::verification(conn)?;
+    if weeks.is_empty() {
+        return Ok(Report::empty(
+            "No releases with signatures or attestations have been downloaded.",
+        ));
+    }
+
+    let mut table = Table::new([
+        Column::left("Week", 12),
+        Column::right("Artifacts", 12),
+        Column::right("Signatures", 12),
+        Column::right("Ratio", 12),
+    ]);
+    let convention = aggregate::stored_week_start(conn)?;
+    for week in weeks.iter().rev().take(limit) {
+        table.push([
+            Cell::text(aggregate::week_label(convention, week.week_start)),
+            Cell::Count(week.artifacts),
+            Cell::Count(week.signatures),
+            Cell::optional(week.signature_ratio(), |ratio| Cell::percent(ratio, 2)),
+        ]);
+    }
+
+    Ok(
+        Report::new("Weekly artifact and signature downloads (signed GitHub releases)")
+            .table(table)
+            .note(
+                "Signature downloads are a proxy: verifying without downloading them isn't counted.",
+            ),
+    )
+}
+
+fn query_metrics(conn: &Connection, metrics: &Metrics) -> Result<Report> {
+    if metrics.is_empty() {
+        return Ok(Report::empty(
+            "No metrics are defined. Add them to the [metrics] section of the config.",
+        ));
+    }
+
+    let mut table = Table::new([
+        Column::left("Metric", 24),
+        Column::left("Week", 12),
+        Column::right("Value", 12),
+        Column::left("Expression", 0),
+        Column::left("Description", 0),
+    ]);
+    let convention = aggregate::stored_week_start(conn)?;
+    let latest = metrics.latest(conn)?;
+    for metric in metrics.iter() {
+        let value = latest.iter().find(|value| value.name == metric.name);
+        table.push([
+            Cell::text(&metric.name),
+            Cell::optional(value, |value| {
+                Cell::text(aggregate::week_label(convention, value.week_start))
+            }),
+            Cell::optional(value, |value| Cell::Number {
+                value: value.value,
+                display: value.display.clone(),
+            }),
+            Cell::text(&metric.expr),
+            Cell::optional(metric.description.as_ref(), Cell::text),
+        ]);
+    }
+
+    Ok(Report::new("Derived metrics (latest complete week)").table(table))
+}
+
+fn query_metric(conn: &Connection, metrics: &Metrics, name: &str, limit: usize) -> Result<Report> {
+    let series = metrics.evaluate(conn, name)?;
+    let metric = metrics.get(name).expect("evaluated metrics exist");
+
+    let mut title = format!("{}: {}", metric.name, metric.expr);
+    if let Some(description) = &metric.description {
+        title = format!("{}\n{}", title, description);
+    }
+    let mut report = Report::new(title);
+    if series.is_empty() {
+        return Ok(report.note("No complete weeks have values for this metric."));
+    }
+
+    let mut table = Table::new([Column::left("Week", 12), Column::right("Value", 15)]);
+    let convention = aggregate::stored_week_start(conn)?;
+    for (week, value) in series.iter().rev().take(limit) {
+        table.push([
+            Cell::text(aggregate::week_label(convention, *week)),
+            Cell::Number {
+                value: *value,
+                display: metrics::format_value(metric.format, *value),
+            },
+        ]);
+    }
+    report.tables.push(table);
+
+    Ok(report)
+}
+
+fn query_headline(conn: &Connection, config: &HeadlineConfig) -> Result<Report> {
+    let headline = headline::compute(conn, config)?;
+
+    let mut table = Table::new([
+        Column::left("Source", 12),
+        Column::right("Downloads", 15),
+        Column::right("Excluded", 15),
+    ]);
+    for source in &headline.sources {
+        table.push([
+            Cell::text(source.kind),
+            Cell::Count(source.downloads),
+            Cell::Count(source.excluded),
+        ]);
+    }
+    if headline.adjustment != 0 {
+        table.push([
+            Cell::text("Adjustment"),
+            Cell::Change(headline.adjustment),
+            Cell::Missing,
+        ]);
+    }
+    if headline.total != headline.exact {
+        table.push([
+            Cell::text("Exact"),
+            Cell::Count(headline.exact),
+            Cell::Missing,
+        ]);
+    }
+    table.push([
+        Cell::text("Headline"),
+        Cell::Count(headline.total),
+        Cell::Missing,
+    ]);
+
+    Ok(Report::new("Headline total downloads").table(table))
+}
+
+fn query_projects(conn: &Connection, limit: usize, monthly: bool) -> Result<Report> {
+    let downloads = analysis::project_downloads(conn, monthly)?;
+    if downloads.projects.is_empty() {
+        return Ok(Report::empty(
+            "No project downloads. Group sources with [[project]] in the configuration.",
+        ));
+    }
+
+    let (period, title) = if monthly {
+        ("Month", "Monthly")
+    } else {
+        ("Week", "Weekly")
+    };
+    let mut table = Table::new(
+        std::iter::once(Column::left(period, 12)).chain(
+            downloads
+                .projects
+                .iter()
+                .map(|project| Column::right(project, 12)),
+        ),
+    );
+
+    let convention = aggregate::stored_week_start(conn)?;
+    for (start, counts) in downloads.periods.iter().rev().take(limit) {
+        let label = if monthly {
+            start.format("%Y-%m").to_string()
+        } else {
+            aggregate::week_label(convention, *start)
+        };
+        table.push(
+            std::iter::once(Cell::Text(label))
+                .chain(counts.iter().map(|count| Cell::Count(*count))),
+        );
+    }
+
+    Ok(Report::new(format!("{} downloads by project", title)).table(table))
+}
+
+fn query_state(conn: &Connection) -> Result<Report> {
+    let states = db::get_collector_state(conn)?;
+    if states.is_empty() {
+        return Ok(Report::empty("No sources have been collected yet."));
+    }
+
+    let mut table = Table::new([
+        Column::left("Source", 0),
+        Column::left("Last attempt", 0),
+        Column::left("Last success", 0),
+        Column::left("Collected through", 0),
+        Column::left("ETag", 0),
+        Column::right("Failures in a row", 0),
+        Column::left("Last error", 0),
+    ]);
+    for state in states {
+        table.push([
+            Cell::Text(format!("{}:{}", state.kind, state.name)),
+            Cell::Text(state.last_attempt_at),
+            Cell::text(state.last_success_at.as_deref().unwrap_or("never")),
+            Cell::optional(state.last_collected_date, |date| {
+                Cell::Text(date.to_string())
+            }),
+            Cell::optional(state.etag, Cell::Text),
+            Cell::Count(state.consecutive_failures as u64),
+            Cell::optional(state.last_error, Cell::Text),
+        ]);
+    }
+
+    Ok(Report::new("Collector state").table(table))
+}
+
+fn query_runs(conn: &Connection, limit: usize) -> Result<Report> {
+    let runs = db::get_collection_runs(conn, limit)?;
+    if runs.is_empty() {
+        return Ok(Report::empty("No collect runs have been recorded yet."));
+    }
+
+    let mut table = Table::new([
+        Column::left("Started", 20),
+        Column::right("Seconds", 0),
+        Column::left("Status", 0),
+    ])
+    .named("runs", "Collect runs");
+    let mut skips = Table::new([
+        Column::left("Started", 20),
+        Column::left("Source", 32),
+        Column::left("Filter", 15),
+        Column::right("Skipped", 8),
+        Column::right("Downloads", 12),
+        Column::right("Kept", 8),
+        Column::left("Excludes everything", 0),
+    ])
+    .named("skips", "Left out by filters");
+    for run in runs {
+        let started = run.started_at.format("%Y-%m-%d %H:%M UTC").to_string();
+        table.push([
+            Cell::text(&started),
+            Cell::Count((run.finished_at - run.started_at).num_seconds().max(0) as u64),
+            Cell::Text(match &run.error {
+                Some(error) => format!("failed: {}", error),
+                None => "ok".to_string(),
+            }),
+        ]);
+        for (source, skip) in &run.skips {
+            skips.push([
+                Cell::text(&started),
+                Cell::text(source),
+                Cell::text(&skip.filter),
+                Cell::Count(skip.skipped),
+                Cell::Count(skip.downloads),
+                Cell::Count(skip.kept),
+                Cell::Flag(skip.excludes_everything()),
+            ]);
+        }
+    }
+
+    Ok(Report::default().table(table).table(skips))
+}
+
+fn query_owners(conn: &Connection, crate_name: Option<&str>) -> Result<Report> {
+    let mut stmt = conn.prepare(
+        "SELECT crate_name, login, kind, first_seen FROM crate_owners
+         WHERE ?1 IS NULL OR crate_name = ?1
+         ORDER BY crate_name, kind DESC, login",
+    )?;
+    let owners = stmt
+        .query_map([crate_name], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    if owners.is_empty() {
+        return Ok(Report::empty("No crate owners have been recorded yet."));
+    }
+
+    let mut table = Table::new([
+        Column::left("Crate", 24),
+        Column::left("Login", 40),
+        Column::left("Kind", 6),
+        Column::left("Since", 0),
+    ])
+    .named("owners", "Crate owners");
+    for (crate_name, login, kind, first_seen) in owners {
+        table.push([
+            Cell::Text(crate_name),
+            Cell::Text(login),
+            Cell::Text(kind),
+            Cell::Text(first_seen),
+        ]);
+    }
+    let mut report = Report::default().table(table);
+
+    let changes = db::get_crate_owner_changes(conn, crate_name)?;
+    if !changes.is_empty() {
+        let mut table = Table::new([
+            Column::left("Date", 12),
+            Column::left("Crate", 24),
+            Column::left("Change", 8),
+            Column::left("Kind", 6),
+            Column::left("Login", 0),
+        ])
+        .named("changes", "Owner changes");
+        for change in changes {
+            table.push([
+                Cell::Text(change.date.to_string()),
+                Cell::Text(change.crate_name),
+                Cell::text(change.change.as_str()),
+                Cell::Text(change.kind),
+                Cell::Text(change.login),
+            ]);
+        }
+        report = report.table(table);
+    }
+
+    Ok(report)
+}
+
+fn query_tombstones(conn: &Connection, source: &str) -> Result<Report> {
+    let source = match source {
+        "github" | "crates" => Some(source),
+        "all" => None,
+        _ => bail!(
+            "unknown source '{}' (expected 'github', 'crates', or 'all')",
+            source
+        ),
+    };
+    let tombstones = db::get_tombstones(conn, source)?;
+    if tombstones.is_empty() {
+        return Ok(Report::empty(
+            "No deleted, yanked, or restored artifacts have been recorded.",
+        ));
+    }
+
+    let mut table = Table::new([
+        Column::left("Date", 12),
+        Column::left("Source", 8),
+        Column::left("Release or crate", 24),
+        Column::left("Asset or version", 48),
+        Column::left("Event", 10),
+        Column::right("Last count", 12),
+    ])
+    .named("tombstones", "Tombstones");
+    for tombstone in tombstones {
+        table.push([
+            Cell::Text(tombstone.date.to_string()),
+            Cell::Text(tombstone.source),
+            Cell::Text(tombstone.identifier),
+            Cell::Text(tombstone.artifact),
+            Cell::text(tombstone.event.as_str()),
+            Cell::Count(tombstone.last_count),
+        ]);
+    }
+    Ok(Report::default().table(table))
+}
+
+fn query_rolling(conn: &Connection, limit: usize, source: &str) -> Result<Report> {
+    let filter = match source {
+        "github" | "crates" | "installer" => "WHERE source = ?2",
+        _ => "WHERE ?2 IS NOT NULL",
+    };
+    let query = format!(
+        "SELECT date, SUM(downloads), SUM(avg_7d), SUM(avg_30d)
+         FROM rolling_stats
+         {}
+         GROUP BY date
+         ORDER BY date DESC LIMIT ?1",
+        filter
+    );
+
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(rusqlite::params![limit, source], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, f64>(1)?,
+            row.get::<_, f64>(2)?,
+            row.get::<_, f64>(3)?,
+        ))
+    })?;
+
+    let mut table = Table::new([
+        Column::left("Date", 12),
+        Column::right("Downloads", 12),
+        Column::right("7-day avg", 12),
+        Column::right("30-day avg", 12),
+    ]);
+    for row in rows {
+        let (date, downloads, avg_7d, avg_30d) = row?;
+        table.push([
+            Cell::Text(date),
+            Cell::Count(downloads.round() as u64),
+            Cell::Count(avg_7d.round() as u64),
+            Cell::Count(avg_30d.round() as u64),
+        ]);
+    }
+
+    Ok(Report::default()
+        .table(table)
+        .note("GitHub downloads are spread evenly over the days between snapshots."))
+}
+
+pub(crate) fn query_growth(conn: &Connection) -> Result<Report> {
+    let growth = analysis::growth(conn)?;
+
+    let mut table = Table::new([
+        Column::left("Source", 10),
+        Column::left("Metric", 7),
+        Column::left("Period", 12),
+        Column::right("Downloads", 12),
+        Column::left("Previous", 12),
+        Column::right("Previous downloads", 12),
+        Column::right("Change", 10),
+    ]);
+    for row in &growth {
+        table.push([
+            Cell::text(row.source),
+            Cell::text(row.metric.label()),
+            Cell::Text(row.period.to_string()),
+            Cell::Count(row.downloads),
+            Cell::Text(row.previous_period.to_string()),
+            Cell::optional(row.previous, Cell::Count),
+            Cell::percent_change(row.change()),
+        ]);
+    }
+
+    Ok(Report::default()
+        .table(table)
+        .note("Only complete weeks and months are compared."))
+}
+
+/// Show downloads from each source in two periods side by side.
+fn query_compare(
+    conn: &Connection,
+    period: ComparePeriod,
+    against: CompareBaseline,
+) -> Result<Report> {
+    let mut latest = None;
+    for source in analysis::SOURCES {
+        latest = latest.max(analysis::latest_data_date(conn, source)?);
+    }
+    let Some(latest) = latest else {
+        return Ok(Report::empty("No downloads collected yet."));
+    };
+    let period = match period {
+        ComparePeriod::Dates(range) => range,
+        ComparePeriod::Last(days) => DateRange::last_days(latest, days)?,
+    };
+    let (Some(since), Some(until), Some(days)) = (period.since, period.until, period.days()) else {
+        bail!("the period to compare has to have a start and an end");
+    };
+    let baseline = match against {
+        CompareBaseline::Previous => {
+            let until = since
+                .pred_opt()
+                .with_context(|| format!("there are no dates before {}", since))?;
+            let days = u32::try_from(days)
+                .with_context(|| format!("the period of {} days is too long", days))?;
+            DateRange::last_days(until, days)?
         }
-        "all" | _ => {
-            let total: i64 =
-                conn.query_row("SELECT SUM(downloads) FROM weekly_stats", [], |row| {
-                    row.get(0)
-                })?;
-            (total, "All sources")
+        CompareBaseline::Year => {
+            let year_before = |date: NaiveDate| {
+                date.checked_sub_months(Months::new(12))
+                    .with_context(|| format!("there is no date a year before {}", date))
+            };
+            DateRange::new(Some(year_before(since)?), Some(year_before(until)?))?
         }
+        CompareBaseline::Dates(range) => range,
+    };
+
+    let current = analysis::source_totals(conn, period)?;
+    let previous = analysis::source_totals(conn, baseline)?;
+    let mut table = Table::new([
+        Column::left("Source", 10),
+        Column::right("Downloads", 12),
+        Column::right("Against", 12),
+        Column::right("Change", 12),
+        Column::right("Change %", 9),
+    ]);
+    let mut row = |source: &str, downloads: u64, against: u64| {
+        table.push([
+            Cell::text(source),
+            Cell::Count(downloads),
+            Cell::Count(against),
+            Cell::Change(downloads as i64 - against as i64),
+            Cell::percent_change(analysis::percent_change(downloads, Some(against))),
+        ]);
     };
+    let earliest = since.min(baseline.since.unwrap_or(since));
+    let mut partial = Vec::new();
+    for (current, previous) in current.iter().zip(&previous) {
+        // Sources that aren't collected, such as install endpoints without
+        // any configured, are left out.
+        if current.first_date.is_none() {
+            continue;
+        }
+        row(current.source, current.downloads, previous.downloads);
+        if current.first_date > Some(earliest) {
+            partial.push(current.source);
+        }
+    }
+    row(
+        "total",
+        current.iter().map(|total| total.downloads).sum(),
+        previous.iter().map(|total| total.downloads).sum(),
+    );
 
-    println!("\nTotal downloads");
-    println!("  Source: {}", description);
-    println!("  Total:  {}", format_number(total_downloads as u64));
+    let mut report = Report::new(format!(
+        "Downloads {} against {}",
+        period.describe(),
+        baseline.describe()
+    ))
+    .table(table)
+    .note("GitHub downloads are counted on the date of the snapshot that first saw them.");
+    if until > latest {
+        report = report.note(format!(
+            "Data only goes up to {}, so the period isn't over yet.",
+            latest
+        ));
+    }
+    if !partial.is_empty() {
+        report = report.note(format!(
+            "Data for {} starts after {}, so the comparison is skewed.",
+            partial.join(", "),
+            earliest
+        ));
+    }
+    Ok(report)
+}
 
+/// Show summary statistics of the complete weeks of each source and of all
+/// sources together.
+pub(crate) fn query_stats(conn: &Connection) -> Result<Report> {
+    let mut table = Table::new([
+        Column::left("Source", 10),
+        Column::right("Weeks", 5),
+        Column::left("First week", 10),
+        Column::left("Last week", 10),
+        Column::right("Total", 12),
+        Column::right("Min", 10),
+        Column::right("Max", 10),
+        Column::left("Max week", 10),
+        Column::right("Mean", 10),
+        Column::right("Median", 10),
+        Column::right("P90", 10),
+        Column::right("Growth streak", 13),
+        Column::left("Streak end", 10),
+    ]);
+    for source in analysis::SOURCES.into_iter().chain(["all"]) {
+        let Some(summary) = analysis::weekly_summary(conn, source)? else {
+            continue;
+        };
+        let round = |value: f64| Cell::Number {
+            value,
+            display: format::number(value.round() as u64),
+        };
+        table.push([
+            Cell::text(source),
+            Cell::Count(summary.weeks as u64),
+            Cell::Text(summary.first_week.to_string()),
+            Cell::Text(summary.last_week.to_string()),
+            Cell::Count(summary.total),
+            Cell::Count(summary.min.1),
+            Cell::Count(summary.max.1),
+            Cell::Text(summary.max.0.to_string()),
+            round(summary.mean),
+            round(summary.median),
+            round(summary.p90),
+            Cell::optional(summary.growth_streak, |(_, _, weeks)| {
+                Cell::Count(weeks as u64)
+            }),
+            Cell::optional(summary.growth_streak, |(_, end, _)| {
+                Cell::Text(end.to_string())
+            }),
+        ]);
+    }
+    if table.rows.is_empty() {
+        return Ok(Report::empty("No complete weeks of downloads yet."));
+    }
+
+    Ok(Report::new("Weekly downloads").table(table).note(
+        "Only complete weeks are counted. The growth streak is the longest run of weeks \
+         each with more downloads than the one before.",
+    ))
+}
+
+/// The first and latest dates with data for each source, with how many days
+/// and weeks are missing data.
+pub(crate) fn freshness_table(gaps: &[analysis::SourceGaps]) -> Table {
+    let mut table = Table::new([
+        Column::left("Source", 10),
+        Column::left("First", 10),
+        Column::left("Latest", 10),
+        Column::right("Age (days)", 10),
+        Column::right("Missing days", 12),
+        Column::right("Incomplete weeks", 16),
+    ])
+    .named("sources", "Freshness");
+    for source in gaps {
+        table.push([
+            Cell::text(source.source),
+            Cell::Text(source.first.to_string()),
+            Cell::Text(source.latest.to_string()),
+            Cell::Count(source.age_days.max(0) as u64),
+            Cell::Count(source.missing_days() as u64),
+            Cell::Count(source.incomplete_weeks.len() as u64),
+        ]);
+    }
+    table
+}
+
+/// The downloads of the week in progress so far, and projected to the full
+/// week.
+pub(crate) fn projection_table(
+    conn: &Connection,
+    projection: &analysis::WeekProjection,
+) -> Result<Table> {
+    let week_start = aggregate::stored_week_start(conn)?;
+    let mut table = Table::new([
+        Column::left("Week", 10),
+        Column::right("Days", 4),
+        Column::right("So far", 10),
+        Column::right("Projected", 10),
+    ])
+    .named("current_week", "Week in progress");
+    table.push([
+        Cell::Text(aggregate::week_label(week_start, projection.week_start)),
+        Cell::Count(projection.days as u64),
+        Cell::Count(projection.downloads),
+        Cell::Count(projection.projected),
+    ]);
+    Ok(table)
+}
+
+/// Show the freshness of each source, the days without data, and the weeks
+/// they leave incomplete, the most recent `limit` of each.
+fn gaps_report(gaps: &[analysis::SourceGaps], limit: usize) -> Report {
+    if gaps.is_empty() {
+        return Report::empty("No downloads collected yet.");
+    }
+
+    let mut missing = Table::new([
+        Column::left("Source", 10),
+        Column::left("From", 10),
+        Column::left("To", 10),
+        Column::right("Days", 6),
+    ])
+    .named("missing", "Days without data");
+    let mut weeks = Table::new([
+        Column::left("Source", 10),
+        Column::left("Week", 10),
+        Column::right("Days with data", 14),
+    ])
+    .named("weeks", "Weeks with incomplete data");
+
+    let mut missing_rows = Vec::new();
+    let mut week_rows = Vec::new();
+    for source in gaps {
+        missing_rows.extend(source.missing.iter().map(|range| (source.source, *range)));
+        week_rows.extend(
+            source
+                .incomplete_weeks
+                .iter()
+                .map(|(week, days)| (source.source, *week, *days)),
+        );
+    }
+
+    // Most recent first, across sources.
+    missing_rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    week_rows.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+    for (source, (from, to)) in missing_rows.iter().take(limit) {
+        missing.push([
+            Cell::text(*source),
+            Cell::Text(from.to_string()),
+            Cell::Text(to.to_string()),
+            Cell::Count(((*to - *from).num_days() + 1) as u64),
+        ]);
+    }
+    for (source, week, days) in week_rows.iter().take(limit) {
+        weeks.push([
+            Cell::text(*source),
+            Cell::Text(week.to_string()),
+            Cell::Count(u64::from(*days)),
+        ]);
+    }
+
+    let mut report = Report::new("Collection gaps")
+        .table(freshness_table(gaps))
+        .table(missing)
+        .table(weeks)
+        .note(
+            "GitHub downloads on missing days are counted with the next snapshot, so \
+             weekly totals stay right unless a gap spans the end of a week.",
+        );
+    let hidden = missing_rows.len().saturating_sub(limit) + week_rows.len().saturating_sub(limit);
+    if hidden > 0 {
+        report = report.note(format!(
+            "{} older rows aren't shown; raise --limit to see them.",
+            hidden
+        ));
+    }
+    report
+}
+
+/// Fail if any source's latest data is older than `max_staleness`.
+fn check_staleness(gaps: &[analysis::SourceGaps], max_staleness: Option<Duration>) -> Result<()> {
+    let Some(max_staleness) = max_staleness else {
+        return Ok(());
+    };
+    if gaps.is_empty() {
+        bail!("no data has been collected yet");
+    }
+    let stale: Vec<String> = gaps
+        .iter()
+        .filter(|source| {
+            Duration::from_secs(source.age_days.max(0) as u64 * 24 * 60 * 60) > max_staleness
+        })
+        .map(|source| format!("{} ({} days old)", source.source, source.age_days))
+        .collect();
+    if !stale.is_empty() {
+        bail!(
+            "data is older than --max-staleness for {}",
+            stale.join(", ")
+        );
+    }
     Ok(())
 }
 
-fn query_latest(conn: &Connection) -> Result<()> {
-    println!("\nLatest statistics\n");
+/// Share of downloads below which an artifact format is reported as rarely
+/// used.
+const RARE_FORMAT_SHARE: f64 = 0.01;
+
+fn query_formats(conn: &Connection, limit: usize, platform: Option<&str>) -> Result<Report> {
+    let mut stmt = conn.prepare(
+        "SELECT week_start, platform, format, downloads FROM weekly_format_stats
+         WHERE week_start IN (
+             SELECT DISTINCT week_start FROM weekly_format_stats
+             ORDER BY week_start DESC LIMIT ?1
+         )
+         AND (?2 IS NULL OR platform = ?2)",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![limit, platform], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+
+    let mut by_week: BTreeMap<String, BTreeMap<String, BTreeMap<String, u64>>> = BTreeMap::new();
+    let mut by_platform: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+    for row in rows {
+        let (week, platform, format, downloads) = row?;
+        *by_platform
+            .entry(platform.clone())
+            .or_default()
+            .entry(format.clone())
+            .or_default() += downloads as u64;
+        by_week
+            .entry(week)
+            .or_default()
+            .entry(platform)
+            .or_default()
+            .insert(format, downloads as u64);
+    }
+
+    if by_week.is_empty() {
+        anyhow::bail!("no format data found");
+    }
+
+    let formats: Vec<&str> = AssetFormat::ALL
+        .iter()
+        .map(|f| f.label())
+        .chain([aggregate::EXCLUDED_FORMAT])
+        .filter(|label| by_platform.values().any(|f| f.contains_key(*label)))
+        .collect();
+
+    let mut table = Table::new(
+        [Column::left("Week", 12), Column::left("Platform", 20)]
+            .into_iter()
+            .chain(formats.iter().map(|format| Column::right(*format, 10))),
+    );
+    for (week, platforms) in by_week.iter().rev() {
+        for (platform, counts) in platforms {
+            let total: u64 = counts.values().sum();
+            table.push([Cell::text(week), Cell::text(platform)].into_iter().chain(
+                formats.iter().map(|format| match counts.get(*format) {
+                    Some(&n) if total > 0 => Cell::percent(n as f64 / total as f64, 1),
+                    _ => Cell::Missing,
+                }),
+            ));
+        }
+    }
+
+    // Flag artifact formats that almost nobody downloads.
+    let mut rare = Vec::new();
+    for (platform, counts) in &by_platform {
+        if platform == crate::platform::UNKNOWN_PLATFORM {
+            continue;
+        }
+        let artifacts: Vec<(&String, u64)> = counts
+            .iter()
+            .filter(|(format, _)| {
+                AssetFormat::ALL
+                    .iter()
+                    .any(|f| f.label() == format.as_str() && f.is_artifact())
+            })
+            .map(|(format, n)| (format, *n))
+            .collect();
+        let total: u64 = artifacts.iter().map(|(_, n)| n).sum();
+        for (format, n) in artifacts {
+            let share = n as f64 / total.max(1) as f64;
+            if share < RARE_FORMAT_SHARE {
+                rare.push(format!(
+                    "\n  {}: {} ({:.2}% of artifact downloads)",
+                    platform,
+                    format,
+                    share * 100.0
+                ));
+            }
+        }
+    }
+
+    let mut report = Report::default()
+        .table(table)
+        .note("Shares are of each platform's downloads for the week.");
+    if !rare.is_empty() {
+        report = report.note(format!(
+            "Rarely downloaded formats (under {}% over these weeks):{}",
+            RARE_FORMAT_SHARE * 100.0,
+            rare.concat()
+        ));
+    }
+
+    Ok(report)
+}
+
+/// Share of recent downloads below which a target that is still shipped is
+/// reported as rarely used.
+const RARE_TARGET_SHARE: f64 = 0.01;
+
+fn query_targets(conn: &Connection) -> Result<Report> {
+    let matrix = analysis::target_matrix(conn)?;
+    if matrix.targets.is_empty() {
+        anyhow::bail!("no release targets found (run aggregation after collecting GitHub data)");
+    }
+
+    let recent_total: u64 = matrix.targets.iter().map(|t| t.recent_downloads).sum();
+    let share = |downloads: u64| downloads as f64 / recent_total.max(1) as f64;
+
+    let mut table = Table::new([
+        Column::left("Target", 36),
+        Column::left("Platform", 20),
+        Column::left("Added", 10),
+        Column::left("Dropped", 10),
+        Column::right("Releases", 8),
+        Column::right("Downloads", 14),
+        Column::right("Recent", 12),
+        Column::right("Share", 8),
+    ]);
+    for target in &matrix.targets {
+        table.push([
+            Cell::text(&target.target),
+            Cell::text(&target.platform),
+            Cell::text(&target.added_in),
+            Cell::optional(target.dropped_in.as_ref(), Cell::text),
+            Cell::Count(target.releases as u64),
+            Cell::Count(target.downloads),
+            Cell::Count(target.recent_downloads),
+            Cell::percent(share(target.recent_downloads), 1),
+        ]);
+    }
+    let mut report = Report::new(format!(
+        "Targets shipped by stable GitHub releases (recent = last {} days of data)",
+        analysis::RECENT_TARGET_DAYS
+    ))
+    .table(table);
+
+    if !matrix.changes.is_empty() {
+        let mut note = "Target changes between stable releases:".to_string();
+        for change in &matrix.changes {
+            let published = change
+                .published_on
+                .map(|date| format!(" ({})", date))
+                .unwrap_or_default();
+            let targets = change
+                .added
+                .iter()
+                .map(|target| format!("+{}", target))
+                .chain(change.dropped.iter().map(|target| format!("-{}", target)))
+                .collect::<Vec<_>>()
+                .join(", ");
+            note += &format!("\n  {}{}: {}", change.version, published, targets);
+        }
+        report = report.note(note);
+    }
+
+    let rare: Vec<_> = matrix
+        .targets
+        .iter()
+        .filter(|t| t.dropped_in.is_none() && share(t.recent_downloads) < RARE_TARGET_SHARE)
+        .collect();
+    if !rare.is_empty() {
+        let mut note = format!(
+            "Still shipped but rarely downloaded (under {}% of recent downloads):",
+            RARE_TARGET_SHARE * 100.0
+        );
+        for target in rare {
+            note += &format!(
+                "\n  {} ({} recent downloads)",
+                target.target,
+                format::number(target.recent_downloads)
+            );
+        }
+        report = report.note(note);
+    }
+
+    Ok(report)
+}
+
+fn query_movers(conn: &Connection, limit: usize, min_downloads: u64) -> Result<Report> {
+    let movers = analysis::movers(conn, min_downloads, limit)?;
+
+    let mut report = Report::default();
+    for (key, title, movers) in [
+        ("gains", "Largest gains", &movers.gains),
+        ("losses", "Largest losses", &movers.losses),
+    ] {
+        let mut table = Table::new([
+            Column::left("Category", 9),
+            Column::left("Identifier", 24),
+            Column::left("Week", 12),
+            Column::right("Previous", 12),
+            Column::right("Downloads", 12),
+            Column::right("Change", 12),
+            Column::right("%", 10),
+        ])
+        .named(key, format!("{}, week over week", title));
+        for mover in movers {
+            table.push([
+                Cell::text(mover.category),
+                Cell::text(&mover.identifier),
+                Cell::Text(mover.week_start.to_string()),
+                Cell::Count(mover.previous),
+                Cell::Count(mover.downloads),
+                Cell::Change(mover.change()),
+                Cell::percent_change(analysis::percent_change(
+                    mover.downloads,
+                    Some(mover.previous),
+                )),
+            ]);
+        }
+        report = report.table(table);
+    }
+
+    Ok(report.note(format!(
+        "Identifiers with fewer than {} downloads in both weeks are ignored.",
+        format::number(min_downloads)
+    )))
+}
+
+fn query_rate(conn: &Connection, limit: usize, window: usize) -> Result<Report> {
+    let rates = analysis::github_download_rate(conn, window)?;
 
+    let mut table = Table::new([
+        Column::left("Date", 12),
+        Column::right("Days", 6),
+        Column::right("Downloads", 12),
+        Column::right("Per day", 10),
+        Column::right("Smoothed", 10),
+    ]);
+    for point in rates.iter().rev().take(limit) {
+        table.push([
+            Cell::Text(point.date.to_string()),
+            Cell::float(point.days, 2),
+            Cell::Count(point.downloads as u64),
+            Cell::Count(point.per_day.round() as u64),
+            Cell::Count(point.smoothed_per_day.round() as u64),
+        ]);
+    }
+
+    Ok(Report::new(format!(
+        "GitHub downloads per day (smoothed over {} intervals)",
+        window
+    ))
+    .table(table))
+}
+
+fn query_diversity(conn: &Connection, limit: usize, min_share: f64) -> Result<Report> {
+    let points = analysis::version_diversity(conn, min_share / 100.0)?;
+
+    let mut table = Table::new([
+        Column::left("Week", 12),
+        Column::right("Versions", 9),
+        Column::right("Active", 7),
+        Column::right("Entropy", 8),
+        Column::right("Effective", 10),
+    ]);
+    for point in points.iter().rev().take(limit) {
+        table.push([
+            Cell::Text(point.week_start.to_string()),
+            Cell::Count(point.versions as u64),
+            Cell::Count(point.active_versions as u64),
+            Cell::float(point.entropy, 2),
+            Cell::float(point.effective_versions, 1),
+        ]);
+    }
+
+    Ok(Report::new(format!(
+        "GitHub version diversity (active = at least {}% of weekly downloads)",
+        min_share
+    ))
+    .table(table))
+}
+
+fn query_release_snapshots(conn: &Connection, tag: Option<&str>, limit: usize) -> Result<Report> {
+    let (repo, tag) = match db::get_latest_snapshotted_release(conn, tag)? {
+        Some(release) => release,
+        None => match tag {
+            Some(tag) => anyhow::bail!("no snapshots of release '{}'", tag),
+            None => {
+                return Ok(Report::empty(
+                    "No release snapshots have been taken yet (run `collect --releases-only`).",
+                ));
+            }
+        },
+    };
+    let snapshots = db::get_github_release_snapshots(conn, &repo, &tag)?;
+
+    let mut table = Table::new([
+        Column::left("Collected", 20),
+        Column::right("Downloads", 14),
+        Column::right("Change", 10),
+        Column::right("Per hour", 10),
+    ]);
+    let skip = snapshots.len().saturating_sub(limit);
+    for (i, snapshot) in snapshots.iter().enumerate().skip(skip) {
+        let (change, per_hour) = match i.checked_sub(1).map(|prev| &snapshots[prev]) {
+            Some(prev) => {
+                let change = snapshot.downloads as i64 - prev.downloads as i64;
+                let hours =
+                    (snapshot.collected_at - prev.collected_at).num_seconds() as f64 / 3600.0;
+                let per_hour = if hours > 0.0 {
+                    Cell::float(change as f64 / hours, 1)
+                } else {
+                    Cell::Missing
+                };
+                (Cell::Change(change), per_hour)
+            }
+            None => (Cell::Missing, Cell::Missing),
+        };
+        table.push([
+            Cell::Text(
+                snapshot
+                    .collected_at
+                    .format("%Y-%m-%d %H:%M UTC")
+                    .to_string(),
+            ),
+            Cell::Count(snapshot.downloads),
+            change,
+            per_hour,
+        ]);
+    }
+
+    Ok(Report::new(format!("Snapshots of {}", tag)).table(table))
+}
+
+fn query_latest(conn: &Connection) -> Result<Report> {
     let (latest_week, crates_downloads): (String, i64) = conn.query_row(
         "SELECT week_start, SUM(downloads) FROM weekly_stats
          WHERE source = 'crates'
@@ -118,139 +2372,700 @@ fn query_latest(conn: &Connection) -> Result<()> {
         |row| Ok((row.get(0)?, row.get(1)?)),
     )?;
 
-    println!("Latest week: {}", latest_week);
-    println!("  crates.io: {}", format_number(crates_downloads as u64));
-
     let github_total: i64 = conn.query_row(
-        "SELECT SUM(download_count) FROM github_snapshots
+        "SELECT SUM(download_count) FROM github_cumulative
          WHERE date = (SELECT MAX(date) FROM github_snapshots)",
         [],
         |row| row.get(0),
     )?;
 
-    println!(
-        "  GitHub (cumulative): {}",
-        format_number(github_total as u64)
-    );
-
     let (first_week, last_week): (String, String) = conn.query_row(
         "SELECT MIN(week_start), MAX(week_start) FROM weekly_stats",
         [],
         |row| Ok((row.get(0)?, row.get(1)?)),
     )?;
 
-    println!("\nData coverage: {} to {}", first_week, last_week);
+    let mut table = Table::new([Column::left("Statistic", 24), Column::right("Value", 12)]);
+    table.push([Cell::text("Latest week"), Cell::Text(latest_week)]);
+    table.push([
+        Cell::text("crates.io (latest week)"),
+        Cell::Count(crates_downloads as u64),
+    ]);
+    table.push([
+        Cell::text("GitHub (cumulative)"),
+        Cell::Count(github_total as u64),
+    ]);
+    table.push([Cell::text("First week"), Cell::Text(first_week)]);
+    table.push([Cell::text("Last week"), Cell::Text(last_week)]);
 
-    Ok(())
+    Ok(Report::new("Latest statistics").table(table))
 }
 
-fn export_csv(conn: &Connection, output: &Utf8Path, table: &str) -> Result<()> {
-    let query = match table {
-        "weekly" => "SELECT * FROM weekly_stats ORDER BY week_start, source, identifier",
-        "daily" => "SELECT * FROM crates_downloads ORDER BY date, crate_name, version",
-        "github" => "SELECT * FROM github_snapshots ORDER BY date, release_tag, asset_name",
-        _ => anyhow::bail!(
-            "Unknown table type: {}. Use 'weekly', 'daily', or 'github'",
-            table
-        ),
-    };
+/// Run a read-only SQL statement, showing whatever it returns.
+///
+/// The statement has to be a query that returns rows, such as a `SELECT` or a
+/// `PRAGMA table_info(...)`. That only makes for clearer errors: some pragmas
+/// take effect as they're prepared, so the connection should come from
+/// [`db::open_read_only`].
+fn query_sql(conn: &Connection, statement: &str) -> Result<Report> {
+    let mut stmt = conn
+        .prepare(statement)
+        .context("failed to prepare SQL statement")?;
+    if !stmt.readonly() || stmt.column_count() == 0 {
+        bail!(
+            "only statements that return rows without changing anything can be run, such as SELECT"
+        );
+    }
 
-    let mut stmt = conn.prepare(query)?;
-    let column_count = stmt.column_count();
-    let column_names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut rows = stmt.query([]).context("failed to run SQL statement")?;
+    let mut cells: Vec<Vec<Cell>> = Vec::new();
+    while let Some(row) = rows.next().context("failed to run SQL statement")? {
+        let row = (0..names.len())
+            .map(|idx| Ok(sql_cell(row.get_ref(idx)?)))
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        cells.push(row);
+    }
 
-    let mut file = File::create(output.as_std_path())
-        .with_context(|| format!("failed to create file at {}", output))?;
-
-    writeln!(file, "{}", column_names.join(","))?;
-
-    let rows = stmt.query_map([], |row| {
-        let mut values = Vec::new();
-        for i in 0..column_count {
-            let value = match row.get_ref(i)? {
-                rusqlite::types::ValueRef::Null => String::new(),
-                rusqlite::types::ValueRef::Integer(i) => i.to_string(),
-                rusqlite::types::ValueRef::Real(f) => f.to_string(),
-                rusqlite::types::ValueRef::Text(s) => {
-                    std::str::from_utf8(s).unwrap_or("").to_string()
-                }
-                rusqlite::types::ValueRef::Blob(b) => format!("{:?}", b),
-            };
-            values.push(value);
+    // Columns holding only numbers are aligned right.
+    let mut table = Table::new(names.iter().enumerate().map(|(idx, name)| {
+        let numeric = cells.iter().all(|row| {
+            matches!(
+                row[idx],
+                Cell::Count(_) | Cell::Change(_) | Cell::Number { .. } | Cell::Missing
+            )
+        });
+        if numeric && !cells.is_empty() {
+            Column::right(name.as_str(), 0)
+        } else {
+            Column::left(name.as_str(), 0)
         }
-        Ok(values)
-    })?;
+    }));
+    for row in cells {
+        table.push(row);
+    }
+    Ok(Report::default().table(table))
+}
 
-    for row in rows {
-        let values = row?;
-        writeln!(file, "{}", values.join(","))?;
+fn sql_cell(value: ValueRef<'_>) -> Cell {
+    match value {
+        ValueRef::Null => Cell::Missing,
+        ValueRef::Integer(n) if n >= 0 => Cell::Count(n as u64),
+        // Negative numbers are shown with their sign either way.
+        ValueRef::Integer(n) => Cell::Change(n),
+        ValueRef::Real(value) => Cell::Number {
+            value,
+            display: value.to_string(),
+        },
+        ValueRef::Text(text) => Cell::Text(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(blob) => Cell::Text(format!("<{} bytes>", blob.len())),
     }
+}
 
-    println!("Exported to {}.", output);
-    Ok(())
+/// List the tables and views in the database with their columns, for writing
+/// statements for `query sql`.
+fn query_tables(conn: &Connection) -> Result<Report> {
+    let mut stmt = conn.prepare(
+        "SELECT name, type FROM sqlite_master
+         WHERE type IN ('table', 'view') AND name NOT LIKE 'sqlite_%'
+         ORDER BY name",
+    )?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut table = Table::new([
+        Column::left("Name", 24),
+        Column::left("Type", 5),
+        Column::right("Rows", 10),
+        Column::left("Columns", 0),
+    ]);
+    for (name, kind) in objects {
+        let columns = conn
+            .prepare("SELECT name FROM pragma_table_info(?1) ORDER BY cid")?
+            .query_map([&name], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        // The name comes from sqlite_master, so quoting it is enough.
+        let rows: i64 = conn
+            .query_row(
+                &format!("SELECT COUNT(*) FROM \"{}\"", name.replace('"', "\"\"")),
+                [],
+                |row| row.get(0),
+            )
+            .with_context(|| format!("failed to count rows in {}", name))?;
+        table.push([
+            Cell::Text(name),
+            Cell::Text(kind),
+            Cell::Count(rows as u64),
+            Cell::Text(columns.join(", ")),
+        ]);
+    }
+    Ok(Report::new("Tables and views")
+        .table(table)
+        .note("Run a read-only statement against them with `query sql \"SELECT ...\"`."))
 }
 
-fn export_json(conn: &Connection, output: &Utf8Path, table: &str) -> Result<()> {
-    let query = match table {
-        "weekly" => "SELECT * FROM weekly_stats ORDER BY week_start, source, identifier",
-        "daily" => "SELECT * FROM crates_downloads ORDER BY date, crate_name, version",
-        "github" => "SELECT * FROM github_snapshots ORDER BY date, release_tag, asset_name",
+/// The query for an export of `table` limited by `filter`, along with the
+/// values of its parameters.
+fn export_query(table: &str, filter: &ExportFilter) -> Result<(String, Vec<String>)> {
+    // Tables other than weekly_stats hold downloads from a single source.
+    let (from, date_column, source, crate_column, order) = match table {
+        "weekly" => (
+            "weekly_stats",
+            "week_start",
+            None,
+            Some("identifier"),
+            "week_start, source, identifier",
+        ),
+        "daily" => (
+            "crates_downloads_named",
+            "date",
+            Some("crates"),
+            Some("crate_name"),
+            "date, crate_name, version",
+        ),
+        "github" => (
+            "github_snapshots",
+            "date",
+            Some("github"),
+            None,
+            "date, release_tag, asset_name",
+        ),
+        "github-daily" => (
+            "github_daily",
+            "date",
+            Some("github"),
+            None,
+            "date, release_tag, asset_name",
+        ),
         _ => anyhow::bail!(
-            "Unknown table type: {}. Use 'weekly', 'daily', or 'github'",
+            "Unknown table type: {}. Use 'weekly', 'daily', 'github', or 'github-daily'",
             table
         ),
     };
 
-    let mut stmt = conn.prepare(query)?;
-    let column_names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let mut conditions = vec![filter.range.condition(date_column)];
+    let mut params = Vec::new();
+    let mut wanted = filter.source.as_deref();
+    if let Some(crate_name) = &filter.crate_name {
+        let Some(column) = crate_column else {
+            bail!("the '{}' table has no crates to filter by", table);
+        };
+        if wanted.is_some_and(|source| source != "crates") {
+            bail!(
+                "--crate only matches crates.io downloads, not --source {}",
+                wanted.unwrap()
+            );
+        }
+        wanted = Some("crates");
+        conditions.push(format!("{} = ?", column));
+        params.push(crate_name.clone());
+    }
+    if let Some(wanted) = wanted {
+        if !analysis::SOURCES.contains(&wanted) {
+            bail!(
+                "unknown source '{}' (expected 'github', 'crates', or 'installer')",
+                wanted
+            );
+        }
+        match source {
+            Some(source) if source != wanted => bail!(
+                "the '{}' table only has {} downloads, so nothing matches --source {}",
+                table,
+                source,
+                wanted
+            ),
+            Some(_) => {}
+            None => {
+                conditions.push("source = ?".to_string());
+                params.push(wanted.to_string());
+            }
+        }
+    }
 
-    let rows = stmt.query_map([], |row| {
-        let mut map = serde_json::Map::new();
-        for (i, name) in column_names.iter().enumerate() {
-            let value = match row.get_ref(i)? {
-                rusqlite::types::ValueRef::Null => serde_json::Value::Null,
-                rusqlite::types::ValueRef::Integer(n) => serde_json::Value::Number(n.into()),
-                rusqlite::types::ValueRef::Real(f) => serde_json::Number::from_f64(f)
-                    .map(serde_json::Value::Number)
-                    .unwrap_or(serde_json::Value::Null),
-                rusqlite::types::ValueRef::Text(s) => {
-                    serde_json::Value::String(std::str::from_utf8(s).unwrap_or("").to_string())
-                }
-                rusqlite::types::ValueRef::Blob(b) => serde_json::Value::String(format!("{:?}", b)),
-            };
-            map.insert(name.clone(), value);
+    let sql = format!(
+        "SELECT * FROM {} WHERE {} ORDER BY {}",
+        from,
+        conditions.join(" AND "),
+        order
+    );
+    Ok((sql, params))
+}
+
+/// Compression applied to an export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Compress {
+    #[default]
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl FromStr for Compress {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "none" => Ok(Compress::None),
+            "gzip" | "gz" => Ok(Compress::Gzip),
+            "zstd" | "zst" => Ok(Compress::Zstd),
+            _ => bail!(
+                "unknown compression '{}' (expected 'gzip', 'zstd' or 'none')",
+                s
+            ),
         }
-        Ok(serde_json::Value::Object(map))
+    }
+}
+
+/// The output path meaning standard output.
+const STDOUT_PATH: &str = "-";
+
+/// Write an export to `output`, or to standard output if it's `-`, through
+/// `write`, returning the number of rows written.
+fn write_export(
+    output: &Utf8Path,
+    compress: Compress,
+    write: impl FnOnce(&mut dyn Write) -> Result<usize>,
+) -> Result<usize> {
+    let out: Box<dyn Write> = if output == STDOUT_PATH {
+        Box::new(std::io::stdout().lock())
+    } else {
+        Box::new(
+            File::create(output.as_std_path())
+                .with_context(|| format!("failed to create file at {}", output))?,
+        )
+    };
+    let mut out = BufWriter::new(out);
+    let rows = match compress {
+        Compress::None => write(&mut out)?,
+        Compress::Gzip => {
+            let mut encoder = GzEncoder::new(&mut out, flate2::Compression::default());
+            let rows = write(&mut encoder)?;
+            encoder.finish().context("failed to compress export")?;
+            rows
+        }
+        Compress::Zstd => {
+            let mut encoder = zstd::Encoder::new(&mut out, zstd::DEFAULT_COMPRESSION_LEVEL)
+                .context("failed to start compressing export")?;
+            let rows = write(&mut encoder)?;
+            encoder.finish().context("failed to compress export")?;
+            rows
+        }
+    };
+    out.flush()
+        .with_context(|| format!("failed to write {}", output))?;
+
+    if output != STDOUT_PATH {
+        output::written(output, format!("Exported {} rows to {}.", rows, output));
+    }
+    Ok(rows)
+}
+
+fn export_csv(
+    conn: &Connection,
+    output: &Utf8Path,
+    table: &str,
+    filter: &ExportFilter,
+    compress: Compress,
+) -> Result<()> {
+    let (sql, params) = export_query(table, filter)?;
+    let mut stmt = conn.prepare(&sql)?;
+    write_export(output, compress, |out| {
+        write_csv(&mut stmt, params_from_iter(&params), out)
     })?;
+    Ok(())
+}
 
-    let mut records = Vec::new();
-    for row in rows {
-        records.push(row?);
+/// Write the results of a statement run with `params` as CSV a row at a
+/// time, returning the number of rows.
+///
+/// Fields are quoted as in RFC 4180 when they contain a comma, a quote, or a
+/// line break.
+pub(crate) fn write_csv(
+    stmt: &mut Statement<'_>,
+    params: impl Params,
+    out: &mut (impl Write + ?Sized),
+) -> Result<usize> {
+    let column_count = stmt.column_count();
+    let header: Vec<String> = stmt.column_names().into_iter().map(csv_field).collect();
+    writeln!(out, "{}", header.join(","))?;
+
+    let mut rows = stmt.query(params)?;
+    let mut count = 0;
+    let mut fields = Vec::with_capacity(column_count);
+    while let Some(row) = rows.next()? {
+        fields.clear();
+        for idx in 0..column_count {
+            fields.push(match row.get_ref(idx)? {
+                ValueRef::Null => String::new(),
+                ValueRef::Integer(n) => n.to_string(),
+                ValueRef::Real(f) => f.to_string(),
+                ValueRef::Text(s) => csv_field(&String::from_utf8_lossy(s)),
+                ValueRef::Blob(b) => csv_field(&format!("{:?}", b)),
+            });
+        }
+        writeln!(out, "{}", fields.join(","))?;
+        count += 1;
     }
 
-    let json = serde_json::to_string_pretty(&records)?;
+    Ok(count)
+}
 
-    let mut file = File::create(output.as_std_path())
-        .with_context(|| format!("failed to create file at {}", output))?;
-    file.write_all(json.as_bytes())?;
+/// Export a table as a JSON array with an object per row, written a row at a
+/// time.
+fn export_json(
+    conn: &Connection,
+    output: &Utf8Path,
+    table: &str,
+    filter: &ExportFilter,
+    compress: Compress,
+) -> Result<()> {
+    let (sql, params) = export_query(table, filter)?;
+    let mut stmt = conn.prepare(&sql)?;
+    let column_names: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
 
-    println!("Exported to {}.", output);
+    write_export(output, compress, |out| {
+        let mut rows = stmt.query(params_from_iter(&params))?;
+        let mut count = 0;
+        out.write_all(b"[")?;
+        while let Some(row) = rows.next()? {
+            let mut record = serde_json::Map::new();
+            for (idx, name) in column_names.iter().enumerate() {
+                let value = match row.get_ref(idx)? {
+                    ValueRef::Null => serde_json::Value::Null,
+                    ValueRef::Integer(n) => n.into(),
+                    ValueRef::Real(f) => serde_json::Number::from_f64(f)
+                        .map_or(serde_json::Value::Null, serde_json::Value::Number),
+                    ValueRef::Text(s) => String::from_utf8_lossy(s).into_owned().into(),
+                    ValueRef::Blob(b) => format!("{:?}", b).into(),
+                };
+                record.insert(name.clone(), value);
+            }
+            out.write_all(if count == 0 { b"\n  " } else { b",\n  " })?;
+            serde_json::to_writer(&mut *out, &record)?;
+            count += 1;
+        }
+        out.write_all(if count == 0 { b"]\n" } else { b"\n]\n" })?;
+        Ok(count)
+    })?;
     Ok(())
 }
 
-/// Format a number with thousands separators.
-fn format_number(n: u64) -> String {
-    let s = n.to_string();
-    let mut result = String::new();
-    let mut count = 0;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{db, table::Align};
+
+    #[test]
+    fn test_date_range() {
+        let date = |month, day| NaiveDate::from_ymd_opt(2024, month, day).unwrap();
+        assert!(DateRange::new(Some(date(2, 1)), Some(date(1, 1))).is_err());
+
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        db::insert_weekly_stat(&conn, date(1, 1), "crates", "cargo-nextest", 100).unwrap();
+        db::insert_weekly_stat(&conn, date(1, 8), "crates", "cargo-nextest", 200).unwrap();
+        db::insert_weekly_stat(&conn, date(12, 30), "crates", "cargo-nextest", 50).unwrap();
+        let total = |since, until| -> i64 {
+            let range = DateRange::new(since, until).unwrap();
+            conn.query_row(
+                &format!(
+                    "SELECT COALESCE(SUM(downloads), 0) FROM weekly_stats WHERE {}",
+                    range.condition("week_start")
+                ),
+                [],
+                |row| row.get(0),
+            )
+            .unwrap()
+        };
+
+        assert_eq!(total(None, None), 350);
+        assert_eq!(total(Some(date(1, 8)), None), 250);
+        assert_eq!(total(None, Some(date(1, 7))), 100);
+        assert_eq!(total(Some(date(1, 8)), Some(date(1, 8))), 200);
+        assert_eq!(total(Some(date(2, 1)), Some(date(12, 1))), 0);
 
-    for c in s.chars().rev() {
-        if count > 0 && count % 3 == 0 {
-            result.push(',');
+        let last = |days| {
+            let range = DateRange::last_days(date(3, 31), days).unwrap();
+            (range.since.unwrap(), range.until.unwrap())
+        };
+        assert_eq!(last(1), (date(3, 31), date(3, 31)));
+        assert_eq!(last(0), (date(3, 31), date(3, 31)));
+        assert_eq!(last(31), (date(3, 1), date(3, 31)));
+        assert!(DateRange::last_days(date(3, 31), DateRange::MAX_DAYS).is_ok());
+        assert!(DateRange::last_days(date(3, 31), DateRange::MAX_DAYS + 1).is_err());
+        assert!(DateRange::last_days(NaiveDate::MIN, 2).is_err());
+    }
+
+    #[test]
+    fn test_query_platforms_days() {
+        let date = |day| NaiveDate::from_ymd_opt(2024, 3, day).unwrap();
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        db::insert_github_snapshot(
+            &conn,
+            date(31),
+            Some(Utc::now()),
+            "nextest-rs/nextest",
+            "cargo-nextest-0.9.1",
+            "cargo-nextest-0.9.1-x86_64-unknown-linux-gnu.tar.gz",
+            10,
+        )
+        .unwrap();
+        db::insert_github_daily(
+            &conn,
+            &db::GithubDaily {
+                date: date(31),
+                repo: "nextest-rs/nextest".to_string(),
+                release_tag: "cargo-nextest-0.9.1".to_string(),
+                asset_name: "cargo-nextest-0.9.1-x86_64-unknown-linux-gnu.tar.gz".to_string(),
+                days: 1,
+                downloads: 10,
+                raw_delta: 0,
+            },
+        )
+        .unwrap();
+
+        let unbounded = DateRange::new(None, None).unwrap();
+        for days in [1, 30, DateRange::MAX_DAYS] {
+            query_platforms(&conn, PlatformGroup::Target, days, unbounded).unwrap();
         }
-        result.push(c);
-        count += 1;
+        let err = query_platforms(&conn, PlatformGroup::Target, u32::MAX, unbounded).unwrap_err();
+        assert!(err.to_string().contains("too long"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_period() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let period = |s: &str| {
+            let range: DateRange = s.parse().unwrap();
+            (range.since.unwrap(), range.until.unwrap())
+        };
+        assert_eq!(period("2024"), (date(2024, 1, 1), date(2024, 12, 31)));
+        assert_eq!(period("2024-Q1"), (date(2024, 1, 1), date(2024, 3, 31)));
+        assert_eq!(period("2023-Q4"), (date(2023, 10, 1), date(2023, 12, 31)));
+        assert_eq!(period("2024-02"), (date(2024, 2, 1), date(2024, 2, 29)));
+        assert_eq!(period("2024-02-03"), (date(2024, 2, 3), date(2024, 2, 3)));
+        assert_eq!(
+            period("2024-01-15..2024-03-01"),
+            (date(2024, 1, 15), date(2024, 3, 1))
+        );
+        for bad in [
+            "",
+            "24-Q1",
+            "2024-Q5",
+            "2024-13",
+            "2024-03-01..",
+            "2024-03-01..2024-01-01",
+        ] {
+            assert!(bad.parse::<DateRange>().is_err(), "for {:?}", bad);
+        }
+
+        assert_eq!(
+            "year".parse::<CompareBaseline>().unwrap(),
+            CompareBaseline::Year
+        );
+        assert!(matches!(
+            "2023-Q1".parse::<CompareBaseline>().unwrap(),
+            CompareBaseline::Dates(_)
+        ));
+        assert!("last".parse::<CompareBaseline>().is_err());
     }
 
-    result.chars().rev().collect()
+    #[test]
+    fn test_query_compare() {
+        let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        for day in 1..=10 {
+            db::insert_crates_download(
+                &conn,
+                date(2024, 3, day),
+                "cargo-nextest",
+                None,
+                day as u64,
+            )
+            .unwrap();
+        }
+        db::insert_crates_download(&conn, date(2023, 3, 9), "cargo-nextest", None, 100).unwrap();
+
+        let compare = |period, against| {
+            let report = query_compare(&conn, period, against)?;
+            let row = &report.tables[0].rows[0];
+            assert_eq!(row[0], Cell::text("crates"));
+            Ok::<_, anyhow::Error>((row[1].clone(), row[2].clone()))
+        };
+        assert_eq!(
+            compare(ComparePeriod::Last(3), CompareBaseline::Previous).unwrap(),
+            (Cell::Count(8 + 9 + 10), Cell::Count(5 + 6 + 7))
+        );
+        assert_eq!(
+            compare(ComparePeriod::Last(3), CompareBaseline::Year).unwrap(),
+            (Cell::Count(8 + 9 + 10), Cell::Count(100))
+        );
+        let march = DateRange::new(Some(date(2024, 3, 1)), Some(date(2024, 3, 31))).unwrap();
+        assert_eq!(
+            compare(
+                ComparePeriod::Dates(march),
+                CompareBaseline::Dates("2023".parse().unwrap())
+            )
+            .unwrap(),
+            (Cell::Count(55), Cell::Count(100))
+        );
+
+        // Periods reaching past the earliest date are errors rather than
+        // panics.
+        assert!(compare(ComparePeriod::Last(u32::MAX), CompareBaseline::Previous).is_err());
+        let forever = DateRange::new(Some(NaiveDate::MIN), Some(date(2024, 3, 10))).unwrap();
+        assert!(compare(ComparePeriod::Dates(forever), CompareBaseline::Previous).is_err());
+        assert!(compare(ComparePeriod::Dates(forever), CompareBaseline::Year).is_err());
+        let first = DateRange::new(Some(NaiveDate::MIN), Some(NaiveDate::MIN)).unwrap();
+        assert!(compare(ComparePeriod::Dates(first), CompareBaseline::Previous).is_err());
+    }
+
+    #[test]
+    fn test_breakdown_table() {
+        let rows: Vec<(String, String, i64)> = [
+            ("2023-12", "cargo-nextest", 100),
+            ("2024-01", "cargo-nextest", 5),
+            ("2024-01", "nextest-runner", 10),
+            ("2024-02", "cargo-nextest", 7),
+        ]
+        .into_iter()
+        .map(|(month, identifier, downloads)| (month.into(), identifier.into(), downloads))
+        .collect();
+
+        assert_eq!(
+            sum_periods(&rows, 2),
+            [("2024-02".to_string(), 7), ("2024-01".to_string(), 15)]
+        );
+
+        let table = breakdown_table("Month", &rows, 2, |month| Ok(month.to_string())).unwrap();
+        let names: Vec<_> = table.columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Month", "cargo-nextest", "nextest-runner", "Total"]);
+        assert_eq!(
+            table.rows,
+            [
+                vec![
+                    Cell::text("2024-02"),
+                    Cell::Count(7),
+                    Cell::Count(0),
+                    Cell::Count(7)
+                ],
+                vec![
+                    Cell::text("2024-01"),
+                    Cell::Count(5),
+                    Cell::Count(10),
+                    Cell::Count(15)
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_query_sql() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let week = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap();
+        db::insert_weekly_stat(&conn, week, "crates", "cargo-nextest", 100).unwrap();
+
+        let report = query_sql(
+            &conn,
+            "SELECT identifier, downloads, downloads / 8.0 AS per_day, NULL AS note
+             FROM weekly_stats",
+        )
+        .unwrap();
+        let table = &report.tables[0];
+        let aligns: Vec<_> = table.columns.iter().map(|c| c.align).collect();
+        assert_eq!(
+            aligns,
+            [Align::Left, Align::Right, Align::Right, Align::Right]
+        );
+        assert_eq!(
+            table.rows,
+            [vec![
+                Cell::text("cargo-nextest"),
+                Cell::Count(100),
+                Cell::float(12.5, 1),
+                Cell::Missing,
+            ]]
+        );
+
+        assert!(query_sql(&conn, "DELETE FROM weekly_stats").is_err());
+        assert!(query_sql(&conn, "ATTACH 'other.db' AS other").is_err());
+    }
+
+    #[test]
+    fn test_write_csv() {
+        let conn = Connection::open_in_memory().unwrap();
+        let mut stmt = conn
+            .prepare(
+                "SELECT 'cargo-nextest-0.9.0-x86_64-unknown-linux-gnu.tar.gz' AS asset, 3 AS n
+                 UNION ALL SELECT 'odd, \"quoted\" name', NULL",
+            )
+            .unwrap();
+        let mut out = Vec::new();
+        assert_eq!(write_csv(&mut stmt, [], &mut out).unwrap(), 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "asset,n\n\
+             cargo-nextest-0.9.0-x86_64-unknown-linux-gnu.tar.gz,3\n\
+             \"odd, \"\"quoted\"\" name\",\n"
+        );
+
+        assert_eq!("gz".parse::<Compress>().unwrap(), Compress::Gzip);
+        assert_eq!("zst".parse::<Compress>().unwrap(), Compress::Zstd);
+        assert!("zip".parse::<Compress>().is_err());
+
+        let dir = camino::Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-zstd-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let output = dir.join("export.csv.zst");
+        let rows =
+            write_export(&output, Compress::Zstd, |out| write_csv(&mut stmt, [], out)).unwrap();
+        assert_eq!(rows, 2);
+        let file = File::open(output.as_std_path()).unwrap();
+        let csv = String::from_utf8(zstd::decode_all(file).unwrap()).unwrap();
+        assert!(csv.starts_with("asset,n\n"));
+        assert_eq!(csv.lines().count(), 3);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_export_filter() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let week = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        db::insert_weekly_stat(&conn, week(3), "crates", "cargo-nextest", 100).unwrap();
+        db::insert_weekly_stat(&conn, week(10), "crates", "cargo-nextest", 120).unwrap();
+        db::insert_weekly_stat(&conn, week(10), "crates", "nextest-runner", 7).unwrap();
+        db::insert_weekly_stat(&conn, week(10), "installer", "get.nexte.st", 5).unwrap();
+
+        let count = |table: &str, filter: &ExportFilter| {
+            let (sql, params) = export_query(table, filter).unwrap();
+            let mut stmt = conn.prepare(&sql).unwrap();
+            write_csv(&mut stmt, params_from_iter(&params), &mut Vec::new()).unwrap()
+        };
+        let filter = |source: Option<&str>, crate_name: Option<&str>| ExportFilter {
+            range: DateRange::default(),
+            source: source.map(str::to_string),
+            crate_name: crate_name.map(str::to_string),
+        };
+        assert_eq!(count("weekly", &filter(None, None)), 4);
+        assert_eq!(count("weekly", &filter(Some("crates"), None)), 3);
+        assert_eq!(count("weekly", &filter(None, Some("cargo-nextest"))), 2);
+        let since = ExportFilter {
+            range: DateRange::new(Some(week(10)), None).unwrap(),
+            ..filter(Some("installer"), None)
+        };
+        assert_eq!(count("weekly", &since), 1);
+
+        for (table, filter) in [
+            ("weekly", filter(Some("pypi"), None)),
+            ("weekly", filter(Some("github"), Some("cargo-nextest"))),
+            ("github", filter(None, Some("cargo-nextest"))),
+            ("daily", filter(Some("installer"), None)),
+        ] {
+            assert!(export_query(table, &filter).is_err(), "{table}: {filter:?}");
+        }
+        assert_eq!(count("github", &filter(Some("github"), None)), 0);
+    }
 }
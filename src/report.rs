@@ -0,0 +1,480 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Reports about published snapshots.
+//!
+//! `report diff` compares two public bundles (as written by `export public`)
+//! so reviewers can see what changed before the stats page deploys. Changes
+//! are attributed to new data when they fall in or after the latest period of
+//! the previous bundle, and to recomputation when they rewrite older periods.
+
+use crate::{format, publish::PublicManifest};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+
+/// Columns that date a row, in order of preference.
+const DATE_COLUMNS: &[&str] = &["date", "week_start", "month_start"];
+
+/// Differences between the two versions of a published table.
+#[derive(Debug, Default, PartialEq)]
+pub struct TableDiff {
+    pub name: String,
+    /// Row count in the previous bundle, or `None` if the table is new.
+    pub previous_rows: Option<usize>,
+    /// Row count in the current bundle, or `None` if the table was dropped.
+    pub current_rows: Option<usize>,
+    /// Rows added in or after the previous bundle's latest period.
+    pub new_data: usize,
+    /// Rows added for periods the previous bundle already covered.
+    pub backfilled: usize,
+    pub removed: usize,
+    /// Rows in the previous bundle's latest period whose values changed,
+    /// typically because that period was still in progress.
+    pub latest_updated: usize,
+    /// Rows in earlier periods whose values changed.
+    pub recomputed: usize,
+    /// Change in the sum of each numeric value column.
+    pub deltas: Vec<(String, f64)>,
+    /// Descriptions of recomputed and removed rows.
+    pub samples: Vec<String>,
+}
+
+impl TableDiff {
+    fn is_unchanged(&self) -> bool {
+        self.new_data + self.backfilled + self.removed + self.latest_updated + self.recomputed == 0
+    }
+}
+
+/// Compare the bundle in `current` against the one in `previous`.
+pub fn diff_bundles(
+    current: &Utf8Path,
+    previous: &Utf8Path,
+    sample_limit: usize,
+) -> Result<Vec<TableDiff>> {
+    let current_manifest = read_manifest(current)?;
+    let previous_manifest = read_manifest(previous)?;
+
+    let mut diffs = Vec::new();
+    for table in &current_manifest.tables {
+        let current_csv = read_table(current, &table.file)?;
+        let Some(previous_table) = previous_manifest
+            .tables
+            .iter()
+            .find(|t| t.name == table.name)
+        else {
+            diffs.push(TableDiff {
+                name: table.name.clone(),
+                current_rows: Some(table.rows),
+                new_data: table.rows,
+                ..Default::default()
+            });
+            continue;
+        };
+
+        let previous_csv = read_table(previous, &previous_table.file)?;
+        let diff = diff_table(
+            &table.name,
+            &table.key,
+            &previous_csv,
+            &current_csv,
+            sample_limit,
+        )
+        .with_context(|| format!("failed to compare table '{}'", table.name))?;
+        diffs.push(diff);
+    }
+
+    for table in &previous_manifest.tables {
+        if !current_manifest.tables.iter().any(|t| t.name == table.name) {
+            diffs.push(TableDiff {
+                name: table.name.clone(),
+                previous_rows: Some(table.rows),
+                removed: table.rows,
+                ..Default::default()
+            });
+        }
+    }
+
+    Ok(diffs)
+}
+
+/// Compare a bundle against a previous one and print a summary.
+pub fn run_diff(current: &Utf8Path, previous: &Utf8Path, sample_limit: usize) -> Result<()> {
+    let diffs = diff_bundles(current, previous, sample_limit)?;
+
+    println!("Comparing {} against {}", current, previous);
+
+    for diff in &diffs {
+        println!();
+        match (diff.previous_rows, diff.current_rows) {
+            (None, Some(rows)) => {
+                println!(
+                    "{}: new table ({} rows)",
+                    diff.name,
+                    format::number(rows as u64)
+                );
+                continue;
+            }
+            (Some(rows), None) => {
+                println!(
+                    "{}: removed from bundle ({} rows)",
+                    diff.name,
+                    format::number(rows as u64)
+                );
+                continue;
+            }
+            (Some(previous), Some(current)) => println!(
+                "{}: {} → {} rows",
+                diff.name,
+                format::number(previous as u64),
+                format::number(current as u64)
+            ),
+            (None, None) => unreachable!("a table is in at least one bundle"),
+        }
+
+        if diff.is_unchanged() {
+            println!("  unchanged");
+            continue;
+        }
+        if diff.new_data + diff.backfilled > 0 {
+            println!(
+                "  + {} added: {} new data, {} backfilled",
+                rows(diff.new_data + diff.backfilled),
+                diff.new_data,
+                diff.backfilled
+            );
+        }
+        if diff.latest_updated + diff.recomputed > 0 {
+            println!(
+                "  ~ {} changed: {} in the latest period, {} recomputed",
+                rows(diff.latest_updated + diff.recomputed),
+                diff.latest_updated,
+                diff.recomputed
+            );
+        }
+        if diff.removed > 0 {
+            println!("  - {} removed", rows(diff.removed));
+        }
+        for (column, delta) in &diff.deltas {
+            println!("  {}: {}", column, format_delta(*delta));
+        }
+        for sample in &diff.samples {
+            println!("    {}", sample);
+        }
+        let sampled = diff.recomputed + diff.removed;
+        if sampled > diff.samples.len() {
+            println!("    ... and {} more", sampled - diff.samples.len());
+        }
+    }
+
+    let recomputed: usize = diffs.iter().map(|d| d.recomputed + d.removed).sum();
+    println!();
+    if recomputed == 0 {
+        println!("No previously published history was rewritten.");
+    } else {
+        println!(
+            "{} previously published rows were recomputed or removed.",
+            format::number(recomputed as u64)
+        );
+    }
+
+    println!();
+    for (label, dir) in [("Current", current), ("Previous", previous)] {
+        let footer = match read_manifest(dir)?.provenance {
+            Some(provenance) => provenance.footer(),
+            None => "no provenance recorded".to_string(),
+        };
+        println!("{} bundle: {}", label, footer);
+    }
+
+    Ok(())
+}
+
+fn read_manifest(dir: &Utf8Path) -> Result<PublicManifest> {
+    let path = dir.join("manifest.json");
+    let contents = fs::read_to_string(path.as_std_path())
+        .with_context(|| format!("failed to read {}", path))?;
+    serde_json::from_str(&contents).with_context(|| format!("failed to parse {}", path))
+}
+
+fn read_table(dir: &Utf8Path, file: &str) -> Result<String> {
+    let path = dir.join(file);
+    fs::read_to_string(path.as_std_path()).with_context(|| format!("failed to read {}", path))
+}
+
+/// A parsed CSV file from a bundle.
+struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    fn parse(csv: &str) -> Result<Self> {
+        let mut reader = csv::Reader::from_reader(csv.as_bytes());
+        let header: Vec<String> = reader
+            .headers()
+            .context("failed to read CSV header")?
+            .iter()
+            .map(str::to_string)
+            .collect();
+        if header.is_empty() {
+            bail!("missing CSV header");
+        }
+        let rows = reader
+            .records()
+            .map(|record| {
+                let record = record.context("failed to read CSV row")?;
+                Ok(record.iter().map(str::to_string).collect())
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { header, rows })
+    }
+
+    fn index(&self, column: &str) -> Option<usize> {
+        self.header.iter().position(|c| c == column)
+    }
+
+    /// Rows keyed by the values of the `key` columns.
+    fn keyed(&self, key: &[usize]) -> BTreeMap<Vec<&str>, &[String]> {
+        self.rows
+            .iter()
+            .map(|row| {
+                (
+                    key.iter().map(|&i| row[i].as_str()).collect(),
+                    row.as_slice(),
+                )
+            })
+            .collect()
+    }
+}
+
+fn diff_table(
+    name: &str,
+    key: &[String],
+    previous_csv: &str,
+    current_csv: &str,
+    sample_limit: usize,
+) -> Result<TableDiff> {
+    let previous = Table::parse(previous_csv)?;
+    let current = Table::parse(current_csv)?;
+    if previous.header != current.header {
+        bail!(
+            "columns changed from ({}) to ({})",
+            previous.header.join(", "),
+            current.header.join(", ")
+        );
+    }
+
+    // Older manifests don't record a key, so fall back to every column that
+    // isn't numeric in the current bundle.
+    let key_indexes: Vec<usize> = if key.is_empty() {
+        (0..current.header.len())
+            .filter(|&i| {
+                !current
+                    .rows
+                    .iter()
+                    .all(|row| row[i].is_empty() || row[i].parse::<f64>().is_ok())
+            })
+            .collect()
+    } else {
+        key.iter()
+            .map(|column| {
+                current
+                    .index(column)
+                    .with_context(|| format!("key column '{}' is missing", column))
+            })
+            .collect::<Result<_>>()?
+    };
+    let value_indexes: Vec<usize> = (0..current.header.len())
+        .filter(|i| !key_indexes.contains(i))
+        .collect();
+
+    let date_index = DATE_COLUMNS
+        .iter()
+        .filter_map(|column| current.index(column))
+        .find(|i| key_indexes.contains(i));
+    let latest_previous =
+        date_index.and_then(|i| previous.rows.iter().map(|row| row[i].as_str()).max());
+    // Whether a row falls in or after the previous bundle's latest period.
+    let is_latest = |row: &[String]| match (date_index, latest_previous) {
+        (Some(i), Some(latest)) => row[i].as_str() >= latest,
+        _ => true,
+    };
+
+    let previous_rows = previous.keyed(&key_indexes);
+    let current_rows = current.keyed(&key_indexes);
+    let describe_key = |row: &[String]| {
+        key_indexes
+            .iter()
+            .map(|&i| format!("{}={}", current.header[i], row[i]))
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+
+    let mut diff = TableDiff {
+        name: name.to_string(),
+        previous_rows: Some(previous.rows.len()),
+        current_rows: Some(current.rows.len()),
+        ..Default::default()
+    };
+
+    for (key, row) in &current_rows {
+        match previous_rows.get(key) {
+            None if is_latest(row) => diff.new_data += 1,
+            None => diff.backfilled += 1,
+            Some(old) => {
+                let changed: Vec<String> = value_indexes
+                    .iter()
+                    .filter(|&&i| old[i] != row[i])
+                    .map(|&i| format!("{} {} → {}", current.header[i], old[i], row[i]))
+                    .collect();
+                if changed.is_empty() {
+                    continue;
+                }
+                if is_latest(old) {
+                    diff.latest_updated += 1;
+                } else {
+                    diff.recomputed += 1;
+                    if diff.samples.len() < sample_limit {
+                        diff.samples
+                            .push(format!("{}: {}", describe_key(row), changed.join(", ")));
+                    }
+                }
+            }
+        }
+    }
+
+    for (key, row) in &previous_rows {
+        if !current_rows.contains_key(key) {
+            diff.removed += 1;
+            if diff.samples.len() < sample_limit {
+                diff.samples.push(format!("{}: removed", describe_key(row)));
+            }
+        }
+    }
+
+    let numeric: BTreeSet<usize> = value_indexes
+        .iter()
+        .copied()
+        .filter(|&i| {
+            current
+                .rows
+                .iter()
+                .chain(&previous.rows)
+                .all(|row| row[i].is_empty() || row[i].parse::<f64>().is_ok())
+        })
+        .collect();
+    for i in numeric {
+        let sum = |table: &Table| {
+            table
+                .rows
+                .iter()
+                .filter_map(|row| row[i].parse::<f64>().ok())
+                .sum::<f64>()
+        };
+        let delta = sum(&current) - sum(&previous);
+        if delta != 0.0 {
+            diff.deltas.push((current.header[i].to_string(), delta));
+        }
+    }
+
+    Ok(diff)
+}
+
+fn rows(count: usize) -> String {
+    if count == 1 {
+        "1 row".to_string()
+    } else {
+        format!("{} rows", format::number(count as u64))
+    }
+}
+
+fn format_delta(delta: f64) -> String {
+    let sign = if delta < 0.0 { "-" } else { "+" };
+    if delta.fract() == 0.0 {
+        format!("{}{}", sign, format::number(delta.abs() as u64))
+    } else {
+        format!("{}{:.1}", sign, delta.abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_diff_table() {
+        let key = ["week_start".to_string(), "source".to_string()];
+        let previous = "week_start,source,downloads\n\
+                        2025-01-06,crates,100\n\
+                        2025-01-13,crates,200\n\
+                        2025-01-13,github,50\n\
+                        2025-01-20,crates,10\n";
+        let current = "week_start,source,downloads\n\
+                       2025-01-06,crates,120\n\
+                       2025-01-13,crates,200\n\
+                       2025-01-20,crates,300\n\
+                       2025-01-20,github,40\n\
+                       2025-01-27,crates,30\n";
+
+        let diff = diff_table("weekly_stats", &key, previous, current, 10).unwrap();
+        assert_eq!(
+            diff,
+            TableDiff {
+                name: "weekly_stats".to_string(),
+                previous_rows: Some(4),
+                current_rows: Some(5),
+                new_data: 2,
+                backfilled: 0,
+                removed: 1,
+                latest_updated: 1,
+                recomputed: 1,
+                deltas: vec![("downloads".to_string(), 330.0)],
+                samples: vec![
+                    "week_start=2025-01-06 source=crates: downloads 100 → 120".to_string(),
+                    "week_start=2025-01-13 source=github: removed".to_string(),
+                ],
+            }
+        );
+
+        // Without a recorded key, non-numeric columns identify rows.
+        let diff = diff_table("weekly_stats", &[], previous, current, 0).unwrap();
+        assert_eq!((diff.new_data, diff.recomputed), (2, 1));
+        assert!(diff.samples.is_empty());
+
+        let renamed = "week_start,source,total\n2025-01-06,crates,100\n";
+        assert!(diff_table("weekly_stats", &key, previous, renamed, 10).is_err());
+    }
+
+    #[test]
+    fn test_parse_quoted_fields() {
+        // Fields are written with `table::csv_field`, which quotes commas,
+        // quotes, and newlines.
+        let csv = "name,label,downloads\n\
+                   a,\"Linux, x86_64\",10\n\
+                   b,\"say \"\"hi\"\"\",20\n\
+                   c,\"two\nlines\",30\n";
+        let table = Table::parse(csv).unwrap();
+        assert_eq!(table.header, ["name", "label", "downloads"]);
+        assert_eq!(
+            table.rows,
+            [
+                ["a", "Linux, x86_64", "10"],
+                ["b", "say \"hi\"", "20"],
+                ["c", "two\nlines", "30"],
+            ]
+        );
+
+        let key = ["name".to_string()];
+        let current = csv.replace(",10", ",15");
+        let diff = diff_table("projects", &key, csv, &current, 10).unwrap();
+        assert_eq!((diff.latest_updated, diff.removed), (1, 0));
+        assert_eq!(diff.deltas, [("downloads".to_string(), 5.0)]);
+
+        assert!(Table::parse("").is_err());
+        assert!(Table::parse("a,b\n1,2,3\n").is_err());
+    }
+}
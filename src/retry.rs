@@ -0,0 +1,345 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Retries of HTTP requests that fail transiently.
+//!
+//! Requests to GitHub, crates.io, and install endpoints go through [`send`],
+//! which retries timeouts, connection failures, server errors, and rate
+//! limiting with exponential backoff and jitter, rather than failing the
+//! whole collection on the first hiccup. The policy is configured in the
+//! `[retry]` section of `config.toml` and installed once at startup with
+//! [`set_policy`]; until then the defaults apply.
+//!
+//! Requests that aren't idempotent, such as creating an issue, are only
+//! retried when they couldn't be sent at all, so they're never made twice.
+//!
+//! Requests are sent with [`http::transport`], so they're answered from
+//! fixtures when collecting offline.
+
+use crate::{
+    config::RetryConfig,
+    http::{self, Transport},
+    progress,
+};
+use anyhow::Result;
+use reqwest::{RequestBuilder, Response, StatusCode, header::RETRY_AFTER};
+use std::{
+    hash::{BuildHasher, RandomState},
+    sync::OnceLock,
+    time::Duration,
+};
+
+static POLICY: OnceLock<RetryConfig> = OnceLock::new();
+
+/// Install the retry policy for this process.
+///
+/// Only the first call has an effect.
+pub fn set_policy(config: RetryConfig) {
+    _ = POLICY.set(config);
+}
+
+/// Send the request `build` returns, retrying transient failures, and
+/// describing it as `what` when logging retries.
+///
+/// The request is rebuilt for each attempt, so signatures and timestamps are
+/// fresh. Once attempts run out, the last response is returned even if it's
+/// unsuccessful, for the caller to report.
+pub async fn send(what: &str, build: impl FnMut() -> Result<RequestBuilder>) -> Result<Response> {
+    send_with(what, build, false, false).await
+}
+
+/// Like [`send`], but for requests that only read whatever their method,
+/// such as GraphQL queries, which are retried like GET requests.
+pub async fn send_read_only(
+    what: &str,
+    build: impl FnMut() -> Result<RequestBuilder>,
+) -> Result<Response> {
+    send_with(what, build, true, false).await
+}
+
+/// Like [`send`], but leaving the URL out of logged and returned errors, for
+/// URLs that are secrets, such as Slack webhooks.
+pub async fn send_secret(
+    what: &str,
+    build: impl FnMut() -> Result<RequestBuilder>,
+) -> Result<Response> {
+    send_with(what, build, false, true).await
+}
+
+async fn send_with(
+    what: &str,
+    build: impl FnMut() -> Result<RequestBuilder>,
+    read_only: bool,
+    secret_url: bool,
+) -> Result<Response> {
+    let policy = POLICY.get_or_init(RetryConfig::default);
+    send_via(
+        http::transport(),
+        policy,
+        what,
+        build,
+        read_only,
+        secret_url,
+    )
+    .await
+}
+
+/// Send requests with `transport`, retrying them as `policy` allows.
+async fn send_via(
+    transport: &dyn Transport,
+    policy: &RetryConfig,
+    what: &str,
+    mut build: impl FnMut() -> Result<RequestBuilder>,
+    read_only: bool,
+    secret_url: bool,
+) -> Result<Response> {
+    let mut attempt = 1;
+    loop {
+        let request = build()?.build()?;
+        let idempotent = read_only || request.method().is_idempotent();
+        let result = transport
+            .execute(request)
+            .await
+            .map_err(|err| if secret_url { err.without_url() } else { err });
+
+        let reason = match &result {
+            Ok(response) if idempotent && is_retryable(response) => {
+                format!("status {}", response.status())
+            }
+            // A request that timed out may still have been handled.
+            Err(err) if err.is_connect() || (idempotent && err.is_timeout()) => err.to_string(),
+            _ => return Ok(result?),
+        };
+        if attempt >= policy.max_attempts {
+            return Ok(result?);
+        }
+
+        let delay = match &result {
+            Ok(response) => retry_after(response),
+            Err(_) => None,
+        }
+        .unwrap_or_else(|| backoff(policy, attempt, jitter()))
+        .min(Duration::from_millis(policy.max_delay_ms));
+        progress::suspend(|| {
+            eprintln!(
+                "  {} failed ({}), retrying in {:.1}s (attempt {} of {})",
+                what,
+                reason,
+                delay.as_secs_f64(),
+                attempt + 1,
+                policy.max_attempts
+            )
+        });
+        tokio::time::sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Whether a response is worth trying again: server errors, rate limiting,
+/// and GitHub's secondary rate limits, which are 403s saying when to retry.
+///
+/// Rate limiting that says the limit is used up (`X-RateLimit-Remaining: 0`)
+/// without saying when to retry lasts until the limit resets, up to an hour
+/// away, so it's left to the caller, which may have another token to use.
+fn is_retryable(response: &Response) -> bool {
+    let status = response.status();
+    let headers = response.headers();
+    let exhausted = headers
+        .get("x-ratelimit-remaining")
+        .is_some_and(|remaining| remaining == "0")
+        && !headers.contains_key(RETRY_AFTER);
+    status.is_server_error()
+        || (status == StatusCode::TOO_MANY_REQUESTS && !exhausted)
+        || (status == StatusCode::FORBIDDEN && headers.contains_key(RETRY_AFTER))
+}
+
+/// The delay a response asks for with `Retry-After`, in seconds.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    seconds.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// The delay before retrying after `attempt` failed attempts: the initial
+/// delay doubled for each attempt after the first, of which the second half
+/// is scaled by `jitter` (from 0 to 1) so clients don't retry in lockstep.
+fn backoff(policy: &RetryConfig, attempt: u32, jitter: f64) -> Duration {
+    let base = policy
+        .initial_delay_ms
+        .saturating_mul(1u64 << (attempt - 1).min(32))
+        .min(policy.max_delay_ms);
+    Duration::from_millis(base / 2 + (base as f64 / 2.0 * jitter) as u64)
+}
+
+/// A random number from 0 to 1.
+fn jitter() -> f64 {
+    RandomState::new().hash_one(0u8) as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::future::BoxFuture;
+    use reqwest::{Method, Request};
+    use std::sync::Mutex;
+
+    /// A status and headers to answer with.
+    type ScriptedResponse = (u16, Vec<(&'static str, &'static str)>);
+
+    /// A transport answering with responses from a script, and recording the
+    /// methods of the requests it was sent.
+    #[derive(Debug, Default)]
+    struct Scripted {
+        responses: Mutex<Vec<ScriptedResponse>>,
+        methods: Mutex<Vec<Method>>,
+    }
+
+    impl Scripted {
+        fn new(statuses: &[u16]) -> Self {
+            Self {
+                responses: Mutex::new(statuses.iter().map(|&s| (s, Vec::new())).collect()),
+                ..Self::default()
+            }
+        }
+    }
+
+    impl Transport for Scripted {
+        fn execute(&self, request: Request) -> BoxFuture<'_, reqwest::Result<Response>> {
+            self.methods.lock().unwrap().push(request.method().clone());
+            let (status, headers) = self.responses.lock().unwrap().remove(0);
+            Box::pin(async move { Ok(response(status, &headers)) })
+        }
+    }
+
+    fn response(status: u16, headers: &[(&str, &str)]) -> Response {
+        let mut builder = ::http::Response::builder().status(status);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        builder.body("").unwrap().into()
+    }
+
+    fn policy(max_attempts: u32) -> RetryConfig {
+        RetryConfig {
+            max_attempts,
+            initial_delay_ms: 1,
+            max_delay_ms: 10,
+        }
+    }
+
+    async fn send_scripted(
+        transport: &Scripted,
+        max_attempts: u32,
+        method: Method,
+        read_only: bool,
+    ) -> u16 {
+        let build = || Ok(http::client().request(method.clone(), "http://localhost/"));
+        let response = send_via(
+            transport,
+            &policy(max_attempts),
+            "test",
+            build,
+            read_only,
+            false,
+        )
+        .await
+        .unwrap();
+        response.status().as_u16()
+    }
+
+    #[tokio::test]
+    async fn test_send_retries() {
+        // Idempotent requests are retried until they succeed.
+        let transport = Scripted::new(&[503, 502, 200]);
+        assert_eq!(send_scripted(&transport, 5, Method::GET, false).await, 200);
+        assert_eq!(transport.methods.lock().unwrap().len(), 3);
+
+        let transport = Scripted::new(&[500, 200]);
+        assert_eq!(send_scripted(&transport, 5, Method::PUT, false).await, 200);
+        assert_eq!(transport.methods.lock().unwrap().len(), 2);
+
+        // Requests that aren't idempotent are never made twice, unless they
+        // only read.
+        let transport = Scripted::new(&[503, 200]);
+        assert_eq!(send_scripted(&transport, 5, Method::POST, false).await, 503);
+        assert_eq!(*transport.methods.lock().unwrap(), [Method::POST]);
+        let transport = Scripted::new(&[503, 200]);
+        assert_eq!(send_scripted(&transport, 5, Method::POST, true).await, 200);
+        assert_eq!(transport.methods.lock().unwrap().len(), 2);
+
+        // Client errors aren't retried.
+        let transport = Scripted::new(&[404, 200]);
+        assert_eq!(send_scripted(&transport, 5, Method::GET, false).await, 404);
+        assert_eq!(transport.methods.lock().unwrap().len(), 1);
+
+        // Once attempts run out, the last response is returned.
+        let transport = Scripted::new(&[500, 503, 200]);
+        assert_eq!(send_scripted(&transport, 2, Method::GET, false).await, 503);
+        assert_eq!(transport.methods.lock().unwrap().len(), 2);
+
+        // Retry-After is honored, up to the maximum delay.
+        let transport = Scripted {
+            responses: Mutex::new(vec![(429, vec![("retry-after", "3600")]), (200, vec![])]),
+            ..Scripted::default()
+        };
+        let started = std::time::Instant::now();
+        assert_eq!(send_scripted(&transport, 5, Method::GET, false).await, 200);
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        for status in [500, 502, 503, 504, 429] {
+            assert!(is_retryable(&response(status, &[])), "{status}");
+        }
+        for status in [200, 400, 401, 403, 404, 422] {
+            assert!(!is_retryable(&response(status, &[])), "{status}");
+        }
+
+        // GitHub's secondary rate limits say when to retry.
+        assert!(is_retryable(&response(403, &[("retry-after", "60")])));
+        // An exhausted rate limit lasts until it resets, unless told when to
+        // retry.
+        let exhausted = [("x-ratelimit-remaining", "0")];
+        assert!(!is_retryable(&response(429, &exhausted)));
+        assert!(is_retryable(&response(
+            429,
+            &[("x-ratelimit-remaining", "0"), ("retry-after", "5")]
+        )));
+        assert!(is_retryable(&response(
+            429,
+            &[("x-ratelimit-remaining", "10")]
+        )));
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let delay = |value| retry_after(&response(429, &[("retry-after", value)]));
+        assert_eq!(delay("120"), Some(Duration::from_secs(120)));
+        assert_eq!(delay(" 5 "), Some(Duration::from_secs(5)));
+        // HTTP dates aren't supported, so backoff applies instead.
+        assert_eq!(delay("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+        assert_eq!(delay("-1"), None);
+        assert_eq!(retry_after(&response(429, &[])), None);
+    }
+
+    #[test]
+    fn test_backoff() {
+        let policy = RetryConfig {
+            max_attempts: 10,
+            initial_delay_ms: 1000,
+            max_delay_ms: 5000,
+        };
+        let delays: Vec<u64> = (1..=5)
+            .map(|attempt| backoff(&policy, attempt, 1.0).as_millis() as u64)
+            .collect();
+        assert_eq!(delays, [1000, 2000, 4000, 5000, 5000]);
+        assert_eq!(backoff(&policy, 2, 0.0), Duration::from_millis(1000));
+        assert_eq!(backoff(&policy, 2, 0.5), Duration::from_millis(1500));
+        // Delays stop growing rather than overflowing.
+        assert_eq!(backoff(&policy, 100, 1.0), Duration::from_millis(5000));
+
+        for _ in 0..100 {
+            assert!((0.0..=1.0).contains(&jitter()));
+        }
+    }
+}
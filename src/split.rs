@@ -0,0 +1,344 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Exports with a file per series.
+//!
+//! Chart components on a static site usually draw one series at a time, so
+//! `export split` writes each crate, platform, or version to a file of its
+//! own, with a `manifest.json` index of the files, instead of one table that
+//! has to be filtered.
+
+use crate::{
+    output::{self, status},
+    provenance::Provenance,
+    query::DateRange,
+    table::csv_field,
+};
+use anyhow::{Context, Result, bail};
+use camino::Utf8Path;
+use chrono::{NaiveDate, Utc};
+use rusqlite::{Connection, types::ValueRef};
+use serde::Serialize;
+use serde_json::Value;
+use std::{collections::BTreeMap, fs, str::FromStr};
+
+/// What each file of a split export holds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitBy {
+    /// Weekly downloads per source and crate, release, or install endpoint,
+    /// from `weekly_stats`.
+    Identifier,
+    /// Weekly GitHub downloads per platform, from `weekly_platform_stats`.
+    Platform,
+    /// Weekly downloads and share per source and major.minor version, from
+    /// `weekly_version_share`.
+    Version,
+}
+
+impl SplitBy {
+    /// The table read, the columns identifying a series, and the columns
+    /// written for each week.
+    fn plan(
+        self,
+    ) -> (
+        &'static str,
+        &'static [&'static str],
+        &'static [&'static str],
+    ) {
+        match self {
+            SplitBy::Identifier => (
+                "weekly_stats",
+                &["source", "identifier"],
+                &["week_start", "downloads"],
+            ),
+            SplitBy::Platform => (
+                "weekly_platform_stats",
+                &["platform"],
+                &["week_start", "downloads"],
+            ),
+            SplitBy::Version => (
+                "weekly_version_share",
+                &["source", "version"],
+                &["week_start", "downloads", "share"],
+            ),
+        }
+    }
+}
+
+impl FromStr for SplitBy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "identifier" => Ok(SplitBy::Identifier),
+            "platform" => Ok(SplitBy::Platform),
+            "version" => Ok(SplitBy::Version),
+            _ => bail!(
+                "unknown split '{}' (expected 'identifier', 'platform', or 'version')",
+                s
+            ),
+        }
+    }
+}
+
+/// The format of each file of a split export.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SplitFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+impl SplitFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            SplitFormat::Csv => "csv",
+            SplitFormat::Json => "json",
+        }
+    }
+}
+
+impl FromStr for SplitFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "csv" => Ok(SplitFormat::Csv),
+            "json" => Ok(SplitFormat::Json),
+            _ => bail!("unknown export format '{}' (expected 'csv' or 'json')", s),
+        }
+    }
+}
+
+/// The index of a split export, written as `manifest.json`.
+#[derive(Debug, Serialize)]
+pub struct SplitManifest {
+    pub generated_at: String,
+    pub provenance: Provenance,
+    pub by: SplitBy,
+    pub format: SplitFormat,
+    /// The first and last weeks exported, if the export was limited to them.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<NaiveDate>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub until: Option<NaiveDate>,
+    /// The columns of every file.
+    pub columns: Vec<&'static str>,
+    pub series: Vec<SplitSeries>,
+}
+
+/// A series written to a file of its own.
+#[derive(Debug, Serialize)]
+pub struct SplitSeries {
+    pub file: String,
+    /// The values identifying the series, such as its source and identifier.
+    pub key: BTreeMap<&'static str, String>,
+    pub rows: usize,
+    /// The first and last weeks of the series.
+    pub first: String,
+    pub last: String,
+    /// Downloads over every week of the series.
+    pub downloads: i64,
+}
+
+/// Export a series per file to `output_dir`, along with a `manifest.json`
+/// listing them.
+///
+/// Each file is named after the values identifying its series, such as
+/// `crates-cargo-nextest.csv`. Only weeks starting in `range` are exported.
+pub fn export_split(
+    conn: &Connection,
+    by: SplitBy,
+    format: SplitFormat,
+    range: DateRange,
+    provenance: &Provenance,
+    output_dir: &Utf8Path,
+) -> Result<SplitManifest> {
+    let (table, keys, columns) = by.plan();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {keys}, {columns} FROM {table} WHERE {range}
+         ORDER BY {keys}, week_start",
+        keys = keys.join(", "),
+        columns = columns.join(", "),
+        range = range.condition("week_start"),
+    ))?;
+    let mut rows = stmt.query([])?;
+    let mut grouped: Vec<(Vec<String>, Vec<Vec<Value>>)> = Vec::new();
+    while let Some(row) = rows.next()? {
+        let key = (0..keys.len())
+            .map(|idx| row.get::<_, String>(idx))
+            .collect::<Result<Vec<_>, _>>()?;
+        let values = (keys.len()..keys.len() + columns.len())
+            .map(|idx| Ok(json_value(row.get_ref(idx)?)))
+            .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+        match grouped.last_mut() {
+            Some((last, series)) if *last == key => series.push(values),
+            _ => grouped.push((key, vec![values])),
+        }
+    }
+
+    fs::create_dir_all(output_dir.as_std_path())
+        .with_context(|| format!("failed to create output directory {}", output_dir))?;
+    status!("Exporting {} series to {}", grouped.len(), output_dir);
+
+    let mut series = Vec::new();
+    let mut files: BTreeMap<String, String> = BTreeMap::new();
+    for (key, rows) in grouped {
+        let name = key.join("-");
+        let file_name = format!("{}.{}", file_stem(&name), format.extension());
+        if let Some(other) = files.insert(file_name.clone(), name.clone()) {
+            bail!(
+                "series '{}' and '{}' would both be written to {}",
+                other,
+                name,
+                file_name
+            );
+        }
+
+        let path = output_dir.join(&file_name);
+        fs::write(path.as_std_path(), render(format, columns, &rows)?)
+            .with_context(|| format!("failed to write {}", path))?;
+        output::written(&path, format!("  • {} ({} rows)", file_name, rows.len()));
+
+        let week = |values: &[Value]| values[0].as_str().unwrap_or_default().to_string();
+        series.push(SplitSeries {
+            file: file_name,
+            key: keys.iter().copied().zip(key).collect(),
+            rows: rows.len(),
+            first: week(&rows[0]),
+            last: week(&rows[rows.len() - 1]),
+            downloads: rows.iter().filter_map(|values| values[1].as_i64()).sum(),
+        });
+    }
+
+    let manifest = SplitManifest {
+        generated_at: Utc::now().to_rfc3339(),
+        provenance: provenance.clone(),
+        by,
+        format,
+        since: range.since,
+        until: range.until,
+        columns: columns.to_vec(),
+        series,
+    };
+    let manifest_path = output_dir.join("manifest.json");
+    let json = serde_json::to_string_pretty(&manifest)?;
+    fs::write(manifest_path.as_std_path(), json)
+        .with_context(|| format!("failed to write {}", manifest_path))?;
+    output::written(&manifest_path, "  • manifest.json");
+
+    Ok(manifest)
+}
+
+/// A file name for a series, keeping only characters that are safe in
+/// paths and URLs.
+fn file_stem(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn json_value(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null | ValueRef::Blob(_) => Value::Null,
+        ValueRef::Integer(n) => n.into(),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(s) => String::from_utf8_lossy(s).into_owned().into(),
+    }
+}
+
+/// Render the rows of a series as a file in `format`.
+fn render(format: SplitFormat, columns: &[&str], rows: &[Vec<Value>]) -> Result<String> {
+    match format {
+        SplitFormat::Csv => {
+            let mut out = columns.join(",") + "\n";
+            for values in rows {
+                let fields: Vec<String> = values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Null => String::new(),
+                        Value::String(s) => csv_field(s),
+                        value => value.to_string(),
+                    })
+                    .collect();
+                out += &fields.join(",");
+                out += "\n";
+            }
+            Ok(out)
+        }
+        SplitFormat::Json => {
+            let records: Vec<serde_json::Map<String, Value>> = rows
+                .iter()
+                .map(|values| {
+                    columns
+                        .iter()
+                        .map(|column| column.to_string())
+                        .zip(values.iter().cloned())
+                        .collect()
+                })
+                .collect();
+            serde_json::to_string_pretty(&records).context("failed to serialize series")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, db};
+    use camino::Utf8PathBuf;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_export_split() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let week = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        db::insert_weekly_stat(&conn, week(3), "crates", "cargo-nextest", 100).unwrap();
+        db::insert_weekly_stat(&conn, week(10), "crates", "cargo-nextest", 120).unwrap();
+        db::insert_weekly_stat(&conn, week(10), "installer", "get.nexte.st/latest", 7).unwrap();
+
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-split-{}", std::process::id()));
+        let provenance = Provenance::collect(&conn, &Config::default()).unwrap();
+        let manifest = export_split(
+            &conn,
+            SplitBy::Identifier,
+            SplitFormat::Csv,
+            DateRange::default(),
+            &provenance,
+            &dir,
+        )
+        .unwrap();
+
+        let files: Vec<_> = manifest.series.iter().map(|s| s.file.as_str()).collect();
+        assert_eq!(
+            files,
+            [
+                "crates-cargo-nextest.csv",
+                "installer-get.nexte.st_latest.csv"
+            ]
+        );
+        let nextest = &manifest.series[0];
+        assert_eq!(nextest.key["identifier"], "cargo-nextest");
+        assert_eq!(
+            (nextest.rows, nextest.first.as_str(), nextest.last.as_str()),
+            (2, "2025-11-03", "2025-11-10")
+        );
+        assert_eq!(nextest.downloads, 220);
+        assert_eq!(
+            fs::read_to_string(dir.join("crates-cargo-nextest.csv")).unwrap(),
+            "week_start,downloads\n2025-11-03,100\n2025-11-10,120\n"
+        );
+        assert!(dir.join("manifest.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
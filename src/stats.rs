@@ -0,0 +1,306 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Batched retrieval of download time series.
+//!
+//! Rendering a page of charts needs many series from the same few aggregate
+//! tables. [`batch`] answers a set of series requests with one scan per
+//! table, shared by every request reading from it, within a single read
+//! transaction so all series reflect the same state of the database.
+
+use crate::config::ChartResolution;
+use anyhow::{Context, Result, bail};
+use chrono::NaiveDate;
+use rusqlite::Connection;
+use std::{collections::BTreeMap, fmt};
+
+/// Identifies a series request within a batch.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpecId(pub String);
+
+impl From<&str> for SpecId {
+    fn from(id: &str) -> Self {
+        SpecId(id.to_string())
+    }
+}
+
+impl fmt::Display for SpecId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// What a series measures.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Measure {
+    /// Downloads per period, from `rolling_stats`, `weekly_stats`, or
+    /// `monthly_stats`.
+    Downloads(ChartResolution),
+    /// The mean daily downloads over the trailing 30 days, from
+    /// `rolling_stats`.
+    Average30d,
+}
+
+impl Measure {
+    fn table(self) -> Table {
+        match self {
+            Measure::Downloads(ChartResolution::Daily) | Measure::Average30d => Table::Rolling,
+            Measure::Downloads(ChartResolution::Weekly) => Table::Weekly,
+            Measure::Downloads(ChartResolution::Monthly) => Table::Monthly,
+        }
+    }
+
+    /// The index of the measure's column in its table's query.
+    fn column(self) -> usize {
+        match self {
+            Measure::Average30d => 3,
+            Measure::Downloads(_) => 2,
+        }
+    }
+}
+
+/// A request for a single series.
+#[derive(Clone, Debug)]
+pub struct SeriesSpec {
+    pub id: SpecId,
+    pub measure: Measure,
+    /// The source to read, or `None` for the sum over all sources.
+    pub source: Option<String>,
+    /// The first date to include, if any.
+    pub since: Option<NaiveDate>,
+    /// The last date to include, if any.
+    pub until: Option<NaiveDate>,
+}
+
+impl SeriesSpec {
+    /// Request a series over all dates, summed over all sources.
+    pub fn new(id: impl Into<SpecId>, measure: Measure) -> Self {
+        Self {
+            id: id.into(),
+            measure,
+            source: None,
+            since: None,
+            until: None,
+        }
+    }
+
+    /// Only read the given source.
+    pub fn source(mut self, source: &str) -> Self {
+        self.source = Some(source.to_string());
+        self
+    }
+
+    /// Only include dates in the given range.
+    pub fn range(mut self, since: Option<NaiveDate>, until: Option<NaiveDate>) -> Self {
+        self.since = since;
+        self.until = until;
+        self
+    }
+
+    fn includes(&self, date: NaiveDate, source: &str) -> bool {
+        self.source.as_deref().is_none_or(|s| s == source)
+            && self.since.is_none_or(|since| date >= since)
+            && self.until.is_none_or(|until| date <= until)
+    }
+}
+
+/// A series of values ordered by date.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Series {
+    pub points: Vec<(NaiveDate, f64)>,
+}
+
+impl Series {
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The points with values rounded to whole downloads.
+    pub fn rounded(&self) -> Vec<(NaiveDate, i64)> {
+        self.points
+            .iter()
+            .map(|(date, value)| (*date, value.round() as i64))
+            .collect()
+    }
+}
+
+/// The tables series are read from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Table {
+    Rolling,
+    Weekly,
+    Monthly,
+}
+
+impl Table {
+    const ALL: [Table; 3] = [Table::Rolling, Table::Weekly, Table::Monthly];
+
+    /// A query returning date, source, and the table's measures, for dates
+    /// between `?1` and `?2` when they aren't null.
+    fn query(self) -> &'static str {
+        match self {
+            Table::Rolling => {
+                "SELECT date, source, downloads, avg_30d
+                 FROM rolling_stats
+                 WHERE (?1 IS NULL OR date >= ?1) AND (?2 IS NULL OR date <= ?2)"
+            }
+            Table::Weekly => {
+                "SELECT week_start, source, SUM(downloads)
+                 FROM weekly_stats
+                 WHERE (?1 IS NULL OR week_start >= ?1) AND (?2 IS NULL OR week_start <= ?2)
+                 GROUP BY week_start, source"
+            }
+            Table::Monthly => {
+                "SELECT month_start, source, SUM(downloads)
+                 FROM monthly_stats
+                 WHERE (?1 IS NULL OR month_start >= ?1) AND (?2 IS NULL OR month_start <= ?2)
+                 GROUP BY month_start, source"
+            }
+        }
+    }
+}
+
+/// Retrieve a batch of series.
+///
+/// Each table is scanned at most once, over the union of the date ranges of
+/// the requests reading from it.
+pub fn batch(conn: &Connection, specs: Vec<SeriesSpec>) -> Result<BTreeMap<SpecId, Series>> {
+    let mut values: BTreeMap<SpecId, BTreeMap<NaiveDate, f64>> = BTreeMap::new();
+    for spec in &specs {
+        if values.insert(spec.id.clone(), BTreeMap::new()).is_some() {
+            bail!("duplicate series '{}' in batch", spec.id);
+        }
+    }
+
+    let tx = conn
+        .unchecked_transaction()
+        .context("failed to start read transaction")?;
+    for table in Table::ALL {
+        let group: Vec<&SeriesSpec> = specs
+            .iter()
+            .filter(|spec| spec.measure.table() == table)
+            .collect();
+        if group.is_empty() {
+            continue;
+        }
+
+        // An open-ended request makes the scan open-ended too.
+        let since = if group.iter().all(|spec| spec.since.is_some()) {
+            group.iter().filter_map(|spec| spec.since).min()
+        } else {
+            None
+        };
+        let until = if group.iter().all(|spec| spec.until.is_some()) {
+            group.iter().filter_map(|spec| spec.until).max()
+        } else {
+            None
+        };
+
+        let mut stmt = tx.prepare(table.query())?;
+        let mut rows = stmt.query(rusqlite::params![
+            since.map(|d| d.to_string()),
+            until.map(|d| d.to_string())
+        ])?;
+        while let Some(row) = rows.next()? {
+            let date: String = row.get(0)?;
+            let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .with_context(|| format!("failed to parse date '{}'", date))?;
+            let source: String = row.get(1)?;
+            for spec in &group {
+                if spec.includes(date, &source) {
+                    let value: f64 = row.get(spec.measure.column())?;
+                    let series = values.get_mut(&spec.id).expect("every spec has a series");
+                    *series.entry(date).or_default() += value;
+                }
+            }
+        }
+    }
+    tx.commit()?;
+
+    Ok(values
+        .into_iter()
+        .map(|(id, points)| {
+            let points = points.into_iter().collect();
+            (id, Series { points })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use camino::Utf8Path;
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_batch() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        conn.execute_batch(
+            "INSERT INTO weekly_stats VALUES
+                 ('2024-01-01', 'crates', 'cargo-nextest', 100),
+                 ('2024-01-01', 'crates', 'nextest-runner', 10),
+                 ('2024-01-01', 'github', 'releases', 50),
+                 ('2024-01-08', 'crates', 'cargo-nextest', 120),
+                 ('2024-01-15', 'crates', 'cargo-nextest', 130);
+             INSERT INTO rolling_stats VALUES
+                 ('2024-01-01', 'crates', 14.6, 14.0, 13.0),
+                 ('2024-01-02', 'crates', 15.0, 14.5, 13.5);",
+        )
+        .unwrap();
+
+        let weekly = Measure::Downloads(ChartResolution::Weekly);
+        let results = batch(
+            &conn,
+            vec![
+                SeriesSpec::new("total", weekly),
+                SeriesSpec::new("crates", weekly)
+                    .source("crates")
+                    .range(Some(date("2024-01-08")), None),
+                SeriesSpec::new("github", weekly).source("github"),
+                SeriesSpec::new("daily", Measure::Downloads(ChartResolution::Daily)),
+                SeriesSpec::new("average", Measure::Average30d).source("crates"),
+                SeriesSpec::new("monthly", Measure::Downloads(ChartResolution::Monthly)),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            results[&"total".into()].rounded(),
+            vec![
+                (date("2024-01-01"), 160),
+                (date("2024-01-08"), 120),
+                (date("2024-01-15"), 130)
+            ]
+        );
+        assert_eq!(
+            results[&"crates".into()].rounded(),
+            vec![(date("2024-01-08"), 120), (date("2024-01-15"), 130)]
+        );
+        assert_eq!(
+            results[&"github".into()].rounded(),
+            vec![(date("2024-01-01"), 50)]
+        );
+        assert_eq!(
+            results[&"daily".into()].rounded(),
+            vec![(date("2024-01-01"), 15), (date("2024-01-02"), 15)]
+        );
+        assert_eq!(
+            results[&"average".into()].points,
+            vec![(date("2024-01-01"), 13.0), (date("2024-01-02"), 13.5)]
+        );
+        assert!(results[&"monthly".into()].is_empty());
+
+        let duplicate = batch(
+            &conn,
+            vec![
+                SeriesSpec::new("total", weekly),
+                SeriesSpec::new("total", Measure::Average30d),
+            ],
+        );
+        assert!(duplicate.is_err());
+    }
+}
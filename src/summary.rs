@@ -0,0 +1,194 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Summary data model for a collection run.
+//!
+//! The summary is serializable so that it can be exposed to notification
+//! templates and other consumers without them depending on internal types.
+
+use crate::{
+    alerts::Alert,
+    analysis::{Anomaly, Movers},
+    db::{ArchivedAsset, CountReset, FilterSkip, OwnerChange, Tombstone},
+    metrics::MetricValue,
+};
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::time::Instant;
+
+/// Summary of a single `collect` run.
+#[derive(Debug, Serialize)]
+pub struct CollectionSummary {
+    /// The date the snapshots were recorded under.
+    pub date: NaiveDate,
+    /// Total number of records inserted across all sources.
+    pub records: u64,
+    /// Total downloads reported across all sources.
+    pub downloads: u64,
+    /// Per-source results, in collection order.
+    pub sources: Vec<SourceSummary>,
+    /// Sources that failed, which the rest of the run went on without.
+    pub failures: Vec<SourceFailure>,
+    /// Sources (as `kind:name`) left for the next run because the time budget
+    /// ran out.
+    pub deferred: Vec<String>,
+    /// GitHub assets whose cumulative count was found to go backwards for
+    /// the first time in this run.
+    pub resets: Vec<CountReset>,
+    /// The largest week-over-week changes by crate, platform, and version,
+    /// after aggregation.
+    pub movers: Movers,
+    /// crates.io owners added in this run that aren't listed as expected in
+    /// the configuration.
+    pub unexpected_owners: Vec<OwnerChange>,
+    /// The latest value of each derived metric, after aggregation.
+    pub metrics: Vec<MetricValue>,
+    /// Sources whose latest complete week of downloads spiked or dropped
+    /// against the weeks before, after aggregation.
+    pub anomalies: Vec<Anomaly>,
+    /// Alert rules that fired, after aggregation.
+    pub alerts: Vec<Alert>,
+    /// The headline total downloads after collection.
+    pub headline: u64,
+    /// The provenance of the collected data, as a single line.
+    pub provenance: String,
+}
+
+/// Summary of collection for a single source.
+#[derive(Debug, Serialize)]
+pub struct SourceSummary {
+    /// The source kind: `github`, `crates`, or `installer`.
+    pub kind: &'static str,
+    /// The source name: `owner/repo` for GitHub, the crate name for crates.io,
+    /// and the configured name for install endpoints.
+    pub name: String,
+    /// Number of records inserted for this source.
+    pub records: u64,
+    /// Downloads reported by this source.
+    ///
+    /// For GitHub this is the cumulative asset count in the snapshot; for
+    /// crates.io this is the crate's all-time total; for install endpoints
+    /// this is the total over the days reported.
+    pub downloads: u64,
+    /// GitHub assets that disappeared from the API since the last collection.
+    /// Their history is kept, with their last seen count carried forward.
+    pub archived: Vec<ArchivedAsset>,
+    /// crates.io owners added or removed since the last collection.
+    pub owner_changes: Vec<OwnerChange>,
+    /// crates.io versions deleted, yanked, unyanked, or listed again since
+    /// the last collection. Deleted versions still count towards totals.
+    pub tombstones: Vec<Tombstone>,
+    /// The latest date the source reported data for.
+    pub latest_date: Option<NaiveDate>,
+    /// The ETag of the source's response, if it sent one.
+    pub etag: Option<String>,
+    /// What collection filters left out of the source.
+    pub skipped: Vec<FilterSkip>,
+}
+
+/// A source that failed to be collected.
+#[derive(Debug, Serialize)]
+pub struct SourceFailure {
+    /// The source kind: `github`, `crates`, or `installer`.
+    pub kind: &'static str,
+    pub name: String,
+    pub error: String,
+}
+
+impl CollectionSummary {
+    /// Create an empty summary for the given date.
+    pub fn new(date: NaiveDate) -> Self {
+        Self {
+            date,
+            records: 0,
+            downloads: 0,
+            sources: Vec::new(),
+            failures: Vec::new(),
+            deferred: Vec::new(),
+            resets: Vec::new(),
+            movers: Movers::default(),
+            unexpected_owners: Vec::new(),
+            metrics: Vec::new(),
+            anomalies: Vec::new(),
+            alerts: Vec::new(),
+            headline: 0,
+            provenance: String::new(),
+        }
+    }
+
+    /// Add a source's results to the summary, updating totals.
+    pub fn push(&mut self, source: SourceSummary) {
+        self.records += source.records;
+        self.downloads += source.downloads;
+        self.sources.push(source);
+    }
+}
+
+/// Summary of a `refresh` run: the outcome of each step, in order.
+#[derive(Debug, Default, Serialize)]
+pub struct RefreshSummary {
+    pub steps: Vec<StepSummary>,
+}
+
+/// The outcome of a single `refresh` step.
+#[derive(Debug, Serialize)]
+pub struct StepSummary {
+    pub step: &'static str,
+    pub status: StepStatus,
+    /// Wall-clock time taken by the step, in seconds.
+    pub seconds: f64,
+    /// A short description of the result, or the error if the step failed.
+    pub detail: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StepStatus {
+    Completed,
+    Skipped,
+    Failed,
+}
+
+impl StepStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            StepStatus::Completed => "completed",
+            StepStatus::Skipped => "skipped",
+            StepStatus::Failed => "failed",
+        }
+    }
+}
+
+impl RefreshSummary {
+    /// Record a step that was skipped.
+    pub fn skip(&mut self, step: &'static str) {
+        self.steps.push(StepSummary {
+            step,
+            status: StepStatus::Skipped,
+            seconds: 0.0,
+            detail: String::new(),
+        });
+    }
+
+    /// Record the result of a step that started at `started`, passing through
+    /// its error so the caller can stop.
+    pub fn record(
+        &mut self,
+        step: &'static str,
+        started: Instant,
+        result: anyhow::Result<String>,
+    ) -> anyhow::Result<()> {
+        let seconds = started.elapsed().as_secs_f64();
+        let (status, detail, result) = match result {
+            Ok(detail) => (StepStatus::Completed, detail, Ok(())),
+            Err(err) => (StepStatus::Failed, format!("{:#}", err), Err(err)),
+        };
+        self.steps.push(StepSummary {
+            step,
+            status,
+            seconds,
+            detail,
+        });
+        result
+    }
+}
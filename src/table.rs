@@ -0,0 +1,604 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Query results as typed tables, rendered separately.
+//!
+//! Queries build a [`Report`] of one or more [`Table`]s whose cells keep their
+//! values, so that the same results can be printed as an aligned table for
+//! people, or as JSON, CSV, or GitHub-flavored Markdown for scripts and
+//! documents. Titles and notes are only part of the table and Markdown
+//! output.
+
+use crate::format::{self, Target};
+use anyhow::{Result, bail};
+use serde_json::{Map, Value};
+use std::{fmt::Write, str::FromStr};
+
+/// How query results are printed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// Aligned columns for the terminal.
+    #[default]
+    Table,
+    /// An array of objects, one per row. Reports with several tables are an
+    /// object with an array per table.
+    Json,
+    /// A header line and a line per row. Several tables are separated by a
+    /// blank line.
+    Csv,
+    /// GitHub-flavored Markdown tables.
+    Markdown,
+    /// An HTML fragment with a `<table>` per table, for embedding in a page.
+    Html,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "table" => Ok(OutputFormat::Table),
+            "json" => Ok(OutputFormat::Json),
+            "csv" => Ok(OutputFormat::Csv),
+            "markdown" | "md" => Ok(OutputFormat::Markdown),
+            "html" => Ok(OutputFormat::Html),
+            _ => bail!(
+                "unknown output format '{}' (expected 'table', 'json', 'csv', 'markdown', or 'html')",
+                s
+            ),
+        }
+    }
+}
+
+/// The results of a query.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    pub title: Option<String>,
+    pub tables: Vec<Table>,
+    /// Paragraphs printed after the tables.
+    pub notes: Vec<String>,
+}
+
+impl Report {
+    pub fn new(title: impl Into<String>) -> Self {
+        Self {
+            title: Some(title.into()),
+            ..Self::default()
+        }
+    }
+
+    /// A report without results, explaining why.
+    pub fn empty(message: impl Into<String>) -> Self {
+        Self {
+            notes: vec![message.into()],
+            ..Self::default()
+        }
+    }
+
+    pub fn table(mut self, table: Table) -> Self {
+        self.tables.push(table);
+        self
+    }
+
+    pub fn note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    /// Print the report to standard output.
+    pub fn print(&self, format: OutputFormat) -> Result<()> {
+        print!("{}", self.render(format)?);
+        Ok(())
+    }
+
+    pub fn render(&self, format: OutputFormat) -> Result<String> {
+        self.render_for(format, Target::Terminal)
+    }
+
+    /// Render the report with numbers formatted for `target`. JSON and CSV
+    /// always contain raw values.
+    pub fn render_for(&self, format: OutputFormat, target: Target) -> Result<String> {
+        match format {
+            OutputFormat::Table => Ok(self.render_table(target)),
+            OutputFormat::Json => self.render_json(),
+            OutputFormat::Csv => Ok(self.render_csv()),
+            OutputFormat::Markdown => Ok(self.render_markdown(target)),
+            OutputFormat::Html => Ok(self.render_html(target)),
+        }
+    }
+
+    fn render_table(&self, target: Target) -> String {
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            _ = writeln!(out, "\n{}", title);
+        }
+        for table in &self.tables {
+            if let Some(title) = &table.title {
+                _ = writeln!(out, "\n{}", title);
+            }
+            let widths = table.widths(target);
+            let line = |cells: Vec<(String, Align)>| {
+                let fields: Vec<String> = cells
+                    .into_iter()
+                    .zip(&widths)
+                    .map(|((text, align), width)| match align {
+                        Align::Left => format!("{:<width$}", text),
+                        Align::Right => format!("{:>width$}", text),
+                    })
+                    .collect();
+                fields.join(" ").trim_end().to_string()
+            };
+            _ = writeln!(
+                out,
+                "\n{}",
+                line(
+                    table
+                        .columns
+                        .iter()
+                        .map(|column| (column.name.clone(), column.align))
+                        .collect()
+                )
+            );
+            let total = widths.iter().sum::<usize>() + widths.len().saturating_sub(1);
+            _ = writeln!(out, "{}", "=".repeat(total));
+            for row in &table.rows {
+                _ = writeln!(
+                    out,
+                    "{}",
+                    line(
+                        row.iter()
+                            .zip(&table.columns)
+                            .map(|(cell, column)| (cell.display(target), column.align))
+                            .collect()
+                    )
+                );
+            }
+            if table.rows.is_empty() {
+                _ = writeln!(out, "None.");
+            }
+        }
+        for note in &self.notes {
+            _ = writeln!(out, "\n{}", note);
+        }
+        out
+    }
+
+    fn render_json(&self) -> Result<String> {
+        let value = match self.tables.as_slice() {
+            [table] => table.to_json(),
+            [] => Value::Array(Vec::new()),
+            tables => Value::Object(
+                tables
+                    .iter()
+                    .map(|table| (table.key.clone(), table.to_json()))
+                    .collect(),
+            ),
+        };
+        Ok(serde_json::to_string_pretty(&value)? + "\n")
+    }
+
+    fn render_csv(&self) -> String {
+        let tables: Vec<String> = self
+            .tables
+            .iter()
+            .map(|table| {
+                let mut out = String::new();
+                let header: Vec<String> = table
+                    .columns
+                    .iter()
+                    .map(|column| csv_field(&column.key))
+                    .collect();
+                _ = writeln!(out, "{}", header.join(","));
+                for row in &table.rows {
+                    let fields: Vec<String> =
+                        row.iter().map(|cell| csv_field(&cell.raw())).collect();
+                    _ = writeln!(out, "{}", fields.join(","));
+                }
+                out
+            })
+            .collect();
+        tables.join("\n")
+    }
+
+    fn render_markdown(&self, target: Target) -> String {
+        let mut blocks = Vec::new();
+        if let Some(title) = &self.title {
+            blocks.push(format!("## {}\n", title));
+        }
+        for table in &self.tables {
+            if let Some(title) = &table.title {
+                blocks.push(format!("### {}\n", title));
+            }
+            let mut out = String::new();
+            let row = |cells: Vec<String>| format!("| {} |\n", cells.join(" | "));
+            out += &row(table
+                .columns
+                .iter()
+                .map(|column| markdown_escape(&column.name))
+                .collect());
+            out += &row(table
+                .columns
+                .iter()
+                .map(|column| match column.align {
+                    Align::Left => ":---".to_string(),
+                    Align::Right => "---:".to_string(),
+                })
+                .collect());
+            for cells in &table.rows {
+                out += &row(cells
+                    .iter()
+                    .map(|cell| markdown_escape(&cell.display(target)))
+                    .collect());
+            }
+            blocks.push(out);
+        }
+        for note in &self.notes {
+            blocks.push(format!("{}\n", note));
+        }
+        blocks.join("\n")
+    }
+
+    fn render_html(&self, target: Target) -> String {
+        let mut out = String::new();
+        if let Some(title) = &self.title {
+            _ = writeln!(out, "<h2>{}</h2>", html_escape(title));
+        }
+        for table in &self.tables {
+            if let Some(title) = &table.title {
+                _ = writeln!(out, "<h3>{}</h3>", html_escape(title));
+            }
+            let cell = |tag: &str, text: &str, align: Align| match align {
+                Align::Left => format!("<{tag}>{}</{tag}>", html_escape(text)),
+                Align::Right => format!("<{tag} class=\"right\">{}</{tag}>", html_escape(text)),
+            };
+            out += "<table>\n<thead>\n<tr>";
+            for column in &table.columns {
+                out += &cell("th", &column.name, column.align);
+            }
+            out += "</tr>\n</thead>\n<tbody>\n";
+            for row in &table.rows {
+                out += "<tr>";
+                for (value, column) in row.iter().zip(&table.columns) {
+                    out += &cell("td", &value.display(target), column.align);
+                }
+                out += "</tr>\n";
+            }
+            out += "</tbody>\n</table>\n";
+        }
+        for note in &self.notes {
+            _ = writeln!(out, "<p>{}</p>", html_escape(note));
+        }
+        out
+    }
+}
+
+/// A table of results.
+#[derive(Clone, Debug)]
+pub struct Table {
+    /// The name of the table in JSON output for reports with several tables.
+    pub key: String,
+    pub title: Option<String>,
+    pub columns: Vec<Column>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Table {
+    pub fn new(columns: impl IntoIterator<Item = Column>) -> Self {
+        Self {
+            key: "rows".to_string(),
+            title: None,
+            columns: columns.into_iter().collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Name the table, for reports with several tables.
+    pub fn named(mut self, key: &str, title: impl Into<String>) -> Self {
+        self.key = key.to_string();
+        self.title = Some(title.into());
+        self
+    }
+
+    /// Add a row, with a cell per column.
+    pub fn push(&mut self, row: impl IntoIterator<Item = Cell>) {
+        let row: Vec<Cell> = row.into_iter().collect();
+        debug_assert_eq!(row.len(), self.columns.len(), "a cell per column");
+        self.rows.push(row);
+    }
+
+    fn widths(&self, target: Target) -> Vec<usize> {
+        self.columns
+            .iter()
+            .enumerate()
+            .map(|(idx, column)| {
+                self.rows
+                    .iter()
+                    .map(|row| row[idx].display(target).chars().count())
+                    .chain([column.width, column.name.chars().count()])
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    fn to_json(&self) -> Value {
+        Value::Array(
+            self.rows
+                .iter()
+                .map(|row| {
+                    let object: Map<String, Value> = self
+                        .columns
+                        .iter()
+                        .zip(row)
+                        .map(|(column, cell)| (column.key.clone(), cell.to_json()))
+                        .collect();
+                    Value::Object(object)
+                })
+                .collect(),
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Align {
+    Left,
+    Right,
+}
+
+/// A column of a table.
+#[derive(Clone, Debug)]
+pub struct Column {
+    /// The header in table and Markdown output.
+    pub name: String,
+    /// The header in JSON and CSV output, such as `pre_release_percent` for
+    /// `Pre-release %`.
+    pub key: String,
+    pub align: Align,
+    /// The minimum width in table output.
+    pub width: usize,
+}
+
+impl Column {
+    pub fn left(name: impl Into<String>, width: usize) -> Self {
+        Self::new(name.into(), Align::Left, width)
+    }
+
+    pub fn right(name: impl Into<String>, width: usize) -> Self {
+        Self::new(name.into(), Align::Right, width)
+    }
+
+    fn new(name: String, align: Align, width: usize) -> Self {
+        let key = column_key(&name);
+        Self {
+            name,
+            key,
+            align,
+            width,
+        }
+    }
+}
+
+/// A header in snake case, with `%` spelled out.
+fn column_key(name: &str) -> String {
+    let name = name.replace('%', " percent ").to_lowercase();
+    name.split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("_")
+}
+
+/// A value in a table.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cell {
+    Text(String),
+    /// A count, such as of downloads.
+    Count(u64),
+    /// A change in a count, shown with its sign.
+    Change(i64),
+    /// A number shown as `display`, such as `42.5%` for a share of `42.5`.
+    Number {
+        value: f64,
+        display: String,
+    },
+    /// Whether something applies, shown as `*` when it does.
+    Flag(bool),
+    /// No value, shown as `-`.
+    Missing,
+}
+
+impl Cell {
+    pub fn text(text: impl Into<String>) -> Self {
+        Cell::Text(text.into())
+    }
+
+    /// A number shown with `decimals` decimal places.
+    pub fn float(value: f64, decimals: usize) -> Self {
+        Cell::Number {
+            value,
+            display: format!("{:.decimals$}", value),
+        }
+    }
+
+    /// A fraction shown as a percentage with `decimals` decimal places. The
+    /// value is the percentage, such as `42.5` for `0.425`.
+    pub fn percent(fraction: f64, decimals: usize) -> Self {
+        let value = fraction * 100.0;
+        Cell::Number {
+            value,
+            display: format!("{:.decimals$}%", value),
+        }
+    }
+
+    /// A percentage change shown with its sign, or missing if there was
+    /// nothing to compare against.
+    pub fn percent_change(change: Option<f64>) -> Self {
+        match change {
+            Some(value) => Cell::Number {
+                value,
+                display: format!("{:+.1}%", value),
+            },
+            None => Cell::Missing,
+        }
+    }
+
+    /// A value that may be missing.
+    pub fn optional<T>(value: Option<T>, cell: impl FnOnce(T) -> Cell) -> Self {
+        value.map_or(Cell::Missing, cell)
+    }
+
+    fn display(&self, target: Target) -> String {
+        match self {
+            Cell::Text(text) => text.clone(),
+            Cell::Count(n) => format::number_for(target, *n),
+            Cell::Change(n) => {
+                let sign = if *n < 0 { "-" } else { "+" };
+                format!("{}{}", sign, format::number_for(target, n.unsigned_abs()))
+            }
+            Cell::Number { display, .. } => display.clone(),
+            Cell::Flag(true) => "*".to_string(),
+            Cell::Flag(false) => String::new(),
+            Cell::Missing => "-".to_string(),
+        }
+    }
+
+    fn raw(&self) -> String {
+        match self {
+            Cell::Text(text) => text.clone(),
+            Cell::Count(n) => n.to_string(),
+            Cell::Change(n) => n.to_string(),
+            Cell::Number { value, .. } => value.to_string(),
+            Cell::Flag(flag) => flag.to_string(),
+            Cell::Missing => String::new(),
+        }
+    }
+
+    fn to_json(&self) -> Value {
+        match self {
+            Cell::Text(text) => text.clone().into(),
+            Cell::Count(n) => (*n).into(),
+            Cell::Change(n) => (*n).into(),
+            Cell::Number { value, .. } => {
+                serde_json::Number::from_f64(*value).map_or(Value::Null, Value::Number)
+            }
+            Cell::Flag(flag) => (*flag).into(),
+            Cell::Missing => Value::Null,
+        }
+    }
+}
+
+/// Quote a CSV field if it needs to be, as in RFC 4180.
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Escape text for HTML element content and quoted attribute values.
+pub(crate) fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn markdown_escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Report {
+        let mut table = Table::new([
+            Column::left("Week", 12),
+            Column::right("Downloads", 10),
+            Column::right("Pre-release %", 0),
+        ]);
+        table.push([
+            Cell::text("2025-11-10"),
+            Cell::Count(1200),
+            Cell::percent(0.125, 1),
+        ]);
+        table.push([Cell::text("a, \"b\""), Cell::Count(7), Cell::Missing]);
+        Report::new("Weekly downloads")
+            .table(table)
+            .note("Shares are of each week.")
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let report = sample();
+        assert_eq!(
+            report.render(OutputFormat::Table).unwrap(),
+            "\nWeekly downloads\n\
+             \nWeek          Downloads Pre-release %\n\
+             =====================================\n\
+             2025-11-10        1,200         12.5%\n\
+             a, \"b\"                7             -\n\
+             \nShares are of each week.\n"
+        );
+        assert_eq!(
+            report.render(OutputFormat::Csv).unwrap(),
+            "week,downloads,pre_release_percent\n\
+             2025-11-10,1200,12.5\n\
+             \"a, \"\"b\"\"\",7,\n"
+        );
+        let json: Value =
+            serde_json::from_str(&report.render(OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"week": "2025-11-10", "downloads": 1200, "pre_release_percent": 12.5},
+                {"week": "a, \"b\"", "downloads": 7, "pre_release_percent": null},
+            ])
+        );
+        assert_eq!(
+            report.render(OutputFormat::Markdown).unwrap(),
+            "## Weekly downloads\n\
+             \n| Week | Downloads | Pre-release % |\n\
+             | :--- | ---: | ---: |\n\
+             | 2025-11-10 | 1,200 | 12.5% |\n\
+             | a, \"b\" | 7 | - |\n\
+             \nShares are of each week.\n"
+        );
+        assert_eq!(
+            report.render(OutputFormat::Html).unwrap(),
+            "<h2>Weekly downloads</h2>\n\
+             <table>\n<thead>\n\
+             <tr><th>Week</th><th class=\"right\">Downloads</th>\
+             <th class=\"right\">Pre-release %</th></tr>\n\
+             </thead>\n<tbody>\n\
+             <tr><td>2025-11-10</td><td class=\"right\">1,200</td>\
+             <td class=\"right\">12.5%</td></tr>\n\
+             <tr><td>a, &quot;b&quot;</td><td class=\"right\">7</td>\
+             <td class=\"right\">-</td></tr>\n\
+             </tbody>\n</table>\n\
+             <p>Shares are of each week.</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_json_several_tables() {
+        let gains = Table::new([Column::left("Identifier", 0)]).named("gains", "Gains");
+        let mut losses = Table::new([Column::left("Identifier", 0)]).named("losses", "Losses");
+        losses.push([Cell::text("cargo-nextest")]);
+        let report = Report::default().table(gains).table(losses);
+        let json: Value =
+            serde_json::from_str(&report.render(OutputFormat::Json).unwrap()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"gains": [], "losses": [{"identifier": "cargo-nextest"}]})
+        );
+    }
+}
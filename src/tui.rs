@@ -0,0 +1,524 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Terminal dashboard with the latest week, a weekly trend, top platforms,
+//! and how fresh each source's data is.
+
+use crate::{
+    aggregate,
+    analysis::{self, Growth, GrowthMetric},
+    config::WeekStart,
+    db, format,
+};
+use anyhow::{Context, Result};
+use chrono::{NaiveDate, Utc};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::Line,
+    widgets::{Block, BorderType, Paragraph, Row, Sparkline, Table, TableState},
+};
+use rusqlite::Connection;
+
+/// Sources the trend panel cycles through. `all` sums the others.
+const TREND_SOURCES: [&str; 4] = ["all", "github", "crates", "installer"];
+
+/// Data older than this many days is shown as stale in the freshness panel.
+const STALE_AFTER_DAYS: i64 = 2;
+
+/// Everything the dashboard shows, read from the database in one go.
+#[derive(Debug)]
+struct Snapshot {
+    week_start: WeekStart,
+    /// Week-over-week growth for each source's latest complete week.
+    latest: Vec<Growth>,
+    /// Weekly downloads through the latest complete week, in the order of
+    /// [`TREND_SOURCES`].
+    trends: Vec<Vec<(NaiveDate, u64)>>,
+    /// The week shown in the platforms panel, with downloads per platform,
+    /// largest first.
+    platforms: Option<(NaiveDate, Vec<(String, u64)>)>,
+    freshness: Vec<Freshness>,
+}
+
+/// How recent one source's data is.
+#[derive(Debug, PartialEq, Eq)]
+struct Freshness {
+    label: String,
+    latest: Option<NaiveDate>,
+    failures: u32,
+}
+
+impl Snapshot {
+    /// Read the dashboard data.
+    fn load(conn: &Connection) -> Result<Self> {
+        let latest = analysis::growth(conn)?
+            .into_iter()
+            .filter(|growth| growth.metric == GrowthMetric::Wow)
+            .collect();
+
+        let trends = TREND_SOURCES
+            .iter()
+            .map(|source| analysis::weekly_trend(conn, source))
+            .collect::<Result<Vec<_>>>()?;
+
+        let platforms = match trends[1].last() {
+            Some(&(week, _)) => Some((week, platform_downloads(conn, week)?)),
+            None => None,
+        };
+
+        // Sources that were never collected aren't worth a row, unless the
+        // collector state below shows attempts.
+        let mut freshness = Vec::new();
+        for source in analysis::SOURCES {
+            if let Some(latest) = analysis::latest_data_date(conn, source)? {
+                freshness.push(Freshness {
+                    label: source_label(source).to_string(),
+                    latest: Some(latest),
+                    failures: 0,
+                });
+            }
+        }
+        for state in db::get_collector_state(conn)? {
+            freshness.push(Freshness {
+                label: format!("{}:{}", state.kind, state.name),
+                latest: state.last_collected_date,
+                failures: state.consecutive_failures,
+            });
+        }
+
+        Ok(Self {
+            week_start: aggregate::stored_week_start(conn)?,
+            latest,
+            trends,
+            platforms,
+            freshness,
+        })
+    }
+}
+
+fn platform_downloads(conn: &Connection, week: NaiveDate) -> Result<Vec<(String, u64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT platform, downloads FROM weekly_platform_stats
+         WHERE week_start = ?1
+         ORDER BY downloads DESC, platform",
+    )?;
+    let platforms = stmt
+        .query_map([week.to_string()], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as u64))
+        })?
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read platform downloads")?;
+    Ok(platforms)
+}
+
+fn source_label(source: &str) -> &'static str {
+    match source {
+        "github" => "GitHub releases",
+        "crates" => "crates.io",
+        "installer" => "Install script",
+        _ => "All sources",
+    }
+}
+
+/// A panel that can have keyboard focus.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Panel {
+    Latest,
+    Trend,
+    Platforms,
+    Freshness,
+}
+
+impl Panel {
+    const ALL: [Panel; 4] = [
+        Panel::Latest,
+        Panel::Trend,
+        Panel::Platforms,
+        Panel::Freshness,
+    ];
+
+    fn index(self) -> usize {
+        Self::ALL.iter().position(|&panel| panel == self).unwrap()
+    }
+
+    fn next(self) -> Self {
+        Self::ALL[(self.index() + 1) % Self::ALL.len()]
+    }
+
+    fn previous(self) -> Self {
+        Self::ALL[(self.index() + Self::ALL.len() - 1) % Self::ALL.len()]
+    }
+}
+
+/// What to do after a key press.
+#[derive(Debug, PartialEq, Eq)]
+enum Action {
+    Continue,
+    Reload,
+    Quit,
+}
+
+/// Dashboard state: the data shown and where the user is.
+struct App {
+    snapshot: Snapshot,
+    today: NaiveDate,
+    focus: Panel,
+    /// Index into [`TREND_SOURCES`].
+    trend_source: usize,
+    platforms: TableState,
+    freshness: TableState,
+}
+
+impl App {
+    fn new(snapshot: Snapshot, today: NaiveDate) -> Self {
+        Self {
+            snapshot,
+            today,
+            focus: Panel::Latest,
+            trend_source: 0,
+            platforms: TableState::default(),
+            freshness: TableState::default(),
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) -> Action {
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Action::Quit,
+            KeyCode::Char('r') => return Action::Reload,
+            KeyCode::Tab => self.focus = self.focus.next(),
+            KeyCode::BackTab => self.focus = self.focus.previous(),
+            KeyCode::Char(c @ '1'..='4') => {
+                self.focus = Panel::ALL[c as usize - '1' as usize];
+            }
+            KeyCode::Left | KeyCode::Char('h') if self.focus == Panel::Trend => {
+                self.trend_source =
+                    (self.trend_source + TREND_SOURCES.len() - 1) % TREND_SOURCES.len();
+            }
+            KeyCode::Right | KeyCode::Char('l') if self.focus == Panel::Trend => {
+                self.trend_source = (self.trend_source + 1) % TREND_SOURCES.len();
+            }
+            KeyCode::Down | KeyCode::Char('j') => self.scroll(1),
+            KeyCode::Up | KeyCode::Char('k') => self.scroll(-1),
+            _ => {}
+        }
+        Action::Continue
+    }
+
+    /// Move the selection in the focused table, if it has one.
+    fn scroll(&mut self, by: isize) {
+        let (state, len) = match self.focus {
+            Panel::Platforms => (
+                &mut self.platforms,
+                self.snapshot
+                    .platforms
+                    .as_ref()
+                    .map_or(0, |(_, rows)| rows.len()),
+            ),
+            Panel::Freshness => (&mut self.freshness, self.snapshot.freshness.len()),
+            _ => return,
+        };
+        if len == 0 {
+            return;
+        }
+        let selected = match state.selected() {
+            Some(idx) => idx.saturating_add_signed(by).min(len - 1),
+            None => 0,
+        };
+        state.select(Some(selected));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [body, help] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [top, bottom] =
+            Layout::vertical([Constraint::Length(7), Constraint::Min(0)]).areas(body);
+        let [latest, trend] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)]).areas(top);
+        let [platforms, freshness] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(bottom);
+
+        self.draw_latest(frame, latest);
+        self.draw_trend(frame, trend);
+        self.draw_platforms(frame, platforms);
+        self.draw_freshness(frame, freshness);
+        frame.render_widget(
+            Paragraph::new(" Tab/1-4: panel  ←/→: trend source  ↑/↓: scroll  r: reload  q: quit")
+                .dark_gray(),
+            help,
+        );
+    }
+
+    /// A bordered block, highlighted when `panel` has focus.
+    fn block(&self, panel: Panel, title: String) -> Block<'static> {
+        let block = Block::bordered()
+            .border_type(BorderType::Rounded)
+            .title(format!(" {}. {} ", panel.index() + 1, title));
+        if self.focus == panel {
+            block
+                .border_style(Style::new().cyan())
+                .title_style(Style::new().add_modifier(Modifier::BOLD))
+        } else {
+            block.border_style(Style::new().dark_gray())
+        }
+    }
+
+    fn draw_latest(&self, frame: &mut Frame, area: Rect) {
+        let rows = self.snapshot.latest.iter().map(|growth| {
+            let change = match growth.change() {
+                Some(change) if change < 0.0 => Line::from(format!("{:+.1}%", change)).red(),
+                Some(change) => Line::from(format!("{:+.1}%", change)).green(),
+                None => Line::from("n/a").dark_gray(),
+            };
+            Row::new(vec![
+                Line::from(source_label(growth.source)),
+                Line::from(aggregate::week_label(
+                    self.snapshot.week_start,
+                    growth.period,
+                )),
+                Line::from(format::number(growth.downloads)).right_aligned(),
+                change.right_aligned(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(10),
+                Constraint::Length(14),
+                Constraint::Length(9),
+            ],
+        )
+        .header(Row::new(["Source", "Week", "Downloads", "Change"]).bold())
+        .block(self.block(Panel::Latest, "Latest complete week".to_string()));
+        frame.render_widget(table, area);
+    }
+
+    fn draw_trend(&self, frame: &mut Frame, area: Rect) {
+        let source = TREND_SOURCES[self.trend_source];
+        let trend = &self.snapshot.trends[self.trend_source];
+        let block = self.block(
+            Panel::Trend,
+            format!("Weekly trend: {} (←/→)", source_label(source)),
+        );
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+        if trend.is_empty() {
+            frame.render_widget(Paragraph::new("No complete weeks yet.").dark_gray(), inner);
+            return;
+        }
+
+        let [spark, caption] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(inner);
+        // Show as many of the most recent weeks as fit, one per column.
+        let shown = &trend[trend.len().saturating_sub(spark.width as usize)..];
+        let values: Vec<u64> = shown.iter().map(|(_, downloads)| *downloads).collect();
+        frame.render_widget(Sparkline::default().data(&values).blue(), spark);
+
+        let max = values.iter().max().copied().unwrap_or(0);
+        let (first, _) = shown[0];
+        let (last, last_downloads) = shown[shown.len() - 1];
+        frame.render_widget(
+            Paragraph::new(format!(
+                "{} to {}  latest {}  peak {}",
+                aggregate::week_label(self.snapshot.week_start, first),
+                aggregate::week_label(self.snapshot.week_start, last),
+                format::number(last_downloads),
+                format::number(max),
+            ))
+            .dark_gray(),
+            caption,
+        );
+    }
+
+    fn draw_platforms(&mut self, frame: &mut Frame, area: Rect) {
+        let Some((week, platforms)) = &self.snapshot.platforms else {
+            let block = self.block(Panel::Platforms, "Top platforms".to_string());
+            frame.render_widget(
+                Paragraph::new("No GitHub platform data yet.")
+                    .dark_gray()
+                    .block(block),
+                area,
+            );
+            return;
+        };
+        let total: u64 = platforms.iter().map(|(_, downloads)| downloads).sum();
+        let rows = platforms.iter().map(|(platform, downloads)| {
+            let share = *downloads as f64 / total.max(1) as f64 * 100.0;
+            Row::new(vec![
+                Line::from(platform.as_str()),
+                Line::from(format::number(*downloads)).right_aligned(),
+                Line::from(format!("{:.1}%", share)).right_aligned(),
+            ])
+        });
+        let title = format!(
+            "Top platforms, week of {}",
+            aggregate::week_label(self.snapshot.week_start, *week)
+        );
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(14),
+                Constraint::Length(7),
+            ],
+        )
+        .header(Row::new(["Platform", "Downloads", "Share"]).bold())
+        .row_highlight_style(Style::new().reversed())
+        .block(self.block(Panel::Platforms, title));
+        frame.render_stateful_widget(table, area, &mut self.platforms);
+    }
+
+    fn draw_freshness(&mut self, frame: &mut Frame, area: Rect) {
+        let rows = self.snapshot.freshness.iter().map(|freshness| {
+            let age = freshness
+                .latest
+                .map(|latest| (self.today - latest).num_days());
+            let color = match (age, freshness.failures) {
+                (_, 1..) | (None, _) => Color::Red,
+                (Some(age), _) if age > STALE_AFTER_DAYS => Color::Yellow,
+                _ => Color::Green,
+            };
+            let age = match age {
+                Some(0) => "today".to_string(),
+                Some(1) => "1 day ago".to_string(),
+                Some(days) => format!("{} days ago", days),
+                None => "never".to_string(),
+            };
+            let failures = match freshness.failures {
+                0 => String::new(),
+                n => format!("{} failed", n),
+            };
+            Row::new(vec![
+                Line::from(freshness.label.as_str()),
+                Line::from(
+                    freshness
+                        .latest
+                        .map_or_else(|| "-".to_string(), |date| date.to_string()),
+                ),
+                Line::from(age).fg(color),
+                Line::from(failures).red(),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(10),
+                Constraint::Length(12),
+                Constraint::Length(9),
+            ],
+        )
+        .header(Row::new(["Source", "Latest", "Age", ""]).bold())
+        .row_highlight_style(Style::new().reversed())
+        .block(self.block(Panel::Freshness, "Collection freshness".to_string()));
+        frame.render_stateful_widget(table, area, &mut self.freshness);
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal, conn: &Connection) -> Result<()> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .context("failed to draw dashboard")?;
+            let Event::Key(key) = event::read().context("failed to read terminal event")? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match self.handle_key(key) {
+                Action::Continue => {}
+                Action::Reload => {
+                    self.snapshot = Snapshot::load(conn)?;
+                    self.today = Utc::now().date_naive();
+                }
+                Action::Quit => return Ok(()),
+            }
+        }
+    }
+}
+
+/// Show the dashboard until the user quits.
+pub fn run(conn: &Connection) -> Result<()> {
+    let snapshot = Snapshot::load(conn)?;
+    let mut app = App::new(snapshot, Utc::now().date_naive());
+    let mut terminal = ratatui::try_init().context("failed to initialize terminal")?;
+    let result = app.run(&mut terminal, conn);
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ratatui::{Terminal, backend::TestBackend, crossterm::event::KeyModifiers};
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_dashboard() {
+        let conn = db::init_db(camino::Utf8Path::new(":memory:")).unwrap();
+        // Two complete weeks of crates.io data, through Sunday May 17.
+        for day in 4..=17 {
+            let date = NaiveDate::from_ymd_opt(2026, 5, day).unwrap();
+            db::insert_crates_download(&conn, date, "cargo-nextest", Some("0.9.100"), 100).unwrap();
+        }
+        aggregate::compute_all_weekly(&conn, &Default::default()).unwrap();
+
+        let snapshot = Snapshot::load(&conn).unwrap();
+        assert_eq!(snapshot.latest.len(), 1);
+        assert_eq!(snapshot.latest[0].downloads, 700);
+        assert_eq!(snapshot.trends[2].len(), 2);
+        assert_eq!(snapshot.trends[0], snapshot.trends[2]);
+        assert_eq!(snapshot.platforms, None);
+        assert_eq!(
+            snapshot.freshness[0],
+            Freshness {
+                label: "crates.io".to_string(),
+                latest: Some(date("2026-05-17")),
+                failures: 0,
+            }
+        );
+
+        let mut app = App::new(snapshot, date("2026-05-18"));
+        let press = |code| KeyEvent::new(code, KeyModifiers::NONE);
+        // Arrow keys only switch the trend source while it has focus.
+        app.handle_key(press(KeyCode::Right));
+        assert_eq!(app.trend_source, 0);
+        app.handle_key(press(KeyCode::Tab));
+        app.handle_key(press(KeyCode::Left));
+        assert_eq!(
+            (app.focus, TREND_SOURCES[app.trend_source]),
+            (Panel::Trend, "installer")
+        );
+        app.handle_key(press(KeyCode::Char('4')));
+        app.handle_key(press(KeyCode::Down));
+        app.handle_key(press(KeyCode::Down));
+        assert_eq!(app.freshness.selected(), Some(0));
+        assert_eq!(app.handle_key(press(KeyCode::Char('q'))), Action::Quit);
+
+        let mut terminal = Terminal::new(TestBackend::new(120, 20)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|cell| cell.symbol())
+            .collect();
+        for text in [
+            "Latest complete week",
+            "2026-05-11",
+            "No GitHub platform data yet.",
+            "Collection freshness",
+            "1 day ago",
+        ] {
+            assert!(screen.contains(text), "dashboard should show '{}'", text);
+        }
+    }
+}
@@ -0,0 +1,266 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Best-effort historical GitHub download counts from the Wayback Machine.
+//!
+//! GitHub only reports cumulative download counts as of now, so history from
+//! before the collector existed is lost. The Internet Archive has
+//! occasionally captured responses from the GitHub releases API, and each
+//! capture records the counts as of the capture date.
+//!
+//! Captures are sparse, may cover only some pages of a paginated listing, and
+//! may have been served from a stale cache, so imported counts are recorded
+//! as low confidence (see the `github_backfill` table).
+
+use crate::{github::Release, http};
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+
+const CDX_API: &str = "https://web.archive.org/cdx/search/cdx";
+const USER_AGENT: &str = "nextest-download-stats-collector (contact: opensource@nexte.st)";
+
+/// An archived capture of a GitHub API response.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Capture {
+    /// Capture timestamp, in the archive's `YYYYMMDDhhmmss` format.
+    pub timestamp: String,
+    /// The URL that was captured.
+    pub original: String,
+}
+
+impl Capture {
+    /// The day the capture was taken.
+    pub fn date(&self) -> Result<NaiveDate> {
+        let day = self.timestamp.get(..8).unwrap_or(&self.timestamp);
+        NaiveDate::parse_from_str(day, "%Y%m%d")
+            .with_context(|| format!("invalid capture timestamp '{}'", self.timestamp))
+    }
+
+    /// The URL of the capture's raw, unmodified response body.
+    pub fn raw_url(&self) -> String {
+        // The `id_` suffix asks for the body as captured, without the
+        // archive's banner or link rewriting.
+        format!(
+            "https://web.archive.org/web/{}id_/{}",
+            self.timestamp, self.original
+        )
+    }
+}
+
+/// Download counts from a single archived capture.
+#[derive(Debug)]
+pub struct CapturedReleases {
+    pub capture: Capture,
+    pub releases: Vec<Release>,
+}
+
+/// List successful captures of the releases API for `owner/repo`, at most one
+/// per URL per day.
+pub async fn list_captures(owner: &str, repo: &str) -> Result<Vec<Capture>> {
+    let client = http::client();
+    let url = format!("api.github.com/repos/{}/{}/releases", owner, repo);
+    let response = client
+        .get(CDX_API)
+        .query(&[
+            ("url", url.as_str()),
+            ("matchType", "prefix"),
+            ("output", "json"),
+            ("fl", "timestamp,original"),
+            ("filter", "statuscode:200"),
+            ("collapse", "timestamp:8"),
+        ])
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await
+        .with_context(|| format!("failed to list archived captures of {}", url))?;
+
+    if !response.status().is_success() {
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        anyhow::bail!(
+            "Wayback Machine CDX request for {} failed with status {}: {}",
+            url,
+            status,
+            body
+        );
+    }
+
+    let body = response
+        .text()
+        .await
+        .context("failed to read Wayback Machine CDX response")?;
+    parse_captures(&body)
+}
+
+/// Fetch an archived capture and parse the releases in it.
+pub async fn fetch_capture(capture: &Capture) -> Result<CapturedReleases> {
+    let client = http::client();
+    let url = capture.raw_url();
+    let response = client
+        .get(&url)
+        .header("User-Agent", USER_AGENT)
+        .send()
+        .await
+        .with_context(|| format!("failed to fetch archived capture {}", url))?;
+
+    if !response.status().is_success() {
+        anyhow::bail!(
+            "archived capture {} failed with status {}",
+            url,
+            response.status()
+        );
+    }
+
+    let body = response
+        .text()
+        .await
+        .with_context(|| format!("failed to read archived capture {}", url))?;
+    let releases = parse_releases(&body)
+        .with_context(|| format!("failed to parse archived capture {}", url))?;
+    Ok(CapturedReleases {
+        capture: capture.clone(),
+        releases,
+    })
+}
+
+/// Parse the CDX API's JSON output: a header row followed by one row per
+/// capture.
+fn parse_captures(body: &str) -> Result<Vec<Capture>> {
+    // An empty result is an empty body rather than an empty array.
+    if body.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let rows: Vec<Vec<String>> =
+        serde_json::from_str(body).context("failed to parse Wayback Machine CDX response")?;
+    rows.into_iter()
+        .skip(1)
+        .map(|row| match <[String; 2]>::try_from(row) {
+            Ok([timestamp, original]) => Ok(Capture {
+                timestamp,
+                original,
+            }),
+            Err(row) => anyhow::bail!("unexpected CDX row: {:?}", row),
+        })
+        .collect()
+}
+
+/// Parse a captured response, either a release listing or a single release
+/// (`/releases/tags/<tag>`, `/releases/latest`).
+fn parse_releases(body: &str) -> Result<Vec<Release>> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Response {
+        List(Vec<Release>),
+        Single(Release),
+    }
+
+    match serde_json::from_str(body)? {
+        Response::List(releases) => Ok(releases),
+        Response::Single(release) => Ok(vec![release]),
+    }
+}
+
+/// An asset's download count as read from an archived capture.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ArchivedCount {
+    pub download_count: u64,
+    /// The raw URL of the capture the count was read from.
+    pub url: String,
+}
+
+/// Archived counts for a single day, keyed by `(release_tag, asset_name)`.
+pub type DayCounts = BTreeMap<(String, String), ArchivedCount>;
+
+/// Merge captures into per-day counts.
+///
+/// A day may have several captures, such as different pages of the listing.
+/// If they disagree about an asset, the highest count wins since counts only
+/// grow.
+pub fn merge_by_day(captures: &[CapturedReleases]) -> Result<BTreeMap<NaiveDate, DayCounts>> {
+    let mut days: BTreeMap<NaiveDate, DayCounts> = BTreeMap::new();
+    for captured in captures {
+        let date = captured.capture.date()?;
+        let counts = days.entry(date).or_default();
+        for release in &captured.releases {
+            for asset in &release.assets {
+                let key = (release.tag_name.clone(), asset.name.clone());
+                let count = ArchivedCount {
+                    download_count: asset.download_count,
+                    url: captured.capture.raw_url(),
+                };
+                match counts.get(&key) {
+                    Some(existing) if existing.download_count >= count.download_count => {}
+                    _ => {
+                        counts.insert(key, count);
+                    }
+                }
+            }
+        }
+    }
+    Ok(days)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_captures() {
+        let body = r#"[["timestamp","original"],
+            ["20211003120000","https://api.github.com/repos/nextest-rs/nextest/releases"],
+            ["20220114080102","https://api.github.com/repos/nextest-rs/nextest/releases?page=2"]]"#;
+        let captures = parse_captures(body).unwrap();
+        assert_eq!(captures.len(), 2);
+        assert_eq!(
+            captures[0].date().unwrap(),
+            NaiveDate::from_ymd_opt(2021, 10, 3).unwrap()
+        );
+        assert_eq!(
+            captures[1].raw_url(),
+            "https://web.archive.org/web/20220114080102id_/https://api.github.com/repos/nextest-rs/nextest/releases?page=2"
+        );
+
+        assert!(parse_captures("").unwrap().is_empty());
+        assert!(parse_captures(r#"[["timestamp","original"],["20211003"]]"#).is_err());
+    }
+
+    #[test]
+    fn test_merge_by_day() {
+        let capture = |timestamp: &str, body: &str| CapturedReleases {
+            capture: Capture {
+                timestamp: timestamp.to_owned(),
+                original: "https://api.github.com/repos/o/r/releases".to_owned(),
+            },
+            releases: parse_releases(body).unwrap(),
+        };
+        let captures = [
+            capture(
+                "20220101000000",
+                r#"[{"tag_name": "v0.9.0", "assets": [{"name": "a.tar.gz", "download_count": 10}]}]"#,
+            ),
+            // A single release from later the same day, with a higher count.
+            capture(
+                "20220101230000",
+                r#"{"tag_name": "v0.9.0", "assets": [{"name": "a.tar.gz", "download_count": 12}]}"#,
+            ),
+            capture(
+                "20220105000000",
+                r#"[{"tag_name": "v0.9.0", "assets": [{"name": "a.tar.gz", "download_count": 20}]}]"#,
+            ),
+        ];
+        let days = merge_by_day(&captures).unwrap();
+        let counts: Vec<_> = days
+            .iter()
+            .map(|(date, assets)| {
+                let count = assets.values().next().unwrap();
+                (date.to_string(), count.download_count)
+            })
+            .collect();
+        assert_eq!(
+            counts,
+            [("2022-01-01".to_owned(), 12), ("2022-01-05".to_owned(), 20)]
+        );
+    }
+}
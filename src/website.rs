@@ -0,0 +1,304 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Data files for the nextest website.
+//!
+//! `export website` writes a few compact JSON files for the website to render
+//! stats from. Unlike public snapshots, which mirror database tables, these
+//! files have a schema of their own, kept stable as the database changes.
+//! Within a schema version fields are only ever added; removing or changing
+//! one bumps [`SCHEMA_VERSION`], which every file records.
+//!
+//! - `index.json`: when the files were generated, the latest date with data,
+//!   and the other files.
+//! - `weekly.json`: downloads from each source in each complete week.
+//! - `totals.json`: all-time downloads, and those of the latest complete
+//!   week against the week before.
+//! - `platforms.json`: each platform's share of GitHub release downloads
+//!   over the last four complete weeks.
+
+use crate::{
+    analysis,
+    config::HeadlineConfig,
+    headline,
+    output::{self, status},
+    platform::PlatformGroup,
+    provenance::Provenance,
+    query::DateRange,
+};
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use chrono::{Days, NaiveDate, Utc};
+use rusqlite::Connection;
+use serde::Serialize;
+use std::{collections::BTreeMap, fs};
+
+/// The version of the schema of the website files.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// The number of complete weeks platform shares are computed over.
+const PLATFORM_WEEKS: u64 = 4;
+
+/// `index.json`: what the files cover.
+#[derive(Debug, Serialize)]
+pub struct WebsiteIndex {
+    pub schema_version: u32,
+    /// When the files were generated, in RFC 3339 format.
+    pub generated_at: String,
+    /// The latest date with data from any source.
+    pub latest_date: Option<NaiveDate>,
+    /// The latest date with data from each source that has any.
+    pub latest_dates: BTreeMap<String, NaiveDate>,
+    /// Changes whenever the data or the tool producing it does, for use as a
+    /// cache-busting key.
+    pub fingerprint: String,
+    /// The other files, relative to this one.
+    pub files: Vec<&'static str>,
+}
+
+/// `weekly.json`: downloads in each complete week, as rows of `columns`.
+#[derive(Debug, Serialize)]
+pub struct WeeklySeries {
+    pub schema_version: u32,
+    /// Always `week_start`, `github`, `crates`, `installer`, and `total`.
+    pub columns: [&'static str; 5],
+    /// The Monday starting each week, then its downloads from each source
+    /// and in total, oldest first.
+    pub rows: Vec<(NaiveDate, u64, u64, u64, u64)>,
+}
+
+/// `totals.json`: all-time downloads and the latest complete week.
+#[derive(Debug, Serialize)]
+pub struct Totals {
+    pub schema_version: u32,
+    /// The headline figure, rounded down as configured.
+    pub headline: u64,
+    /// All-time downloads from each source counted in the headline figure.
+    pub all_time: BTreeMap<&'static str, u64>,
+    pub latest_week: Option<WeekTotals>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WeekTotals {
+    pub week_start: NaiveDate,
+    pub downloads: u64,
+    /// Downloads in the week before, if there's data for it.
+    pub previous: Option<u64>,
+    /// The change from the week before, in percent.
+    pub change_percent: Option<f64>,
+}
+
+/// `platforms.json`: shares of GitHub release downloads.
+#[derive(Debug, Serialize)]
+pub struct PlatformShares {
+    pub schema_version: u32,
+    /// The first and last days counted, or `null` without complete weeks of
+    /// GitHub data.
+    pub since: Option<NaiveDate>,
+    pub until: Option<NaiveDate>,
+    /// By operating system, such as `linux`, most downloaded first.
+    pub os: Vec<PlatformShare>,
+    /// By target triple, such as `x86_64-unknown-linux-gnu`.
+    pub target: Vec<PlatformShare>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PlatformShare {
+    /// The platform, or `other` for assets without a recognized one.
+    pub name: String,
+    pub downloads: u64,
+    /// The fraction of all downloads in the period, from 0 to 1.
+    pub share: f64,
+}
+
+/// Write the website files to `output_dir`.
+pub fn export_website(
+    conn: &Connection,
+    headline: &HeadlineConfig,
+    provenance: &Provenance,
+    output_dir: &Utf8Path,
+) -> Result<WebsiteIndex> {
+    fs::create_dir_all(output_dir.as_std_path())
+        .with_context(|| format!("failed to create output directory {}", output_dir))?;
+    status!("Exporting website data to {}", output_dir);
+
+    let files = vec![
+        write_json(output_dir, "weekly.json", &weekly_series(conn)?)?,
+        write_json(output_dir, "totals.json", &totals(conn, headline)?)?,
+        write_json(output_dir, "platforms.json", &platform_shares(conn)?)?,
+    ];
+
+    let index = WebsiteIndex {
+        schema_version: SCHEMA_VERSION,
+        generated_at: Utc::now().to_rfc3339(),
+        latest_date: provenance.data_watermark.values().max().copied(),
+        latest_dates: provenance.data_watermark.clone(),
+        fingerprint: provenance.fingerprint.clone(),
+        files,
+    };
+    write_json(output_dir, "index.json", &index)?;
+    Ok(index)
+}
+
+/// Write `value` as compact JSON to `file` in `output_dir`, returning the
+/// file name.
+fn write_json(
+    output_dir: &Utf8Path,
+    file: &'static str,
+    value: &impl Serialize,
+) -> Result<&'static str> {
+    let path = output_dir.join(file);
+    let json = serde_json::to_string(value)? + "\n";
+    fs::write(path.as_std_path(), json).with_context(|| format!("failed to write {}", path))?;
+    output::written(&path, format!("  • {}", file));
+    Ok(file)
+}
+
+fn weekly_series(conn: &Connection) -> Result<WeeklySeries> {
+    let mut by_source = Vec::new();
+    for source in analysis::SOURCES {
+        let weekly: BTreeMap<NaiveDate, u64> =
+            analysis::weekly_trend(conn, source)?.into_iter().collect();
+        by_source.push(weekly);
+    }
+    let downloads = |idx: usize, week: &NaiveDate| by_source[idx].get(week).copied().unwrap_or(0);
+    let rows = analysis::weekly_trend(conn, "all")?
+        .into_iter()
+        .map(|(week, total)| {
+            (
+                week,
+                downloads(0, &week),
+                downloads(1, &week),
+                downloads(2, &week),
+                total,
+            )
+        })
+        .collect();
+    Ok(WeeklySeries {
+        schema_version: SCHEMA_VERSION,
+        columns: ["week_start", "github", "crates", "installer", "total"],
+        rows,
+    })
+}
+
+fn totals(conn: &Connection, config: &HeadlineConfig) -> Result<Totals> {
+    let headline = headline::compute(conn, config)?;
+    let trend = analysis::weekly_trend(conn, "all")?;
+    let latest_week = trend.last().map(|&(week_start, downloads)| {
+        let previous = trend
+            .iter()
+            .rev()
+            .nth(1)
+            .filter(|(week, _)| *week == week_start - Days::new(7))
+            .map(|(_, downloads)| *downloads);
+        WeekTotals {
+            week_start,
+            downloads,
+            previous,
+            change_percent: analysis::percent_change(downloads, previous).map(round),
+        }
+    });
+    Ok(Totals {
+        schema_version: SCHEMA_VERSION,
+        headline: headline.total,
+        all_time: headline
+            .sources
+            .iter()
+            .map(|source| (source.kind, source.downloads))
+            .collect(),
+        latest_week,
+    })
+}
+
+fn platform_shares(conn: &Connection) -> Result<PlatformShares> {
+    let Some(week) = analysis::latest_complete_week(conn, "github")? else {
+        return Ok(PlatformShares {
+            schema_version: SCHEMA_VERSION,
+            since: None,
+            until: None,
+            os: Vec::new(),
+            target: Vec::new(),
+        });
+    };
+    let range = DateRange::new(
+        Some(week - Days::new(7 * (PLATFORM_WEEKS - 1))),
+        Some(week + Days::new(6)),
+    )?;
+    let shares = |by| -> Result<Vec<PlatformShare>> {
+        let platforms = analysis::platform_downloads(conn, by, range)?;
+        let total: u64 = platforms.iter().map(|platform| platform.downloads).sum();
+        Ok(platforms
+            .into_iter()
+            .map(|platform| PlatformShare {
+                share: round(platform.downloads as f64 / total.max(1) as f64),
+                name: platform.group,
+                downloads: platform.downloads,
+            })
+            .collect())
+    };
+    Ok(PlatformShares {
+        schema_version: SCHEMA_VERSION,
+        since: range.since,
+        until: range.until,
+        os: shares(PlatformGroup::Os)?,
+        target: shares(PlatformGroup::Target)?,
+    })
+}
+
+/// Round to four decimal places, which is plenty for display and keeps the
+/// files small.
+fn round(value: f64) -> f64 {
+    (value * 10_000.0).round() / 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::Config, db};
+    use camino::Utf8PathBuf;
+
+    #[test]
+    fn test_export_website() {
+        let conn = db::init_db(Utf8Path::new(":memory:")).unwrap();
+        let date = |day| NaiveDate::from_ymd_opt(2025, 11, day).unwrap();
+        // Weeks start on Mondays: Nov 3 and 10. Nov 17 isn't complete.
+        db::insert_crates_download(&conn, date(17), "cargo-nextest", None, 10).unwrap();
+        db::insert_weekly_stat(&conn, date(3), "crates", "cargo-nextest", 100).unwrap();
+        db::insert_weekly_stat(&conn, date(10), "crates", "cargo-nextest", 120).unwrap();
+        db::insert_weekly_stat(&conn, date(17), "crates", "cargo-nextest", 10).unwrap();
+
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .unwrap()
+            .join(format!("download-stats-website-{}", std::process::id()));
+        let config = Config::default();
+        let provenance = Provenance::collect(&conn, &config).unwrap();
+        let index = export_website(&conn, &config.headline, &provenance, &dir).unwrap();
+        assert_eq!(index.latest_date, Some(date(17)));
+        assert_eq!(
+            index.files,
+            ["weekly.json", "totals.json", "platforms.json"]
+        );
+
+        let read = |file| fs::read_to_string(dir.join(file)).unwrap();
+        assert_eq!(
+            read("weekly.json"),
+            "{\"schema_version\":1,\
+             \"columns\":[\"week_start\",\"github\",\"crates\",\"installer\",\"total\"],\
+             \"rows\":[[\"2025-11-03\",0,100,0,100],[\"2025-11-10\",0,120,0,120]]}\n"
+        );
+        let totals: serde_json::Value = serde_json::from_str(&read("totals.json")).unwrap();
+        assert_eq!(
+            totals["latest_week"],
+            serde_json::json!({
+                "week_start": "2025-11-10",
+                "downloads": 120,
+                "previous": 100,
+                "change_percent": 20.0,
+            })
+        );
+        let platforms: serde_json::Value = serde_json::from_str(&read("platforms.json")).unwrap();
+        assert_eq!(platforms["since"], serde_json::Value::Null);
+        assert!(dir.join("index.json").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,77 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Collection from the fixtures in `tests/fixtures/offline`, as with
+//! `collect --offline`.
+//!
+//! Fixtures are installed for the whole process, so these tests live apart
+//! from the unit tests, some of which reach the network.
+
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::NaiveDate;
+use download_stats_collector::{
+    commands::{self, CollectOptions},
+    config::Config,
+    fixtures::Fixtures,
+    http,
+};
+
+#[tokio::test]
+async fn test_offline_collect() {
+    let fixtures = Utf8Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/offline");
+    http::set_transport(Box::new(Fixtures::new(&fixtures).unwrap()));
+    let config = Config::load(&fixtures.join("config.toml")).unwrap();
+    let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+        .unwrap()
+        .join(format!("download-stats-offline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let database = dir.join("stats.db");
+
+    let as_of = NaiveDate::from_ymd_opt(2025, 11, 18).unwrap();
+    let options = CollectOptions {
+        as_of: Some(as_of),
+        ..CollectOptions::default()
+    };
+    let summary = commands::run_collect(&database, &config, &options)
+        .await
+        .unwrap();
+    assert!(summary.failures.is_empty(), "{:?}", summary.failures);
+    assert_eq!(summary.date, as_of);
+    let sources: Vec<_> = summary
+        .sources
+        .iter()
+        .map(|source| (source.kind, source.name.as_str(), source.downloads))
+        .collect();
+    assert_eq!(
+        sources.len(),
+        3,
+        "all sources were collected: {:?}",
+        sources
+    );
+    assert!(sources.contains(&("github", "nextest-rs/nextest", 6500)));
+    assert!(sources.contains(&("crates", "cargo-nextest", 250_000)));
+    assert!(sources.contains(&("installer", "get.nexte.st", 200)));
+
+    let conn = rusqlite::Connection::open(&database).unwrap();
+    let query = |sql: &str| -> i64 { conn.query_row(sql, [], |row| row.get(0)).unwrap() };
+    assert_eq!(
+        query("SELECT COUNT(*) FROM github_snapshots WHERE date = '2025-11-18'"),
+        3
+    );
+    assert_eq!(
+        query("SELECT SUM(downloads) FROM crates_downloads WHERE date = '2025-11-17'"),
+        705
+    );
+    // Headers come from the fixtures too.
+    assert_eq!(
+        conn.query_row(
+            "SELECT etag FROM collector_state WHERE kind = 'github'",
+            [],
+            |row| row.get::<_, String>(0)
+        )
+        .unwrap(),
+        "\"releases-page-1\""
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}